@@ -0,0 +1,89 @@
+#[path = "../tests/common/generator.rs"]
+mod generator;
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use docker_tags::{Tag, TagCollector, sort_tags_desc};
+use regex::Regex;
+use semver::Version;
+
+const SIZES: &[usize] = &[1_000, 10_000, 50_000];
+
+fn tags(count: usize) -> Vec<Tag> {
+    generator::tag_names(count, 42)
+        .into_iter()
+        .map(Tag::new)
+        .collect()
+}
+
+/// Key equivalent to `Tag::cmp`, computed once per tag
+fn sort_key(tag: &Tag) -> (Option<Version>, String) {
+    let version = Version::parse(tag.name().trim_start_matches('v')).ok();
+    (version, tag.name().to_string())
+}
+
+fn bench_sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort");
+    for &size in SIZES {
+        let input = tags(size);
+        group.bench_with_input(BenchmarkId::new("ord", size), &input, |b, input| {
+            b.iter_batched(
+                || input.clone(),
+                |mut tags| tags.sort(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("desc", size), &input, |b, input| {
+            b.iter_batched(
+                || input.clone(),
+                |mut tags| sort_tags_desc(&mut tags),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("cached_key", size), &input, |b, input| {
+            b.iter_batched(
+                || input.clone(),
+                |mut tags| tags.sort_by_cached_key(sort_key),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter");
+    let re = Regex::new(r"^1\.2[0-9]\.").unwrap();
+    for &size in SIZES {
+        let input = tags(size);
+        group.bench_with_input(BenchmarkId::new("regex", size), &input, |b, input| {
+            b.iter(|| input.iter().filter(|t| re.is_match(t.name())).count())
+        });
+    }
+    group.finish();
+}
+
+fn bench_paginate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("paginate");
+    for &size in SIZES {
+        let pages = generator::pages(&generator::tag_names(size, 42), 100);
+        group.bench_with_input(BenchmarkId::new("assemble", size), &pages, |b, pages| {
+            b.iter_batched(
+                || pages.clone(),
+                |pages| {
+                    let mut collector = TagCollector::new();
+                    for page in pages {
+                        collector.push_page(page);
+                    }
+                    black_box(collector.finish())
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sort, bench_filter, bench_paginate);
+criterion_main!(benches);
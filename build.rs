@@ -0,0 +1,31 @@
+use std::{env, path::Path, process::Command};
+
+/// Embed the commit the crate is built from, when it's built from a git
+/// checkout with git at hand, and the target triple
+fn main() {
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let git = Path::new(&dir).join(".git");
+    for path in ["HEAD", "refs", "packed-refs"] {
+        let path = git.join(path);
+        // a missing path would rerun the script on every build
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .current_dir(&dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=DOCKER_TAGS_GIT_COMMIT={}", commit.trim());
+    }
+    println!(
+        "cargo:rustc-env=DOCKER_TAGS_TARGET={}",
+        env::var("TARGET").unwrap()
+    );
+}
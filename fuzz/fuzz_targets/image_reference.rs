@@ -0,0 +1,10 @@
+#![no_main]
+
+use docker_tags::Image;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(reference) = std::str::from_utf8(data) {
+        let _ = Image::try_from(reference);
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use docker_tags::unstable::auth::{parse_challenge, parse_www_authenticate};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(hdr) = std::str::from_utf8(data) {
+        let _ = parse_challenge(hdr);
+        let _ = parse_www_authenticate(hdr);
+    }
+});
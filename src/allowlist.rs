@@ -0,0 +1,181 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock},
+};
+
+use reqwest::{Url, redirect};
+
+use crate::Error;
+
+/// Most redirects followed for one request, as reqwest does by default
+const MAX_REDIRECTS: usize = 10;
+
+/// Registries a [`Client`](crate::Client) may contact and the other hosts
+/// its requests may go to, see [`ClientBuilder::allowed_registries`](crate::ClientBuilder::allowed_registries)
+/// and [`ClientBuilder::denied_registries`](crate::ClientBuilder::denied_registries)
+#[derive(Debug, Default)]
+pub(crate) struct HostAllowlist {
+    /// Allowed registries, and as addressed (`registry-1.docker.io` for
+    /// `docker.io`) with their token services and other explicitly allowed
+    /// hosts; any unless restricted
+    allowed: Option<(HashSet<String>, HashSet<String>)>,
+    /// Hosts never contacted, whether as registries or otherwise
+    denied: HashSet<String>,
+}
+
+impl HostAllowlist {
+    pub(crate) fn new(registries: HashSet<String>, hosts: HashSet<String>) -> Self {
+        HostAllowlist {
+            allowed: Some((registries, hosts)),
+            denied: HashSet::new(),
+        }
+    }
+
+    /// Never contact `hosts`, even if allowed
+    pub(crate) fn deny(mut self, hosts: HashSet<String>) -> Self {
+        self.denied.extend(hosts);
+        self
+    }
+
+    /// Whether a request on behalf of `registry` may go to `url`
+    pub(crate) fn check(&self, registry: &str, url: &Url) -> Result<(), Error> {
+        let allowed = match &self.allowed {
+            Some((registries, _)) => registries.contains(registry),
+            None => true,
+        };
+        if !allowed || self.denied.contains(registry) {
+            return Err(Error::RegistryNotAllowed {
+                host: registry.to_string(),
+            });
+        }
+        self.check_url(url)
+    }
+
+    fn check_url(&self, url: &Url) -> Result<(), Error> {
+        let host = authority(url);
+        let allowed = match &self.allowed {
+            Some((_, hosts)) => hosts.contains(&host),
+            None => true,
+        };
+        match allowed && !self.denied.contains(&host) {
+            true => Ok(()),
+            false => Err(Error::RegistryNotAllowed { host }),
+        }
+    }
+}
+
+/// `host[:port]` of `url`, as registries are named
+pub(crate) fn authority(url: &Url) -> String {
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (host, _) => host.unwrap_or_default().to_string(),
+    }
+}
+
+/// Redirect policy of every HTTP client built by a
+/// [`ClientBuilder`](crate::ClientBuilder), refusing hosts outside the
+/// allowlist once it's set at build time
+pub(crate) fn redirect_policy(allowlist: Arc<OnceLock<HostAllowlist>>) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        let denied = allowlist
+            .get()
+            .and_then(|allowlist| allowlist.check_url(attempt.url()).err());
+        match denied {
+            Some(err) => attempt.error(err),
+            None if attempt.previous().len() >= MAX_REDIRECTS => {
+                attempt.error("too many redirects")
+            }
+            None => attempt.follow(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let allowlist = HostAllowlist::new(
+            HashSet::from(["docker.io".to_string(), "localhost:5000".to_string()]),
+            HashSet::from([
+                "registry-1.docker.io".to_string(),
+                "auth.docker.io".to_string(),
+                "localhost:5000".to_string(),
+            ]),
+        );
+        let url = |url| Url::parse(url).unwrap();
+        let denied = |host: &str| {
+            Err(Error::RegistryNotAllowed {
+                host: host.to_string(),
+            })
+        };
+
+        assert_eq!(
+            allowlist.check("docker.io", &url("https://registry-1.docker.io/v2/")),
+            Ok(())
+        );
+        assert_eq!(
+            allowlist.check("docker.io", &url("https://auth.docker.io/token")),
+            Ok(())
+        );
+        assert_eq!(
+            allowlist.check("localhost:5000", &url("http://localhost:5000/v2/")),
+            Ok(())
+        );
+        // the port is part of the host
+        assert_eq!(
+            allowlist.check("localhost:5000", &url("http://localhost:5001/token")),
+            denied("localhost:5001")
+        );
+        assert_eq!(
+            allowlist.check("docker.io", &url("http://169.254.169.254/latest")),
+            denied("169.254.169.254")
+        );
+        assert_eq!(
+            allowlist.check("ghcr.io", &url("https://ghcr.io/v2/")),
+            denied("ghcr.io")
+        );
+
+        // denied hosts win over allowed ones
+        let allowlist = allowlist.deny(HashSet::from(["auth.docker.io".to_string()]));
+        assert_eq!(
+            allowlist.check("docker.io", &url("https://auth.docker.io/token")),
+            denied("auth.docker.io")
+        );
+        assert_eq!(
+            allowlist.check("docker.io", &url("https://registry-1.docker.io/v2/")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_check_denied() {
+        let denylist =
+            HostAllowlist::default().deny(HashSet::from(["registry.corp:5000".to_string()]));
+        let url = |url| Url::parse(url).unwrap();
+        let denied = |host: &str| {
+            Err(Error::RegistryNotAllowed {
+                host: host.to_string(),
+            })
+        };
+
+        assert_eq!(
+            denylist.check("ghcr.io", &url("https://ghcr.io/token")),
+            Ok(())
+        );
+        assert_eq!(
+            denylist.check("registry.corp:5000", &url("https://registry.corp:5000/v2/")),
+            denied("registry.corp:5000")
+        );
+        // as a token service of another registry
+        assert_eq!(
+            denylist.check("ghcr.io", &url("https://registry.corp:5000/token")),
+            denied("registry.corp:5000")
+        );
+        assert_eq!(
+            denylist.check("registry.corp", &url("https://registry.corp/v2/")),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::{Result, anyhow};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use reqwest::{StatusCode, Url};
+
+use crate::Secret;
+
+/// A token obtained for a bearer challenge, along with what was asked for
+#[derive(Clone, Debug)]
+pub struct TokenGrant {
+    /// Scheme of the challenge, normally `Bearer`
+    pub scheme: String,
+    pub realm: String,
+    pub service: Option<String>,
+    /// Scopes requested, the challenge's own first
+    pub scopes: Vec<String>,
+    /// Whether credentials were sent to the token service
+    pub authenticated: bool,
+    pub token: Secret,
+}
+
+/// How a registry authorizes pulls from a repository, see
+/// [`Client::exchange_token`](crate::Client::exchange_token)
+#[derive(Debug)]
+pub struct TokenExchange {
+    /// `None` if the registry didn't ask for a token
+    pub grant: Option<TokenGrant>,
+    /// Status of the manifest request made with the token
+    pub status: StatusCode,
+    /// Rate limit headers of that response, e.g. Docker Hub's `ratelimit-remaining`
+    pub rate_limits: Vec<(String, String)>,
+}
+
+/// Whether `name`, a lowercase header name, tells about rate limiting
+pub(crate) fn is_rate_limit_header(name: &str) -> bool {
+    name.starts_with("ratelimit-")
+        || name.starts_with("x-ratelimit-")
+        || name.starts_with("docker-ratelimit-")
+        || name == "retry-after"
+}
+
+/// Claims of `token` if it's a JWT, leaving out its header and signature
+pub fn jwt_claims(token: &str) -> Option<serde_json::Value> {
+    let mut parts = token.split('.');
+    let (Some(_), Some(payload), Some(_), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<serde_json::Value>(&payload)
+        .ok()
+        .filter(serde_json::Value::is_object)
+}
+
+/// Split a `WWW-Authenticate` header into its scheme and parameters
+///
+/// Quoted values are taken as they are between the quotes, commas and spaces
+/// included, e.g. Harbor's `scope="repository:project/app:pull,push"`.
+pub fn parse_challenge(hdr: &str) -> Result<(&str, HashMap<&str, &str>)> {
+    let (scheme, rest) = hdr
+        .split_once(' ')
+        .ok_or(anyhow!("Invalid authentication header: {hdr}"))?;
+    let mut params = HashMap::new();
+    for param in split_params(rest) {
+        if let Some((k, v)) = param.split_once('=') {
+            let v = v.trim();
+            let v = match v.strip_prefix('"') {
+                Some(quoted) => quoted.strip_suffix('"').unwrap_or(quoted),
+                None => v,
+            };
+            params.insert(k.trim(), v);
+        }
+    }
+
+    Ok((scheme, params))
+}
+
+/// One challenge of a `WWW-Authenticate` header, e.g.
+/// `Bearer realm="https://auth.docker.io/token",service="registry.docker.io"`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Challenge {
+    /// Scheme as sent, normally `Bearer` or `Basic`
+    pub scheme: String,
+    /// Token service to ask for a token, or the protection space for `Basic`
+    pub realm: Option<String>,
+    pub service: Option<String>,
+    /// Scopes named by the challenge, none if it didn't name any
+    pub scopes: Vec<Scope>,
+    /// Other parameters by lowercase name, e.g. `error="insufficient_scope"`
+    pub params: BTreeMap<String, String>,
+}
+
+impl Challenge {
+    pub fn is_bearer(&self) -> bool {
+        self.scheme.eq_ignore_ascii_case("bearer")
+    }
+}
+
+/// `Bearer realm="...",service="...",scope="..."`, with the scopes separated
+/// by spaces and every value quoted
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scopes = (!self.scopes.is_empty()).then(|| {
+            let scopes: Vec<_> = self.scopes.iter().map(Scope::to_string).collect();
+            scopes.join(" ")
+        });
+        let params = [("realm", &self.realm), ("service", &self.service)]
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value.clone()?)))
+            .chain(scopes.map(|scopes| ("scope", scopes)))
+            .chain(self.params.iter().map(|(k, v)| (k.as_str(), v.clone())));
+        write!(f, "{}", self.scheme)?;
+        for (i, (name, value)) in params.enumerate() {
+            let separator = if i == 0 { " " } else { "," };
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            write!(f, "{separator}{name}=\"{value}\"")?;
+        }
+        Ok(())
+    }
+}
+
+/// `realm` with the `service` and the `scopes` of a token request added to
+/// its query, after the realm's own parameters, which are kept as they are
+///
+/// Only what the realm doesn't already ask for is added: its own `service`
+/// wins over the challenge's, and a scope it names isn't repeated.
+pub(crate) fn token_url(realm: &Url, service: Option<&str>, scopes: &[String]) -> Url {
+    let mut url = realm.clone();
+    url.set_fragment(None);
+    let present: Vec<_> = url.query_pairs().into_owned().collect();
+    let has = |key: &str, value: Option<&str>| {
+        present
+            .iter()
+            .any(|(k, v)| k == key && value.is_none_or(|value| v == value))
+    };
+    let service = service.filter(|_| !has("service", None));
+    let scopes: Vec<_> = scopes
+        .iter()
+        .filter(|scope| !has("scope", Some(scope)))
+        .collect();
+    if service.is_some() || !scopes.is_empty() {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(service.map(|service| ("service", service)));
+        query.extend_pairs(scopes.into_iter().map(|scope| ("scope", scope)));
+    }
+    url
+}
+
+/// Access to a resource a token is asked for, as in the `scope` of a token
+/// request: `repository:library/alpine:pull` or `registry:catalog:*`
+///
+/// The name may hold colons (`registry.corp:5000/app`), so the type ends at
+/// the first colon and the actions start after the last one.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Scope {
+    /// `repository` or `registry`, with an optional class such as
+    /// `repository(plugin)`
+    pub resource_type: String,
+    pub name: String,
+    /// E.g. `pull` and `push`, or `*`
+    pub actions: Vec<String>,
+}
+
+impl Scope {
+    /// `repository:<name>:pull`
+    pub fn pull(repository: impl Into<String>) -> Self {
+        Scope {
+            resource_type: "repository".to_string(),
+            name: repository.into(),
+            actions: vec!["pull".to_string()],
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.resource_type,
+            self.name,
+            self.actions.join(",")
+        )
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid scope: {s:?}");
+        let (resource_type, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (name, actions) = rest.rsplit_once(':').ok_or_else(invalid)?;
+        let valid_type = match resource_type.split_once('(') {
+            Some((kind, class)) => {
+                is_scope_word(kind) && class.strip_suffix(')').is_some_and(is_scope_word)
+            }
+            None => is_scope_word(resource_type),
+        };
+        let actions: Vec<_> = actions.split(',').map(str::to_string).collect();
+        if !valid_type
+            || name.trim().is_empty()
+            || actions
+                .iter()
+                .any(|action| action != "*" && !is_scope_word(action))
+        {
+            return Err(invalid());
+        }
+        Ok(Scope {
+            resource_type: resource_type.to_string(),
+            name: name.to_string(),
+            actions,
+        })
+    }
+}
+
+/// Whether `word` is a resource type or an action: letters, maybe digits
+fn is_scope_word(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic())
+        && word.bytes().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Every challenge of a `WWW-Authenticate` header, such as
+/// `Basic realm="corp", Bearer realm="https://auth.corp/token"`, in order
+///
+/// Each scope-token of a challenge's `scope` is parsed, so a malformed one
+/// fails the whole header, as does a header without any challenge.
+pub fn parse_www_authenticate(hdr: &str) -> Result<Vec<Challenge>> {
+    let invalid = || anyhow!("Invalid authentication header: {hdr}");
+    let mut challenges: Vec<(&str, Vec<&str>)> = Vec::new();
+    for part in split_params(hdr) {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        // a new challenge starts with its scheme, which has no `=`
+        match part.split_once(char::is_whitespace) {
+            Some((scheme, param))
+                if !scheme.contains('=') && !param.trim_start().starts_with('=') =>
+            {
+                challenges.push((scheme, vec![param.trim()]));
+            }
+            None if !part.contains('=') => challenges.push((part, Vec::new())),
+            _ => challenges.last_mut().ok_or_else(invalid)?.1.push(part),
+        }
+    }
+    if challenges.is_empty() {
+        return Err(invalid());
+    }
+
+    challenges
+        .into_iter()
+        .map(|(scheme, params)| {
+            let mut challenge = Challenge {
+                scheme: scheme.to_string(),
+                realm: None,
+                service: None,
+                scopes: Vec::new(),
+                params: BTreeMap::new(),
+            };
+            for param in params {
+                let (name, value) = param.split_once('=').ok_or_else(invalid)?;
+                let (name, value) = (name.trim().to_ascii_lowercase(), unquote(value.trim()));
+                if name.is_empty() {
+                    return Err(invalid());
+                }
+                match name.as_str() {
+                    "realm" => challenge.realm = Some(value),
+                    "service" => challenge.service = Some(value),
+                    "scope" => {
+                        for scope in split_scopes(&value) {
+                            challenge.scopes.push(scope.parse()?);
+                        }
+                    }
+                    _ => {
+                        challenge.params.insert(name, value);
+                    }
+                }
+            }
+            Ok(challenge)
+        })
+        .collect()
+}
+
+/// Scope-tokens of a `scope` parameter, separated by spaces, though a part
+/// not starting with a resource type continues the previous name, as
+/// Harbor's `repository:team one/app:pull` does
+fn split_scopes(value: &str) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for part in value.split_whitespace() {
+        let starts_scope = part.matches(':').count() >= 2
+            && part.split(['(', ':']).next().is_some_and(is_scope_word);
+        match scopes.last_mut() {
+            Some(scope) if !starts_scope => {
+                scope.push(' ');
+                scope.push_str(part);
+            }
+            _ => scopes.push(part.to_string()),
+        }
+    }
+    scopes
+}
+
+/// `value` without its quotes and escapes, if it's a quoted string
+fn unquote(value: &str) -> String {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return value.to_string();
+    };
+    let quoted = quoted.strip_suffix('"').unwrap_or(quoted);
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// `key=value` parts of a challenge, split at the commas outside quotes
+fn split_params(params: &str) -> impl Iterator<Item = &str> {
+    let (mut quoted, mut escaped, mut start) = (false, false, 0);
+    let mut parts = Vec::new();
+    for (i, c) in params.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&params[start..]);
+    parts.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_challenge() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io""#,
+        )
+        .unwrap();
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://auth.docker.io/token");
+        assert_eq!(params["service"], "registry.docker.io");
+
+        assert!(parse_challenge("Bearer").is_err());
+    }
+
+    #[test]
+    fn test_parse_harbor_challenge() {
+        let (scheme, params) =
+            parse_challenge(include_str!("../tests/fixtures/harbor_challenge.txt").trim_end())
+                .unwrap();
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://harbor.corp/service/token");
+        assert_eq!(params["service"], "Harbor Registry");
+        assert_eq!(params["scope"], "repository:team one/app:pull,push");
+        assert_eq!(params.len(), 3);
+
+        // unquoted values, spacing and escaped quotes
+        let (_, params) =
+            parse_challenge(r#"Bearer realm=https://r.corp/token , service = "a \"b\", c""#)
+                .unwrap();
+        assert_eq!(params["realm"], "https://r.corp/token");
+        assert_eq!(params["service"], r#"a \"b\", c"#);
+    }
+
+    #[test]
+    fn test_scope() {
+        let scope: Scope = "repository:library/alpine:pull".parse().unwrap();
+        assert_eq!(scope, Scope::pull("library/alpine"));
+
+        let scope: Scope = "repository:registry.corp:5000/team/app:pull,push"
+            .parse()
+            .unwrap();
+        assert_eq!(scope.name, "registry.corp:5000/team/app");
+        assert_eq!(scope.actions, ["pull", "push"]);
+        assert_eq!(
+            scope.to_string(),
+            "repository:registry.corp:5000/team/app:pull,push"
+        );
+
+        let scope: Scope = "registry:catalog:*".parse().unwrap();
+        assert_eq!(
+            (scope.resource_type.as_str(), scope.actions),
+            ("registry", vec!["*".to_string()])
+        );
+        let scope: Scope = "repository(plugin):vieux/sshfs:pull".parse().unwrap();
+        assert_eq!(scope.resource_type, "repository(plugin)");
+
+        for scope in [
+            "",
+            "repository",
+            "repository:app",
+            ":app:pull",
+            "repository::pull",
+            "repository:app:",
+            "repository:app:pull,",
+            "repository(:app:pull",
+            "repo-sitory:app:pull",
+        ] {
+            assert!(scope.parse::<Scope>().is_err(), "{scope:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_www_authenticate() {
+        let challenges = parse_www_authenticate(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull""#,
+        )
+        .unwrap();
+        assert_eq!(
+            challenges,
+            [Challenge {
+                scheme: "Bearer".to_string(),
+                realm: Some("https://auth.docker.io/token".to_string()),
+                service: Some("registry.docker.io".to_string()),
+                scopes: vec![Scope::pull("library/alpine")],
+                params: BTreeMap::new(),
+            }]
+        );
+        assert!(challenges[0].is_bearer());
+
+        // Harbor's scope, with a comma and a space in quotes
+        let challenges = parse_www_authenticate(
+            include_str!("../tests/fixtures/harbor_challenge.txt").trim_end(),
+        )
+        .unwrap();
+        assert_eq!(challenges.len(), 1);
+        assert_eq!(challenges[0].service.as_deref(), Some("Harbor Registry"));
+        assert_eq!(challenges[0].scopes.len(), 1);
+        assert_eq!(challenges[0].scopes[0].name, "team one/app");
+        assert_eq!(challenges[0].scopes[0].actions, ["pull", "push"]);
+    }
+
+    #[test]
+    fn test_parse_multiple_scopes() {
+        let [challenge] = &parse_www_authenticate(
+            r#"Bearer realm="https://r.corp/token",scope="repository:a:pull repository:b:pull,push",error="insufficient_scope""#,
+        )
+        .unwrap()[..] else {
+            panic!("expected one challenge");
+        };
+        assert_eq!(
+            challenge.scopes,
+            [Scope::pull("a"), "repository:b:pull,push".parse().unwrap()]
+        );
+        assert_eq!(challenge.params["error"], "insufficient_scope");
+        assert_eq!(
+            challenge.to_string(),
+            r#"Bearer realm="https://r.corp/token",scope="repository:a:pull repository:b:pull,push",error="insufficient_scope""#
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_challenges() {
+        let challenges = parse_www_authenticate(
+            r#"Basic realm="corp, inc", Bearer realm=https://r.corp/token , SERVICE = "a \"b\", c", Negotiate"#,
+        )
+        .unwrap();
+        let schemes: Vec<_> = challenges.iter().map(|c| c.scheme.as_str()).collect();
+        assert_eq!(schemes, ["Basic", "Bearer", "Negotiate"]);
+        assert_eq!(challenges[0].realm.as_deref(), Some("corp, inc"));
+        assert!(!challenges[0].is_bearer());
+        assert_eq!(challenges[1].realm.as_deref(), Some("https://r.corp/token"));
+        assert_eq!(challenges[1].service.as_deref(), Some(r#"a "b", c"#));
+        assert_eq!(challenges[2].realm, None);
+
+        // formatting escapes what parsing unescaped
+        let again = parse_www_authenticate(&challenges[1].to_string()).unwrap();
+        assert_eq!(again, challenges[1..2]);
+    }
+
+    #[test]
+    fn test_parse_malformed_www_authenticate() {
+        for hdr in [
+            "",
+            " , ",
+            r#"realm="https://r.corp/token""#,
+            r#"Bearer realm="https://r.corp/token",scope="repository:app""#,
+            r#"Bearer realm="https://r.corp/token",=value"#,
+        ] {
+            assert!(parse_www_authenticate(hdr).is_err(), "{hdr:?}");
+        }
+        // an unterminated quote runs to the end
+        let challenges = parse_www_authenticate(r#"Bearer realm="https://r.corp/token"#).unwrap();
+        assert_eq!(challenges[0].realm.as_deref(), Some("https://r.corp/token"));
+    }
+
+    #[test]
+    fn test_token_url() {
+        let url = |realm: &str, service: Option<&str>, scopes: &[&str]| {
+            let scopes: Vec<_> = scopes.iter().map(|scope| scope.to_string()).collect();
+            token_url(&Url::parse(realm).unwrap(), service, &scopes).to_string()
+        };
+        assert_eq!(
+            url(
+                "https://quay.io/v2/auth",
+                Some("quay.io"),
+                &["repository:org/app:pull"]
+            ),
+            "https://quay.io/v2/auth?service=quay.io&scope=repository%3Aorg%2Fapp%3Apull"
+        );
+        // the realm's own parameters stay first and as they were
+        assert_eq!(
+            url(
+                "https://auth.corp/token?tenant=a%20b&account=ci#top",
+                Some("registry.corp"),
+                &["repository:app:pull"]
+            ),
+            "https://auth.corp/token?tenant=a%20b&account=ci&service=registry.corp\
+             &scope=repository%3Aapp%3Apull"
+        );
+        assert_eq!(
+            url(
+                "https://auth.corp/token?service=a",
+                Some("b"),
+                &["repository:app:pull"]
+            ),
+            "https://auth.corp/token?service=a&scope=repository%3Aapp%3Apull"
+        );
+        // each scope is a key of its own, those of the realm not repeated
+        assert_eq!(
+            url(
+                "https://auth.corp/token?scope=repository%3Abase%3Apull",
+                None,
+                &[
+                    "repository:app:pull",
+                    "repository:base:pull",
+                    "repository:team one/app:pull,push"
+                ]
+            ),
+            "https://auth.corp/token?scope=repository%3Abase%3Apull\
+             &scope=repository%3Aapp%3Apull&scope=repository%3Ateam+one%2Fapp%3Apull%2Cpush"
+        );
+        assert_eq!(
+            url("https://auth.corp/token?", Some("a&b=c"), &[]),
+            "https://auth.corp/token?service=a%26b%3Dc"
+        );
+        assert_eq!(
+            url("https://auth.corp/token", None, &[]),
+            "https://auth.corp/token"
+        );
+    }
+
+    #[test]
+    fn test_jwt_claims() {
+        let claims = r#"{"access":[{"type":"repository","name":"library/alpine","actions":["pull"]}],"exp":1760400300}"#;
+        let token = format!(
+            "{}.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let claims = jwt_claims(&token).unwrap();
+        assert_eq!(claims["exp"], 1760400300);
+        assert_eq!(claims["access"][0]["name"], "library/alpine");
+
+        // opaque tokens, e.g. GitLab's or Quay's
+        assert_eq!(jwt_claims("mock-token"), None);
+        assert_eq!(jwt_claims("a.b.c"), None);
+        assert_eq!(jwt_claims(&format!("{token}.extra")), None);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+use crate::{manifest::format_rfc3339, secret::Secret};
+
+/// An AWS access key signing requests to AWS APIs, such as ECR's
+#[derive(Clone, Debug)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: Secret,
+    /// Set for temporary credentials, such as those of an assumed role
+    pub session_token: Option<Secret>,
+}
+
+impl AwsCredentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        AwsCredentials {
+            access_key_id: access_key_id.into(),
+            secret_access_key: Secret::new(secret_access_key),
+            session_token: None,
+        }
+    }
+
+    /// The key of `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+    /// `AWS_SESSION_TOKEN`, or else the `AWS_PROFILE` (by default `default`)
+    /// of `~/.aws/credentials` or `AWS_SHARED_CREDENTIALS_FILE`
+    ///
+    /// Only keys written out are read; profiles getting them from SSO or a
+    /// command aren't.
+    pub fn from_env() -> Result<Self> {
+        Self::lookup(|name| env::var(name).ok(), env::home_dir())
+    }
+
+    fn lookup(var: impl Fn(&str) -> Option<String>, home: Option<PathBuf>) -> Result<Self> {
+        if let (Some(id), Some(secret)) = (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            return Ok(AwsCredentials {
+                session_token: var("AWS_SESSION_TOKEN").map(Secret::new),
+                ..AwsCredentials::new(id, secret)
+            });
+        }
+        let path = var("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| Some(home?.join(".aws").join("credentials")))
+            .ok_or_else(|| anyhow!("No AWS credentials: AWS_ACCESS_KEY_ID isn't set"))?;
+        let profile = var("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+        let text = fs::read_to_string(&path).with_context(|| {
+            format!("No AWS credentials: AWS_ACCESS_KEY_ID isn't set and {path:?} can't be read")
+        })?;
+        parse_profile(&text, &profile)
+            .with_context(|| format!("No AWS credentials for profile {profile} in {path:?}"))
+    }
+}
+
+/// The keys of `[profile]` in a shared credentials file
+fn parse_profile(text: &str, profile: &str) -> Result<AwsCredentials> {
+    let (mut id, mut secret, mut token) = (None, None, None);
+    let mut in_profile = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = name.trim() == profile;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_profile) else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "aws_access_key_id" => id = Some(value),
+            "aws_secret_access_key" => secret = Some(value),
+            "aws_session_token" => token = Some(Secret::new(value)),
+            _ => {}
+        }
+    }
+    match (id, secret) {
+        (Some(id), Some(secret)) => Ok(AwsCredentials {
+            session_token: token,
+            ..AwsCredentials::new(id, secret)
+        }),
+        _ => Err(anyhow!(
+            "aws_access_key_id and aws_secret_access_key aren't both set"
+        )),
+    }
+}
+
+/// Signature Version 4 headers of a POST of `body` to `url`, a `service`
+/// endpoint in `region`, at `now`: the `headers` given (lowercase names),
+/// `x-amz-date`, the session token if any, then `authorization`
+pub(crate) fn sign(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    now: SystemTime,
+) -> Vec<(String, String)> {
+    // `20150830T123600Z`, without the fraction of a second
+    let seconds = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let amz_date =
+        format_rfc3339(UNIX_EPOCH + Duration::from_secs(seconds)).replace(['-', ':'], "");
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    signed.push(("x-amz-date".to_string(), amz_date.clone()));
+    if let Some(token) = &credentials.session_token {
+        signed.push((
+            "x-amz-security-token".to_string(),
+            token.expose().to_string(),
+        ));
+    }
+    let mut canonical = signed.clone();
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    canonical.push(("host".to_string(), host));
+    canonical.sort();
+    let authorization = authorization(
+        credentials,
+        region,
+        service,
+        url,
+        &canonical,
+        body,
+        &amz_date,
+    );
+    signed.push(("authorization".to_string(), authorization));
+    signed
+}
+
+/// `Authorization` header of a POST of `body` to `url` (without a query)
+/// with the `headers` given, sorted and including `host`
+fn authorization(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    url: &Url,
+    headers: &[(String, String)],
+    body: &[u8],
+    amz_date: &str,
+) -> String {
+    let names = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut request = format!("POST\n{}\n\n", url.path());
+    for (name, value) in headers {
+        request.push_str(&format!("{name}:{}\n", value.trim()));
+    }
+    request.push_str(&format!("\n{names}\n{}", hex(&Sha256::digest(body))));
+
+    let date = &amz_date[..8];
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(request))
+    );
+    let secret = format!("AWS4{}", credentials.secret_access_key.expose());
+    let key = [region, service, "aws4_request"]
+        .into_iter()
+        .fold(hmac(secret.as_bytes(), date.as_bytes()), |key, part| {
+            hmac(&key, part.as_bytes())
+        });
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={names}, Signature={}",
+        credentials.access_key_id,
+        hex(&hmac(&key, to_sign.as_bytes()))
+    )
+}
+
+/// HMAC-SHA256 of `data` with `key`, as RFC 2104 has it
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0; 64];
+    match key.len() > block.len() {
+        true => block[..32].copy_from_slice(&Sha256::digest(key)),
+        false => block[..key.len()].copy_from_slice(key),
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(data)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231, test case 2
+        let mac = hmac(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            hex(&mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_sign() {
+        // `post-vanilla` of AWS's Signature Version 4 test suite
+        let credentials =
+            AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+        let headers = sign(&credentials, "us-east-1", "service", &url, &[], b"", now);
+        assert_eq!(
+            headers,
+            [
+                ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+                (
+                    "authorization".to_string(),
+                    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+                     SignedHeaders=host;x-amz-date, \
+                     Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+                        .to_string()
+                ),
+            ]
+        );
+
+        let credentials = AwsCredentials {
+            session_token: Some(Secret::new("token")),
+            ..credentials
+        };
+        let url = Url::parse("http://127.0.0.1:5000/").unwrap();
+        let headers = sign(&credentials, "us-east-1", "ecr", &url, &[], b"{}", now);
+        let authorization = &headers.last().unwrap().1;
+        assert_eq!(
+            headers[1],
+            ("x-amz-security-token".to_string(), "token".to_string())
+        );
+        assert!(
+            authorization.contains(" SignedHeaders=host;x-amz-date;x-amz-security-token, "),
+            "{authorization}"
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let vars = HashMap::from([
+            ("AWS_ACCESS_KEY_ID", "AKID"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+            ("AWS_SESSION_TOKEN", "token"),
+        ]);
+        let var = |name: &str| vars.get(name).map(|value| value.to_string());
+        let credentials = AwsCredentials::lookup(var, None).unwrap();
+        assert_eq!(credentials.access_key_id, "AKID");
+        assert_eq!(credentials.secret_access_key.expose(), "secret");
+        assert_eq!(credentials.session_token.unwrap().expose(), "token");
+
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir(home.path().join(".aws")).unwrap();
+        fs::write(
+            home.path().join(".aws").join("credentials"),
+            "[default]\naws_access_key_id = AKID1\naws_secret_access_key = one\n\n\
+             [ci]\naws_access_key_id=AKID2\naws_secret_access_key=two\n",
+        )
+        .unwrap();
+        let home = Some(home.path().to_path_buf());
+        let credentials = AwsCredentials::lookup(|_| None, home.clone()).unwrap();
+        assert_eq!(credentials.access_key_id, "AKID1");
+        assert!(credentials.session_token.is_none());
+        let profile = |name: &str| (name == "AWS_PROFILE").then(|| "ci".to_string());
+        let credentials = AwsCredentials::lookup(profile, home.clone()).unwrap();
+        assert_eq!(credentials.secret_access_key.expose(), "two");
+        let missing = |name: &str| (name == "AWS_PROFILE").then(|| "prod".to_string());
+        let err = AwsCredentials::lookup(missing, home).unwrap_err();
+        assert!(
+            format!("{err:#}").starts_with("No AWS credentials for profile prod in "),
+            "{err:#}"
+        );
+        assert!(AwsCredentials::lookup(|_| None, None).is_err());
+    }
+}
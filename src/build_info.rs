@@ -0,0 +1,99 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// How this build of the crate was made, to tell in bug reports
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Commit built from, unless the crate wasn't built from a git checkout
+    pub git_commit: Option<&'static str>,
+    /// Library making the TLS connections
+    pub tls_backend: &'static str,
+    /// Cargo features enabled
+    pub features: Vec<&'static str>,
+    pub target: &'static str,
+}
+
+/// Features [`BuildInfo::features`] tells about
+const FEATURES: &[(&str, bool)] = &[
+    ("cli", cfg!(feature = "cli")),
+    ("github", cfg!(feature = "github")),
+    ("otel", cfg!(feature = "otel")),
+    ("regex", cfg!(feature = "regex")),
+    ("serve", cfg!(feature = "serve")),
+    ("yaml", cfg!(feature = "yaml")),
+];
+
+/// How this build was made
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("DOCKER_TAGS_GIT_COMMIT"),
+        tls_backend: "rustls",
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+        target: env!("DOCKER_TAGS_TARGET"),
+    }
+}
+
+/// Lines of `docker-tags version`
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "docker-tags {}", self.version)?;
+        writeln!(f, "commit: {}", self.git_commit.unwrap_or("unknown"))?;
+        writeln!(f, "tls: {}", self.tls_backend)?;
+        writeln!(f, "features: {}", self.features.join(", "))?;
+        write!(f, "target: {}", self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> BuildInfo {
+        BuildInfo {
+            version: "0.2.0",
+            git_commit: None,
+            tls_backend: "rustls",
+            features: vec!["cli", "regex"],
+            target: "x86_64-unknown-linux-gnu",
+        }
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_string(&info()).unwrap(),
+            r#"{"version":"0.2.0","git_commit":null,"tls_backend":"rustls","features":["cli","regex"],"target":"x86_64-unknown-linux-gnu"}"#
+        );
+        let info = BuildInfo {
+            git_commit: Some("de1d332a94f0"),
+            ..info()
+        };
+        assert_eq!(
+            serde_json::to_value(&info).unwrap()["git_commit"],
+            "de1d332a94f0"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            info().to_string(),
+            "docker-tags 0.2.0\ncommit: unknown\ntls: rustls\nfeatures: cli, regex\ntarget: x86_64-unknown-linux-gnu"
+        );
+    }
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.features.contains(&"cli"), cfg!(feature = "cli"));
+        assert!(!info.target.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeSet;
+
+use crate::Tag;
+
+/// Channel names known by default: tags which move along with a release
+/// line rather than naming a release
+pub const DEFAULT_CHANNELS: &[&str] = &[
+    "beta", "canary", "current", "dev", "edge", "latest", "lts", "mainline", "next", "nightly",
+    "preview", "rolling", "stable", "testing", "unstable",
+];
+
+/// Set of release channel names, telling which tags are channel tags
+///
+/// A tag is one if its name is a channel, or a channel followed by a variant
+/// suffix (`stable-alpine`), compared regardless of case.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Channels {
+    names: BTreeSet<String>,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Channels::new(DEFAULT_CHANNELS.iter().copied())
+    }
+}
+
+impl Channels {
+    /// Only the channels in `names`, without the defaults
+    pub fn new<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Self {
+        Channels {
+            names: names
+                .into_iter()
+                .map(|name| name.as_ref().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Also count `name` (e.g. `insiders`) as a channel
+    pub fn with(mut self, name: &str) -> Self {
+        self.names.insert(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Channel `tag` follows, e.g. `stable` for `stable-alpine`
+    pub fn channel_of(&self, tag: &Tag) -> Option<&str> {
+        let name = tag.name().to_ascii_lowercase();
+        let channel = match name.split_once('-') {
+            Some((channel, variant)) if !variant.is_empty() => channel,
+            _ => &name,
+        };
+        self.names
+            .get(channel)
+            .or_else(|| self.names.get(&name))
+            .map(String::as_str)
+    }
+
+    pub fn is_channel(&self, tag: &Tag) -> bool {
+        self.channel_of(tag).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_of() {
+        let channels = Channels::default().with("Insiders");
+        let cases = [
+            ("stable", Some("stable")),
+            ("Stable", Some("stable")),
+            ("mainline-alpine", Some("mainline")),
+            ("lts-slim-bookworm", Some("lts")),
+            ("insiders", Some("insiders")),
+            ("latest", Some("latest")),
+            ("1.27.2", None),
+            ("stable-", None),
+            ("stablecoin", None),
+            ("alpine-edge", None),
+            ("", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(channels.channel_of(&Tag::new(name)), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_custom_channels() {
+        let channels = Channels::new(["Release-Candidate", "ga"]);
+        assert!(channels.is_channel(&Tag::new("ga")));
+        assert!(channels.is_channel(&Tag::new("ga-alpine")));
+        // a channel name may contain dashes itself
+        assert!(channels.is_channel(&Tag::new("release-candidate")));
+        assert!(!channels.is_channel(&Tag::new("stable")));
+    }
+}
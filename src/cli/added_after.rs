@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use docker_tags::{Client, Error, Image, Selection, Tag, Warning, collate, output::TagEntry};
+
+use super::enrich_cache::{self, Sources};
+
+/// Tags created after `reference`, newest first, among those matching the
+/// pattern of `selection`
+///
+/// Every candidate's image config is fetched for its date; tags without one
+/// are left out and reported as warnings. With a `cache` file, dates found
+/// there are reused and where each one came from is told too.
+pub async fn select(
+    client: &Client,
+    image: &Image,
+    tags: &[Tag],
+    reference: &str,
+    selection: &Selection,
+    cache: Option<&Path>,
+) -> Result<(Vec<TagEntry>, Vec<Warning>, Option<Sources>)> {
+    if !tags.iter().any(|tag| tag.name() == reference) {
+        return Err(anyhow!(Error::NotFound).context(format!("No tag {reference} in {image}")));
+    }
+    let since = client
+        .fetch_config(image, reference)
+        .await
+        .with_context(|| format!("Failed to fetch the image config of {reference}"))?
+        .created_at()
+        .with_context(|| format!("{reference} has no created date"))?;
+
+    let filter = selection.filtering();
+    let candidates: Vec<_> = filter
+        .apply(tags.to_vec())
+        .into_iter()
+        .filter(|tag| tag.name() != reference)
+        .collect();
+    let (created, warnings, sources) = match cache {
+        Some(path) => {
+            let (created, warnings, sources) =
+                enrich_cache::fetch_created(client, image, tags, &candidates, path).await?;
+            (created, warnings, Some(sources))
+        }
+        None => {
+            let (created, warnings) = client.fetch_created(image, &candidates).await;
+            (created, warnings, None)
+        }
+    };
+    let mut newer: Vec<_> = created.into_iter().filter(|(_, at)| *at > since).collect();
+    newer.sort_by(|(a, a_at), (b, b_at)| b_at.cmp(a_at).then_with(|| collate(a.name(), b.name())));
+
+    Ok((
+        selection
+            .arrange(newer)
+            .into_iter()
+            .map(|(tag, _)| tag.into())
+            .collect(),
+        warnings,
+        sources,
+    ))
+}
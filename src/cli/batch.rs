@@ -0,0 +1,360 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use docker_tags::{
+    Error, Selection, Tag,
+    output::{Listing, NoticeEntry, TagEntry},
+    unstable::Reference,
+};
+use futures_util::{StreamExt, stream};
+use serde::Serialize;
+
+use super::{
+    Registry,
+    output::Output,
+    policy::{self, Condition, Results, Trigger},
+    prom::Metrics,
+    report::{BatchReport, Format, ImageSummary},
+};
+
+/// Images listed at the same time
+const CONCURRENCY: usize = 8;
+
+/// How listing an image went, by the kind of error it failed with
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    NotFound,
+    /// The registry refused the credentials or access to the repository
+    AccessDenied,
+    /// Any other failure, e.g. an unreachable registry
+    Error,
+}
+
+impl Status {
+    /// As in the documents, e.g. `not_found`
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::NotFound => "not_found",
+            Status::AccessDenied => "access_denied",
+            Status::Error => "error",
+        }
+    }
+
+    pub fn of(err: &anyhow::Error) -> Self {
+        // typed errors added as context are only found by downcasting `err`
+        let typed = err
+            .downcast_ref::<Error>()
+            .or_else(|| err.chain().find_map(|cause| cause.downcast_ref()));
+        match typed {
+            Some(Error::NotFound | Error::NotInLibrary { .. }) => Status::NotFound,
+            Some(Error::AccessDenied { .. } | Error::Unauthorized(_)) => Status::AccessDenied,
+            _ => Status::Error,
+        }
+    }
+}
+
+/// How the run went as a whole: `Ok` if every image was listed, the kind of
+/// failure when all failures were of one kind, `Error` otherwise
+pub fn overall(statuses: impl IntoIterator<Item = Status>) -> Status {
+    let mut failures = statuses.into_iter().filter(|&status| status != Status::Ok);
+    match failures.next() {
+        None => Status::Ok,
+        Some(first) if failures.all(|status| status == first) => first,
+        Some(_) => Status::Error,
+    }
+}
+
+/// The text of the file of images `input`, `-` for stdin
+pub fn read_images(input: &Path) -> Result<String> {
+    match input.to_str() {
+        Some("-") => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read images from stdin")?;
+            Ok(text)
+        }
+        _ => fs::read_to_string(input)
+            .with_context(|| format!("Failed to read images from {input:?}")),
+    }
+}
+
+/// Print the tags of every image of `lines` through `selection`, then a
+/// summary of how each went on stderr unless `no_summary`, returning how the
+/// run went and the `fail_on` conditions the images met
+///
+/// An image may be given with the tag in use (`nginx:1.25.3`), which
+/// Prometheus metrics and `outdated` conditions compare with the newest
+/// version.
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    lines: &[&str],
+    selection: &Selection,
+    format: Format,
+    no_summary: bool,
+    fail_on: &[Condition],
+) -> Result<(Status, Vec<Trigger>)> {
+    let results: Vec<_> = stream::iter(lines.iter().copied())
+        .map(|line| async move {
+            let result = async {
+                let image = registry.image(split_current(line).0)?;
+                if let Some(cache) = &registry.not_found {
+                    cache.check(&image)?;
+                }
+                registry.client(&image)?.fetch_tags_partial(&image).await
+            }
+            .await;
+            (line, result)
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+    if let Some(cache) = &registry.not_found {
+        let images: Vec<_> = results
+            .iter()
+            .map(|(line, _)| registry.image(split_current(line).0).ok())
+            .collect();
+        let outcomes = images
+            .iter()
+            .zip(&results)
+            .filter_map(|(image, (_, result))| {
+                // a listing cut short by a failure counts as one
+                let error = match result {
+                    Ok((_, outcome)) => outcome.error.as_ref(),
+                    Err(err) => Some(err),
+                };
+                Some((image.as_ref()?, error))
+            });
+        // a cache that can't be written only costs requests
+        let _ = cache.update(outcomes);
+    }
+
+    let mut report = BatchReport::default();
+    let mut metrics = Metrics::default();
+    let mut triggered = Vec::new();
+    for (line, result) in results {
+        let (name, current) = split_current(line);
+        let (tags, outcome) = match result {
+            Ok((tags, outcome)) if outcome.error.is_none() => (tags, outcome),
+            Ok((_, outcome)) => {
+                let err = outcome.error.expect("checked above");
+                report
+                    .summary
+                    .push(ImageSummary::failed(line, Status::of(&err), &err));
+                metrics.failed(name, current);
+                continue;
+            }
+            Err(err) => {
+                report
+                    .summary
+                    .push(ImageSummary::failed(line, Status::of(&err), &err));
+                metrics.failed(name, current);
+                continue;
+            }
+        };
+        if !registry.quiet {
+            for warning in &outcome.warnings {
+                eprintln!("Warning: {line}: {warning}");
+            }
+        }
+        let image = registry.image(name)?;
+        registry.warn_notices(&image, &outcome.notices);
+        let selection = registry.selection_for(&image, selection);
+        let candidates: Vec<_> = tags
+            .iter()
+            .filter(|tag| selection.matches(tag))
+            .cloned()
+            .collect();
+        let tags: Vec<Tag> = selection.apply(tags);
+        report.summary.push(ImageSummary::ok(line, tags.len()));
+        let results = Results {
+            current,
+            candidates: &candidates,
+            shown: tags.len(),
+            unsigned: None,
+        };
+        for trigger in policy::evaluate(fail_on, &results) {
+            triggered.push(Trigger {
+                reason: format!("{line}: {}", trigger.reason),
+                ..trigger
+            });
+        }
+        let matched = candidates.len();
+        match format {
+            Format::Text => {
+                for tag in &tags {
+                    out.line(Reference::from((&image, tag)))?;
+                }
+                continue;
+            }
+            Format::Prom => {
+                metrics.listed(name, current, outcome.listed, &tags);
+                continue;
+            }
+            _ => {}
+        }
+        let entries = tags.into_iter().map(TagEntry::from).collect();
+        let mut listing = Listing::new(Some(line.to_string()), entries, &outcome.warnings);
+        (listing.total, listing.matched) = (outcome.listed, matched);
+        listing.notices = outcome.notices.iter().map(NoticeEntry::from).collect();
+        report.images.push(listing);
+    }
+
+    match format {
+        Format::Text | Format::Prom => {
+            if format == Format::Prom {
+                out.line(metrics.render())?;
+            }
+            // after the tags, which are still buffered
+            out.flush()?;
+            if !no_summary {
+                for line in format_summary(&report.summary) {
+                    eprintln!("{line}");
+                }
+            }
+        }
+        format => out.document(format, &report)?,
+    }
+    let status = overall(report.summary.iter().map(|image| image.status));
+    Ok((status, triggered))
+}
+
+/// Image names in `text`, one per line, skipping blank lines and `#` comments
+pub fn parse_images(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+/// The image name of a line and the tag in use after it, if any
+fn split_current(line: &str) -> (&str, Option<&str>) {
+    match line.rsplit_once(':') {
+        // the colon of a port comes before a `/`
+        Some((name, tag)) if !tag.contains('/') => (name, Some(tag)),
+        _ => (line, None),
+    }
+}
+
+/// Table of the images with their status, tag count and error, columns
+/// padded to the longest entry
+fn format_summary(summary: &[ImageSummary]) -> Vec<String> {
+    let rows: Vec<[String; 4]> = summary
+        .iter()
+        .map(|image| {
+            [
+                image.image.clone(),
+                image.status.as_str().to_string(),
+                image.tags.map_or("-".to_string(), |tags| tags.to_string()),
+                image.error.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let header = ["IMAGE", "STATUS", "TAGS", "ERROR"].map(str::to_string);
+    let widths: Vec<_> = (0..3)
+        .map(|i| {
+            rows.iter()
+                .chain([&header])
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    [&header]
+        .into_iter()
+        .chain(&rows)
+        .map(|row| {
+            let line = format!(
+                "{:w0$}  {:w1$}  {:w2$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2]
+            );
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_images() {
+        let text = "# base images\nalpine\n\n  ghcr.io/org/app  \n#nginx\n";
+        assert_eq!(parse_images(text), ["alpine", "ghcr.io/org/app"]);
+    }
+
+    #[test]
+    fn test_split_current() {
+        assert_eq!(split_current("nginx"), ("nginx", None));
+        assert_eq!(split_current("nginx:1.25.3"), ("nginx", Some("1.25.3")));
+        assert_eq!(
+            split_current("localhost:5000/app"),
+            ("localhost:5000/app", None)
+        );
+        assert_eq!(
+            split_current("localhost:5000/app:1.0"),
+            ("localhost:5000/app", Some("1.0"))
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let not_found = anyhow!("401").context(Error::NotFound);
+        assert_eq!(Status::of(&not_found), Status::NotFound);
+        let denied = anyhow!(Error::Unauthorized(String::new()));
+        assert_eq!(Status::of(&denied), Status::AccessDenied);
+        assert_eq!(Status::of(&anyhow!("connection refused")), Status::Error);
+    }
+
+    #[test]
+    fn test_overall() {
+        use Status::*;
+        let cases: [(&[Status], Status); 6] = [
+            (&[], Ok),
+            (&[Ok, Ok], Ok),
+            (&[Ok, NotFound, NotFound], NotFound),
+            (&[AccessDenied, Ok], AccessDenied),
+            (&[NotFound, AccessDenied], Error),
+            (&[Ok, Error], Error),
+        ];
+        for (statuses, expected) in cases {
+            assert_eq!(overall(statuses.iter().copied()), expected, "{statuses:?}");
+        }
+    }
+
+    #[test]
+    fn test_format_summary() {
+        let summary = [
+            ImageSummary::ok("alpine", 12),
+            ImageSummary::failed(
+                "ghcr.io/org/private",
+                Status::AccessDenied,
+                &anyhow!("Access denied to ghcr.io/org/private"),
+            ),
+        ];
+        assert_eq!(
+            format_summary(&summary),
+            [
+                "IMAGE                STATUS         TAGS  ERROR",
+                "alpine               ok             12",
+                "ghcr.io/org/private  access_denied  -     Access denied to ghcr.io/org/private",
+            ]
+        );
+    }
+}
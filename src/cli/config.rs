@@ -0,0 +1,506 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs, io,
+    num::NonZeroU16,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use docker_tags::TlsVersion;
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+use super::output::write_atomic;
+
+/// What `config.toml` holds: the named image sets and the settings of
+/// registries
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct Config {
+    #[serde(default)]
+    pub sets: BTreeMap<String, ImageSet>,
+    /// Profiles by registry host, from `[registries."HOST"]` tables
+    #[serde(default)]
+    pub registries: BTreeMap<String, RegistryProfile>,
+    /// Whether listed images are remembered for completion, as they are
+    /// unless this is `false`
+    pub history: Option<bool>,
+    /// Tags to leave out, like `--ignore-file`, which replaces it
+    pub ignore_file: Option<PathBuf>,
+}
+
+/// Settings of a registry, which the command line flags for the same host
+/// come before
+///
+/// Relative paths are read from the config file's directory.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryProfile {
+    /// PEM file of extra CA certificates, like `--ca-cert HOST=FILE`
+    pub ca_cert: Option<PathBuf>,
+    /// CA certificates of the registry's token services, by host, for those
+    /// which don't share the registry's
+    #[serde(default)]
+    pub auth_hosts: BTreeMap<String, PathBuf>,
+    /// Name to check the certificate of a registry given by IP address
+    /// against, like `--tls-server-name HOST=NAME`
+    pub tls_server_name: Option<String>,
+    /// Oldest TLS version allowed with the registry and its token service,
+    /// like `--tls-min HOST=VERSION`
+    #[serde(default, deserialize_with = "tls_version")]
+    pub tls_min: Option<TlsVersion>,
+    /// User to log in as, with the password in `password_file`, like
+    /// `--username HOST=USER` and `--password-file HOST=FILE`
+    pub username: Option<String>,
+    pub password_file: Option<PathBuf>,
+    /// File of a registry token to send as it is, like `--token-file
+    /// HOST=FILE`
+    pub token_file: Option<PathBuf>,
+    /// Limits on the requests to the registry, like
+    /// `--max-concurrent-requests` and `--min-request-interval` (in
+    /// milliseconds)
+    pub max_concurrent_requests: Option<NonZeroU16>,
+    pub min_request_interval: Option<u64>,
+}
+
+/// Images listed together by `--set NAME`, from the `[sets.NAME]` table
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageSet {
+    /// Image names, each optionally with the tag in use (`nginx:1.25.3`)
+    pub images: Vec<String>,
+}
+
+/// The config file, and its text as read so that edits keep the rest of it
+#[derive(Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    text: String,
+    pub config: Config,
+}
+
+impl ConfigFile {
+    /// Read the config file at `path`, one that doesn't exist being empty
+    pub fn open(path: &Path) -> Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(anyhow!(err).context(format!("Failed to read config file {path:?}")));
+            }
+        };
+        let config =
+            toml::from_str(&text).with_context(|| format!("Invalid config file {path:?}"))?;
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            text,
+            config,
+        })
+    }
+
+    /// `$XDG_CONFIG_HOME/docker-tags/config.toml`, or `~/.config/...`
+    pub fn default_path() -> Option<PathBuf> {
+        config_path(env::var_os("XDG_CONFIG_HOME"), env::home_dir())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Profiles by registry host, with their paths made absolute
+    pub fn profiles(&self) -> BTreeMap<String, RegistryProfile> {
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        let mut profiles = self.config.registries.clone();
+        for profile in profiles.values_mut() {
+            let paths = profile
+                .ca_cert
+                .iter_mut()
+                .chain(&mut profile.password_file)
+                .chain(&mut profile.token_file)
+                .chain(profile.auth_hosts.values_mut());
+            for path in paths {
+                *path = dir.join(&*path);
+            }
+        }
+        profiles
+    }
+
+    /// The `ignore_file`, made absolute
+    pub fn ignore_file(&self) -> Option<PathBuf> {
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        self.config.ignore_file.as_ref().map(|path| dir.join(path))
+    }
+
+    /// The set called `name`, failing with the names of those there are
+    pub fn set(&self, name: &str) -> Result<&ImageSet> {
+        self.config.sets.get(name).ok_or_else(|| {
+            let known = match self.config.sets.is_empty() {
+                true => "there are none".to_string(),
+                false => {
+                    let names: Vec<_> = self.config.sets.keys().map(String::as_str).collect();
+                    format!("there are {}", names.join(", "))
+                }
+            };
+            anyhow!("No set named {name:?} in {:?}; {known}", self.path)
+        })
+    }
+
+    /// Give the set `name` these `images`, or remove it if there are none,
+    /// then write the file
+    ///
+    /// Only the set's `[sets.NAME]` table is rewritten, so comments and
+    /// formatting elsewhere stay. A set defined otherwise, such as inline in
+    /// a `[sets]` table, is left alone with an error.
+    pub fn update(&mut self, name: &str, images: Option<Vec<String>>) -> Result<()> {
+        let mut expected = self.config.clone();
+        match &images {
+            Some(images) => {
+                let set = expected.sets.entry(name.to_string()).or_default();
+                set.images = images.clone();
+            }
+            None => {
+                expected.sets.remove(name);
+            }
+        }
+        let text = rewrite(&self.text, name, images.as_deref());
+        if toml::from_str::<Config>(&text).ok().as_ref() != Some(&expected) {
+            bail!(
+                "Failed to update the set {name:?} without rewriting {:?}; edit its [sets.{name}] \
+                 table by hand",
+                self.path
+            );
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+        write_atomic(&self.path, text.as_bytes())?;
+        (self.text, self.config) = (text, expected);
+        Ok(())
+    }
+}
+
+/// A TLS version written as for `--tls-min`, e.g. `"1.3"`
+fn tls_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<TlsVersion>, D::Error> {
+    let version = String::deserialize(deserializer)?;
+    version.parse().map(Some).map_err(de::Error::custom)
+}
+
+/// `$XDG_CONFIG_HOME/docker-tags/config.toml`, or `~/.config/...`
+fn config_path(config_home: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let dir = match config_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        Some(dir) => dir,
+        None => home?.join(".config"),
+    };
+    Some(dir.join("docker-tags").join("config.toml"))
+}
+
+/// Parse the name of a set, which is used as a bare TOML key
+pub fn parse_set_name(value: &str) -> Result<String, String> {
+    let valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(value.to_string()),
+        false => Err("expected letters, digits, `-` and `_`".to_string()),
+    }
+}
+
+/// `text` with the `[sets.NAME]` table replaced by one listing `images`, or
+/// added at the end if there's none, or removed without `images`
+///
+/// The table's comments and the comments before the next table stay.
+fn rewrite(text: &str, name: &str, images: Option<&[String]>) -> String {
+    let header = format!("[sets.{name}]");
+    let is_header = |line: &str| {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        line.starts_with('[')
+    };
+    let lines: Vec<_> = text.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| line.split('#').next().unwrap_or_default().trim() == header);
+
+    let mut table = Vec::new();
+    if let Some(images) = images {
+        table.push(header.clone());
+        table.push("images = [".to_string());
+        for image in images {
+            table.push(format!("    {},", toml::Value::String(image.clone())));
+        }
+        table.push("]".to_string());
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    match start {
+        Some(start) => {
+            let mut end = (start + 1..lines.len())
+                .find(|&i| is_header(lines[i]))
+                .unwrap_or(lines.len());
+            // comments right before the next table are about it
+            while end > start + 1 && is_comment_or_blank(lines[end - 1]) {
+                end -= 1;
+            }
+            match images {
+                Some(_) => {
+                    out.extend(lines[..start].iter().map(|line| line.to_string()));
+                    // the header line may have a comment of its own
+                    table[0] = lines[start].to_string();
+                    let comments = lines[start + 1..end]
+                        .iter()
+                        .filter(|line| line.trim_start().starts_with('#'))
+                        .map(|line| line.to_string());
+                    table.splice(1..1, comments);
+                    out.extend(table);
+                }
+                None => {
+                    // and the comments right before it, which are about it
+                    let mut begin = start;
+                    while begin > 0 && lines[begin - 1].trim_start().starts_with('#') {
+                        begin -= 1;
+                    }
+                    out.extend(lines[..begin].iter().map(|line| line.to_string()));
+                    // no blank lines twice, first or last where it was
+                    while out.last().is_none_or(|line| line.trim().is_empty())
+                        && lines.get(end).is_some_and(|line| line.trim().is_empty())
+                    {
+                        end += 1;
+                    }
+                    if end == lines.len() {
+                        while out.last().is_some_and(|line| line.trim().is_empty()) {
+                            out.pop();
+                        }
+                    }
+                }
+            }
+            out.extend(lines[end..].iter().map(|line| line.to_string()));
+        }
+        None => {
+            out.extend(lines.iter().map(|line| line.to_string()));
+            if !table.is_empty() {
+                if out.last().is_some_and(|line| !line.trim().is_empty()) {
+                    out.push(String::new());
+                }
+                out.extend(table);
+            }
+        }
+    }
+    match out.is_empty() {
+        true => String::new(),
+        false => format!("{}\n", out.join("\n")),
+    }
+}
+
+fn is_comment_or_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "\
+# checked every morning
+[sets.platform] # the cluster's
+# web first
+images = [\"nginx\", \"redis\"]
+
+# ours
+[sets.apps]
+images = [
+    \"ghcr.io/org/app:1.2.0\",
+]
+";
+
+    fn images(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_config_path() {
+        assert_eq!(
+            config_path(Some("/config".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/config/docker-tags/config.toml"))
+        );
+        assert_eq!(
+            config_path(Some("config".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.config/docker-tags/config.toml"))
+        );
+        assert_eq!(config_path(None, None), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let config: Config = toml::from_str(TEXT).unwrap();
+        assert_eq!(config.sets["platform"].images, ["nginx", "redis"]);
+        assert_eq!(config.sets["apps"].images, ["ghcr.io/org/app:1.2.0"]);
+        assert!(toml::from_str::<Config>("[sets.typo]\nimage = [\"nginx\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "[registries.\"registry.corp:5000\"]\nca_cert = \"certs/corp.pem\"\n\
+             tls_server_name = \"registry.corp\"\n\n\
+             [registries.\"registry.corp:5000\".auth_hosts]\n\"auth.corp\" = \"/etc/auth.pem\"\n",
+        )
+        .unwrap();
+        let profiles = ConfigFile::open(&path).unwrap().profiles();
+        let profile = &profiles["registry.corp:5000"];
+        assert_eq!(profile.ca_cert, Some(dir.path().join("certs/corp.pem")));
+        assert_eq!(
+            profile.auth_hosts["auth.corp"],
+            PathBuf::from("/etc/auth.pem")
+        );
+        assert_eq!(profile.tls_server_name.as_deref(), Some("registry.corp"));
+
+        fs::write(
+            &path,
+            "[registries.\"ghcr.io\"]\nusername = \"ci\"\npassword_file = \"secrets/ghcr\"\n\n\
+             [registries.\"harbor.corp\"]\ntoken_file = \"/run/secrets/harbor\"\n",
+        )
+        .unwrap();
+        let profiles = ConfigFile::open(&path).unwrap().profiles();
+        assert_eq!(profiles["ghcr.io"].username.as_deref(), Some("ci"));
+        assert_eq!(
+            profiles["ghcr.io"].password_file,
+            Some(dir.path().join("secrets/ghcr"))
+        );
+        assert_eq!(
+            profiles["harbor.corp"].token_file,
+            Some(PathBuf::from("/run/secrets/harbor"))
+        );
+
+        fs::write(
+            &path,
+            "[registries.\"registry.corp\"]\nmax_concurrent_requests = 2\nmin_request_interval = 200\n",
+        )
+        .unwrap();
+        let profiles = ConfigFile::open(&path).unwrap().profiles();
+        let profile = &profiles["registry.corp"];
+        assert_eq!(profile.max_concurrent_requests, NonZeroU16::new(2));
+        assert_eq!(profile.min_request_interval, Some(200));
+        assert_eq!(profile.tls_server_name, None);
+        assert_eq!(profile.tls_min, None);
+        fs::write(&path, "[registries.\"registry.corp\"]\ntls_min = \"1.3\"\n").unwrap();
+        let profiles = ConfigFile::open(&path).unwrap().profiles();
+        assert_eq!(profiles["registry.corp"].tls_min, Some(TlsVersion::Tls13));
+        fs::write(&path, "[registries.\"registry.corp\"]\ntls_min = \"1.1\"\n").unwrap();
+        let err = ConfigFile::open(&path).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("TLS 1.1 can't be allowed"),
+            "{err:#}"
+        );
+        fs::write(
+            &path,
+            "[registries.\"registry.corp\"]\nmax_concurrent_requests = 0\n",
+        )
+        .unwrap();
+        assert!(ConfigFile::open(&path).is_err());
+
+        fs::write(
+            &path,
+            "[registries.\"registry.corp\"]\nca_certs = \"a.pem\"\n",
+        )
+        .unwrap();
+        let err = ConfigFile::open(&path).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown field `ca_certs`"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let text = rewrite(TEXT, "platform", Some(&images(&["nginx", "postgres"])));
+        assert_eq!(
+            text,
+            "\
+# checked every morning
+[sets.platform] # the cluster's
+# web first
+images = [
+    \"nginx\",
+    \"postgres\",
+]
+
+# ours
+[sets.apps]
+images = [
+    \"ghcr.io/org/app:1.2.0\",
+]
+"
+        );
+
+        let text = rewrite(TEXT, "platform", None);
+        assert_eq!(
+            text,
+            "\
+# ours
+[sets.apps]
+images = [
+    \"ghcr.io/org/app:1.2.0\",
+]
+"
+        );
+
+        let text = rewrite(TEXT, "apps", None);
+        assert!(
+            text.ends_with("images = [\"nginx\", \"redis\"]\n"),
+            "{text}"
+        );
+
+        let text = rewrite(TEXT, "db", Some(&images(&["postgres"])));
+        assert_eq!(
+            text,
+            format!("{TEXT}\n[sets.db]\nimages = [\n    \"postgres\",\n]\n")
+        );
+        assert_eq!(
+            rewrite("", "db", Some(&images(&[r#"a"b"#]))),
+            "[sets.db]\nimages = [\n    \"a\\\"b\",\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("docker-tags").join("config.toml");
+        let mut file = ConfigFile::open(&path).unwrap();
+        assert!(file.config.sets.is_empty());
+        assert_eq!(
+            file.set("platform").unwrap_err().to_string(),
+            format!("No set named \"platform\" in {path:?}; there are none")
+        );
+
+        file.update("platform", Some(images(&["nginx"]))).unwrap();
+        file.update("apps", Some(images(&["ghcr.io/org/app"])))
+            .unwrap();
+        let file = ConfigFile::open(&path).unwrap();
+        assert_eq!(file.set("platform").unwrap().images, ["nginx"]);
+        assert_eq!(
+            file.set("db").unwrap_err().to_string(),
+            format!("No set named \"db\" in {path:?}; there are apps, platform")
+        );
+
+        // defined where only rewriting the whole file could change it
+        fs::write(&path, "[sets]\nplatform = { images = [\"nginx\"] }\n").unwrap();
+        let mut file = ConfigFile::open(&path).unwrap();
+        let err = file.update("platform", None).unwrap_err();
+        assert!(
+            err.to_string().contains("edit its [sets.platform] table"),
+            "{err}"
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[sets]\nplatform = { images = [\"nginx\"] }\n"
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt},
+        process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use docker_tags::Selection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::Notify,
+    time::Instant,
+};
+
+use super::{Registry, history::state_path, report::ErrorReport, serve::Cache};
+
+/// Version of the protocol spoken over the socket, raised whenever a
+/// request or reply changes incompatibly
+pub const PROTOCOL: u32 = 1;
+
+/// Longest request read, beyond which the connection is dropped
+const MAX_REQUEST: u64 = 64 * 1024;
+
+/// How long `daemon start` waits for the daemon it ran to listen
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `$XDG_RUNTIME_DIR/docker-tags.sock`, or
+/// `$XDG_STATE_HOME/docker-tags/daemon.sock`, or `~/.local/state/...`
+pub fn default_socket() -> Option<PathBuf> {
+    match env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        Some(dir) => Some(dir.join("docker-tags.sock")),
+        None => state_path(
+            "daemon.sock",
+            env::var_os("XDG_STATE_HOME"),
+            env::home_dir(),
+        ),
+    }
+}
+
+/// A request, one line of JSON per connection, e.g. `{"version":1,
+/// "command":"tags","image":"nginx","select":{...}}`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Request {
+    pub version: u32,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    /// Tell whether the daemon is up, and which process it is
+    Ping,
+    /// Exit once the requests in flight are answered
+    Stop,
+    /// List `image`, sorted and filtered by the flags of `select` as the
+    /// command line parsed them
+    Tags { image: String, select: Value },
+}
+
+/// The reply to a request, one line of JSON before the daemon hangs up
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Reply {
+    pub version: u32,
+    /// Process answering a `ping`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// The listing, as `-o json` prints it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listing: Option<Value>,
+    /// Why the request failed, as `-o json` reports errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+impl Reply {
+    fn new() -> Self {
+        Reply {
+            version: PROTOCOL,
+            ..Reply::default()
+        }
+    }
+
+    fn failed(err: &anyhow::Error) -> Self {
+        Reply {
+            error: serde_json::to_value(ErrorReport::from(err)).ok(),
+            ..Reply::new()
+        }
+    }
+}
+
+/// Reads the `select` of the requests into a selection, the way the
+/// command line would
+type SelectionOf = Box<dyn Fn(Value) -> Result<Selection> + Send + Sync>;
+
+/// What every connection of a daemon shares
+struct Daemon {
+    cache: Cache,
+    selection: SelectionOf,
+    /// Owner of the socket, the only user answered
+    uid: u32,
+    /// Connections being answered, and when the last one was
+    active: AtomicUsize,
+    last: Mutex<Instant>,
+    stop: Notify,
+}
+
+/// Answer requests on `socket` until stopped, or once no request came for
+/// `idle_timeout`, printing the path listened on to stderr; `selection`
+/// reads the flags forwarded by `--via-daemon`
+pub async fn serve(
+    registry: Registry,
+    socket: &Path,
+    idle_timeout: Duration,
+    selection: impl Fn(Value) -> Result<Selection> + Send + Sync + 'static,
+) -> Result<()> {
+    let listener = bind(socket).await?;
+    eprintln!("Listening on {}", socket.display());
+
+    let daemon = Arc::new(Daemon {
+        // every request lists anew, only connections and tokens are kept
+        cache: Cache::new(registry, Duration::ZERO),
+        selection: Box::new(selection),
+        uid: fs::metadata(socket)
+            .with_context(|| format!("Failed to read {socket:?}"))?
+            .uid(),
+        active: AtomicUsize::new(0),
+        last: Mutex::new(Instant::now()),
+        stop: Notify::new(),
+    });
+    loop {
+        let idle_until = match daemon.active.load(Ordering::SeqCst) {
+            0 => *daemon.last.lock().unwrap() + idle_timeout,
+            _ => Instant::now() + idle_timeout,
+        };
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.context("Failed to accept")?;
+                let daemon = daemon.clone();
+                daemon.active.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    // the client hanging up is none of the daemon's business
+                    let _ = handle(stream, &daemon).await;
+                    *daemon.last.lock().unwrap() = Instant::now();
+                    daemon.active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            () = daemon.stop.notified() => break,
+            () = tokio::time::sleep_until(idle_until) => {
+                if daemon.active.load(Ordering::SeqCst) == 0
+                    && daemon.last.lock().unwrap().elapsed() >= idle_timeout
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    // gone before the last replies, so that another daemon may start
+    let _ = fs::remove_file(socket);
+    while daemon.active.load(Ordering::SeqCst) > 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Ok(())
+}
+
+/// Listen on `socket`, readable and writable by the user only, replacing
+/// the socket of a daemon which is gone
+async fn bind(socket: &Path) -> Result<UnixListener> {
+    if let Some(dir) = socket
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+    {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {dir:?}"))?;
+    }
+    match fs::symlink_metadata(socket) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if ping(socket).await.is_ok() {
+                bail!("A daemon is already listening on {socket:?}");
+            }
+            fs::remove_file(socket).with_context(|| format!("Failed to remove {socket:?}"))?;
+        }
+        Ok(_) => bail!("{socket:?} exists and isn't a socket"),
+        Err(_) => {}
+    }
+    let listener =
+        UnixListener::bind(socket).with_context(|| format!("Failed to listen on {socket:?}"))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {socket:?} to the user"))?;
+    Ok(listener)
+}
+
+async fn handle(stream: UnixStream, daemon: &Daemon) -> Result<()> {
+    // whatever the mode of the socket, other users get nothing
+    if stream.peer_cred()?.uid() != daemon.uid {
+        return Ok(());
+    }
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    (&mut stream).take(MAX_REQUEST).read_line(&mut line).await?;
+    if !line.ends_with('\n') {
+        return Ok(());
+    }
+
+    let reply = answer(&line, daemon).await;
+    let mut body = serde_json::to_vec(&reply).expect("replies serialize");
+    body.push(b'\n');
+    let stream = stream.get_mut();
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn answer(line: &str, daemon: &Daemon) -> Reply {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return Reply::failed(&anyhow!(err).context("Malformed request")),
+    };
+    // checked first, the requests of other versions may not even parse
+    match request["version"].as_u64() {
+        Some(version) if version == u64::from(PROTOCOL) => {}
+        version => {
+            let version = version.map_or("none".to_string(), |v| v.to_string());
+            return Reply::failed(&anyhow!(
+                "Unsupported protocol version {version}; the daemon speaks version {PROTOCOL}"
+            ));
+        }
+    }
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(err) => return Reply::failed(&anyhow!(err).context("Malformed request")),
+    };
+    match request.command {
+        Command::Ping => Reply {
+            pid: Some(std::process::id()),
+            ..Reply::new()
+        },
+        Command::Stop => {
+            daemon.stop.notify_one();
+            Reply::new()
+        }
+        Command::Tags { image, select } => match list(&image, select, daemon).await {
+            Ok(listing) => Reply {
+                listing: Some(listing),
+                ..Reply::new()
+            },
+            Err(err) => Reply::failed(&err),
+        },
+    }
+}
+
+/// The listing of `name`, skipping and remembering missing repositories
+/// like a listing of the command line
+async fn list(name: &str, select: Value, daemon: &Daemon) -> Result<Value> {
+    let registry = &daemon.cache.registry;
+    let image = registry.image(name)?;
+    let selection = (daemon.selection)(select)?;
+    let result = async {
+        if let Some(cache) = &registry.not_found {
+            cache.check(&image)?;
+        }
+        daemon.cache.listing(&image, &selection).await
+    }
+    .await;
+    if let Some(cache) = &registry.not_found {
+        // a cache that can't be written only costs requests
+        let _ = cache.update([(&image, result.as_ref().err())]);
+    }
+    Ok(serde_json::to_value(result?)?)
+}
+
+/// Send `command` to the daemon on `socket` and read its reply, failing
+/// with the error it replied with
+async fn send(socket: &Path, command: Command) -> Result<Reply> {
+    let mut stream = UnixStream::connect(socket).await.with_context(|| {
+        format!("No daemon is listening on {socket:?}; start one with `docker-tags daemon start`")
+    })?;
+    let request = Request {
+        version: PROTOCOL,
+        command,
+    };
+    let mut line = serde_json::to_vec(&request).expect("requests serialize");
+    line.push(b'\n');
+    stream.write_all(&line).await?;
+    let mut body = String::new();
+    stream.read_to_string(&mut body).await?;
+    let reply: Reply = serde_json::from_str(&body)
+        .with_context(|| format!("Malformed reply of the daemon on {socket:?}"))?;
+    if reply.version != PROTOCOL {
+        bail!(
+            "The daemon on {socket:?} speaks protocol version {}, not {PROTOCOL}; restart it with \
+             `docker-tags daemon stop` and `docker-tags daemon start`",
+            reply.version
+        );
+    }
+    match reply.error {
+        Some(error) => Err(remote_error(&error)),
+        None => Ok(reply),
+    }
+}
+
+/// The error of a reply with its causes, as it failed in the daemon
+fn remote_error(report: &Value) -> anyhow::Error {
+    let mut messages: Vec<_> = [&report["error"]]
+        .into_iter()
+        .chain(report["causes"].as_array().into_iter().flatten())
+        .map(|message| message.as_str().unwrap_or("unknown error").to_string())
+        .collect();
+    // the root cause first, wrapped in the contexts above it
+    messages.reverse();
+    let (root, contexts) = messages.split_first().expect("there's the error itself");
+    contexts.iter().fold(anyhow!(root.clone()), |err, context| {
+        err.context(context.clone())
+    })
+}
+
+/// The process ID of the daemon on `socket`, if one answers
+pub async fn ping(socket: &Path) -> Result<u32> {
+    let reply = send(socket, Command::Ping).await?;
+    reply.pid.context("The daemon didn't tell its process ID")
+}
+
+/// The listing of `image` by the daemon on `socket`, in the schema of `-o
+/// json`, `select` being the sorting and filtering flags
+pub async fn tags(socket: &Path, image: &str, select: Value) -> Result<Value> {
+    let command = Command::Tags {
+        image: image.to_string(),
+        select,
+    };
+    let reply = send(socket, command).await?;
+    reply
+        .listing
+        .context("The daemon replied without a listing")
+}
+
+/// Run a daemon on `socket` in the background, this binary again with
+/// `args` and `--foreground`, unless one is listening already; true if it
+/// was started
+pub async fn start(socket: &Path, args: impl IntoIterator<Item = OsString>) -> Result<bool> {
+    if ping(socket).await.is_ok() {
+        return Ok(false);
+    }
+    let binary = env::current_exe().context("Failed to find the docker-tags binary")?;
+    // nothing is left to read what it prints once this process exits
+    let mut child = std::process::Command::new(binary)
+        .args(args)
+        .arg("--foreground")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // out of the terminal's process group, so Ctrl-C leaves it running
+        .process_group(0)
+        .spawn()
+        .context("Failed to start the daemon")?;
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        if ping(socket).await.is_ok() {
+            return Ok(true);
+        }
+        if let Some(status) = child.try_wait()? {
+            bail!(
+                "The daemon exited ({status}) before listening on {socket:?}; run `docker-tags \
+                 daemon start --foreground` to see why"
+            );
+        }
+        if Instant::now() >= deadline {
+            bail!("The daemon didn't listen on {socket:?} within {START_TIMEOUT:?}");
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// Ask the daemon on `socket` to exit; false if none was listening
+pub async fn stop(socket: &Path) -> Result<bool> {
+    if ping(socket).await.is_err() {
+        return Ok(false);
+    }
+    send(socket, Command::Stop).await?;
+    Ok(true)
+}
+
+/// Print the tags of `listing` one per line, its aliases apart first
+pub fn print(out: &mut super::output::Output, listing: &Value) -> Result<()> {
+    let names = |key: &str| -> Vec<String> {
+        listing[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry["name"].as_str())
+            .map(String::from)
+            .collect()
+    };
+    let (aliases, tags) = (names("aliases"), names("tags"));
+    for alias in &aliases {
+        out.line(alias)?;
+    }
+    if !aliases.is_empty() && !tags.is_empty() {
+        out.line("")?;
+    }
+    for tag in &tags {
+        out.line(tag)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_format() {
+        let request = Request {
+            version: PROTOCOL,
+            command: Command::Tags {
+                image: "nginx".to_string(),
+                select: serde_json::json!({"reverse": true}),
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "version": 1,
+                "command": "tags",
+                "image": "nginx",
+                "select": {"reverse": true},
+            })
+        );
+        let ping: Request = serde_json::from_str(r#"{"version":1,"command":"ping"}"#).unwrap();
+        assert!(matches!(ping.command, Command::Ping));
+    }
+
+    #[test]
+    fn test_remote_error() {
+        let report = ErrorReport::from(
+            &anyhow!("connection refused")
+                .context("Failed to fetch tags")
+                .context("Failed to list ghcr.io/org/app"),
+        );
+        let err = remote_error(&serde_json::to_value(report).unwrap());
+        assert_eq!(err.to_string(), "Failed to list ghcr.io/org/app");
+        let causes: Vec<_> = err.chain().skip(1).map(ToString::to_string).collect();
+        assert_eq!(causes, ["Failed to fetch tags", "connection refused"]);
+    }
+}
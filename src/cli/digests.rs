@@ -0,0 +1,14 @@
+use docker_tags::{Client, Image, Tag, Warning, output::TagEntry};
+
+/// Look up the manifest digests of the tags of `entries`, concurrently
+///
+/// Only the entries given are resolved, so callers pass those left after
+/// filtering, sorting and the limit.
+pub async fn enrich(client: &Client, image: &Image, entries: &mut [TagEntry]) -> Vec<Warning> {
+    let tags: Vec<_> = entries.iter().map(|entry| Tag::new(&entry.name)).collect();
+    let (digests, warnings) = client.fetch_digests(image, &tags).await;
+    for (entry, digest) in entries.iter_mut().zip(digests) {
+        entry.digest = Some(digest);
+    }
+    warnings
+}
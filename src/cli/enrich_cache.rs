@@ -0,0 +1,195 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use docker_tags::{Client, CreatedAt, Image, Tag, Warning};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::output::write_atomic;
+
+/// Format of the cache file, which is ignored when it was written by another
+const VERSION: u32 = 1;
+
+/// Creation dates looked up by earlier runs, for `--enrich-cache`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EnrichCache {
+    pub version: u32,
+    pub image: String,
+    /// Digest of the tag names listed when the entries were last checked
+    pub etag: String,
+    pub entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub digest: String,
+    pub created: SystemTime,
+}
+
+/// Tags whose dates came from the cache and those looked up again
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Sources {
+    pub cached: Vec<String>,
+    pub refetched: Vec<String>,
+}
+
+impl Sources {
+    /// `Filled 2 tags from the cache: a, b` and `Refetched 1 tag: c` lines
+    pub fn format(&self) -> Vec<String> {
+        let plural = |n: usize| if n == 1 { "tag" } else { "tags" };
+        let mut lines = vec![match self.cached.is_empty() {
+            true => "Filled 0 tags from the cache".to_string(),
+            false => format!(
+                "Filled {} {} from the cache: {}",
+                self.cached.len(),
+                plural(self.cached.len()),
+                self.cached.join(", ")
+            ),
+        }];
+        lines.push(match self.refetched.is_empty() {
+            true => "Refetched 0 tags".to_string(),
+            false => format!(
+                "Refetched {} {}: {}",
+                self.refetched.len(),
+                plural(self.refetched.len()),
+                self.refetched.join(", ")
+            ),
+        });
+        lines
+    }
+}
+
+impl EnrichCache {
+    fn new(image: &Image, tags: &[Tag]) -> Self {
+        EnrichCache {
+            version: VERSION,
+            image: image.to_string(),
+            etag: etag(tags),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Load the cache of `image`, starting afresh if there's none, it's of
+    /// another image or in another format
+    fn load(path: &Path, image: &Image, tags: &[Tag]) -> Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(EnrichCache::new(image, tags));
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to read {path:?}")),
+        };
+        Ok(serde_json::from_str::<EnrichCache>(&text)
+            .ok()
+            .filter(|cache| cache.version == VERSION && cache.image == image.to_string())
+            .unwrap_or_else(|| EnrichCache::new(image, tags)))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_vec_pretty(self).context("Failed to serialize the cache")?;
+        write_atomic(path, &contents)
+    }
+}
+
+/// Digest of the names in the listing, standing in for an ETag, which
+/// registries rarely send for tag lists
+fn etag(tags: &[Tag]) -> String {
+    let mut hasher = Sha256::new();
+    for tag in tags {
+        hasher.update(tag.name());
+        hasher.update("\n");
+    }
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+/// Creation times of `candidates` like [`Client::fetch_created`], reusing
+/// those cached in `path` and updating it
+///
+/// While `tags`, the whole listing, is unchanged, cached tags aren't looked
+/// up again, so only those which failed or are new are. Otherwise every
+/// candidate's manifest is fetched again, and its config too if its digest
+/// changed. Failed lookups are left out of the cache.
+pub async fn fetch_created(
+    client: &Client,
+    image: &Image,
+    tags: &[Tag],
+    candidates: &[Tag],
+    path: &Path,
+) -> Result<(Vec<(Tag, SystemTime)>, Vec<Warning>, Sources)> {
+    let previous = EnrichCache::load(path, image, tags)?;
+    let unchanged = previous.etag == etag(tags);
+    let (cached, missing): (Vec<_>, Vec<_>) = candidates
+        .iter()
+        .partition(|tag| unchanged && previous.entries.contains_key(tag.name()));
+    let missing: Vec<_> = missing.into_iter().cloned().collect();
+    let known: HashMap<_, _> = previous
+        .entries
+        .values()
+        .map(|entry| (entry.digest.clone(), entry.created))
+        .collect();
+    let (fetched, warnings) = client.fetch_created_digests(image, &missing, &known).await;
+
+    // entries of an older listing only stay once checked again
+    let mut cache = EnrichCache::new(image, tags);
+    if unchanged {
+        cache.entries = previous.entries;
+    }
+    for (tag, CreatedAt { digest, created }) in &fetched {
+        cache.entries.insert(
+            tag.name().to_string(),
+            Entry {
+                digest: digest.clone(),
+                created: *created,
+            },
+        );
+    }
+    cache.save(path)?;
+
+    let created = candidates
+        .iter()
+        .filter_map(|tag| Some((tag.clone(), cache.entries.get(tag.name())?.created)))
+        .collect();
+    let sources = Sources {
+        cached: cached.iter().map(|tag| tag.name().to_string()).collect(),
+        refetched: missing.iter().map(|tag| tag.name().to_string()).collect(),
+    };
+    Ok((created, warnings, sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag() {
+        let tags = |names: &[&str]| names.iter().copied().map(Tag::new).collect::<Vec<_>>();
+        assert_eq!(etag(&tags(&["1.0", "2.0"])), etag(&tags(&["1.0", "2.0"])));
+        assert_ne!(etag(&tags(&["1.0", "2.0"])), etag(&tags(&["1.0"])));
+        // names can't run into each other
+        assert_ne!(etag(&tags(&["1.0", "2.0"])), etag(&tags(&["1.02.0"])));
+    }
+
+    #[test]
+    fn test_sources_format() {
+        let sources = Sources {
+            cached: vec!["1.0".to_string(), "1.1".to_string()],
+            refetched: vec!["2.0".to_string()],
+        };
+        assert_eq!(
+            sources.format(),
+            [
+                "Filled 2 tags from the cache: 1.0, 1.1",
+                "Refetched 1 tag: 2.0"
+            ]
+        );
+        assert_eq!(
+            Sources::default().format(),
+            ["Filled 0 tags from the cache", "Refetched 0 tags"]
+        );
+    }
+}
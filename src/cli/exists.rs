@@ -0,0 +1,32 @@
+use anyhow::Result;
+use docker_tags::ExistsOutcome;
+
+use super::{
+    Registry,
+    output::Output,
+    report::{ExistsReport, Format},
+};
+
+/// Print whether the repository `name` exists and can be read
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    name: &str,
+    format: Format,
+) -> Result<ExistsOutcome> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+
+    let outcome = client.repository_exists(&image).await?;
+    match format {
+        Format::Text => out.line(format!("{image}: {outcome}"))?,
+        format => out.document(
+            format,
+            &ExistsReport {
+                image: image.to_string(),
+                outcome: outcome.to_string(),
+            },
+        )?,
+    }
+    Ok(outcome)
+}
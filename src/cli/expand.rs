@@ -0,0 +1,165 @@
+use anyhow::{Context, Result, bail};
+use docker_tags::{
+    Image, Selection, TagStats,
+    output::{Listing, TagEntry},
+    registries::is_docker_hub,
+};
+use futures_util::{StreamExt, stream};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, GroupedReport, StatsReport},
+    stats::format_stats,
+};
+
+/// Repositories listed at the same time
+const CONCURRENCY: usize = 8;
+
+/// Whether `name` is a pattern of repositories, such as
+/// `harbor.corp/team-*/app`, rather than an image
+pub fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
+/// The repositories of the registry `pattern` names whose path matches it,
+/// by name, no more than `max` of them
+async fn expand(registry: &Registry, pattern: &str, max: usize) -> Result<Vec<Image>> {
+    // the wildcards stand in for characters of a name so that it parses,
+    // leaving the repository part of the pattern at the end
+    let probe = registry.image(&pattern.replace(['*', '?'], "x"))?;
+    if is_docker_hub(probe.registry()) {
+        bail!(
+            "Docker Hub doesn't offer the catalog API, so {pattern} can't be expanded; \
+             patterns need a registry that does, e.g. harbor.corp/team-*/app"
+        );
+    }
+    let path = &pattern[pattern.len() - probe.repository().len()..];
+    let catalog = docker_tags::Registry::connect(probe.registry(), &registry.client(&probe)?)?;
+    let mut names = catalog
+        .match_repositories(path)
+        .await
+        .with_context(|| format!("Failed to expand {pattern} from the catalog"))?;
+    if names.is_empty() {
+        bail!("No repository of {} matches {path}", probe.registry());
+    }
+    if names.len() > max {
+        bail!(
+            "{pattern} matches {} repositories, more than --max-matches {max}; \
+             narrow it down or raise --max-matches",
+            names.len()
+        );
+    }
+    names.sort();
+    names.iter().map(|name| catalog.repository(name)).collect()
+}
+
+/// Print the tags of every repository matching `pattern` through
+/// `selection`, grouped by repository
+pub async fn list(
+    out: &mut Output,
+    registry: &Registry,
+    pattern: &str,
+    max: usize,
+    selection: &Selection,
+    format: Format,
+) -> Result<()> {
+    let images = expand(registry, pattern, max).await?;
+    let results: Vec<_> = stream::iter(&images)
+        .map(|image| async move {
+            let client = registry.client(image)?;
+            client
+                .fetch_tags(image)
+                .await
+                .with_context(|| format!("Failed to list {image}"))
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut report = GroupedReport::new(pattern);
+    for (i, (image, tags)) in images.iter().zip(results).enumerate() {
+        let tags = tags?;
+        let selection = registry.selection_for(image, selection);
+        let total = tags.len();
+        let matched = tags.iter().filter(|tag| selection.matches(tag)).count();
+        let tags = selection.apply(tags);
+        if format != Format::Text {
+            let entries = tags.into_iter().map(TagEntry::from).collect();
+            let mut listing = Listing::new(Some(image.to_string()), entries, &[]);
+            (listing.total, listing.matched) = (total, matched);
+            report.images.push(listing);
+            continue;
+        }
+        if i > 0 {
+            out.line("")?;
+        }
+        out.line(format!("{image}:"))?;
+        for tag in &tags {
+            out.line(tag)?;
+        }
+    }
+    match format {
+        Format::Text => Ok(()),
+        format => out.document(format, &report),
+    }
+}
+
+/// Print an overview of the tags of every repository matching `pattern`,
+/// grouped by repository
+pub async fn stats(
+    out: &mut Output,
+    registry: &Registry,
+    pattern: &str,
+    max: usize,
+    format: Format,
+) -> Result<()> {
+    let images = expand(registry, pattern, max).await?;
+    let results: Vec<_> = stream::iter(&images)
+        .map(|image| async move {
+            let client = registry.client(image)?;
+            let tags = client
+                .fetch_tags(image)
+                .await
+                .with_context(|| format!("Failed to list {image}"))?;
+            anyhow::Ok(TagStats::compute(&tags))
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut report = GroupedReport::new(pattern);
+    for (i, (image, stats)) in images.iter().zip(results).enumerate() {
+        let stats = stats?;
+        if format != Format::Text {
+            report
+                .images
+                .push(StatsReport::new(image.to_string(), &stats));
+            continue;
+        }
+        if i > 0 {
+            out.line("")?;
+        }
+        out.line(format!("{image}:"))?;
+        for line in format_stats(&stats) {
+            out.line(line)?;
+        }
+    }
+    match format {
+        Format::Text => Ok(()),
+        format => out.document(format, &report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(is_pattern("harbor.corp/team-*/app"));
+        assert!(is_pattern("harbor.corp/team-?/app"));
+        assert!(!is_pattern("harbor.corp/team-a/app"));
+        assert!(!is_pattern("nginx"));
+    }
+}
@@ -0,0 +1,144 @@
+use std::{env, ffi::OsString, fs, io, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::output::write_atomic;
+
+/// Most entries kept, older ones are dropped
+const MAX_ENTRIES: usize = 200;
+
+/// Images listed before, most recent first, one per line
+///
+/// Every change replaces the file atomically, so concurrent runs never leave
+/// it half-written, though one of their entries may be lost.
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        History { path: path.into() }
+    }
+
+    /// `$XDG_STATE_HOME/docker-tags/history`, or `~/.local/state/...`
+    pub fn open() -> Option<Self> {
+        state_path("history", env::var_os("XDG_STATE_HOME"), env::home_dir()).map(History::new)
+    }
+
+    /// Entries, most recent first, empty if there's no history yet
+    pub fn entries(&self) -> Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read {:?}", self.path)),
+        }
+    }
+
+    /// Move `image` to the front, adding it if it's new
+    pub fn record(&self, image: &str) -> Result<()> {
+        let mut entries = self.entries()?;
+        entries.retain(|entry| entry != image);
+        entries.insert(0, image.to_string());
+        entries.truncate(MAX_ENTRIES);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {dir:?}"))?;
+        }
+        write_atomic(&self.path, format!("{}\n", entries.join("\n")).as_bytes())
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Failed to remove {:?}", self.path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Entries starting with `prefix`, Docker Hub ones also without their
+    /// `docker.io/` (and then completed that way)
+    pub fn complete(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter_map(|entry| match entry.strip_prefix("docker.io/") {
+                _ if entry.starts_with(prefix) => Some(entry),
+                Some(name) if name.starts_with(prefix) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// `$XDG_STATE_HOME/docker-tags/<name>`, or `~/.local/state/...`
+pub fn state_path(
+    name: &str,
+    state_home: Option<OsString>,
+    home: Option<PathBuf>,
+) -> Option<PathBuf> {
+    let dir = match state_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        Some(dir) => dir,
+        None => home?.join(".local").join("state"),
+    };
+    Some(dir.join("docker-tags").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_path() {
+        assert_eq!(
+            state_path("history", Some("/state".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/state/docker-tags/history"))
+        );
+        // relative ones are to be ignored by the XDG spec
+        assert_eq!(
+            state_path("history", Some("state".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.local/state/docker-tags/history"))
+        );
+        assert_eq!(state_path("history", None, None), None);
+    }
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(dir.path().join("docker-tags").join("history"));
+        assert!(history.entries().unwrap().is_empty());
+
+        for image in [
+            "nginx",
+            "ghcr.io/org/app",
+            "nginx",
+            "docker.io/prom/prometheus",
+        ] {
+            history.record(image).unwrap();
+        }
+        assert_eq!(
+            history.entries().unwrap(),
+            ["docker.io/prom/prometheus", "nginx", "ghcr.io/org/app"]
+        );
+        assert_eq!(history.complete("pro").unwrap(), ["prom/prometheus"]);
+        assert_eq!(history.complete("").unwrap().len(), 3);
+
+        for i in 0..MAX_ENTRIES {
+            history.record(&format!("app{i}")).unwrap();
+        }
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0], format!("app{}", MAX_ENTRIES - 1));
+
+        history.clear().unwrap();
+        history.clear().unwrap();
+        assert!(history.entries().unwrap().is_empty());
+    }
+}
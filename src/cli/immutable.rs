@@ -0,0 +1,12 @@
+use docker_tags::{Client, Image, Tag, Warning, output::TagEntry};
+
+/// Look up whether the tags of `entries` can be overwritten, where the
+/// registry tells
+pub async fn enrich(client: &Client, image: &Image, entries: &mut [TagEntry]) -> Vec<Warning> {
+    let tags: Vec<_> = entries.iter().map(|entry| Tag::new(&entry.name)).collect();
+    let (immutable, warnings) = client.fetch_immutable(image, &tags).await;
+    for (entry, immutable) in entries.iter_mut().zip(immutable) {
+        entry.immutable = Some(immutable.immutable);
+    }
+    warnings
+}
@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use docker_tags::{
+    FetchManyOptions, Selection, Tag, intersect_tags,
+    output::{Listing, TagEntry},
+    union_tags,
+};
+use futures_util::{StreamExt, TryStreamExt, stream};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, PresenceRow},
+};
+
+/// Print the tags picked by `selection` among those every image of `names`
+/// has, or with `union` those any of them has along with which; returns
+/// whether some tag is in all of them
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    names: &[String],
+    union: bool,
+    selection: &Selection,
+    format: Format,
+) -> Result<bool> {
+    let listings: Vec<Vec<Tag>> = stream::iter(names)
+        .map(|name| async move {
+            let image = registry.image(name)?;
+            let mut tags = registry
+                .client(&image)?
+                .fetch_tags(&image)
+                .await
+                .with_context(|| format!("Failed to list the tags of {name}"))?;
+            // a tag ignored for one image isn't shared by it
+            let unignored = registry.selection_for(&image, &Selection::default());
+            tags.retain(|tag| unignored.matches(tag));
+            Ok::<_, anyhow::Error>(tags)
+        })
+        .buffered(FetchManyOptions::default().concurrency)
+        .try_collect()
+        .await?;
+
+    if !union {
+        let common = intersect_tags(listings);
+        let total = common.len();
+        let matched = common.iter().filter(|tag| selection.matches(tag)).count();
+        let tags = selection.apply(common);
+        match format {
+            Format::Text => {
+                for tag in &tags {
+                    out.line(tag)?;
+                }
+            }
+            format => {
+                let entries = tags.into_iter().map(TagEntry::from).collect();
+                let mut listing = Listing::new(None, entries, &[]);
+                (listing.total, listing.matched) = (total, matched);
+                out.document(format, &listing)?;
+            }
+        }
+        return Ok(total > 0);
+    }
+
+    let mut presence: HashMap<_, _> = union_tags(listings).into_iter().collect();
+    let common = presence.values().any(|present| present.iter().all(|&p| p));
+    let tags = selection.apply(presence.keys().cloned().collect());
+    let rows: Vec<_> = tags
+        .into_iter()
+        .map(|tag| {
+            let present = presence.remove(&tag).expect("listed in the union");
+            PresenceRow {
+                tag: tag.name().to_string(),
+                images: names
+                    .iter()
+                    .cloned()
+                    .zip(present)
+                    .collect::<BTreeMap<_, _>>(),
+            }
+        })
+        .collect();
+    match format {
+        Format::Text => {
+            out.line(format!("TAG\t{}", names.join("\t")))?;
+            for row in &rows {
+                out.line(format_row(row, names))?;
+            }
+        }
+        format => out.document(format, &rows)?,
+    }
+    Ok(common)
+}
+
+/// Tab-separated `1.2.0 ✓ ✗` cells, in the order of `names`
+fn format_row(row: &PresenceRow, names: &[String]) -> String {
+    let cells = names.iter().map(|name| match row.images.get(name) {
+        Some(true) => "✓",
+        _ => "✗",
+    });
+    [row.tag.as_str()]
+        .into_iter()
+        .chain(cells)
+        .collect::<Vec<_>>()
+        .join("\t")
+}
@@ -0,0 +1,539 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use docker_tags::{registries::is_docker_hub, unstable::Reference};
+use regex::Regex;
+use serde::Serialize;
+
+use super::{
+    output::Output,
+    report::{Format, LintReport},
+};
+
+/// How much a finding matters: only errors fail the run
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// What the rules may take into account besides the reference
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Registry mirroring Docker Hub, which references should pull from
+    pub mirror: Option<String>,
+}
+
+/// A check of a reference, telling what's wrong with it if anything
+pub struct Rule {
+    pub name: &'static str,
+    pub severity: Severity,
+    /// Whether the rule runs unless disabled, or only once enabled
+    pub default: bool,
+    check: fn(&Reference, &Options) -> Option<String>,
+}
+
+/// Name of the finding a reference which can't be parsed gets, whatever the
+/// rules enabled
+const INVALID: &str = "invalid";
+
+pub static RULES: [Rule; 8] = [
+    Rule {
+        name: "missing-tag",
+        severity: Severity::Error,
+        default: true,
+        check: |reference, _| {
+            (reference.tag().is_none() && reference.digest().is_none())
+                .then(|| "no tag, so it pulls whatever `latest` is".to_string())
+        },
+    },
+    Rule {
+        name: "latest",
+        severity: Severity::Warning,
+        default: true,
+        check: |reference, _| {
+            (reference.tag()?.name() == "latest" && reference.digest().is_none())
+                .then(|| "`latest` moves with every push; name a version".to_string())
+        },
+    },
+    Rule {
+        name: "uppercase",
+        severity: Severity::Error,
+        default: true,
+        check: |reference, _| {
+            let repository = reference.image().repository();
+            repository
+                .contains(|c: char| c.is_ascii_uppercase())
+                .then(|| format!("repository names are lowercase, `{repository}` won't pull"))
+        },
+    },
+    Rule {
+        name: "deprecated-registry",
+        severity: Severity::Error,
+        default: true,
+        check: |reference, _| match reference.image().registry() {
+            "k8s.gcr.io" => Some("k8s.gcr.io is frozen, pull from registry.k8s.io".to_string()),
+            registry if registry == "gcr.io" || registry.ends_with(".gcr.io") => Some(format!(
+                "{registry} is Container Registry, which is shut down; move to Artifact Registry (pkg.dev)"
+            )),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "docker-hub",
+        severity: Severity::Warning,
+        default: true,
+        check: |reference, options| {
+            let mirror = options.mirror.as_deref()?;
+            is_docker_hub(reference.image().registry())
+                .then(|| format!("pulls from Docker Hub rather than the mirror {mirror}"))
+        },
+    },
+    Rule {
+        name: "digest-only",
+        severity: Severity::Warning,
+        default: true,
+        check: |reference, _| {
+            (reference.tag().is_none() && reference.digest().is_some())
+                .then(|| "pinned to a digest without the tag it stands for".to_string())
+        },
+    },
+    Rule {
+        name: "unpinned",
+        severity: Severity::Warning,
+        default: false,
+        check: |reference, _| {
+            (reference.tag().is_some() && reference.digest().is_none())
+                .then(|| "not pinned to a digest (see `docker-tags pin`)".to_string())
+        },
+    },
+    Rule {
+        name: "floating-version",
+        severity: Severity::Warning,
+        default: false,
+        check: |reference, _| {
+            let tag = reference.tag()?.name();
+            let version = tag.split('-').next()?;
+            let version = version.strip_prefix('v').unwrap_or(version);
+            let parts: Vec<_> = version.split('.').collect();
+            let numeric =
+                |part: &&str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+            let moves = match parts.len() {
+                1 => "minor",
+                2 => "patch",
+                _ => return None,
+            };
+            parts
+                .iter()
+                .all(numeric)
+                .then(|| format!("`{tag}` moves with each {moves} release"))
+        },
+    },
+];
+
+/// The rules `enable`d and not `disable`d, the default ones enabled
+pub fn rules(enable: &[String], disable: &[String]) -> Vec<&'static Rule> {
+    let named = |names: &[String], rule: &Rule| names.iter().any(|name| name == rule.name);
+    RULES
+        .iter()
+        .filter(|rule| rule.default || named(enable, rule))
+        .filter(|rule| !named(disable, rule))
+        .collect()
+}
+
+/// The rules to check the references `pattern` finds against
+pub struct Linter {
+    pub pattern: Regex,
+    pub rules: Vec<&'static Rule>,
+    pub options: Options,
+}
+
+impl Linter {
+    /// Findings of the rules for the references of `text`, read from `file`
+    fn lint(&self, file: &Path, text: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for spelled in scan(text, &self.pattern) {
+            let finding = |rule, severity, message| Finding {
+                file: file.to_path_buf(),
+                line: spelled.line,
+                column: spelled.column,
+                reference: spelled.text.to_string(),
+                rule,
+                severity,
+                message,
+            };
+            let reference = match Reference::try_from(spelled.text) {
+                Ok(reference) => reference,
+                Err(err) => {
+                    findings.push(finding(INVALID, Severity::Error, err.to_string()));
+                    continue;
+                }
+            };
+            for rule in &self.rules {
+                if let Some(message) = (rule.check)(&reference, &self.options) {
+                    findings.push(finding(rule.name, rule.severity, message));
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Something a rule found wrong with a reference
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Finding {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub reference: String,
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    /// A GitHub Actions workflow command, which annotates the line of the
+    /// pull request
+    fn annotation(&self) -> String {
+        format!(
+            "::{} file={},line={},col={},title={}::{}",
+            self.severity.as_str(),
+            escape_property(&self.file.display().to_string()),
+            self.line,
+            self.column,
+            self.rule,
+            escape_data(&format!("{}: {}", self.reference, self.message)),
+        )
+    }
+}
+
+/// A reference as it's spelled in a file, with its 1-based position
+#[derive(Debug, Eq, PartialEq)]
+struct Spelled<'a> {
+    line: usize,
+    column: usize,
+    text: &'a str,
+}
+
+/// Lint the references in `path`, or in the Dockerfiles, compose files and
+/// manifests under it; returns whether there were errors
+pub fn run(
+    out: &mut Output,
+    path: &Path,
+    linter: &Linter,
+    format: Format,
+    github: bool,
+    quiet: bool,
+) -> Result<bool> {
+    let files = match path.is_dir() {
+        true => files(path)?,
+        false => vec![path.to_path_buf()],
+    };
+    let mut findings = Vec::new();
+    for file in files {
+        let text = fs::read_to_string(&file).with_context(|| format!("Failed to read {file:?}"))?;
+        findings.extend(linter.lint(&file, &text));
+    }
+    let count = |severity| {
+        findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    };
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+
+    match format {
+        Format::Text if github => {
+            for finding in &findings {
+                out.line(finding.annotation())?;
+            }
+        }
+        Format::Text => {
+            for line in format_findings(&findings) {
+                out.line(line)?;
+            }
+            if !quiet && !findings.is_empty() {
+                eprintln!("{errors} errors, {warnings} warnings");
+            }
+        }
+        format => out.document(
+            format,
+            &LintReport {
+                findings,
+                errors,
+                warnings,
+            },
+        )?,
+    }
+    Ok(errors > 0)
+}
+
+/// References matched by `pattern`'s `ref` group, or else the whole match,
+/// tagged or not; build stages, `scratch` and references built from
+/// variables are skipped
+fn scan<'a>(text: &'a str, pattern: &Regex) -> Vec<Spelled<'a>> {
+    let stage = Regex::new(r"(?im)^[ \t]*from[ \t]+\S+[ \t]+as[ \t]+([\w.-]+)").unwrap();
+    let stages: Vec<_> = stage
+        .captures_iter(text)
+        .map(|captures| captures[1].to_lowercase())
+        .collect();
+    pattern
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let matched = captures.name("ref").or_else(|| captures.get(0))?;
+            let name = matched.as_str().to_lowercase();
+            if name == "scratch"
+                || stages.contains(&name)
+                || text[matched.end()..].starts_with(['$', '{'])
+            {
+                return None;
+            }
+            let before = &text[..matched.start()];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            Some(Spelled {
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+                text: matched.as_str(),
+            })
+        })
+        .collect()
+}
+
+/// Dockerfiles, compose files and Kubernetes manifests under `dir`, sorted,
+/// hidden files and directories left out
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).with_context(|| format!("Failed to read {dir:?}"))?;
+        for entry in entries {
+            let path = entry
+                .with_context(|| format!("Failed to read {dir:?}"))?
+                .path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_lintable(&name) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether a file named `name` is a Dockerfile or YAML
+fn is_lintable(name: &str) -> bool {
+    name.starts_with("Dockerfile")
+        || name.starts_with("Containerfile")
+        || [".dockerfile", ".yml", ".yaml"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+}
+
+/// Table of the findings, columns padded to the longest entry
+fn format_findings(findings: &[Finding]) -> Vec<String> {
+    if findings.is_empty() {
+        return Vec::new();
+    }
+    let rows: Vec<[String; 5]> = findings
+        .iter()
+        .map(|finding| {
+            [
+                format!(
+                    "{}:{}:{}",
+                    finding.file.display(),
+                    finding.line,
+                    finding.column
+                ),
+                finding.severity.as_str().to_string(),
+                finding.rule.to_string(),
+                finding.reference.clone(),
+                finding.message.clone(),
+            ]
+        })
+        .collect();
+    let header = ["LOCATION", "SEVERITY", "RULE", "REFERENCE", "MESSAGE"].map(str::to_string);
+    let widths: Vec<_> = (0..4)
+        .map(|i| {
+            rows.iter()
+                .chain([&header])
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    [&header]
+        .into_iter()
+        .chain(&rows)
+        .map(|row| {
+            format!(
+                "{:w0$}  {:w1$}  {:w2$}  {:w3$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3]
+            )
+        })
+        .collect()
+}
+
+/// The message of a workflow command, its percent signs and line breaks
+/// escaped
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A property of a workflow command, which also escapes `:` and `,`
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::pin::DEFAULT_PATTERN;
+
+    /// The rules `reference` breaks, all of them enabled
+    fn broken(reference: &str) -> Vec<&'static str> {
+        let options = Options {
+            mirror: Some("mirror.corp".to_string()),
+        };
+        let reference = Reference::try_from(reference).unwrap();
+        RULES
+            .iter()
+            .filter(|rule| (rule.check)(&reference, &options).is_some())
+            .map(|rule| rule.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let digest = "sha256:".to_string() + &"a".repeat(64);
+        assert_eq!(broken("mirror.corp/nginx:1.27.3"), ["unpinned"]);
+        assert_eq!(
+            broken(&format!("mirror.corp/nginx:1.27.3@{digest}")),
+            Vec::<&str>::new()
+        );
+        assert_eq!(broken("mirror.corp/nginx"), ["missing-tag"]);
+        assert_eq!(broken("mirror.corp/nginx:latest"), ["latest", "unpinned"]);
+        assert_eq!(
+            broken(&format!("mirror.corp/nginx:latest@{digest}")),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            broken("mirror.corp/Org/app:1.0.0"),
+            ["uppercase", "unpinned"]
+        );
+        assert_eq!(
+            broken("k8s.gcr.io/pause:3.9"),
+            ["deprecated-registry", "unpinned", "floating-version"]
+        );
+        assert_eq!(
+            broken("eu.gcr.io/org/app:1.0.0"),
+            ["deprecated-registry", "unpinned"]
+        );
+        assert_eq!(
+            broken(&format!("nginx@{digest}")),
+            ["docker-hub", "digest-only"]
+        );
+        assert_eq!(
+            broken("mirror.corp/node:20-alpine"),
+            ["unpinned", "floating-version"]
+        );
+        assert_eq!(
+            broken("mirror.corp/node:v20.1"),
+            ["unpinned", "floating-version"]
+        );
+        assert_eq!(broken("mirror.corp/node:alpine"), ["unpinned"]);
+
+        // Docker Hub is fine without a mirror to use instead
+        let reference = Reference::try_from("nginx:1.27.3").unwrap();
+        assert_eq!((RULES[4].check)(&reference, &Options::default()), None);
+    }
+
+    #[test]
+    fn test_enabled_rules() {
+        let names = |rules: Vec<&Rule>| -> Vec<_> { rules.iter().map(|rule| rule.name).collect() };
+        let defaults = names(rules(&[], &[]));
+        assert!(!defaults.contains(&"unpinned"), "{defaults:?}");
+        assert!(defaults.contains(&"latest"), "{defaults:?}");
+        let chosen = names(rules(
+            &["unpinned".to_string()],
+            &["latest".to_string(), "uppercase".to_string()],
+        ));
+        assert!(chosen.contains(&"unpinned"), "{chosen:?}");
+        assert!(!chosen.contains(&"latest") && !chosen.contains(&"uppercase"));
+    }
+
+    #[test]
+    fn test_scan() {
+        let text = "FROM golang:1.23 AS Build\n\
+                    FROM --platform=$BUILDPLATFORM build\n\
+                    FROM scratch\n\
+                    FROM base-${VARIANT}\n\
+                    FROM alpine\n\
+                    \x20 image: \"redis:7\"\n";
+        let pattern = Regex::new(DEFAULT_PATTERN).unwrap();
+        assert_eq!(
+            scan(text, &pattern),
+            [
+                Spelled {
+                    line: 1,
+                    column: 6,
+                    text: "golang:1.23"
+                },
+                Spelled {
+                    line: 5,
+                    column: 6,
+                    text: "alpine"
+                },
+                Spelled {
+                    line: 6,
+                    column: 11,
+                    text: "redis:7"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_annotation() {
+        let finding = Finding {
+            file: PathBuf::from("deploy/a,b.yaml"),
+            line: 3,
+            column: 12,
+            reference: "nginx".to_string(),
+            rule: "missing-tag",
+            severity: Severity::Error,
+            message: "100% wrong\nreally".to_string(),
+        };
+        assert_eq!(
+            finding.annotation(),
+            "::error file=deploy/a%2Cb.yaml,line=3,col=12,title=missing-tag::nginx: 100%25 wrong%0Areally"
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::Result;
+use docker_tags::{
+    Client, FetchOutcome, Image, Selection, Tag, Warning,
+    output::{Listing, NoticeEntry, Pipeline, Sort, TagEntry, TimingsReport},
+    sort_tags_desc,
+};
+
+use super::{
+    Registry,
+    state::{Lock, State},
+};
+
+/// How the tags of one image are listed, as the command line asks
+pub struct Plan<'a> {
+    /// Which tags to list, and the same without the ignored ones left out
+    pub selection: &'a Selection,
+    pub unignored: &'a Selection,
+    pub strategy: Strategy<'a>,
+    /// State file recording when each tag was first seen
+    pub state: Option<&'a Path>,
+    /// What to look up about the tags shown
+    pub digests: bool,
+    pub source: bool,
+    pub immutable: bool,
+    /// Also tell which tags the ignore list left out
+    pub show_ignored: bool,
+    /// List what was fetched before pagination failed
+    pub allow_partial: bool,
+    pub quiet: bool,
+    pub timings: bool,
+}
+
+/// How the tags shown are chosen among those fetched
+pub enum Strategy<'a> {
+    /// Ordered by the [`Pipeline`], as by version, the registry or series
+    Sorted(Sort),
+    /// By when the state file first saw them
+    FirstSeen,
+    /// The N tags sorting first, no more kept while listing
+    Top(usize),
+    /// Those created after a tag, newest first, the dates kept in `cache`
+    AddedAfter {
+        reference: &'a str,
+        cache: Option<&'a Path>,
+        retry_failed: bool,
+    },
+    /// With their signatures looked up, only the signed ones if `only`
+    Signed { only: bool },
+}
+
+/// How the tags are fetched, the cheapest way the plan allows
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Fetch {
+    /// Keeping only the N tags sorting first
+    Top(usize),
+    /// In registry order, stopping once the limit matched
+    UntilLimit,
+    /// Through the registry's search API for the pattern, if it has one
+    Matching,
+    /// Dropping the tags the selection (without the ignore list, to tell
+    /// which tags it left out) doesn't keep
+    Filtered,
+    /// Every tag
+    All,
+}
+
+/// A listing of one image, before it's written
+pub struct Listed {
+    pub listing: Listing,
+    /// Tags matching the selection, whatever the strategy and the limit
+    pub matching: Vec<Tag>,
+}
+
+impl Plan<'_> {
+    /// How to fetch the tags for this plan
+    pub fn fetch(&self) -> Fetch {
+        let selection = self.selection;
+        let signed = matches!(self.strategy, Strategy::Signed { .. });
+        let added_after = matches!(self.strategy, Strategy::AddedAfter { .. });
+        // signatures are tags of their own and a state records every tag
+        let whole = signed || self.state.is_some();
+        let pins_aliases = matches!(selection.aliases, docker_tags::Aliases::Pinned(_));
+        if let Strategy::Top(n) = self.strategy {
+            return Fetch::Top(n);
+        }
+        // in registry order the filter and the limit can stop pagination
+        // early, unless the order is reversed and the last page comes first
+        if selection.keep_order
+            && !selection.reverse
+            && !whole
+            && !self.show_ignored
+            && !pins_aliases
+        {
+            return Fetch::UntilLimit;
+        }
+        if selection.pattern.is_some() && !whole && !added_after {
+            return Fetch::Matching;
+        }
+        if selection.is_filtered() && !whole && !added_after {
+            return Fetch::Filtered;
+        }
+        Fetch::All
+    }
+
+    /// Tags of `tags` which only the ignore list left out, newest first
+    pub fn ignored(&self, tags: &[Tag]) -> Vec<String> {
+        if !self.show_ignored {
+            return Vec::new();
+        }
+        let mut ignored: Vec<_> = tags
+            .iter()
+            .filter(|tag| self.unignored.matches(tag) && !self.selection.matches(tag))
+            .cloned()
+            .collect();
+        sort_tags_desc(&mut ignored);
+        ignored.iter().map(Tag::to_string).collect()
+    }
+}
+
+/// Fetch the tags of `image` as `plan` has it
+pub async fn fetch(
+    client: &Client,
+    image: &Image,
+    plan: &Plan<'_>,
+) -> Result<(Vec<Tag>, FetchOutcome)> {
+    let selection = plan.selection;
+    match plan.fetch() {
+        Fetch::Top(n) => {
+            let filter = selection.clone();
+            client
+                .fetch_tags_top(
+                    image,
+                    move |tag: &Tag| filter.matches(tag),
+                    n,
+                    selection.reverse,
+                )
+                .await
+        }
+        Fetch::UntilLimit => {
+            let filter = selection.clone();
+            client
+                .fetch_tags_filtered(image, move |tag: &Tag| filter.matches(tag), selection.limit)
+                .await
+        }
+        Fetch::Matching => {
+            let pattern = selection
+                .pattern
+                .as_ref()
+                .expect("matching without a pattern");
+            client.fetch_tags_matching(image, pattern).await
+        }
+        Fetch::Filtered => {
+            // tags the filters drop are never kept, the ignored ones listed
+            // along with the others to tell which of them the registry has
+            let filter = match plan.show_ignored {
+                true => plan.unignored.clone(),
+                false => selection.clone(),
+            };
+            client
+                .fetch_tags_filtered(image, move |tag: &Tag| filter.matches(tag), None)
+                .await
+        }
+        Fetch::All => client.fetch_tags_partial(image).await,
+    }
+}
+
+/// List the tags of `image` as `plan` has it: fetch them, record them in
+/// the state file, choose those shown, then look up what's asked about them
+pub async fn list(
+    registry: &Registry,
+    client: &Client,
+    image: &Image,
+    plan: &Plan<'_>,
+) -> Result<Listed> {
+    let selection = plan.selection;
+    let (tags, outcome) = fetch(client, image, plan).await?;
+    let complete = outcome.is_complete();
+    if !plan.quiet {
+        for warning in &outcome.warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    registry.warn_notices(image, &outcome.notices);
+    if let Some(err) = outcome.error {
+        if !plan.allow_partial {
+            return Err(err);
+        }
+        eprintln!(
+            "Warning: listing is incomplete, stopped after {} pages: {err:#}",
+            outcome.pages
+        );
+    }
+    let mut warnings = outcome.warnings;
+    let ignored = plan.ignored(&tags);
+    let matching: Vec<_> = tags
+        .iter()
+        .filter(|tag| selection.matches(tag))
+        .cloned()
+        .collect();
+    let matched = match plan.strategy {
+        // the tags dropped on the way matched too
+        Strategy::Top(_) => outcome.matched,
+        _ => matching.len(),
+    };
+    let state = match plan.state {
+        Some(path) => {
+            let _lock = Lock::acquire(path)?;
+            let previous = State::load_for(path, &image.to_string())?;
+            let state = State::new(image.to_string(), &tags, previous.as_ref());
+            // tags missing from an incomplete listing would lose their times
+            if complete {
+                state.save(path)?;
+            }
+            Some(state)
+        }
+        None => None,
+    };
+
+    let (selecting, fetched) = (Instant::now(), client.timings());
+    let (aliases, tags) = selection.split_aliases(tags);
+    let mut entries = match &plan.strategy {
+        Strategy::AddedAfter {
+            reference,
+            cache,
+            retry_failed,
+        } => {
+            // the order is by date, so every candidate's date is needed first
+            if !plan.quiet && selection.limit.is_some() {
+                eprintln!(
+                    "Warning: --added-after-tag looks up the date of every matching tag, --limit only applies afterwards"
+                );
+            }
+            let (entries, dates, sources) =
+                super::added_after::select(client, image, &tags, reference, selection, *cache)
+                    .await?;
+            if !plan.quiet {
+                for warning in &dates {
+                    eprintln!("Warning: {warning}");
+                }
+            }
+            for line in sources
+                .iter()
+                .filter(|_| *retry_failed)
+                .flat_map(|s| s.format())
+            {
+                eprintln!("{line}");
+            }
+            warnings.extend(dates);
+            entries
+        }
+        Strategy::Signed { only } => {
+            super::signed::select(client, image, &tags, selection, *only).await?
+        }
+        Strategy::Sorted(sort) => Pipeline::new(selection, sort.clone()).entries(tags),
+        Strategy::Top(_) => Pipeline::new(selection, Sort::Version).entries(tags),
+        Strategy::FirstSeen => {
+            let state = state.as_ref().expect("--sort first-seen requires --state");
+            Pipeline::new(selection, Sort::FirstSeen(state.first_seen())).entries(tags)
+        }
+    };
+    let pinned = aliases.len();
+    entries.splice(0..0, aliases.into_iter().map(TagEntry::from));
+    // the entries are final: filtered, sorted and limited, so only the tags
+    // printed cost a request each
+    warnings.extend(enrich(client, image, &mut entries, plan).await);
+
+    let timings = client.timings();
+    // time waited on manifests and configs isn't spent sorting
+    let sorting = selecting
+        .elapsed()
+        .saturating_sub(timings.enrichment.saturating_sub(fetched.enrichment));
+    let tags = entries.split_off(pinned);
+    let mut listing = Listing::new(Some(image.to_string()), tags, &warnings);
+    (listing.total, listing.matched) = (outcome.listed, matched);
+    listing.aliases = entries;
+    listing.notices = outcome.notices.iter().map(NoticeEntry::from).collect();
+    listing.complete = complete;
+    listing.timings = Some(TimingsReport::new(&timings, sorting)).filter(|_| plan.timings);
+    listing.ignored = ignored;
+    Ok(Listed { listing, matching })
+}
+
+/// Look up the digests, sources and immutability of `entries` as `plan`
+/// asks, warning about what couldn't be unless quiet
+async fn enrich(
+    client: &Client,
+    image: &Image,
+    entries: &mut [TagEntry],
+    plan: &Plan<'_>,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if plan.digests {
+        warnings.extend(super::digests::enrich(client, image, entries).await);
+    }
+    if plan.source {
+        warnings.extend(super::source::enrich(client, image, entries).await);
+    }
+    if plan.immutable {
+        warnings.extend(super::immutable::enrich(client, image, entries).await);
+    }
+    if !plan.quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use docker_tags::{Aliases, IgnoreList, TagFilter};
+
+    use super::*;
+
+    fn plan<'a>(selection: &'a Selection, strategy: Strategy<'a>) -> Plan<'a> {
+        Plan {
+            selection,
+            unignored: selection,
+            strategy,
+            state: None,
+            digests: false,
+            source: false,
+            immutable: false,
+            show_ignored: false,
+            allow_partial: false,
+            quiet: true,
+            timings: false,
+        }
+    }
+
+    #[test]
+    fn test_fetch() {
+        let sorted = || Strategy::Sorted(Sort::Version);
+        let all = Selection::default();
+        assert_eq!(plan(&all, sorted()).fetch(), Fetch::All);
+        assert_eq!(plan(&all, Strategy::Top(5)).fetch(), Fetch::Top(5));
+
+        let filtered = Selection {
+            filters: vec![TagFilter::stable()],
+            ..Selection::default()
+        };
+        assert_eq!(plan(&filtered, sorted()).fetch(), Fetch::Filtered);
+        assert_eq!(
+            plan(&filtered, Strategy::Signed { only: false }).fetch(),
+            Fetch::All
+        );
+        let added_after = Strategy::AddedAfter {
+            reference: "1.0",
+            cache: None,
+            retry_failed: false,
+        };
+        assert_eq!(plan(&filtered, added_after).fetch(), Fetch::All);
+        let mut with_state = plan(&filtered, sorted());
+        with_state.state = Some(Path::new("state.json"));
+        assert_eq!(with_state.fetch(), Fetch::All);
+
+        let pattern = Selection {
+            pattern: Some(regex::Regex::new("^1").unwrap()),
+            ..Selection::default()
+        };
+        assert_eq!(plan(&pattern, sorted()).fetch(), Fetch::Matching);
+
+        let registry_order = Selection {
+            keep_order: true,
+            limit: Some(3),
+            ..Selection::default()
+        };
+        assert_eq!(plan(&registry_order, sorted()).fetch(), Fetch::UntilLimit);
+        let reversed = Selection {
+            reverse: true,
+            ..registry_order.clone()
+        };
+        assert_eq!(plan(&reversed, sorted()).fetch(), Fetch::All);
+        let pinned = Selection {
+            aliases: Aliases::pinned(),
+            ..registry_order
+        };
+        assert_eq!(plan(&pinned, sorted()).fetch(), Fetch::All);
+    }
+
+    #[test]
+    fn test_ignored() {
+        let image = Image::new("docker.io", "library/nginx");
+        let ignore = IgnoreList::parse("1.27.*\n!1.27.3\n").unwrap();
+        let unignored = Selection {
+            filters: vec![TagFilter::stable()],
+            ..Selection::default()
+        };
+        let mut selection = unignored.clone();
+        selection
+            .filters
+            .push(TagFilter::not_ignored(&ignore, &image));
+        let tags = ["1.26.0", "1.27.1", "1.27.2", "1.27.3", "1.28.0-rc1"].map(Tag::new);
+
+        let mut plan = Plan {
+            unignored: &unignored,
+            ..plan(&selection, Strategy::Sorted(Sort::Version))
+        };
+        assert!(plan.ignored(&tags).is_empty());
+        plan.show_ignored = true;
+        // pre-releases are left out by the filter rather than the ignore list
+        assert_eq!(plan.ignored(&tags), ["1.27.2", "1.27.1"]);
+        assert_eq!(plan.fetch(), Fetch::Filtered);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    env, fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use docker_tags::{Image, Tag};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{history::state_path, output::write_atomic};
+
+/// Format of the files, which are ignored when written by another
+const VERSION: u32 = 1;
+
+/// Tag listings of `serve`, one file per image, so that a restarted daemon
+/// reuses those still within its TTL instead of asking the registries again
+#[derive(Debug)]
+pub struct ListingCache {
+    dir: PathBuf,
+    /// List every image again, only recording the listings
+    refresh: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    version: u32,
+    image: String,
+    listed_at: SystemTime,
+    tags: Vec<String>,
+}
+
+impl ListingCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ListingCache {
+            dir: dir.into(),
+            refresh: false,
+        }
+    }
+
+    /// Let [`ListingCache::load`] find nothing, while listings are still
+    /// recorded
+    pub fn refresh(self, refresh: bool) -> Self {
+        ListingCache { refresh, ..self }
+    }
+
+    /// `$XDG_STATE_HOME/docker-tags/listings`, or `~/.local/state/...`
+    pub fn open() -> Option<Self> {
+        state_path("listings", env::var_os("XDG_STATE_HOME"), env::home_dir())
+            .map(ListingCache::new)
+    }
+
+    /// Tags of `image` listed less than `ttl` ago and how long ago that was,
+    /// `None` if there's no such listing or it can't be read, since it only
+    /// saves requests
+    pub fn load(&self, image: &Image, ttl: Duration) -> Option<(Vec<Tag>, Duration)> {
+        if self.refresh {
+            return None;
+        }
+        let text = fs::read_to_string(self.path(image)).ok()?;
+        let entry = serde_json::from_str::<Entry>(&text)
+            .ok()
+            .filter(|entry| entry.version == VERSION && entry.image == image.to_string())?;
+        let age = SystemTime::now()
+            .duration_since(entry.listed_at)
+            .unwrap_or_default();
+        let tags = entry.tags.into_iter().map(Tag::new).collect();
+        (age < ttl).then_some((tags, age))
+    }
+
+    /// Record `tags` as just listed for `image`
+    pub fn store(&self, image: &Image, tags: &[Tag]) -> Result<()> {
+        let entry = Entry {
+            version: VERSION,
+            image: image.to_string(),
+            listed_at: SystemTime::now(),
+            tags: tags.iter().map(|tag| tag.name().to_string()).collect(),
+        };
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {:?}", self.dir))?;
+        let contents = serde_json::to_vec(&entry).context("Failed to serialize the listing")?;
+        write_atomic(&self.path(image), &contents)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Failed to remove {:?}", self.dir))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// File named by a digest of the image, which may hold any character
+    fn path(&self, image: &Image) -> PathBuf {
+        let digest = Sha256::digest(image.to_string());
+        self.dir.join(format!("{digest:x}.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ListingCache::new(dir.path().join("listings"));
+        let image = Image::new("ghcr.io", "org/app");
+        let ttl = Duration::from_secs(60);
+        assert!(cache.load(&image, ttl).is_none());
+
+        cache
+            .store(&image, &[Tag::new("1.1.0"), Tag::new("1.0.0")])
+            .unwrap();
+        let (tags, age) = cache.load(&image, ttl).unwrap();
+        assert_eq!(tags, [Tag::new("1.1.0"), Tag::new("1.0.0")]);
+        assert!(age < ttl);
+        assert!(cache.load(&image, Duration::ZERO).is_none());
+        let refreshing = ListingCache::new(dir.path().join("listings")).refresh(true);
+        assert!(refreshing.load(&image, ttl).is_none());
+        assert!(
+            cache
+                .load(&Image::new("ghcr.io", "org/other"), ttl)
+                .is_none()
+        );
+
+        cache.clear().unwrap();
+        assert!(cache.load(&image, ttl).is_none());
+        cache.clear().unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use docker_tags::{Platform, Selection, Tag};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, MatrixRow},
+};
+
+/// Print which of `platforms` each tag picked by `selection` can be pulled for
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    name: &str,
+    platforms: &[String],
+    selection: &Selection,
+    format: Format,
+    quiet: bool,
+) -> Result<()> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+    let selection = registry.selection_for(&image, selection);
+    let tags = selection.apply(client.fetch_tags(&image).await?);
+
+    let (found, warnings) = client.fetch_platforms(&image, &tags).await;
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    let rows: Vec<_> = tags
+        .iter()
+        .zip(&found)
+        .map(|(tag, found)| row(tag, platforms, found.as_deref()))
+        .collect();
+
+    match format {
+        Format::Text => {
+            out.line(format!("TAG\t{}", platforms.join("\t")))?;
+            for row in &rows {
+                out.line(format_row(row, platforms))?;
+            }
+            Ok(())
+        }
+        format => out.document(format, &rows),
+    }
+}
+
+/// Availability of `platforms` for `tag`, unknown for all if `found` is
+fn row(tag: &Tag, platforms: &[String], found: Option<&[Platform]>) -> MatrixRow {
+    MatrixRow {
+        tag: tag.name().to_string(),
+        platforms: platforms
+            .iter()
+            .map(|platform| {
+                let available = found.map(|found| found.iter().any(|p| p.matches(platform)));
+                (platform.clone(), available)
+            })
+            .collect(),
+    }
+}
+
+/// Tab-separated `1.27.2 ✓ ✗` cells, `?` where availability is unknown
+fn format_row(row: &MatrixRow, platforms: &[String]) -> String {
+    let cells = platforms
+        .iter()
+        .map(|platform| match row.platforms.get(platform) {
+            Some(Some(true)) => "✓",
+            Some(Some(false)) => "✗",
+            _ => "?",
+        });
+    [row.tag.as_str()]
+        .into_iter()
+        .chain(cells)
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_row() {
+        let platforms = ["linux/amd64", "linux/arm64", "linux/arm/v7"].map(String::from);
+        let platform = |os: &str, architecture: &str, variant: Option<&str>| Platform {
+            architecture: architecture.to_string(),
+            os: os.to_string(),
+            variant: variant.map(String::from),
+        };
+        let found = [
+            platform("linux", "amd64", None),
+            platform("linux", "arm64", Some("v8")),
+            platform("linux", "arm", Some("v6")),
+            platform("unknown", "unknown", None),
+        ];
+
+        let tag = Tag::new("1.27.2");
+        assert_eq!(
+            format_row(&row(&tag, &platforms, Some(&found)), &platforms),
+            "1.27.2\t✓\t✓\t✗"
+        );
+        assert_eq!(
+            format_row(&row(&tag, &platforms, None), &platforms),
+            "1.27.2\t?\t?\t?"
+        );
+    }
+}
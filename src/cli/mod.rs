@@ -0,0 +1,421 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+#[cfg(feature = "github")]
+use docker_tags::Secret;
+use docker_tags::{
+    Anonymous, Client, DockerConfigProvider, Error, IgnoreList, Image, OidcTokens, PasswordFiles,
+    Selection, ServerNotice, TagFilter, TlsVersion, TokenSource, registries::GHCR,
+};
+use regex::Regex;
+
+pub mod added_after;
+pub mod batch;
+pub mod config;
+#[cfg(all(feature = "serve", unix))]
+pub mod daemon;
+pub mod digests;
+pub mod enrich_cache;
+pub mod exists;
+pub mod expand;
+pub mod history;
+pub mod immutable;
+pub mod intersect;
+pub mod lint;
+pub mod listing;
+#[cfg(feature = "serve")]
+pub mod listing_cache;
+pub mod matrix;
+pub mod not_found;
+pub mod notify;
+pub mod output;
+pub mod pin;
+pub mod plugin;
+pub mod policy;
+pub mod prom;
+pub mod published;
+pub mod referrers;
+pub mod renovate;
+pub mod report;
+pub mod resolve;
+pub mod series;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod signed;
+pub mod snapshot;
+pub mod sort;
+pub mod source;
+pub mod state;
+pub mod stats;
+pub mod token;
+pub mod upgrades;
+pub mod verify_order;
+pub mod watch;
+
+/// Split `name:tag` or `name@digest` into the image name and the reference,
+/// defaulting to `latest`
+pub fn split_reference(reference: &str) -> (&str, &str) {
+    if let Some((name, digest)) = reference.split_once('@') {
+        return (name, digest);
+    }
+    match reference.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => (name, tag),
+        _ => (reference, "latest"),
+    }
+}
+
+/// Compile the regex `pattern`, its error telling what's wrong and where
+pub fn regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).with_context(|| format!("Invalid regex pattern: {pattern:?}"))
+}
+
+/// Read the ignore file at `path`
+pub fn load_ignore_file(path: &Path) -> Result<IgnoreList> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    IgnoreList::parse(&text).with_context(|| format!("Invalid ignore file {path:?}"))
+}
+
+/// What the user can do about `err`, if there's anything specific
+pub fn hint(err: &anyhow::Error) -> Option<String> {
+    let Some(Error::AccessDenied {
+        registry,
+        repository,
+        ..
+    }) = err.chain().find_map(|cause| cause.downcast_ref::<Error>())
+    else {
+        return None;
+    };
+    let login = match registry.as_str() {
+        GHCR => "with a personal access token that has the read:packages scope",
+        _ => "with an account allowed to pull it",
+    };
+    Some(format!(
+        "access denied to {registry}/{repository}; run `docker login {registry}` {login}"
+    ))
+}
+
+/// Registry settings given on the command line and in the config file
+#[derive(Default)]
+pub struct Registry {
+    /// Path the registry serves `/v2/` under, e.g. `/artifactory/api/docker/<repo>`
+    pub path_prefix: Option<String>,
+    /// Always filter client-side, even where the registry could do it
+    pub no_filter_pushdown: bool,
+    /// PEM files of extra CA certificates, by host
+    pub ca_certs: Vec<(String, PathBuf)>,
+    /// Names to verify the certificates of registries given by IP against
+    pub tls_server_names: Vec<(String, String)>,
+    /// Oldest TLS versions allowed, for every host or by host
+    pub min_tls_versions: Vec<(Option<String>, TlsVersion)>,
+    /// Other hosts the image's registry may send to for pages of tags
+    pub trusted_page_hosts: Vec<String>,
+    /// Hosts to restrict requests to, unrestricted if there are no registries
+    pub allowed_registries: Vec<String>,
+    pub allowed_auth_hosts: Vec<String>,
+    /// Hosts never to send requests to
+    pub denied_registries: Vec<String>,
+    /// List the tags again when pages overlap
+    pub consistent: bool,
+    #[cfg(feature = "github")]
+    pub github_token: Option<String>,
+    /// File to read the GitHub token from, when there's no `github_token`
+    #[cfg(feature = "github")]
+    pub github_token_file: Option<PathBuf>,
+    /// Users to log in to registries as, by host, with the password in the
+    /// file for the same host
+    pub usernames: Vec<(String, String)>,
+    pub password_files: Vec<(String, PathBuf)>,
+    /// Registry tokens to send as they are, by host
+    pub token_files: Vec<(String, PathBuf)>,
+    /// Client IDs to exchange OIDC tokens for at registries, by host, with
+    /// the token read from the source for the same host
+    pub oidc_client_ids: Vec<(String, String)>,
+    pub oidc_tokens: Vec<(String, TokenSource)>,
+    /// Media types accepted for the image's registry's manifests, all
+    /// supported ones if empty
+    pub manifest_accept: Vec<String>,
+    /// Limits on the requests to the image's registry
+    pub max_concurrent_requests: Option<usize>,
+    pub min_request_interval: Option<Duration>,
+    /// Repositories found missing by earlier runs, skipped while cached
+    pub not_found: Option<not_found::NotFoundCache>,
+    /// Docker's config, shared by the clients so that it's read only once
+    pub docker_config: DockerConfigProvider,
+    /// Never send credentials, not even reading Docker's config
+    pub anonymous: bool,
+    /// Fail without usable credentials in Docker's config
+    pub require_auth: bool,
+    /// Don't warn about corrupt credentials
+    pub quiet: bool,
+    /// Tags to leave out of listings and suggestions
+    pub ignore: Option<IgnoreList>,
+    /// Notices of registries already warned about, by registry
+    pub notices_shown: Mutex<HashSet<(String, ServerNotice)>>,
+    /// Settings of the config file, by registry host
+    pub profiles: BTreeMap<String, config::RegistryProfile>,
+}
+
+impl Registry {
+    pub fn image(&self, name: &str) -> Result<Image> {
+        let image = match &self.path_prefix {
+            Some(prefix) => Image::parse_with_prefix(name, prefix),
+            None => Image::try_from(name),
+        };
+        image.map_err(|err| anyhow!(err).context(format!("Invalid image name: {name:?}")))
+    }
+
+    pub fn client(&self, image: &Image) -> Result<Client> {
+        let mut builder = Client::builder()
+            .filter_pushdown(!self.no_filter_pushdown)
+            .consistent_listing(self.consistent)
+            .credential_provider(self.credential_provider()?);
+        if let Some(prefix) = &self.path_prefix {
+            builder = builder.path_prefix(image.registry(), prefix);
+        }
+        // the command line's, then the config file's registries and their
+        // token services
+        let mut ca_certs: Vec<_> = self
+            .ca_certs
+            .iter()
+            .map(|(host, path)| (host, path))
+            .collect();
+        for (host, profile) in &self.profiles {
+            ca_certs.extend(profile.ca_cert.iter().map(|path| (host, path)));
+            ca_certs.extend(&profile.auth_hosts);
+        }
+        for (host, path) in ca_certs {
+            let pem = fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+            builder = builder.root_certificate(host, &pem)?;
+        }
+        // the config file's, which the command line's then replace
+        let mut server_names: Vec<_> = self
+            .profiles
+            .iter()
+            .filter_map(|(host, profile)| Some((host, profile.tls_server_name.as_ref()?)))
+            .collect();
+        server_names.extend(
+            self.tls_server_names
+                .iter()
+                .map(|(host, name)| (host, name)),
+        );
+        for (host, name) in server_names {
+            builder = builder.tls_server_name(host, name)?;
+        }
+        // the config file's, which the command line's then replace
+        let mut min_tls_versions: Vec<_> = self
+            .profiles
+            .iter()
+            .filter_map(|(host, profile)| Some((Some(host.clone()), profile.tls_min?)))
+            .collect();
+        min_tls_versions.extend(self.min_tls_versions.iter().cloned());
+        for (host, version) in &min_tls_versions {
+            builder = match host {
+                Some(host) => builder.min_tls_version_for(host, *version)?,
+                None => builder.min_tls_version(*version)?,
+            };
+        }
+        #[cfg(feature = "github")]
+        if let Some(token) = &self.github_token {
+            builder = builder.github_token(token.as_str());
+        } else if let Some(path) = &self.github_token_file {
+            builder = builder.github_token(Secret::from_file(path)?);
+        }
+        for host in &self.trusted_page_hosts {
+            builder = builder.trusted_page_host(image.registry(), host);
+        }
+        if !self.allowed_registries.is_empty() {
+            builder = builder
+                .allowed_registries(&self.allowed_registries)
+                .allowed_auth_hosts(&self.allowed_auth_hosts);
+        }
+        if !self.denied_registries.is_empty() {
+            builder = builder.denied_registries(&self.denied_registries);
+        }
+        if !self.manifest_accept.is_empty() {
+            builder = builder.manifest_accept(image.registry(), &self.manifest_accept)?;
+        }
+        for (host, profile) in &self.profiles {
+            if let Some(max) = profile.max_concurrent_requests {
+                builder = builder.max_concurrent_requests(host, max.get().into());
+            }
+            if let Some(ms) = profile.min_request_interval {
+                builder = builder.min_request_interval(host, Duration::from_millis(ms));
+            }
+        }
+        if let Some(max) = self.max_concurrent_requests {
+            builder = builder.max_concurrent_requests(image.registry(), max);
+        }
+        if let Some(interval) = self.min_request_interval {
+            builder = builder.min_request_interval(image.registry(), interval);
+        }
+        Ok(builder.build())
+    }
+
+    /// Warn about what `image`'s registry announced unless quiet, each notice
+    /// once per run however many images of the registry are listed
+    pub fn warn_notices(&self, image: &Image, notices: &[ServerNotice]) {
+        if self.quiet {
+            return;
+        }
+        let mut shown = self.notices_shown.lock().unwrap();
+        for notice in notices {
+            if shown.insert((image.registry().to_string(), notice.clone())) {
+                eprintln!("Warning: {}: {notice}", image.registry());
+            }
+        }
+    }
+
+    /// The OIDC tokens, password files and token files given, then those of
+    /// the registry profiles, then Docker's config, warning about its corrupt
+    /// entries unless quiet, or no credentials at all if anonymous
+    fn credential_provider(&self) -> Result<OidcTokens> {
+        let mut provider = self.docker_config.clone();
+        if self.require_auth {
+            provider = provider.require_credentials();
+        }
+        if !self.quiet {
+            provider = provider.on_corrupt_entry(|entry| {
+                eprintln!(
+                    "Warning: corrupt credential entry {entry}; continuing without credentials"
+                );
+            });
+        }
+
+        let mut files = match self.anonymous {
+            true => PasswordFiles::new(Anonymous),
+            false => PasswordFiles::new(provider),
+        };
+        // the config file's, which the command line's then replace
+        let profiles = self.profiles.iter().filter(|_| !self.anonymous);
+        for (host, profile) in profiles {
+            files = match (
+                &profile.username,
+                &profile.password_file,
+                &profile.token_file,
+            ) {
+                (Some(username), Some(path), None) => files.add(host, username, path),
+                (None, None, Some(path)) => files.add_token(host, path),
+                (None, None, None) => files,
+                _ => bail!(
+                    "The profile of {host} needs username with password_file, or token_file alone"
+                ),
+            };
+        }
+        for (host, path) in &self.password_files {
+            let Some((_, username)) = self
+                .usernames
+                .iter()
+                .find(|(user_host, _)| user_host == host)
+            else {
+                bail!("--password-file {host}=… needs --username {host}=USER");
+            };
+            files = files.add(host, username, path);
+        }
+        if let Some((host, _)) = self.usernames.iter().find(|(host, _)| {
+            !self
+                .password_files
+                .iter()
+                .any(|(file_host, _)| file_host == host)
+        }) {
+            bail!("--username {host}=… needs --password-file {host}=FILE");
+        }
+        for (host, path) in &self.token_files {
+            if self
+                .password_files
+                .iter()
+                .any(|(file_host, _)| file_host == host)
+            {
+                bail!("Only one of --password-file and --token-file may be given for {host}");
+            }
+            files = files.add_token(host, path);
+        }
+
+        let mut tokens = OidcTokens::new(files);
+        for (i, (host, source)) in self.oidc_tokens.iter().enumerate() {
+            if self.oidc_tokens[..i].iter().any(|(other, _)| other == host) {
+                bail!("Only one of --oidc-token-file and --oidc-token-env may be given for {host}");
+            }
+            let Some((_, client_id)) = self
+                .oidc_client_ids
+                .iter()
+                .find(|(id_host, _)| id_host == host)
+            else {
+                bail!("The OIDC token for {host} needs --oidc-client-id {host}=ID");
+            };
+            tokens = tokens.add(host, client_id, source.clone());
+        }
+        if let Some((host, _)) = self.oidc_client_ids.iter().find(|(host, _)| {
+            !self
+                .oidc_tokens
+                .iter()
+                .any(|(token_host, _)| token_host == host)
+        }) {
+            bail!(
+                "--oidc-client-id {host}=… needs --oidc-token-file or --oidc-token-env for {host}"
+            );
+        }
+        Ok(tokens)
+    }
+
+    /// `selection`, also leaving out the tags ignored for `image`
+    pub fn selection_for(&self, image: &Image, selection: &Selection) -> Selection {
+        let mut selection = selection.clone();
+        selection.filters.extend(
+            self.ignore
+                .as_ref()
+                .map(|ignore| TagFilter::not_ignored(ignore, image)),
+        );
+        selection
+    }
+
+    /// Parse `name[:tag|@digest]` into the image and the reference
+    pub fn parse_reference<'a>(&self, reference: &'a str) -> Result<(Image, &'a str)> {
+        let (name, tag) = split_reference(reference);
+        Ok((self.image(name)?, tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_reference() {
+        assert_eq!(split_reference("alpine:3.21"), ("alpine", "3.21"));
+        assert_eq!(split_reference("alpine"), ("alpine", "latest"));
+        assert_eq!(
+            split_reference("localhost:5000/app"),
+            ("localhost:5000/app", "latest")
+        );
+        assert_eq!(
+            split_reference("localhost:5000/app:1.0"),
+            ("localhost:5000/app", "1.0")
+        );
+        assert_eq!(
+            split_reference("ghcr.io/org/app@sha256:abc"),
+            ("ghcr.io/org/app", "sha256:abc")
+        );
+    }
+
+    #[test]
+    fn test_hint() {
+        let err = anyhow::Error::new(Error::AccessDenied {
+            registry: "ghcr.io".to_string(),
+            repository: "org/private".to_string(),
+            message: String::new(),
+        })
+        .context("Failed to list tags");
+        assert_eq!(
+            hint(&err).unwrap(),
+            "access denied to ghcr.io/org/private; run `docker login ghcr.io` with a personal \
+             access token that has the read:packages scope"
+        );
+        assert_eq!(hint(&anyhow::Error::new(Error::NotFound)), None);
+    }
+}
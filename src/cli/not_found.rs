@@ -0,0 +1,206 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use docker_tags::{Error, Image};
+use serde::{Deserialize, Serialize};
+
+use super::{batch::Status, history::state_path, output::write_atomic};
+
+/// Format of the file, which is ignored when it was written by another
+const VERSION: u32 = 1;
+
+/// Repositories earlier runs found missing, so that runs within `ttl` don't
+/// ask the registry again
+///
+/// Entries go once `ttl` passed or the repository is listed after all.
+#[derive(Debug)]
+pub struct NotFoundCache {
+    path: PathBuf,
+    ttl: Duration,
+    /// Ask about every repository, only recording the answers
+    refresh: bool,
+}
+
+/// Context of the errors of images skipped thanks to the cache
+#[derive(Debug)]
+struct Cached(String);
+
+impl fmt::Display for Cached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: not found (cached)", self.0)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entries {
+    version: u32,
+    /// When each image was last found missing
+    entries: BTreeMap<String, SystemTime>,
+}
+
+impl NotFoundCache {
+    pub fn new(path: impl Into<PathBuf>, ttl: Duration) -> Self {
+        NotFoundCache {
+            path: path.into(),
+            ttl,
+            refresh: false,
+        }
+    }
+
+    /// Let [`NotFoundCache::check`] pass every image, while updates still
+    /// record which were found
+    pub fn refresh(self, refresh: bool) -> Self {
+        NotFoundCache { refresh, ..self }
+    }
+
+    /// `$XDG_STATE_HOME/docker-tags/not-found.json`, or `~/.local/state/...`
+    pub fn open(ttl: Duration) -> Option<Self> {
+        state_path(
+            "not-found.json",
+            env::var_os("XDG_STATE_HOME"),
+            env::home_dir(),
+        )
+        .map(|path| NotFoundCache::new(path, ttl))
+    }
+
+    /// Fail with [`Error::NotFound`] if `image` was found missing within the TTL
+    pub fn check(&self, image: &Image) -> Result<()> {
+        if self.refresh {
+            return Ok(());
+        }
+        let now = SystemTime::now();
+        match self.load().entries.get(&image.to_string()) {
+            Some(&since) if now.duration_since(since).unwrap_or_default() < self.ttl => {
+                Err(anyhow::Error::new(Error::NotFound).context(Cached(image.to_string())))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the images whose listings failed as not found, and forget those
+    /// listed without an error, as well as expired entries
+    pub fn update<'a>(
+        &self,
+        results: impl IntoIterator<Item = (&'a Image, Option<&'a anyhow::Error>)>,
+    ) -> Result<()> {
+        let now = SystemTime::now();
+        let mut cache = self.load();
+        let before = cache.entries.clone();
+        cache
+            .entries
+            .retain(|_, since| now.duration_since(*since).unwrap_or_default() < self.ttl);
+        for (image, error) in results {
+            match error {
+                None => {
+                    cache.entries.remove(&image.to_string());
+                }
+                // a cached failure keeps the time it was found missing
+                Some(err) if err.downcast_ref::<Cached>().is_some() => {}
+                Some(err) if is_missing(err) => {
+                    cache.entries.insert(image.to_string(), now);
+                }
+                Some(_) => {}
+            }
+        }
+        if cache.entries == before {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {dir:?}"))?;
+        }
+        let contents =
+            serde_json::to_vec_pretty(&cache).context("Failed to serialize the cache")?;
+        write_atomic(&self.path, &contents)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Failed to remove {:?}", self.path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Entries of the file, none if it's missing, unreadable or of another
+    /// format, since it only saves requests
+    fn load(&self) -> Entries {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Entries>(&text).ok())
+            .filter(|cache| cache.version == VERSION)
+            .unwrap_or(Entries {
+                version: VERSION,
+                entries: BTreeMap::new(),
+            })
+    }
+}
+
+/// Whether `err` tells the repository is missing, rather than that its token
+/// couldn't be had, which is also taken for not found
+fn is_missing(err: &anyhow::Error) -> bool {
+    let unreachable = err.chain().any(|cause| {
+        cause.is::<reqwest::Error>()
+            || matches!(cause.downcast_ref(), Some(Error::RegistryNotAllowed { .. }))
+    });
+    Status::of(err) == Status::NotFound && !unreachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_found_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = NotFoundCache::new(dir.path().join("not-found.json"), Duration::from_secs(60));
+        let (dead, alive) = (
+            Image::new("ghcr.io", "org/dead"),
+            Image::new("ghcr.io", "org/app"),
+        );
+        let missing = anyhow::Error::new(Error::NotFound).context("Failed to list tags");
+        let failed = anyhow::Error::new(Error::RegistryNotAllowed {
+            host: "auth.corp".to_string(),
+        })
+        .context(Error::NotFound);
+        cache
+            .update([(&dead, Some(&missing)), (&alive, Some(&failed))])
+            .unwrap();
+
+        let err = cache.check(&dead).unwrap_err();
+        assert_eq!(err.to_string(), "ghcr.io/org/dead: not found (cached)");
+        assert_eq!(Status::of(&err), Status::NotFound);
+        assert!(cache.check(&alive).is_ok());
+
+        // checking again from the cache doesn't extend the entry
+        let before = fs::read(dir.path().join("not-found.json")).unwrap();
+        cache.update([(&dead, Some(&err))]).unwrap();
+        assert_eq!(fs::read(dir.path().join("not-found.json")).unwrap(), before);
+
+        let cache = cache.refresh(true);
+        assert!(cache.check(&dead).is_ok());
+        cache.update([(&dead, None)]).unwrap();
+        let cache = cache.refresh(false);
+        assert!(cache.check(&dead).is_ok());
+
+        cache.update([(&dead, Some(&missing))]).unwrap();
+        cache.clear().unwrap();
+        cache.clear().unwrap();
+        assert!(cache.check(&dead).is_ok());
+    }
+
+    #[test]
+    fn test_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = NotFoundCache::new(dir.path().join("not-found.json"), Duration::ZERO);
+        let dead = Image::new("ghcr.io", "org/dead");
+        let missing = anyhow::Error::new(Error::NotFound);
+        cache.update([(&dead, Some(&missing))]).unwrap();
+        assert!(cache.check(&dead).is_ok());
+    }
+}
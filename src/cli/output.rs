@@ -0,0 +1,204 @@
+use std::{
+    fmt, fs,
+    io::{self, BufWriter, IsTerminal, StdoutLock, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use docker_tags::output::{Formatter, Listing};
+use serde::Serialize;
+
+use super::report::Format;
+
+/// Buffered, locked stdout shared by all output modes, or a file written once
+/// the output is complete
+///
+/// Write errors are returned rather than panicking like `println!`, so a
+/// reader closing the pipe early (e.g. `| head`) can be told apart with
+/// [`is_broken_pipe`].
+pub struct Output {
+    sink: Sink,
+}
+
+enum Sink {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    /// Kept in memory so a failed run never leaves a partial file behind
+    File {
+        path: PathBuf,
+        contents: Vec<u8>,
+    },
+}
+
+impl Output {
+    pub fn new() -> Self {
+        Output {
+            sink: Sink::Stdout(BufWriter::new(io::stdout().lock())),
+        }
+    }
+
+    /// Collect the output for `path`, which is written by [`Output::finish`]
+    ///
+    /// An existing file is only replaced with `force`; `mkdir` creates the
+    /// missing parent directories.
+    pub fn to_file(path: &Path, mkdir: bool, force: bool) -> Result<Self> {
+        if !force && path.exists() {
+            return Err(anyhow!(
+                "Output file {path:?} already exists (pass --force to overwrite it)"
+            ));
+        }
+        if mkdir && let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+
+        Ok(Output {
+            sink: Sink::File {
+                path: path.to_path_buf(),
+                contents: Vec::new(),
+            },
+        })
+    }
+
+    /// Whether the output goes to a terminal rather than a pipe or a file
+    pub fn is_terminal(&self) -> bool {
+        matches!(self.sink, Sink::Stdout(_)) && io::stdout().is_terminal()
+    }
+
+    pub fn line(&mut self, line: impl fmt::Display) -> Result<()> {
+        writeln!(self.writer(), "{line}").context("Failed to write output")
+    }
+
+    /// Write `text` as is, without adding a newline
+    pub fn text(&mut self, text: &str) -> Result<()> {
+        self.writer()
+            .write_all(text.as_bytes())
+            .context("Failed to write output")
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.sink {
+            Sink::Stdout(out) => out,
+            Sink::File { contents, .. } => contents,
+        }
+    }
+
+    /// Print `document` as JSON or YAML
+    pub fn document(&mut self, format: Format, document: &impl Serialize) -> Result<()> {
+        let text = format.render(document)?;
+        self.line(text)
+    }
+
+    /// Write `listing` with `formatter`
+    pub fn listing(&mut self, formatter: &dyn Formatter, listing: &Listing) -> Result<()> {
+        formatter
+            .write(self.writer(), listing)
+            .context("Failed to write output")
+    }
+
+    /// Push buffered lines out, e.g. before waiting for the next poll
+    pub fn flush(&mut self) -> Result<()> {
+        match &mut self.sink {
+            Sink::Stdout(out) => out.flush().context("Failed to write output"),
+            Sink::File { .. } => Ok(()),
+        }
+    }
+
+    /// Flush stdout, or atomically replace the output file
+    pub fn finish(mut self) -> Result<()> {
+        match &mut self.sink {
+            Sink::Stdout(_) => self.flush(),
+            Sink::File { path, contents } => write_atomic(path, contents),
+        }
+    }
+}
+
+/// Write to a temporary file next to `path`, then rename it over `path`,
+/// keeping the permissions of the file it replaces
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let result = fs::write(&tmp, contents)
+        .and_then(|()| match fs::metadata(path) {
+            Ok(metadata) => fs::set_permissions(&tmp, metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .with_context(|| format!("Failed to write output file {path:?}"))
+        .and_then(|()| {
+            fs::rename(&tmp, path)
+                .with_context(|| format!("Failed to replace output file {path:?}"))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Whether `err` was caused by stdout being closed by the reader
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("injected failure"))
+        }
+    }
+
+    #[test]
+    fn test_output_file_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tags.txt");
+        fs::write(&path, "old\n").unwrap();
+
+        assert!(Output::to_file(&path, false, false).is_err());
+
+        let mut out = Output::to_file(&path, false, true).unwrap();
+        out.line("1.0.0").unwrap();
+        out.finish().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1.0.0\n");
+    }
+
+    #[test]
+    fn test_output_file_is_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshots/alpine.json");
+        assert!(
+            Output::to_file(&path, false, false)
+                .unwrap()
+                .finish()
+                .is_err()
+        );
+
+        let mut out = Output::to_file(&path, true, false).unwrap();
+        out.line("1.0.0").unwrap();
+        assert!(out.document(Format::Json, &Unserializable).is_err());
+        drop(out);
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_is_broken_pipe() {
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::BrokenPipe))
+            .context("Failed to write output");
+        assert!(is_broken_pipe(&err));
+
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::StorageFull));
+        assert!(!is_broken_pipe(&err));
+        assert!(!is_broken_pipe(&anyhow!("Image not found")));
+    }
+}
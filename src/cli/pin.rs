@@ -0,0 +1,310 @@
+use std::{collections::HashMap, fs, ops::Range, path::Path};
+
+use anyhow::{Context, Result};
+use docker_tags::{Tag, unstable::Reference};
+use regex::Regex;
+
+use super::{
+    Registry,
+    output::{Output, write_atomic},
+    report::{Format, PinEntry, PinReport, PinState},
+};
+
+/// References after `FROM` in Dockerfiles and `image:` in compose files or
+/// Kubernetes manifests
+pub const DEFAULT_PATTERN: &str = r#"(?m)(?:^[ \t]*(?i-u:from)[ \t]+(?:--platform=[^ \t\n]+[ \t]+)?|(?-u:\b)image:[ \t]*["']?)(?P<ref>(?-u:\w[\w./:-]*)(?:@[a-z0-9]+:[0-9a-f]+)?)"#;
+
+/// What to do with the file
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Print the rewritten file
+    Print,
+    /// Rewrite the file in place
+    Write,
+    /// Only list the references which aren't pinned or whose pin is outdated
+    Check,
+}
+
+/// A tagged image reference found in a file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Found {
+    /// Bytes of the reference, digest included
+    range: Range<usize>,
+    pub line: usize,
+    pub name: String,
+    pub tag: String,
+    digest: Option<String>,
+}
+
+impl Found {
+    /// The reference as it's spelled in the file, without its digest
+    pub fn spelled(&self) -> String {
+        format!("{}:{}", self.name, self.tag)
+    }
+
+    /// The reference as `registry` reads its name, without its digest
+    pub fn reference(&self, registry: &Registry) -> Result<Reference> {
+        let image = registry.image(&self.name)?;
+        Ok(Reference::from((&image, &Tag::new(&self.tag))))
+    }
+}
+
+/// How a reference compares to the digest its tag points to now
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Pin {
+    Current,
+    Missing,
+    Outdated { pinned: String },
+}
+
+/// Pin the references `pattern` finds in `path`; returns whether any
+/// reference isn't pinned or is outdated, after `--update` in write mode
+///
+/// A document `format` lists every reference instead of the rewritten file
+/// or the references left to pin.
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    path: &Path,
+    pattern: &Regex,
+    mode: Mode,
+    update: bool,
+    format: Format,
+) -> Result<bool> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    let found = scan(&text, pattern);
+
+    let mut digests = HashMap::new();
+    let mut references = Vec::new();
+    for reference in &found {
+        let parsed = reference.reference(registry)?;
+        references.push(parsed.clone());
+        let key = (reference.name.clone(), reference.tag.clone());
+        if digests.contains_key(&key) {
+            continue;
+        }
+        let image = parsed.image();
+        let digest = registry
+            .client(image)?
+            .fetch_digest(image, &reference.tag)
+            .await
+            .with_context(|| format!("Failed to resolve {parsed}"))?;
+        digests.insert(key, digest);
+    }
+
+    let document = format != Format::Text;
+    let mut pending = false;
+    let mut pins = Vec::new();
+    let mut entries = Vec::new();
+    for (reference, parsed) in found.iter().zip(&references) {
+        let digest = &digests[&(reference.name.clone(), reference.tag.clone())];
+        let pin = match &reference.digest {
+            Some(pinned) if pinned == digest => Pin::Current,
+            Some(pinned) => Pin::Outdated {
+                pinned: pinned.clone(),
+            },
+            None => Pin::Missing,
+        };
+        entries.push(PinEntry {
+            line: reference.line,
+            reference: parsed.to_string(),
+            digest: digest.clone(),
+            pinned: reference.digest.clone(),
+            state: match pin {
+                Pin::Current => PinState::Current,
+                Pin::Missing => PinState::Missing,
+                Pin::Outdated { .. } => PinState::Outdated,
+            },
+            rewritten: false,
+        });
+        match (&pin, mode) {
+            (Pin::Current, _) => continue,
+            (_, Mode::Check) if document => {}
+            (Pin::Missing, Mode::Check) => {
+                out.line(format!("{}: {parsed} is not pinned", reference.line))?;
+            }
+            (Pin::Outdated { pinned }, Mode::Check) => out.line(format!(
+                "{}: {parsed} is pinned to {pinned}, the tag now points to {digest}",
+                reference.line,
+            ))?,
+            (Pin::Outdated { .. }, _) if !update => {
+                if !registry.quiet {
+                    eprintln!(
+                        "Warning: line {}: {parsed} is pinned to an outdated digest (pass --update to replace it)",
+                        reference.line,
+                    );
+                }
+                pending = true;
+                continue;
+            }
+            _ => {}
+        }
+        pending |= mode == Mode::Check;
+        if mode != Mode::Check
+            && let Some(entry) = entries.last_mut()
+        {
+            entry.rewritten = true;
+        }
+        pins.push((reference, digest.as_str()));
+    }
+
+    let written = mode == Mode::Write && !pins.is_empty();
+    match mode {
+        _ if document => {}
+        Mode::Check => {}
+        Mode::Print => out.text(&rewrite(&text, &pins))?,
+        Mode::Write => {
+            for (reference, digest) in &pins {
+                let parsed = reference.reference(registry)?;
+                out.line(format!(
+                    "{}: {parsed} -> {}",
+                    reference.line,
+                    parsed.clone().with_digest(*digest)
+                ))?;
+            }
+        }
+    }
+    if written {
+        write_atomic(path, rewrite(&text, &pins).as_bytes())?;
+    }
+    if document {
+        let report = PinReport {
+            file: path.display().to_string(),
+            references: entries,
+            written,
+        };
+        out.document(format, &report)?;
+    }
+
+    Ok(pending)
+}
+
+/// Tagged references matched by `pattern`'s `ref` group, or else the whole
+/// match; untagged ones such as build stages or `scratch` are skipped
+pub fn scan(text: &str, pattern: &Regex) -> Vec<Found> {
+    pattern
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let matched = captures.name("ref").or_else(|| captures.get(0))?;
+            let (name_tag, digest) = match matched.as_str().split_once('@') {
+                Some((name_tag, digest)) => (name_tag, Some(digest.to_string())),
+                None => (matched.as_str(), None),
+            };
+            let (name, tag) = name_tag
+                .rsplit_once(':')
+                .filter(|(name, tag)| !name.is_empty() && !tag.is_empty() && !tag.contains('/'))?;
+            Some(Found {
+                range: matched.range(),
+                line: text[..matched.start()].matches('\n').count() + 1,
+                name: name.to_string(),
+                tag: tag.to_string(),
+                digest,
+            })
+        })
+        .collect()
+}
+
+/// `text` with each reference of `pins` replaced by `name:tag@digest`,
+/// leaving every other byte alone
+fn rewrite(text: &str, pins: &[(&Found, &str)]) -> String {
+    let mut rewritten = String::with_capacity(text.len());
+    let mut end = 0;
+    for (reference, digest) in pins {
+        rewritten.push_str(&text[end..reference.range.start]);
+        rewritten.push_str(&format!("{}@{digest}", reference.spelled()));
+        end = reference.range.end;
+    }
+    rewritten.push_str(&text[end..]);
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(byte: char) -> String {
+        format!("sha256:{}", byte.to_string().repeat(64))
+    }
+
+    /// Pin every reference of `text` to the digest of `digests`
+    fn pin_all(text: &str, digests: &[(&str, String)]) -> String {
+        let found = scan(text, &Regex::new(DEFAULT_PATTERN).unwrap());
+        let digests: HashMap<_, _> = digests.iter().cloned().collect();
+        let pins: Vec<_> = found
+            .iter()
+            .map(|reference| (reference, digests[reference.spelled().as_str()].as_str()))
+            .collect();
+        rewrite(text, &pins)
+    }
+
+    #[test]
+    fn test_scan() {
+        let text = "FROM --platform=$BUILDPLATFORM golang:1.23 AS build\nFROM build\nfrom scratch\n  image: \"quay.io/prometheus/prometheus:v3.0.0@sha256:abc\"\n";
+        let found = scan(text, &Regex::new(DEFAULT_PATTERN).unwrap());
+        let summary: Vec<_> = found
+            .iter()
+            .map(|f| (f.line, f.spelled(), f.digest.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, "golang:1.23".to_string(), None),
+                (
+                    4,
+                    "quay.io/prometheus/prometheus:v3.0.0".to_string(),
+                    Some("sha256:abc")
+                ),
+            ]
+        );
+
+        // registry ports aren't tags
+        let found = scan(
+            "image: localhost:5000/app\nimage: localhost:5000/app:1.0\n",
+            &Regex::new(DEFAULT_PATTERN).unwrap(),
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "localhost:5000/app");
+
+        // a custom pattern without a `ref` group
+        let found = scan("base=alpine:3.21\n", &Regex::new(r"alpine:[\w.]+").unwrap());
+        assert_eq!(found[0].spelled(), "alpine:3.21");
+    }
+
+    #[test]
+    fn test_rewrite_golden() {
+        let digests = [
+            ("golang:1.23", digest('1')),
+            ("gcr.io/distroless/static:nonroot", digest('2')),
+            ("postgres:16-alpine", digest('3')),
+            ("nginx:1.27", digest('4')),
+        ];
+        let cases = [
+            (
+                include_str!("../../tests/fixtures/pin/Dockerfile"),
+                include_str!("../../tests/fixtures/pin/Dockerfile.pinned"),
+            ),
+            (
+                include_str!("../../tests/fixtures/pin/compose.yaml"),
+                include_str!("../../tests/fixtures/pin/compose.pinned.yaml"),
+            ),
+        ];
+        for (input, golden) in cases {
+            let pinned = pin_all(input, &digests);
+            assert_eq!(pinned, golden);
+            // pinning again changes nothing
+            assert_eq!(pin_all(&pinned, &digests), golden);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_keeps_other_bytes() {
+        let text = "FROM alpine:3.21\r\n\tRUN true  \r\n# no trailing newline";
+        assert_eq!(
+            pin_all(text, &[("alpine:3.21", digest('a'))]),
+            format!(
+                "FROM alpine:3.21@{}\r\n\tRUN true  \r\n# no trailing newline",
+                digest('a')
+            )
+        );
+    }
+}
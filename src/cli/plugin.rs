@@ -0,0 +1,172 @@
+use std::ffi::OsString;
+
+use serde::Serialize;
+
+/// Argument the docker CLI runs plugins with to read their metadata
+pub const METADATA_COMMAND: &str = "docker-cli-plugin-metadata";
+
+/// Set by the docker CLI, to its own path, when it runs a plugin
+pub const PLUGIN_VAR: &str = "DOCKER_CLI_PLUGIN_ORIGINAL_CLI_COMMAND";
+
+/// Name of the plugin, `docker tags`, which docker passes first
+const NAME: &str = "tags";
+
+/// Global options of the docker CLI, which it passes on to plugins ahead of
+/// their name, and whether they take a value
+const DOCKER_OPTIONS: &[(&str, bool)] = &[
+    ("--config", true),
+    ("--context", true),
+    ("-c", true),
+    ("--host", true),
+    ("-H", true),
+    ("--log-level", true),
+    ("-l", true),
+    ("--tlscacert", true),
+    ("--tlscert", true),
+    ("--tlskey", true),
+    ("--debug", false),
+    ("-D", false),
+    ("--tls", false),
+    ("--tlsverify", false),
+];
+
+/// What `docker-cli-plugin-metadata` prints
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Metadata {
+    schema_version: &'static str,
+    vendor: &'static str,
+    version: &'static str,
+    short_description: &'static str,
+    #[serde(rename = "URL")]
+    url: &'static str,
+}
+
+/// The metadata the docker CLI asks a plugin for before listing or running
+/// it
+pub fn metadata() -> String {
+    let metadata = Metadata {
+        schema_version: "0.1.0",
+        vendor: "toogle",
+        version: env!("CARGO_PKG_VERSION"),
+        short_description: "List tags for Docker images with sensible sorting",
+        url: "https://github.com/toogle/docker-tags",
+    };
+    serde_json::to_string_pretty(&metadata).expect("metadata serializes")
+}
+
+/// The arguments to parse and the Docker config directory docker was told
+/// to use, if any
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Invocation {
+    pub args: Vec<OsString>,
+    pub config: Option<OsString>,
+}
+
+/// `args` as if docker-tags ran on its own: when docker runs it as a plugin,
+/// docker's global options and the plugin name are taken out, `--config`
+/// kept aside for the credentials
+///
+/// The other options, such as `--context`, only pick the daemon, which isn't
+/// involved in listing tags.
+pub fn invocation(args: Vec<OsString>, plugin: bool) -> Invocation {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap_or_default();
+    let rest: Vec<_> = args.collect();
+    if !plugin {
+        return Invocation {
+            args: [program].into_iter().chain(rest).collect(),
+            config: None,
+        };
+    }
+
+    let mut config = None;
+    let mut i = 0;
+    while let Some(arg) = rest.get(i).and_then(|arg| arg.to_str()) {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+            _ => (arg, None),
+        };
+        let Some(&(_, takes_value)) = DOCKER_OPTIONS.iter().find(|(option, _)| *option == name)
+        else {
+            break;
+        };
+        let value = match (takes_value, inline) {
+            (true, Some(value)) => Some(OsString::from(value)),
+            (true, None) => {
+                i += 1;
+                rest.get(i).cloned()
+            }
+            (false, _) => None,
+        };
+        if name == "--config" {
+            config = value;
+        }
+        i += 1;
+    }
+    let rest = match rest.get(i) {
+        Some(name) if name == NAME => &rest[i + 1..],
+        _ => &rest[i..],
+    };
+    Invocation {
+        args: [program].into_iter().chain(rest.iter().cloned()).collect(),
+        config,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_invocation() {
+        let standalone = args(&["docker-tags", "tags", "-n", "5"]);
+        assert_eq!(
+            invocation(standalone.clone(), false),
+            Invocation {
+                args: standalone,
+                config: None,
+            }
+        );
+
+        assert_eq!(
+            invocation(args(&["docker-tags", "tags", "nginx", "-n", "5"]), true),
+            Invocation {
+                args: args(&["docker-tags", "nginx", "-n", "5"]),
+                config: None,
+            }
+        );
+        assert_eq!(
+            invocation(
+                args(&[
+                    "docker-tags",
+                    "--config",
+                    "/ci/docker",
+                    "-D",
+                    "--context=prod",
+                    "-l",
+                    "debug",
+                    "tags",
+                    "nginx",
+                    "-D",
+                ]),
+                true
+            ),
+            Invocation {
+                args: args(&["docker-tags", "nginx", "-D"]),
+                config: Some("/ci/docker".into()),
+            }
+        );
+        assert_eq!(
+            invocation(args(&["docker-tags", "--config=/ci/docker", "tags"]), true),
+            Invocation {
+                args: args(&["docker-tags"]),
+                config: Some("/ci/docker".into()),
+            }
+        );
+    }
+}
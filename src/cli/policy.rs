@@ -0,0 +1,338 @@
+use std::fmt;
+
+use docker_tags::{LenientVersion, Tag, UpgradePolicy, UpgradeScope, suggest_upgrade};
+
+/// How far behind `outdated` conditions look
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Behind {
+    /// Any newer stable version
+    Any,
+    /// At least N major versions
+    Major(u64),
+    /// At least N minor versions, or any major one
+    Minor(u64),
+}
+
+/// A `--fail-on` condition
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Condition {
+    /// The newest stable tag is ahead of the current one
+    Outdated(Behind),
+    /// Nothing was listed
+    NoMatch,
+    /// A tag listed has no signature
+    Unsigned,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Outdated(Behind::Any) => write!(f, "outdated"),
+            Condition::Outdated(Behind::Major(1)) => write!(f, "outdated:major"),
+            Condition::Outdated(Behind::Major(n)) => write!(f, "outdated:major>={n}"),
+            Condition::Outdated(Behind::Minor(1)) => write!(f, "outdated:minor"),
+            Condition::Outdated(Behind::Minor(n)) => write!(f, "outdated:minor>={n}"),
+            Condition::NoMatch => write!(f, "no-match"),
+            Condition::Unsigned => write!(f, "unsigned"),
+        }
+    }
+}
+
+/// Parse a `--fail-on` condition: `outdated`, `outdated:major`,
+/// `outdated:minor>=N`, `no-match` or `unsigned`
+pub fn parse_condition(value: &str) -> Result<Condition, String> {
+    const EXPECTED: &str = "expected outdated, outdated:major[>=N], outdated:minor[>=N], \
+                            no-match or unsigned";
+    let behind = match value {
+        "outdated" => return Ok(Condition::Outdated(Behind::Any)),
+        "no-match" => return Ok(Condition::NoMatch),
+        "unsigned" => return Ok(Condition::Unsigned),
+        value => value
+            .strip_prefix("outdated:")
+            .ok_or_else(|| format!("unknown condition {value:?}; {EXPECTED}"))?,
+    };
+    let (level, at_least) = match behind.split_once(">=") {
+        Some((level, n)) => match n.parse() {
+            Ok(n) if n > 0 => (level, n),
+            _ => {
+                return Err(format!(
+                    "invalid count in {value:?}; expected a number from 1"
+                ));
+            }
+        },
+        None => (behind, 1),
+    };
+    match level {
+        "major" => Ok(Condition::Outdated(Behind::Major(at_least))),
+        "minor" => Ok(Condition::Outdated(Behind::Minor(at_least))),
+        _ => Err(format!("unknown condition {value:?}; {EXPECTED}")),
+    }
+}
+
+/// What the conditions are checked against, for one image
+#[derive(Debug, Default)]
+pub struct Results<'a> {
+    /// Tag in use, which `outdated` compares the newest stable candidate with
+    pub current: Option<&'a str>,
+    /// Tags the filters matched
+    pub candidates: &'a [Tag],
+    /// Number of tags listed
+    pub shown: usize,
+    /// Tags listed without a signature, `None` unless signatures were looked up
+    pub unsigned: Option<Vec<&'a str>>,
+}
+
+/// A condition which `results` met, and how
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Trigger {
+    pub condition: Condition,
+    pub reason: String,
+}
+
+/// The `conditions` met by `results`, in the order they were given: any of
+/// them fails the run
+pub fn evaluate(conditions: &[Condition], results: &Results) -> Vec<Trigger> {
+    conditions
+        .iter()
+        .filter_map(|&condition| {
+            let reason = match condition {
+                Condition::Outdated(behind) => outdated(behind, results)?,
+                Condition::NoMatch => (results.shown == 0).then(|| "no tag matched".to_string())?,
+                Condition::Unsigned => match results.unsigned.as_deref()? {
+                    [] => return None,
+                    tags => format!("{} isn't signed", tags.join(", ")),
+                },
+            };
+            Some(Trigger { condition, reason })
+        })
+        .collect()
+}
+
+/// How far the newest stable candidate is ahead of the current tag, if at
+/// least `behind`
+fn outdated(behind: Behind, results: &Results) -> Option<String> {
+    let current = results.current?;
+    let policy = UpgradePolicy {
+        scope: UpgradeScope::Latest,
+        ..UpgradePolicy::default()
+    };
+    let newest = suggest_upgrade(current, results.candidates, policy)?.tag;
+    let (from, to) = (
+        LenientVersion::parse(current)?,
+        LenientVersion::parse(newest.name())?,
+    );
+    let majors = to.major - from.major;
+    let minors = to
+        .minor
+        .unwrap_or(0)
+        .saturating_sub(from.minor.unwrap_or(0));
+    let (ahead, unit) = match behind {
+        Behind::Any => return Some(format!("{newest} is newer than {current}")),
+        Behind::Major(n) if majors >= n => (majors, "major"),
+        Behind::Minor(_) if majors > 0 => (majors, "major"),
+        Behind::Minor(n) if minors >= n => (minors, "minor"),
+        _ => return None,
+    };
+    let versions = match ahead {
+        1 => "version",
+        _ => "versions",
+    };
+    Some(format!(
+        "{newest} is {ahead} {unit} {versions} ahead of {current}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<Tag> {
+        names.iter().map(|name| Tag::new(*name)).collect()
+    }
+
+    fn conditions(value: &str) -> Vec<Condition> {
+        value
+            .split(',')
+            .map(|condition| parse_condition(condition).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        for (value, condition) in [
+            ("outdated", Condition::Outdated(Behind::Any)),
+            ("outdated:major", Condition::Outdated(Behind::Major(1))),
+            ("outdated:major>=2", Condition::Outdated(Behind::Major(2))),
+            ("outdated:minor", Condition::Outdated(Behind::Minor(1))),
+            ("outdated:minor>=3", Condition::Outdated(Behind::Minor(3))),
+            ("no-match", Condition::NoMatch),
+            ("unsigned", Condition::Unsigned),
+        ] {
+            assert_eq!(parse_condition(value), Ok(condition), "{value}");
+        }
+        assert_eq!(
+            parse_condition("outdated:minor>=2").unwrap().to_string(),
+            "outdated:minor>=2"
+        );
+        assert_eq!(
+            parse_condition("outdated:major>=1").unwrap().to_string(),
+            "outdated:major"
+        );
+        for value in [
+            "",
+            "stale",
+            "outdated:",
+            "outdated:patch",
+            "outdated:minor>=0",
+            "outdated:minor>=x",
+            "outdated:minor>2",
+            "no-match:1",
+        ] {
+            assert!(parse_condition(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_outdated() {
+        let candidates = tags(&["1.25.3", "1.26.0", "1.27.1", "1.28.0-rc.1", "latest"]);
+        let results = |current| Results {
+            current: Some(current),
+            candidates: &candidates,
+            ..Results::default()
+        };
+
+        let triggered = evaluate(&conditions("outdated"), &results("1.25.3"));
+        assert_eq!(
+            triggered,
+            [Trigger {
+                condition: Condition::Outdated(Behind::Any),
+                reason: "1.27.1 is newer than 1.25.3".to_string(),
+            }]
+        );
+        // pre-releases aren't stable
+        assert_eq!(evaluate(&conditions("outdated"), &results("1.27.1")), []);
+        // without a current version there's nothing to compare
+        assert_eq!(evaluate(&conditions("outdated"), &results("latest")), []);
+        assert_eq!(evaluate(&conditions("outdated"), &Results::default()), []);
+    }
+
+    #[test]
+    fn test_outdated_minor() {
+        let candidates = tags(&["1.25.3", "1.26.0", "1.27.1"]);
+        let results = |current| Results {
+            current: Some(current),
+            candidates: &candidates,
+            ..Results::default()
+        };
+
+        let triggered = evaluate(&conditions("outdated:minor>=2"), &results("1.25.3"));
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(
+            triggered[0].reason,
+            "1.27.1 is 2 minor versions ahead of 1.25.3"
+        );
+        assert_eq!(
+            evaluate(&conditions("outdated:minor>=2"), &results("1.26.0")),
+            []
+        );
+        assert_eq!(
+            evaluate(&conditions("outdated:minor"), &results("1.26.0"))[0].reason,
+            "1.27.1 is 1 minor version ahead of 1.26.0"
+        );
+
+        // a major version is more than any number of minor ones
+        let candidates = tags(&["1.27.1", "2.0.0"]);
+        let results = Results {
+            current: Some("1.27.1"),
+            candidates: &candidates,
+            ..Results::default()
+        };
+        assert_eq!(
+            evaluate(&conditions("outdated:minor>=5"), &results)[0].reason,
+            "2.0.0 is 1 major version ahead of 1.27.1"
+        );
+    }
+
+    #[test]
+    fn test_outdated_major() {
+        let candidates = tags(&["1.25.3", "1.27.1", "2.0.0", "3.1.0"]);
+        let results = |current| Results {
+            current: Some(current),
+            candidates: &candidates,
+            ..Results::default()
+        };
+
+        assert_eq!(
+            evaluate(&conditions("outdated:major"), &results("1.25.3"))[0].reason,
+            "3.1.0 is 2 major versions ahead of 1.25.3"
+        );
+        assert_eq!(
+            evaluate(&conditions("outdated:major>=2"), &results("2.0.0")),
+            []
+        );
+        assert_eq!(
+            evaluate(&conditions("outdated:major"), &results("3.0.0")),
+            []
+        );
+    }
+
+    #[test]
+    fn test_no_match() {
+        let triggered = evaluate(&conditions("no-match"), &Results::default());
+        assert_eq!(
+            triggered,
+            [Trigger {
+                condition: Condition::NoMatch,
+                reason: "no tag matched".to_string(),
+            }]
+        );
+        let results = Results {
+            shown: 3,
+            ..Results::default()
+        };
+        assert_eq!(evaluate(&conditions("no-match"), &results), []);
+    }
+
+    #[test]
+    fn test_unsigned() {
+        let results = Results {
+            shown: 3,
+            unsigned: Some(vec!["1.0.1", "1.0.0"]),
+            ..Results::default()
+        };
+        assert_eq!(
+            evaluate(&conditions("unsigned"), &results)[0].reason,
+            "1.0.1, 1.0.0 isn't signed"
+        );
+        let results = Results {
+            shown: 3,
+            unsigned: Some(Vec::new()),
+            ..Results::default()
+        };
+        assert_eq!(evaluate(&conditions("unsigned"), &results), []);
+        // signatures weren't looked up
+        assert_eq!(evaluate(&conditions("unsigned"), &Results::default()), []);
+    }
+
+    #[test]
+    fn test_combinations() {
+        let candidates = tags(&["1.25.3", "1.27.1", "2.0.0"]);
+        let results = Results {
+            current: Some("1.25.3"),
+            candidates: &candidates,
+            shown: 3,
+            unsigned: Some(vec!["2.0.0"]),
+        };
+
+        // any condition fails the run, each named in the order given
+        let triggered = evaluate(&conditions("no-match,unsigned,outdated:major"), &results);
+        let names: Vec<_> = triggered
+            .iter()
+            .map(|trigger| trigger.condition.to_string())
+            .collect();
+        assert_eq!(names, ["unsigned", "outdated:major"]);
+
+        let triggered = evaluate(&conditions("no-match,outdated:major>=2"), &results);
+        assert_eq!(triggered, []);
+    }
+}
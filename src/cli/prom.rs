@@ -0,0 +1,209 @@
+use std::{collections::HashSet, fmt::Write};
+
+use docker_tags::{SortKey, Tag, UpgradePolicy, UpgradeScope, suggest_upgrade};
+
+/// Metrics of the images listed, in the Prometheus text exposition format
+/// which node_exporter's textfile collector reads
+///
+/// Each image has one series per metric at most, whatever its number of
+/// tags, so the labels never multiply with the tags of a repository.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    images: Vec<ImageMetrics>,
+}
+
+#[derive(Debug)]
+struct ImageMetrics {
+    image: String,
+    /// Tag in use, as given along with the image
+    current: Option<String>,
+    /// What the listing found, `None` if it failed
+    listing: Option<ListingMetrics>,
+}
+
+#[derive(Debug)]
+struct ListingMetrics {
+    total: usize,
+    latest: Option<String>,
+    newer: Option<bool>,
+}
+
+/// Name, help and the series of each metric, by image
+type Family = (
+    &'static str,
+    &'static str,
+    fn(&ImageMetrics) -> Option<(Vec<(&'static str, &str)>, usize)>,
+);
+
+const FAMILIES: [Family; 4] = [
+    (
+        "docker_tags_total",
+        "Tags the registry lists for the image",
+        |image| {
+            let listing = image.listing.as_ref()?;
+            Some((vec![("image", image.image.as_str())], listing.total))
+        },
+    ),
+    (
+        "docker_tags_latest_semver_info",
+        "Newest stable semantic version among the tags kept for the image",
+        |image| {
+            let latest = image.listing.as_ref()?.latest.as_deref()?;
+            Some((vec![("image", image.image.as_str()), ("tag", latest)], 1))
+        },
+    ),
+    (
+        "docker_tags_newer_available",
+        "Whether a newer version than the tag in use is available, 1 if so",
+        |image| {
+            let newer = image.listing.as_ref()?.newer?;
+            let current = image.current.as_deref()?;
+            Some((
+                vec![("image", image.image.as_str()), ("current_tag", current)],
+                newer as usize,
+            ))
+        },
+    ),
+    (
+        "docker_tags_scrape_error",
+        "Whether listing the image's tags failed, 1 if so",
+        |image| {
+            let failed = image.listing.is_none();
+            Some((vec![("image", image.image.as_str())], failed as usize))
+        },
+    ),
+];
+
+impl Metrics {
+    /// `image`, with `current` in use, listed `total` tags of which the
+    /// selection kept `tags`
+    pub fn listed(&mut self, image: &str, current: Option<&str>, total: usize, tags: &[Tag]) {
+        let latest = tags
+            .iter()
+            .filter(|tag| matches!(tag.sort_key(), SortKey::Semver(v) if v.pre.is_empty()))
+            .max_by_key(|tag| tag.sort_key());
+        let policy = UpgradePolicy {
+            scope: UpgradeScope::Latest,
+            ..UpgradePolicy::default()
+        };
+        let newer = current.map(|current| suggest_upgrade(current, tags, policy).is_some());
+        self.images.push(ImageMetrics {
+            image: image.to_string(),
+            current: current.map(str::to_string),
+            listing: Some(ListingMetrics {
+                total,
+                latest: latest.map(|tag| tag.name().to_string()),
+                newer,
+            }),
+        });
+    }
+
+    /// Listing `image`, with `current` in use, failed
+    pub fn failed(&mut self, image: &str, current: Option<&str>) {
+        self.images.push(ImageMetrics {
+            image: image.to_string(),
+            current: current.map(str::to_string),
+            listing: None,
+        });
+    }
+
+    /// `# HELP` and `# TYPE` lines then the series of each metric, an image
+    /// given twice keeping its first series
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, help, series) in FAMILIES {
+            let _ = writeln!(text, "# HELP {name} {}", escape_help(help));
+            let _ = writeln!(text, "# TYPE {name} gauge");
+            let mut seen = HashSet::new();
+            for (labels, value) in self.images.iter().filter_map(series) {
+                let labels: Vec<_> = labels
+                    .iter()
+                    .map(|(label, value)| format!("{label}=\"{}\"", escape_label(value)))
+                    .collect();
+                let labels = labels.join(",");
+                if seen.insert(labels.clone()) {
+                    let _ = writeln!(text, "{name}{{{labels}}} {value}");
+                }
+            }
+        }
+        text.trim_end().to_string()
+    }
+}
+
+/// A label value between double quotes, its backslashes, quotes and line
+/// feeds escaped
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Help text, its backslashes and line feeds escaped
+fn escape_help(help: &str) -> String {
+    help.replace('\\', r"\\").replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<Tag> {
+        names.iter().map(|&name| Tag::new(name)).collect()
+    }
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+        metrics.listed(
+            "nginx",
+            Some("1.25.3"),
+            5,
+            &tags(&["1.27.0-rc1", "1.26.2", "1.25.3", "latest"]),
+        );
+        metrics.listed("redis", None, 2, &tags(&["latest", "alpine"]));
+        metrics.failed("ghcr.io/org/app", Some("1.0"));
+        // the second time adds nothing
+        metrics.listed("nginx", Some("1.25.3"), 5, &tags(&["1.26.2"]));
+        assert_eq!(
+            metrics.render(),
+            "# HELP docker_tags_total Tags the registry lists for the image\n\
+             # TYPE docker_tags_total gauge\n\
+             docker_tags_total{image=\"nginx\"} 5\n\
+             docker_tags_total{image=\"redis\"} 2\n\
+             # HELP docker_tags_latest_semver_info Newest stable semantic version among the tags kept for the image\n\
+             # TYPE docker_tags_latest_semver_info gauge\n\
+             docker_tags_latest_semver_info{image=\"nginx\",tag=\"1.26.2\"} 1\n\
+             # HELP docker_tags_newer_available Whether a newer version than the tag in use is available, 1 if so\n\
+             # TYPE docker_tags_newer_available gauge\n\
+             docker_tags_newer_available{image=\"nginx\",current_tag=\"1.25.3\"} 1\n\
+             # HELP docker_tags_scrape_error Whether listing the image's tags failed, 1 if so\n\
+             # TYPE docker_tags_scrape_error gauge\n\
+             docker_tags_scrape_error{image=\"nginx\"} 0\n\
+             docker_tags_scrape_error{image=\"redis\"} 0\n\
+             docker_tags_scrape_error{image=\"ghcr.io/org/app\"} 1"
+        );
+    }
+
+    #[test]
+    fn test_newer_available() {
+        let newer = |current, names: &[&str]| {
+            let mut metrics = Metrics::default();
+            metrics.listed("app", Some(current), names.len(), &tags(names));
+            metrics.images[0].listing.as_ref().unwrap().newer
+        };
+        assert_eq!(newer("1.25.3", &["1.25.3", "2.0.0"]), Some(true));
+        assert_eq!(
+            newer("2.0.0", &["1.25.3", "2.0.0", "2.1.0-rc1"]),
+            Some(false)
+        );
+        assert_eq!(newer("latest", &["1.25.3"]), Some(false));
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(escape_label(r#"a"b\c"#), r#"a\"b\\c"#);
+        assert_eq!(escape_label("a\nb"), r"a\nb");
+        assert_eq!(escape_help("a\\b\n\"c\""), "a\\\\b\\n\"c\"");
+    }
+}
@@ -0,0 +1,243 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Result, bail};
+use docker_tags::{
+    Selection, collate,
+    unstable::time::{format_rfc3339, parse_rfc3339},
+};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, PublishedReport, PublishedTag},
+};
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+/// A bound of the window `published` looks at, as given on the command line
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Moment {
+    /// A whole day in UTC (`2026-03-03`), by its first instant
+    Day(SystemTime),
+    /// An exact time (`2026-03-03T12:00:00+01:00`)
+    At(SystemTime),
+    /// So long before now (`-48h`)
+    Ago(Duration),
+    /// The time the command runs
+    Now,
+}
+
+impl Moment {
+    /// The time a window starts at when this is its `--from`
+    fn start(self, now: SystemTime) -> SystemTime {
+        match self {
+            Moment::Day(at) | Moment::At(at) => at,
+            Moment::Ago(ago) => now - ago,
+            Moment::Now => now,
+        }
+    }
+
+    /// The time a window ends at when this is its `--to`: the end of a day
+    fn end(self, now: SystemTime) -> SystemTime {
+        match self {
+            Moment::Day(at) => at + DAY,
+            moment => moment.start(now),
+        }
+    }
+}
+
+/// Parse `2026-03-03`, an RFC 3339 timestamp, `now` or a duration before now
+/// in seconds, minutes, hours, days or weeks (`-90m`, `-48h`, `-2w`)
+pub fn parse_moment(value: &str) -> Result<Moment, String> {
+    const EXPECTED: &str = "expected a date (2026-03-03), an RFC 3339 time \
+                            (2026-03-03T12:00:00Z), `now` or a duration ago (-48h)";
+    if value == "now" {
+        return Ok(Moment::Now);
+    }
+    if let Some(ago) = value.strip_prefix('-') {
+        let (i, seconds) = match ago.char_indices().last() {
+            Some((i, 's')) => (i, 1),
+            Some((i, 'm')) => (i, 60),
+            Some((i, 'h')) => (i, 3600),
+            Some((i, 'd')) => (i, 86_400),
+            Some((i, 'w')) => (i, 7 * 86_400),
+            _ => return Err(EXPECTED.to_string()),
+        };
+        return match ago[..i].parse::<u64>() {
+            Ok(count) if ago[..i].bytes().all(|b| b.is_ascii_digit()) => count
+                .checked_mul(seconds)
+                .map(|seconds| Moment::Ago(Duration::from_secs(seconds)))
+                .ok_or_else(|| format!("{value} is too long ago")),
+            _ => Err(EXPECTED.to_string()),
+        };
+    }
+    if value.len() == 10 {
+        return parse_rfc3339(&format!("{value}T00:00:00Z"))
+            .map(Moment::Day)
+            .ok_or_else(|| EXPECTED.to_string());
+    }
+    parse_rfc3339(value)
+        .map(Moment::At)
+        .ok_or_else(|| EXPECTED.to_string())
+}
+
+/// Times a tag may have been published at to be listed: from `from` on, up to
+/// but excluding `to`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Window {
+    pub from: SystemTime,
+    pub to: SystemTime,
+}
+
+impl Window {
+    /// The window from `from` to `to` (now if not given); a day is included
+    /// whole at either end
+    pub fn new(from: Moment, to: Option<Moment>, now: SystemTime) -> Result<Self> {
+        let window = Window {
+            from: from.start(now),
+            to: to.unwrap_or(Moment::Now).end(now),
+        };
+        if window.from >= window.to {
+            bail!(
+                "--from {} isn't before --to {}",
+                format_rfc3339(window.from),
+                format_rfc3339(window.to)
+            );
+        }
+        Ok(window)
+    }
+
+    pub fn contains(&self, at: SystemTime) -> bool {
+        self.from <= at && at < self.to
+    }
+}
+
+/// Print the tags picked by `selection` which were published within
+/// `window`, oldest first, then those whose date couldn't be found
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    name: &str,
+    window: Window,
+    selection: &Selection,
+    format: Format,
+    quiet: bool,
+) -> Result<()> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+    let selection = registry.selection_for(&image, selection);
+    let candidates = selection
+        .filtering()
+        .apply(client.fetch_tags(&image).await?);
+
+    let (created, warnings) = client.fetch_created(&image, &candidates).await;
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    let unknown: Vec<_> = candidates
+        .iter()
+        .filter(|tag| !created.iter().any(|(dated, _)| dated == *tag))
+        .map(|tag| tag.name().to_string())
+        .collect();
+    let mut published: Vec<_> = created
+        .into_iter()
+        .filter(|(_, at)| window.contains(*at))
+        .collect();
+    published
+        .sort_by(|(a, a_at), (b, b_at)| a_at.cmp(b_at).then_with(|| collate(a.name(), b.name())));
+    let tags: Vec<_> = selection
+        .arrange(published)
+        .into_iter()
+        .map(|(tag, at)| PublishedTag {
+            name: tag.name().to_string(),
+            published: format_rfc3339(at),
+        })
+        .collect();
+
+    match format {
+        Format::Text => {
+            for tag in &tags {
+                out.line(format!("{}\t{}", tag.published, tag.name))?;
+            }
+            for name in &unknown {
+                out.line(format!("unknown date\t{name}"))?;
+            }
+            Ok(())
+        }
+        format => out.document(
+            format,
+            &PublishedReport {
+                from: format_rfc3339(window.from),
+                to: format_rfc3339(window.to),
+                tags,
+                unknown,
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_moment() {
+        let day = parse_rfc3339("2026-03-03T00:00:00Z").unwrap();
+        assert_eq!(parse_moment("2026-03-03"), Ok(Moment::Day(day)));
+        assert_eq!(
+            parse_moment("2026-03-03T01:00:00+01:00"),
+            Ok(Moment::At(day))
+        );
+        assert_eq!(parse_moment("now"), Ok(Moment::Now));
+        assert_eq!(
+            parse_moment("-48h"),
+            Ok(Moment::Ago(Duration::from_secs(48 * 3600)))
+        );
+        assert_eq!(
+            parse_moment("-2w"),
+            Ok(Moment::Ago(Duration::from_secs(14 * 86_400)))
+        );
+        assert_eq!(
+            parse_moment("-90s"),
+            Ok(Moment::Ago(Duration::from_secs(90)))
+        );
+        for value in [
+            "-48",
+            "-h",
+            "-+4h",
+            "48h",
+            "2026-02-30",
+            "2026-3-3",
+            "yesterday",
+        ] {
+            assert!(parse_moment(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn test_window() {
+        let at = |timestamp| parse_rfc3339(timestamp).unwrap();
+        let now = at("2026-03-10T12:00:00Z");
+        let moment = |value: &str| parse_moment(value).unwrap();
+
+        // both days whole
+        let window = Window::new(moment("2026-03-03"), Some(moment("2026-03-05")), now).unwrap();
+        assert!(!window.contains(at("2026-03-02T23:59:59.999Z")));
+        assert!(window.contains(at("2026-03-03T00:00:00Z")));
+        assert!(window.contains(at("2026-03-05T23:59:59.999Z")));
+        assert!(!window.contains(at("2026-03-06T00:00:00Z")));
+
+        // an exact end is left out
+        let end = parse_moment("2026-03-05T00:00:00Z").unwrap();
+        let window = Window::new(moment("2026-03-03"), Some(end), now).unwrap();
+        assert!(!window.contains(at("2026-03-05T00:00:00Z")));
+
+        let window = Window::new(moment("-48h"), None, now).unwrap();
+        assert_eq!(window.from, at("2026-03-08T12:00:00Z"));
+        assert_eq!(window.to, now);
+
+        assert!(Window::new(moment("2026-03-05"), Some(moment("-10d")), now).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use docker_tags::{Descriptor, unstable::Reference};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{ArtifactEntry, Format, ReferrersReport},
+};
+
+/// Print the artifacts attached to `reference`, grouped by artifact type
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    reference: &str,
+    format: Format,
+) -> Result<()> {
+    let (image, tag) = registry.parse_reference(reference)?;
+    let client = registry.client(&image)?;
+
+    let digest = client.fetch_digest(&image, tag).await?;
+    let referrers = client.list_referrers(&image, &digest).await?;
+    if format != Format::Text {
+        return out.document(
+            format,
+            &report(&image.to_string(), tag, &digest, &referrers),
+        );
+    }
+    if referrers.is_empty() {
+        eprintln!(
+            "No artifacts attached to {}",
+            Reference::new(image.clone()).with_digest(&digest)
+        );
+    }
+    for line in format_groups(&referrers) {
+        out.line(line)?;
+    }
+
+    Ok(())
+}
+
+/// `referrers` by artifact type, or media type if they have none
+fn groups(referrers: &[Descriptor]) -> BTreeMap<&str, Vec<&Descriptor>> {
+    let mut groups: BTreeMap<&str, Vec<&Descriptor>> = BTreeMap::new();
+    for referrer in referrers {
+        let kind = referrer
+            .artifact_type
+            .as_deref()
+            .unwrap_or(&referrer.media_type);
+        groups.entry(kind).or_default().push(referrer);
+    }
+    groups
+}
+
+fn report(image: &str, tag: &str, digest: &str, referrers: &[Descriptor]) -> ReferrersReport {
+    let artifacts = groups(referrers)
+        .into_iter()
+        .map(|(kind, referrers)| {
+            let entries = referrers
+                .into_iter()
+                .map(|referrer| ArtifactEntry {
+                    digest: referrer.digest.clone(),
+                    size: referrer.size,
+                })
+                .collect();
+            (kind.to_string(), entries)
+        })
+        .collect();
+    ReferrersReport {
+        image: image.to_string(),
+        tag: tag.to_string(),
+        digest: digest.to_string(),
+        artifacts,
+    }
+}
+
+fn format_groups(referrers: &[Descriptor]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (kind, referrers) in groups(referrers) {
+        lines.push(kind.to_string());
+        for referrer in referrers {
+            lines.push(format!("  {} ({} bytes)", referrer.digest, referrer.size));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn referrers() -> Vec<Descriptor> {
+        serde_json::from_str(
+            r#"[
+                {"mediaType":"application/vnd.oci.image.manifest.v1+json","artifactType":"application/spdx+json","digest":"sha256:bb","size":2},
+                {"mediaType":"application/vnd.oci.image.manifest.v1+json","artifactType":"application/vnd.dev.cosign.artifact.sig.v1+json","digest":"sha256:aa","size":1},
+                {"mediaType":"application/vnd.oci.image.manifest.v1+json","artifactType":"application/spdx+json","digest":"sha256:cc","size":3}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_format_groups() {
+        assert_eq!(
+            format_groups(&referrers()),
+            [
+                "application/spdx+json",
+                "  sha256:bb (2 bytes)",
+                "  sha256:cc (3 bytes)",
+                "application/vnd.dev.cosign.artifact.sig.v1+json",
+                "  sha256:aa (1 bytes)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let report = report("docker.io/library/app", "1.0", "sha256:ff", &referrers());
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "image": "docker.io/library/app",
+                "tag": "1.0",
+                "digest": "sha256:ff",
+                "artifacts": {
+                    "application/spdx+json": [
+                        {"digest": "sha256:bb", "size": 2},
+                        {"digest": "sha256:cc", "size": 3},
+                    ],
+                    "application/vnd.dev.cosign.artifact.sig.v1+json": [
+                        {"digest": "sha256:aa", "size": 1},
+                    ],
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,647 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, anyhow};
+use docker_tags::{Selection, Tag, TagFilter, UpgradePolicy, UpgradeScope, VersionRange};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Top-level options read, or that don't bear on versions
+const TOP_LEVEL: &[&str] = &["$schema", "packageRules", "ignoreDeps", "description"];
+
+/// Upgrade policies read from a Renovate config (`renovate.json`) or a
+/// Dependabot one (`dependabot.yml`)
+///
+/// Only what decides which tag an image may move to is read: `ignoreDeps`
+/// and the `packageRules` matching images by name with `enabled`,
+/// `allowedVersions`, `ignoreUnstable` and `matchUpdateTypes`, or the
+/// `ignore` entries of Dependabot's `docker` updates. Other options are
+/// reported by [`PolicyFile::warnings`] and otherwise ignored.
+#[derive(Debug, Default)]
+pub struct PolicyFile {
+    ignore_deps: Vec<String>,
+    rules: Vec<Rule>,
+    warnings: Vec<String>,
+}
+
+/// A `packageRules` entry applying to Docker images
+#[derive(Debug, Default)]
+struct Rule {
+    names: Vec<NameMatcher>,
+    enabled: Option<bool>,
+    allowed: Option<AllowedVersions>,
+    ignore_unstable: Option<bool>,
+    /// `matchUpdateTypes` of a rule disabling them
+    disabled_updates: Vec<UpdateType>,
+}
+
+#[derive(Debug)]
+enum NameMatcher {
+    Exact(String),
+    Pattern { re: Regex, negated: bool },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum UpdateType {
+    Major,
+    Minor,
+    Patch,
+}
+
+/// `allowedVersions`: a `/regex/` (`!/regex/` for tags not matching it) or
+/// a range like `>=1.2 <2` whose alternatives are separated by `||`
+#[derive(Clone, Debug)]
+pub enum AllowedVersions {
+    Pattern {
+        re: Regex,
+        negated: bool,
+    },
+    Range(VersionRange),
+    /// Dependabot's ignored `versions`, of all the entries for an image
+    Excluded(Vec<VersionRange>),
+}
+
+/// How an image is to be upgraded after the rules matching it
+#[derive(Clone, Debug)]
+pub enum ImagePolicy {
+    /// Ignored, or disabled by a rule
+    Ignored,
+    Check {
+        policy: UpgradePolicy,
+        /// Tags which may be suggested
+        selection: Selection,
+        allowed: Option<AllowedVersions>,
+    },
+}
+
+impl PolicyFile {
+    /// Read a Renovate config, or a Dependabot one if it's a YAML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+        let yaml = path
+            .extension()
+            .is_some_and(|ext| ext == "yml" || ext == "yaml");
+        match yaml {
+            true => PolicyFile::parse_dependabot(&text),
+            false => PolicyFile::parse(&text),
+        }
+        .with_context(|| format!("Failed to read the policies of {path:?}"))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Map<String, Value> =
+            serde_json::from_str(text).context("Invalid Renovate config")?;
+        let mut file = PolicyFile::default();
+        for (key, value) in &config {
+            match key.as_str() {
+                "ignoreDeps" => file.ignore_deps = strings(key, value)?,
+                "packageRules" => {
+                    let rules = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("packageRules isn't a list"))?;
+                    for (i, rule) in rules.iter().enumerate() {
+                        let rule = rule
+                            .as_object()
+                            .ok_or_else(|| anyhow!("packageRules[{i}] isn't an object"))?;
+                        if let Some(rule) = file.parse_rule(i, rule)? {
+                            file.rules.push(rule);
+                        }
+                    }
+                }
+                "extends" => file
+                    .warnings
+                    .push("extends: presets aren't read, only this file's rules".to_string()),
+                key if TOP_LEVEL.contains(&key) => {}
+                key => file.warnings.push(format!("{key}: unsupported, ignored")),
+            }
+        }
+        Ok(file)
+    }
+
+    /// Policies of a Dependabot config: each `ignore` entry of the `docker`
+    /// updates becomes a rule for the images its `dependency-name` (with `*`
+    /// wildcards) matches, ignoring them, the update types of its
+    /// `update-types` or the ranges of its `versions`
+    pub fn parse_dependabot(text: &str) -> Result<Self> {
+        let config: Map<String, Value> =
+            serde_yaml_ng::from_str(text).context("Invalid Dependabot config")?;
+        let mut file = PolicyFile::default();
+        for (key, value) in &config {
+            match key.as_str() {
+                "updates" => {
+                    let updates = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("updates isn't a list"))?;
+                    for (i, update) in updates.iter().enumerate() {
+                        let update = update
+                            .as_object()
+                            .ok_or_else(|| anyhow!("updates[{i}] isn't a map"))?;
+                        file.parse_update(i, update)?;
+                    }
+                }
+                "version" => {}
+                key => file.warnings.push(format!("{key}: unsupported, ignored")),
+            }
+        }
+        Ok(file)
+    }
+
+    /// The rules of the `ignore` entries at `updates[i]`, none unless it's
+    /// for the `docker` ecosystem
+    fn parse_update(&mut self, i: usize, options: &Map<String, Value>) -> Result<()> {
+        if options.get("package-ecosystem").and_then(Value::as_str) != Some("docker") {
+            return Ok(());
+        }
+        for (key, value) in options {
+            let at = format!("updates[{i}].{key}");
+            match key.as_str() {
+                "ignore" => {
+                    let entries = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("{at} isn't a list"))?;
+                    for (j, entry) in entries.iter().enumerate() {
+                        let at = format!("{at}[{j}]");
+                        let entry = entry
+                            .as_object()
+                            .ok_or_else(|| anyhow!("{at} isn't a map"))?;
+                        let rule = self.parse_ignore(&at, entry)?;
+                        self.rules.push(rule);
+                    }
+                }
+                // where and when to look, not which versions to take
+                "package-ecosystem"
+                | "directory"
+                | "directories"
+                | "schedule"
+                | "open-pull-requests-limit"
+                | "labels"
+                | "reviewers"
+                | "assignees"
+                | "commit-message"
+                | "groups"
+                | "registries"
+                | "target-branch" => {}
+                _ => self.warnings.push(format!("{at}: unsupported, ignored")),
+            }
+        }
+        Ok(())
+    }
+
+    /// The rule of an `ignore` entry
+    fn parse_ignore(&mut self, at: &str, options: &Map<String, Value>) -> Result<Rule> {
+        let mut rule = Rule::default();
+        for (key, value) in options {
+            let at = format!("{at}.{key}");
+            match key.as_str() {
+                "dependency-name" => {
+                    let name = value
+                        .as_str()
+                        .ok_or_else(|| anyhow!("{at} isn't a string"))?;
+                    rule.names.push(NameMatcher::wildcard(name, &at)?);
+                }
+                "versions" => {
+                    let ranges = strings(&at, value)?
+                        .iter()
+                        .map(|range| VersionRange::parse(&dependabot_range(range)))
+                        .collect::<Result<_>>()
+                        .context(at)?;
+                    rule.allowed = Some(AllowedVersions::Excluded(ranges));
+                }
+                "update-types" => {
+                    for update in strings(&at, value)? {
+                        match update.as_str() {
+                            "version-update:semver-major" => {
+                                rule.disabled_updates.push(UpdateType::Major)
+                            }
+                            "version-update:semver-minor" => {
+                                rule.disabled_updates.push(UpdateType::Minor)
+                            }
+                            "version-update:semver-patch" => {
+                                rule.disabled_updates.push(UpdateType::Patch)
+                            }
+                            other => self
+                                .warnings
+                                .push(format!("{at}: update type {other:?} unsupported, ignored")),
+                        }
+                    }
+                }
+                _ => self.warnings.push(format!("{at}: unsupported, ignored")),
+            }
+        }
+        if rule.names.is_empty() {
+            return Err(anyhow!("{at} has no dependency-name"));
+        }
+        if rule.allowed.is_none() && !options.contains_key("update-types") {
+            rule.enabled = Some(false);
+        }
+        Ok(rule)
+    }
+
+    /// The rule at `packageRules[i]`, `None` if it's for other datasources
+    fn parse_rule(&mut self, i: usize, options: &Map<String, Value>) -> Result<Option<Rule>> {
+        let mut rule = Rule::default();
+        let mut update_types = Vec::new();
+        for (key, value) in options {
+            let at = format!("packageRules[{i}].{key}");
+            match key.as_str() {
+                "matchPackageNames" | "matchDepNames" => {
+                    for name in strings(&at, value)? {
+                        rule.names.push(NameMatcher::parse(&name, &at)?);
+                    }
+                }
+                "matchPackagePatterns" => {
+                    for pattern in strings(&at, value)? {
+                        let re = super::regex(&pattern).with_context(|| at.clone())?;
+                        rule.names.push(NameMatcher::Pattern { re, negated: false });
+                    }
+                }
+                "matchDatasources" => {
+                    if !strings(&at, value)?.iter().any(|source| source == "docker") {
+                        return Ok(None);
+                    }
+                }
+                "enabled" => rule.enabled = Some(boolean(&at, value)?),
+                "ignoreUnstable" => rule.ignore_unstable = Some(boolean(&at, value)?),
+                "allowedVersions" => {
+                    let allowed = value
+                        .as_str()
+                        .ok_or_else(|| anyhow!("{at} isn't a string"))?;
+                    rule.allowed = Some(AllowedVersions::parse(allowed).context(at)?);
+                }
+                "matchUpdateTypes" => {
+                    for update in strings(&at, value)? {
+                        match update.as_str() {
+                            "major" => update_types.push(UpdateType::Major),
+                            "minor" => update_types.push(UpdateType::Minor),
+                            "patch" => update_types.push(UpdateType::Patch),
+                            other => self
+                                .warnings
+                                .push(format!("{at}: update type {other:?} unsupported, ignored")),
+                        }
+                    }
+                }
+                "versioning" => {
+                    if value.as_str() != Some("docker") {
+                        self.warnings
+                            .push(format!("{at}: only docker versioning is supported"));
+                    }
+                }
+                "description" | "matchManagers" => {}
+                _ => self.warnings.push(format!("{at}: unsupported, ignored")),
+            }
+        }
+        if !update_types.is_empty() {
+            if rule.enabled == Some(false) {
+                rule.enabled = None;
+                rule.disabled_updates = update_types;
+            } else {
+                // the rule only sets options this file doesn't read for them
+                self.warnings.push(format!(
+                    "packageRules[{i}]: matchUpdateTypes is only supported with enabled: false, rule ignored"
+                ));
+                return Ok(None);
+            }
+        }
+        Ok(Some(rule))
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// How to upgrade `name`, as written in the scanned file, the rules
+    /// matching it applying in order so that later ones win
+    pub fn policy(&self, name: &str, mut policy: UpgradePolicy) -> ImagePolicy {
+        if self.ignore_deps.iter().any(|ignored| ignored == name) {
+            return ImagePolicy::Ignored;
+        }
+        let mut allowed = None;
+        let mut disabled = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.matches(name)) {
+            if rule.enabled == Some(false) {
+                return ImagePolicy::Ignored;
+            }
+            if let Some(rule_allowed) = &rule.allowed {
+                allowed = Some(match (allowed, rule_allowed) {
+                    // every ignored version stays ignored
+                    (
+                        Some(AllowedVersions::Excluded(mut ranges)),
+                        AllowedVersions::Excluded(more),
+                    ) => {
+                        ranges.extend(more.iter().cloned());
+                        AllowedVersions::Excluded(ranges)
+                    }
+                    (_, rule_allowed) => rule_allowed.clone(),
+                });
+            }
+            if let Some(ignore_unstable) = rule.ignore_unstable {
+                policy.pre_releases = !ignore_unstable;
+            }
+            disabled.extend(&rule.disabled_updates);
+        }
+
+        let enabled = |update| !disabled.contains(&update);
+        match (
+            enabled(UpdateType::Major),
+            enabled(UpdateType::Minor),
+            enabled(UpdateType::Patch),
+        ) {
+            (false, false, false) => return ImagePolicy::Ignored,
+            (false, false, _) => policy.scope = UpgradeScope::Patch,
+            (false, true, _) => policy.scope = policy.scope.min(UpgradeScope::Minor),
+            _ => {}
+        }
+
+        let mut selection = Selection {
+            filters: vec![TagFilter::version_like()],
+            ..Selection::default()
+        };
+        if let Some(AllowedVersions::Pattern { re, negated: false }) = &allowed {
+            selection.filters.push(TagFilter::pattern(re.clone()));
+            allowed = None;
+        }
+        ImagePolicy::Check {
+            policy,
+            selection,
+            allowed,
+        }
+    }
+}
+
+impl Rule {
+    /// Whether the rule is for `name`: every image without name matchers,
+    /// else those any positive matcher takes and no negated one
+    fn matches(&self, name: &str) -> bool {
+        let (negated, positive): (Vec<_>, Vec<_>) = self
+            .names
+            .iter()
+            .partition(|matcher| matches!(matcher, NameMatcher::Pattern { negated: true, .. }));
+        (positive.is_empty() || positive.iter().any(|matcher| matcher.matches(name)))
+            && negated.iter().all(|matcher| matcher.matches(name))
+    }
+}
+
+impl NameMatcher {
+    /// An exact name, a `/regex/` or a `!/regex/`
+    fn parse(name: &str, at: &str) -> Result<Self> {
+        let (negated, pattern) = match name.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, name),
+        };
+        match pattern
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            Some(pattern) => Ok(NameMatcher::Pattern {
+                re: super::regex(pattern).with_context(|| at.to_string())?,
+                negated,
+            }),
+            None if negated => Err(anyhow!("{at}: only regexes can be negated, not {name:?}")),
+            None => Ok(NameMatcher::Exact(name.to_string())),
+        }
+    }
+
+    /// A Dependabot `dependency-name`, whose `*` match any characters
+    fn wildcard(name: &str, at: &str) -> Result<Self> {
+        if !name.contains('*') {
+            return Ok(NameMatcher::Exact(name.to_string()));
+        }
+        let parts: Vec<_> = name.split('*').map(regex::escape).collect();
+        let re =
+            super::regex(&format!("^{}$", parts.join(".*"))).with_context(|| at.to_string())?;
+        Ok(NameMatcher::Pattern { re, negated: false })
+    }
+
+    /// Whether `name` passes: matched, or not matched when negated
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Exact(exact) => exact == name,
+            NameMatcher::Pattern { re, negated } => re.is_match(name) != *negated,
+        }
+    }
+}
+
+impl AllowedVersions {
+    pub fn parse(allowed: &str) -> Result<Self> {
+        let (negated, pattern) = match allowed.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, allowed),
+        };
+        if let Some(pattern) = pattern
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            let re = super::regex(pattern)?;
+            return Ok(AllowedVersions::Pattern { re, negated });
+        }
+
+        Ok(AllowedVersions::Range(VersionRange::parse(allowed)?))
+    }
+
+    /// Whether `tag` is allowed; tags which aren't versions are only allowed
+    /// by regexes
+    pub fn matches(&self, tag: &Tag) -> bool {
+        match self {
+            AllowedVersions::Pattern { re, negated } => re.is_match(tag.name()) != *negated,
+            AllowedVersions::Range(range) => range.matches(tag),
+            AllowedVersions::Excluded(ranges) => !ranges.iter().any(|range| range.matches(tag)),
+        }
+    }
+}
+
+/// A Dependabot range such as `>= 8, < 9` as a [`VersionRange`], without
+/// the spaces after its operators
+fn dependabot_range(range: &str) -> String {
+    let mut out = String::new();
+    for c in range.chars() {
+        let after_operator = out.ends_with(['<', '>', '=']);
+        if !(c.is_whitespace() && after_operator) {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn strings(key: &str, value: &Value) -> Result<Vec<String>> {
+    match value {
+        Value::String(s) => Ok(vec![s.clone()]),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("{key} isn't a list of strings"))
+            })
+            .collect(),
+        _ => Err(anyhow!("{key} isn't a list of strings")),
+    }
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow!("{key} isn't true or false"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(range: &str, names: &[&str]) -> Vec<String> {
+        let allowed = AllowedVersions::parse(range).unwrap();
+        names
+            .iter()
+            .map(|name| Tag::new(*name))
+            .filter(|tag| allowed.matches(tag))
+            .map(|tag| tag.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_allowed_versions() {
+        let names = [
+            "1.1.9",
+            "1.2",
+            "1.2.0",
+            "1.2.3-alpine",
+            "1.3.0",
+            "2.0.0-rc1",
+            "2.0.0",
+            "latest",
+        ];
+        assert_eq!(
+            allowed(">=1.2 <2", &names),
+            ["1.2", "1.2.0", "1.2.3-alpine", "1.3.0", "2.0.0-rc1"]
+        );
+        assert_eq!(allowed("<=1.2.0", &names), ["1.1.9", "1.2", "1.2.0"]);
+        assert_eq!(allowed("1.2.x", &names), ["1.2", "1.2.0", "1.2.3-alpine"]);
+        assert_eq!(allowed("=1.2", &names), allowed("1.2", &names));
+        assert_eq!(allowed("1.1 || >=2", &names), ["1.1.9", "2.0.0"]);
+        assert_eq!(allowed("/-alpine$/", &names), ["1.2.3-alpine"]);
+        assert_eq!(
+            allowed("!/^1\\./", &names),
+            ["2.0.0-rc1", "2.0.0", "latest"]
+        );
+
+        for range in ["", "<", ">=a", "1.2.3.4", "/(/"] {
+            assert!(AllowedVersions::parse(range).is_err(), "{range}");
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let file = PolicyFile::parse(
+            r#"{
+                "extends": ["config:recommended"],
+                "ignoreDeps": ["mcr.microsoft.com/windows/servercore"],
+                "labels": ["deps"],
+                "packageRules": [
+                    {"matchPackageNames": ["postgres"], "allowedVersions": "<17"},
+                    {"matchPackagePatterns": ["^ghcr\\.io/"], "matchUpdateTypes": ["major"], "enabled": false},
+                    {"matchPackageNames": ["/^ghcr\\.io/org/legacy/"], "enabled": false},
+                    {"matchDatasources": ["npm"], "enabled": false},
+                    {"matchPackageNames": ["redis"], "ignoreUnstable": false, "automerge": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            file.warnings(),
+            [
+                "extends: presets aren't read, only this file's rules",
+                "labels: unsupported, ignored",
+                "packageRules[4].automerge: unsupported, ignored",
+            ]
+        );
+
+        let default = UpgradePolicy {
+            scope: UpgradeScope::Latest,
+            ..UpgradePolicy::default()
+        };
+        let check = |name| match file.policy(name, default) {
+            ImagePolicy::Check {
+                policy, allowed, ..
+            } => Some((policy, allowed.is_some())),
+            ImagePolicy::Ignored => None,
+        };
+        assert_eq!(check("mcr.microsoft.com/windows/servercore"), None);
+        assert_eq!(check("ghcr.io/org/legacy"), None);
+        assert_eq!(check("postgres"), Some((default, true)));
+        assert_eq!(check("node"), Some((default, false)));
+        let (policy, _) = check("ghcr.io/org/app").unwrap();
+        assert_eq!(policy.scope, UpgradeScope::Minor);
+        let (policy, _) = check("redis").unwrap();
+        assert!(policy.pre_releases);
+    }
+
+    #[test]
+    fn test_dependabot_policy() {
+        let file = PolicyFile::parse_dependabot(
+            r#"
+version: 2
+updates:
+  - package-ecosystem: npm
+    directory: /
+    ignore:
+      - dependency-name: "*"
+  - package-ecosystem: docker
+    directory: /
+    schedule:
+      interval: weekly
+    rebase-strategy: disabled
+    ignore:
+      - dependency-name: "ghcr.io/org/legacy"
+      - dependency-name: "ghcr.io/*"
+        update-types: ["version-update:semver-major"]
+      - dependency-name: "postgres"
+        versions: [">= 17"]
+      - dependency-name: "postgres"
+        versions: ["16.1", "16.2"]
+        cooldown: 7
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            file.warnings(),
+            [
+                "updates[1].ignore[3].cooldown: unsupported, ignored",
+                "updates[1].rebase-strategy: unsupported, ignored",
+            ]
+        );
+
+        let default = UpgradePolicy {
+            scope: UpgradeScope::Latest,
+            ..UpgradePolicy::default()
+        };
+        let check = |name| match file.policy(name, default) {
+            ImagePolicy::Check {
+                policy, allowed, ..
+            } => Some((policy.scope, allowed)),
+            ImagePolicy::Ignored => None,
+        };
+        assert!(check("ghcr.io/org/legacy").is_none());
+        let (scope, allowed) = check("ghcr.io/org/app").unwrap();
+        assert_eq!((scope, allowed.is_none()), (UpgradeScope::Minor, true));
+        let (scope, allowed) = check("postgres").unwrap();
+        assert_eq!(scope, UpgradeScope::Latest);
+        let allowed = allowed.unwrap();
+        let names = ["15.6", "16.1", "16.2", "16.3", "17.0"];
+        let kept: Vec<_> = names
+            .into_iter()
+            .filter(|name| allowed.matches(&Tag::new(*name)))
+            .collect();
+        assert_eq!(kept, ["15.6", "16.3"]);
+        assert_eq!(
+            check("node").map(|(scope, _)| scope),
+            Some(UpgradeScope::Latest)
+        );
+
+        for config in [
+            "updates: [{package-ecosystem: docker, ignore: [{versions: ['1.x']}]}]",
+            "updates: [{package-ecosystem: docker, ignore: [{dependency-name: app, versions: ['>= a']}]}]",
+            "updates: docker",
+        ] {
+            assert!(PolicyFile::parse_dependabot(config).is_err(), "{config}");
+        }
+    }
+}
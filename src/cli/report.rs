@@ -0,0 +1,518 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use docker_tags::{
+    BuildInfo, LenientVersion, SortKey, Tag, TagStats, WindowsRelease,
+    output::{Formatter, Json, Listing, Plain, Table, Yaml},
+    unstable::time::format_rfc3339,
+};
+use serde::Serialize;
+
+use super::{batch::Status, config::ImageSet, lint::Finding};
+
+/// Format of the listing printed on stdout
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// One tag per line
+    #[default]
+    Text,
+    /// Aligned columns under a header, for tag listings
+    Table,
+    Json,
+    Yaml,
+    /// Prometheus metrics, for node_exporter's textfile collector
+    Prom,
+}
+
+impl Format {
+    /// Render `document` as JSON or YAML, both from the same serde types
+    pub fn render(self, document: &impl Serialize) -> Result<String> {
+        Ok(match self {
+            Format::Text => unreachable!("text output isn't a document"),
+            Format::Table => bail!("--output table is only for tag listings"),
+            Format::Prom => bail!("--output prom is only for tag listings and batch"),
+            Format::Json => serde_json::to_string_pretty(document)?,
+            Format::Yaml => serde_yaml_ng::to_string(document)?.trim_end().to_string(),
+        })
+    }
+
+    /// The formatter writing listings in this format, with a signed/unsigned
+    /// column if `show_signed`
+    pub fn formatter(self, show_signed: bool) -> Result<Box<dyn Formatter>> {
+        Ok(match self {
+            Format::Text => Box::new(Plain { show_signed }),
+            Format::Table => Box::new(Table { show_signed }),
+            Format::Json => Box::new(Json),
+            Format::Yaml => Box::new(Yaml),
+            Format::Prom => bail!("--output prom is only for tag listings and batch"),
+        })
+    }
+}
+
+/// Output of the `batch` command: the listings of the images which could be
+/// listed, and how each one went
+#[derive(Debug, Default, Serialize)]
+pub struct BatchReport {
+    pub images: Vec<Listing>,
+    pub summary: Vec<ImageSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageSummary {
+    pub image: String,
+    pub status: Status,
+    /// Tags shown, unless listing failed
+    pub tags: Option<usize>,
+    /// The error listing failed with, and its causes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImageSummary {
+    pub fn ok(image: &str, tags: usize) -> Self {
+        ImageSummary {
+            image: image.to_string(),
+            status: Status::Ok,
+            tags: Some(tags),
+            error: None,
+        }
+    }
+
+    pub fn failed(image: &str, status: Status, err: &anyhow::Error) -> Self {
+        ImageSummary {
+            image: image.to_string(),
+            status,
+            tags: None,
+            error: Some(format!("{err:#}")),
+        }
+    }
+}
+
+/// Output of a listing or `stats` for a pattern such as
+/// `harbor.corp/team-*/app`: one document per repository matching it
+#[derive(Debug, Serialize)]
+pub struct GroupedReport<T> {
+    pub pattern: String,
+    pub images: Vec<T>,
+}
+
+impl<T> GroupedReport<T> {
+    pub fn new(pattern: &str) -> Self {
+        GroupedReport {
+            pattern: pattern.to_string(),
+            images: Vec::new(),
+        }
+    }
+}
+
+/// Output of the `stats` command
+#[derive(Debug, Serialize)]
+pub struct StatsReport {
+    pub image: String,
+    pub tags: usize,
+    pub versions: usize,
+    pub pre_releases: usize,
+    pub pre_release_ratio: f64,
+    pub majors: usize,
+    pub minors: usize,
+    pub newest: Option<String>,
+    pub oldest: Option<String>,
+    pub variants: Vec<String>,
+    /// RFC 3339, where the registry tells (Docker Hub)
+    pub last_pushed: Option<String>,
+}
+
+impl StatsReport {
+    pub fn new(image: String, stats: &TagStats) -> Self {
+        StatsReport {
+            image,
+            tags: stats.tags,
+            versions: stats.versions,
+            pre_releases: stats.pre_releases,
+            pre_release_ratio: stats.pre_release_ratio(),
+            majors: stats.majors,
+            minors: stats.minors,
+            newest: stats.newest.as_ref().map(Tag::to_string),
+            oldest: stats.oldest.as_ref().map(Tag::to_string),
+            variants: stats.variants.clone(),
+            last_pushed: stats.last_pushed.map(format_rfc3339),
+        }
+    }
+}
+
+/// Output of the `lint` command
+#[derive(Debug, Serialize)]
+pub struct LintReport {
+    pub findings: Vec<Finding>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// Output of the `published` command, with RFC 3339 timestamps
+#[derive(Debug, Serialize)]
+pub struct PublishedReport {
+    pub from: String,
+    pub to: String,
+    pub tags: Vec<PublishedTag>,
+    /// Tags whose date couldn't be found, which may or may not be in the
+    /// window
+    pub unknown: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublishedTag {
+    pub name: String,
+    pub published: String,
+}
+
+/// Row of the `verify-order` command: what the order of tags derived from a
+/// tag's name
+#[derive(Debug, Serialize)]
+pub struct OrderEntry {
+    pub tag: String,
+    /// `semver`, `windows`, or `opaque` for tags which aren't versions
+    pub scheme: &'static str,
+    /// `versions`, newest first, `windows`, after them by recency, or
+    /// `others`, last alphabetically
+    pub bucket: &'static str,
+    /// Position within the bucket, from 1
+    pub position: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<VersionParts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub windows: Option<WindowsParts>,
+    /// Why an opaque tag isn't a version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// How `--latest-per`, `series` and `stats` read the tag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lenient: Option<LenientParts>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionParts {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_release: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+    /// Whether a leading `v` was dropped
+    pub prefixed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowsParts {
+    pub build: u32,
+    /// Update revision, absent for a release's name such as `ltsc2022`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LenientParts {
+    pub major: u64,
+    pub minor: Option<u64>,
+    pub patch: Option<u64>,
+    pub pre_release: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+impl OrderEntry {
+    /// Entry of `tag` in a listing, a Windows image's if `windows`
+    pub fn new(tag: &Tag, windows: bool, position: usize) -> Self {
+        let key = tag.sort_key_in(windows);
+        let version = match &key {
+            SortKey::Semver(version) => Some(VersionParts {
+                major: version.major,
+                minor: version.minor,
+                patch: version.patch,
+                pre_release: Some(version.pre.to_string()).filter(|pre| !pre.is_empty()),
+                build: Some(version.build.to_string()).filter(|build| !build.is_empty()),
+                prefixed: tag.name().starts_with('v'),
+            }),
+            SortKey::Windows(_) | SortKey::Opaque => None,
+        };
+        let windows = match key {
+            SortKey::Windows(WindowsRelease { build, revision }) => {
+                Some(WindowsParts { build, revision })
+            }
+            _ => None,
+        };
+        let lenient = LenientVersion::parse(tag.name()).map(|lenient| LenientParts {
+            major: lenient.major,
+            minor: lenient.minor,
+            patch: lenient.patch,
+            pre_release: lenient.pre_release,
+            variant: lenient.variant.map(str::to_string),
+        });
+        let (scheme, bucket) = match (&version, &windows) {
+            (Some(_), _) => ("semver", "versions"),
+            (None, Some(_)) => ("windows", "windows"),
+            (None, None) => ("opaque", "others"),
+        };
+        OrderEntry {
+            tag: tag.name().to_string(),
+            scheme,
+            bucket,
+            position,
+            version,
+            windows,
+            reason: tag.opaque_reason().filter(|_| bucket == "others"),
+            lenient,
+        }
+    }
+}
+
+/// Row of the `matrix` command: whether the tag is available for each
+/// requested platform, `None` if its manifest couldn't be fetched
+#[derive(Debug, Serialize)]
+pub struct MatrixRow {
+    pub tag: String,
+    pub platforms: BTreeMap<String, Option<bool>>,
+}
+
+/// Row of `intersect --union`: whether each image has the tag
+#[derive(Debug, Serialize)]
+pub struct PresenceRow {
+    pub tag: String,
+    pub images: BTreeMap<String, bool>,
+}
+
+/// Output of the `resolve` command: the tags pointing to the same manifest
+/// as `tag`, newest version first
+#[derive(Debug, Serialize)]
+pub struct ResolveReport {
+    pub image: String,
+    pub tag: String,
+    pub digest: String,
+    pub aliases: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Output of the `referrers` command: the artifacts attached to the manifest
+/// `tag` points to, by artifact type
+#[derive(Debug, Serialize)]
+pub struct ReferrersReport {
+    pub image: String,
+    pub tag: String,
+    pub digest: String,
+    pub artifacts: BTreeMap<String, Vec<ArtifactEntry>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtifactEntry {
+    pub digest: String,
+    pub size: u64,
+}
+
+/// Output of the `exists` command
+#[derive(Debug, Serialize)]
+pub struct ExistsReport {
+    pub image: String,
+    /// `exists`, `not found` or `access denied`
+    pub outcome: String,
+}
+
+/// Output of the `pin` command: each tagged reference found in the file,
+/// by line
+#[derive(Debug, Serialize)]
+pub struct PinReport {
+    pub file: String,
+    pub references: Vec<PinEntry>,
+    /// Whether the file was rewritten, with `--write`
+    pub written: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PinEntry {
+    pub line: usize,
+    pub reference: String,
+    /// The digest the tag points to now
+    pub digest: String,
+    /// The digest the file pins, if any
+    pub pinned: Option<String>,
+    pub state: PinState,
+    /// Whether the rewritten file pins it to `digest`
+    pub rewritten: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PinState {
+    Current,
+    Missing,
+    Outdated,
+}
+
+/// Output of the `check-upgrades` command: the references which have a newer
+/// tag under their policy
+#[derive(Debug, Serialize)]
+pub struct UpgradesReport {
+    pub file: String,
+    pub upgrades: Vec<UpgradeEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpgradeEntry {
+    pub line: usize,
+    pub reference: String,
+    pub upgrade: String,
+    pub reason: String,
+}
+
+/// Output of `history`: images listed before, most recent first
+#[derive(Debug, Serialize)]
+pub struct HistoryReport {
+    pub images: Vec<String>,
+}
+
+/// Output of `sets list`, and of `sets show` with a single set
+#[derive(Debug, Serialize)]
+pub struct SetsReport {
+    pub sets: BTreeMap<String, ImageSet>,
+}
+
+/// Output of the `token` command
+#[derive(Debug, Serialize)]
+pub struct TokenReport {
+    pub image: String,
+    /// Token service and what it was asked for, unless no token was needed
+    pub realm: Option<String>,
+    pub service: Option<String>,
+    pub scopes: Vec<String>,
+    /// Where the credentials sent to the token service came from
+    pub credentials: String,
+    /// Status of the manifest request made with the token
+    pub status: u16,
+    /// Claims of the token if it's a JWT
+    pub claims: Option<serde_json::Value>,
+    pub rate_limits: BTreeMap<String, String>,
+    /// The token itself, with `--show-token` only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// An error printed as a document instead of plain text
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub error: String,
+    pub causes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// How the binary was built, for bug reports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildInfo>,
+}
+
+impl From<&anyhow::Error> for ErrorReport {
+    fn from(err: &anyhow::Error) -> Self {
+        ErrorReport {
+            error: err.to_string(),
+            causes: err.chain().skip(1).map(ToString::to_string).collect(),
+            hint: super::hint(err),
+            build: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use docker_tags::{Warning, output::TagEntry};
+
+    use super::*;
+
+    fn listing() -> Listing {
+        let mut signed = TagEntry::from(Tag::new("1.1.0"));
+        signed.signed = Some(true);
+        let mut listing = Listing::new(
+            Some("ghcr.io/org/app".to_string()),
+            vec![signed, TagEntry::from(Tag::new("latest"))],
+            &[Warning::DuplicateTag {
+                name: "1.0.0".to_string(),
+            }],
+        );
+        listing.complete = false;
+        (listing.total, listing.matched) = (5, 3);
+        listing
+    }
+
+    #[test]
+    fn test_render_listing_json() {
+        let expected = r#"{
+  "image": "ghcr.io/org/app",
+  "tags": [
+    {
+      "name": "1.1.0",
+      "signed": true
+    },
+    {
+      "name": "latest"
+    }
+  ],
+  "warnings": [
+    "skipped duplicate tag 1.0.0"
+  ],
+  "complete": false,
+  "total": 5,
+  "matched": 3,
+  "shown": 2
+}"#;
+        assert_eq!(Format::Json.render(&listing()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_listing_yaml() {
+        let expected = "\
+image: ghcr.io/org/app
+tags:
+- name: 1.1.0
+  signed: true
+- name: latest
+warnings:
+- skipped duplicate tag 1.0.0
+complete: false
+total: 5
+matched: 3
+shown: 2";
+        assert_eq!(Format::Yaml.render(&listing()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_matrix_json() {
+        let rows = [MatrixRow {
+            tag: "1.27.2".to_string(),
+            platforms: [("linux/amd64", Some(true)), ("linux/arm/v7", None)]
+                .map(|(platform, available)| (platform.to_string(), available))
+                .into(),
+        }];
+        let expected = r#"[
+  {
+    "tag": "1.27.2",
+    "platforms": {
+      "linux/amd64": true,
+      "linux/arm/v7": null
+    }
+  }
+]"#;
+        assert_eq!(Format::Json.render(&rows).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_error() {
+        let err = anyhow!("401 Unauthorized").context("Image not found");
+        let report = ErrorReport::from(&err);
+        assert_eq!(
+            Format::Yaml.render(&report).unwrap(),
+            "error: Image not found\ncauses:\n- 401 Unauthorized"
+        );
+        assert_eq!(
+            Format::Json.render(&report).unwrap(),
+            "{\n  \"error\": \"Image not found\",\n  \"causes\": [\n    \"401 Unauthorized\"\n  ]\n}"
+        );
+    }
+}
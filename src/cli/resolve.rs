@@ -0,0 +1,156 @@
+use anyhow::Result;
+use docker_tags::{
+    Client, Image, Selection, Tag, TagFilter, Warning, output::Listing, sort_tags_desc,
+};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, ResolveReport},
+};
+
+/// Print the tags pointing to the same manifest as `reference`, checking at
+/// most `search_limit` candidates, versions first
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    reference: &str,
+    search_limit: Option<usize>,
+    format: Format,
+    quiet: bool,
+) -> Result<()> {
+    let (image, tag) = registry.parse_reference(reference)?;
+    let client = registry.client(&image)?;
+
+    let digest = client.fetch_digest(&image, tag).await?;
+    let mut candidates = client.fetch_tags(&image).await?;
+    candidates.retain(|t| t.name() != tag);
+    // newest versions first, then everything else alphabetically
+    sort_tags_desc(&mut candidates);
+    if let Some(limit) = search_limit {
+        candidates.truncate(limit);
+    }
+
+    let (aliases, warnings) = client.find_aliases(&image, &digest, &candidates).await;
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    match format {
+        Format::Text => out.line(format_aliases(tag, &digest, &aliases)),
+        format => out.document(format, &report(&image, tag, digest, &aliases, &warnings)),
+    }
+}
+
+fn report(
+    image: &Image,
+    tag: &str,
+    digest: String,
+    aliases: &[Tag],
+    warnings: &[Warning],
+) -> ResolveReport {
+    ResolveReport {
+        image: image.to_string(),
+        tag: tag.to_string(),
+        digest,
+        aliases: aliases.iter().map(Tag::to_string).collect(),
+        warnings: warnings.iter().map(Warning::to_string).collect(),
+    }
+}
+
+/// Print the version tags of `image` pointing to the same manifest as the
+/// channel tag `channel`, newest first, among those `selection` keeps
+pub async fn channel(
+    out: &mut Output,
+    client: &Client,
+    image: &Image,
+    channel: &str,
+    selection: &Selection,
+    format: Format,
+    quiet: bool,
+) -> Result<()> {
+    let digest = client.fetch_digest(image, channel).await?;
+    let versions = TagFilter::version_like();
+    let mut candidates = client.fetch_tags(image).await?;
+    candidates.retain(|tag| versions.matches(tag) && selection.matches(tag));
+    sort_tags_desc(&mut candidates);
+
+    let (aliases, warnings) = client.find_aliases(image, &digest, &candidates).await;
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    let aliases = selection.arrange(aliases);
+    match format {
+        Format::Text => {
+            for tag in &aliases {
+                out.line(tag)?;
+            }
+            Ok(())
+        }
+        format => {
+            let entries = aliases.into_iter().map(Into::into).collect();
+            out.document(
+                format,
+                &Listing::new(Some(image.to_string()), entries, &warnings),
+            )
+        }
+    }
+}
+
+/// `latest = 1.27.2 (also: 1, 1.27, mainline)`, the first alias being the
+/// newest version if there is one
+fn format_aliases(tag: &str, digest: &str, aliases: &[Tag]) -> String {
+    match aliases {
+        [] => format!("{tag} = {digest} (no other tags)"),
+        [primary] => format!("{tag} = {primary}"),
+        [primary, rest @ ..] => {
+            let rest: Vec<_> = rest.iter().map(Tag::name).collect();
+            format!("{tag} = {primary} (also: {})", rest.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_aliases() {
+        let aliases: Vec<_> = ["1.27.2", "1", "1.27", "mainline"]
+            .into_iter()
+            .map(Tag::new)
+            .collect();
+        assert_eq!(
+            format_aliases("latest", "sha256:abc", &aliases),
+            "latest = 1.27.2 (also: 1, 1.27, mainline)"
+        );
+        assert_eq!(
+            format_aliases("latest", "sha256:abc", &aliases[..1]),
+            "latest = 1.27.2"
+        );
+        assert_eq!(
+            format_aliases("edge", "sha256:abc", &[]),
+            "edge = sha256:abc (no other tags)"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let image = Image::new("docker.io", "library/nginx");
+        let aliases = [Tag::new("1.27.2"), Tag::new("1.27")];
+        let report = report(&image, "latest", "sha256:abc".to_string(), &aliases, &[]);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "image": "docker.io/library/nginx",
+                "tag": "latest",
+                "digest": "sha256:abc",
+                "aliases": ["1.27.2", "1.27"],
+                "warnings": [],
+            })
+        );
+    }
+}
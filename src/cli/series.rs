@@ -0,0 +1,34 @@
+use anyhow::Result;
+use docker_tags::{SeriesLevel, version_series};
+use regex::Regex;
+
+use super::{Registry, output::Output, report::Format};
+
+/// Print the version series of `name`'s tags matching `pattern`, newest first
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    name: &str,
+    level: SeriesLevel,
+    stable: bool,
+    pattern: Option<&Regex>,
+    format: Format,
+) -> Result<()> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+    let mut tags = client.fetch_tags(&image).await?;
+    if let Some(re) = pattern {
+        tags.retain(|tag| re.is_match(tag.name()));
+    }
+
+    let series = version_series(&tags, level, stable);
+    match format {
+        Format::Text => {
+            for series in series {
+                out.line(series)?;
+            }
+            Ok(())
+        }
+        format => out.document(format, &series),
+    }
+}
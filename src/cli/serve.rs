@@ -0,0 +1,319 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow};
+use docker_tags::{
+    Aliases, Error, Image, Selection, Tag,
+    output::{Listing, TagEntry},
+};
+use reqwest::Url;
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::OnceCell,
+    time::timeout,
+};
+
+use super::{Registry, batch::Status, listing_cache::ListingCache, report::ErrorReport};
+
+/// Longest request head read, beyond which the connection is dropped
+const MAX_HEAD: u64 = 8 * 1024;
+
+/// How long a client gets to send the request head
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lookup of an image's tags, shared by concurrent requests
+type Listed = Arc<OnceCell<Arc<Vec<Tag>>>>;
+
+/// Listings shared by every request of a daemon, each image's kept for `ttl`
+pub struct Cache {
+    pub registry: Registry,
+    ttl: Duration,
+    /// Connections by registry host, which keep their tokens between requests
+    registries: Mutex<HashMap<String, docker_tags::Registry>>,
+    /// Lookups by image and when they started
+    listings: Mutex<HashMap<String, (Instant, Listed)>>,
+    /// Listings kept on disk for the next daemon, if any
+    disk: Option<ListingCache>,
+}
+
+impl Cache {
+    pub fn new(registry: Registry, ttl: Duration) -> Self {
+        Cache {
+            registry,
+            ttl,
+            registries: Mutex::default(),
+            listings: Mutex::default(),
+            disk: None,
+        }
+    }
+
+    /// Also keep the listings in `disk`, reusing those an earlier daemon
+    /// recorded within the TTL
+    pub fn with_disk(self, disk: Option<ListingCache>) -> Self {
+        Cache { disk, ..self }
+    }
+
+    /// The listing of `image` by `selection`, leaving out the ignored tags,
+    /// with the aliases apart if they're pinned
+    pub async fn listing(&self, image: &Image, selection: &Selection) -> Result<Listing> {
+        let selection = self.registry.selection_for(image, selection);
+        let tags = self.tags(image).await?;
+        let matched = tags.iter().filter(|tag| selection.matches(tag)).count();
+        let (aliases, entries) = selection.apply_split(tags.to_vec());
+        let entries = entries.into_iter().map(TagEntry::from).collect();
+        let mut listing = Listing::new(Some(image.to_string()), entries, &[]);
+        (listing.total, listing.matched) = (tags.len(), matched);
+        listing.aliases = aliases.into_iter().map(TagEntry::from).collect();
+        Ok(listing)
+    }
+
+    /// All tags of `image`, listed at most once per `ttl`; failures aren't
+    /// kept, nor are the listings of other images once expired
+    async fn tags(&self, image: &Image) -> Result<Arc<Vec<Tag>>> {
+        let cell = {
+            let mut listings = self.listings.lock().unwrap();
+            let key = image.to_string();
+            match listings.get(&key) {
+                Some((since, cell)) if since.elapsed() < self.ttl => cell.clone(),
+                _ => {
+                    let stored = self
+                        .disk
+                        .as_ref()
+                        .and_then(|disk| disk.load(image, self.ttl));
+                    let (since, cell) = match stored {
+                        // kept for what's left of its TTL
+                        Some((tags, age)) => (
+                            Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+                            Arc::new(OnceCell::new_with(Some(Arc::new(tags)))),
+                        ),
+                        None => (Instant::now(), Arc::new(OnceCell::new())),
+                    };
+                    // images asked for once don't stay around for good
+                    listings.retain(|_, (since, _)| since.elapsed() < self.ttl);
+                    listings.insert(key, (since, cell.clone()));
+                    cell
+                }
+            }
+        };
+        cell.get_or_try_init(|| async {
+            let registry = self.connect(image)?;
+            let tags = registry.fetch_tags(image.repository()).await?;
+            if let Some(disk) = &self.disk {
+                // a cache that can't be written only costs requests
+                let _ = disk.store(image, &tags);
+            }
+            Ok(Arc::new(tags))
+        })
+        .await
+        .cloned()
+    }
+
+    fn connect(&self, image: &Image) -> Result<docker_tags::Registry> {
+        let mut registries = self.registries.lock().unwrap();
+        if let Some(registry) = registries.get(image.registry()) {
+            return Ok(registry.clone());
+        }
+        let client = self.registry.client(image)?;
+        let registry = docker_tags::Registry::connect(image.registry(), &client)?;
+        registries.insert(image.registry().to_string(), registry.clone());
+        Ok(registry)
+    }
+}
+
+/// Answer `GET /v1/tags` and `GET /healthz` on `listen` until killed,
+/// printing the address listened on to stderr
+pub async fn run(
+    registry: Registry,
+    listen: SocketAddr,
+    ttl: Duration,
+    disk: Option<ListingCache>,
+) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {listen}"))?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+
+    let cache = Arc::new(Cache::new(registry, ttl).with_disk(disk));
+    loop {
+        let (stream, _) = listener.accept().await.context("Failed to accept")?;
+        let cache = cache.clone();
+        tokio::spawn(async move {
+            // the client hanging up is none of the daemon's business
+            let _ = handle(stream, &cache).await;
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, cache: &Cache) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut head = String::new();
+    let read = async {
+        let mut limited = (&mut stream).take(MAX_HEAD);
+        loop {
+            // nothing left once the client hangs up or the head's too long
+            if limited.read_line(&mut head).await? == 0 {
+                return Ok::<_, std::io::Error>(false);
+            }
+            if head.ends_with("\r\n\r\n") || head.ends_with("\n\n") {
+                return Ok(true);
+            }
+        }
+    };
+    match timeout(HEAD_TIMEOUT, read).await {
+        Ok(Ok(true)) => {}
+        Ok(Err(err)) => return Err(err.into()),
+        Ok(Ok(false)) | Err(_) => return Ok(()),
+    }
+
+    let (status, body) = match head.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", target, _, ..] => route(target, cache).await,
+        [_, _, _, ..] => error(405, anyhow!("Only GET is supported")),
+        _ => error(400, anyhow!("Malformed request")),
+    };
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Bad Gateway",
+    };
+    let head = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    let stream = stream.get_mut();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn route(target: &str, cache: &Cache) -> (u16, Vec<u8>) {
+    let Ok(url) = Url::parse("http://localhost").and_then(|base| base.join(target)) else {
+        return error(400, anyhow!("Malformed request target"));
+    };
+    match url.path() {
+        "/healthz" => (200, br#"{"status":"ok"}"#.to_vec()),
+        "/v1/tags" => match list(&url, cache).await {
+            Ok(listing) => json(200, &listing),
+            Err((status, err)) => error(status, err),
+        },
+        _ => error(404, anyhow!("No such endpoint: {}", url.path())),
+    }
+}
+
+/// The listing asked for by the query of `url`: `image`, then optionally
+/// `filter` (a regex), `sort` (`version` or `registry`), `reverse` and `limit`
+async fn list(url: &Url, cache: &Cache) -> Result<Listing, (u16, anyhow::Error)> {
+    let query: HashMap<_, _> = url.query_pairs().collect();
+    let bad_request = |err: anyhow::Error| (400, err);
+    let image = query
+        .get("image")
+        .ok_or_else(|| anyhow!("Missing the image parameter"))
+        .and_then(|name| cache.registry.image(name))
+        .map_err(bad_request)?;
+    let pattern = query
+        .get("filter")
+        .map(|p| super::regex(p))
+        .transpose()
+        .map_err(bad_request)?;
+    let keep_order = match query.get("sort").map(|sort| sort.as_ref()) {
+        None | Some("version") => false,
+        Some("registry") => true,
+        Some(sort) => return Err(bad_request(anyhow!("Unknown sort order: {sort:?}"))),
+    };
+    let limit = query
+        .get("limit")
+        .map(|limit| {
+            limit
+                .parse()
+                .map_err(|_| anyhow!("Invalid limit: {limit:?}"))
+        })
+        .transpose()
+        .map_err(bad_request)?;
+    let selection = Selection {
+        reverse: matches!(query.get("reverse").map(|r| r.as_ref()), Some("true" | "1")),
+        keep_order,
+        pattern,
+        filters: Vec::new(),
+        limit,
+        aliases: Aliases::Sorted,
+    };
+    cache
+        .listing(&image, &selection)
+        .await
+        .map_err(|err| (status_of(&err), err))
+}
+
+/// HTTP status answering a failed listing
+fn status_of(err: &anyhow::Error) -> u16 {
+    let typed = err
+        .downcast_ref::<Error>()
+        .or_else(|| err.chain().find_map(|cause| cause.downcast_ref()));
+    match Status::of(err) {
+        _ if matches!(typed, Some(Error::RegistryNotAllowed { .. })) => 403,
+        Status::NotFound => 404,
+        Status::AccessDenied => 403,
+        Status::Ok | Status::Error => 502,
+    }
+}
+
+fn json<T: Serialize>(status: u16, document: &T) -> (u16, Vec<u8>) {
+    let body = serde_json::to_vec(document).expect("documents serialize");
+    (status, body)
+}
+
+fn error(status: u16, err: anyhow::Error) -> (u16, Vec<u8>) {
+    json(status, &ErrorReport::from(&err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_of() {
+        let denied = anyhow::Error::new(Error::AccessDenied {
+            registry: "ghcr.io".to_string(),
+            repository: "org/private".to_string(),
+            message: String::new(),
+        });
+        assert_eq!(status_of(&denied.context("Failed to list tags")), 403);
+        assert_eq!(status_of(&anyhow::Error::new(Error::NotFound)), 404);
+        let not_allowed = anyhow::Error::new(Error::RegistryNotAllowed {
+            host: "quay.io".to_string(),
+        });
+        assert_eq!(status_of(&not_allowed.context("Failed to list tags")), 403);
+        assert_eq!(status_of(&anyhow!("connection refused")), 502);
+    }
+
+    #[tokio::test]
+    async fn test_cache_expiry() {
+        let ttl = Duration::from_secs(60);
+        let cache = Cache::new(Registry::default(), ttl);
+        let expired = Instant::now() - 2 * ttl;
+        for (image, since) in [("old/app", expired), ("new/app", Instant::now())] {
+            let cell = Arc::new(OnceCell::new());
+            cache
+                .listings
+                .lock()
+                .unwrap()
+                .insert(image.to_string(), (since, cell));
+        }
+
+        // nothing listens there, which doesn't matter to what's kept
+        let image: Image = "127.0.0.1:1/app".parse().unwrap();
+        assert!(cache.tags(&image).await.is_err());
+        let listings = cache.listings.lock().unwrap();
+        let mut images: Vec<_> = listings.keys().map(String::as_str).collect();
+        images.sort();
+        assert_eq!(images, ["127.0.0.1:1/app", "new/app"]);
+    }
+}
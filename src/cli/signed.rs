@@ -0,0 +1,38 @@
+use anyhow::Result;
+use docker_tags::{Client, Image, Selection, SignatureLookup, Tag, output::TagEntry};
+
+/// Look up signatures for the tags picked by `selection`, keeping only the
+/// signed ones with `only`
+///
+/// `tags` is the full listing, which also holds cosign's signature tags.
+pub async fn select(
+    client: &Client,
+    image: &Image,
+    tags: &[Tag],
+    selection: &Selection,
+    only: bool,
+) -> Result<Vec<TagEntry>> {
+    // the limit applies to the tags printed, not to those looked up
+    let candidates = Selection {
+        limit: None,
+        ..selection.clone()
+    }
+    .apply(tags.to_vec());
+    let mut lookup = SignatureLookup::new(client, image, tags);
+    let mut entries = Vec::new();
+    for tag in candidates {
+        if selection.limit.is_some_and(|limit| entries.len() >= limit) {
+            break;
+        }
+        let signed = lookup.is_signed(&tag).await?;
+        if only && !signed {
+            continue;
+        }
+        entries.push(TagEntry {
+            signed: Some(signed),
+            ..tag.into()
+        });
+    }
+
+    Ok(entries)
+}
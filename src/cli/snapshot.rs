@@ -0,0 +1,262 @@
+use std::{collections::HashMap, fs, path::Path, time::SystemTime};
+
+use anyhow::{Context, Result, bail};
+use docker_tags::{Selection, Tag, sort_tags_desc, unstable::time::format_rfc3339};
+use serde::{Deserialize, Serialize};
+
+use super::{Registry, output::Output};
+
+/// Version of the snapshot documents written, the newest one read
+pub const VERSION: u32 = 1;
+
+/// The tags an image had at some point, written by `snapshot` and compared
+/// by `diff-snapshots`
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub image: String,
+    /// When the tags were listed, in RFC 3339
+    pub fetched_at: String,
+    /// Newest versions first, as listed
+    pub tags: Vec<SnapshotTag>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SnapshotTag {
+    pub name: String,
+    /// Digest of the tag's manifest, with `--digests` or `--dates`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// When the tag's image was created, in RFC 3339, with `--dates`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+}
+
+/// What changed between two snapshots of an image
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<SnapshotTag>,
+    pub removed: Vec<SnapshotTag>,
+    /// Tags pointing to another image, as far as both snapshots have their
+    /// digests
+    pub changed: Vec<DigestChange>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct DigestChange {
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Snapshot of the tags of the image `name`, the ignore file's left out,
+/// with their digests and with `dates` the creation times of their images
+pub async fn take(registry: &Registry, name: &str, digests: bool, dates: bool) -> Result<Snapshot> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+    let mut tags = client.fetch_tags(&image).await?;
+    let fetched_at = format_rfc3339(SystemTime::now());
+    let unignored = registry.selection_for(&image, &Selection::default());
+    sort_tags_desc(&mut tags);
+    tags.retain(|tag| unignored.matches(tag));
+
+    let mut entries: Vec<_> = tags
+        .iter()
+        .map(|tag| SnapshotTag {
+            name: tag.name().to_string(),
+            digest: None,
+            created: None,
+        })
+        .collect();
+    let warnings = match (digests, dates) {
+        (_, true) => {
+            let (created, warnings) = client
+                .fetch_created_digests(&image, &tags, &HashMap::new())
+                .await;
+            let created: HashMap<_, _> = created
+                .into_iter()
+                .map(|(tag, at)| (tag.name().to_string(), at))
+                .collect();
+            for entry in &mut entries {
+                if let Some(at) = created.get(&entry.name) {
+                    entry.digest = Some(at.digest.clone());
+                    entry.created = Some(format_rfc3339(at.created));
+                }
+            }
+            warnings
+        }
+        (true, false) => {
+            let (digests, warnings) = client.fetch_digests(&image, &tags).await;
+            for (entry, digest) in entries.iter_mut().zip(digests) {
+                entry.digest = digest;
+            }
+            warnings
+        }
+        (false, false) => Vec::new(),
+    };
+    if !registry.quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+
+    Ok(Snapshot {
+        version: VERSION,
+        image: image.to_string(),
+        fetched_at,
+        tags: entries,
+    })
+}
+
+impl Snapshot {
+    /// Read the snapshot at `path`, refusing versions newer than this binary
+    /// knows
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot {path:?}"))?;
+        Self::parse(&text).with_context(|| format!("Invalid snapshot {path:?}"))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        match value.get("version").map(|version| version.as_u64()) {
+            None => bail!("no version; is it a snapshot?"),
+            Some(Some(version)) if version > u64::from(VERSION) => bail!(
+                "version {version} is newer than the snapshots this docker-tags reads (up to \
+                 {VERSION}); upgrade docker-tags to read it"
+            ),
+            Some(Some(1..)) => Ok(serde_json::from_value(value)?),
+            Some(_) => bail!("invalid version {}", value["version"]),
+        }
+    }
+
+    /// Tags `new` has and this one doesn't, the other way round, and those
+    /// whose digest changed, each in the order of the snapshot they're from
+    pub fn diff(&self, new: &Snapshot) -> SnapshotDiff {
+        let old: HashMap<_, _> = self.tags.iter().map(|tag| (&tag.name, tag)).collect();
+        let current: HashMap<_, _> = new.tags.iter().map(|tag| (&tag.name, tag)).collect();
+        let mut diff = SnapshotDiff {
+            removed: self
+                .tags
+                .iter()
+                .filter(|tag| !current.contains_key(&tag.name))
+                .cloned()
+                .collect(),
+            ..SnapshotDiff::default()
+        };
+        for tag in &new.tags {
+            match old.get(&tag.name) {
+                None => diff.added.push(tag.clone()),
+                Some(before) => {
+                    if let (Some(old), Some(new)) = (&before.digest, &tag.digest)
+                        && old != new
+                    {
+                        diff.changed.push(DigestChange {
+                            name: tag.name.clone(),
+                            old: old.clone(),
+                            new: new.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
+/// Print `diff` as tab-separated `added`, `removed` and `changed` rows, with
+/// the digests known
+pub fn print_diff(out: &mut Output, diff: &SnapshotDiff) -> Result<()> {
+    for line in format_diff(diff) {
+        out.line(line)?;
+    }
+    Ok(())
+}
+
+fn format_diff(diff: &SnapshotDiff) -> Vec<String> {
+    let row = |kind: &str, tag: &SnapshotTag| match &tag.digest {
+        Some(digest) => format!("{kind}\t{}\t{digest}", Tag::new(&tag.name)),
+        None => format!("{kind}\t{}", Tag::new(&tag.name)),
+    };
+    let added = diff.added.iter().map(|tag| row("added", tag));
+    let removed = diff.removed.iter().map(|tag| row("removed", tag));
+    let changed = diff
+        .changed
+        .iter()
+        .map(|change| format!("changed\t{}\t{}\t{}", change.name, change.old, change.new));
+    added.chain(removed).chain(changed).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, digest: Option<&str>) -> SnapshotTag {
+        SnapshotTag {
+            name: name.to_string(),
+            digest: digest.map(String::from),
+            created: None,
+        }
+    }
+
+    fn snapshot(tags: Vec<SnapshotTag>) -> Snapshot {
+        Snapshot {
+            version: VERSION,
+            image: "docker.io/library/alpine".to_string(),
+            fetched_at: "2026-10-05T06:00:00Z".to_string(),
+            tags,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let old = snapshot(vec![tag("3.21", Some("sha256:a")), tag("3.20", None)]);
+        let text = serde_json::to_string(&old).unwrap();
+        assert_eq!(Snapshot::parse(&text).unwrap(), old);
+        assert!(!text.contains("created"), "{text}");
+
+        let err = Snapshot::parse(&text.replace("\"version\":1", "\"version\":2")).unwrap_err();
+        assert!(err.to_string().contains("version 2 is newer"), "{err}");
+        for text in [
+            r#"{"image":"alpine","tags":[]}"#,
+            r#"{"version":0,"image":"alpine","fetched_at":"","tags":[]}"#,
+            r#"{"version":"1","image":"alpine","fetched_at":"","tags":[]}"#,
+            r#"{"version":1,"image":"alpine"}"#,
+        ] {
+            assert!(Snapshot::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot(vec![
+            tag("3.21", Some("sha256:a")),
+            tag("3.20", Some("sha256:b")),
+            tag("3.19", Some("sha256:c")),
+            tag("edge", None),
+        ]);
+        let new = snapshot(vec![
+            tag("3.22", Some("sha256:d")),
+            tag("3.21", Some("sha256:e")),
+            tag("3.20", Some("sha256:b")),
+            tag("edge", Some("sha256:f")),
+        ]);
+        let diff = old.diff(&new);
+        assert_eq!(
+            format_diff(&diff),
+            [
+                "added\t3.22\tsha256:d",
+                "removed\t3.19\tsha256:c",
+                "changed\t3.21\tsha256:a\tsha256:e",
+            ]
+        );
+        assert!(!diff.is_empty());
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use docker_tags::{
+    Selection, Tag, TagCollector, Warning,
+    output::{Listing, TagEntry},
+};
+
+use super::{output::Output, report::Format};
+
+/// Print the tags listed in `input` (`-` for stdin) through `selection`
+pub fn run(
+    out: &mut Output,
+    input: &Path,
+    selection: &Selection,
+    dedup: bool,
+    format: Format,
+    quiet: bool,
+) -> Result<()> {
+    let text = match input.to_str() {
+        Some("-") => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read tags from stdin")?;
+            text
+        }
+        _ => fs::read_to_string(input)
+            .with_context(|| format!("Failed to read tags from {input:?}"))?,
+    };
+
+    let (tags, warnings) = parse_lines(&text, dedup);
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    let (total, matched) = (
+        tags.len(),
+        tags.iter().filter(|tag| selection.matches(tag)).count(),
+    );
+    let entries = selection
+        .apply(tags)
+        .into_iter()
+        .map(TagEntry::from)
+        .collect();
+    let mut listing = Listing::new(None, entries, &warnings);
+    (listing.total, listing.matched) = (total, matched);
+    out.listing(&*format.formatter(false)?, &listing)
+}
+
+/// One tag per line, ignoring surrounding whitespace and blank lines
+fn parse_lines(text: &str, dedup: bool) -> (Vec<Tag>, Vec<Warning>) {
+    let mut collector = match dedup {
+        true => TagCollector::new(),
+        false => TagCollector::new().keep_duplicates(),
+    };
+    collector.push_page(
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string),
+    );
+    collector.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines() {
+        let text = "1.0.0\n\n  1.1.0\r\nlatest\n1.0.0\nnot a tag\n";
+
+        let (tags, warnings) = parse_lines(text, false);
+        let names: Vec<_> = tags.iter().map(Tag::name).collect();
+        assert_eq!(names, ["1.0.0", "1.1.0", "latest", "1.0.0"]);
+        assert_eq!(
+            warnings,
+            [Warning::InvalidTagName {
+                raw: "not a tag".to_string()
+            }]
+        );
+
+        let (tags, warnings) = parse_lines(text, true);
+        assert_eq!(tags.len(), 3);
+        assert_eq!(warnings.len(), 2);
+    }
+}
@@ -0,0 +1,16 @@
+use docker_tags::{Client, Image, Tag, Warning, output::TagEntry};
+
+/// Look up where the tags of `entries` were built from, concurrently, as
+/// the other manifest lookups do
+pub async fn enrich(client: &Client, image: &Image, entries: &mut [TagEntry]) -> Vec<Warning> {
+    let tags: Vec<_> = entries.iter().map(|entry| Tag::new(&entry.name)).collect();
+    let (sources, warnings) = client.fetch_sources(image, &tags).await;
+    for (entry, source) in entries.iter_mut().zip(sources) {
+        entry.source = Some(
+            source
+                .filter(|source| !source.is_empty())
+                .map(|source| source.to_string()),
+        );
+    }
+    warnings
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use docker_tags::Tag;
+use serde::{Deserialize, Serialize};
+
+/// Order of a listing, chosen with `--sort`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum SortOrder {
+    /// Newest versions first, then the other tags
+    #[default]
+    Version,
+    /// Tags first seen most recently first, as recorded in the --state file
+    FirstSeen,
+}
+
+/// Tags seen by a previous run, persisted between invocations
+#[derive(Debug, Deserialize, Serialize)]
+pub struct State {
+    pub image: String,
+    pub fetched_at: u64,
+    /// When each tag was first seen, in milliseconds since the epoch; unknown
+    /// for tags carried over from state files listing only names
+    #[serde(deserialize_with = "first_seen_or_names")]
+    pub tags: BTreeMap<String, Option<u64>>,
+}
+
+/// `tags` as written now, or as the bare list of names of older versions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredTags {
+    FirstSeen(BTreeMap<String, Option<u64>>),
+    Names(Vec<String>),
+}
+
+fn first_seen_or_names<'de, D>(deserializer: D) -> Result<BTreeMap<String, Option<u64>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match StoredTags::deserialize(deserializer)? {
+        StoredTags::FirstSeen(tags) => tags,
+        StoredTags::Names(names) => names.into_iter().map(|name| (name, None)).collect(),
+    })
+}
+
+impl State {
+    /// State of `tags` listed now, keeping when `previous` first saw them
+    pub fn new(image: impl Into<String>, tags: &[Tag], previous: Option<&State>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let first_seen = |name: &str| match previous.and_then(|state| state.tags.get(name)) {
+            Some(&at) => at,
+            None => Some(now.as_millis() as u64),
+        };
+        State {
+            image: image.into(),
+            fetched_at: now.as_secs(),
+            tags: tags
+                .iter()
+                .map(|tag| (tag.name().to_string(), first_seen(tag.name())))
+                .collect(),
+        }
+    }
+
+    /// Load the state file of `image`, failing if it belongs to another
+    pub fn load_for(path: &Path, image: &str) -> Result<Option<Self>> {
+        let state = State::load(path)?;
+        if let Some(state) = &state
+            && state.image != image
+        {
+            return Err(anyhow!(
+                "State file {path:?} belongs to {}, not {image}",
+                state.image
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Load the state file, returning `None` if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read state file {path:?}"));
+            }
+        };
+        let state = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse state file {path:?}"))?;
+
+        Ok(Some(state))
+    }
+
+    /// Atomically replace the state file (write to a temporary file, then rename)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = sibling(path, ".tmp");
+        let contents = serde_json::to_vec_pretty(self).context("Failed to serialize state")?;
+        fs::write(&tmp, contents).with_context(|| format!("Failed to write state file {tmp:?}"))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace state file {path:?}"))
+    }
+
+    /// Tags from `tags` which were not recorded in this state, in listing order
+    pub fn new_tags<'a>(&self, tags: &'a [Tag]) -> Vec<&'a Tag> {
+        tags.iter()
+            .filter(|t| !self.tags.contains_key(t.name()))
+            .collect()
+    }
+
+    /// When each tag was first seen, for
+    /// [`Sort::FirstSeen`](docker_tags::output::Sort::FirstSeen), leaving out
+    /// those seen at unknown times
+    pub fn first_seen(&self) -> HashMap<String, SystemTime> {
+        self.tags
+            .iter()
+            .filter_map(|(name, millis)| {
+                let at = UNIX_EPOCH + Duration::from_millis((*millis)?);
+                Some((name.clone(), at))
+            })
+            .collect()
+    }
+}
+
+/// Exclusive lock guarding a state file; released on drop
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    pub fn acquire(state_path: &Path) -> Result<Self> {
+        let path = sibling(state_path, ".lock");
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let _ = writeln!(file, "{}", std::process::id());
+                Ok(Lock { path })
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Err(anyhow!(
+                "State file is locked by another run (remove {path:?} if it is stale)"
+            )),
+            Err(err) => Err(err).with_context(|| format!("Failed to create lock file {path:?}")),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use docker_tags::output::{Pipeline, Sort};
+
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        assert!(State::load(&path).unwrap().is_none());
+
+        let tags = [Tag::new("1.27.2"), Tag::new("1.27.1")];
+        State::new("docker.io/nginx", &tags, None)
+            .save(&path)
+            .unwrap();
+
+        let state = State::load(&path).unwrap().unwrap();
+        assert_eq!(state.image, "docker.io/nginx");
+        assert_eq!(state.tags.keys().collect::<Vec<_>>(), ["1.27.1", "1.27.2"]);
+        assert!(state.tags.values().all(Option::is_some));
+        assert!(!sibling(&path, ".tmp").exists());
+        assert!(State::load_for(&path, "docker.io/nginx").is_ok());
+        assert!(State::load_for(&path, "docker.io/httpd").is_err());
+    }
+
+    #[test]
+    fn test_state_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(
+            &path,
+            r#"{"image":"docker.io/nginx","fetched_at":1,"tags":["1.27.2","1.27.1"]}"#,
+        )
+        .unwrap();
+
+        let previous = State::load(&path).unwrap().unwrap();
+        assert_eq!(previous.tags["1.27.2"], None);
+        let tags = [Tag::new("1.27.3"), Tag::new("1.27.2")];
+        let state = State::new("docker.io/nginx", &tags, Some(&previous));
+        assert!(state.tags["1.27.3"].is_some());
+        assert_eq!(state.tags["1.27.2"], None);
+        assert!(!state.tags.contains_key("1.27.1"));
+    }
+
+    #[test]
+    fn test_first_seen() {
+        let state = State {
+            image: "docker.io/nginx".to_string(),
+            fetched_at: 0,
+            tags: BTreeMap::from([
+                ("latest".to_string(), None),
+                ("1.0".to_string(), Some(100)),
+                ("1.1".to_string(), Some(200)),
+                ("1.2".to_string(), Some(200)),
+            ]),
+        };
+        let first_seen = state.first_seen();
+        assert_eq!(first_seen.len(), 3);
+        assert_eq!(first_seen["1.1"], UNIX_EPOCH + Duration::from_millis(200));
+        let tags = ["latest", "1.2", "1.0", "1.1", "unknown"].map(Tag::new);
+        let pipeline = Pipeline {
+            sort: Sort::FirstSeen(first_seen),
+            ..Pipeline::default()
+        };
+        assert_eq!(
+            pipeline.apply(tags.to_vec()),
+            ["1.2", "1.1", "1.0", "latest", "unknown"].map(Tag::new)
+        );
+    }
+
+    #[test]
+    fn test_state_new_tags() {
+        let state = State::new("docker.io/nginx", &[Tag::new("1.27.1")], None);
+        let tags = [Tag::new("1.27.3"), Tag::new("1.27.2"), Tag::new("1.27.1")];
+        assert_eq!(state.new_tags(&tags), [&tags[0], &tags[1]]);
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let lock = Lock::acquire(&path).unwrap();
+        assert!(Lock::acquire(&path).is_err());
+        drop(lock);
+        assert!(Lock::acquire(&path).is_ok());
+    }
+}
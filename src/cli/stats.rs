@@ -0,0 +1,94 @@
+use anyhow::Result;
+use docker_tags::{Tag, TagStats, unstable::time::format_rfc3339};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, StatsReport},
+};
+
+/// Print an overview of `name`'s tags
+pub async fn run(out: &mut Output, registry: &Registry, name: &str, format: Format) -> Result<()> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+    let mut stats = TagStats::compute(&client.fetch_tags(&image).await?);
+    // the summary is still worth printing without it
+    match client.fetch_last_pushed(&image).await {
+        Ok(at) => stats.last_pushed = at,
+        Err(err) if !registry.quiet => eprintln!("Warning: {err:#}"),
+        Err(_) => {}
+    }
+
+    match format {
+        Format::Text => {
+            for line in format_stats(&stats) {
+                out.line(line)?;
+            }
+            Ok(())
+        }
+        format => out.document(format, &StatsReport::new(image.to_string(), &stats)),
+    }
+}
+
+pub fn format_stats(stats: &TagStats) -> Vec<String> {
+    let tag = |tag: &Option<Tag>| tag.as_ref().map_or("none".to_string(), Tag::to_string);
+    let variants = match stats.variants.is_empty() {
+        true => "none".to_string(),
+        false => stats.variants.join(", "),
+    };
+    vec![
+        format!("tags: {}", stats.tags),
+        format!(
+            "versions: {} ({} pre-releases, {:.0}%)",
+            stats.versions,
+            stats.pre_releases,
+            stats.pre_release_ratio() * 100.0
+        ),
+        format!("series: {} major, {} minor", stats.majors, stats.minors),
+        format!("newest: {}", tag(&stats.newest)),
+        format!("oldest: {}", tag(&stats.oldest)),
+        format!("variants: {variants}"),
+        format!(
+            "last pushed: {}",
+            stats
+                .last_pushed
+                .map_or("unknown".to_string(), format_rfc3339)
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    #[test]
+    fn test_format_stats() {
+        let tags = ["1.27.2", "1.27.2-alpine", "1.27.1", "1.28.0-rc1", "latest"].map(Tag::new);
+        assert_eq!(
+            format_stats(&TagStats::compute(&tags)),
+            [
+                "tags: 5",
+                "versions: 4 (1 pre-releases, 25%)",
+                "series: 1 major, 2 minor",
+                "newest: 1.27.2",
+                "oldest: 1.27.1",
+                "variants: alpine",
+                "last pushed: unknown",
+            ]
+        );
+
+        let empty = format_stats(&TagStats::compute(&[]));
+        assert_eq!(
+            empty[3..6],
+            ["newest: none", "oldest: none", "variants: none"]
+        );
+
+        let stats = TagStats {
+            last_pushed: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_760_000_000)),
+            ..TagStats::compute(&tags[..1])
+        };
+        assert_eq!(format_stats(&stats)[6], "last pushed: 2025-10-09T08:53:20Z");
+    }
+}
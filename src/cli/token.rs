@@ -0,0 +1,173 @@
+use anyhow::Result;
+use docker_tags::{
+    AuthOutcome, CredentialTrace, HelperOutcome, TraceStep, explain_credentials,
+    unstable::auth::{TokenExchange, jwt_claims},
+};
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, TokenReport},
+};
+
+/// Print how a token for pulling from `name` is obtained, without the token
+/// itself unless `show_token`
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    name: &str,
+    show_token: bool,
+    format: Format,
+) -> Result<()> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+
+    let exchange = client.exchange_token(&image).await?;
+    let trace = explain_credentials(image.registry()).await;
+    let report = report(image.to_string(), exchange, &trace, show_token);
+    match format {
+        Format::Text => {
+            for line in format_report(&report) {
+                out.line(line)?;
+            }
+            Ok(())
+        }
+        format => out.document(format, &report),
+    }
+}
+
+fn report(
+    image: String,
+    exchange: TokenExchange,
+    trace: &CredentialTrace,
+    show_token: bool,
+) -> TokenReport {
+    let grant = exchange.grant;
+    let authenticated = grant.as_ref().is_some_and(|grant| grant.authenticated);
+    let token = grant.as_ref().map(|grant| grant.token.expose().to_string());
+    TokenReport {
+        image,
+        realm: grant.as_ref().map(|grant| grant.realm.clone()),
+        service: grant.as_ref().and_then(|grant| grant.service.clone()),
+        scopes: grant.map(|grant| grant.scopes).unwrap_or_default(),
+        credentials: credential_source(trace, authenticated),
+        status: exchange.status.as_u16(),
+        claims: token.as_deref().and_then(jwt_claims),
+        rate_limits: exchange.rate_limits.into_iter().collect(),
+        token: token.filter(|_| show_token),
+    }
+}
+
+/// Where the credentials sent to the token service were found in Docker's
+/// config, going by `trace`
+fn credential_source(trace: &CredentialTrace, authenticated: bool) -> String {
+    if !authenticated {
+        return "anonymous".to_string();
+    }
+    let config = trace.steps.iter().find_map(|step| match step {
+        TraceStep::Config { path, .. } => Some(path.display().to_string()),
+        _ => None,
+    });
+    let config = config.as_deref().unwrap_or("Docker config");
+    for step in &trace.steps {
+        match step {
+            TraceStep::Helper {
+                helper,
+                configured_by,
+                outcome: HelperOutcome::Found { username },
+            } => return format!("helper {helper:?} of {configured_by} in {config} ({username})"),
+            TraceStep::Auth {
+                key,
+                outcome: AuthOutcome::Found { username },
+            } => return format!("auths[{key:?}] in {config} ({username})"),
+            _ => {}
+        }
+    }
+    "credential provider".to_string()
+}
+
+fn format_report(report: &TokenReport) -> Vec<String> {
+    let mut lines = Vec::new();
+    match &report.realm {
+        Some(realm) => {
+            lines.push(format!("realm: {realm}"));
+            lines.push(format!(
+                "service: {}",
+                report.service.as_deref().unwrap_or("none")
+            ));
+            for scope in &report.scopes {
+                lines.push(format!("scope: {scope}"));
+            }
+        }
+        None => lines.push("no token needed".to_string()),
+    }
+    lines.push(format!("credentials: {}", report.credentials));
+    lines.push(format!("status: {}", report.status));
+    match &report.claims {
+        Some(serde_json::Value::Object(claims)) => {
+            lines.push("claims:".to_string());
+            for (name, value) in claims {
+                lines.push(format!("  {name}: {value}"));
+            }
+        }
+        _ if report.realm.is_some() => lines.push("claims: not a JWT".to_string()),
+        _ => {}
+    }
+    for (name, value) in &report.rate_limits {
+        lines.push(format!("{name}: {value}"));
+    }
+    if let Some(token) = &report.token {
+        lines.push(format!("token: {token}"));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use docker_tags::{AuthDecision, ConfigOutcome};
+
+    use super::*;
+
+    #[test]
+    fn test_credential_source() {
+        let trace = |step| CredentialTrace {
+            registry: "ghcr.io".to_string(),
+            server: "ghcr.io".to_string(),
+            steps: vec![
+                TraceStep::Config {
+                    path: PathBuf::from("/home/user/.docker/config.json"),
+                    outcome: ConfigOutcome::Loaded,
+                },
+                step,
+            ],
+            decision: AuthDecision::Basic {
+                username: "user".to_string(),
+            },
+        };
+        let auth = trace(TraceStep::Auth {
+            key: "ghcr.io".to_string(),
+            outcome: AuthOutcome::Found {
+                username: "user".to_string(),
+            },
+        });
+        assert_eq!(
+            credential_source(&auth, true),
+            r#"auths["ghcr.io"] in /home/user/.docker/config.json (user)"#
+        );
+        assert_eq!(credential_source(&auth, false), "anonymous");
+
+        let helper = trace(TraceStep::Helper {
+            helper: "pass".to_string(),
+            configured_by: "credsStore".to_string(),
+            outcome: HelperOutcome::Found {
+                username: "user".to_string(),
+            },
+        });
+        assert_eq!(
+            credential_source(&helper, true),
+            r#"helper "pass" of credsStore in /home/user/.docker/config.json (user)"#
+        );
+    }
+}
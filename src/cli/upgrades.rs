@@ -0,0 +1,109 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use docker_tags::{Selection, UpgradePolicy, suggest_upgrade};
+use regex::Regex;
+
+use super::{
+    Registry,
+    output::Output,
+    pin::scan,
+    policy::{self, Condition, Results, Trigger},
+    renovate::{ImagePolicy, PolicyFile},
+    report::{Format, UpgradeEntry, UpgradesReport},
+};
+
+/// The references `pattern` finds in `path` which have a newer tag under
+/// `policy`, as narrowed per image by `policies`, and the `fail_on`
+/// conditions the references met
+pub async fn run(
+    registry: &Registry,
+    path: &Path,
+    pattern: &Regex,
+    policy: UpgradePolicy,
+    policies: Option<&PolicyFile>,
+    fail_on: &[Condition],
+) -> Result<(UpgradesReport, Vec<Trigger>)> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    let default = PolicyFile::default();
+    let policies = policies.unwrap_or(&default);
+    if !registry.quiet {
+        for warning in policies.warnings() {
+            eprintln!("Warning: {warning}");
+        }
+    }
+
+    let mut upgrades = Vec::new();
+    let mut listings = HashMap::new();
+    let mut triggered = Vec::new();
+    for reference in scan(&text, pattern) {
+        let ImagePolicy::Check {
+            policy,
+            selection,
+            allowed,
+        } = policies.policy(&reference.name, policy)
+        else {
+            continue;
+        };
+
+        if !listings.contains_key(&reference.name) {
+            let image = registry.image(&reference.name)?;
+            let mut tags = registry
+                .client(&image)?
+                .fetch_tags(&image)
+                .await
+                .with_context(|| format!("Failed to list the tags of {}", reference.name))?;
+            // never suggested, whatever the policy
+            let unignored = registry.selection_for(&image, &Selection::default());
+            tags.retain(|tag| unignored.matches(tag));
+            listings.insert(reference.name.clone(), tags);
+        }
+        let tags: Vec<_> = listings[&reference.name]
+            .iter()
+            .filter(|tag| selection.matches(tag))
+            .filter(|tag| allowed.as_ref().is_none_or(|allowed| allowed.matches(tag)))
+            .cloned()
+            .collect();
+        let results = Results {
+            current: Some(&reference.tag),
+            candidates: &tags,
+            shown: tags.len(),
+            unsigned: None,
+        };
+        for trigger in policy::evaluate(fail_on, &results) {
+            triggered.push(Trigger {
+                reason: format!("{}: {}: {}", reference.line, reference.name, trigger.reason),
+                ..trigger
+            });
+        }
+        if let Some(suggestion) = suggest_upgrade(&reference.tag, &tags, policy) {
+            let current = reference.reference(registry)?;
+            upgrades.push(UpgradeEntry {
+                line: reference.line,
+                upgrade: current.clone().with_tag(suggestion.tag).to_string(),
+                reference: current.to_string(),
+                reason: suggestion.reason,
+            });
+        }
+    }
+    let report = UpgradesReport {
+        file: path.display().to_string(),
+        upgrades,
+    };
+    Ok((report, triggered))
+}
+
+/// Print `report` as `LINE: REFERENCE -> UPGRADE (REASON)` lines, or as a
+/// document
+pub fn print(out: &mut Output, report: &UpgradesReport, format: Format) -> Result<()> {
+    if format != Format::Text {
+        return out.document(format, report);
+    }
+    for upgrade in &report.upgrades {
+        out.line(format!(
+            "{}: {} -> {} ({})",
+            upgrade.line, upgrade.reference, upgrade.upgrade, upgrade.reason
+        ))?;
+    }
+    Ok(())
+}
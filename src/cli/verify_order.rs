@@ -0,0 +1,203 @@
+use anyhow::Result;
+use docker_tags::{Tag, is_windows_listing, sort_tags_desc};
+use regex::Regex;
+
+use super::{
+    Registry,
+    output::Output,
+    report::{Format, OrderEntry},
+};
+
+/// Explain where each of `name`'s tags matching `pattern` sorts, in the
+/// order of a listing, for the first `limit` of them
+pub async fn run(
+    out: &mut Output,
+    registry: &Registry,
+    name: &str,
+    pattern: Option<&Regex>,
+    limit: Option<usize>,
+    format: Format,
+) -> Result<()> {
+    let image = registry.image(name)?;
+    let client = registry.client(&image)?;
+    let tags = client.fetch_tags(&image).await?;
+
+    let mut entries = explain(tags, pattern);
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    match format {
+        Format::Text => {
+            for entry in &entries {
+                out.line(format_entry(entry))?;
+            }
+            Ok(())
+        }
+        format => out.document(format, &entries),
+    }
+}
+
+/// Entries of the tags matching `pattern` sorted newest first, numbered
+/// within their bucket, the whole listing telling a Windows image's
+fn explain(mut tags: Vec<Tag>, pattern: Option<&Regex>) -> Vec<OrderEntry> {
+    let windows = is_windows_listing(&tags);
+    sort_tags_desc(&mut tags);
+    if let Some(re) = pattern {
+        tags.retain(|tag| re.is_match(tag.name()));
+    }
+    let mut entries: Vec<OrderEntry> = Vec::with_capacity(tags.len());
+    for tag in &tags {
+        let mut entry = OrderEntry::new(tag, windows, 1);
+        if let Some(previous) = entries.last().filter(|prev| prev.bucket == entry.bucket) {
+            entry.position = previous.position + 1;
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+/// `1.27.2-alpine: versions #2, semver 1.27.2 pre-release alpine; lenient
+/// 1.27.2 variant alpine`
+fn format_entry(entry: &OrderEntry) -> String {
+    let mut line = format!("{}: {} #{}, ", entry.tag, entry.bucket, entry.position);
+    match (&entry.version, &entry.windows, &entry.reason) {
+        (Some(version), _, _) => {
+            line += &format!(
+                "semver {}.{}.{}",
+                version.major, version.minor, version.patch
+            );
+            if let Some(pre) = &version.pre_release {
+                line += &format!(" pre-release {pre}");
+            }
+            if let Some(build) = &version.build {
+                line += &format!(" build {build}");
+            }
+            if version.prefixed {
+                line += " after v";
+            }
+        }
+        (None, Some(windows), _) => {
+            line += &format!("windows build {}", windows.build);
+            match windows.revision {
+                Some(revision) => line += &format!(".{revision}"),
+                None => line += " (newest revision)",
+            }
+        }
+        (None, None, reason) => {
+            line += "opaque";
+            if let Some(reason) = reason {
+                line += &format!(" ({reason})");
+            }
+        }
+    }
+    if let Some(lenient) = &entry.lenient {
+        line += &format!("; lenient {}", lenient.major);
+        for part in [lenient.minor, lenient.patch].into_iter().flatten() {
+            line += &format!(".{part}");
+        }
+        if lenient.pre_release {
+            line += " pre-release";
+        }
+        if let Some(variant) = &lenient.variant {
+            line += &format!(" variant {variant}");
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &[&str] = &[
+        "latest",
+        "1.27",
+        "1.27.2",
+        "1.27.2-alpine",
+        "v1.28.0-rc.1+build.5",
+        "1.27.10",
+        "1.28rc1",
+        "stable-alpine",
+    ];
+
+    #[test]
+    fn test_format_entries() {
+        let lines: Vec<_> = explain(FIXTURE.iter().copied().map(Tag::new).collect(), None)
+            .iter()
+            .map(format_entry)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "v1.28.0-rc.1+build.5: versions #1, semver 1.28.0 pre-release rc.1 build build.5 after v; lenient 1.28.0 pre-release",
+                "1.27.10: versions #2, semver 1.27.10; lenient 1.27.10",
+                "1.27.2: versions #3, semver 1.27.2; lenient 1.27.2",
+                "1.27.2-alpine: versions #4, semver 1.27.2 pre-release alpine; lenient 1.27.2 variant alpine",
+                "1.27: others #1, opaque (unexpected end of input while parsing minor version number); lenient 1.27",
+                "1.28rc1: others #2, opaque (unexpected character 'r' after minor version number); lenient 1.28 pre-release",
+                "latest: others #3, opaque (unexpected character 'l' while parsing major version number)",
+                "stable-alpine: others #4, opaque (unexpected character 's' while parsing major version number)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_windows_entries() {
+        let tags = || ["1810", "ltsc2019", "1809", "latest"].map(Tag::new).into();
+        let lines = |pattern| {
+            explain(tags(), Some(&Regex::new(pattern).unwrap()))
+                .iter()
+                .map(format_entry)
+                .collect::<Vec<_>>()
+        };
+        // `ltsc2019` tells a Windows image even once filtered out
+        assert_eq!(
+            lines("^18"),
+            [
+                "1809: windows #1, windows build 17763 (newest revision); lenient 1809",
+                "1810: others #1, opaque (unexpected end of input while parsing major version number); lenient 1810",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_entries_json() {
+        let entries = explain(["1.27.2-alpine", "1.27"].map(Tag::new).into(), None);
+        let expected = r#"[
+  {
+    "tag": "1.27.2-alpine",
+    "scheme": "semver",
+    "bucket": "versions",
+    "position": 1,
+    "version": {
+      "major": 1,
+      "minor": 27,
+      "patch": 2,
+      "pre_release": "alpine",
+      "prefixed": false
+    },
+    "lenient": {
+      "major": 1,
+      "minor": 27,
+      "patch": 2,
+      "pre_release": false,
+      "variant": "alpine"
+    }
+  },
+  {
+    "tag": "1.27",
+    "scheme": "opaque",
+    "bucket": "others",
+    "position": 1,
+    "reason": "unexpected end of input while parsing minor version number",
+    "lenient": {
+      "major": 1,
+      "minor": 27,
+      "patch": null,
+      "pre_release": false
+    }
+  }
+]"#;
+        assert_eq!(Format::Json.render(&entries).unwrap(), expected);
+    }
+}
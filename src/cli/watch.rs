@@ -0,0 +1,69 @@
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::Result;
+use docker_tags::Tag;
+
+use super::{notify::Notifier, output::Output};
+
+/// Poll `fetch` every `interval`, printing the initial listing and then
+/// only the tags that were not seen before.
+pub async fn run<F, Fut>(
+    out: &mut Output,
+    image: &str,
+    interval: Duration,
+    mut notifiers: Vec<Notifier>,
+    mut fetch: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Vec<Tag>>>,
+{
+    let mut seen: Option<HashSet<String>> = None;
+    loop {
+        match fetch().await {
+            Ok(tags) => {
+                let new_tags = match seen.as_mut() {
+                    Some(seen) => new_tags(seen, &tags),
+                    None => {
+                        for tag in &tags {
+                            out.line(tag)?;
+                        }
+                        seen = Some(tags.iter().map(|t| t.name().to_string()).collect());
+                        Vec::new()
+                    }
+                };
+                for tag in &new_tags {
+                    out.line(tag)?;
+                }
+                out.flush()?;
+                for notifier in &mut notifiers {
+                    notifier.notify(image, &new_tags).await;
+                }
+            }
+            Err(err) => eprintln!("Warning: failed to fetch tags: {err:#}"),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Names of `tags` missing from `seen`, in listing order; `seen` is updated
+fn new_tags(seen: &mut HashSet<String>, tags: &[Tag]) -> Vec<String> {
+    tags.iter()
+        .filter(|tag| seen.insert(tag.name().to_string()))
+        .map(|tag| tag.name().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_tags() {
+        let mut seen = HashSet::from(["1.27.1".to_string(), "latest".to_string()]);
+        let tags = [Tag::new("1.27.3"), Tag::new("1.27.2"), Tag::new("1.27.1")];
+        assert_eq!(new_tags(&mut seen, &tags), ["1.27.3", "1.27.2"]);
+        assert!(new_tags(&mut seen, &tags).is_empty());
+    }
+}
@@ -0,0 +1,2492 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, anyhow};
+use futures_util::{FutureExt, StreamExt, stream};
+#[cfg(feature = "regex")]
+use regex::Regex;
+use reqwest::{
+    Method, StatusCode, Url,
+    header::{self, HeaderName},
+};
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+#[cfg(feature = "unstable")]
+use crate::auth::{TokenExchange, is_rate_limit_header};
+#[cfg(feature = "github")]
+use crate::github::GitHubPackages;
+#[cfg(feature = "regex")]
+use crate::search::{SearchApi, pushdown_literal};
+use crate::{
+    Descriptor, Error, Image, ImageConfig, ImageSource, Manifest, ManifestIndex, PageCursor,
+    Platform, ServerNotice, Tag, TagCollector, Warning,
+    allowlist::{HostAllowlist, authority, redirect_policy},
+    auth::{Scope, TokenGrant, parse_www_authenticate, token_url},
+    aws::AwsCredentials,
+    credentials::{CachedProvider, CredentialProvider, Credentials, DockerConfigProvider},
+    host_ip,
+    immutable::{Ecr, ImmutabilityLookup, TagImmutability, immutable_tags},
+    is_valid_component, is_valid_host, is_valid_tag,
+    manifest::{
+        MANIFEST_ACCEPT, OCI_INDEX, cosign_artifact_type, is_digest, is_schema1, sha256_digest,
+        verify_digest,
+    },
+    reference::Reference,
+    registries::{DOCKER_HUB, DOCKER_HUB_REGISTRY},
+    secret::{Secret, redact_str, redact_url},
+    split_port,
+    throttle::Throttle,
+    timings::{Recorder, RequestKind, Timings},
+    tls::{TlsVersion, is_version_mismatch},
+};
+
+/// Structure for authentication response
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(alias = "access_token")]
+    token: Secret,
+}
+
+/// Error answered by an OAuth 2.0 token endpoint
+#[derive(Deserialize)]
+struct OAuthError {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+/// Grant type and subject token type of an OIDC token exchange (RFC 8693)
+const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ID_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:id_token";
+
+/// Tags asked for per page when listing them all
+const PAGE_SIZE: usize = 100;
+
+/// Structure for tags response
+#[derive(Deserialize)]
+struct TagsResponse {
+    tags: Option<Vec<String>>,
+}
+
+/// Details about a single tags list request, passed to [`ClientBuilder::on_page`]
+#[derive(Debug)]
+#[cfg_attr(not(feature = "unstable"), allow(dead_code))]
+pub struct PageInfo<'a> {
+    pub url: &'a str,
+    pub status: StatusCode,
+    pub tags: usize,
+    pub elapsed: Duration,
+}
+
+/// Result of a tags listing which may have stopped early
+#[derive(Debug, Default)]
+pub struct FetchOutcome {
+    /// Number of pages fetched successfully
+    pub pages: usize,
+    /// Error which stopped the pagination, if any
+    pub error: Option<anyhow::Error>,
+    /// Tags which were skipped or looked suspicious
+    pub warnings: Vec<Warning>,
+    /// What the registry announced about itself, e.g. a `Sunset` date
+    pub notices: Vec<ServerNotice>,
+    /// Distinct tags the registry listed before filtering, only the candidates
+    /// of a search API and only the pages fetched if the listing stopped early
+    pub listed: usize,
+    /// Listed tags which matched the filter, see [`TagCollector::matched`]
+    pub matched: usize,
+}
+
+impl FetchOutcome {
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Whether a repository is there, as told by [`Client::repository_exists`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExistsOutcome {
+    Exists,
+    /// The registry doesn't know the repository, or hides it from the
+    /// credentials as Docker Hub does
+    NotFound,
+    /// The repository is there but the credentials may not pull from it
+    AccessDenied,
+}
+
+impl fmt::Display for ExistsOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExistsOutcome::Exists => write!(f, "exists"),
+            ExistsOutcome::NotFound => write!(f, "not found"),
+            ExistsOutcome::AccessDenied => write!(f, "access denied"),
+        }
+    }
+}
+
+/// Options for [`Client::fetch_tags_many`]
+#[derive(Clone, Debug)]
+pub struct FetchManyOptions {
+    /// Maximum number of images fetched at the same time
+    pub concurrency: usize,
+}
+
+impl Default for FetchManyOptions {
+    fn default() -> Self {
+        FetchManyOptions { concurrency: 8 }
+    }
+}
+
+/// Creation time of a tag's image and the digest of the manifest it was
+/// read for, as told by [`Client::fetch_created_digests`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreatedAt {
+    pub digest: String,
+    pub created: SystemTime,
+}
+
+/// Manifest bytes along with what the registry said about them
+struct RawManifest {
+    content_type: Option<String>,
+    digest: String,
+    body: Vec<u8>,
+}
+
+type PageHook = dyn Fn(&PageInfo) + Send + Sync;
+
+#[cfg(feature = "otel")]
+type ContextHook = dyn Fn(&tracing::Span, &mut header::HeaderMap) + Send + Sync;
+
+/// Unfiltered listings in flight, by registry and repository
+type InFlight = Mutex<HashMap<(String, String), Arc<OnceCell<Arc<Listed>>>>>;
+
+/// An unfiltered listing, shared by the callers which asked for it while
+/// it was in flight
+struct Listed {
+    tags: Vec<Tag>,
+    pages: usize,
+    error: Option<anyhow::Error>,
+    warnings: Vec<Warning>,
+    notices: Vec<ServerNotice>,
+    listed: usize,
+}
+
+impl Listed {
+    /// The tags and the outcome of the listing, taken from the last caller
+    /// holding it and copied for the others
+    fn outcome(listed: Arc<Listed>) -> (Vec<Tag>, FetchOutcome) {
+        let listed = Arc::try_unwrap(listed).unwrap_or_else(|shared| Listed {
+            tags: shared.tags.clone(),
+            error: shared.error.as_ref().map(copy_error),
+            warnings: shared.warnings.clone(),
+            notices: shared.notices.clone(),
+            ..*shared
+        });
+        let outcome = FetchOutcome {
+            pages: listed.pages,
+            error: listed.error,
+            warnings: listed.warnings,
+            notices: listed.notices,
+            listed: listed.listed,
+            matched: listed.listed,
+        };
+        (listed.tags, outcome)
+    }
+}
+
+/// Registry client shared between image lookups
+///
+/// Its futures run their concurrent requests themselves rather than through
+/// spawned tasks, so dropping one, e.g. on a timeout, aborts every request it
+/// has in flight. A lookup among several which panics, say in a
+/// [`CredentialProvider`], fails alone with a `Panicked: …` error or warning.
+///
+/// Callers asking for the same unfiltered listing while it's in flight, e.g.
+/// from clones of the client, share its requests and each get its tags or
+/// its error. Nothing is kept once it's done.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    credentials: Arc<dyn CredentialProvider>,
+    on_page: Option<Arc<PageHook>>,
+    #[cfg(feature = "otel")]
+    inject_context: Option<Arc<ContextHook>>,
+    insecure_registries: HashSet<String>,
+    path_prefixes: HashMap<String, String>,
+    #[cfg(feature = "regex")]
+    search_apis: HashMap<String, SearchApi>,
+    /// Where `docker.io` is reached instead of `https://registry-1.docker.io`
+    docker_hub: Option<Url>,
+    filter_pushdown: bool,
+    /// List the tags again when pages overlap
+    consistent_listing: bool,
+    #[cfg(feature = "github")]
+    github: Option<GitHubPackages>,
+    /// Clients trusting extra root certificates or verifying another server
+    /// name, by host
+    tls_clients: HashMap<String, reqwest::Client>,
+    /// Names in the certificates of registries addressed by IP
+    tls_server_names: HashMap<String, String>,
+    /// Oldest TLS version allowed, by default and by host
+    min_tls_version: TlsVersion,
+    min_tls_versions: HashMap<String, TlsVersion>,
+    /// `Accept` headers of manifest requests narrowed by registry
+    manifest_accepts: HashMap<String, String>,
+    throttles: HashMap<String, Arc<Throttle>>,
+    /// Hosts besides each registry which its pages of tags may lead to and
+    /// its token is sent to
+    trusted_page_hosts: HashMap<String, HashSet<String>>,
+    recorder: Arc<Recorder>,
+    /// Unset unless [`ClientBuilder::allowed_registries`] or
+    /// [`ClientBuilder::denied_registries`] was called, shared with the
+    /// redirect policy of the HTTP clients
+    allowlist: Arc<OnceLock<HostAllowlist>>,
+    in_flight: Arc<InFlight>,
+    /// Tokens issued by [`Client::pre_authorize`], by registry and scope,
+    /// shared by the clones of the client
+    tokens: Arc<Mutex<HashMap<(String, Scope), Secret>>>,
+    /// APIs telling which tags can't be overwritten, by registry, instead of
+    /// the one built in for its kind
+    immutability_lookups: HashMap<String, Arc<dyn ImmutabilityLookup>>,
+    ecr: Ecr,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("Client");
+        f.field("insecure_registries", &self.insecure_registries)
+            .field("path_prefixes", &self.path_prefixes);
+        #[cfg(feature = "regex")]
+        f.field("search_apis", &self.search_apis);
+        f.field("docker_hub", &self.docker_hub)
+            .field("filter_pushdown", &self.filter_pushdown)
+            .field("consistent_listing", &self.consistent_listing)
+            .field("tls_hosts", &self.tls_clients.keys())
+            .field("tls_server_names", &self.tls_server_names)
+            .field("min_tls_version", &self.min_tls_version)
+            .field("min_tls_versions", &self.min_tls_versions)
+            .field("manifest_accepts", &self.manifest_accepts)
+            .field("throttles", &self.throttles)
+            .field("trusted_page_hosts", &self.trusted_page_hosts)
+            .field("allowlist", &self.allowlist.get())
+            .field("immutability_lookups", &self.immutability_lookups.keys())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::builder().build()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Client::default()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Build `<scheme>://<host>/v2/<repository>/<endpoint...>`, percent-encoding
+    /// every path segment
+    fn registry_url(&self, image: &Image, endpoint: &[&str]) -> Result<Url> {
+        let repository = repository_path(image);
+        // Url silently drops "." and ".." segments, so reject them instead
+        if repository
+            .split('/')
+            .any(|c| c.is_empty() || c == "." || c == "..")
+        {
+            return Err(anyhow!("Invalid repository name: {repository:?}"));
+        }
+
+        self.v2_url(
+            &image.registry,
+            repository.split('/').chain(endpoint.iter().copied()),
+        )
+    }
+
+    /// `Accept` header of manifest requests to `registry`
+    fn manifest_accept(&self, registry: &str) -> String {
+        self.manifest_accepts
+            .get(registry)
+            .cloned()
+            .unwrap_or_else(|| MANIFEST_ACCEPT.join(", "))
+    }
+
+    /// `<scheme>://<host>[/<path prefix>]/v2/<segments...>` of `registry`
+    pub(crate) fn v2_url<'a>(
+        &self,
+        registry: &str,
+        segments: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Url> {
+        let mut url = self.registry_base(registry)?;
+        let prefix = self.path_prefixes.get(registry).map_or("", String::as_str);
+        let prefix: Vec<_> = prefix.split('/').filter(|s| !s.is_empty()).collect();
+        if !prefix.iter().all(|segment| is_valid_component(segment)) {
+            return Err(anyhow!("Invalid path prefix for {registry}: {prefix:?}"));
+        }
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid registry host: {registry:?}"))?
+            .clear()
+            .extend(prefix)
+            .push("v2")
+            .extend(segments);
+
+        Ok(url)
+    }
+
+    /// `<scheme>://<host>` of `registry`
+    pub(crate) fn registry_base(&self, registry: &str) -> Result<Url> {
+        if !is_valid_host(registry) {
+            return Err(anyhow!("Invalid registry host: {registry:?}"));
+        }
+        let scheme = match self.insecure_registries.contains(registry) {
+            true => "http",
+            false => "https",
+        };
+        let host = match (registry, self.tls_server_names.get(registry)) {
+            (DOCKER_HUB, _) => match &self.docker_hub {
+                Some(base) => return Ok(base.clone()),
+                None => DOCKER_HUB_REGISTRY.to_string(),
+            },
+            // resolved to the registry's address by its own client
+            (registry, Some(name)) => match split_port(registry) {
+                Some((_, Some(port))) => format!("{name}:{port}"),
+                _ => name.clone(),
+            },
+            (registry, None) => registry.to_string(),
+        };
+        Url::parse(&format!("{scheme}://{host}"))
+            .with_context(|| format!("Invalid registry host: {registry:?}"))
+    }
+
+    /// HTTP client trusting the certificates given for `url`'s host, or else
+    /// those of `registry`, whose token service or search API `url` may be
+    pub(crate) fn http_for(&self, registry: &str, url: &Url) -> &reqwest::Client {
+        self.tls_clients
+            .get(&authority(url))
+            .or_else(|| self.tls_clients.get(registry))
+            .unwrap_or(&self.http)
+    }
+
+    /// Oldest TLS version the client [`Client::http_for`] picks allows
+    fn min_tls_for(&self, registry: &str, url: &Url) -> TlsVersion {
+        [authority(url).as_str(), registry]
+            .into_iter()
+            .find(|host| self.tls_clients.contains_key(*host))
+            .and_then(|host| self.min_tls_versions.get(host))
+            .copied()
+            .unwrap_or(self.min_tls_version)
+    }
+
+    /// Send `req`, a request on behalf of `registry`, once its throttle allows
+    /// and if the allowlist has both `registry` and the host of `req`, and
+    /// denies neither
+    pub(crate) async fn send(
+        &self,
+        registry: &str,
+        kind: RequestKind,
+        req: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        self.resend(registry, kind, req, 0).await
+    }
+
+    /// [`Client::send`] for the `resends`-th time, after as many
+    /// authentication challenges
+    #[cfg_attr(not(feature = "otel"), allow(unused_mut, unused_variables))]
+    async fn resend(
+        &self,
+        registry: &str,
+        kind: RequestKind,
+        req: reqwest::RequestBuilder,
+        resends: u32,
+    ) -> Result<reqwest::Response> {
+        let (http, req) = req.build_split();
+        let mut req = req.map_err(|err| err.without_url())?;
+        if let Some(allowlist) = self.allowlist.get() {
+            allowlist.check(registry, req.url())?;
+        }
+        let _permit = match self.throttles.get(registry) {
+            Some(throttle) => throttle.acquire().await,
+            None => None,
+        };
+        // a HEAD response announces a body it doesn't have
+        let has_body = req.method() != Method::HEAD;
+        let (host, min_tls) = (authority(req.url()), self.min_tls_for(registry, req.url()));
+        #[cfg(feature = "otel")]
+        let span = crate::otel::request_span(&req, resends);
+        #[cfg(feature = "otel")]
+        if let Some(inject) = &self.inject_context {
+            inject(&span, req.headers_mut());
+        }
+        let started = Instant::now();
+        #[cfg(feature = "otel")]
+        let resp = tracing::Instrument::instrument(http.execute(req), span.clone()).await;
+        #[cfg(not(feature = "otel"))]
+        let resp = http.execute(req).await;
+        #[cfg(feature = "otel")]
+        crate::otel::record_response(&span, &resp);
+        let bytes = match &resp {
+            Ok(resp) if has_body => resp.content_length(),
+            _ => None,
+        };
+        self.recorder.request(kind, started, bytes);
+        resp.map_err(|err| {
+            let tls = is_version_mismatch(&err);
+            let err = anyhow::Error::new(err.without_url());
+            match tls {
+                true => err.context(Error::TlsVersion { host, min: min_tls }),
+                false => err,
+            }
+        })
+    }
+
+    /// Requests sent so far by this client and its clones
+    pub fn timings(&self) -> Timings {
+        self.recorder.timings()
+    }
+
+    /// Request a token for the challenge in `hdr`, asking for `extra_scopes`
+    /// on top of the scope named by the challenge itself
+    async fn handle_auth_challenge(
+        &self,
+        registry: &str,
+        hdr: &str,
+        extra_scopes: &[String],
+    ) -> Result<TokenGrant> {
+        let challenges = parse_www_authenticate(hdr)?;
+        let challenge = challenges
+            .iter()
+            .find(|challenge| challenge.is_bearer())
+            .unwrap_or(&challenges[0]);
+        let realm = challenge
+            .realm
+            .as_deref()
+            .with_context(|| format!("No realm found in WWW-Authenticate header: {hdr}"))?;
+        let realm_url = Url::parse(realm)
+            .with_context(|| format!("Failed to parse realm URL: {}", redact_str(realm)))?;
+        let mut scopes: Vec<String> = Vec::new();
+        let own = challenge.scopes.iter().map(Scope::to_string);
+        for scope in own.chain(extra_scopes.iter().cloned()) {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        let credentials = self.credentials.credentials(registry).await?;
+        // good for whatever the challenge asks, without the token service
+        if let Some(Credentials::Token { token }) = credentials {
+            return Ok(TokenGrant {
+                scheme: challenge.scheme.clone(),
+                realm: realm.to_string(),
+                service: challenge.service.clone(),
+                scopes,
+                authenticated: true,
+                token,
+            });
+        }
+        let url = token_url(&realm_url, challenge.service.as_deref(), &scopes);
+
+        let mut req = self.http_for(registry, &url).get(url.clone());
+        match &credentials {
+            Some(Credentials::Basic { username, password }) => {
+                req = req.basic_auth(username, Some(password.expose()));
+            }
+            // posted to the realm as a form rather than in the query
+            Some(Credentials::OidcExchange {
+                subject_token,
+                client_id,
+            }) => {
+                let scope = scopes.join(" ");
+                let mut form = vec![
+                    ("grant_type", TOKEN_EXCHANGE_GRANT),
+                    ("subject_token", subject_token.expose()),
+                    ("subject_token_type", ID_TOKEN_TYPE),
+                    ("client_id", client_id),
+                ];
+                if let Some(service) = &challenge.service {
+                    form.push(("service", service));
+                }
+                if !scope.is_empty() {
+                    form.push(("scope", &scope));
+                }
+                req = self
+                    .http_for(registry, &realm_url)
+                    .post(realm_url.clone())
+                    .form(&form);
+            }
+            Some(Credentials::Token { .. }) | None => {}
+        }
+
+        let resp = self
+            .send(registry, RequestKind::Token, req)
+            .await
+            .with_context(|| format!("Failed to fetch token from {}", redact_url(&url)))?;
+        let data: TokenResponse = match resp.status() {
+            StatusCode::OK => resp
+                .json()
+                .await
+                .map_err(|err| err.without_url())
+                .with_context(|| {
+                    format!("Failed to parse token response from {}", redact_url(&url))
+                })?,
+            status if matches!(credentials, Some(Credentials::OidcExchange { .. })) => {
+                let body = resp.bytes().await.unwrap_or_default();
+                let message = match serde_json::from_slice::<OAuthError>(&body) {
+                    Ok(OAuthError {
+                        error,
+                        error_description,
+                    }) if error_description.is_empty() => error,
+                    Ok(OAuthError {
+                        error,
+                        error_description,
+                    }) => format!("{error}: {error_description}"),
+                    Err(_) => String::from_utf8_lossy(&body).trim().to_string(),
+                };
+                return Err(Error::TokenExchange {
+                    realm: redact_url(&realm_url),
+                    status: status.as_u16(),
+                    message,
+                }
+                .into());
+            }
+            status => return Err(anyhow!("Failed to authenticate: {status}")),
+        };
+
+        Ok(TokenGrant {
+            scheme: challenge.scheme.clone(),
+            realm: realm.to_string(),
+            service: challenge.service.clone(),
+            scopes,
+            authenticated: credentials.is_some(),
+            token: data.token,
+        })
+    }
+
+    /// Token for pulling all of `scopes` from `registry`, empty if the
+    /// registry doesn't ask for one or can't issue it
+    pub(crate) async fn registry_token(&self, registry: &str, scopes: &[String]) -> Secret {
+        let Ok(url) = self.v2_url(registry, [""]) else {
+            return Secret::default();
+        };
+        let req = self.http_for(registry, &url).get(url.clone());
+        let Ok(resp) = self.send(registry, RequestKind::Ping, req).await else {
+            return Secret::default();
+        };
+        let hdr = match resp.status() {
+            StatusCode::UNAUTHORIZED => resp.headers().get(header::WWW_AUTHENTICATE),
+            _ => None,
+        };
+        match hdr.and_then(|hdr| hdr.to_str().ok()) {
+            Some(hdr) => match self.handle_auth_challenge(registry, hdr, scopes).await {
+                Ok(grant) => grant.token,
+                Err(_) => Secret::default(),
+            },
+            None => Secret::default(),
+        }
+    }
+
+    /// Fetch a single token for all of `scopes` at `registry` and start the
+    /// requests needing any of them with it, instead of a challenge and a
+    /// token request per repository
+    ///
+    /// Scopes which already have a token keep it. Returns whether a token
+    /// was issued; without one, requests are authorized one by one as usual.
+    pub async fn pre_authorize(&self, registry: &str, scopes: &[Scope]) -> bool {
+        let mut wanted: Vec<&Scope> = Vec::new();
+        for scope in scopes {
+            if !wanted.contains(&scope) {
+                wanted.push(scope);
+            }
+        }
+        if wanted.is_empty() {
+            return false;
+        }
+        let names: Vec<_> = wanted.iter().map(|scope| scope.to_string()).collect();
+        let token = self.registry_token(registry, &names).await;
+        if token.is_empty() {
+            return false;
+        }
+        let mut tokens = self.tokens.lock().unwrap();
+        for scope in wanted {
+            tokens
+                .entry((registry.to_string(), scope.clone()))
+                .or_insert_with(|| token.clone());
+        }
+        true
+    }
+
+    /// Token [`Client::pre_authorize`] got for pulling from `image`, or an
+    /// empty one to be answered by a challenge
+    fn pull_token(&self, image: &Image) -> Secret {
+        let key = (image.registry.clone(), Scope::pull(repository_path(image)));
+        self.tokens
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn fetch_tags(&self, image: &Image) -> Result<Vec<Tag>> {
+        let (tags, outcome) =
+            Listed::outcome(self.list_shared(image, self.pull_token(image)).await);
+        match outcome.error {
+            Some(err) => Err(err),
+            None => Ok(tags),
+        }
+    }
+
+    /// Every page of `image`'s tags, starting with `token` if it's not
+    /// empty, or those of the same listing another caller has in flight
+    async fn list_shared(&self, image: &Image, token: Secret) -> Arc<Listed> {
+        let key = (image.registry.clone(), image.repository.clone());
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        // if the caller listing is dropped, one of those waiting takes over
+        let listed = cell
+            .get_or_init(|| async {
+                let (mut collector, mut pages) = (TagCollector::new(), 0);
+                let error = self
+                    .collect_tags(image, &mut collector, &mut pages, token)
+                    .await
+                    .err();
+                let listed = collector.listed();
+                let notices = std::mem::take(collector.notices_mut());
+                let (tags, warnings) = collector.finish();
+                Arc::new(Listed {
+                    tags,
+                    pages,
+                    error,
+                    warnings,
+                    notices,
+                    listed,
+                })
+            })
+            .await
+            .clone();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(&key)
+            .is_some_and(|held| Arc::ptr_eq(held, &cell))
+        {
+            in_flight.remove(&key);
+        }
+        listed
+    }
+
+    /// Fetch tags of several images concurrently, returning the results in
+    /// input order
+    ///
+    /// Images on the same registry share a single token for all of their
+    /// repositories. A failing image doesn't affect the others, and dropping
+    /// the future aborts every request in flight.
+    pub async fn fetch_tags_many(
+        &self,
+        images: Vec<Image>,
+        opts: FetchManyOptions,
+    ) -> Vec<(Image, Result<Vec<Tag>>)> {
+        let mut scopes: HashMap<String, Vec<String>> = HashMap::new();
+        for image in &images {
+            let scope = pull_scope(image);
+            let registry_scopes = scopes.entry(image.registry.clone()).or_default();
+            if !registry_scopes.contains(&scope) {
+                registry_scopes.push(scope);
+            }
+        }
+        let tokens: HashMap<&str, OnceCell<Secret>> = scopes
+            .keys()
+            .map(|registry| (registry.as_str(), OnceCell::new()))
+            .collect();
+
+        stream::iter(images)
+            .map(|image| {
+                let (scopes, tokens) = (&scopes, &tokens);
+                async move {
+                    let result = isolated(async {
+                        let registry = image.registry.as_str();
+                        let token = tokens[registry]
+                            .get_or_init(|| self.registry_token(registry, &scopes[registry]))
+                            .await
+                            .clone();
+                        let (tags, outcome) =
+                            Listed::outcome(self.list_shared(&image, token).await);
+                        match outcome.error {
+                            Some(err) => Err(err),
+                            None => Ok(tags),
+                        }
+                    })
+                    .await;
+                    (image, result)
+                }
+            })
+            .buffered(opts.concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Like [`Client::fetch_tags`], but a failure after at least one page was
+    /// fetched returns the tags collected so far along with the error
+    pub async fn fetch_tags_partial(&self, image: &Image) -> Result<(Vec<Tag>, FetchOutcome)> {
+        match Listed::outcome(self.list_shared(image, self.pull_token(image)).await) {
+            (
+                _,
+                FetchOutcome {
+                    pages: 0,
+                    error: Some(err),
+                    ..
+                },
+            ) => Err(err),
+            listing => Ok(listing),
+        }
+    }
+
+    /// Like [`Client::fetch_tags_partial`], keeping only tags matching
+    /// `predicate` in registry order
+    ///
+    /// Pagination stops as soon as `limit` matching tags were seen, so this
+    /// only makes sense when the registry order is wanted: sorting the result
+    /// afterwards doesn't give the same tags as sorting everything.
+    pub async fn fetch_tags_filtered(
+        &self,
+        image: &Image,
+        predicate: impl Fn(&Tag) -> bool + Send + Sync + 'static,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Tag>, FetchOutcome)> {
+        let mut collector = TagCollector::new().filter(predicate);
+        if let Some(limit) = limit {
+            collector = collector.limit(limit);
+        }
+        self.fetch_into(image, collector).await
+    }
+
+    /// Like [`Client::fetch_tags_filtered`], but keeping the `n` matching tags
+    /// which sort first (newest first as with [`sort_tags_desc`](crate::sort_tags_desc)),
+    /// or last if `reverse`, in that order, see [`TagCollector::top`]
+    ///
+    /// Every page is fetched, while memory holds `2n` tags besides the names
+    /// seen, which takes far less than sorting repositories of hundreds of
+    /// thousands of tags.
+    pub async fn fetch_tags_top(
+        &self,
+        image: &Image,
+        predicate: impl Fn(&Tag) -> bool + Send + Sync + 'static,
+        n: usize,
+        reverse: bool,
+    ) -> Result<(Vec<Tag>, FetchOutcome)> {
+        let collector = TagCollector::new().filter(predicate).top(n, reverse);
+        self.fetch_into(image, collector).await
+    }
+
+    /// Like [`Client::fetch_tags_partial`], keeping only tags matching `pattern`
+    ///
+    /// If `pattern` is a plain literal (optionally anchored) and the registry
+    /// has a [`SearchApi`], the literal is pushed down to it so that only
+    /// candidate tags are downloaded; `pattern` is still applied to them. The
+    /// regular listing is used when the search API fails right away.
+    #[cfg(feature = "regex")]
+    pub async fn fetch_tags_matching(
+        &self,
+        image: &Image,
+        pattern: &Regex,
+    ) -> Result<(Vec<Tag>, FetchOutcome)> {
+        let matches = |pattern: &Regex| {
+            let pattern = pattern.clone();
+            move |tag: &Tag| pattern.is_match(tag.name())
+        };
+        let needle = pushdown_literal(pattern.as_str()).filter(|_| self.filter_pushdown);
+        if let (Some(needle), Some(api)) = (needle, self.search_api(&image.registry)) {
+            let mut collector = TagCollector::new().filter(matches(pattern));
+            let mut pages = 0;
+            match api
+                .collect(self, image, &needle, &mut collector, &mut pages)
+                .await
+            {
+                Ok(()) => return Ok(outcome(collector, pages, None)),
+                Err(err) if pages > 0 => return Ok(outcome(collector, pages, Some(err))),
+                Err(_) => {}
+            }
+        }
+
+        self.fetch_into(image, TagCollector::new().filter(matches(pattern)))
+            .await
+    }
+
+    #[cfg(feature = "regex")]
+    fn search_api(&self, registry: &str) -> Option<SearchApi> {
+        match self.search_apis.get(registry) {
+            Some(api) => Some(api.clone()),
+            None => SearchApi::for_registry(registry),
+        }
+    }
+
+    async fn fetch_into(
+        &self,
+        image: &Image,
+        mut collector: TagCollector,
+    ) -> Result<(Vec<Tag>, FetchOutcome)> {
+        let mut pages = 0;
+        let error = match self
+            .collect_tags(image, &mut collector, &mut pages, self.pull_token(image))
+            .await
+        {
+            Ok(()) => None,
+            Err(err) if pages == 0 => return Err(err),
+            Err(err) => Some(err),
+        };
+
+        Ok(outcome(collector, pages, error))
+    }
+
+    /// Feed every page into `collector`, counting successfully fetched pages,
+    /// starting with `token` if it's not empty
+    pub(crate) async fn collect_tags(
+        &self,
+        image: &Image,
+        collector: &mut TagCollector,
+        pages: &mut usize,
+        mut token: Secret,
+    ) -> Result<()> {
+        let (mut cursor, mut last) = (None, None::<String>);
+        let mut retried = false;
+        loop {
+            let (page, next) = match self
+                .tags_page(
+                    image,
+                    cursor.as_ref(),
+                    PAGE_SIZE,
+                    &mut token,
+                    collector.notices_mut(),
+                )
+                .await
+            {
+                Ok(page) => page,
+                Err(err) if *pages == 0 => return Err(self.suggest_namespace(image, err).await),
+                Err(err) => return Err(err),
+            };
+            *pages += 1;
+            // registries list tags in lexical order, so an overlap means the
+            // tags changed while paging
+            let overlap = match (&last, page.first()) {
+                (Some(last), Some(first)) if first <= last => Some((last.clone(), first.clone())),
+                _ => None,
+            };
+            if let Some((last_tag, first)) = overlap {
+                if self.consistent_listing && !retried {
+                    retried = true;
+                    self.recorder.retry();
+                    collector.reset();
+                    (cursor, last) = (None, None);
+                    continue;
+                }
+                collector.warn(Warning::InconsistentPage {
+                    last: last_tag,
+                    first,
+                });
+            }
+            last = page.last().cloned().or(last);
+            collector.push_page(page);
+            match next {
+                Some(next) if !collector.is_full() => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// One page of at most `page_size` of `image`'s tags, starting at `cursor`
+    /// or at the first tag, and the cursor of the next page if there is one
+    ///
+    /// Each call authenticates on its own, so the cursor can be held by the
+    /// caller across requests or processes. Invalid tag names are skipped.
+    pub async fn fetch_tags_page(
+        &self,
+        image: &Image,
+        cursor: Option<PageCursor>,
+        page_size: usize,
+    ) -> Result<(Vec<Tag>, Option<PageCursor>)> {
+        let (page, next) = self
+            .tags_page(
+                image,
+                cursor.as_ref(),
+                page_size.max(1),
+                &mut self.pull_token(image),
+                &mut Vec::new(),
+            )
+            .await?;
+        let tags = page
+            .into_iter()
+            .filter(|name| is_valid_tag(name))
+            .map(Tag::new)
+            .collect();
+        Ok((tags, next))
+    }
+
+    /// Whether `image`'s repository exists and can be read, from a listing of
+    /// a single tag instead of all of them
+    ///
+    /// Errors other than a missing repository or refused access, e.g. an
+    /// unreachable registry, are returned as such.
+    pub async fn repository_exists(&self, image: &Image) -> Result<ExistsOutcome> {
+        let err = match self
+            .tags_page(image, None, 1, &mut self.pull_token(image), &mut Vec::new())
+            .await
+        {
+            Ok(_) => return Ok(ExistsOutcome::Exists),
+            Err(err) => err,
+        };
+        match err.chain().find_map(|cause| cause.downcast_ref::<Error>()) {
+            Some(Error::NotFound) => Ok(ExistsOutcome::NotFound),
+            Some(Error::AccessDenied { .. } | Error::Unauthorized(_)) => {
+                Ok(ExistsOutcome::AccessDenied)
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Go through the token exchange for pulling `image` as every other
+    /// request does, reporting each step instead of just using the token
+    ///
+    /// The challenge comes from a `HEAD` of the `latest` manifest, which is
+    /// sent again with the token for the registry's rate limit headers; Docker
+    /// Hub doesn't count such requests against the limit.
+    #[cfg(feature = "unstable")]
+    pub async fn exchange_token(&self, image: &Image) -> Result<TokenExchange> {
+        let url = self.registry_url(image, &["manifests", "latest"])?;
+        let mut grant: Option<TokenGrant> = None;
+        loop {
+            let mut req = self
+                .http_for(&image.registry, &url)
+                .head(url.clone())
+                .header(header::ACCEPT, self.manifest_accept(&image.registry));
+            if let Some(grant) = &grant {
+                req = req.header(
+                    header::AUTHORIZATION,
+                    format!("Bearer {}", grant.token.expose()),
+                );
+            }
+            let resp = self
+                .resend(
+                    &image.registry,
+                    RequestKind::Manifest,
+                    req,
+                    grant.is_some().into(),
+                )
+                .await
+                .with_context(|| format!("Failed to fetch {}", redact_url(&url)))?;
+            let challenge = resp
+                .headers()
+                .get(header::WWW_AUTHENTICATE)
+                .filter(|_| resp.status() == StatusCode::UNAUTHORIZED && grant.is_none());
+            if let Some(hdr) = challenge {
+                let hdr = hdr
+                    .to_str()
+                    .context("Failed to parse WWW-Authenticate header")?;
+                let scopes = [pull_scope(image)];
+                grant = Some(
+                    self.handle_auth_challenge(&image.registry, hdr, &scopes)
+                        .await?,
+                );
+                self.recorder.retry();
+                continue;
+            }
+
+            let rate_limits = resp
+                .headers()
+                .iter()
+                .filter(|(name, _)| is_rate_limit_header(name.as_str()))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+            return Ok(TokenExchange {
+                grant,
+                status: resp.status(),
+                rate_limits,
+            });
+        }
+    }
+
+    /// One page of tags, adding what the registry announced along with it
+    /// to `notices` unless they're there already
+    async fn tags_page(
+        &self,
+        image: &Image,
+        cursor: Option<&PageCursor>,
+        page_size: usize,
+        token: &mut Secret,
+        notices: &mut Vec<ServerNotice>,
+    ) -> Result<(Vec<String>, Option<PageCursor>)> {
+        let mut challenged = false;
+
+        let first = self.registry_url(image, &["tags", "list"])?;
+        let registry_host = authority(&first);
+        let url = match cursor {
+            Some(cursor) => cursor.url(&first, page_size)?,
+            None => {
+                let mut url = first;
+                url.query_pairs_mut()
+                    .append_pair("n", &page_size.to_string());
+                url
+            }
+        };
+        // the token is only for the registry and the hosts trusted with it
+        let host = authority(&url);
+        let trusted = host == registry_host
+            || self
+                .trusted_page_hosts
+                .get(&image.registry)
+                .is_some_and(|hosts| hosts.contains(&host));
+        loop {
+            let mut req = self.http_for(&image.registry, &url).get(url.clone());
+            if trusted && !token.is_empty() {
+                req = req.header(header::AUTHORIZATION, format!("Bearer {}", token.expose()));
+            }
+
+            let started = Instant::now();
+            let resp = self
+                .resend(
+                    &image.registry,
+                    RequestKind::TagsPage,
+                    req,
+                    challenged.into(),
+                )
+                .await
+                .with_context(|| format!("Failed to fetch tags from {}", redact_url(&url)))?;
+            let status = resp.status();
+            for notice in ServerNotice::from_headers(resp.headers()) {
+                if !notices.contains(&notice) {
+                    notices.push(notice);
+                }
+            }
+            match status {
+                StatusCode::OK => {
+                    let link = resp
+                        .headers()
+                        .get(header::LINK)
+                        .and_then(|link| link.to_str().ok())
+                        .map(str::to_string);
+                    let body = resp
+                        .bytes()
+                        .await
+                        .map_err(|err| err.without_url())
+                        .with_context(|| {
+                            format!("Failed to fetch tags from {}", redact_url(&url))
+                        })?;
+                    if let Some(err) = Error::from_body(&body) {
+                        self.page_done(url.as_str(), status, 0, started);
+                        return Err(err.into());
+                    }
+                    let data: TagsResponse = serde_json::from_slice(&body).with_context(|| {
+                        format!("Failed to parse JSON from {}", redact_url(&url))
+                    })?;
+                    let page = data.tags.unwrap_or_default();
+                    self.page_done(url.as_str(), status, page.len(), started);
+                    let next = PageCursor::after(&url, link.as_deref(), &page, page_size);
+                    return Ok((page, next));
+                }
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN if !trusted => {
+                    self.page_done(url.as_str(), status, 0, started);
+                    return Err(anyhow!(
+                        "Got HTTP {status} for tags at {}: the registry's pages lead to {host}, \
+                         which isn't trusted with the token of {}",
+                        redact_url(&url),
+                        image.registry
+                    ));
+                }
+                StatusCode::UNAUTHORIZED
+                    if resp.headers().contains_key(header::WWW_AUTHENTICATE) =>
+                {
+                    self.page_done(url.as_str(), status, 0, started);
+                    // a token passed in may lack this scope, so get our own once
+                    if challenged {
+                        return Err(anyhow!("Got HTTP 401 with authentication token")
+                            .context(Error::NotFound));
+                    }
+                    challenged = true;
+
+                    let hdr = resp
+                        .headers()
+                        .get(header::WWW_AUTHENTICATE)
+                        .unwrap()
+                        .to_str()
+                        .context("Failed to parse WWW-Authenticate header")?;
+                    *token = match self.handle_auth_challenge(&image.registry, hdr, &[]).await {
+                        Ok(grant) => grant.token,
+                        // the credentials, not the repository, are at fault
+                        Err(err) if is_credentials_error(&err) => return Err(err),
+                        Err(err) => return Err(err.context(Error::NotFound)),
+                    };
+                    self.recorder.retry();
+                }
+                // anonymous access may be denied where stored credentials aren't
+                StatusCode::FORBIDDEN if !challenged => {
+                    self.page_done(url.as_str(), status, 0, started);
+                    challenged = true;
+                    match self.reauthenticate(image).await? {
+                        Some(new_token) => {
+                            *token = new_token;
+                            self.recorder.retry();
+                        }
+                        None => return Err(status_error(resp, Some(image)).await),
+                    }
+                }
+                status => {
+                    self.page_done(url.as_str(), status, 0, started);
+                    return Err(status_error(resp, Some(image)).await);
+                }
+            }
+        }
+    }
+
+    /// `err` of listing a Docker Hub image without a namespace, which was
+    /// looked up under `library/`, naming the user's own repository of the
+    /// same name if the stored credentials have one
+    ///
+    /// That takes a single lookup, and `err` is returned as it is if it fails.
+    async fn suggest_namespace(&self, image: &Image, err: anyhow::Error) -> anyhow::Error {
+        // a token failing for the scope adds `NotFound` as context
+        let not_found = matches!(
+            err.downcast_ref::<Error>()
+                .or_else(|| err.chain().find_map(|cause| cause.downcast_ref())),
+            Some(Error::NotFound)
+        );
+        if !not_found || repository_path(image) == image.repository {
+            return err;
+        }
+        let Ok(Some(credentials)) = self.credentials.credentials(&image.registry).await else {
+            return err;
+        };
+        if credentials.username().is_empty() {
+            return err;
+        }
+        let suggestion = Image::new(
+            &image.registry,
+            format!("{}/{}", credentials.username(), image.repository),
+        );
+        match self.repository_exists(&suggestion).await {
+            Ok(ExistsOutcome::Exists) => err.context(Error::NotInLibrary {
+                repository: image.repository.clone(),
+                suggestion: suggestion.repository,
+            }),
+            _ => err,
+        }
+    }
+
+    /// Token obtained with the stored credentials for `image`, or `None` if
+    /// there are none or the registry doesn't issue one
+    async fn reauthenticate(&self, image: &Image) -> Result<Option<Secret>> {
+        if self
+            .credentials
+            .credentials(&image.registry)
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let token = self
+            .registry_token(&image.registry, &[pull_scope(image)])
+            .await;
+        Ok(Some(token).filter(|token| !token.is_empty()))
+    }
+
+    /// Fetch the manifest of `reference`, a tag or a digest
+    pub async fn fetch_manifest(&self, image: &Image, reference: &str) -> Result<Manifest> {
+        self.manifest(image, reference, &mut self.pull_token(image))
+            .await
+    }
+
+    /// Fetch the image config of `reference`, using the `linux/amd64` entry
+    /// if it points to an index
+    pub async fn fetch_config(&self, image: &Image, reference: &str) -> Result<ImageConfig> {
+        self.config(image, reference, &mut self.pull_token(image))
+            .await
+    }
+
+    /// When `image` was last pushed to, for registries whose [`SearchApi`]
+    /// tells (Docker Hub's), `None` for the others
+    #[cfg(feature = "regex")]
+    pub async fn fetch_last_pushed(&self, image: &Image) -> Result<Option<SystemTime>> {
+        match self.search_api(&image.registry) {
+            Some(api) => api.last_pushed(self, image).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Creation times of `tags` from their image configs, in the same order
+    ///
+    /// Tags whose config can't be fetched or has no valid `created` timestamp
+    /// are skipped and reported as warnings. With a GitHub token, `ghcr.io`
+    /// images use the push times of the GitHub Packages API instead, where the
+    /// token can see the package.
+    pub async fn fetch_created(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+    ) -> (Vec<(Tag, SystemTime)>, Vec<Warning>) {
+        #[cfg(feature = "github")]
+        if let Some(github) = &self.github
+            && let Some(Ok(dates)) = github.created(self, image).await
+        {
+            // tags pushed since or otherwise unknown to the API
+            let missing: Vec<_> = tags
+                .iter()
+                .filter(|tag| !dates.contains_key(tag.name()))
+                .cloned()
+                .collect();
+            let (fetched, warnings) = self.created_from_configs(image, &missing).await;
+            let fetched: HashMap<_, _> =
+                fetched.iter().map(|(tag, at)| (tag.name(), *at)).collect();
+            let created = tags
+                .iter()
+                .filter_map(|tag| {
+                    let at = dates.get(tag.name()).or(fetched.get(tag.name()))?;
+                    Some((tag.clone(), *at))
+                })
+                .collect();
+            return (created, warnings);
+        }
+        self.created_from_configs(image, tags).await
+    }
+
+    async fn created_from_configs(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+    ) -> (Vec<(Tag, SystemTime)>, Vec<Warning>) {
+        let (created, warnings) = self
+            .fetch_created_digests(image, tags, &HashMap::new())
+            .await;
+        let created = created
+            .into_iter()
+            .map(|(tag, at)| (tag, at.created))
+            .collect();
+        (created, warnings)
+    }
+
+    /// Like [`Client::fetch_created`] without the GitHub Packages API, along
+    /// with the digest of each tag's manifest
+    ///
+    /// The dates of digests in `known` are taken from there rather than from
+    /// their configs, so a tag whose date was looked up before costs a single
+    /// manifest request as long as it still points to the same image.
+    pub async fn fetch_created_digests(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+        known: &HashMap<String, SystemTime>,
+    ) -> (Vec<(Tag, CreatedAt)>, Vec<Warning>) {
+        let Some((first, rest)) = tags.split_first() else {
+            return (Vec::new(), Vec::new());
+        };
+        // fetch one config up front so the concurrent lookups share its token
+        let mut token = self.pull_token(image);
+        let first = isolated(self.created_at(image, first.name(), known, &mut token)).await;
+        let token = &token;
+        let rest: Vec<_> = stream::iter(rest)
+            .map(|tag| async move {
+                isolated(self.created_at(image, tag.name(), known, &mut token.clone())).await
+            })
+            .buffered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+
+        let mut created = Vec::new();
+        let mut warnings = Vec::new();
+        for (tag, found) in tags.iter().zip([first].into_iter().chain(rest)) {
+            match found {
+                Ok((digest, Some(created_at))) => created.push((
+                    tag.clone(),
+                    CreatedAt {
+                        digest,
+                        created: created_at,
+                    },
+                )),
+                Ok((_, None)) => warnings.push(Warning::NoCreatedDate {
+                    tag: tag.name().to_string(),
+                }),
+                Err(err) => warnings.push(Warning::ManifestFetchFailed {
+                    tag: tag.name().to_string(),
+                    source: format!("{err:#}"),
+                }),
+            }
+        }
+        (created, warnings)
+    }
+
+    /// Manifest digest of `reference` and the creation time of its image
+    async fn created_at(
+        &self,
+        image: &Image,
+        reference: &str,
+        known: &HashMap<String, SystemTime>,
+        token: &mut Secret,
+    ) -> Result<(String, Option<SystemTime>)> {
+        let raw = self.manifest_raw(image, reference, token).await?;
+        if let Some(at) = known.get(&raw.digest) {
+            return Ok((raw.digest, Some(*at)));
+        }
+        let manifest = Manifest::parse(raw.content_type.as_deref(), &raw.body)?;
+        let config = self
+            .manifest_config(image, reference, manifest, token)
+            .await?;
+        Ok((raw.digest, config.created_at()))
+    }
+
+    async fn config(
+        &self,
+        image: &Image,
+        reference: &str,
+        token: &mut Secret,
+    ) -> Result<ImageConfig> {
+        let manifest = self.manifest(image, reference, token).await?;
+        self.manifest_config(image, reference, manifest, token)
+            .await
+    }
+
+    /// Image config of `manifest`, the one of `reference`, going through
+    /// the `linux/amd64` entry of an index
+    async fn manifest_config(
+        &self,
+        image: &Image,
+        reference: &str,
+        manifest: Manifest,
+        token: &mut Secret,
+    ) -> Result<ImageConfig> {
+        let manifest = match manifest {
+            Manifest::Index(index) => {
+                let entry = index.find("linux", "amd64").with_context(|| {
+                    format!(
+                        "No linux/amd64 manifest for {}",
+                        Reference::of(image, reference)
+                    )
+                })?;
+                self.manifest(image, &entry.digest, token).await?
+            }
+            manifest => manifest,
+        };
+        let config = &manifest
+            .image()
+            .context("Manifest index points to another index")?
+            .config;
+        self.config_blob(image, config, token).await
+    }
+
+    /// Fetch and verify the config blob `config` refers to
+    async fn config_blob(
+        &self,
+        image: &Image,
+        config: &Descriptor,
+        token: &mut Secret,
+    ) -> Result<ImageConfig> {
+        let url = self.registry_url(image, &["blobs", &config.digest])?;
+        let resp = self
+            .send_authorized(
+                &image.registry,
+                RequestKind::Blob,
+                &url,
+                Some(&config.media_type),
+                token,
+            )
+            .await?;
+        let body = read_body(resp, &url, Some(image)).await?;
+        verify_digest(&config.digest, &body)
+            .with_context(|| format!("Failed to verify config from {}", redact_url(&url)))?;
+
+        ImageConfig::parse(&body)
+    }
+
+    /// Platforms each of `tags` can be pulled for, in the same order
+    ///
+    /// Manifests are fetched concurrently and a single-platform image's config
+    /// once per digest, so aliases cost one request each. Tags which can't be
+    /// resolved get `None` and a warning.
+    pub async fn fetch_platforms(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+    ) -> (Vec<Option<Vec<Platform>>>, Vec<Warning>) {
+        let Some((first, rest)) = tags.split_first() else {
+            return (Vec::new(), Vec::new());
+        };
+        // fetch one manifest up front so the concurrent lookups share its token
+        let mut token = self.pull_token(image);
+        let first = isolated(self.manifest(image, first.name(), &mut token)).await;
+        let token = &token;
+        let rest: Vec<_> = stream::iter(rest)
+            .map(|tag| async move {
+                isolated(self.manifest(image, tag.name(), &mut token.clone())).await
+            })
+            .buffered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+        let manifests: Vec<_> = [first].into_iter().chain(rest).collect();
+
+        // only the config tells a single-platform image's platform
+        let descriptors: HashMap<_, _> = manifests
+            .iter()
+            .filter_map(|manifest| manifest.as_ref().ok()?.image())
+            .map(|manifest| (manifest.config.digest.as_str(), &manifest.config))
+            .collect();
+        let configs: HashMap<_, _> = stream::iter(descriptors)
+            .map(|(digest, config)| async move {
+                let config = isolated(self.config_blob(image, config, &mut token.clone())).await;
+                (digest, config)
+            })
+            .buffer_unordered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+
+        let mut platforms = Vec::new();
+        let mut warnings = Vec::new();
+        for (tag, manifest) in tags.iter().zip(&manifests) {
+            let found = match manifest {
+                Ok(Manifest::Index(index)) => Ok(index
+                    .manifests
+                    .iter()
+                    .filter_map(|entry| entry.platform.clone())
+                    .collect()),
+                Ok(Manifest::DockerV2(manifest) | Manifest::Oci(manifest)) => {
+                    match &configs[manifest.config.digest.as_str()] {
+                        Ok(config) => Ok(vec![Platform {
+                            architecture: config.architecture.clone(),
+                            os: config.os.clone(),
+                            variant: config.variant.clone(),
+                        }]),
+                        Err(err) => Err(format!("{err:#}")),
+                    }
+                }
+                Err(err) => Err(format!("{err:#}")),
+            };
+            match found {
+                Ok(found) => platforms.push(Some(found)),
+                Err(source) => {
+                    platforms.push(None);
+                    warnings.push(Warning::ManifestFetchFailed {
+                        tag: tag.name().to_string(),
+                        source,
+                    });
+                }
+            }
+        }
+        (platforms, warnings)
+    }
+
+    /// Repository and commit each of `tags` was built from, in the same order
+    ///
+    /// The `org.opencontainers.image.source` and `.revision` annotations of a
+    /// tag's manifest come first, then those of the `linux/amd64` entry of an
+    /// index and of its manifest, then the labels of the image config, which
+    /// is fetched once per digest. Tags which can't be resolved get `None` and
+    /// a warning.
+    pub async fn fetch_sources(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+    ) -> (Vec<Option<ImageSource>>, Vec<Warning>) {
+        let Some((first, rest)) = tags.split_first() else {
+            return (Vec::new(), Vec::new());
+        };
+        // fetch one manifest up front so the concurrent lookups share its token
+        let mut token = self.pull_token(image);
+        let first = isolated(self.annotated_source(image, first.name(), &mut token)).await;
+        let token = &token;
+        let rest: Vec<_> = stream::iter(rest)
+            .map(|tag| async move {
+                isolated(self.annotated_source(image, tag.name(), &mut token.clone())).await
+            })
+            .buffered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+        let found: Vec<_> = [first].into_iter().chain(rest).collect();
+
+        let descriptors: HashMap<_, _> = found
+            .iter()
+            .filter_map(|found| found.as_ref().ok()?.1.as_ref())
+            .map(|config| (config.digest.as_str(), config))
+            .collect();
+        let configs: HashMap<_, _> = stream::iter(descriptors)
+            .map(|(digest, config)| async move {
+                let config = isolated(self.config_blob(image, config, &mut token.clone())).await;
+                (digest, config)
+            })
+            .buffer_unordered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+
+        let mut sources = Vec::new();
+        let mut warnings = Vec::new();
+        for (tag, found) in tags.iter().zip(&found) {
+            match found {
+                Ok((source, config)) => {
+                    let mut source = source.clone();
+                    match config
+                        .as_ref()
+                        .map(|config| &configs[config.digest.as_str()])
+                    {
+                        Some(Ok(config)) => source.fill(&config.labels),
+                        Some(Err(err)) => warnings.push(Warning::ManifestFetchFailed {
+                            tag: tag.name().to_string(),
+                            source: format!("{err:#}"),
+                        }),
+                        None => {}
+                    }
+                    sources.push(Some(source));
+                }
+                Err(err) => {
+                    sources.push(None);
+                    warnings.push(Warning::ManifestFetchFailed {
+                        tag: tag.name().to_string(),
+                        source: format!("{err:#}"),
+                    });
+                }
+            }
+        }
+        (sources, warnings)
+    }
+
+    /// Source annotations of `reference`'s manifests, and its config if they
+    /// don't tell everything
+    async fn annotated_source(
+        &self,
+        image: &Image,
+        reference: &str,
+        token: &mut Secret,
+    ) -> Result<(ImageSource, Option<Descriptor>)> {
+        let mut source = ImageSource::default();
+        let manifest = match self.manifest(image, reference, token).await? {
+            Manifest::Index(index) => {
+                source.fill(&index.annotations);
+                let Some(entry) = index.find("linux", "amd64") else {
+                    return Ok((source, None));
+                };
+                source.fill(&entry.annotations);
+                if source.is_complete() {
+                    return Ok((source, None));
+                }
+                self.manifest(image, &entry.digest, token).await?
+            }
+            manifest => manifest,
+        };
+        let Some(manifest) = manifest.image() else {
+            return Ok((source, None));
+        };
+        source.fill(&manifest.annotations);
+        let config = Some(manifest.config.clone()).filter(|_| !source.is_complete());
+        Ok((source, config))
+    }
+
+    /// Resolve `reference` to the digest of its manifest
+    pub async fn fetch_digest(&self, image: &Image, reference: &str) -> Result<String> {
+        self.resolve_digest(image, reference, &mut self.pull_token(image))
+            .await
+    }
+
+    /// Digest of each of `tags`' manifest, in the same order
+    ///
+    /// Tags which can't be resolved get `None` and a warning.
+    pub async fn fetch_digests(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+    ) -> (Vec<Option<String>>, Vec<Warning>) {
+        let Some((first, rest)) = tags.split_first() else {
+            return (Vec::new(), Vec::new());
+        };
+        // resolve one tag up front so the concurrent lookups share its token
+        let mut token = self.pull_token(image);
+        let first = isolated(self.resolve_digest(image, first.name(), &mut token)).await;
+        let token = &token;
+        let rest: Vec<_> = stream::iter(rest)
+            .map(|tag| async move {
+                isolated(self.resolve_digest(image, tag.name(), &mut token.clone())).await
+            })
+            .buffered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+
+        let mut digests = Vec::new();
+        let mut warnings = Vec::new();
+        for (tag, resolved) in tags.iter().zip([first].into_iter().chain(rest)) {
+            match resolved {
+                Ok(digest) => digests.push(Some(digest)),
+                Err(err) => {
+                    digests.push(None);
+                    warnings.push(Warning::ManifestFetchFailed {
+                        tag: tag.name().to_string(),
+                        source: format!("{err:#}"),
+                    });
+                }
+            }
+        }
+        (digests, warnings)
+    }
+
+    /// Whether each of `tags` can't be overwritten, in the same order
+    ///
+    /// Harbor's artifact API tells which tags its immutability rules protect,
+    /// and Azure Container Registry whether writing each tag is disabled, in
+    /// a request per page of tags. ECR's repository setting and its exclusion
+    /// filters come from the AWS API, signed with [`AwsCredentials`].
+    /// [`ClientBuilder::immutability_lookup`] gives an API for a registry
+    /// instead. Elsewhere every tag is `None`, as are tags the answer leaves
+    /// out; a lookup which fails adds a warning.
+    pub async fn fetch_immutable(
+        &self,
+        image: &Image,
+        tags: &[Tag],
+    ) -> (Vec<TagImmutability>, Vec<Warning>) {
+        let (immutable, warnings) = match immutable_tags(self, image, tags).await {
+            Ok(immutable) => (immutable, Vec::new()),
+            Err(err) => (
+                vec![None; tags.len()],
+                vec![Warning::ImmutabilityUnknown {
+                    source: format!("{err:#}"),
+                }],
+            ),
+        };
+        let immutable = tags
+            .iter()
+            .zip(immutable)
+            .map(|(tag, immutable)| TagImmutability {
+                tag: tag.clone(),
+                immutable,
+            })
+            .collect();
+        (immutable, warnings)
+    }
+
+    /// Tags among `candidates` whose manifest has `digest`, in the same order
+    ///
+    /// Tags which can't be resolved are skipped and reported as warnings.
+    pub async fn find_aliases(
+        &self,
+        image: &Image,
+        digest: &str,
+        candidates: &[Tag],
+    ) -> (Vec<Tag>, Vec<Warning>) {
+        let Some((first, rest)) = candidates.split_first() else {
+            return (Vec::new(), Vec::new());
+        };
+        // resolve one tag up front so the concurrent lookups share its token
+        let mut token = self.pull_token(image);
+        let first = isolated(self.resolve_digest(image, first.name(), &mut token)).await;
+        let token = &token;
+        let rest: Vec<_> = stream::iter(rest)
+            .map(|tag| async move {
+                isolated(self.resolve_digest(image, tag.name(), &mut token.clone())).await
+            })
+            .buffered(FetchManyOptions::default().concurrency)
+            .collect()
+            .await;
+
+        let mut aliases = Vec::new();
+        let mut warnings = Vec::new();
+        for (tag, resolved) in candidates.iter().zip([first].into_iter().chain(rest)) {
+            match resolved {
+                Ok(resolved) if resolved == digest => aliases.push(tag.clone()),
+                Ok(_) => {}
+                Err(err) => warnings.push(Warning::ManifestFetchFailed {
+                    tag: tag.name().to_string(),
+                    source: format!("{err:#}"),
+                }),
+            }
+        }
+        (aliases, warnings)
+    }
+
+    pub(crate) async fn resolve_digest(
+        &self,
+        image: &Image,
+        reference: &str,
+        token: &mut Secret,
+    ) -> Result<String> {
+        if is_digest(reference) {
+            return Ok(reference.to_string());
+        }
+        Ok(self.manifest_raw(image, reference, token).await?.digest)
+    }
+
+    /// List artifacts (signatures, SBOMs, attestations) attached to `digest`
+    ///
+    /// Uses the OCI referrers API, falling back to cosign-style
+    /// `sha256-<hex>.<kind>` tags on registries which don't support it.
+    pub async fn list_referrers(&self, image: &Image, digest: &str) -> Result<Vec<Descriptor>> {
+        if !is_digest(digest) {
+            return Err(anyhow!("Invalid digest: {digest:?}"));
+        }
+        let mut token = self.pull_token(image);
+        if let Some(referrers) = self.native_referrers(image, digest, &mut token).await? {
+            return Ok(referrers);
+        }
+
+        let mut collector = TagCollector::new();
+        self.collect_tags(image, &mut collector, &mut 0, token.clone())
+            .await?;
+        let prefix = format!("{}.", digest.replacen(':', "-", 1));
+        let mut referrers = Vec::new();
+        for tag in collector.finish().0 {
+            let Some(kind) = tag.name().strip_prefix(&prefix) else {
+                continue;
+            };
+            let raw = self.manifest_raw(image, tag.name(), &mut token).await?;
+            referrers.push(Descriptor {
+                media_type: raw.content_type.unwrap_or_default(),
+                artifact_type: cosign_artifact_type(kind).map(str::to_string),
+                digest: raw.digest,
+                size: raw.body.len() as u64,
+                platform: None,
+                annotations: BTreeMap::new(),
+            });
+        }
+
+        Ok(referrers)
+    }
+
+    /// Referrers reported by the registry's referrers API, or `None` if it
+    /// doesn't support it
+    pub(crate) async fn native_referrers(
+        &self,
+        image: &Image,
+        digest: &str,
+        token: &mut Secret,
+    ) -> Result<Option<Vec<Descriptor>>> {
+        let url = self.registry_url(image, &["referrers", digest])?;
+        let resp = self
+            .send_authorized(
+                &image.registry,
+                RequestKind::Manifest,
+                &url,
+                Some(OCI_INDEX),
+                token,
+            )
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = read_body(resp, &url, Some(image)).await?;
+        let index: ManifestIndex = serde_json::from_slice(&body)
+            .with_context(|| format!("Failed to parse referrers from {}", redact_url(&url)))?;
+        Ok(Some(index.manifests))
+    }
+
+    async fn manifest(
+        &self,
+        image: &Image,
+        reference: &str,
+        token: &mut Secret,
+    ) -> Result<Manifest> {
+        let raw = self.manifest_raw(image, reference, token).await?;
+        Manifest::parse(raw.content_type.as_deref(), &raw.body)
+    }
+
+    /// Fetch the manifest bytes of `reference`, verified against its digest
+    async fn manifest_raw(
+        &self,
+        image: &Image,
+        reference: &str,
+        token: &mut Secret,
+    ) -> Result<RawManifest> {
+        if !is_valid_tag(reference) && !is_digest(reference) {
+            return Err(anyhow!("Invalid reference: {reference:?}"));
+        }
+        let url = self.registry_url(image, &["manifests", reference])?;
+        let resp = self
+            .send_authorized(
+                &image.registry,
+                RequestKind::Manifest,
+                &url,
+                Some(&self.manifest_accept(&image.registry)),
+                token,
+            )
+            .await?;
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let content_type = header(header::CONTENT_TYPE);
+        // a digest reference is what the body must match, whatever the header says
+        let digest = match is_digest(reference) {
+            true => Some(reference.to_string()),
+            false => header(HeaderName::from_static("docker-content-digest")),
+        };
+        let body = read_body(resp, &url, Some(image)).await?;
+        // checked first, as a signed one never matches its digest
+        if is_schema1(content_type.as_deref(), &body) {
+            let separator = if is_digest(reference) { '@' } else { ':' };
+            return Err(anyhow!(
+                "Legacy schema1 manifest not supported: {image}{separator}{reference}"
+            ));
+        }
+        let digest = match digest {
+            Some(digest) => {
+                verify_digest(&digest, &body).with_context(|| {
+                    format!("Failed to verify manifest from {}", redact_url(&url))
+                })?;
+                digest
+            }
+            None => sha256_digest(&body),
+        };
+
+        Ok(RawManifest {
+            content_type,
+            digest,
+            body,
+        })
+    }
+
+    /// GET `url`, answering at most one bearer challenge; `token` is sent if
+    /// set and replaced by the one obtained from the challenge
+    pub(crate) async fn send_authorized(
+        &self,
+        registry: &str,
+        kind: RequestKind,
+        url: &Url,
+        accept: Option<&str>,
+        token: &mut Secret,
+    ) -> Result<reqwest::Response> {
+        let mut challenged = false;
+        loop {
+            let mut req = self.http_for(registry, url).get(url.clone());
+            if let Some(accept) = accept {
+                req = req.header(header::ACCEPT, accept);
+            }
+            if !token.is_empty() {
+                req = req.header(header::AUTHORIZATION, format!("Bearer {}", token.expose()));
+            }
+            let resp = self
+                .resend(registry, kind, req, challenged.into())
+                .await
+                .with_context(|| format!("Failed to fetch {}", redact_url(url)))?;
+
+            match resp.headers().get(header::WWW_AUTHENTICATE) {
+                Some(hdr) if resp.status() == StatusCode::UNAUTHORIZED && !challenged => {
+                    challenged = true;
+                    let hdr = hdr
+                        .to_str()
+                        .context("Failed to parse WWW-Authenticate header")?;
+                    *token = self.handle_auth_challenge(registry, hdr, &[]).await?.token;
+                    self.recorder.retry();
+                }
+                _ => return Ok(resp),
+            }
+        }
+    }
+
+    /// Credentials of `registry` for APIs of its own beside the registry API,
+    /// such as Harbor's
+    pub(crate) async fn api_credentials(&self, registry: &str) -> Result<Option<Credentials>> {
+        self.credentials.credentials(registry).await
+    }
+
+    pub(crate) fn immutability_lookup(&self, registry: &str) -> Option<&dyn ImmutabilityLookup> {
+        self.immutability_lookups
+            .get(registry)
+            .map(|lookup| &**lookup)
+    }
+
+    pub(crate) fn ecr(&self) -> &Ecr {
+        &self.ecr
+    }
+
+    /// Same client, asking its credential provider at most once per registry
+    pub(crate) fn with_cached_credentials(&self) -> Client {
+        Client {
+            credentials: Arc::new(CachedProvider::new(self.credentials.clone())),
+            ..self.clone()
+        }
+    }
+
+    fn page_done(&self, url: &str, status: StatusCode, tags: usize, started: Instant) {
+        if let Some(on_page) = &self.on_page {
+            on_page(&PageInfo {
+                url,
+                status,
+                tags,
+                elapsed: started.elapsed(),
+            });
+        }
+    }
+}
+
+fn outcome(
+    mut collector: TagCollector,
+    pages: usize,
+    error: Option<anyhow::Error>,
+) -> (Vec<Tag>, FetchOutcome) {
+    let (listed, matched) = (collector.listed(), collector.matched());
+    let notices = std::mem::take(collector.notices_mut());
+    let (tags, warnings) = collector.finish();
+    (
+        tags,
+        FetchOutcome {
+            pages,
+            error,
+            warnings,
+            notices,
+            listed,
+            matched,
+        },
+    )
+}
+
+/// A copy of `err` for another caller: its messages, from the [`Error`] in
+/// its chain if there is one, so that it downcasts the same
+fn copy_error(err: &anyhow::Error) -> anyhow::Error {
+    let causes: Vec<_> = err.chain().collect();
+    let found = causes
+        .iter()
+        .position(|cause| cause.downcast_ref::<Error>().is_some());
+    let (last, mut copy) = match found {
+        Some(i) => (
+            i,
+            anyhow::Error::new(causes[i].downcast_ref::<Error>().unwrap().clone()),
+        ),
+        None => (causes.len() - 1, anyhow!("{}", causes[causes.len() - 1])),
+    };
+    for cause in causes[..last].iter().rev() {
+        copy = copy.context(cause.to_string());
+    }
+    copy
+}
+
+/// Result of `task`, or an error if it panicked, so that one lookup among
+/// concurrent ones fails alone instead of unwinding through its caller
+async fn isolated<T>(task: impl Future<Output = Result<T>>) -> Result<T> {
+    match AssertUnwindSafe(task).catch_unwind().await {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("no message");
+            Err(anyhow!("Panicked: {message}"))
+        }
+    }
+}
+
+/// Body of a successful response, or the error the registry reported
+pub(crate) async fn read_body(
+    resp: reqwest::Response,
+    url: &Url,
+    image: Option<&Image>,
+) -> Result<Vec<u8>> {
+    if resp.status() != StatusCode::OK {
+        return Err(status_error(resp, image).await);
+    }
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|err| err.without_url())
+        .with_context(|| format!("Failed to fetch {}", redact_url(url)))?;
+    Ok(body.to_vec())
+}
+
+/// Error for an unexpected status, preferring the registry's own error envelope
+///
+/// A 403 for `image` is reported as [`Error::AccessDenied`] whatever the
+/// envelope's code.
+async fn status_error(resp: reqwest::Response, image: Option<&Image>) -> anyhow::Error {
+    let status = resp.status();
+    let body = resp.bytes().await.unwrap_or_default();
+    match (Error::from_body(&body), status, image) {
+        (err @ (None | Some(Error::Unauthorized(_))), StatusCode::FORBIDDEN, Some(image)) => {
+            let message = match err {
+                Some(Error::Unauthorized(message)) => message,
+                _ => String::new(),
+            };
+            Error::AccessDenied {
+                registry: image.registry.clone(),
+                repository: image.repository.clone(),
+                message,
+            }
+            .into()
+        }
+        (Some(err), _, _) => err.into(),
+        (None, StatusCode::NOT_FOUND, _) => Error::NotFound.into(),
+        (None, status, _) => anyhow!(status),
+    }
+}
+
+/// Whether `err` is about the credentials for a registry, e.g. those
+/// [required](DockerConfigProvider::require_credentials) or an unreadable
+/// password file
+fn is_credentials_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref(),
+        Some(
+            Error::CorruptCredentials(_)
+                | Error::MissingCredentials { .. }
+                | Error::PasswordFile { .. }
+                | Error::TokenFile { .. }
+                | Error::OidcToken { .. }
+                | Error::TokenExchange { .. }
+        )
+    )
+}
+
+/// Token scope needed to list tags and pull manifests of `image`
+pub(crate) fn pull_scope(image: &Image) -> String {
+    Scope::pull(repository_path(image)).to_string()
+}
+
+/// Repository path as the registry knows it, with Docker Hub's `library/` prefix
+pub(crate) fn repository_path(image: &Image) -> String {
+    match image.registry == DOCKER_HUB && !image.repository.contains('/') {
+        true => format!("library/{}", image.repository),
+        false => image.repository.clone(),
+    }
+}
+
+/// Builder for [`Client`]
+#[derive(Default)]
+pub struct ClientBuilder {
+    credentials: Option<Arc<dyn CredentialProvider>>,
+    on_page: Option<Arc<PageHook>>,
+    #[cfg(feature = "otel")]
+    inject_context: Option<Arc<ContextHook>>,
+    insecure_registries: HashSet<String>,
+    path_prefixes: HashMap<String, String>,
+    #[cfg(feature = "regex")]
+    search_apis: HashMap<String, SearchApi>,
+    docker_hub: Option<Url>,
+    no_filter_pushdown: bool,
+    consistent_listing: bool,
+    #[cfg(feature = "github")]
+    github_token: Option<Secret>,
+    #[cfg(feature = "github")]
+    github_apis: HashMap<String, Url>,
+    root_certificates: HashMap<String, Vec<reqwest::Certificate>>,
+    tls_server_names: HashMap<String, String>,
+    min_tls_version: TlsVersion,
+    min_tls_versions: HashMap<String, TlsVersion>,
+    tls_clients: HashMap<String, reqwest::Client>,
+    manifest_accepts: HashMap<String, String>,
+    max_concurrent_requests: HashMap<String, usize>,
+    min_request_intervals: HashMap<String, Duration>,
+    trusted_page_hosts: HashMap<String, HashSet<String>>,
+    allowed_registries: Option<HashSet<String>>,
+    allowed_auth_hosts: HashSet<String>,
+    denied_registries: HashSet<String>,
+    allowlist: Arc<OnceLock<HostAllowlist>>,
+    immutability_lookups: HashMap<String, Arc<dyn ImmutabilityLookup>>,
+    ecr: Ecr,
+}
+
+impl ClientBuilder {
+    /// Use `provider` instead of `~/.docker/config.json` to look up credentials
+    pub fn credential_provider(mut self, provider: impl CredentialProvider + 'static) -> Self {
+        self.credentials = Some(Arc::new(provider));
+        self
+    }
+
+    /// Call `hook` after every tags list request
+    #[cfg(feature = "unstable")]
+    pub fn on_page(mut self, hook: impl Fn(&PageInfo) + Send + Sync + 'static) -> Self {
+        self.on_page = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the span of every request, a child of the span it's
+    /// sent from, and the headers it's about to be sent with, e.g. to add a
+    /// `traceparent` one with `tracing-opentelemetry`'s
+    /// `OpenTelemetrySpanExt::context` and the global propagator
+    #[cfg(feature = "otel")]
+    pub fn inject_context(
+        mut self,
+        hook: impl Fn(&tracing::Span, &mut header::HeaderMap) + Send + Sync + 'static,
+    ) -> Self {
+        self.inject_context = Some(Arc::new(hook));
+        self
+    }
+
+    /// Talk plain HTTP to `registry` (e.g. `localhost:5000`)
+    pub fn insecure_registry(mut self, registry: impl Into<String>) -> Self {
+        self.insecure_registries.insert(registry.into());
+        self
+    }
+
+    /// Serve `registry` under `prefix`, e.g. `/artifactory/api/docker/<repo>`
+    /// for Artifactory or `/repository/<repo>` for Nexus
+    pub fn path_prefix(mut self, registry: impl Into<String>, prefix: impl Into<String>) -> Self {
+        self.path_prefixes.insert(registry.into(), prefix.into());
+        self
+    }
+
+    /// Use `api` to search the tags of `registry` instead of the default one
+    /// (Docker Hub for `docker.io`, Quay for `quay.io`)
+    #[cfg(feature = "regex")]
+    pub fn search_api(mut self, registry: impl Into<String>, api: SearchApi) -> Self {
+        self.search_apis.insert(registry.into(), api);
+        self
+    }
+
+    /// Reach `docker.io` at `base` (e.g. a pull-through mirror) instead of
+    /// `https://registry-1.docker.io`
+    pub fn docker_hub_endpoint(mut self, base: Url) -> Self {
+        self.docker_hub = Some(base);
+        self
+    }
+
+    /// Whether [`Client::fetch_tags_matching`] may filter server-side (the default)
+    pub fn filter_pushdown(mut self, enabled: bool) -> Self {
+        self.no_filter_pushdown = !enabled;
+        self
+    }
+
+    /// When a page overlaps the previous one, as when tags are deleted while
+    /// paging, list the tags once more from the start instead of only warning
+    pub fn consistent_listing(mut self, enabled: bool) -> Self {
+        self.consistent_listing = enabled;
+        self
+    }
+
+    /// Look up creation times of `ghcr.io` images with the GitHub Packages API
+    /// and `token`, falling back to their image configs where it can't help
+    #[cfg(feature = "github")]
+    pub fn github_token(mut self, token: impl Into<Secret>) -> Self {
+        self.github_token = Some(token.into());
+        self
+    }
+
+    /// Use the GitHub API at `base` (e.g. `https://github.corp.example.com/api/v3/`
+    /// for GitHub Enterprise Server) for the packages of `registry`
+    #[cfg(feature = "github")]
+    pub fn github_api(mut self, registry: impl Into<String>, base: Url) -> Self {
+        self.github_apis.insert(registry.into(), base);
+        self
+    }
+
+    /// Ask `lookup` which tags of `registry` are immutable, instead of the
+    /// API built in for its kind of registry, if any
+    pub fn immutability_lookup(
+        mut self,
+        registry: impl Into<String>,
+        lookup: impl ImmutabilityLookup + 'static,
+    ) -> Self {
+        self.immutability_lookups
+            .insert(registry.into(), Arc::new(lookup));
+        self
+    }
+
+    /// Use the ECR API at `base` (e.g. a VPC endpoint) for the repositories
+    /// of `registry`, instead of `https://api.ecr.<region>.amazonaws.com/`
+    pub fn ecr_api(mut self, registry: impl Into<String>, base: Url) -> Self {
+        self.ecr.api(registry.into(), base);
+        self
+    }
+
+    /// Sign ECR API requests with `credentials` instead of those
+    /// [`AwsCredentials::from_env`] finds
+    pub fn aws_credentials(mut self, credentials: AwsCredentials) -> Self {
+        self.ecr.credentials(credentials);
+        self
+    }
+
+    /// Trust the PEM-encoded CA certificates in `pem` for `host` (e.g.
+    /// `registry.corp:5000`), on top of the system's
+    ///
+    /// A registry's certificates also apply to its token service, unless that
+    /// host (e.g. `auth.corp.example.com`) is given certificates of its own.
+    pub fn root_certificate(mut self, host: impl Into<String>, pem: &[u8]) -> Result<Self> {
+        let host = host.into();
+        let certs = reqwest::Certificate::from_pem_bundle(pem)
+            .with_context(|| format!("Invalid CA certificate for {host}"))?;
+        if certs.is_empty() {
+            return Err(anyhow!("No CA certificate found for {host}"));
+        }
+
+        self.root_certificates
+            .entry(host.clone())
+            .or_default()
+            .extend(certs);
+        self.tls_client(host)?;
+        Ok(self)
+    }
+
+    /// Check the certificate of `registry`, an IP address with an optional
+    /// port (`10.0.3.7:5000`, `[2001:db8::1]:5000`), against `server_name`,
+    /// also sent for SNI, while still connecting to the address
+    ///
+    /// Requests then carry `server_name` as their `Host`.
+    pub fn tls_server_name(
+        mut self,
+        registry: impl Into<String>,
+        server_name: impl Into<String>,
+    ) -> Result<Self> {
+        let (registry, server_name) = (registry.into(), server_name.into());
+        if !is_valid_host(&registry) || host_ip(&registry).is_none() {
+            return Err(anyhow!(
+                "TLS server names can only be set for registries given by IP address, not {registry:?}"
+            ));
+        }
+        if !is_valid_host(&server_name)
+            || server_name.contains(':')
+            || host_ip(&server_name).is_some()
+        {
+            return Err(anyhow!("Invalid TLS server name: {server_name:?}"));
+        }
+        self.tls_server_names.insert(registry.clone(), server_name);
+        self.tls_client(registry)?;
+        Ok(self)
+    }
+
+    /// Refuse to connect with TLS older than `version` (1.2 by default)
+    ///
+    /// Fails if the TLS backend can't be set up so.
+    pub fn min_tls_version(mut self, version: TlsVersion) -> Result<Self> {
+        self.min_tls_version = version;
+        let hosts: Vec<_> = self.tls_clients.keys().cloned().collect();
+        for host in hosts {
+            self.tls_client(host)?;
+        }
+        Ok(self)
+    }
+
+    /// Like [`ClientBuilder::min_tls_version`] for `host` alone, e.g. to
+    /// require TLS 1.3 of one registry
+    ///
+    /// A registry's minimum also applies to its token service, unless that
+    /// host is given certificates or a minimum of its own.
+    pub fn min_tls_version_for(
+        mut self,
+        host: impl Into<String>,
+        version: TlsVersion,
+    ) -> Result<Self> {
+        let host = host.into();
+        self.min_tls_versions.insert(host.clone(), version);
+        self.tls_client(host)?;
+        Ok(self)
+    }
+
+    /// (Re)build the client for `host` from its certificates, server name and
+    /// TLS version
+    fn tls_client(&mut self, host: String) -> Result<()> {
+        let min_tls = self
+            .min_tls_versions
+            .get(&host)
+            .unwrap_or(&self.min_tls_version);
+        let mut http = reqwest::Client::builder()
+            .redirect(redirect_policy(self.allowlist.clone()))
+            .min_tls_version(min_tls.to_reqwest());
+        for cert in self.root_certificates.get(&host).into_iter().flatten() {
+            http = http.add_root_certificate(cert.clone());
+        }
+        if let (Some(name), Some(ip)) = (self.tls_server_names.get(&host), host_ip(&host)) {
+            // port 0 keeps the one of the URL
+            http = http.resolve(name, SocketAddr::new(ip, 0));
+        }
+        let http = http
+            .build()
+            .with_context(|| format!("Failed to set up TLS for {host}"))?;
+        self.tls_clients.insert(host, http);
+        Ok(())
+    }
+
+    /// Accept only `media_types` for the manifests of `registry` instead of
+    /// the OCI index and manifest and the Docker manifest list and schema 2
+    /// manifest, in the order given, e.g. only the Docker ones for older
+    /// Artifactory versions which answer the full list with schema 1
+    /// manifests
+    ///
+    /// Fails for no types or types other than these four.
+    pub fn manifest_accept<S: AsRef<str>>(
+        mut self,
+        registry: impl Into<String>,
+        media_types: impl IntoIterator<Item = S>,
+    ) -> Result<Self> {
+        let mut accept = Vec::new();
+        for media_type in media_types {
+            let media_type = media_type.as_ref();
+            let Some(known) = MANIFEST_ACCEPT.iter().find(|known| **known == media_type) else {
+                return Err(anyhow!(
+                    "Unsupported manifest type {media_type:?}; expected one of {}",
+                    MANIFEST_ACCEPT.join(", ")
+                ));
+            };
+            accept.push(*known);
+        }
+        if accept.is_empty() {
+            return Err(anyhow!("No manifest types to accept"));
+        }
+        self.manifest_accepts
+            .insert(registry.into(), accept.join(", "));
+        Ok(self)
+    }
+
+    /// Send at most `max` requests to `registry` at the same time, counting
+    /// token requests and every lookup of every clone of the client
+    pub fn max_concurrent_requests(mut self, registry: impl Into<String>, max: usize) -> Self {
+        self.max_concurrent_requests.insert(registry.into(), max);
+        self
+    }
+
+    /// Start requests to `registry` at least `interval` apart, a politeness
+    /// delay shared like [`ClientBuilder::max_concurrent_requests`]
+    pub fn min_request_interval(mut self, registry: impl Into<String>, interval: Duration) -> Self {
+        self.min_request_intervals.insert(registry.into(), interval);
+        self
+    }
+
+    /// Send the token of `registry` along to `host` (e.g. `cdn.corp:8443`)
+    /// too when its pages of tags lead there
+    ///
+    /// By default a `Link` to the next page on another host is followed
+    /// without credentials, and a page there asking for them fails.
+    pub fn trusted_page_host(
+        mut self,
+        registry: impl Into<String>,
+        host: impl Into<String>,
+    ) -> Self {
+        self.trusted_page_hosts
+            .entry(registry.into())
+            .or_default()
+            .insert(host.into());
+        self
+    }
+
+    /// Contact only `registries` (e.g. `["docker.io", "ghcr.io"]`), failing
+    /// lookups of images elsewhere with [`Error::RegistryNotAllowed`] before
+    /// any connection is made
+    ///
+    /// Requests, redirects included, may then only go to these registries and
+    /// the hosts given to [`ClientBuilder::allowed_auth_hosts`], so token
+    /// services, search APIs and the GitHub API elsewhere have to be allowed
+    /// there. Search and creation time lookups fall back as when they fail.
+    pub fn allowed_registries<S: AsRef<str>>(
+        mut self,
+        registries: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.allowed_registries
+            .get_or_insert_default()
+            .extend(registries.into_iter().map(|r| r.as_ref().to_string()));
+        self
+    }
+
+    /// Let requests also go to `hosts` (e.g. `auth.docker.io`,
+    /// `auth.corp:8443`) when the registries are restricted with
+    /// [`ClientBuilder::allowed_registries`], such as token realms named by
+    /// their challenges
+    pub fn allowed_auth_hosts<S: AsRef<str>>(mut self, hosts: impl IntoIterator<Item = S>) -> Self {
+        self.allowed_auth_hosts
+            .extend(hosts.into_iter().map(|h| h.as_ref().to_string()));
+        self
+    }
+
+    /// Never contact `hosts` (e.g. `169.254.169.254`, `registry.corp:5000`),
+    /// failing lookups of images on them with [`Error::RegistryNotAllowed`]
+    /// before any connection is made
+    ///
+    /// Token services, search APIs and redirects on these hosts are refused
+    /// too, even when [`ClientBuilder::allowed_registries`] or
+    /// [`ClientBuilder::allowed_auth_hosts`] has them.
+    pub fn denied_registries<S: AsRef<str>>(mut self, hosts: impl IntoIterator<Item = S>) -> Self {
+        self.denied_registries
+            .extend(hosts.into_iter().map(|h| h.as_ref().to_string()));
+        self
+    }
+
+    pub fn build(self) -> Client {
+        let mut allowlist = None;
+        if let Some(registries) = self.allowed_registries {
+            let mut hosts = self.allowed_auth_hosts;
+            for registry in &registries {
+                hosts.insert(registry.clone());
+                // as addressed by registry_base
+                match (registry.as_str(), self.tls_server_names.get(registry)) {
+                    (DOCKER_HUB, _) => {
+                        hosts.insert(match &self.docker_hub {
+                            Some(base) => authority(base),
+                            None => DOCKER_HUB_REGISTRY.to_string(),
+                        });
+                    }
+                    (registry, Some(name)) => {
+                        hosts.insert(match split_port(registry) {
+                            Some((_, Some(port))) => format!("{name}:{port}"),
+                            _ => name.clone(),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            allowlist = Some(HostAllowlist::new(registries, hosts));
+        }
+        if !self.denied_registries.is_empty() {
+            allowlist = Some(allowlist.unwrap_or_default().deny(self.denied_registries));
+        }
+        if let Some(allowlist) = allowlist {
+            let _ = self.allowlist.set(allowlist);
+        }
+        let registries: HashSet<_> = self
+            .max_concurrent_requests
+            .keys()
+            .chain(self.min_request_intervals.keys())
+            .collect();
+        let throttles = registries
+            .into_iter()
+            .map(|registry| {
+                let throttle = Throttle::new(
+                    self.max_concurrent_requests.get(registry).copied(),
+                    self.min_request_intervals
+                        .get(registry)
+                        .copied()
+                        .unwrap_or_default(),
+                );
+                (registry.clone(), Arc::new(throttle))
+            })
+            .collect();
+        Client {
+            http: reqwest::Client::builder()
+                .redirect(redirect_policy(self.allowlist.clone()))
+                .min_tls_version(self.min_tls_version.to_reqwest())
+                .build()
+                .expect("Failed to set up the HTTP client"),
+            credentials: self
+                .credentials
+                .unwrap_or_else(|| Arc::new(DockerConfigProvider::default())),
+            on_page: self.on_page,
+            #[cfg(feature = "otel")]
+            inject_context: self.inject_context,
+            insecure_registries: self.insecure_registries,
+            path_prefixes: self.path_prefixes,
+            #[cfg(feature = "regex")]
+            search_apis: self.search_apis,
+            docker_hub: self.docker_hub,
+            filter_pushdown: !self.no_filter_pushdown,
+            consistent_listing: self.consistent_listing,
+            #[cfg(feature = "github")]
+            github: self
+                .github_token
+                .map(|token| GitHubPackages::new(token, self.github_apis)),
+            tls_clients: self.tls_clients,
+            tls_server_names: self.tls_server_names,
+            min_tls_version: self.min_tls_version,
+            min_tls_versions: self.min_tls_versions,
+            manifest_accepts: self.manifest_accepts,
+            throttles,
+            trusted_page_hosts: self.trusted_page_hosts,
+            recorder: Arc::default(),
+            allowlist: self.allowlist,
+            in_flight: Arc::default(),
+            tokens: Arc::default(),
+            immutability_lookups: self.immutability_lookups,
+            ecr: self.ecr,
+        }
+    }
+}
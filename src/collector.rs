@@ -0,0 +1,375 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt,
+    hash::{BuildHasher, RandomState},
+};
+
+use crate::{ServerNotice, SortKey, Tag, Warning, WindowsRelease, collate, is_valid_tag};
+
+type Predicate = dyn Fn(&Tag) -> bool + Send + Sync;
+
+/// Assembles tags from paginated responses, skipping invalid and duplicate names
+///
+/// Every listed name is remembered to skip duplicates, back to back in one
+/// buffer, which takes a few bytes more than the names themselves. Only the
+/// tags kept are allocated one by one: those matching the filter, and with
+/// [`TagCollector::top`] no more than the best few of them.
+#[derive(Default)]
+pub struct TagCollector {
+    tags: Vec<Tag>,
+    seen: NameSet,
+    warnings: Vec<Warning>,
+    notices: Vec<ServerNotice>,
+    keep_duplicates: bool,
+    predicate: Option<Box<Predicate>>,
+    limit: Option<usize>,
+    top: Option<Top>,
+    matched: usize,
+}
+
+impl fmt::Debug for TagCollector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TagCollector")
+            .field("tags", &self.tags)
+            .field("warnings", &self.warnings)
+            .field("limit", &self.limit)
+            .field("top", &self.top.as_ref().map(|top| (top.n, top.reverse)))
+            .finish_non_exhaustive()
+    }
+}
+
+impl TagCollector {
+    pub fn new() -> Self {
+        TagCollector::default()
+    }
+
+    /// Keep repeated names instead of skipping them with a warning
+    pub fn keep_duplicates(mut self) -> Self {
+        self.keep_duplicates = true;
+        self
+    }
+
+    /// Keep only tags matching `predicate`
+    pub fn filter(mut self, predicate: impl Fn(&Tag) -> bool + Send + Sync + 'static) -> Self {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// Stop collecting after `limit` tags, see [`TagCollector::is_full`]
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Keep only the `n` matching tags [`sort_tags_desc`](crate::sort_tags_desc)
+    /// puts first, or last if `reverse`, instead of every matching tag
+    ///
+    /// Unlike [`TagCollector::limit`] this needs every page, but holds no more
+    /// than twice `n` tags at a time however many are listed: the best as any
+    /// image's tags, and as a Windows image's in case a tag turns out to name
+    /// a release (see [`is_windows_listing`](crate::is_windows_listing)).
+    pub fn top(mut self, n: usize, reverse: bool) -> Self {
+        self.top = Some(Top {
+            n,
+            reverse,
+            windows: false,
+            heaps: Default::default(),
+        });
+        self
+    }
+
+    /// Whether the limit was reached, so further pages can't change the result
+    pub fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.tags.len() >= limit)
+    }
+
+    pub fn push_page(&mut self, names: impl IntoIterator<Item = String>) {
+        for name in names {
+            if !is_valid_tag(&name) {
+                self.warnings.push(Warning::InvalidTagName { raw: name });
+            } else if !self.seen.insert(&name) && !self.keep_duplicates {
+                self.warnings.push(Warning::DuplicateTag { name });
+            } else if !self.is_full() {
+                if let Some(top) = &mut self.top {
+                    // tags the filter leaves out tell about the image too
+                    top.windows |= WindowsRelease::parse_named(&name).is_some();
+                }
+                let tag = Tag::new(name);
+                if self.predicate.as_ref().is_some_and(|p| !p(&tag)) {
+                    continue;
+                }
+                self.matched += 1;
+                match &mut self.top {
+                    Some(top) => top.push(tag),
+                    None => self.tags.push(tag),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn warn(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    /// What the registry announced while listing, each notice once; kept
+    /// across [`TagCollector::reset`]
+    pub(crate) fn notices_mut(&mut self) -> &mut Vec<ServerNotice> {
+        &mut self.notices
+    }
+
+    /// Forget everything collected, keeping the filter and limit, to start
+    /// the listing over
+    pub(crate) fn reset(&mut self) {
+        self.tags.clear();
+        if let Some(top) = &mut self.top {
+            top.windows = false;
+            top.heaps.iter_mut().for_each(BinaryHeap::clear);
+        }
+        self.seen = NameSet::default();
+        self.matched = 0;
+        self.warnings.clear();
+    }
+
+    /// Number of tags collected so far
+    pub fn len(&self) -> usize {
+        self.tags.len() + self.top.as_ref().map_or(0, |top| top.heaps[0].len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of distinct valid names listed so far, whether or not they
+    /// matched the filter or fit within the limit
+    pub fn listed(&self) -> usize {
+        self.seen.ends.len()
+    }
+
+    /// Number of tags which matched the filter so far, including those
+    /// [`TagCollector::top`] dropped but not those past the limit
+    pub fn matched(&self) -> usize {
+        self.matched
+    }
+
+    /// Collected tags in registry order, or with [`TagCollector::top`] in
+    /// the order of [`sort_tags_desc`](crate::sort_tags_desc) (reversed if
+    /// asked), along with warnings about skipped names
+    pub fn finish(self) -> (Vec<Tag>, Vec<Warning>) {
+        match self.top {
+            Some(top) => (top.finish(), self.warnings),
+            None => (self.tags, self.warnings),
+        }
+    }
+}
+
+/// The best `n` tags seen so far, the worst of them on top of the heap: one
+/// heap ranking them as any image's tags, the other as a Windows image's
+struct Top {
+    n: usize,
+    reverse: bool,
+    /// Whether a tag named a release as only Windows does
+    windows: bool,
+    heaps: [BinaryHeap<Ranked>; 2],
+}
+
+impl Top {
+    fn push(&mut self, tag: Tag) {
+        if self.n == 0 {
+            return;
+        }
+        for (windows, heap) in [false, true].into_iter().zip(&mut self.heaps) {
+            let ranked = Ranked {
+                key: tag.sort_key_in(windows),
+                tag: tag.clone(),
+                reverse: self.reverse,
+            };
+            if heap.len() < self.n {
+                heap.push(ranked);
+            } else if let Some(mut worst) = heap.peek_mut()
+                && ranked < *worst
+            {
+                *worst = ranked;
+            }
+        }
+    }
+
+    fn finish(self) -> Vec<Tag> {
+        let [heap, windows_heap] = self.heaps;
+        match self.windows {
+            true => windows_heap,
+            false => heap,
+        }
+        .into_sorted_vec()
+        .into_iter()
+        .map(|ranked| ranked.tag)
+        .collect()
+    }
+}
+
+/// Tag ordered by its place in the listing, later ones being greater
+struct Ranked {
+    key: SortKey,
+    tag: Tag,
+    reverse: bool,
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // as sort_tags_desc, which never finds distinct names equal
+        let order = other
+            .key
+            .cmp(&self.key)
+            .then_with(|| collate(self.tag.name(), other.tag.name()));
+        match self.reverse {
+            true => order.reverse(),
+            false => order,
+        }
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+/// Set of names stored back to back in one buffer, so that remembering
+/// hundreds of thousands of tags doesn't allocate a `String` for each
+#[derive(Default)]
+struct NameSet {
+    hasher: RandomState,
+    bytes: String,
+    /// End of each name in `bytes`
+    ends: Vec<usize>,
+    /// Open addressing table of indices into `ends`, `usize::MAX` if free
+    slots: Vec<usize>,
+}
+
+impl NameSet {
+    /// Add `name`, returning whether it wasn't there yet
+    fn insert(&mut self, name: &str) -> bool {
+        if (self.ends.len() + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        let slot = match self.find(name) {
+            Ok(_) => return false,
+            Err(slot) => slot,
+        };
+        self.bytes.push_str(name);
+        self.ends.push(self.bytes.len());
+        self.slots[slot] = self.ends.len() - 1;
+        true
+    }
+
+    /// Slot holding `name`, or else the free slot where it belongs
+    fn find(&self, name: &str) -> Result<usize, usize> {
+        let mask = self.slots.len() - 1;
+        let mut slot = self.hasher.hash_one(name) as usize & mask;
+        loop {
+            match self.slots[slot] {
+                usize::MAX => return Err(slot),
+                index if self.name(index) == name => return Ok(slot),
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    fn name(&self, index: usize) -> &str {
+        let start = index.checked_sub(1).map_or(0, |prev| self.ends[prev]);
+        &self.bytes[start..self.ends[index]]
+    }
+
+    /// Double the table, keeping it at most 3/4 full
+    fn grow(&mut self) {
+        self.slots = vec![usize::MAX; (self.slots.len() * 2).max(16)];
+        for index in 0..self.ends.len() {
+            let Err(slot) = self.find(self.name(index)) else {
+                unreachable!("names are unique");
+            };
+            self.slots[slot] = index;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listed() {
+        let mut collector = TagCollector::new()
+            .filter(|tag| tag.name().starts_with('1'))
+            .limit(1);
+        collector.push_page(["1.0", "1.1", "2.0", "1.0", "bad tag"].map(String::from));
+        // the duplicate and the invalid name aren't counted
+        assert_eq!((collector.len(), collector.listed()), (1, 3));
+    }
+
+    #[test]
+    fn test_top() {
+        let names = [
+            "latest", "1.0.0", "2.0.0", "edge", "1.5.0", "2.0.0", "1.1.0",
+        ];
+        let collect = |collector: TagCollector| {
+            let mut collector = collector.filter(|tag| tag.name() != "1.5.0");
+            collector.push_page(names.map(String::from));
+            let (tags, _) = collector.finish();
+            tags.iter()
+                .map(|tag| tag.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            collect(TagCollector::new().top(3, false)),
+            ["2.0.0", "1.1.0", "1.0.0"]
+        );
+        assert_eq!(
+            collect(TagCollector::new().top(2, true)),
+            ["latest", "edge"]
+        );
+        assert_eq!(collect(TagCollector::new().top(10, false)).len(), 5);
+        assert!(collect(TagCollector::new().top(0, false)).is_empty());
+
+        let mut collector = TagCollector::new().top(1, false);
+        collector.push_page(names.map(String::from));
+        assert_eq!((collector.len(), collector.matched()), (1, 6));
+    }
+
+    #[test]
+    fn test_top_windows() {
+        let collect = |names: &[&str]| {
+            let mut collector = TagCollector::new()
+                .filter(|tag| !tag.name().starts_with("ltsc"))
+                .top(1, false);
+            collector.push_page(names.iter().map(|name| name.to_string()));
+            collector.finish().0
+        };
+        // releases newest first, though `ltsc2016` itself is filtered out
+        assert_eq!(collect(&["1809", "2004", "ltsc2016"]), [Tag::new("2004")]);
+        assert_eq!(collect(&["1809", "2004"]), [Tag::new("1809")]);
+    }
+
+    #[test]
+    fn test_name_set() {
+        let mut set = NameSet::default();
+        assert!(set.insert(""));
+        for i in 0..1000 {
+            assert!(set.insert(&format!("1.{i}")));
+        }
+        for i in 0..1000 {
+            assert!(!set.insert(&format!("1.{i}")));
+        }
+        assert!(!set.insert(""));
+        assert!(set.insert("1.1000"));
+        assert_eq!(set.ends.len(), 1002);
+        assert!(set.slots.len() >= 1002 * 4 / 3);
+    }
+}
@@ -0,0 +1,1520 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::{OsStr, OsString},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, process::Command, sync::Mutex};
+
+use crate::{
+    Error, Secret,
+    registries::{auth_config_key, canonical_host},
+};
+
+/// Docker configuration
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DockerConfig {
+    #[serde(default)]
+    auths: HashMap<String, DockerAuth>,
+    /// Credential helper for all registries
+    #[serde(default)]
+    creds_store: Option<String>,
+    /// Credential helpers for specific registries
+    #[serde(default)]
+    cred_helpers: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct DockerAuth {
+    // empty when the credentials live in a helper
+    #[serde(default)]
+    auth: Secret,
+}
+
+/// Output of `docker-credential-<helper> get`
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperCredentials {
+    username: String,
+    secret: Secret,
+}
+
+/// Credentials used to obtain a registry token
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Credentials {
+    Basic {
+        username: String,
+        password: Secret,
+    },
+    /// An OIDC token, such as the ID token of a CI job, which the token
+    /// service exchanges for a registry token (OAuth 2.0 token exchange,
+    /// RFC 8693) on behalf of `client_id`
+    OidcExchange {
+        subject_token: Secret,
+        client_id: String,
+    },
+    /// A registry token minted elsewhere, sent to the registry as it is
+    /// instead of one from its token service
+    Token {
+        token: Secret,
+    },
+}
+
+impl Credentials {
+    pub fn basic(username: impl Into<String>, password: impl Into<Secret>) -> Self {
+        Credentials::Basic {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Credentials of `username` with the password read from `path`, see
+    /// [`Secret::from_file`]
+    pub fn from_file(username: impl Into<String>, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Credentials::basic(username, Secret::from_file(path)?))
+    }
+
+    /// Registry token read from `path`, see [`Secret::from_file`]
+    pub fn token_from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Credentials::Token {
+            token: Secret::from_file(path)?,
+        })
+    }
+
+    pub fn oidc_exchange(subject_token: impl Into<Secret>, client_id: impl Into<String>) -> Self {
+        Credentials::OidcExchange {
+            subject_token: subject_token.into(),
+            client_id: client_id.into(),
+        }
+    }
+
+    /// The user logging in, or the client an OIDC token is exchanged for,
+    /// empty for a registry token
+    pub fn username(&self) -> &str {
+        match self {
+            Credentials::Basic { username, .. } => username,
+            Credentials::OidcExchange { client_id, .. } => client_id,
+            Credentials::Token { .. } => "",
+        }
+    }
+
+    /// Decode the base64 `user:password` value stored in Docker's config
+    fn from_auth(auth: &str) -> Result<Self, CorruptAuth> {
+        let decoded = STANDARD.decode(auth).map_err(|_| CorruptAuth::NotBase64)?;
+        let decoded = String::from_utf8(decoded).map_err(|_| CorruptAuth::NotUtf8)?;
+        let (username, password) = decoded.split_once(':').ok_or(CorruptAuth::NoColon)?;
+        Ok(Credentials::basic(username, password))
+    }
+}
+
+/// Why an `auth` value of Docker's config can't be used, e.g. when it was
+/// truncated
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CorruptAuth {
+    NotBase64,
+    NotUtf8,
+    /// Decoded, but without the `:` between the user and the password
+    NoColon,
+}
+
+impl fmt::Display for CorruptAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorruptAuth::NotBase64 => write!(f, "not valid base64"),
+            CorruptAuth::NotUtf8 => write!(f, "not UTF-8 once decoded"),
+            CorruptAuth::NoColon => write!(f, "no user:password once decoded"),
+        }
+    }
+}
+
+/// An `auths` entry of the Docker config at `path` whose `auth` value is
+/// corrupt
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CorruptEntry {
+    pub key: String,
+    pub path: PathBuf,
+    pub reason: CorruptAuth,
+}
+
+impl fmt::Display for CorruptEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "auths[{:?}] in {}: {}",
+            self.key,
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+/// A source of credentials for registries, consulted on authentication challenges
+#[async_trait]
+pub trait CredentialProvider: Send + Sync {
+    /// Return credentials for `registry` (as written in the image reference,
+    /// e.g. `docker.io` or `ghcr.io`), or `None` for anonymous access
+    async fn credentials(&self, registry: &str) -> Result<Option<Credentials>>;
+}
+
+type CorruptHook = dyn Fn(&CorruptEntry) + Send + Sync;
+
+/// Docker configs by path, each read by the first lookup needing it
+type Configs = std::sync::Mutex<HashMap<PathBuf, Arc<OnceLock<Arc<LoadedConfig>>>>>;
+
+/// Default provider reading `~/.docker/config.json` (or `$DOCKER_CONFIG/config.json`),
+/// including credential helpers
+///
+/// The config is only read once a registry asks for credentials, and then
+/// parsed once for every registry asking: the provider and its clones keep
+/// it by path for as long as they live. Credential helpers still run on
+/// each lookup. A corrupt `auth` value is skipped for anonymous access,
+/// unless credentials are [required](Self::require_credentials).
+#[derive(Clone, Default)]
+pub struct DockerConfigProvider {
+    required: bool,
+    on_corrupt: Option<Arc<CorruptHook>>,
+    /// Keys of the corrupt entries already reported
+    reported: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Configs read so far, by path
+    configs: Arc<Configs>,
+}
+
+impl DockerConfigProvider {
+    /// Fail instead of falling back to anonymous access when the config has
+    /// no usable credentials for a registry asking for them
+    pub fn require_credentials(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Call `hook` once per corrupt `auths` entry skipped for anonymous access
+    pub fn on_corrupt_entry(
+        mut self,
+        hook: impl Fn(&CorruptEntry) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_corrupt = Some(Arc::new(hook));
+        self
+    }
+}
+
+impl fmt::Debug for DockerConfigProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DockerConfigProvider")
+            .field("required", &self.required)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for DockerConfigProvider {
+    async fn credentials(&self, registry: &str) -> Result<Option<Credentials>> {
+        self.lookup(registry, env::var_os("DOCKER_CONFIG"), env::home_dir())
+            .await
+    }
+}
+
+impl DockerConfigProvider {
+    /// [`resolve`] the credentials of `registry`, dealing with a corrupt entry
+    async fn lookup(
+        &self,
+        registry: &str,
+        docker_config: Option<OsString>,
+        home: Option<PathBuf>,
+    ) -> Result<Option<Credentials>> {
+        let config = config_path(docker_config, home).map(|path| self.config(path));
+        let (credentials, trace) = resolve(registry, config.as_deref()).await;
+        let credentials = credentials?;
+        if credentials.is_some() {
+            return Ok(credentials);
+        }
+        match trace.corrupt_entry() {
+            Some(entry) if self.required => Err(Error::CorruptCredentials(entry).into()),
+            Some(entry) => {
+                let first = self.reported.lock().unwrap().insert(entry.key.clone());
+                if let (true, Some(hook)) = (first, &self.on_corrupt) {
+                    hook(&entry);
+                }
+                Ok(None)
+            }
+            None if self.required => Err(Error::MissingCredentials {
+                registry: registry.to_string(),
+            }
+            .into()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl DockerConfigProvider {
+    /// The config at `path`, read the first time it's asked for
+    fn config(&self, path: PathBuf) -> Arc<LoadedConfig> {
+        let cell = self
+            .configs
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_default()
+            .clone();
+        // read outside the lock, so only lookups of the same path wait
+        cell.get_or_init(|| Arc::new(LoadedConfig::read(path)))
+            .clone()
+    }
+}
+
+/// How [`DockerConfigProvider`] looks up the credentials of `registry`, step
+/// by step, without running anything but the credential helper it would run
+pub async fn explain_credentials(registry: &str) -> CredentialTrace {
+    let config = config_path(env::var_os("DOCKER_CONFIG"), env::home_dir()).map(LoadedConfig::read);
+    resolve(registry, config.as_ref()).await.1
+}
+
+/// Steps of a credential lookup in Docker's config, see [`explain_credentials`]
+///
+/// Usernames are included, passwords and tokens never are.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CredentialTrace {
+    pub registry: String,
+    /// Key `docker login` writes in `auths` for the registry, e.g.
+    /// `https://index.docker.io/v1/` for `docker.io`; keys spelling it with
+    /// another scheme, path or the default port match too
+    pub server: String,
+    pub steps: Vec<TraceStep>,
+    pub decision: AuthDecision,
+}
+
+impl CredentialTrace {
+    /// The `auths` entry looked up, if it was corrupt
+    pub fn corrupt_entry(&self) -> Option<CorruptEntry> {
+        let path = self.steps.iter().find_map(|step| match step {
+            TraceStep::Config { path, .. } => Some(path),
+            _ => None,
+        })?;
+        self.steps.iter().find_map(|step| match step {
+            TraceStep::Auth {
+                key,
+                outcome: AuthOutcome::Corrupt(reason),
+            } => Some(CorruptEntry {
+                key: key.clone(),
+                path: path.clone(),
+                reason: *reason,
+            }),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TraceStep {
+    /// Neither `$DOCKER_CONFIG` nor the home directory is known
+    NoConfigPath,
+    Config {
+        path: PathBuf,
+        outcome: ConfigOutcome,
+    },
+    /// The helper of `credHelpers` or `credsStore`, as `configured_by`
+    Helper {
+        helper: String,
+        configured_by: String,
+        outcome: HelperOutcome,
+    },
+    /// The `auths` entry used, or the server's key without one
+    Auth { key: String, outcome: AuthOutcome },
+    /// An `auths` key naming the same registry as `used`, which is closer
+    /// to the key `docker login` writes
+    Shadowed { key: String, used: String },
+    /// An `auths` key which looks like `expected` but names another
+    /// registry, on another port or of a subdomain, and isn't used
+    NearMatch { key: String, expected: String },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigOutcome {
+    Loaded,
+    Missing,
+    Unreadable(String),
+    Invalid(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HelperOutcome {
+    NotInstalled { program: String },
+    NoCredentials,
+    Found { username: String },
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuthOutcome {
+    Found {
+        username: String,
+    },
+    /// No `auth` value, as left behind when a helper holds the credentials
+    Empty,
+    /// Not base64 of `user:password`, e.g. truncated
+    Corrupt(CorruptAuth),
+    Missing,
+}
+
+/// Credentials sent to the token service; either way the registry may hand
+/// out a bearer token in exchange
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum AuthDecision {
+    #[default]
+    Anonymous,
+    Basic {
+        username: String,
+    },
+}
+
+/// Docker's config as read from `path`, or how reading it failed
+struct LoadedConfig {
+    path: PathBuf,
+    outcome: ConfigOutcome,
+    /// Set when the outcome is [`ConfigOutcome::Loaded`]
+    config: Option<DockerConfig>,
+}
+
+impl LoadedConfig {
+    fn read(path: PathBuf) -> Self {
+        let (outcome, config) = match fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(config) => (ConfigOutcome::Loaded, Some(config)),
+                Err(err) => (ConfigOutcome::Invalid(err.to_string()), None),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (ConfigOutcome::Missing, None),
+            Err(err) => (ConfigOutcome::Unreadable(err.to_string()), None),
+        };
+        LoadedConfig {
+            path,
+            outcome,
+            config,
+        }
+    }
+}
+
+/// Look up the credentials of `registry` in `loaded`, the config found by
+/// [`config_path`] if there's a path
+async fn resolve(
+    registry: &str,
+    loaded: Option<&LoadedConfig>,
+) -> (Result<Option<Credentials>>, CredentialTrace) {
+    let server = auth_config_key(registry);
+    let server = server.as_str();
+    let mut trace = CredentialTrace {
+        registry: registry.to_string(),
+        server: server.to_string(),
+        steps: Vec::new(),
+        decision: AuthDecision::Anonymous,
+    };
+
+    let Some(loaded) = loaded else {
+        trace.steps.push(TraceStep::NoConfigPath);
+        return (Ok(None), trace);
+    };
+    trace.steps.push(TraceStep::Config {
+        path: loaded.path.clone(),
+        outcome: loaded.outcome.clone(),
+    });
+    let config = match (&loaded.config, &loaded.outcome) {
+        (Some(config), _) => config,
+        (None, ConfigOutcome::Invalid(err)) => {
+            let err = anyhow!("{err}").context("Failed to parse Docker config");
+            return (Err(err), trace);
+        }
+        (None, _) => return (Ok(None), trace),
+    };
+
+    let mut auth_keys = matching_keys(config.auths.keys(), registry);
+    // helpers keep the credentials under the key of the `auths` entry left
+    // behind by `docker login`
+    let helper_server = auth_keys.first().map_or(server, |key| key.as_str());
+    let helper = matching_keys(config.cred_helpers.keys(), registry)
+        .first()
+        .map(|key| (&config.cred_helpers[*key], format!("credHelpers[{key:?}]")))
+        .or_else(|| Some((config.creds_store.as_ref()?, "credsStore".to_string())));
+    if let Some((helper, configured_by)) = helper {
+        let program = helper_program(helper);
+        let (outcome, result) = match run_helper(&program, helper_server).await {
+            Ok(HelperReply::NotInstalled) => (
+                HelperOutcome::NotInstalled {
+                    program: program.to_string_lossy().into_owned(),
+                },
+                Ok(None),
+            ),
+            Ok(HelperReply::NoCredentials) => (HelperOutcome::NoCredentials, Ok(None)),
+            Ok(HelperReply::Found(credentials)) => {
+                let username = credentials.username().to_string();
+                trace.decision = AuthDecision::Basic {
+                    username: username.clone(),
+                };
+                (HelperOutcome::Found { username }, Ok(Some(credentials)))
+            }
+            Err(err) => (HelperOutcome::Failed(format!("{err:#}")), Err(err)),
+        };
+        trace.steps.push(TraceStep::Helper {
+            helper: helper.clone(),
+            configured_by,
+            outcome,
+        });
+        if !matches!(result, Ok(None)) {
+            return (result, trace);
+        }
+    }
+
+    let used = match auth_keys.is_empty() {
+        true => None,
+        false => Some(auth_keys.remove(0)),
+    };
+    let credentials = match used.map(|key| (key, &config.auths[key])) {
+        Some((key, auth)) => {
+            let credentials = Credentials::from_auth(auth.auth.expose());
+            let outcome = match &credentials {
+                Ok(credentials) => {
+                    let username = credentials.username().to_string();
+                    trace.decision = AuthDecision::Basic {
+                        username: username.clone(),
+                    };
+                    AuthOutcome::Found { username }
+                }
+                Err(_) if auth.auth.is_empty() => AuthOutcome::Empty,
+                Err(reason) => AuthOutcome::Corrupt(*reason),
+            };
+            trace.steps.push(TraceStep::Auth {
+                key: key.clone(),
+                outcome,
+            });
+            credentials.ok()
+        }
+        None => {
+            trace.steps.push(TraceStep::Auth {
+                key: server.to_string(),
+                outcome: AuthOutcome::Missing,
+            });
+            None
+        }
+    };
+
+    if let Some(used) = used {
+        for key in auth_keys {
+            trace.steps.push(TraceStep::Shadowed {
+                key: key.clone(),
+                used: used.clone(),
+            });
+        }
+    }
+    for key in near_misses(config.auths.keys(), registry) {
+        trace.steps.push(TraceStep::NearMatch {
+            key: key.clone(),
+            expected: server.to_string(),
+        });
+    }
+
+    (Ok(credentials), trace)
+}
+
+/// Registry an `auths` or `credHelpers` key stands for, the way Docker's
+/// config spells them: `https://index.docker.io/v1/`, `ghcr.io`,
+/// `https://registry.example.com/`, `registry.example.com:443/v2/`
+#[derive(Debug, Eq, PartialEq)]
+struct KeyHost {
+    /// Lowercase, and `docker.io` for any name of Docker Hub
+    host: String,
+    /// Left out when it's 443
+    port: Option<String>,
+    /// Whether the key has a path besides Docker's `/v1/` and `/v2/`, which
+    /// isn't a registry of its own
+    other_path: bool,
+    /// Parts of the key besides the host and port: scheme, path, the
+    /// default port
+    stripped: u8,
+}
+
+impl KeyHost {
+    fn parse(key: &str) -> Self {
+        let lower = key.to_ascii_lowercase();
+        let mut stripped = 0;
+        let rest = match lower.split_once("://") {
+            Some((_, rest)) => {
+                stripped += 1;
+                rest
+            }
+            None => &lower,
+        };
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let other_path = !matches!(path.trim_end_matches('/'), "" | "v1" | "v2");
+        if rest.contains('/') {
+            stripped += 1;
+        }
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+                (host, Some(port))
+            }
+            _ => (authority, None),
+        };
+        let port = match port {
+            Some("443") => {
+                stripped += 1;
+                None
+            }
+            port => port.map(String::from),
+        };
+        KeyHost {
+            host: canonical_host(host),
+            port,
+            other_path,
+            stripped,
+        }
+    }
+
+    /// Whether this key and `other` name the same registry
+    fn same(&self, other: &KeyHost) -> bool {
+        !self.other_path && self.host == other.host && self.port == other.port
+    }
+
+    /// Whether this key is easily taken for `other`'s, though it names
+    /// another registry: on another port, of a subdomain or a parent domain,
+    /// or with a path
+    fn near(&self, other: &KeyHost) -> bool {
+        let (host, other_host) = (&self.host, &other.host);
+        let sub = |a: &str, b: &str| a.strip_suffix(b).is_some_and(|a| a.ends_with('.'));
+        !self.same(other) && (host == other_host || sub(host, other_host) || sub(other_host, host))
+    }
+}
+
+/// Keys of `keys` naming `registry`, the closest to the one `docker login`
+/// writes first: the key itself, then keys with the fewest parts to strip
+fn matching_keys<'a>(
+    keys: impl IntoIterator<Item = &'a String>,
+    registry: &str,
+) -> Vec<&'a String> {
+    let server = auth_config_key(registry);
+    let wanted = KeyHost::parse(registry);
+    let mut matching: Vec<_> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let parsed = KeyHost::parse(key);
+            let exact = *key == server;
+            parsed
+                .same(&wanted)
+                .then_some(((!exact, parsed.stripped), key))
+        })
+        .collect();
+    matching.sort();
+    matching.into_iter().map(|(_, key)| key).collect()
+}
+
+/// Keys of `keys` which look like `registry`'s but name another registry,
+/// see [`KeyHost::near`]
+fn near_misses<'a>(keys: impl IntoIterator<Item = &'a String>, registry: &str) -> Vec<&'a String> {
+    let wanted = KeyHost::parse(registry);
+    let mut near: Vec<_> = keys
+        .into_iter()
+        .filter(|key| KeyHost::parse(key).near(&wanted))
+        .collect();
+    near.sort();
+    near
+}
+
+impl fmt::Display for CredentialTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Credentials for {}:", self.registry)?;
+        for step in &self.steps {
+            writeln!(f, "  {step}")?;
+        }
+        write!(f, "  decision: {}", self.decision)
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceStep::NoConfigPath => {
+                write!(f, "no Docker config: neither DOCKER_CONFIG nor HOME is set")
+            }
+            TraceStep::Config { path, outcome } => match outcome {
+                ConfigOutcome::Loaded => write!(f, "read {}", path.display()),
+                ConfigOutcome::Missing => write!(f, "{} doesn't exist", path.display()),
+                ConfigOutcome::Unreadable(err) => {
+                    write!(f, "failed to read {}: {err}", path.display())
+                }
+                ConfigOutcome::Invalid(err) => {
+                    write!(f, "failed to parse {}: {err}", path.display())
+                }
+            },
+            TraceStep::Helper {
+                helper,
+                configured_by,
+                outcome,
+            } => {
+                write!(f, "helper {helper:?} of {configured_by}: ")?;
+                match outcome {
+                    HelperOutcome::NotInstalled { program } => {
+                        write!(f, "{program} isn't installed, skipped")
+                    }
+                    HelperOutcome::NoCredentials => write!(f, "no credentials"),
+                    HelperOutcome::Found { username } => write!(f, "credentials of {username}"),
+                    HelperOutcome::Failed(err) => write!(f, "failed: {err}"),
+                }
+            }
+            TraceStep::Auth { key, outcome } => {
+                write!(f, "auths[{key:?}]: ")?;
+                match outcome {
+                    AuthOutcome::Found { username } => write!(f, "credentials of {username}"),
+                    AuthOutcome::Empty => {
+                        write!(f, "empty, the credentials are expected from a helper")
+                    }
+                    AuthOutcome::Corrupt(reason) => write!(f, "corrupt, {reason}; ignored"),
+                    AuthOutcome::Missing => write!(f, "no entry"),
+                }
+            }
+            TraceStep::Shadowed { key, used } => write!(
+                f,
+                "auths[{key:?}] names the same registry but isn't used, {used:?} is"
+            ),
+            TraceStep::NearMatch { key, expected } => write!(
+                f,
+                "auths[{key:?}] names another registry than {expected:?}, not used"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for AuthDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthDecision::Anonymous => write!(
+                f,
+                "anonymous, with an anonymous bearer token if the registry asks for one"
+            ),
+            AuthDecision::Basic { username } => write!(
+                f,
+                "basic credentials of {username}, exchanged for a bearer token if the registry asks for one"
+            ),
+        }
+    }
+}
+
+/// `$DOCKER_CONFIG/config.json`, or `.docker/config.json` in the home directory
+fn config_path(docker_config: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    match docker_config.filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join("config.json")),
+        None => Some(home?.join(".docker").join("config.json")),
+    }
+}
+
+/// Executable of a credential helper, e.g. `docker-credential-wincred.exe`
+fn helper_program(helper: &str) -> OsString {
+    format!("docker-credential-{helper}{}", env::consts::EXE_SUFFIX).into()
+}
+
+/// What a credential helper answered
+#[derive(Debug, Eq, PartialEq)]
+enum HelperReply {
+    NotInstalled,
+    NoCredentials,
+    Found(Credentials),
+}
+
+/// Ask a credential helper for the credentials of `server`
+async fn run_helper(program: &OsStr, server: &str) -> Result<HelperReply> {
+    let spawned = Command::new(program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HelperReply::NotInstalled),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to run credential helper {program:?}"));
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(server.as_bytes())
+            .await
+            .with_context(|| format!("Failed to write to credential helper {program:?}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .with_context(|| format!("Failed to run credential helper {program:?}"))?;
+    if !output.status.success() {
+        return Ok(HelperReply::NoCredentials);
+    }
+
+    let credentials: HelperCredentials = serde_json::from_slice(&output.stdout)
+        .map_err(|_| anyhow!("Invalid output from credential helper {program:?}"))?;
+    Ok(HelperReply::Found(Credentials::basic(
+        credentials.username,
+        credentials.secret,
+    )))
+}
+
+/// Provider reading passwords or registry tokens from files when a registry
+/// asks for credentials, so that they're never held in arguments or the
+/// environment, and asking `fallback` about the other registries
+pub struct PasswordFiles {
+    /// Files by registry, with the user the password is for, `None` for a
+    /// token
+    files: HashMap<String, (Option<String>, PathBuf)>,
+    fallback: Arc<dyn CredentialProvider>,
+}
+
+impl PasswordFiles {
+    pub fn new(fallback: impl CredentialProvider + 'static) -> Self {
+        PasswordFiles {
+            files: HashMap::new(),
+            fallback: Arc::new(fallback),
+        }
+    }
+
+    /// Log in to `registry` as `username` with the password in `path`
+    pub fn add(
+        mut self,
+        registry: impl Into<String>,
+        username: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        self.files
+            .insert(registry.into(), (Some(username.into()), path.into()));
+        self
+    }
+
+    /// Send the registry token in `path` to `registry`, replacing a password
+    /// file added for it
+    pub fn add_token(mut self, registry: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.files.insert(registry.into(), (None, path.into()));
+        self
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for PasswordFiles {
+    async fn credentials(&self, registry: &str) -> Result<Option<Credentials>> {
+        match self.files.get(registry) {
+            Some((Some(username), path)) => match Credentials::from_file(username, path) {
+                Ok(credentials) => Ok(Some(credentials)),
+                Err(err) => Err(Error::PasswordFile {
+                    registry: registry.to_string(),
+                    message: format!("{err:#}"),
+                }
+                .into()),
+            },
+            Some((None, path)) => match Credentials::token_from_file(path) {
+                Ok(credentials) => Ok(Some(credentials)),
+                Err(err) => Err(Error::TokenFile {
+                    registry: registry.to_string(),
+                    message: format!("{err:#}"),
+                }
+                .into()),
+            },
+            None => self.fallback.credentials(registry).await,
+        }
+    }
+}
+
+/// Where the OIDC token of [`OidcTokens`] is read from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TokenSource {
+    /// A file, without a trailing newline, see [`Secret::from_file`]
+    File(PathBuf),
+    /// An environment variable, such as GitLab CI's `id_tokens`
+    Env(String),
+}
+
+impl TokenSource {
+    fn read(&self) -> Result<Secret> {
+        match self {
+            TokenSource::File(path) => Secret::from_file(path),
+            TokenSource::Env(name) => match env::var(name) {
+                Ok(value) if !value.trim().is_empty() => Ok(Secret::new(value.trim())),
+                Ok(_) => Err(anyhow!("${name} is empty")),
+                Err(_) => Err(anyhow!("${name} is not set")),
+            },
+        }
+    }
+}
+
+/// Provider exchanging OIDC tokens, such as those of CI jobs, for registry
+/// tokens at the registries configured, and asking `fallback` about the
+/// others
+///
+/// Tokens are read whenever a registry asks for credentials, so that one
+/// renewed by the CI system is picked up.
+pub struct OidcTokens {
+    sources: HashMap<String, (String, TokenSource)>,
+    fallback: Arc<dyn CredentialProvider>,
+}
+
+impl OidcTokens {
+    pub fn new(fallback: impl CredentialProvider + 'static) -> Self {
+        OidcTokens {
+            sources: HashMap::new(),
+            fallback: Arc::new(fallback),
+        }
+    }
+
+    /// Exchange the token read from `source` at `registry` on behalf of
+    /// `client_id`
+    pub fn add(
+        mut self,
+        registry: impl Into<String>,
+        client_id: impl Into<String>,
+        source: TokenSource,
+    ) -> Self {
+        self.sources
+            .insert(registry.into(), (client_id.into(), source));
+        self
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for OidcTokens {
+    async fn credentials(&self, registry: &str) -> Result<Option<Credentials>> {
+        match self.sources.get(registry) {
+            Some((client_id, source)) => match source.read() {
+                Ok(token) => Ok(Some(Credentials::oidc_exchange(token, client_id))),
+                Err(err) => Err(Error::OidcToken {
+                    registry: registry.to_string(),
+                    message: format!("{err:#}"),
+                }
+                .into()),
+            },
+            None => self.fallback.credentials(registry).await,
+        }
+    }
+}
+
+/// Provider that never returns credentials
+#[derive(Debug, Default)]
+pub struct Anonymous;
+
+#[async_trait]
+impl CredentialProvider for Anonymous {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(None)
+    }
+}
+
+/// Provider asking `inner` once per registry and remembering the answer
+pub(crate) struct CachedProvider {
+    inner: Arc<dyn CredentialProvider>,
+    resolved: Mutex<HashMap<String, Option<Credentials>>>,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Arc<dyn CredentialProvider>) -> Self {
+        CachedProvider {
+            inner,
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for CachedProvider {
+    async fn credentials(&self, registry: &str) -> Result<Option<Credentials>> {
+        // held across the lookup so concurrent challenges don't read twice
+        let mut resolved = self.resolved.lock().await;
+        if let Some(credentials) = resolved.get(registry) {
+            return Ok(credentials.clone());
+        }
+        let credentials = self.inner.credentials(registry).await?;
+        resolved.insert(registry.to_string(), credentials.clone());
+        Ok(credentials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_path() {
+        let home = Some(PathBuf::from("home").join("user"));
+        assert_eq!(
+            config_path(None, home.clone()),
+            Some(PathBuf::from_iter([
+                "home",
+                "user",
+                ".docker",
+                "config.json"
+            ]))
+        );
+        assert_eq!(
+            config_path(Some("custom".into()), home.clone()),
+            Some(PathBuf::from_iter(["custom", "config.json"]))
+        );
+        assert_eq!(
+            config_path(Some("".into()), home),
+            Some(PathBuf::from_iter([
+                "home",
+                "user",
+                ".docker",
+                "config.json"
+            ]))
+        );
+        assert_eq!(config_path(None, None), None);
+    }
+
+    #[test]
+    fn test_helper_program() {
+        #[cfg(windows)]
+        assert_eq!(helper_program("wincred"), "docker-credential-wincred.exe");
+        #[cfg(not(windows))]
+        assert_eq!(helper_program("pass"), "docker-credential-pass");
+    }
+
+    #[test]
+    fn test_parse_config_with_helpers() {
+        let config: DockerConfig = serde_json::from_str(
+            r#"{
+                "auths": {"https://index.docker.io/v1/": {}, "ghcr.io": {"auth": "dXNlcjpwYXNz"}},
+                "credsStore": "wincred",
+                "credHelpers": {"123456789012.dkr.ecr.us-east-1.amazonaws.com": "ecr-login"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.creds_store.as_deref(), Some("wincred"));
+        assert_eq!(config.cred_helpers.len(), 1);
+        assert!(config.auths["https://index.docker.io/v1/"].auth.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_helper() {
+        let program = helper_program("does-not-exist");
+        assert_eq!(
+            run_helper(&program, "ghcr.io").await.unwrap(),
+            HelperReply::NotInstalled
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_helper() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("docker-credential-fake");
+        fs::write(
+            &program,
+            "#!/bin/sh\nread server\n[ \"$server\" = ghcr.io ] || exit 1\necho '{\"ServerURL\":\"ghcr.io\",\"Username\":\"user\",\"Secret\":\"pass\"}'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(
+            run_helper(program.as_os_str(), "ghcr.io").await.unwrap(),
+            HelperReply::Found(Credentials::basic("user", "pass"))
+        );
+        assert_eq!(
+            run_helper(program.as_os_str(), "quay.io").await.unwrap(),
+            HelperReply::NoCredentials
+        );
+    }
+
+    #[test]
+    fn test_credentials_from_auth() {
+        assert_eq!(
+            Credentials::from_auth("dXNlcjpwYXNzOndvcmQ="),
+            Ok(Credentials::basic("user", "pass:word"))
+        );
+        assert_eq!(
+            Credentials::from_auth("not base64"),
+            Err(CorruptAuth::NotBase64)
+        );
+        // truncated
+        assert_eq!(
+            Credentials::from_auth("dXNlcjpwYXNzOndvcm"),
+            Err(CorruptAuth::NotBase64)
+        );
+        assert_eq!(
+            Credentials::from_auth("dXNlcjr//g=="),
+            Err(CorruptAuth::NotUtf8)
+        );
+        assert_eq!(
+            Credentials::from_auth("bm9jb2xvbg=="),
+            Err(CorruptAuth::NoColon)
+        );
+        // Harbor robot accounts, with nothing trimmed or decoded
+        let auth = STANDARD.encode("robot$team+ci: s3cr3t%2B ");
+        assert_eq!(
+            Credentials::from_auth(&auth),
+            Ok(Credentials::basic("robot$team+ci", " s3cr3t%2B "))
+        );
+    }
+
+    /// Resolve `registry` with `config` as `$DOCKER_CONFIG/config.json`
+    async fn explain(
+        config: Option<&str>,
+        registry: &str,
+    ) -> (Option<Credentials>, CredentialTrace) {
+        let dir = tempfile::tempdir().unwrap();
+        if let Some(config) = config {
+            fs::write(dir.path().join("config.json"), config).unwrap();
+        }
+        let (credentials, trace) = resolve(
+            registry,
+            Some(&LoadedConfig::read(dir.path().join("config.json"))),
+        )
+        .await;
+        (credentials.unwrap(), trace)
+    }
+
+    fn loaded() -> TraceStep {
+        TraceStep::Config {
+            path: PathBuf::new(),
+            outcome: ConfigOutcome::Loaded,
+        }
+    }
+
+    /// `trace`'s steps with the temporary config path left out
+    fn steps(trace: &CredentialTrace) -> Vec<TraceStep> {
+        let mut steps = trace.steps.clone();
+        if let Some(TraceStep::Config { path, .. }) = steps.first_mut() {
+            *path = PathBuf::new();
+        }
+        steps
+    }
+
+    #[tokio::test]
+    async fn test_explain_unsupported_store() {
+        let config = include_str!("../tests/fixtures/docker_config/unsupported_store.json");
+        let (credentials, trace) = explain(Some(config), "registry.example.com").await;
+        assert_eq!(credentials, None);
+        assert_eq!(trace.decision, AuthDecision::Anonymous);
+        assert_eq!(
+            steps(&trace),
+            [
+                loaded(),
+                TraceStep::Helper {
+                    helper: "not-installed-anywhere".to_string(),
+                    configured_by: "credsStore".to_string(),
+                    outcome: HelperOutcome::NotInstalled {
+                        program: helper_program("not-installed-anywhere")
+                            .to_string_lossy()
+                            .into_owned()
+                    },
+                },
+                TraceStep::Auth {
+                    key: "registry.example.com".to_string(),
+                    outcome: AuthOutcome::Missing,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_host() {
+        let key = |host: &str, port: Option<&str>, stripped| KeyHost {
+            host: host.to_string(),
+            port: port.map(String::from),
+            other_path: false,
+            stripped,
+        };
+        for (text, parsed) in [
+            ("ghcr.io", key("ghcr.io", None, 0)),
+            ("https://ghcr.io", key("ghcr.io", None, 1)),
+            ("HTTPS://GHCR.io/", key("ghcr.io", None, 2)),
+            ("https://index.docker.io/v1/", key("docker.io", None, 2)),
+            (
+                "registry.example.com:443",
+                key("registry.example.com", None, 1),
+            ),
+            (
+                "registry.example.com/v2",
+                key("registry.example.com", None, 1),
+            ),
+            (
+                "http://localhost:5000/v2/",
+                key("localhost", Some("5000"), 2),
+            ),
+            ("[::1]:5000", key("[::1]", Some("5000"), 0)),
+        ] {
+            assert_eq!(KeyHost::parse(text), parsed, "{text}");
+        }
+        assert!(KeyHost::parse("registry.example.com/team").other_path);
+    }
+
+    #[test]
+    fn test_matching_keys() {
+        let config: DockerConfig = serde_json::from_str(include_str!(
+            "../tests/fixtures/docker_config/auth_keys.json"
+        ))
+        .unwrap();
+        let matching = |registry| matching_keys(config.auths.keys(), registry);
+        let near = |registry| near_misses(config.auths.keys(), registry);
+
+        assert_eq!(
+            matching("registry.example.com"),
+            ["registry.example.com:443", "https://registry.example.com/"]
+        );
+        assert_eq!(
+            matching("Registry.Example.com:443"),
+            matching("registry.example.com")
+        );
+        assert_eq!(
+            near("registry.example.com"),
+            [
+                "example.com",
+                "mirror.registry.example.com",
+                "registry.example.com:5000"
+            ]
+        );
+        assert_eq!(
+            matching("docker.io"),
+            ["https://index.docker.io/v1/", "docker.io"]
+        );
+        assert_eq!(matching("index.docker.io"), matching("docker.io"));
+        assert_eq!(matching("harbor.example.com"), ["harbor.example.com/v2/"]);
+        assert_eq!(
+            near("harbor.example.com"),
+            ["example.com", "harbor.example.com/team"]
+        );
+        assert_eq!(matching("quay.io"), ["HTTPS://Quay.io/v1"]);
+        assert_eq!(matching("ghcr.io"), ["https://ghcr.io"]);
+        assert_eq!(matching("localhost:5000"), ["localhost:5000/v2/"]);
+        assert_eq!(near("localhost:5000"), Vec::<&String>::new());
+        // neither another port nor a subdomain is the registry
+        for registry in [
+            "localhost",
+            "registry.example.com:5443",
+            "eu.registry.example.com",
+            "ghcr.io.example.com",
+        ] {
+            assert_eq!(matching(registry), Vec::<&String>::new(), "{registry}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_explain_auth_keys() {
+        let config = include_str!("../tests/fixtures/docker_config/auth_keys.json");
+        let (credentials, trace) = explain(Some(config), "registry.example.com").await;
+        assert_eq!(credentials, Some(Credentials::basic("port", "secret")));
+        assert_eq!(trace.server, "registry.example.com");
+        let near = |key: &str| TraceStep::NearMatch {
+            key: key.to_string(),
+            expected: "registry.example.com".to_string(),
+        };
+        assert_eq!(
+            steps(&trace)[1..],
+            [
+                TraceStep::Auth {
+                    key: "registry.example.com:443".to_string(),
+                    outcome: AuthOutcome::Found {
+                        username: "port".to_string()
+                    },
+                },
+                TraceStep::Shadowed {
+                    key: "https://registry.example.com/".to_string(),
+                    used: "registry.example.com:443".to_string(),
+                },
+                near("example.com"),
+                near("mirror.registry.example.com"),
+                near("registry.example.com:5000"),
+            ]
+        );
+        let text = trace.to_string();
+        assert!(
+            text.contains(r#"auths["registry.example.com:5000"] names another registry than"#),
+            "{text}"
+        );
+
+        let (credentials, trace) = explain(Some(config), "docker.io").await;
+        assert_eq!(credentials, Some(Credentials::basic("hub", "secret")));
+        assert_eq!(
+            steps(&trace).last(),
+            Some(&TraceStep::Shadowed {
+                key: "docker.io".to_string(),
+                used: "https://index.docker.io/v1/".to_string(),
+            })
+        );
+        for (registry, username) in [
+            ("ghcr.io", "ghcr"),
+            ("quay.io", "quay"),
+            ("harbor.example.com", "harbor"),
+            ("localhost:5000", "local"),
+        ] {
+            let (credentials, _) = explain(Some(config), registry).await;
+            assert_eq!(
+                credentials,
+                Some(Credentials::basic(username, "secret")),
+                "{registry}"
+            );
+        }
+        let (credentials, trace) = explain(Some(config), "localhost").await;
+        assert_eq!(credentials, None);
+        assert_eq!(
+            steps(&trace).last(),
+            Some(&TraceStep::NearMatch {
+                key: "localhost:5000/v2/".to_string(),
+                expected: "localhost".to_string(),
+            })
+        );
+
+        // credHelpers keys match the same way
+        let (_, trace) = explain(Some(config), "gcr.io").await;
+        assert!(matches!(
+            &steps(&trace)[1],
+            TraceStep::Helper { configured_by, .. } if configured_by == r#"credHelpers["https://gcr.io/"]"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_explain_auths() {
+        let config = include_str!("../tests/fixtures/docker_config/basic.json");
+        let (credentials, trace) = explain(Some(config), "docker.io").await;
+        assert_eq!(credentials, Some(Credentials::basic("user", "hunter2")));
+        assert_eq!(
+            trace.decision,
+            AuthDecision::Basic {
+                username: "user".to_string()
+            }
+        );
+        let text = trace.to_string();
+        assert!(text.contains("credentials of user"), "{text}");
+        assert!(!text.contains("hunter2") && !text.contains("dXNlcjpodW50ZXIy"));
+
+        let outcome = |trace: &CredentialTrace| match steps(trace).last() {
+            Some(TraceStep::Auth { outcome, .. }) => outcome.clone(),
+            step => panic!("{step:?}"),
+        };
+        let (_, quay) = explain(Some(config), "quay.io").await;
+        assert_eq!(outcome(&quay), AuthOutcome::Empty);
+        let (_, gcr) = explain(Some(config), "gcr.io").await;
+        assert_eq!(outcome(&gcr), AuthOutcome::Corrupt(CorruptAuth::NoColon));
+        assert_eq!(gcr.decision, AuthDecision::Anonymous);
+
+        // a helper for one registry doesn't apply to others
+        let (_, example) = explain(Some(config), "registry.example.com").await;
+        assert!(matches!(
+            &steps(&example)[1],
+            TraceStep::Helper { configured_by, .. } if configured_by == "credHelpers[\"registry.example.com\"]"
+        ));
+        assert_eq!(steps(&quay).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_entries() {
+        let config = include_str!("../tests/fixtures/docker_config/corrupt.json");
+        for (registry, reason) in [
+            ("ghcr.io", CorruptAuth::NotBase64),
+            ("quay.io", CorruptAuth::NotUtf8),
+            ("gcr.io", CorruptAuth::NoColon),
+        ] {
+            let (credentials, trace) = explain(Some(config), registry).await;
+            assert_eq!(credentials, None);
+            assert_eq!(trace.decision, AuthDecision::Anonymous);
+            let entry = trace.corrupt_entry().unwrap();
+            assert_eq!((entry.key.as_str(), entry.reason), (registry, reason));
+            assert!(entry.path.ends_with("config.json"));
+            let text = trace.to_string();
+            assert!(text.contains(&format!("corrupt, {reason}")), "{text}");
+        }
+        let (credentials, trace) = explain(Some(config), "registry.example.com").await;
+        assert_eq!(credentials, Some(Credentials::basic("user", "hunter2")));
+        assert_eq!(trace.corrupt_entry(), None);
+        let (_, trace) = explain(Some(config), "docker.io").await;
+        assert_eq!(trace.corrupt_entry(), None);
+    }
+
+    #[tokio::test]
+    async fn test_provider_on_corrupt_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = include_str!("../tests/fixtures/docker_config/corrupt.json");
+        fs::write(dir.path().join("config.json"), config).unwrap();
+        let lookup = |provider: &DockerConfigProvider, registry: &'static str| {
+            let provider = provider.clone();
+            let dir = dir.path().as_os_str().to_owned();
+            async move { provider.lookup(registry, Some(dir), None).await }
+        };
+
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let provider = DockerConfigProvider::default().on_corrupt_entry({
+            let reported = reported.clone();
+            move |entry| reported.lock().unwrap().push(entry.to_string())
+        });
+        assert_eq!(lookup(&provider, "ghcr.io").await.unwrap(), None);
+        assert_eq!(lookup(&provider, "ghcr.io").await.unwrap(), None);
+        assert_eq!(lookup(&provider, "docker.io").await.unwrap(), None);
+        let reported = reported.lock().unwrap().clone();
+        assert_eq!(reported.len(), 1, "{reported:?}");
+        assert!(
+            reported[0].starts_with(r#"auths["ghcr.io"] in "#)
+                && reported[0].ends_with("config.json: not valid base64"),
+            "{reported:?}"
+        );
+
+        let required = DockerConfigProvider::default().require_credentials();
+        let err = lookup(&required, "gcr.io").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::CorruptCredentials(CorruptEntry {
+                reason: CorruptAuth::NoColon,
+                ..
+            }))
+        ));
+        assert!(
+            err.to_string()
+                .starts_with(r#"Corrupt credential entry auths["gcr.io"]"#),
+            "{err}"
+        );
+        let err = lookup(&required, "docker.io").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No credentials for docker.io in the Docker config"
+        );
+        assert_eq!(
+            lookup(&required, "registry.example.com").await.unwrap(),
+            Some(Credentials::basic("user", "hunter2"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_reads_config_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let config = |user: &str| {
+            let auth = STANDARD.encode(format!("{user}:secret"));
+            format!(
+                r#"{{"auths": {{"ghcr.io": {{"auth": "{auth}"}}, "quay.io": {{"auth": "{auth}"}}}}}}"#
+            )
+        };
+        let lookup = |provider: &DockerConfigProvider, registry: &'static str| {
+            let provider = provider.clone();
+            let dir = dir.path().as_os_str().to_owned();
+            async move { provider.lookup(registry, Some(dir), None).await.unwrap() }
+        };
+
+        // nothing is read before a registry asks
+        let provider = DockerConfigProvider::default();
+        fs::write(&path, config("first")).unwrap();
+        assert_eq!(
+            lookup(&provider, "ghcr.io").await,
+            Some(Credentials::basic("first", "secret"))
+        );
+
+        // the clones and other registries reuse what was read
+        fs::write(&path, config("second")).unwrap();
+        assert_eq!(
+            lookup(&provider.clone(), "quay.io").await,
+            Some(Credentials::basic("first", "secret"))
+        );
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            lookup(&provider, "ghcr.io").await,
+            Some(Credentials::basic("first", "secret"))
+        );
+
+        // a missing config is remembered as such
+        let provider = DockerConfigProvider::default();
+        assert_eq!(lookup(&provider, "ghcr.io").await, None);
+        fs::write(&path, config("third")).unwrap();
+        assert_eq!(lookup(&provider, "ghcr.io").await, None);
+        assert_eq!(
+            lookup(&DockerConfigProvider::default(), "ghcr.io").await,
+            Some(Credentials::basic("third", "secret"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_password_files() {
+        struct Stored;
+
+        #[async_trait]
+        impl CredentialProvider for Stored {
+            async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+                Ok(Some(Credentials::basic("stored", "secret")))
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("password");
+        let provider = PasswordFiles::new(Stored).add("ghcr.io", "ci", &path);
+
+        // read when asked, not when added
+        fs::write(&path, "hunter2\n").unwrap();
+        assert_eq!(
+            provider.credentials("ghcr.io").await.unwrap(),
+            Some(Credentials::basic("ci", "hunter2"))
+        );
+        fs::write(&path, "rotated").unwrap();
+        assert_eq!(
+            provider.credentials("ghcr.io").await.unwrap(),
+            Some(Credentials::basic("ci", "rotated"))
+        );
+        assert_eq!(
+            provider.credentials("quay.io").await.unwrap(),
+            Some(Credentials::basic("stored", "secret"))
+        );
+
+        fs::write(&path, "\n").unwrap();
+        let err = provider.credentials("ghcr.io").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::PasswordFile { registry, .. }) if registry == "ghcr.io"
+        ));
+        assert!(err.to_string().ends_with(" is empty"), "{err}");
+
+        let provider = provider.add_token("ghcr.io", &path);
+        fs::write(&path, "minted\n").unwrap();
+        assert_eq!(
+            provider.credentials("ghcr.io").await.unwrap(),
+            Some(Credentials::Token {
+                token: Secret::new("minted")
+            })
+        );
+        fs::remove_file(&path).unwrap();
+        let err = provider.credentials("ghcr.io").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Error::TokenFile { registry, .. }) if registry == "ghcr.io"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_explain_missing_or_invalid_config() {
+        let (credentials, trace) = explain(None, "docker.io").await;
+        assert_eq!(credentials, None);
+        assert!(matches!(
+            &trace.steps[..],
+            [TraceStep::Config {
+                outcome: ConfigOutcome::Missing,
+                ..
+            }]
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("config.json"), "{").unwrap();
+        let (credentials, trace) = resolve(
+            "docker.io",
+            Some(&LoadedConfig::read(dir.path().join("config.json"))),
+        )
+        .await;
+        assert!(credentials.is_err());
+        assert!(matches!(
+            &trace.steps[..],
+            [TraceStep::Config {
+                outcome: ConfigOutcome::Invalid(_),
+                ..
+            }]
+        ));
+
+        let (_, trace) = resolve("docker.io", None).await;
+        assert_eq!(trace.steps, [TraceStep::NoConfigPath]);
+    }
+}
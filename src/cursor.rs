@@ -0,0 +1,188 @@
+use anyhow::{Result, anyhow};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Where to resume listing a repository's tags, see
+/// [`Client::fetch_tags_page`](crate::Client::fetch_tags_page)
+///
+/// It can be serialized to hand it to a browser or another process, holds no
+/// credentials and stays valid as long as the registry's own cursor does.
+/// The page it points to may be on another host, which the registry's token
+/// is only sent to if it's trusted with it, see
+/// [`ClientBuilder::trusted_page_host`](crate::ClientBuilder::trusted_page_host).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PageCursor(Position);
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Position {
+    /// Last tag of the previous page, passed as `last`
+    Last(String),
+    /// Path and query of the registry's `Link: <…>; rel="next"`, or the
+    /// whole URL if it's on another host
+    Next(String),
+}
+
+impl PageCursor {
+    /// Cursor after a page of `page_size` tags fetched from `url`, following
+    /// the `Link` header unless it leaves HTTPS
+    pub(crate) fn after(
+        url: &Url,
+        link: Option<&str>,
+        page: &[String],
+        page_size: usize,
+    ) -> Option<Self> {
+        let next = link
+            .and_then(next_link)
+            .and_then(|link| url.join(link).ok())
+            .filter(|next| next.scheme() == url.scheme() && next.has_host());
+        match next {
+            Some(next) if next.origin() == url.origin() => {
+                let mut position = next.path().to_string();
+                if let Some(query) = next.query() {
+                    position = format!("{position}?{query}");
+                }
+                return Some(PageCursor(Position::Next(position)));
+            }
+            Some(next) => return Some(PageCursor(Position::Next(next.into()))),
+            None => {}
+        }
+        // a page longer than asked for is a registry ignoring `n`, such as
+        // mcr.microsoft.com, which lists every tag at once
+        match page.last() {
+            Some(last) if page.len() == page_size => Some(PageCursor(Position::Last(last.clone()))),
+            _ => None,
+        }
+    }
+
+    /// URL of the page this cursor points to, `first` being the tags list URL
+    /// without a query
+    ///
+    /// Cursors come from outside, so one which isn't a path on the registry or
+    /// a URL with its scheme is an error.
+    pub(crate) fn url(&self, first: &Url, page_size: usize) -> Result<Url> {
+        let mut url = first.clone();
+        match &self.0 {
+            Position::Last(last) => {
+                url.query_pairs_mut()
+                    .append_pair("n", &page_size.to_string())
+                    .append_pair("last", last);
+            }
+            Position::Next(position) => {
+                url = Url::parse(position)
+                    .ok()
+                    .filter(|next| next.scheme() == first.scheme() && next.has_host())
+                    .or_else(|| {
+                        first.join(position).ok().filter(|next| {
+                            position.starts_with('/') && next.origin() == first.origin()
+                        })
+                    })
+                    .ok_or_else(|| anyhow!("Invalid page cursor: {position:?}"))?;
+                // the registry's own page size, unless asked for another
+                let pairs: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(key, _)| key != "n")
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect();
+                url.query_pairs_mut()
+                    .clear()
+                    .append_pair("n", &page_size.to_string())
+                    .extend_pairs(pairs);
+            }
+        }
+        Ok(url)
+    }
+}
+
+/// Target of the `rel="next"` link of a `Link` header
+pub(crate) fn next_link(header: &str) -> Option<&str> {
+    header.split(',').find_map(|link| {
+        let (target, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+        params
+            .split(';')
+            .filter_map(|param| param.trim().split_once('='))
+            .any(|(key, value)| {
+                key.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+            .then_some(target)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(
+            next_link(r#"</v2/app/tags/list?n=2&last=b>; rel="next""#),
+            Some("/v2/app/tags/list?n=2&last=b")
+        );
+        assert_eq!(
+            next_link(r#"<https://a/prev>; rel="prev", <https://a/next>; rel=next"#),
+            Some("https://a/next")
+        );
+        assert_eq!(next_link(r#"</v2/app/tags/list>; rel="prev""#), None);
+        assert_eq!(next_link("garbage"), None);
+    }
+
+    #[test]
+    fn test_cursor() {
+        let first = Url::parse("https://registry.example.com/v2/app/tags/list").unwrap();
+
+        // a short page without a link is the last one
+        assert_eq!(PageCursor::after(&first, None, &page(&["a"]), 2), None);
+        assert_eq!(PageCursor::after(&first, None, &[], 2), None);
+
+        let cursor = PageCursor::after(&first, None, &page(&["a", "b"]), 2).unwrap();
+        assert_eq!(
+            cursor.url(&first, 2).unwrap().as_str(),
+            "https://registry.example.com/v2/app/tags/list?n=2&last=b"
+        );
+
+        let link = r#"</v2/app/tags/list?last=b&n=2>; rel="next""#;
+        let cursor = PageCursor::after(&first, Some(link), &page(&["a"]), 2).unwrap();
+        let json = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(json, r#"{"next":"/v2/app/tags/list?last=b&n=2"}"#);
+        let cursor: PageCursor = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            cursor.url(&first, 50).unwrap().as_str(),
+            "https://registry.example.com/v2/app/tags/list?n=50&last=b"
+        );
+
+        // links to other hosts are followed, but not away from HTTPS
+        let link = r#"<https://cdn.example.com/v2/app/tags/list?last=b>; rel="next""#;
+        let cursor = PageCursor::after(&first, Some(link), &page(&["a"]), 2).unwrap();
+        assert_eq!(
+            serde_json::to_string(&cursor).unwrap(),
+            r#"{"next":"https://cdn.example.com/v2/app/tags/list?last=b"}"#
+        );
+        assert_eq!(
+            cursor.url(&first, 2).unwrap().as_str(),
+            "https://cdn.example.com/v2/app/tags/list?n=2&last=b"
+        );
+        let link = r#"<http://cdn.example.com/v2/app/tags/list?last=b>; rel="next""#;
+        assert_eq!(
+            PageCursor::after(&first, Some(link), &page(&["a"]), 2),
+            None
+        );
+        for position in [
+            "//elsewhere.example.com/x",
+            "http://elsewhere.example.com/x",
+            "file:///etc/passwd",
+            "x",
+        ] {
+            let cursor = PageCursor(Position::Next(position.to_string()));
+            assert!(cursor.url(&first, 2).is_err(), "{position}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::{CorruptEntry, TlsVersion};
+
+/// Typed errors reported by registries, reachable via `anyhow::Error::downcast_ref`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The repository doesn't exist or isn't visible with the current credentials
+    NotFound,
+    /// The registry rejected the credentials or the token
+    Unauthorized(String),
+    /// The registry refused access to the repository (HTTP 403), e.g. a private
+    /// package without (enough) credentials
+    AccessDenied {
+        registry: String,
+        repository: String,
+        message: String,
+    },
+    /// A Docker Hub image without a namespace isn't under `library/`, but
+    /// `suggestion` of the same name is readable with the stored credentials
+    NotInLibrary {
+        repository: String,
+        suggestion: String,
+    },
+    /// Any other error reported by the registry
+    Registry { code: String, message: String },
+    /// A registry or host outside [`ClientBuilder::allowed_registries`](crate::ClientBuilder::allowed_registries)
+    /// and [`ClientBuilder::allowed_auth_hosts`](crate::ClientBuilder::allowed_auth_hosts),
+    /// or given to [`ClientBuilder::denied_registries`](crate::ClientBuilder::denied_registries),
+    /// was about to be contacted
+    RegistryNotAllowed { host: String },
+    /// The TLS handshake with `host` failed since it doesn't offer `min`,
+    /// the oldest version allowed by [`ClientBuilder::min_tls_version`](crate::ClientBuilder::min_tls_version),
+    /// or a later one
+    TlsVersion { host: String, min: TlsVersion },
+    /// Credentials were [required](crate::DockerConfigProvider::require_credentials)
+    /// but the registry's `auths` entry is corrupt
+    CorruptCredentials(CorruptEntry),
+    /// Credentials were [required](crate::DockerConfigProvider::require_credentials)
+    /// but the Docker config has none for `registry`
+    MissingCredentials { registry: String },
+    /// The password for `registry` couldn't be read from its file, see
+    /// [`PasswordFiles`](crate::PasswordFiles)
+    PasswordFile { registry: String, message: String },
+    /// The registry token for `registry` couldn't be read from its file, see
+    /// [`PasswordFiles::add_token`](crate::PasswordFiles::add_token)
+    TokenFile { registry: String, message: String },
+    /// The OIDC token for `registry` couldn't be read, see
+    /// [`OidcTokens`](crate::OidcTokens)
+    OidcToken { registry: String, message: String },
+    /// The token service at `realm` refused to exchange an OIDC token for a
+    /// registry token with HTTP `status`; `message` is its OAuth error, or
+    /// else the body of its answer
+    TokenExchange {
+        realm: String,
+        status: u16,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "Image not found"),
+            Error::Unauthorized(message) if message.is_empty() => {
+                write!(f, "Authentication failed")
+            }
+            Error::Unauthorized(message) => write!(f, "Authentication failed: {message}"),
+            Error::AccessDenied {
+                registry,
+                repository,
+                message,
+            } if message.is_empty() => write!(f, "Access denied to {registry}/{repository}"),
+            Error::AccessDenied {
+                registry,
+                repository,
+                message,
+            } => write!(f, "Access denied to {registry}/{repository}: {message}"),
+            Error::NotInLibrary {
+                repository,
+                suggestion,
+            } => write!(
+                f,
+                "Image not found as library/{repository}; did you mean {suggestion}?"
+            ),
+            Error::Registry { code, message } if message.is_empty() => {
+                write!(f, "Registry error {code}")
+            }
+            Error::Registry { code, message } => write!(f, "Registry error {code}: {message}"),
+            Error::RegistryNotAllowed { host } => write!(f, "{host} is not an allowed host"),
+            Error::TlsVersion { host, min } => write!(
+                f,
+                "TLS handshake with {host} failed: it doesn't offer {min} or later, the minimum allowed"
+            ),
+            Error::CorruptCredentials(entry) => write!(f, "Corrupt credential entry {entry}"),
+            Error::MissingCredentials { registry } => {
+                write!(f, "No credentials for {registry} in the Docker config")
+            }
+            Error::PasswordFile { registry, message } => {
+                write!(f, "Failed to read the password for {registry}: {message}")
+            }
+            Error::TokenFile { registry, message } => {
+                write!(f, "Failed to read the token for {registry}: {message}")
+            }
+            Error::OidcToken { registry, message } => {
+                write!(f, "Failed to read the OIDC token for {registry}: {message}")
+            }
+            Error::TokenExchange {
+                realm,
+                status,
+                message,
+            } if message.is_empty() => {
+                write!(f, "Token exchange at {realm} failed with HTTP {status}")
+            }
+            Error::TokenExchange {
+                realm,
+                status,
+                message,
+            } => write!(
+                f,
+                "Token exchange at {realm} failed with HTTP {status}: {message}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Standard OCI distribution error envelope
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errors: Vec<ErrorEntry>,
+}
+
+#[derive(Deserialize)]
+struct ErrorEntry {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+impl Error {
+    /// Parse an OCI error envelope, returning the error for its first entry
+    pub(crate) fn from_body(body: &[u8]) -> Option<Self> {
+        let response: ErrorResponse = serde_json::from_slice(body).ok()?;
+        let entry = response.errors.into_iter().next()?;
+        Some(match entry.code.as_str() {
+            "NAME_UNKNOWN" | "NAME_INVALID" => Error::NotFound,
+            "DENIED" | "UNAUTHORIZED" => Error::Unauthorized(entry.message),
+            _ => Error::Registry {
+                code: entry.code,
+                message: entry.message,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_body_not_found() {
+        // GitLab
+        let body = br#"{"errors":[{"code":"NAME_UNKNOWN","message":"repository name not known to registry","detail":{"name":"group/app"}}]}"#;
+        assert_eq!(Error::from_body(body), Some(Error::NotFound));
+
+        let body = br#"{"errors":[{"code":"NAME_INVALID","message":"invalid repository name"}]}"#;
+        assert_eq!(Error::from_body(body), Some(Error::NotFound));
+    }
+
+    #[test]
+    fn test_from_body_auth() {
+        // Artifactory
+        let body =
+            br#"{"errors":[{"code":"DENIED","message":"Permission denied for the repository"}]}"#;
+        assert_eq!(
+            Error::from_body(body),
+            Some(Error::Unauthorized(
+                "Permission denied for the repository".to_string()
+            ))
+        );
+
+        let body = br#"{"errors":[{"code":"UNAUTHORIZED","message":"authentication required","detail":null}]}"#;
+        assert_eq!(
+            Error::from_body(body).unwrap().to_string(),
+            "Authentication failed: authentication required"
+        );
+    }
+
+    #[test]
+    fn test_from_body_other() {
+        let body = br#"{"errors":[{"code":"TOOMANYREQUESTS","message":"rate limit exceeded"}]}"#;
+        assert_eq!(
+            Error::from_body(body).unwrap().to_string(),
+            "Registry error TOOMANYREQUESTS: rate limit exceeded"
+        );
+
+        let body = br#"{"errors":[{"code":"UNSUPPORTED"}]}"#;
+        assert_eq!(
+            Error::from_body(body).unwrap().to_string(),
+            "Registry error UNSUPPORTED"
+        );
+    }
+
+    #[test]
+    fn test_from_body_invalid() {
+        assert_eq!(Error::from_body(br#"{"errors":[]}"#), None);
+        assert_eq!(Error::from_body(br#"{"tags":["latest"]}"#), None);
+        assert_eq!(Error::from_body(b"<html>Bad Gateway</html>"), None);
+    }
+}
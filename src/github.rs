@@ -0,0 +1,199 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{StatusCode, Url, header};
+use serde::Deserialize;
+
+use crate::{
+    Client, Image,
+    cursor::next_link,
+    manifest::parse_rfc3339,
+    registries::GHCR,
+    secret::{Secret, redact_url},
+    timings::RequestKind,
+};
+
+/// API answering for `ghcr.io` unless another one is given
+const GITHUB_API: &str = "https://api.github.com/";
+
+/// A version of a container package, i.e. one digest and the tags on it
+#[derive(Deserialize)]
+struct PackageVersion {
+    created_at: String,
+    #[serde(default)]
+    metadata: Option<VersionMetadata>,
+}
+
+#[derive(Deserialize)]
+struct VersionMetadata {
+    container: Option<ContainerMetadata>,
+}
+
+#[derive(Deserialize)]
+struct ContainerMetadata {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// The GitHub Packages REST API, giving the creation times of all tags of a
+/// `ghcr.io` image in a few requests instead of a manifest and config each
+#[derive(Clone, Debug)]
+pub(crate) struct GitHubPackages {
+    token: Secret,
+    /// APIs of registries other than `ghcr.io`, e.g. GitHub Enterprise Server
+    apis: HashMap<String, Url>,
+}
+
+impl GitHubPackages {
+    pub(crate) fn new(token: Secret, apis: HashMap<String, Url>) -> Self {
+        GitHubPackages { token, apis }
+    }
+
+    fn api(&self, registry: &str) -> Option<Url> {
+        match self.apis.get(registry) {
+            Some(api) => Some(api.clone()),
+            None if registry == GHCR => Url::parse(GITHUB_API).ok(),
+            None => None,
+        }
+    }
+
+    /// Creation time of each tag of `image`, or `None` if its registry isn't
+    /// backed by a GitHub API
+    ///
+    /// The package is looked up under an organization, then under a user;
+    /// GitHub answers 404 alike for missing packages and for ones the token
+    /// may not read, so both end up as an error.
+    pub(crate) async fn created(
+        &self,
+        client: &Client,
+        image: &Image,
+    ) -> Option<Result<HashMap<String, SystemTime>>> {
+        let api = self.api(&image.registry)?;
+        let (owner, package) = image.repository.split_once('/')?;
+        let package = package.replace('/', "%2F");
+        for kind in ["orgs", "users"] {
+            let path = format!("{kind}/{owner}/packages/container/{package}/versions?per_page=100");
+            match self.versions(client, image, &api, &path).await {
+                Ok(Some(created)) => return Some(Ok(created)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(Err(anyhow!(
+            "No package {} visible to the GitHub token",
+            image.repository
+        )))
+    }
+
+    /// All pages of versions at `path`, `None` if it's not found
+    async fn versions(
+        &self,
+        client: &Client,
+        image: &Image,
+        api: &Url,
+        path: &str,
+    ) -> Result<Option<HashMap<String, SystemTime>>> {
+        let mut created = HashMap::new();
+        let mut url = api.join(path)?;
+        loop {
+            let req = client
+                .http_for(&image.registry, &url)
+                .get(url.clone())
+                .header(header::ACCEPT, "application/vnd.github+json")
+                .header(
+                    header::USER_AGENT,
+                    concat!("docker-tags/", env!("CARGO_PKG_VERSION")),
+                )
+                .header("X-GitHub-Api-Version", "2022-11-28")
+                .bearer_auth(self.token.expose());
+            let resp = client
+                .send(&image.registry, RequestKind::Other, req)
+                .await
+                .with_context(|| format!("Failed to fetch {}", redact_url(&url)))?;
+            match resp.status() {
+                StatusCode::OK => {}
+                StatusCode::NOT_FOUND => return Ok(None),
+                status => return Err(anyhow!("Got HTTP {status} from {}", redact_url(&url))),
+            }
+            let next = resp
+                .headers()
+                .get(header::LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(next_link)
+                .and_then(|next| url.join(next).ok())
+                .filter(|next| next.origin() == api.origin());
+            let versions: Vec<PackageVersion> = resp
+                .json()
+                .await
+                .map_err(|err| err.without_url())
+                .with_context(|| format!("Failed to parse JSON from {}", redact_url(&url)))?;
+            add_versions(&mut created, versions);
+            match next {
+                Some(next) => url = next,
+                None => return Ok(Some(created)),
+            }
+        }
+    }
+}
+
+/// Record when each tag of `versions`, listed newest first, was pushed
+fn add_versions(created: &mut HashMap<String, SystemTime>, versions: Vec<PackageVersion>) {
+    for version in versions {
+        let Some(at) = parse_rfc3339(&version.created_at) else {
+            continue;
+        };
+        let tags = version
+            .metadata
+            .and_then(|metadata| metadata.container)
+            .map(|container| container.tags)
+            .unwrap_or_default();
+        for tag in tags {
+            created.entry(tag).or_insert(at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_add_versions() {
+        let versions =
+            serde_json::from_str(include_str!("../tests/fixtures/github/versions.json")).unwrap();
+        let mut created = HashMap::new();
+        add_versions(&mut created, versions);
+
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut tags: Vec<_> = created.into_iter().collect();
+        tags.sort();
+        // untagged versions and ones without a valid date are left out
+        assert_eq!(
+            tags,
+            [
+                ("1.1.0".to_string(), at(1725179400)),
+                ("1.2.0".to_string(), at(1727784000)),
+                ("latest".to_string(), at(1727784000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_api() {
+        let github = GitHubPackages::new(
+            Secret::default(),
+            HashMap::from([(
+                "containers.corp.example.com".to_string(),
+                Url::parse("https://github.corp.example.com/api/v3/").unwrap(),
+            )]),
+        );
+        assert_eq!(github.api("ghcr.io").unwrap().as_str(), GITHUB_API);
+        assert_eq!(
+            github.api("containers.corp.example.com").unwrap().as_str(),
+            "https://github.corp.example.com/api/v3/"
+        );
+        assert_eq!(github.api("quay.io"), None);
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::{Result, bail};
+
+use crate::{Image, Tag, is_valid_tag};
+
+/// Tags to pretend don't exist, such as a botched release re-pushed under
+/// another name, as read from an ignore file
+///
+/// Each rule is a tag name or a glob (`*` for any characters, `?` for one),
+/// everywhere or only for an image (`nginx: 1.27.1`), and a leading `!`
+/// keeps the tags it matches instead. The last rule matching a tag decides,
+/// so a rule overrides the ones above it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IgnoreList {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    /// Image the rule is scoped to, any if `None`
+    image: Option<Image>,
+    pattern: String,
+    /// Keep the matching tags rather than ignore them
+    keep: bool,
+}
+
+impl IgnoreList {
+    /// Parse one rule per line, `#` starting a comment
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(rule, _)| rule).trim();
+            if line.is_empty() {
+                continue;
+            }
+            // tags have no colon, the image before them may have a port
+            let (image, rule) = match line.rsplit_once(':') {
+                Some((name, rule)) => match Image::try_from(name.trim()) {
+                    Ok(image) => (Some(image), rule.trim()),
+                    Err(err) => bail!("line {}: {err}: {:?}", n + 1, name.trim()),
+                },
+                None => (None, line),
+            };
+            let (keep, pattern) = match rule.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, rule),
+            };
+            if !is_valid_pattern(pattern) {
+                bail!("line {}: not a tag name or a glob: {pattern:?}", n + 1);
+            }
+            rules.push(Rule {
+                image,
+                pattern: pattern.to_string(),
+                keep,
+            });
+        }
+        Ok(IgnoreList { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether `tag` of `image` is to be left out
+    pub fn is_ignored(&self, image: &Image, tag: &Tag) -> bool {
+        self.for_image(image).ignores(tag)
+    }
+
+    /// The rules applying to `image`, those for every image included
+    pub fn for_image(&self, image: &Image) -> IgnoreList {
+        IgnoreList {
+            rules: self
+                .rules
+                .iter()
+                .filter(|rule| rule.image.as_ref().is_none_or(|scope| scope == image))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether the last rule matching `tag` ignores it, whatever the images
+    /// the rules are scoped to
+    pub(crate) fn ignores(&self, tag: &Tag) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| glob_match(&rule.pattern, tag.name()))
+            .is_some_and(|rule| !rule.keep)
+    }
+}
+
+/// A tag name, once each `*` and `?` stands for a character
+fn is_valid_pattern(pattern: &str) -> bool {
+    let name: String = pattern
+        .chars()
+        .map(|c| match c {
+            '*' | '?' => '_',
+            c => c,
+        })
+        .collect();
+    is_valid_tag(&name)
+}
+
+/// Whether `name` matches `pattern` as a whole, `*` matching any characters
+/// and `?` one
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was, and how much of `name` it took so far
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(list: &IgnoreList, image: &str, names: &[&str]) -> Vec<String> {
+        let image = Image::try_from(image).unwrap();
+        names
+            .iter()
+            .map(|&name| Tag::new(name))
+            .filter(|tag| list.is_ignored(&image, tag))
+            .map(|tag| tag.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        for (pattern, name) in [
+            ("1.27.1", "1.27.1"),
+            ("1.27.*", "1.27.1"),
+            ("1.27.*", "1.27."),
+            ("*-rc?", "2.0-rc1"),
+            ("*", ""),
+            ("*alpine*", "1.27-alpine3.21"),
+            ("a*b*c", "aXbYbZc"),
+        ] {
+            assert!(glob_match(pattern, name), "{pattern} {name}");
+        }
+        for (pattern, name) in [
+            ("1.27.1", "1.27.10"),
+            ("1.27.1", "1.27"),
+            ("1.27.?", "1.27.10"),
+            ("*-rc?", "2.0-rc"),
+            ("a*b*c", "aXbYbZ"),
+        ] {
+            assert!(!glob_match(pattern, name), "{pattern} {name}");
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let list = IgnoreList::parse(
+            "# yanked releases\n\
+             \n\
+             1.27.1\n\
+             nginx: 1.25.*   # re-pushed\n\
+             localhost:5000/app: !dev-?\n",
+        )
+        .unwrap();
+        assert_eq!(
+            list.rules,
+            [
+                Rule {
+                    image: None,
+                    pattern: "1.27.1".to_string(),
+                    keep: false
+                },
+                Rule {
+                    image: Some(Image::new("docker.io", "library/nginx")),
+                    pattern: "1.25.*".to_string(),
+                    keep: false
+                },
+                Rule {
+                    image: Some(Image::new("localhost:5000", "app")),
+                    pattern: "dev-?".to_string(),
+                    keep: true
+                },
+            ]
+        );
+        assert!(IgnoreList::parse("# nothing\n").unwrap().is_empty());
+
+        for (text, err) in [
+            (
+                "1.0\n1.0/x\n",
+                r#"line 2: not a tag name or a glob: "1.0/x""#,
+            ),
+            ("nginx:\n", r#"line 1: not a tag name or a glob: """#),
+            (
+                "a/b/c/d: 1.0\n",
+                r#"line 1: Invalid image format: "a/b/c/d""#,
+            ),
+            ("-1.0\n", r#"line 1: not a tag name or a glob: "-1.0""#),
+        ] {
+            assert_eq!(IgnoreList::parse(text).unwrap_err().to_string(), err);
+        }
+    }
+
+    #[test]
+    fn test_scopes() {
+        let list = IgnoreList::parse("1.27.1\nnginx: 1.25.*\nghcr.io/org/app: edge\n").unwrap();
+        let names = ["1.27.1", "1.25.3", "edge", "1.27.2"];
+        // however the image is spelled
+        for image in ["nginx", "docker.io/library/nginx"] {
+            assert_eq!(ignored(&list, image, &names), ["1.27.1", "1.25.3"]);
+        }
+        assert_eq!(
+            ignored(&list, "ghcr.io/org/app", &names),
+            ["1.27.1", "edge"]
+        );
+        assert_eq!(ignored(&list, "redis", &names), ["1.27.1"]);
+    }
+
+    #[test]
+    fn test_precedence() {
+        // the last matching rule decides
+        let list = IgnoreList::parse("1.27.*\n!1.27.3\nnginx: 1.27.3\n").unwrap();
+        let names = ["1.27.1", "1.27.3", "1.28.0"];
+        assert_eq!(ignored(&list, "nginx", &names), ["1.27.1", "1.27.3"]);
+        assert_eq!(ignored(&list, "redis", &names), ["1.27.1"]);
+
+        let list = IgnoreList::parse("redis: !1.27.1\n1.27.1\n").unwrap();
+        assert_eq!(ignored(&list, "redis", &names), ["1.27.1"]);
+    }
+}
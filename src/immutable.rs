@@ -0,0 +1,558 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use reqwest::{StatusCode, Url, header};
+use serde::Deserialize;
+
+use crate::{
+    Client, Credentials, Image, Tag,
+    aws::{self, AwsCredentials},
+    cursor::next_link,
+    ignore::glob_match,
+    registries::{GHCR, QUAY, is_docker_hub},
+    secret::{Secret, redact_url},
+    timings::RequestKind,
+};
+
+/// Tags asked for in each page of Harbor's and ACR's APIs
+const PAGE_SIZE: &str = "100";
+
+/// Target of ECR's `DescribeRepositories` in its JSON protocol
+const DESCRIBE_REPOSITORIES: &str = "AmazonEC2ContainerRegistry_V20150921.DescribeRepositories";
+
+/// A tag and whether it can be overwritten, as [`Client::fetch_immutable`]
+/// looks it up
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TagImmutability {
+    pub tag: Tag,
+    /// Whether the registry refuses to overwrite the tag, `None` if it
+    /// doesn't tell
+    pub immutable: Option<bool>,
+}
+
+/// A registry's API telling which of its tags can't be overwritten, which
+/// [`Client::fetch_immutable`] asks
+///
+/// Harbor's, Azure Container Registry's and ECR's are built in, each asked
+/// for the registries it's known to serve; others are given to
+/// [`ClientBuilder::immutability_lookup`](crate::ClientBuilder::immutability_lookup).
+#[async_trait]
+pub trait ImmutabilityLookup: Send + Sync {
+    /// Whether each of `tags` of `image` is immutable, in the same order,
+    /// `None` for those the API doesn't tell about
+    async fn immutable(
+        &self,
+        client: &Client,
+        image: &Image,
+        tags: &[Tag],
+    ) -> Result<Vec<Option<bool>>>;
+}
+
+/// The API telling which tags of a registry can't be overwritten
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Backend {
+    /// Harbor's artifact API, where tag immutability rules mark the tags
+    /// they protect; any registry not known to be something else may be one
+    Harbor,
+    /// Azure Container Registry's `/acr/v1/` API, a tag being immutable when
+    /// writing it is disabled
+    Acr,
+    /// ECR's repository setting, from the AWS API
+    Ecr,
+    /// Registries known not to tell, which aren't asked
+    Unknown,
+}
+
+impl Backend {
+    fn of(registry: &str) -> Self {
+        let host = host_of(registry);
+        if host.ends_with(".azurecr.io") {
+            return Backend::Acr;
+        }
+        if ecr_registry(&host).is_some() {
+            return Backend::Ecr;
+        }
+        match is_docker_hub(&host) || [GHCR, QUAY, "mcr.microsoft.com"].contains(&&*host) {
+            true => Backend::Unknown,
+            false => Backend::Harbor,
+        }
+    }
+}
+
+/// `registry` in lowercase, without its port
+fn host_of(registry: &str) -> String {
+    registry
+        .rsplit_once(':')
+        .filter(|(_, port)| port.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(registry, |(host, _)| host)
+        .to_ascii_lowercase()
+}
+
+/// The account, the region and the domain of an ECR registry such as
+/// `123456789012.dkr.ecr.us-east-1.amazonaws.com`
+fn ecr_registry(host: &str) -> Option<(&str, &str, &str)> {
+    let (account, rest) = host.split_once(".dkr.ecr.")?;
+    let (region, domain) = rest.split_once('.')?;
+    let is_account = account.len() == 12 && account.bytes().all(|b| b.is_ascii_digit());
+    let is_aws = ["amazonaws.com", "amazonaws.com.cn"].contains(&domain);
+    (is_account && is_aws).then_some((account, region, domain))
+}
+
+/// Whether each of `tags` of `image` is immutable, asking the lookup given
+/// for its registry, or else the one built in for the kind of registry it is
+pub(crate) async fn immutable_tags(
+    client: &Client,
+    image: &Image,
+    tags: &[Tag],
+) -> Result<Vec<Option<bool>>> {
+    if let Some(lookup) = client.immutability_lookup(&image.registry) {
+        return lookup.immutable(client, image, tags).await;
+    }
+    match Backend::of(&image.registry) {
+        Backend::Harbor => Harbor.immutable(client, image, tags).await,
+        Backend::Acr => Acr.immutable(client, image, tags).await,
+        Backend::Ecr => client.ecr().immutable(client, image, tags).await,
+        Backend::Unknown => Ok(vec![None; tags.len()]),
+    }
+}
+
+/// Harbor's artifact API
+///
+/// A registry taken for Harbor which answers 404 or with something else
+/// than Harbor's JSON isn't one, as far as this goes.
+struct Harbor;
+
+/// Azure Container Registry's tags API
+struct Acr;
+
+/// ECR's `DescribeRepositories`, whose `imageTagMutability` holds for every
+/// tag of a repository but those its exclusion filters match
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Ecr {
+    /// APIs of registries reached elsewhere than
+    /// `https://api.ecr.<region>.amazonaws.com/`, e.g. a VPC endpoint
+    apis: HashMap<String, Url>,
+    /// Read from the environment for each lookup unless given
+    credentials: Option<AwsCredentials>,
+}
+
+/// An artifact of Harbor's artifact API, with the tags on it
+#[derive(Deserialize)]
+struct HarborArtifact {
+    /// `null` for an untagged artifact
+    #[serde(default)]
+    tags: Option<Vec<HarborTag>>,
+}
+
+#[derive(Deserialize)]
+struct HarborTag {
+    name: String,
+    /// Set with `with_immutable_status=true`
+    #[serde(default)]
+    immutable: bool,
+}
+
+/// A page of tags of ACR's `/acr/v1/<repository>/_tags`
+#[derive(Deserialize)]
+struct AcrTags {
+    #[serde(default)]
+    tags: Vec<AcrTag>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AcrTag {
+    name: String,
+    changeable_attributes: AcrAttributes,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AcrAttributes {
+    write_enabled: bool,
+}
+
+#[async_trait]
+impl ImmutabilityLookup for Harbor {
+    async fn immutable(
+        &self,
+        client: &Client,
+        image: &Image,
+        tags: &[Tag],
+    ) -> Result<Vec<Option<bool>>> {
+        let immutable = harbor(client, image).await?.unwrap_or_default();
+        Ok(by_name(&immutable, tags))
+    }
+}
+
+#[async_trait]
+impl ImmutabilityLookup for Acr {
+    async fn immutable(
+        &self,
+        client: &Client,
+        image: &Image,
+        tags: &[Tag],
+    ) -> Result<Vec<Option<bool>>> {
+        Ok(by_name(&acr(client, image).await?, tags))
+    }
+}
+
+#[async_trait]
+impl ImmutabilityLookup for Ecr {
+    async fn immutable(
+        &self,
+        client: &Client,
+        image: &Image,
+        tags: &[Tag],
+    ) -> Result<Vec<Option<bool>>> {
+        let host = host_of(&image.registry);
+        let Some((account, region, domain)) = ecr_registry(&host) else {
+            return Ok(vec![None; tags.len()]);
+        };
+        let url = match self.apis.get(&image.registry) {
+            Some(api) => api.clone(),
+            None => Url::parse(&format!("https://api.ecr.{region}.{domain}/"))?,
+        };
+        let credentials = match &self.credentials {
+            Some(credentials) => credentials.clone(),
+            None => AwsCredentials::from_env()?,
+        };
+        let body = serde_json::json!({
+            "registryId": account,
+            "repositoryNames": [image.repository],
+        })
+        .to_string();
+        let headers = [
+            ("content-type", "application/x-amz-json-1.1"),
+            ("x-amz-target", DESCRIBE_REPOSITORIES),
+        ];
+        let signed = aws::sign(
+            &credentials,
+            region,
+            "ecr",
+            &url,
+            &headers,
+            body.as_bytes(),
+            SystemTime::now(),
+        );
+        let mut req = client.http_for(&image.registry, &url).post(url.clone());
+        for (name, value) in signed {
+            req = req.header(name, value);
+        }
+        let resp = client
+            .send(&image.registry, RequestKind::Other, req.body(body))
+            .await
+            .with_context(|| format!("Failed to ask {}", redact_url(&url)))?;
+        let status = resp.status();
+        if status != StatusCode::OK {
+            let error = resp.json::<EcrError>().await.unwrap_or_default();
+            return Err(anyhow!(
+                "Got HTTP {status} from {}: {}",
+                redact_url(&url),
+                error.message.unwrap_or(error.kind)
+            ));
+        }
+        let described: EcrRepositories = resp
+            .json()
+            .await
+            .map_err(|err| err.without_url())
+            .with_context(|| format!("Failed to parse JSON from {}", redact_url(&url)))?;
+        let repository = described
+            .repositories
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("ECR didn't describe {}", image.repository))?;
+        tags.iter()
+            .map(|tag| repository.is_immutable(tag.name()).map(Some))
+            .collect()
+    }
+}
+
+impl Ecr {
+    pub(crate) fn api(&mut self, registry: String, base: Url) {
+        self.apis.insert(registry, base);
+    }
+
+    pub(crate) fn credentials(&mut self, credentials: AwsCredentials) {
+        self.credentials = Some(credentials);
+    }
+}
+
+/// `immutable` of each of `tags`, by name
+fn by_name(immutable: &HashMap<String, bool>, tags: &[Tag]) -> Vec<Option<bool>> {
+    tags.iter()
+        .map(|tag| immutable.get(tag.name()).copied())
+        .collect()
+}
+
+async fn harbor(client: &Client, image: &Image) -> Result<Option<HashMap<String, bool>>> {
+    // Harbor's repositories are always in a project
+    let Some((project, repository)) = image.repository.split_once('/') else {
+        return Ok(None);
+    };
+    let mut url = client.registry_base(&image.registry)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid registry host: {:?}", image.registry))?
+        .extend(["api", "v2.0", "projects", project, "repositories"])
+        // encoded once more, as Harbor wants the slashes of the name
+        .push(&repository.replace('/', "%2F"))
+        .push("artifacts");
+    url.query_pairs_mut()
+        .append_pair("with_tag", "true")
+        .append_pair("with_immutable_status", "true")
+        .append_pair("page_size", PAGE_SIZE);
+    let basic = match client.api_credentials(&image.registry).await? {
+        Some(Credentials::Basic { username, password }) => Some((username, password)),
+        _ => None,
+    };
+
+    let mut immutable = HashMap::new();
+    loop {
+        let mut req = client.http_for(&image.registry, &url).get(url.clone());
+        if let Some((username, password)) = &basic {
+            req = req.basic_auth(username, Some(password.expose()));
+        }
+        let resp = client
+            .send(&image.registry, RequestKind::Other, req)
+            .await
+            .with_context(|| format!("Failed to fetch {}", redact_url(&url)))?;
+        match resp.status() {
+            StatusCode::OK => {}
+            StatusCode::NOT_FOUND => return Ok(None),
+            status => return Err(anyhow!("Got HTTP {status} from {}", redact_url(&url))),
+        }
+        let next = next_page(&resp, &url);
+        let Ok(artifacts) = resp.json::<Vec<HarborArtifact>>().await else {
+            return Ok(None);
+        };
+        add_harbor_tags(&mut immutable, artifacts);
+        match next {
+            Some(next) => url = next,
+            None => return Ok(Some(immutable)),
+        }
+    }
+}
+
+async fn acr(client: &Client, image: &Image) -> Result<HashMap<String, bool>> {
+    let mut url = client.registry_base(&image.registry)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid registry host: {:?}", image.registry))?
+        .extend(["acr", "v1"])
+        .extend(image.repository.split('/'))
+        .push("_tags");
+    url.query_pairs_mut().append_pair("n", PAGE_SIZE);
+
+    let (mut immutable, mut token) = (HashMap::new(), Secret::default());
+    loop {
+        let resp = client
+            .send_authorized(&image.registry, RequestKind::Other, &url, None, &mut token)
+            .await?;
+        if resp.status() != StatusCode::OK {
+            return Err(anyhow!(
+                "Got HTTP {} from {}",
+                resp.status(),
+                redact_url(&url)
+            ));
+        }
+        let next = next_page(&resp, &url);
+        let page: AcrTags = resp
+            .json()
+            .await
+            .map_err(|err| err.without_url())
+            .with_context(|| format!("Failed to parse JSON from {}", redact_url(&url)))?;
+        add_acr_tags(&mut immutable, page);
+        match next {
+            Some(next) => url = next,
+            None => return Ok(immutable),
+        }
+    }
+}
+
+/// The answer of `DescribeRepositories`
+#[derive(Deserialize)]
+struct EcrRepositories {
+    #[serde(default)]
+    repositories: Vec<EcrRepository>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcrRepository {
+    /// `MUTABLE` or `IMMUTABLE`, either `_WITH_EXCLUSION` of the tags the
+    /// filters match
+    image_tag_mutability: String,
+    #[serde(default)]
+    image_tag_mutability_exclusion_filters: Vec<EcrExclusionFilter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcrExclusionFilter {
+    /// `WILDCARD`, `*` matching any characters
+    filter_type: String,
+    filter: String,
+}
+
+impl EcrRepository {
+    fn is_immutable(&self, tag: &str) -> Result<bool> {
+        let (immutable, exclusions) = match self.image_tag_mutability.as_str() {
+            "MUTABLE" => (false, false),
+            "IMMUTABLE" => (true, false),
+            "MUTABLE_WITH_EXCLUSION" => (false, true),
+            "IMMUTABLE_WITH_EXCLUSION" => (true, true),
+            other => return Err(anyhow!("Unknown imageTagMutability {other:?} from ECR")),
+        };
+        let excluded = exclusions
+            && self
+                .image_tag_mutability_exclusion_filters
+                .iter()
+                .any(|exclusion| {
+                    exclusion.filter_type == "WILDCARD" && glob_match(&exclusion.filter, tag)
+                });
+        Ok(immutable != excluded)
+    }
+}
+
+/// An error of the AWS API, such as `RepositoryNotFoundException`
+#[derive(Default, Deserialize)]
+struct EcrError {
+    #[serde(rename = "__type", default)]
+    kind: String,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// The `rel="next"` page of `resp`, on the same server as `url`
+fn next_page(resp: &reqwest::Response, url: &Url) -> Option<Url> {
+    resp.headers()
+        .get(header::LINK)
+        .and_then(|link| link.to_str().ok())
+        .and_then(next_link)
+        .and_then(|next| url.join(next).ok())
+        .filter(|next| next.origin() == url.origin())
+}
+
+fn add_harbor_tags(immutable: &mut HashMap<String, bool>, artifacts: Vec<HarborArtifact>) {
+    for tag in artifacts
+        .into_iter()
+        .flat_map(|a| a.tags.unwrap_or_default())
+    {
+        immutable.insert(tag.name, tag.immutable);
+    }
+}
+
+fn add_acr_tags(immutable: &mut HashMap<String, bool>, page: AcrTags) {
+    for tag in page.tags {
+        immutable.insert(tag.name, !tag.changeable_attributes.write_enabled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(immutable: HashMap<String, bool>) -> Vec<(String, bool)> {
+        let mut tags: Vec<_> = immutable.into_iter().collect();
+        tags.sort();
+        tags
+    }
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(Backend::of("harbor.corp"), Backend::Harbor);
+        assert_eq!(Backend::of("127.0.0.1:5000"), Backend::Harbor);
+        assert_eq!(Backend::of("corp.azurecr.io"), Backend::Acr);
+        assert_eq!(Backend::of("Corp.AzureCR.io:443"), Backend::Acr);
+        assert_eq!(
+            Backend::of("123456789012.dkr.ecr.us-east-1.amazonaws.com"),
+            Backend::Ecr
+        );
+        assert_eq!(
+            Backend::of("123456789012.dkr.ecr.cn-north-1.amazonaws.com.cn"),
+            Backend::Ecr
+        );
+        for registry in [
+            "docker.io",
+            "registry-1.docker.io",
+            "ghcr.io",
+            "quay.io",
+            "mcr.microsoft.com",
+        ] {
+            assert_eq!(Backend::of(registry), Backend::Unknown, "{registry}");
+        }
+    }
+
+    #[test]
+    fn test_ecr_registry() {
+        assert_eq!(
+            ecr_registry("123456789012.dkr.ecr.eu-west-3.amazonaws.com"),
+            Some(("123456789012", "eu-west-3", "amazonaws.com"))
+        );
+        assert_eq!(
+            ecr_registry("123456789012.dkr.ecr.cn-north-1.amazonaws.com.cn"),
+            Some(("123456789012", "cn-north-1", "amazonaws.com.cn"))
+        );
+        for host in [
+            "12345.dkr.ecr.us-east-1.amazonaws.com",
+            "123456789012.dkr.ecr.us-east-1.example.com",
+            "public.ecr.aws",
+        ] {
+            assert_eq!(ecr_registry(host), None, "{host}");
+        }
+    }
+
+    #[test]
+    fn test_ecr_mutability() {
+        let described: EcrRepositories = serde_json::from_str(include_str!(
+            "../tests/fixtures/immutable/ecr_repositories.json"
+        ))
+        .unwrap();
+        let mut repository = described.repositories.into_iter().next().unwrap();
+        let immutable = |repository: &EcrRepository| {
+            ["1.0.0", "latest", "dev-abc", "devel"].map(|tag| repository.is_immutable(tag).unwrap())
+        };
+        // the exclusions are mutable
+        assert_eq!(immutable(&repository), [true, false, false, true]);
+        repository.image_tag_mutability = "MUTABLE_WITH_EXCLUSION".to_string();
+        assert_eq!(immutable(&repository), [false, true, true, false]);
+        // and only apply with an exclusion setting
+        repository.image_tag_mutability = "IMMUTABLE".to_string();
+        assert_eq!(immutable(&repository), [true; 4]);
+        repository.image_tag_mutability = "MUTABLE".to_string();
+        assert_eq!(immutable(&repository), [false; 4]);
+        repository.image_tag_mutability = "SOMETIMES".to_string();
+        assert!(repository.is_immutable("1.0.0").is_err());
+    }
+
+    #[test]
+    fn test_add_harbor_tags() {
+        let artifacts = serde_json::from_str(include_str!(
+            "../tests/fixtures/immutable/harbor_artifacts.json"
+        ))
+        .unwrap();
+        let mut immutable = HashMap::new();
+        add_harbor_tags(&mut immutable, artifacts);
+        // the untagged artifact is left out
+        assert_eq!(
+            sorted(immutable),
+            [
+                ("1.0.0".to_string(), true),
+                ("1.1.0".to_string(), false),
+                ("latest".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_acr_tags() {
+        let page = serde_json::from_str(include_str!("../tests/fixtures/immutable/acr_tags.json"))
+            .unwrap();
+        let mut immutable = HashMap::new();
+        add_acr_tags(&mut immutable, page);
+        assert_eq!(
+            sorted(immutable),
+            [("v1".to_string(), true), ("v2".to_string(), false)]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Tag, sort_tags_desc};
+
+/// Tags present in every one of `listings`, such as the repositories of
+/// images deployed together, newest first as [`sort_tags_desc`] orders them
+///
+/// No listings have no tags in common.
+pub fn intersect_tags(listings: Vec<Vec<Tag>>) -> Vec<Tag> {
+    let mut listings = listings.into_iter();
+    let Some(first) = listings.next() else {
+        return Vec::new();
+    };
+    let others: Vec<HashSet<Tag>> = listings.map(HashSet::from_iter).collect();
+    let mut seen = HashSet::new();
+    let mut common: Vec<_> = first
+        .into_iter()
+        .filter(|tag| others.iter().all(|other| other.contains(tag)))
+        .filter(|tag| seen.insert(tag.clone()))
+        .collect();
+    sort_tags_desc(&mut common);
+    common
+}
+
+/// Tags present in any of `listings`, newest first, each with whether it's
+/// in each listing, in the order of `listings`
+pub fn union_tags(listings: Vec<Vec<Tag>>) -> Vec<(Tag, Vec<bool>)> {
+    let count = listings.len();
+    let mut presence: HashMap<Tag, Vec<bool>> = HashMap::new();
+    for (i, listing) in listings.into_iter().enumerate() {
+        for tag in listing {
+            presence.entry(tag).or_insert_with(|| vec![false; count])[i] = true;
+        }
+    }
+    let mut tags: Vec<_> = presence.keys().cloned().collect();
+    sort_tags_desc(&mut tags);
+    tags.into_iter()
+        .map(|tag| {
+            let present = presence.remove(&tag).expect("taken from the keys");
+            (tag, present)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<Tag> {
+        names.iter().map(|&name| Tag::new(name)).collect()
+    }
+
+    #[test]
+    fn test_intersect_tags() {
+        let api = tags(&["1.0.0", "1.1.0", "2.0.0", "latest", "1.1.0"]);
+        let worker = tags(&["latest", "1.1.0", "2.0.0", "2.1.0"]);
+        let ui = tags(&["2.0.0", "1.1.0", "0.9.0", "latest"]);
+        assert_eq!(
+            intersect_tags(vec![api.clone(), worker, ui]),
+            tags(&["2.0.0", "1.1.0", "latest"])
+        );
+        assert_eq!(intersect_tags(vec![api.clone(), Vec::new()]), []);
+        assert_eq!(
+            intersect_tags(vec![api]),
+            tags(&["2.0.0", "1.1.0", "1.0.0", "latest"])
+        );
+        assert_eq!(intersect_tags(Vec::new()), []);
+    }
+
+    #[test]
+    fn test_union_tags() {
+        let union = union_tags(vec![tags(&["1.0.0", "2.0.0"]), tags(&["2.0.0", "edge"])]);
+        assert_eq!(
+            union,
+            [
+                (Tag::new("2.0.0"), vec![true, true]),
+                (Tag::new("1.0.0"), vec![true, false]),
+                (Tag::new("edge"), vec![false, true]),
+            ]
+        );
+    }
+}
@@ -1,38 +1,102 @@
-use std::{cmp::Ordering, collections::HashMap, fmt, fs};
-
-use anyhow::{Context, Result, anyhow};
-use reqwest::{StatusCode, Url, header};
+use std::{
+    cmp::Ordering,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use anyhow::Result;
 use semver::Version;
-use serde::Deserialize;
 
-/// Docker configuration
-#[derive(Deserialize)]
-struct DockerConfig {
-    auths: HashMap<String, DockerAuth>,
-}
-
-#[derive(Deserialize)]
-struct DockerAuth {
-    auth: String,
-}
-
-/// Structure for authentication response
-#[derive(Deserialize)]
-struct TokenResponse {
-    token: String,
-}
-
-/// Structure for tags response
-#[derive(Deserialize)]
-struct TagsResponse {
-    tags: Vec<String>,
-}
+#[cfg(feature = "regex")]
+pub use crate::search::SearchApi;
+pub use crate::{
+    auth::Scope,
+    aws::AwsCredentials,
+    build_info::{BuildInfo, build_info},
+    channel::{Channels, DEFAULT_CHANNELS},
+    client::{Client, ClientBuilder, CreatedAt, ExistsOutcome, FetchManyOptions, FetchOutcome},
+    collector::TagCollector,
+    credentials::{
+        Anonymous, AuthDecision, AuthOutcome, ConfigOutcome, CorruptAuth, CorruptEntry,
+        CredentialProvider, CredentialTrace, Credentials, DockerConfigProvider, HelperOutcome,
+        OidcTokens, PasswordFiles, TokenSource, TraceStep, explain_credentials,
+    },
+    cursor::PageCursor,
+    error::Error,
+    ignore::IgnoreList,
+    immutable::{ImmutabilityLookup, TagImmutability},
+    intersect::{intersect_tags, union_tags},
+    manifest::{
+        Descriptor, ImageConfig, ImageManifest, ImageSource, Manifest, ManifestIndex, Platform,
+    },
+    notice::ServerNotice,
+    range::VersionRange,
+    registry::Registry,
+    secret::Secret,
+    select::{Aliases, DEFAULT_ALIASES, Selection, TagFilter},
+    series::{LenientVersion, SeriesLevel, SeriesTag, latest_per_series, version_series},
+    signature::SignatureLookup,
+    stats::TagStats,
+    timings::Timings,
+    tls::TlsVersion,
+    upgrade::{Suggestion, UpgradePolicy, UpgradeScope, suggest_upgrade},
+    warning::Warning,
+    windows::{WindowsRelease, is_windows_listing},
+};
+use crate::{
+    client::repository_path,
+    registries::{DOCKER_HUB, canonical_host},
+};
+
+mod allowlist;
+// partly reachable through `unstable::auth` only
+#[cfg_attr(not(feature = "unstable"), allow(dead_code))]
+mod auth;
+mod aws;
+mod build_info;
+mod channel;
+mod client;
+mod collector;
+mod credentials;
+mod cursor;
+mod error;
+#[cfg(feature = "github")]
+mod github;
+mod ignore;
+mod immutable;
+mod intersect;
+mod manifest;
+mod notice;
+#[cfg(feature = "otel")]
+mod otel;
+pub mod output;
+mod range;
+#[cfg_attr(not(feature = "unstable"), allow(dead_code))]
+mod reference;
+pub mod registries;
+mod registry;
+#[cfg(feature = "regex")]
+mod search;
+mod secret;
+mod select;
+mod series;
+mod signature;
+mod stats;
+mod throttle;
+mod timings;
+mod tls;
+#[cfg(feature = "unstable")]
+pub mod unstable;
+mod upgrade;
+mod warning;
+mod windows;
 
 /// A Docker image representation
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Image {
-    registry: String,
-    repository: String,
+    pub(crate) registry: String,
+    pub(crate) repository: String,
 }
 
 impl Image {
@@ -43,154 +107,273 @@ impl Image {
         }
     }
 
-    fn read_auth_token(&self) -> Result<Option<String>> {
-        let path = shellexpand::tilde("~/.docker/config.json").to_string();
-        if let Ok(contents) = fs::read_to_string(path) {
-            let config: DockerConfig =
-                serde_json::from_str(&contents).context("Failed to parse Docker config")?;
-            let registry = match self.registry.as_str() {
-                "docker.io" => "https://index.docker.io/v1/",
-                registry => registry,
-            };
-            return Ok(config.auths.get(registry).map(|a| a.auth.clone()));
+    /// Parse `reference` for a registry serving images under `prefix` (see
+    /// [`ClientBuilder::path_prefix`]), which may be spelled out in the
+    /// reference, e.g. `artifactory.corp/artifactory/api/docker/team/app`
+    pub fn parse_with_prefix(reference: &str, prefix: &str) -> Result<Self, &'static str> {
+        let reference = reference.strip_prefix("docker://").unwrap_or(reference);
+        let prefix = prefix.trim_matches('/');
+        if let Some((registry, rest)) = reference.split_once('/')
+            && is_registry(registry)
+            && !prefix.is_empty()
+            && let Some(rest) = rest.strip_prefix(prefix).and_then(|r| r.strip_prefix('/'))
+        {
+            return Image::try_from(format!("{registry}/{rest}").as_str());
         }
+        Image::try_from(reference)
+    }
 
-        Ok(None)
+    pub fn registry(&self) -> &str {
+        &self.registry
     }
 
-    async fn handle_auth_challenge(&self, hdr: &str) -> Result<(String, String)> {
-        let (scheme, rest) = hdr
-            .split_once(' ')
-            .ok_or(anyhow!("Invalid authentication header: {hdr}"))?;
-        let mut params = HashMap::new();
-        for param in rest.split(',') {
-            if let Some((k, v)) = param.split_once('=') {
-                params.insert(k.trim(), v.trim().trim_matches('"'));
-            }
-        }
+    pub fn repository(&self) -> &str {
+        &self.repository
+    }
 
-        let realm = params
-            .remove("realm")
-            .with_context(|| format!("No realm found in WWW-Authenticate header: {hdr}"))?;
-        let url = Url::parse_with_params(realm, params)
-            .with_context(|| format!("Failed to parse realm URL: {realm}"))?;
+    /// Fetch all tags through a transient [`Registry`] using a default [`Client`]
+    pub async fn fetch_tags(&self) -> Result<Vec<Tag>> {
+        Registry::connect(&self.registry, &Client::new())?
+            .fetch_tags(&self.repository)
+            .await
+    }
 
-        let mut req = reqwest::Client::new().get(url.clone());
-        if let Some(auth_token) = self.read_auth_token()? {
-            req = req.header(header::AUTHORIZATION, format!("Basic {auth_token}"));
-        }
+    /// Fetch tags using a default [`Client`], keeping what was collected on failure
+    pub async fn fetch_tags_partial(&self) -> Result<(Vec<Tag>, FetchOutcome)> {
+        Client::new().fetch_tags_partial(self).await
+    }
 
-        let resp = req
-            .send()
-            .await
-            .with_context(|| format!("Failed to fetch token from {url}"))?;
-        let data: TokenResponse = match resp.status() {
-            StatusCode::OK => resp
-                .json()
-                .await
-                .with_context(|| format!("Failed to parse token response from {url}"))?,
-            status => return Err(anyhow!("Failed to authenticate: {status}")),
-        };
+    /// Fetch the manifest of a tag or digest using a default [`Client`]
+    pub async fn fetch_manifest(&self, reference: &str) -> Result<Manifest> {
+        Client::new().fetch_manifest(self, reference).await
+    }
 
-        Ok((scheme.to_string(), data.token))
+    /// Fetch the image config of a tag or digest using a default [`Client`]
+    pub async fn fetch_config(&self, reference: &str) -> Result<ImageConfig> {
+        Client::new().fetch_config(self, reference).await
     }
 
-    pub async fn fetch_tags(&self) -> Result<Vec<Tag>> {
-        let mut tags = Vec::new();
-        let client = reqwest::Client::new();
-        let mut token = String::new();
+    /// List artifacts attached to `digest` using a default [`Client`]
+    pub async fn list_referrers(&self, digest: &str) -> Result<Vec<Descriptor>> {
+        Client::new().list_referrers(self, digest).await
+    }
+}
 
-        let registry = match self.registry.as_str() {
-            "docker.io" => "registry-1.docker.io",
-            registry => registry,
-        };
-        let repository = if self.registry == "docker.io" && !self.repository.contains('/') {
-            &format!("library/{}", self.repository)
-        } else {
-            &self.repository
-        };
-        let url = format!("https://{}/v2/{}/tags/list?n=100", registry, repository,);
-        let mut next_url = url.clone();
-        loop {
-            let mut req = client.get(&next_url);
-            if !token.is_empty() {
-                req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
-            }
-
-            let resp = req
-                .send()
-                .await
-                .with_context(|| format!("Failed to fetch tags from {next_url:?}"))?;
-            let data: TagsResponse = match resp.status() {
-                StatusCode::OK => resp
-                    .json()
-                    .await
-                    .with_context(|| format!("Failed to parse JSON from {next_url:?}"))?,
-                StatusCode::UNAUTHORIZED
-                    if resp.headers().contains_key(header::WWW_AUTHENTICATE) =>
-                {
-                    if !token.is_empty() {
-                        return Err(anyhow!("Got HTTP 401 with authentication token")
-                            .context("Image not found"));
-                    }
-
-                    let hdr = resp
-                        .headers()
-                        .get(header::WWW_AUTHENTICATE)
-                        .unwrap()
-                        .to_str()
-                        .context("Failed to parse WWW-Authenticate header")?;
-                    (_, token) = self
-                        .handle_auth_challenge(hdr)
-                        .await
-                        .context("Image not found")?;
-                    continue;
-                }
-                StatusCode::NOT_FOUND => return Err(anyhow!("Image not found")),
-                status => return Err(anyhow!(status)),
-            };
-
-            let page_tags: Vec<_> = data.tags.into_iter().map(|tag| Tag { name: tag }).collect();
-            let page_len = page_tags.len();
-            let last_tag = page_tags[page_len - 1].name.clone();
-            tags.extend(page_tags);
-
-            if page_len < 100 {
-                break;
-            } else {
-                next_url = Url::parse_with_params(&url, &[("last", last_tag)])
-                    .with_context(|| format!("Failed to parse URL: {next_url:?}"))?
-                    .to_string();
-            }
-        }
+impl fmt::Display for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.registry, self.repository)
+    }
+}
+
+/// Images are the same if the registry serves them at the same path, e.g.
+/// `nginx` and `docker.io/library/nginx`
+impl PartialEq for Image {
+    fn eq(&self, other: &Self) -> bool {
+        self.registry == other.registry && repository_path(self) == repository_path(other)
+    }
+}
+
+impl Eq for Image {}
+
+impl FromStr for Image {
+    type Err = &'static str;
 
-        Ok(tags)
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Image::try_from(value)
     }
 }
 
 impl TryFrom<&str> for Image {
     type Error = &'static str;
 
+    /// Parse `[<registry>/][<namespace>/]<image>`, optionally prefixed by a
+    /// `docker://` scheme as skopeo and podman take them
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = match value.split_once("://") {
+            Some(("docker", reference)) => reference,
+            Some(("oci" | "oci-layout", _)) => return Err("Local OCI layouts aren't supported"),
+            Some(_) => return Err("Unsupported reference scheme"),
+            None => value,
+        };
         let parts: Vec<_> = value.split("/").collect();
-        match parts.len() {
-            1 => Ok(Image::new("docker.io", value)),
-            2 => match parts[0].contains('.') {
-                true => Ok(Image::new(parts[0], parts[1])),
-                false => Ok(Image::new("docker.io", value)),
+        let image = match parts.len() {
+            1 => Image::new(DOCKER_HUB, value),
+            2 => match is_registry(parts[0]) {
+                true => Image::new(canonical_host(parts[0]), parts[1]),
+                false => Image::new(DOCKER_HUB, value),
             },
-            3 if parts[0].contains('.') => {
-                Ok(Image::new(parts[0], format!("{}/{}", parts[1], parts[2])))
-            }
-            _ => Err("Invalid image format"),
+            3 if is_registry(parts[0]) => Image::new(
+                canonical_host(parts[0]),
+                format!("{}/{}", parts[1], parts[2]),
+            ),
+            _ => return Err("Invalid image format"),
+        };
+
+        if !is_valid_host(&image.registry) {
+            return Err("Invalid registry host");
+        }
+        if !image.repository.split('/').all(is_valid_component) {
+            return Err("Invalid repository name");
+        }
+
+        Ok(image)
+    }
+}
+
+/// Whether the first part of a reference names a registry rather than a namespace
+fn is_registry(part: &str) -> bool {
+    part.contains(['.', ':']) || part == "localhost"
+}
+
+/// Registry host: a hostname, an IPv4 address or a bracketed IPv6 one, with
+/// an optional port, nothing else
+pub(crate) fn is_valid_host(host: &str) -> bool {
+    let (name, port) = match split_port(host) {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let valid_name = match name.strip_prefix('[') {
+        Some(ip) => ip
+            .strip_suffix(']')
+            .is_some_and(|ip| ip.parse::<Ipv6Addr>().is_ok()),
+        None => {
+            !name.is_empty()
+                && name.split('.').all(|label| {
+                    !label.is_empty()
+                        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                })
         }
+    };
+    let valid_port =
+        port.is_none_or(|p| !p.is_empty() && p.len() <= 5 && p.chars().all(|c| c.is_ascii_digit()));
+    valid_name && valid_port
+}
+
+/// Split `host` into the name and the port, if any; the colons of a
+/// bracketed IPv6 address aren't taken for one
+pub(crate) fn split_port(host: &str) -> Option<(&str, Option<&str>)> {
+    if host.starts_with('[') {
+        let end = host.find(']')? + 1;
+        return match &host[end..] {
+            "" => Some((host, None)),
+            rest => Some((&host[..end], Some(rest.strip_prefix(':')?))),
+        };
+    }
+    Some(match host.rsplit_once(':') {
+        Some((name, port)) => (name, Some(port)),
+        None => (host, None),
+    })
+}
+
+/// IP address of a registry given as one, e.g. `10.0.3.7:5000` or `[::1]`
+pub(crate) fn host_ip(host: &str) -> Option<IpAddr> {
+    let (name, _) = split_port(host)?;
+    match name.strip_prefix('[') {
+        Some(ip) => ip
+            .strip_suffix(']')?
+            .parse::<Ipv6Addr>()
+            .ok()
+            .map(IpAddr::V6),
+        None => name.parse::<Ipv4Addr>().ok().map(IpAddr::V4),
     }
 }
 
+/// Repository path component which can't change the structure of a URL
+pub(crate) fn is_valid_component(component: &str) -> bool {
+    !component.is_empty()
+        && component != "."
+        && component != ".."
+        && component
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Tag name as allowed by the OCI distribution spec
+pub(crate) fn is_valid_tag(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.len() <= 128
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Longest tag name read as a version, bounding the cost of comparisons;
+/// longer ones sort among the tags which aren't versions
+pub(crate) const MAX_VERSION_LEN: usize = 256;
+
 /// A Docker image tag representation
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Tag {
-    name: String,
+    pub(crate) name: String,
+}
+
+impl Tag {
+    pub fn new(name: impl Into<String>) -> Self {
+        Tag { name: name.into() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What the tag sorts by in the order of tags, whatever the image
+    pub fn sort_key(&self) -> SortKey {
+        self.sort_key_in(false)
+    }
+
+    /// What the tag sorts by in a listing, a Windows image's if `windows`
+    /// (see [`is_windows_listing`]), as [`sort_tags_desc`] has it
+    ///
+    /// Only there are bare release numbers (`1809`) and builds
+    /// (`10.0.20348.2582`) Windows releases, while names like `ltsc2022`
+    /// are in any listing.
+    pub fn sort_key_in(&self, windows: bool) -> SortKey {
+        if let Ok(version) = self.version() {
+            return SortKey::Semver(version);
+        }
+        let release = match windows {
+            true => WindowsRelease::parse(&self.name),
+            false => WindowsRelease::parse_named(&self.name),
+        };
+        release.map_or(SortKey::Opaque, SortKey::Windows)
+    }
+
+    /// Why the tag sorts among the tags which aren't versions, `None` if it's
+    /// a version or a Windows release in any image
+    pub fn opaque_reason(&self) -> Option<String> {
+        let reason = self.version().err()?;
+        match WindowsRelease::parse_named(&self.name) {
+            Some(_) => None,
+            None => Some(reason),
+        }
+    }
+
+    /// Semantic version of the tag, which may start with `v`
+    ///
+    /// Numbers too large for `u64` and names longer than [`MAX_VERSION_LEN`]
+    /// aren't versions, so any name sorts without panicking.
+    fn version(&self) -> Result<Version, String> {
+        if self.name.len() > MAX_VERSION_LEN {
+            return Err(format!("longer than {MAX_VERSION_LEN} characters"));
+        }
+        Version::parse(self.name.trim_start_matches('v')).map_err(|err| err.to_string())
+    }
+}
+
+/// Key derived from a tag's name which the order of tags goes by, ties
+/// between equal keys (`v1.0.0` and `1.0.0`) being broken by [`collate`]
+///
+/// Ascending like [`Tag`]: tags which aren't versions first, then Windows
+/// releases, then semantic versions.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SortKey {
+    /// Not a semantic version, e.g. `latest` or `1.27`
+    Opaque,
+    /// Windows release, e.g. `ltsc2022` or `10.0.20348.2582`
+    Windows(WindowsRelease),
+    /// Semantic version, read after any leading `v`
+    Semver(Version),
 }
 
 impl fmt::Display for Tag {
@@ -205,16 +388,47 @@ impl PartialOrd for Tag {
     }
 }
 
+/// Ascending: older versions before newer ones, and tags which aren't
+/// versions (`latest`, `edge`) before all versions, alphabetically, with
+/// Windows releases (`ltsc2022`) between them by recency
+///
+/// So `max()` is the newest version; use [`sort_tags_desc`] to list the
+/// newest first.
 impl Ord for Tag {
     fn cmp(&self, other: &Self) -> Ordering {
-        let a = Version::parse(self.name.trim_start_matches('v'));
-        let b = Version::parse(other.name.trim_start_matches('v'));
-        match (a, b) {
-            (Ok(a), Ok(b)) => b.cmp(&a),       // latest versions first
-            (Ok(_), Err(_)) => Ordering::Less, // alphanumeric tags at the end
-            (Err(_), Ok(_)) => Ordering::Greater,
-            _ => self.name.cmp(&other.name), // and sorted alphabetically
-        }
+        self.sort_key()
+            .cmp(&other.sort_key())
+            .then_with(|| collate(&self.name, &other.name))
+    }
+}
+
+/// Alphabetical order of tag names: ASCII letters compared regardless of
+/// case, then uppercase first, so `Alpha`, `alpha`, `beta`, `RC1`, `rc1`
+///
+/// Tag names are ASCII and the order doesn't depend on the locale by design,
+/// so a listing sorts the same on every machine.
+pub fn collate(a: &str, b: &str) -> Ordering {
+    fn folded(name: &str) -> impl Iterator<Item = u8> + '_ {
+        name.bytes().map(|c| c.to_ascii_lowercase())
+    }
+    folded(a).cmp(folded(b)).then_with(|| a.cmp(b))
+}
+
+/// Sort newest versions first, then Windows releases, newest first, followed
+/// by the other tags alphabetically (see [`collate`])
+///
+/// Bare release numbers and builds are Windows releases if `tags` are a
+/// Windows image's, see [`Tag::sort_key_in`].
+pub fn sort_tags_desc(tags: &mut [Tag]) {
+    let windows = is_windows_listing(tags);
+    // each key is derived once rather than on every comparison
+    let mut keyed: Vec<_> = tags
+        .iter_mut()
+        .map(|tag| (tag.sort_key_in(windows), std::mem::take(&mut tag.name)))
+        .collect();
+    keyed.sort_by(|(a_key, a), (b_key, b)| b_key.cmp(a_key).then_with(|| collate(a, b)));
+    for (tag, (_, name)) in tags.iter_mut().zip(keyed) {
+        tag.name = name;
     }
 }
 
@@ -267,4 +481,209 @@ mod tests {
             Err("Invalid image format")
         ));
     }
+
+    #[test]
+    fn test_image_try_from_localhost() {
+        assert!(matches!(
+            Image::try_from("localhost:5000/app"),
+            Ok(Image { registry, repository })
+                if registry == "localhost:5000" && repository == "app"
+        ));
+
+        assert!(matches!(
+            Image::try_from("localhost/team/app"),
+            Ok(Image { registry, repository })
+                if registry == "localhost" && repository == "team/app"
+        ));
+    }
+
+    #[test]
+    fn test_image_try_from_registry_aliases() {
+        for reference in [
+            "index.docker.io/library/nginx",
+            "registry-1.docker.io/library/nginx",
+            "registry.hub.docker.com/library/nginx",
+            "Docker.IO/library/nginx",
+        ] {
+            assert!(
+                matches!(
+                    Image::try_from(reference),
+                    Ok(Image { registry, repository })
+                        if registry == "docker.io" && repository == "library/nginx"
+                ),
+                "{reference}"
+            );
+        }
+
+        assert!(matches!(
+            Image::try_from("GHCR.io/org/app"),
+            Ok(Image { registry, .. }) if registry == "ghcr.io"
+        ));
+    }
+
+    #[test]
+    fn test_image_parse_with_prefix() {
+        let prefix = "/artifactory/api/docker/team-docker/";
+        for reference in [
+            "artifactory.corp/artifactory/api/docker/team-docker/myimage",
+            "artifactory.corp/myimage",
+        ] {
+            assert!(matches!(
+                Image::parse_with_prefix(reference, prefix),
+                Ok(Image { registry, repository })
+                    if registry == "artifactory.corp" && repository == "myimage"
+            ));
+        }
+
+        assert!(matches!(
+            Image::parse_with_prefix("nexus.corp/repository/docker-hosted/team/app", "repository/docker-hosted"),
+            Ok(Image { registry, repository })
+                if registry == "nexus.corp" && repository == "team/app"
+        ));
+        assert!(
+            Image::parse_with_prefix(
+                "nexus.corp/repository/docker-hosted/../app",
+                "repository/docker-hosted"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_image_try_from_ip() {
+        for (reference, registry) in [
+            ("10.0.3.7:5000/app", "10.0.3.7:5000"),
+            ("10.0.3.7/team/app", "10.0.3.7"),
+            ("[2001:db8::1]:5000/app", "[2001:db8::1]:5000"),
+            ("[2001:db8::1]/team/app", "[2001:db8::1]"),
+            ("[::1]:5000/app", "[::1]:5000"),
+            ("[::ffff:10.0.3.7]:443/app", "[::ffff:10.0.3.7]:443"),
+        ] {
+            let image = Image::try_from(reference).unwrap();
+            assert_eq!(image.registry, registry, "{reference}");
+            assert_eq!(image.to_string(), reference);
+        }
+
+        for reference in [
+            "2001:db8::1/app",
+            "[2001:db8::1/app",
+            "[2001:db8::1]5000/app",
+            "[2001:db8::1]:/app",
+            "[2001:db8::g]:5000/app",
+            "[10.0.3.7]:5000/app",
+            "[]:5000/app",
+            "[::1]:5000:1/app",
+        ] {
+            assert!(Image::try_from(reference).is_err(), "{reference}");
+        }
+    }
+
+    #[test]
+    fn test_image_try_from_scheme() {
+        for (reference, bare) in [
+            ("docker://docker.io/library/nginx", "nginx"),
+            ("docker://nginx", "nginx"),
+            ("docker://ghcr.io/org/app", "ghcr.io/org/app"),
+            ("docker://localhost:5000/app", "localhost:5000/app"),
+        ] {
+            assert_eq!(
+                Image::try_from(reference),
+                Image::try_from(bare),
+                "{reference}"
+            );
+            assert_eq!(reference.parse::<Image>(), bare.parse(), "{reference}");
+        }
+        assert_eq!(
+            Image::parse_with_prefix("docker://artifactory.corp/docker/team/app", "docker"),
+            Ok(Image::new("artifactory.corp", "team/app"))
+        );
+
+        for (reference, err) in [
+            (
+                "oci:///srv/layouts/app",
+                "Local OCI layouts aren't supported",
+            ),
+            ("oci-layout://app", "Local OCI layouts aren't supported"),
+            ("https://ghcr.io/org/app", "Unsupported reference scheme"),
+            // only one scheme is stripped
+            ("docker://docker://nginx", "Invalid registry host"),
+            ("docker:/nginx", "Invalid registry host"),
+        ] {
+            assert_eq!(Image::try_from(reference), Err(err), "{reference}");
+        }
+    }
+
+    #[test]
+    fn test_collate() {
+        let mut tags: Vec<_> = [
+            "rc1", "latest", "Beta", "RC1", "1.0.0", "alpha", "Alpha", "beta",
+        ]
+        .map(Tag::new)
+        .into();
+        sort_tags_desc(&mut tags);
+        let names: Vec<_> = tags.iter().map(Tag::name).collect();
+        assert_eq!(
+            names,
+            [
+                "1.0.0", "Alpha", "alpha", "Beta", "beta", "latest", "RC1", "rc1"
+            ]
+        );
+
+        tags.sort();
+        let names: Vec<_> = tags.iter().map(Tag::name).collect();
+        assert_eq!(
+            names,
+            [
+                "Alpha", "alpha", "Beta", "beta", "latest", "RC1", "rc1", "1.0.0"
+            ]
+        );
+        // other characters are compared with the lowercase letters
+        assert_eq!(collate("_edge", "Edge"), Ordering::Less);
+        assert_eq!(collate("Z", "a"), Ordering::Greater);
+        assert_eq!(collate("a", "ab"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_host_ip() {
+        assert_eq!(host_ip("10.0.3.7:5000"), Some("10.0.3.7".parse().unwrap()));
+        assert_eq!(
+            host_ip("[2001:db8::1]:5000"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(host_ip("[::1]"), Some("::1".parse().unwrap()));
+        assert_eq!(host_ip("registry.corp:5000"), None);
+        assert_eq!(host_ip("10.0.3"), None);
+    }
+
+    #[test]
+    fn test_image_try_from_hostile() {
+        for reference in [
+            "foo/..%2F..%2Fother",
+            "foo/bar?n=1",
+            "foo/bar#fragment",
+            "foo/..",
+            "registry.example.com/./bar",
+            "registry.example.com//bar",
+            "foo bar",
+        ] {
+            assert_eq!(
+                Image::try_from(reference).unwrap_err(),
+                "Invalid repository name",
+                "{reference}"
+            );
+        }
+
+        for reference in [
+            "user:password@evil.com/app",
+            "evil.com:80@internal/app",
+            "evil.com:http/app",
+            "evil..com/app",
+        ] {
+            assert_eq!(
+                Image::try_from(reference).unwrap_err(),
+                "Invalid registry host",
+                "{reference}"
+            );
+        }
+    }
 }
@@ -1,14 +1,727 @@
-use std::process::ExitCode;
+mod cli;
 
-use anyhow::{Result, anyhow};
-use clap::Parser;
-use docker_tags::Image;
-use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    env,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{
+    Parser, Subcommand,
+    builder::{PossibleValuesParser, TypedValueParser},
+};
+use cli::{
+    Registry,
+    batch::Status,
+    config::{ConfigFile, RegistryProfile},
+    history::History,
+    lint::Linter,
+    listing::{Listed, Plan, Strategy},
+    not_found::NotFoundCache,
+    notify::{Notifier, Target},
+    output::{Output, is_broken_pipe},
+    pin::Mode,
+    policy::{Condition, Results, Trigger},
+    prom::Metrics,
+    published::{Moment, Window},
+    renovate::PolicyFile,
+    report::{ErrorReport, Format, HistoryReport, SetsReport},
+    snapshot::Snapshot,
+    state::{Lock, SortOrder, State},
+};
+use docker_tags::{
+    Aliases, Channels, Client, DEFAULT_ALIASES, DockerConfigProvider, ExistsOutcome, Image,
+    LenientVersion, Selection, SeriesLevel, Tag, TagFilter, TlsVersion, TokenSource, UpgradePolicy,
+    UpgradeScope, VersionRange, explain_credentials,
+    output::{Formatter, Listing, Sort, Template, TimingsReport},
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
 
 /// Docker Tags CLI
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    select: SelectArgs,
+
+    /// Keep polling the registry every SECONDS and print new tags
+    #[arg(
+        short = 'w',
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["output", "output_file"]
+    )]
+    watch: Option<u64>,
+
+    /// POST a JSON notification to URL when new tags appear
+    #[arg(long, value_name = "URL", requires = "watch")]
+    notify_url: Option<Url>,
+
+    /// Run COMMAND with a JSON notification on stdin when new tags appear
+    #[arg(long, value_name = "COMMAND", requires = "watch")]
+    notify_cmd: Option<String>,
+
+    /// Print only tags not recorded in FILE by the previous run, then update it
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    changed_since_state: Option<PathBuf>,
+
+    /// Print all tags when the state file doesn't exist yet instead of just seeding it
+    #[arg(long, action, requires = "changed_since_state")]
+    seed_print: bool,
+
+    /// Record in FILE when each tag was first seen, in the format of
+    /// --changed-since-state, while listing all of them
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["watch", "changed_since_state"],
+        required_if_eq("sort", "first-seen")
+    )]
+    state: Option<PathBuf>,
+
+    /// Order of the tags: by version, or by when the --state file first saw
+    /// them, most recent first and tags it saw at unknown times last, each
+    /// group in registry order
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with_all = ["no_sort", "latest_per", "added_after_tag", "show_signed", "signed_only"]
+    )]
+    sort: SortOrder,
+
+    /// Print the tags fetched so far if pagination fails midway
+    #[arg(long, action)]
+    allow_partial: bool,
+
+    /// List through the daemon of `docker-tags daemon start`, which keeps
+    /// connections and tokens between runs; only the sorting and filtering
+    /// flags apply, the daemon using the registry settings it was started with
+    #[cfg(all(feature = "serve", unix))]
+    #[arg(long, action)]
+    via_daemon: bool,
+
+    /// Socket of the daemon for --via-daemon and `docker-tags daemon`
+    /// (default: $XDG_RUNTIME_DIR/docker-tags.sock)
+    #[cfg(all(feature = "serve", unix))]
+    #[arg(long, value_name = "PATH", global = true)]
+    daemon_socket: Option<PathBuf>,
+
+    /// Exit with a code of its own after listing if any of the CONDITIONS is
+    /// met: `outdated` (7), `outdated:major[>=N]`, `outdated:minor[>=N]`
+    /// against --current, `no-match` (8) if nothing is listed, `unsigned` (9)
+    /// with --show-signed
+    #[arg(
+        long,
+        value_name = "CONDITIONS",
+        value_delimiter = ',',
+        value_parser = cli::policy::parse_condition,
+        conflicts_with_all = ["watch", "changed_since_state", "channel"]
+    )]
+    fail_on: Vec<Condition>,
+
+    /// Tag in use, which --fail-on outdated compares the newest stable tag
+    /// matching the filters with
+    #[arg(long, value_name = "TAG", requires = "fail_on")]
+    current: Option<String>,
+
+    /// Add a column telling whether each tag has a cosign signature attached
+    /// (presence only, signatures are not verified)
+    #[arg(long, action, conflicts_with_all = ["watch", "changed_since_state", "pin_aliases"])]
+    show_signed: bool,
+
+    /// Add a column with the digest of each tag's manifest, looked up for
+    /// the tags printed only, after --filter, sorting and --limit
+    #[arg(long, action, conflicts_with_all = ["watch", "changed_since_state"])]
+    digests: bool,
+
+    /// Add a column with the repository and commit each tag was built from,
+    /// as its OCI annotations or labels tell (`github.com/org/repo@abcdef1`),
+    /// `-` if they don't
+    #[arg(long, action, conflicts_with_all = ["watch", "changed_since_state"])]
+    show_source: bool,
+
+    /// Add a column telling whether each tag is `immutable` or `mutable`, as
+    /// Harbor's immutability rules, ACR's write-enabled attribute or ECR's
+    /// repository setting (with AWS credentials) tell, `-` on other registries
+    #[arg(long, action, conflicts_with_all = ["watch", "changed_since_state"])]
+    show_immutable: bool,
+
+    /// Print each tag as TEMPLATE, whose fields `{name}`, `{signed}`,
+    /// `{series}`, `{digest}`, `{source}` and `{immutable}` are replaced with
+    /// the tag's (`-` if not looked up), `{{`, `}}`, `\t` and `\n` standing for
+    /// themselves
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["output", "watch", "changed_since_state", "channel"]
+    )]
+    template: Option<String>,
+
+    /// Only list tags with a cosign signature attached (presence only,
+    /// signatures are not verified)
+    #[arg(long, action, conflicts_with_all = ["watch", "changed_since_state", "pin_aliases"])]
+    signed_only: bool,
+
+    /// Print only the newest stable tag of each major or minor version series
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        value_parser = series_level(),
+        conflicts_with_all = ["watch", "changed_since_state", "show_signed", "signed_only", "no_sort", "reverse", "pin_aliases"]
+    )]
+    latest_per: Option<SeriesLevel>,
+
+    /// With --latest-per, give variants such as `-alpine` series of their own
+    #[arg(long, action, requires = "latest_per")]
+    per_variant: bool,
+
+    /// Print only tags created after TAG, newest first, going by the `created`
+    /// date of each image config (works for tags which aren't versions); the
+    /// config of every matching tag is fetched, --limit only applies after
+    #[arg(
+        long,
+        value_name = "TAG",
+        conflicts_with_all = ["watch", "changed_since_state", "show_signed", "signed_only", "no_sort", "latest_per", "pin_aliases"]
+    )]
+    added_after_tag: Option<String>,
+
+    /// Keep the creation dates looked up for --added-after-tag in FILE and
+    /// reuse them, so that a run only looks up those which failed or whose
+    /// tag moved
+    #[arg(long, value_name = "FILE", requires = "added_after_tag")]
+    enrich_cache: Option<PathBuf>,
+
+    /// With --enrich-cache, tell which tags' dates were filled from the cache
+    /// and which were fetched again, such as those which failed last time
+    #[arg(long, action, requires = "enrich_cache")]
+    retry_failed: bool,
+
+    /// Print the N tags which sort first (oldest with -r), keeping no more
+    /// than N in memory while listing, unlike -n which sorts them all
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["watch", "changed_since_state", "state", "no_sort", "limit", "show_signed", "signed_only", "latest_per", "added_after_tag", "channel", "pin_aliases"]
+    )]
+    top: Option<usize>,
+
+    /// Print the version tags pointing to the same image as the channel tag
+    /// NAME (e.g. `stable`), newest first
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["watch", "changed_since_state", "state", "show_signed", "signed_only", "latest_per", "added_after_tag", "channels"]
+    )]
+    channel: Option<String>,
+
+    /// Tell which tags present in the registry --ignore-file left out, on
+    /// stderr or under `ignored` in the document
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["watch", "changed_since_state", "top"]
+    )]
+    show_ignored: bool,
+
+    /// Don't let Docker Hub or Quay pre-filter tags by a literal --filter
+    #[arg(long, action)]
+    no_filter_pushdown: bool,
+
+    /// Print how credentials for the registry are looked up in Docker's config
+    /// (files, keys, helpers), without secrets, to stderr before listing
+    #[arg(long, action)]
+    explain_auth: bool,
+
+    /// Don't print the `N tags total, M matched filter, showing K` line to
+    /// stderr after a listing on a terminal
+    #[arg(long, action)]
+    no_summary: bool,
+
+    /// Print the requests sent, by kind, the bytes received, retries and the
+    /// time spent in each phase to stderr, or under `timings` in the document
+    #[arg(long, action, conflicts_with_all = ["watch", "changed_since_state"])]
+    timings: bool,
+
+    /// Don't print warnings about skipped tags
+    #[arg(short = 'q', long, action, global = true)]
+    quiet: bool,
+
+    /// Fail rather than fall back to anonymous access when Docker's config
+    /// has no usable credentials for a registry asking for them
+    #[arg(long, action, global = true)]
+    require_auth: bool,
+
+    /// Never send credentials, without even reading Docker's config, e.g.
+    /// to list public images the way anyone else sees them
+    #[arg(
+        long,
+        action,
+        global = true,
+        conflicts_with_all = ["require_auth", "username", "password_file", "token_file", "oidc_token_file", "oidc_token_env", "oidc_client_id"]
+    )]
+    anonymous: bool,
+
+    /// Path the registry serves its API under (e.g. `/artifactory/api/docker/<repo>`
+    /// for Artifactory, `/repository/<repo>` for Nexus)
+    #[arg(long, value_name = "PATH", global = true)]
+    path_prefix: Option<String>,
+
+    /// Trust the CA certificates in FILE for HOST, a registry or its token
+    /// service (e.g. `auth.corp.example.com=/etc/ssl/corp-ca.pem`)
+    #[arg(long, value_name = "HOST=FILE", value_parser = parse_host_path, global = true)]
+    ca_cert: Vec<(String, PathBuf)>,
+
+    /// Check the certificate of HOST, a registry given by IP address, against
+    /// NAME (e.g. `10.0.3.7:5000=registry.corp`)
+    #[arg(long, value_name = "HOST=NAME", value_parser = parse_server_name, global = true)]
+    tls_server_name: Vec<(String, String)>,
+
+    /// Refuse TLS older than VERSION, 1.2 or 1.3 (default 1.2), for every
+    /// host or only HOST, a registry or its token service (repeatable, e.g.
+    /// `--tls-min 1.3`, `--tls-min registry.corp=1.3`)
+    #[arg(long, value_name = "[HOST=]VERSION", value_parser = parse_tls_min, global = true)]
+    tls_min: Vec<(Option<String>, TlsVersion)>,
+
+    /// Send the registry's token to HOST too when its pages of tags lead
+    /// there, e.g. a CDN in front of it (repeatable)
+    #[arg(long = "trust-page-host", value_name = "HOST", global = true)]
+    trusted_page_hosts: Vec<String>,
+
+    /// Only contact HOST, failing for images on other registries (repeatable,
+    /// e.g. `--allow-registry docker.io --allow-registry ghcr.io`)
+    #[arg(long = "allow-registry", value_name = "HOST", global = true)]
+    allowed_registries: Vec<String>,
+
+    /// Also let requests go to HOST, a token service or search API of an
+    /// allowed registry (e.g. `auth.docker.io`, repeatable)
+    #[arg(long = "allow-auth-host", value_name = "HOST", global = true)]
+    allowed_auth_hosts: Vec<String>,
+
+    /// Never contact HOST, whether as the image's registry, a token service
+    /// or a redirect target (repeatable, e.g. `--deny-registry 169.254.169.254`)
+    #[arg(long = "deny-registry", value_name = "HOST", global = true)]
+    denied_registries: Vec<String>,
+
+    /// List the tags once more when the registry's pages overlap, as when tags
+    /// are deleted while paging
+    #[arg(long, action, global = true)]
+    consistent: bool,
+
+    /// GitHub token for the push times of ghcr.io images (default: $GITHUB_TOKEN)
+    #[cfg(feature = "github")]
+    #[arg(long, value_name = "TOKEN", global = true)]
+    github_token: Option<String>,
+
+    /// Read the GitHub token from FILE when it's needed, e.g. a systemd
+    /// credential, instead of $GITHUB_TOKEN
+    #[cfg(feature = "github")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "github_token",
+        global = true
+    )]
+    github_token_file: Option<PathBuf>,
+
+    /// Log in to HOST as USER, with the password of --password-file,
+    /// instead of the credentials in Docker's config (repeatable)
+    #[arg(long, value_name = "HOST=USER", value_parser = parse_username, global = true)]
+    username: Vec<(String, String)>,
+
+    /// Read the password of --username for HOST from FILE, e.g. a systemd
+    /// credential or a mounted Kubernetes secret, whenever the registry asks
+    /// for it, without a trailing newline (repeatable)
+    #[arg(long, value_name = "HOST=FILE", value_parser = parse_host_path, global = true)]
+    password_file: Vec<(String, PathBuf)>,
+
+    /// Send the registry token in FILE to HOST as it is, instead of asking
+    /// its token service for one, e.g. a token minted by a CI job; read
+    /// whenever the registry asks for credentials (repeatable)
+    #[arg(long, value_name = "HOST=FILE", value_parser = parse_host_path, global = true)]
+    token_file: Vec<(String, PathBuf)>,
+
+    /// Exchange the OIDC token in FILE, such as a CI job's ID token, for a
+    /// registry token whenever HOST asks for credentials, on behalf of
+    /// --oidc-client-id (repeatable)
+    #[arg(long, value_name = "HOST=FILE", value_parser = parse_host_path, global = true)]
+    oidc_token_file: Vec<(String, PathBuf)>,
+
+    /// Same as --oidc-token-file with the token in the environment variable
+    /// VAR (repeatable)
+    #[arg(long, value_name = "HOST=VAR", value_parser = parse_env_var, global = true)]
+    oidc_token_env: Vec<(String, String)>,
+
+    /// Client ID HOST's token service exchanges the OIDC token for
+    /// (repeatable)
+    #[arg(long, value_name = "HOST=ID", value_parser = parse_client_id, global = true)]
+    oidc_client_id: Vec<(String, String)>,
+
+    /// Leave out the tags FILE lists, one name or glob (`1.27.*`) per line,
+    /// for every image or only one (`nginx: 1.27.1`), from listings and
+    /// upgrade suggestions; `!` keeps tags an earlier line ignores (default:
+    /// `ignore_file` in the config file)
+    #[arg(long, value_name = "FILE", global = true)]
+    ignore_file: Option<PathBuf>,
+
+    /// Don't add the image to the history used for completion (also with
+    /// $DOCKER_TAGS_NO_HISTORY set, or `history = false` in the config file)
+    #[arg(long, action, global = true)]
+    no_history: bool,
+
+    /// Accept only MEDIA_TYPE for the registry's manifests, instead of the
+    /// OCI and Docker v2 types, to debug registries answering the full list
+    /// badly (repeatable, e.g. `--accept
+    /// application/vnd.docker.distribution.manifest.v2+json`)
+    #[arg(long, value_name = "MEDIA_TYPE", global = true)]
+    accept: Vec<String>,
+
+    /// Send at most N requests to the registry at a time
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), global = true)]
+    max_concurrent_requests: Option<u16>,
+
+    /// Start requests to the registry at least MS milliseconds apart
+    #[arg(long, value_name = "MS", global = true)]
+    min_request_interval: Option<u64>,
+
+    /// Ask the registry even about repositories found missing by a recent
+    /// run, still remembering its answers
+    #[arg(long, action, global = true)]
+    no_cache: bool,
+
+    /// Remember for SECONDS that a repository wasn't found, failing right
+    /// away for it meanwhile
+    #[arg(long, value_name = "SECONDS", default_value_t = 600, global = true)]
+    not_found_ttl: u64,
+
+    /// Fail rather than list more than N repositories matching an image
+    /// pattern such as `harbor.corp/team-*/app`
+    #[arg(long, value_name = "N", default_value_t = 200, global = true)]
+    max_matches: usize,
+
+    /// Print tags (and errors) as plain text, a JSON or a YAML document, the
+    /// tags of a listing also as a table, or print metrics of the images
+    /// listed for node_exporter's textfile collector (`prom`)
+    #[arg(short = 'o', long, value_enum, default_value_t, global = true)]
+    output: Format,
+
+    /// Write the output to FILE (replaced atomically) instead of stdout
+    #[arg(long, visible_alias = "out", value_name = "FILE", global = true)]
+    output_file: Option<PathBuf>,
+
+    /// Create the missing parent directories of --output-file
+    #[arg(long, action, global = true, requires = "output_file")]
+    mkdir: bool,
+
+    /// Overwrite an existing --output-file
+    #[arg(long, action, global = true, requires = "output_file")]
+    force: bool,
+
+    /// List the images of the set NAME from the config file, as `batch`
+    /// does, instead of a single image
+    #[arg(long, value_name = "NAME", value_parser = cli::config::parse_set_name, conflicts_with = "image")]
+    set: Option<String>,
+
+    /// Config file with the image sets and registry profiles (default:
+    /// $XDG_CONFIG_HOME/docker-tags/config.toml)
+    #[arg(long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+
+    /// Docker image name
+    #[arg(required_unless_present = "set")]
+    image: Option<String>,
+}
+
+impl Args {
+    /// The first of the flags given which only go with a plain listing of a
+    /// single image, not with metrics or an image pattern
+    fn single_image_flag(&self) -> Option<&'static str> {
+        let flags = [
+            (self.watch.is_some(), "--watch"),
+            (self.changed_since_state.is_some(), "--changed-since-state"),
+            (self.state.is_some(), "--state"),
+            (self.channel.is_some(), "--channel"),
+            (self.top.is_some(), "--top"),
+            (self.latest_per.is_some(), "--latest-per"),
+            (self.added_after_tag.is_some(), "--added-after-tag"),
+            (
+                self.show_signed || self.signed_only,
+                "--show-signed/--signed-only",
+            ),
+            (self.digests, "--digests"),
+            (self.show_source, "--show-source"),
+            (self.show_immutable, "--show-immutable"),
+            (self.show_ignored, "--show-ignored"),
+            (self.template.is_some(), "--template"),
+            (!self.fail_on.is_empty(), "--fail-on"),
+        ];
+        flags
+            .into_iter()
+            .find(|(set, _)| *set)
+            .map(|(_, flag)| flag)
+    }
+
+    /// How the tags of the image are listed, choosing them among `selection`,
+    /// which is `unignored` with the ignore list applied
+    fn plan<'a>(&'a self, selection: &'a Selection, unignored: &'a Selection) -> Plan<'a> {
+        let strategy = if let Some(n) = self.top {
+            Strategy::Top(n)
+        } else if let Some(reference) = &self.added_after_tag {
+            Strategy::AddedAfter {
+                reference,
+                cache: self.enrich_cache.as_deref(),
+                retry_failed: self.retry_failed,
+            }
+        } else if self.show_signed || self.signed_only {
+            Strategy::Signed {
+                only: self.signed_only,
+            }
+        } else if let Some(level) = self.latest_per {
+            Strategy::Sorted(Sort::LatestPer {
+                level,
+                per_variant: self.per_variant,
+            })
+        } else if self.sort == SortOrder::FirstSeen {
+            Strategy::FirstSeen
+        } else {
+            Strategy::Sorted(Sort::Version)
+        };
+        Plan {
+            selection,
+            unignored,
+            strategy,
+            state: self.state.as_deref(),
+            digests: self.digests,
+            source: self.show_source,
+            immutable: self.show_immutable,
+            show_ignored: self.show_ignored,
+            allow_partial: self.allow_partial,
+            quiet: self.quiet,
+            timings: self.timings,
+        }
+    }
+
+    #[cfg(all(feature = "serve", unix))]
+    fn daemon_socket(&self) -> Result<PathBuf> {
+        self.daemon_socket
+            .clone()
+            .or_else(cli::daemon::default_socket)
+            .context("Neither XDG_RUNTIME_DIR, XDG_STATE_HOME nor HOME is set")
+    }
+
+    fn config_file(&self) -> Result<ConfigFile> {
+        let path = self
+            .config
+            .clone()
+            .or_else(ConfigFile::default_path)
+            .context("Neither XDG_CONFIG_HOME nor HOME is set")?;
+        ConfigFile::open(&path)
+    }
+
+    /// Profiles of the config file by registry host, none if there's no file
+    fn profiles(&self) -> Result<BTreeMap<String, RegistryProfile>> {
+        match self.config.clone().or_else(ConfigFile::default_path) {
+            Some(path) => Ok(ConfigFile::open(&path)?.profiles()),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// The --ignore-file, or else the config file's
+    fn ignore_file(&self) -> Result<Option<PathBuf>> {
+        if let Some(path) = &self.ignore_file {
+            return Ok(Some(path.clone()));
+        }
+        match self.config.clone().or_else(ConfigFile::default_path) {
+            Some(path) => Ok(ConfigFile::open(&path)?.ignore_file()),
+            None => Ok(None),
+        }
+    }
+
+    fn records_history(&self) -> bool {
+        if self.no_history
+            || std::env::var_os("DOCKER_TAGS_NO_HISTORY").is_some_and(|v| !v.is_empty())
+        {
+            return false;
+        }
+        // read for the registry profiles already, so it's valid
+        match self.config.clone().or_else(ConfigFile::default_path) {
+            Some(path) => {
+                ConfigFile::open(&path).is_ok_and(|file| file.config.history != Some(false))
+            }
+            None => true,
+        }
+    }
+
+    fn registry(&self) -> Result<Registry> {
+        Ok(Registry {
+            path_prefix: self.path_prefix.clone(),
+            no_filter_pushdown: self.no_filter_pushdown,
+            ca_certs: self.ca_cert.clone(),
+            tls_server_names: self.tls_server_name.clone(),
+            min_tls_versions: self.tls_min.clone(),
+            trusted_page_hosts: self.trusted_page_hosts.clone(),
+            allowed_registries: self.allowed_registries.clone(),
+            allowed_auth_hosts: self.allowed_auth_hosts.clone(),
+            denied_registries: self.denied_registries.clone(),
+            consistent: self.consistent,
+            #[cfg(feature = "github")]
+            github_token: self
+                .github_token
+                .clone()
+                .or_else(|| {
+                    std::env::var("GITHUB_TOKEN")
+                        .ok()
+                        .filter(|_| self.github_token_file.is_none())
+                })
+                .filter(|token| !token.is_empty()),
+            #[cfg(feature = "github")]
+            github_token_file: self.github_token_file.clone(),
+            usernames: self.username.clone(),
+            password_files: self.password_file.clone(),
+            token_files: self.token_file.clone(),
+            oidc_client_ids: self.oidc_client_id.clone(),
+            oidc_tokens: self
+                .oidc_token_file
+                .iter()
+                .map(|(host, path)| (host.clone(), TokenSource::File(path.clone())))
+                .chain(
+                    self.oidc_token_env
+                        .iter()
+                        .map(|(host, var)| (host.clone(), TokenSource::Env(var.clone()))),
+                )
+                .collect(),
+            manifest_accept: self.accept.clone(),
+            max_concurrent_requests: self.max_concurrent_requests.map(usize::from),
+            min_request_interval: self.min_request_interval.map(Duration::from_millis),
+            not_found: NotFoundCache::open(Duration::from_secs(self.not_found_ttl))
+                .map(|cache| cache.refresh(self.no_cache)),
+            docker_config: DockerConfigProvider::default(),
+            anonymous: self.anonymous,
+            require_auth: self.require_auth,
+            quiet: self.quiet,
+            notices_shown: Default::default(),
+            profiles: self.profiles()?,
+            ignore: self
+                .ignore_file()?
+                .as_deref()
+                .map(cli::load_ignore_file)
+                .transpose()?,
+        })
+    }
+}
+
+fn series_level() -> impl TypedValueParser<Value = SeriesLevel> {
+    PossibleValuesParser::new(["major", "minor"]).map(|level| match level.as_str() {
+        "major" => SeriesLevel::Major,
+        _ => SeriesLevel::Minor,
+    })
+}
+
+fn lint_rule() -> PossibleValuesParser {
+    PossibleValuesParser::new(cli::lint::RULES.iter().map(|rule| rule.name))
+}
+
+fn upgrade_scope() -> impl TypedValueParser<Value = UpgradeScope> {
+    PossibleValuesParser::new(["patch", "minor", "major", "latest"]).map(|scope| {
+        match scope.as_str() {
+            "patch" => UpgradeScope::Patch,
+            "minor" => UpgradeScope::Minor,
+            "major" => UpgradeScope::Major,
+            _ => UpgradeScope::Latest,
+        }
+    })
+}
+
+fn parse_platform(value: &str) -> Result<String, String> {
+    let parts: Vec<_> = value.split('/').collect();
+    match parts.len() {
+        2 | 3 if parts.iter().all(|part| !part.is_empty()) => Ok(value.to_string()),
+        _ => Err("expected os/architecture[/variant], e.g. linux/arm64".to_string()),
+    }
+}
+
+fn parse_username(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((host, user)) if !host.is_empty() && !user.is_empty() => {
+            Ok((host.to_string(), user.to_string()))
+        }
+        _ => Err("expected HOST=USER".to_string()),
+    }
+}
+
+fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((host, var)) if !host.is_empty() && !var.is_empty() => {
+            Ok((host.to_string(), var.to_string()))
+        }
+        _ => Err("expected HOST=VAR".to_string()),
+    }
+}
+
+fn parse_client_id(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((host, id)) if !host.is_empty() && !id.is_empty() => {
+            Ok((host.to_string(), id.to_string()))
+        }
+        _ => Err("expected HOST=ID".to_string()),
+    }
+}
+
+fn parse_server_name(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((host, name)) if !host.is_empty() && !name.is_empty() => {
+            Ok((host.to_string(), name.to_string()))
+        }
+        _ => Err("expected HOST=NAME".to_string()),
+    }
+}
+
+fn parse_tls_min(value: &str) -> Result<(Option<String>, TlsVersion), String> {
+    let (host, version) = match value.split_once('=') {
+        Some((host, version)) if !host.is_empty() => (Some(host.to_string()), version),
+        Some(_) => return Err("expected [HOST=]VERSION".to_string()),
+        None => (None, value),
+    };
+    let version = version
+        .parse()
+        .map_err(|err: anyhow::Error| err.to_string())?;
+    Ok((host, version))
+}
+
+fn parse_constraint(value: &str) -> Result<String, String> {
+    VersionRange::parse(value)
+        .map(|_| value.to_string())
+        .map_err(|err| err.to_string())
+}
+
+/// `HOST=FILE`, a file of a registry or its token service
+fn parse_host_path(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((host, path)) if !host.is_empty() && !path.is_empty() => {
+            Ok((host.to_string(), PathBuf::from(path)))
+        }
+        _ => Err("expected HOST=FILE".to_string()),
+    }
+}
+
+/// Sorting and filtering options shared by the listing commands, which
+/// `--via-daemon` forwards as they're parsed
+#[derive(Clone, Deserialize, Serialize, clap::Args)]
+struct SelectArgs {
     /// Sort tags in reverse order
     #[arg(short = 'r', long, action)]
     reverse: bool,
@@ -21,62 +734,1453 @@ struct Args {
     #[arg(short = 'f', long = "filter")]
     pattern: Option<String>,
 
-    /// Docker image name
-    image: String,
+    /// How --filter reads its pattern: as a regex found anywhere in the tag,
+    /// or as text the tag starts with, contains or equals
+    #[arg(long = "match", value_name = "MODE", value_enum, default_value_t)]
+    match_mode: MatchMode,
+
+    /// Match --filter and --exclude regardless of case
+    #[arg(long, action)]
+    ignore_case: bool,
+
+    /// Hide tags matching a regex (repeatable, e.g. `--exclude '-rc'`)
+    #[arg(long, value_name = "PATTERN", allow_hyphen_values = true)]
+    exclude: Vec<String>,
+
+    /// Hide pre-releases, such as `2.0.0-rc1` or `17beta2`
+    #[arg(long, action)]
+    stable: bool,
+
+    /// Print only the newest stable version, leaving out pre-releases, channel
+    /// tags and the --aliases tags, and the tags --ignore-file lists
+    #[arg(long, action, conflicts_with_all = ["reverse", "limit", "no_sort", "pin_aliases"])]
+    latest: bool,
+
+    /// Keep only versions in RANGE, e.g. `>=1.2 <2`, `1.27.x` or `1 || 2`
+    #[arg(long, value_name = "RANGE", value_parser = parse_constraint)]
+    constraint: Option<String>,
+
+    /// Keep the registry's order (reversed with -r); with --limit and without
+    /// -r, stop fetching once enough tags matched
+    #[arg(long, action)]
+    no_sort: bool,
+
+    /// Hide commit SHAs (12 or more hex digits) and `sha256-<digest>` tags
+    #[arg(long, action)]
+    no_sha: bool,
+
+    /// Hide tags with names longer than N characters
+    #[arg(long, value_name = "N")]
+    max_name_length: Option<usize>,
+
+    /// Keep only tags which read as versions, e.g. `16`, `3.12-slim`, `1.2.3`
+    #[arg(long, visible_alias = "numeric-only", action)]
+    version_like: bool,
+
+    /// Keep only channel tags, such as `latest`, `stable` or `mainline-alpine`
+    #[arg(long, action, conflicts_with = "no_channels")]
+    channels: bool,
+
+    /// Hide channel tags, such as `latest`, `stable` or `mainline-alpine`
+    #[arg(long, action)]
+    no_channels: bool,
+
+    /// Count NAME as a channel too, besides `latest`, `stable`, `edge`, `lts`,
+    /// `nightly` and the like (repeatable)
+    #[arg(long, value_name = "NAME")]
+    channel_name: Vec<String>,
+
+    /// List the --aliases tags first, apart from the others and not counted
+    /// by --limit, whatever the order; the default when printing text to a
+    /// terminal
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        conflicts_with = "no_aliases"
+    )]
+    pin_aliases: Option<bool>,
+
+    /// Hide the --aliases tags
+    #[arg(long, action)]
+    no_aliases: bool,
+
+    /// Alias tags for --pin-aliases and --no-aliases
+    #[arg(
+        long,
+        value_name = "NAMES",
+        value_delimiter = ',',
+        default_values_t = DEFAULT_ALIASES.map(String::from)
+    )]
+    aliases: Vec<String>,
 }
 
-async fn print_tags(
-    image_name: &str,
-    reverse: bool,
-    pattern: Option<&str>,
-    limit: Option<usize>,
-) -> Result<()> {
-    let image =
-        Image::try_from(image_name).map_err(|_| anyhow!("Invalid image name: {image_name:?}"))?;
-    let mut tags = image.fetch_tags().await?;
+/// How `--filter` reads its pattern, chosen with `--match`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, clap::ValueEnum)]
+enum MatchMode {
+    /// Regex matching anywhere in the tag, `1.27` matching `0.1.27-test`
+    #[default]
+    Regex,
+    /// Text the tag starts with
+    Prefix,
+    /// Text anywhere in the tag
+    Contains,
+    /// The whole tag
+    Exact,
+}
+
+impl SelectArgs {
+    fn selection(&self) -> Result<Selection> {
+        let mut pattern = None;
+        let mut filters = Vec::new();
+        if let Some(text) = &self.pattern {
+            let ignore_case = self.ignore_case;
+            match self.match_mode {
+                MatchMode::Regex => {
+                    let re = cli::regex(text)?;
+                    // `(?i)` rather than a builder flag, so the filter isn't
+                    // pushed down to case-sensitive tag searches
+                    pattern = Some(match ignore_case {
+                        true => cli::regex(&format!("(?i){text}"))?,
+                        false => re,
+                    });
+                }
+                MatchMode::Prefix => filters.push(TagFilter::prefix(text, ignore_case)),
+                MatchMode::Contains => filters.push(TagFilter::contains(text, ignore_case)),
+                MatchMode::Exact => filters.push(TagFilter::exact(text, ignore_case)),
+            }
+        }
+        for text in &self.exclude {
+            filters.push(TagFilter::excluding(match self.ignore_case {
+                true => cli::regex(&format!("(?i){text}"))?,
+                false => cli::regex(text)?,
+            }));
+        }
+        if self.stable || self.latest {
+            filters.push(TagFilter::stable());
+        }
+        if let Some(range) = &self.constraint {
+            filters.push(TagFilter::version_range(range.parse()?));
+        }
+        if self.no_sha {
+            filters.push(TagFilter::no_sha());
+        }
+        if let Some(max) = self.max_name_length {
+            filters.push(TagFilter::max_name_length(max));
+        }
+        if self.version_like {
+            filters.push(TagFilter::version_like());
+        }
+        if self.channels {
+            filters.push(TagFilter::channels(self.channel_set()));
+        }
+        if self.no_channels || self.latest {
+            filters.push(TagFilter::no_channels(self.channel_set()));
+        }
+        Ok(Selection {
+            reverse: self.reverse,
+            keep_order: self.no_sort,
+            pattern,
+            filters,
+            limit: match self.latest {
+                true => Some(1),
+                false => self.limit,
+            },
+            aliases: self.alias_mode(self.pin_aliases == Some(true)),
+        })
+    }
+
+    /// Pinned aliases if `pin`, unless they are hidden
+    fn alias_mode(&self, pin: bool) -> Aliases {
+        match (self.no_aliases || self.latest, pin) {
+            (true, _) => Aliases::Hidden(self.aliases.clone()),
+            (false, true) => Aliases::Pinned(self.aliases.clone()),
+            (false, false) => Aliases::Sorted,
+        }
+    }
+
+    fn channel_set(&self) -> Channels {
+        self.channel_name
+            .iter()
+            .fold(Channels::default(), |channels, name| channels.with(name))
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List signatures, SBOMs and attestations attached to an image
+    Referrers {
+        /// Image with a tag or digest, e.g. `alpine:3.21`
+        reference: String,
+    },
+    /// Tell whether a repository exists and can be read, without listing its
+    /// tags; exits with code 4 if it's not found and 6 if access is denied
+    Exists {
+        /// Docker image name, without a tag
+        image: String,
+    },
+    /// Answer tag listings over HTTP (`GET /v1/tags?image=...`, `GET
+    /// /healthz`), sharing tokens and listings between requests
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8675")]
+        listen: std::net::SocketAddr,
+
+        /// Reuse an image's listing for SECONDS before asking the registry again
+        #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+        ttl: u64,
+    },
+    /// Keep connections and tokens for the listings of --via-daemon in a
+    /// daemon answering them over a Unix socket, only to the user running it
+    #[cfg(all(feature = "serve", unix))]
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+    /// Show which other tags point to the same image as a tag
+    Resolve {
+        /// Image with a tag, e.g. `nginx:latest`
+        reference: String,
+
+        /// Check at most N candidate tags, versions first
+        #[arg(long, value_name = "N")]
+        search_limit: Option<usize>,
+    },
+    /// Summarize an image's tags: counts, version series, newest and oldest
+    /// versions, pre-releases and variants
+    Stats {
+        /// Docker image name
+        image: String,
+    },
+    /// List the version series an image's tags belong to, newest first, e.g.
+    /// `17`, `16`, `15` for `series postgres`
+    Series {
+        /// Docker image name
+        image: String,
+
+        /// Series of major versions (`17`) or minor ones (`3.12`)
+        #[arg(long, value_name = "LEVEL", value_parser = series_level(), default_value = "major")]
+        level: SeriesLevel,
+
+        /// Leave out pre-releases, so that series with only release
+        /// candidates aren't listed
+        #[arg(long, action)]
+        stable: bool,
+
+        /// Only consider tags matching a pattern
+        #[arg(short = 'f', long = "filter")]
+        pattern: Option<String>,
+    },
+    /// Explain where each tag sorts: the scheme it was read with, its
+    /// version components and the bucket it landed in, in listing order
+    VerifyOrder {
+        /// Docker image name
+        image: String,
+
+        /// Explain only the first N tags
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+
+        /// Only consider tags matching a pattern
+        #[arg(short = 'f', long = "filter")]
+        pattern: Option<String>,
+    },
+    /// Show which platforms the newest tags can be pulled for, e.g.
+    /// `matrix nginx --platforms linux/amd64,linux/arm64 -n 20`
+    Matrix {
+        /// Docker image name
+        image: String,
+
+        /// Platforms to check, as `os/architecture[/variant]`
+        #[arg(
+            long,
+            required = true,
+            value_delimiter = ',',
+            value_name = "PLATFORMS",
+            value_parser = parse_platform
+        )]
+        platforms: Vec<String>,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// List the tags published within a time window, oldest first with their
+    /// exact times, e.g. `published ghcr.io/org/app --from 2026-03-03 --to
+    /// 2026-03-05`, going by the `created` date of each image config
+    Published {
+        /// Docker image name
+        image: String,
+
+        /// Start of the window, included: a date (`2026-03-03`, from its
+        /// start in UTC), an RFC 3339 time or a duration ago (`-48h`, `-7d`)
+        #[arg(long, value_name = "TIME", allow_hyphen_values = true, value_parser = cli::published::parse_moment)]
+        from: Moment,
+
+        /// End of the window, excluded, as for --from except that a date
+        /// includes the whole day; defaults to now
+        #[arg(long, value_name = "TIME", allow_hyphen_values = true, value_parser = cli::published::parse_moment)]
+        to: Option<Moment>,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Pin image references in a Dockerfile, compose file, Kubernetes manifest
+    /// or any text file to their digests (`alpine:3.21@sha256:…`), printing
+    /// the result
+    Pin {
+        /// File to scan
+        file: PathBuf,
+
+        /// Rewrite FILE in place (atomically) instead of printing it
+        #[arg(long, action, conflicts_with_all = ["check", "output_file"])]
+        write: bool,
+
+        /// Only list references which aren't pinned or whose pin is outdated,
+        /// exiting with code 3 if there are any
+        #[arg(long, action)]
+        check: bool,
+
+        /// Replace pinned digests which no longer match their tag
+        #[arg(long, action, conflicts_with = "check")]
+        update: bool,
+
+        /// Regex finding the references, its `ref` group (or else the whole
+        /// match) being one; defaults to `FROM` and `image:` lines
+        #[arg(long, value_name = "REGEX")]
+        pattern: Option<String>,
+    },
+    /// List the image references of a file which have newer tags, exiting
+    /// with code 3 if there are any
+    CheckUpgrades {
+        /// File to scan
+        file: PathBuf,
+
+        /// How far to upgrade: within the patch series, within the major
+        /// version, at most one major version up, or to any newer version
+        #[arg(long, value_name = "SCOPE", value_parser = upgrade_scope(), default_value = "latest")]
+        scope: UpgradeScope,
+
+        /// Also suggest pre-releases
+        #[arg(long, action)]
+        pre_releases: bool,
+
+        /// Renovate config (`renovate.json`) whose `ignoreDeps` and
+        /// `packageRules` (`allowedVersions`, `enabled`, `ignoreUnstable`,
+        /// `matchUpdateTypes`) narrow the upgrade of each image, or a
+        /// Dependabot config (`dependabot.yml`) whose `docker` updates'
+        /// `ignore` entries do
+        #[arg(long, value_name = "FILE")]
+        policy_file: Option<PathBuf>,
+
+        /// Regex finding the references, its `ref` group (or else the whole
+        /// match) being one; defaults to `FROM` and `image:` lines
+        #[arg(long, value_name = "REGEX")]
+        pattern: Option<String>,
+
+        /// Exit with a code of its own if any of the CONDITIONS is met by a
+        /// reference: `outdated` (7), `outdated:major[>=N]` or
+        /// `outdated:minor[>=N]` going by the newest stable tag the policy
+        /// allows, or `no-match` (8) if it allows none
+        #[arg(
+            long,
+            value_name = "CONDITIONS",
+            value_delimiter = ',',
+            value_parser = cli::policy::parse_condition
+        )]
+        fail_on: Vec<Condition>,
+    },
+    /// Check the image references of a file, or of the Dockerfiles, compose
+    /// files and manifests under a directory, for floating tags, deprecated
+    /// registries and other mistakes, exiting with code 3 if any is an error
+    Lint {
+        /// File or directory to scan
+        path: PathBuf,
+
+        /// Rules to turn off, such as `latest` or `docker-hub`
+        #[arg(long, value_name = "RULES", value_delimiter = ',', value_parser = lint_rule())]
+        disable: Vec<String>,
+
+        /// Rules off by default to turn on: `unpinned` (no digest) and
+        /// `floating-version` (`3.21` rather than `3.21.2`)
+        #[arg(long, value_name = "RULES", value_delimiter = ',', value_parser = lint_rule())]
+        enable: Vec<String>,
+
+        /// Registry mirroring Docker Hub, which references to `docker.io`
+        /// get a `docker-hub` warning for not using
+        #[arg(long, value_name = "HOST")]
+        mirror: Option<String>,
+
+        /// Print the findings as GitHub Actions workflow commands, which
+        /// annotate the lines of the pull request
+        #[arg(long, action)]
+        github: bool,
+
+        /// Regex finding the references, its `ref` group (or else the whole
+        /// match) being one; defaults to `FROM` and `image:` lines
+        #[arg(long, value_name = "REGEX")]
+        pattern: Option<String>,
+    },
+    /// Go through the token exchange for pulling an image and print the token
+    /// service, scope, credentials used, token claims and rate limits
+    Token {
+        /// Docker image name
+        image: String,
+
+        /// Also print the token itself
+        #[arg(long, action)]
+        show_token: bool,
+    },
+    /// Show or clear the images listed before, which shell completion
+    /// suggests
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+    },
+    /// Manage the named image sets of the config file, which `--set NAME`
+    /// lists
+    Sets {
+        #[command(subcommand)]
+        action: SetsAction,
+    },
+    /// Manage what earlier runs remembered, such as repositories not found
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print how this binary was built: version, git commit, TLS backend,
+    /// features and target, to include in bug reports
+    Version,
+    /// Print the images from the history starting with CURRENT, for shell
+    /// completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(default_value = "")]
+        current: String,
+    },
+    /// List the tags of every image in a file, as `image:tag` lines, followed
+    /// by a summary of how each image went on stderr (or under `summary` in
+    /// a document); exits with code 4 if images weren't found, 6 if access
+    /// to them was denied and 1 for other or mixed failures
+    Batch {
+        /// File with one image per line (`#` starts a comment), or `-` for
+        /// stdin; a tag after the image (`nginx:1.25.3`) is the one in use,
+        /// which `-o prom` compares with the newest version
+        input: PathBuf,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// List the tags every one of several images has, such as the images of a
+    /// product deployed at the same version, exiting with code 3 if there
+    /// are none
+    Intersect {
+        /// Docker image names
+        #[arg(required = true, num_args = 2..)]
+        images: Vec<String>,
+
+        /// List the tags any of the images has instead, telling which have them
+        #[arg(long, action)]
+        union: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Sort and filter tag names read from a file or stdin, without network access
+    Sort {
+        /// File with one tag per line, or `-` for stdin
+        input: PathBuf,
+
+        /// Skip repeated tags, with a warning
+        #[arg(long, action)]
+        dedup: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Write the tags of an image as a JSON snapshot to compare later with
+    /// diff-snapshots, e.g. `snapshot alpine --digests --out alpine.json`
+    Snapshot {
+        /// Docker image name
+        image: String,
+
+        /// Record the digest of each tag's manifest, a request per tag
+        #[arg(long, action)]
+        digests: bool,
+
+        /// Record when each tag's image was created, along with its digest
+        #[arg(long, action)]
+        dates: bool,
+    },
+    /// Compare two snapshots written by `snapshot`: the tags added, removed
+    /// and pointing to another image, exiting with code 3 if there are any
+    DiffSnapshots {
+        /// The earlier snapshot
+        old: PathBuf,
+
+        /// The later snapshot
+        new: PathBuf,
+    },
+}
+
+#[cfg(all(feature = "serve", unix))]
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Start the daemon in the background with the registry settings given,
+    /// unless it's running already
+    Start {
+        /// Exit once no request came for SECONDS
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 600,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        idle_timeout: u64,
+
+        /// Run the daemon in the foreground instead, until it's stopped or idle
+        #[arg(long, action)]
+        foreground: bool,
+    },
+    /// Stop the daemon, after it answered the listings in flight
+    Stop,
+}
+
+#[derive(Subcommand)]
+enum SetsAction {
+    /// Print the names of the sets
+    List,
+    /// Print the images of a set
+    Show {
+        #[arg(value_parser = cli::config::parse_set_name)]
+        name: String,
+    },
+    /// Add images to a set, creating it if needed
+    Add {
+        #[arg(value_parser = cli::config::parse_set_name)]
+        name: String,
+
+        /// Docker image names, optionally with the tag in use (`nginx:1.25.3`)
+        #[arg(required = true)]
+        images: Vec<String>,
+    },
+    /// Remove images from a set, or the whole set if none are given
+    Remove {
+        #[arg(value_parser = cli::config::parse_set_name)]
+        name: String,
+
+        /// Docker image names, as in the set
+        images: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Forget the repositories found missing, and the listings of `serve`
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum HistoryAction {
+    /// Print the images, most recent first (the default)
+    List,
+    /// Forget all images
+    Clear,
+}
+
+async fn list_tags(client: &Client, image: &Image, selection: &Selection) -> Result<Vec<Tag>> {
+    let tags = client.fetch_tags(image).await?;
+
+    Ok(selection.apply(tags))
+}
+
+/// Exit code signalling that `--changed-since-state` found new tags, `pin
+/// --check` or `check-upgrades` references to update, `intersect` no tag
+/// common to the images, or `lint` errors
+const EXIT_CHANGED: u8 = 3;
+
+/// Exit code signalling that `exists` found no such repository, or that
+/// `batch` found none for some images
+const EXIT_NOT_FOUND: u8 = 4;
+
+/// Exit code signalling that `--allow-partial` printed an incomplete listing
+const EXIT_PARTIAL: u8 = 5;
+
+/// Exit code signalling that `exists` was denied access to the repository,
+/// or `batch` to some of the images
+const EXIT_DENIED: u8 = 6;
+
+/// Exit codes signalling that a `--fail-on` condition was met
+const EXIT_OUTDATED: u8 = 7;
+const EXIT_NO_MATCH: u8 = 8;
+const EXIT_UNSIGNED: u8 = 9;
+
+/// Name the `--fail-on` conditions met on stderr and exit with the code of
+/// the first one, if any
+fn fail_on_exit(triggered: &[Trigger]) -> Option<ExitCode> {
+    for trigger in triggered {
+        eprintln!("Failed --fail-on {}: {}", trigger.condition, trigger.reason);
+    }
+    let code = match triggered.first()?.condition {
+        Condition::Outdated(_) => EXIT_OUTDATED,
+        Condition::NoMatch => EXIT_NO_MATCH,
+        Condition::Unsigned => EXIT_UNSIGNED,
+    };
+    Some(ExitCode::from(code))
+}
+
+/// Metrics of `image` for `--output prom`, which tell when listing its tags
+/// failed rather than leave the textfile collector without a file
+async fn print_metrics(
+    out: &mut Output,
+    registry: &cli::Registry,
+    client: &Client,
+    image: &Image,
+    selection: &Selection,
+) -> Result<ExitCode> {
+    let name = image.to_string();
+    let mut metrics = Metrics::default();
+    let failure = match client.fetch_tags_partial(image).await {
+        Ok((tags, outcome)) => {
+            if !registry.quiet {
+                for warning in &outcome.warnings {
+                    eprintln!("Warning: {warning}");
+                }
+            }
+            registry.warn_notices(image, &outcome.notices);
+            match outcome.error {
+                Some(err) => Some(err),
+                None => {
+                    metrics.listed(&name, None, outcome.listed, &selection.apply(tags));
+                    None
+                }
+            }
+        }
+        Err(err) => Some(err),
+    };
+    if failure.is_some() {
+        metrics.failed(&name, None);
+    }
+    out.line(metrics.render())?;
+    match failure {
+        Some(err) => {
+            eprintln!("Error: {err:#}");
+            Ok(ExitCode::from(1))
+        }
+        None => Ok(ExitCode::SUCCESS),
+    }
+}
+
+async fn print_tags(args: &Args, out: &mut Output) -> Result<ExitCode> {
+    let registry = args.registry()?;
+    let image = registry.image(args.image.as_deref().unwrap_or_default())?;
+    let client = registry.client(&image)?;
+    let mut unignored = args.select.selection()?;
+    // people reading the listing look for `latest` first; the strategies
+    // choosing tags by more than their names keep it where it sorts
+    let chooses = args.top.is_some()
+        || args.latest_per.is_some()
+        || args.added_after_tag.is_some()
+        || args.show_signed
+        || args.signed_only;
+    if args.select.pin_aliases.is_none()
+        && args.watch.is_none()
+        && args.changed_since_state.is_none()
+        && !chooses
+        && matches!(args.output, Format::Text | Format::Table)
+        && out.is_terminal()
+    {
+        unignored.aliases = args.select.alias_mode(true);
+    }
+    let outdated = args
+        .fail_on
+        .iter()
+        .any(|condition| matches!(condition, Condition::Outdated(_)));
+    match &args.current {
+        None if outdated => bail!("--fail-on outdated needs --current TAG, the tag in use"),
+        Some(current) if outdated && LenientVersion::parse(current).is_none() => {
+            bail!("--current {current} isn't a version, so --fail-on outdated can't compare it")
+        }
+        _ => {}
+    }
+    if args.fail_on.contains(&Condition::Unsigned) && !args.show_signed {
+        bail!("--fail-on unsigned needs --show-signed");
+    }
+    if args.show_ignored && registry.ignore.is_none() {
+        bail!("--show-ignored needs --ignore-file FILE or `ignore_file` in the config file");
+    }
+    let selection = registry.selection_for(&image, &unignored);
+    if args.explain_auth {
+        eprintln!("{}", explain_credentials(image.registry()).await);
+    }
 
-    tags.sort();
-    if reverse {
-        tags.reverse();
+    if args.output == Format::Prom {
+        if let Some(flag) = args.single_image_flag() {
+            bail!("--output prom doesn't go with {flag}");
+        }
+        return print_metrics(out, &registry, &client, &image, &selection).await;
+    }
+
+    if let Some(channel) = &args.channel {
+        if !args.select.channel_set().is_channel(&Tag::new(channel)) {
+            bail!(
+                "{channel:?} is not a channel tag; count it as one with --channel-name {channel}"
+            );
+        }
+        cli::resolve::channel(
+            out,
+            &client,
+            &image,
+            channel,
+            &selection,
+            args.output,
+            args.quiet,
+        )
+        .await?;
+        return Ok(ExitCode::SUCCESS);
     }
-    if let Some(pattern) = pattern {
-        let re = Regex::new(pattern).map_err(|_| anyhow!("Invalid regex pattern: {pattern:?}"))?;
-        tags.retain(|tag| re.is_match(&tag.to_string()));
+
+    if let Some(interval) = args.watch {
+        let mut notifiers = Vec::new();
+        if let Some(url) = &args.notify_url {
+            notifiers.push(Notifier::new(Target::Url(url.clone())));
+        }
+        if let Some(cmd) = &args.notify_cmd {
+            notifiers.push(Notifier::new(Target::Command(cmd.clone())));
+        }
+        cli::watch::run(
+            out,
+            &image.to_string(),
+            Duration::from_secs(interval),
+            notifiers,
+            || list_tags(&client, &image, &selection),
+        )
+        .await?;
+        return Ok(ExitCode::SUCCESS);
     }
-    if let Some(limit) = limit {
-        tags.truncate(limit);
+
+    if let Some(path) = &args.changed_since_state {
+        let _lock = Lock::acquire(path)?;
+        let previous = State::load_for(path, &image.to_string())?;
+
+        let tags = list_tags(&client, &image, &selection).await?;
+        let new_tags = match &previous {
+            Some(state) => state.new_tags(&tags),
+            None if args.seed_print => tags.iter().collect(),
+            None => Vec::new(),
+        };
+        let entries = new_tags.iter().map(|&tag| tag.clone().into()).collect();
+        out.listing(
+            &*args.output.formatter(false)?,
+            &Listing::new(Some(image.to_string()), entries, &[]),
+        )?;
+        State::new(image.to_string(), &tags, previous.as_ref()).save(path)?;
+
+        return Ok(match new_tags.is_empty() {
+            true => ExitCode::SUCCESS,
+            false => ExitCode::from(EXIT_CHANGED),
+        });
     }
 
-    for tag in tags {
-        println!("{tag}");
+    let formatter: Box<dyn Formatter> = match &args.template {
+        Some(template) => Box::new(Template::parse(template)?),
+        None => args.output.formatter(args.show_signed)?,
+    };
+    let plan = args.plan(&selection, &unignored);
+    let Listed { listing, matching } =
+        cli::listing::list(&registry, &client, &image, &plan).await?;
+    out.listing(&*formatter, &listing)?;
+    if matches!(args.output, Format::Text | Format::Table) {
+        if !args.quiet {
+            for tag in &listing.ignored {
+                eprintln!("Warning: left out {tag}, ignored by the ignore file");
+            }
+        }
+        // after the tags, which are still buffered
+        out.flush()?;
+        if !args.no_summary && out.is_terminal() {
+            eprintln!("{}", listing.summary(selection.is_filtered()));
+        }
+        for line in listing.timings.iter().flat_map(TimingsReport::lines) {
+            eprintln!("{line}");
+        }
     }
 
-    Ok(())
+    // what --fail-on outdated compares --current with
+    let candidates = match args.current {
+        Some(_) => matching,
+        None => Vec::new(),
+    };
+    let results = Results {
+        current: args.current.as_deref(),
+        candidates: &candidates,
+        shown: listing.aliases.len() + listing.tags.len(),
+        unsigned: args.show_signed.then(|| {
+            listing
+                .tags
+                .iter()
+                .filter(|entry| entry.signed == Some(false))
+                .map(|entry| entry.name.as_str())
+                .collect()
+        }),
+    };
+    if let Some(code) = fail_on_exit(&cli::policy::evaluate(&args.fail_on, &results)) {
+        return Ok(code);
+    }
+    Ok(match listing.complete {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(EXIT_PARTIAL),
+    })
 }
 
-#[tokio::main]
-async fn main() -> ExitCode {
-    let args = Args::parse();
+/// `--via-daemon`: the listing of the daemon, which keeps its connections
+/// and tokens between runs
+#[cfg(all(feature = "serve", unix))]
+async fn print_via_daemon(args: &Args, out: &mut Output) -> Result<ExitCode> {
+    let flags = [
+        (args.output == Format::Prom, "--output prom"),
+        (args.allow_partial, "--allow-partial"),
+        (args.timings, "--timings"),
+        (args.explain_auth, "--explain-auth"),
+    ];
+    let flag = args.single_image_flag().or_else(|| {
+        flags
+            .into_iter()
+            .find(|(set, _)| *set)
+            .map(|(_, flag)| flag)
+    });
+    if let Some(flag) = flag {
+        bail!("--via-daemon doesn't go with {flag}");
+    }
+    let mut select = args.select.clone();
+    if select.pin_aliases.is_none() && args.output == Format::Text && out.is_terminal() {
+        select.pin_aliases = Some(true);
+    }
+    let listing = cli::daemon::tags(
+        &args.daemon_socket()?,
+        args.image.as_deref().unwrap_or_default(),
+        serde_json::to_value(&select)?,
+    )
+    .await?;
+    match args.output {
+        Format::Text => cli::daemon::print(out, &listing)?,
+        format => out.document(format, &listing)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-    if let Err(err) = print_tags(
-        args.image.as_str(),
-        args.reverse,
-        args.pattern.as_deref(),
-        args.limit,
+/// `--set NAME`: the images of the set listed as `batch` lists them, then
+/// `--fail-on` checked for each
+async fn print_set(args: &Args, out: &mut Output) -> Result<ExitCode> {
+    // --fail-on comes last, so any other flag is found first
+    let flag = args
+        .single_image_flag()
+        .filter(|&flag| flag != "--fail-on")
+        .or_else(|| {
+            [
+                (args.allow_partial, "--allow-partial"),
+                (args.timings, "--timings"),
+                (args.explain_auth, "--explain-auth"),
+                (args.current.is_some(), "--current"),
+                #[cfg(all(feature = "serve", unix))]
+                (args.via_daemon, "--via-daemon"),
+            ]
+            .into_iter()
+            .find(|(set, _)| *set)
+            .map(|(_, flag)| flag)
+        });
+    match flag {
+        Some("--current") => {
+            bail!("--set doesn't go with --current; give the tag in use with the images of the set")
+        }
+        Some(flag) => bail!("--set doesn't go with {flag}"),
+        None => {}
+    }
+    if args.fail_on.contains(&Condition::Unsigned) {
+        bail!("--fail-on unsigned needs --show-signed, which doesn't go with --set");
+    }
+
+    let file = args.config_file()?;
+    let name = args.set.as_deref().unwrap_or_default();
+    let images: Vec<_> = file.set(name)?.images.iter().map(String::as_str).collect();
+    let (status, triggered) = cli::batch::run(
+        out,
+        &args.registry()?,
+        &images,
+        &args.select.selection()?,
+        args.output,
+        args.no_summary,
+        &args.fail_on,
     )
-    .await
+    .await?;
+    if status != Status::Ok {
+        return Ok(batch_exit(status));
+    }
+    Ok(fail_on_exit(&triggered).unwrap_or(ExitCode::SUCCESS))
+}
+
+/// Exit code of a `batch` run which went as `status`, the same as `exists`
+/// gives for the outcome (see the README's exit code table)
+fn batch_exit(status: Status) -> ExitCode {
+    match status {
+        Status::Ok => ExitCode::SUCCESS,
+        Status::NotFound => ExitCode::from(EXIT_NOT_FOUND),
+        Status::AccessDenied => ExitCode::from(EXIT_DENIED),
+        Status::Error => ExitCode::from(1),
+    }
+}
+
+async fn run(args: &Args, out: &mut Output) -> Result<ExitCode> {
+    if matches!(
+        args.command,
+        Some(Command::Cache { .. } | Command::Complete { .. })
+    ) && args.output != Format::Text
     {
-        println!("Error: {err}");
-        for (level, cause) in err.chain().skip(1).enumerate() {
-            eprintln!(
-                "{:indent$}Caused by: {}",
-                "",
-                cause,
-                indent = (level + 1) * 2
+        return Err(anyhow!("--output is only supported for tag listings"));
+    }
+    match &args.command {
+        Some(Command::Referrers { reference }) => {
+            cli::referrers::run(out, &args.registry()?, reference, args.output).await?
+        }
+        Some(Command::Exists { image }) => {
+            return Ok(
+                match cli::exists::run(out, &args.registry()?, image, args.output).await? {
+                    ExistsOutcome::Exists => ExitCode::SUCCESS,
+                    ExistsOutcome::NotFound => ExitCode::from(EXIT_NOT_FOUND),
+                    ExistsOutcome::AccessDenied => ExitCode::from(EXIT_DENIED),
+                },
             );
         }
-        return ExitCode::from(1);
+        Some(Command::Resolve {
+            reference,
+            search_limit,
+        }) => {
+            cli::resolve::run(
+                out,
+                &args.registry()?,
+                reference,
+                *search_limit,
+                args.output,
+                args.quiet,
+            )
+            .await?
+        }
+        #[cfg(feature = "serve")]
+        Some(Command::Serve { listen, ttl }) => {
+            let disk =
+                cli::listing_cache::ListingCache::open().map(|cache| cache.refresh(args.no_cache));
+            cli::serve::run(args.registry()?, *listen, Duration::from_secs(*ttl), disk).await?
+        }
+        #[cfg(all(feature = "serve", unix))]
+        Some(Command::Daemon { action }) => {
+            if args.output != Format::Text {
+                bail!("--output is only supported for tag listings");
+            }
+            let socket = args.daemon_socket()?;
+            match action {
+                DaemonAction::Start {
+                    idle_timeout,
+                    foreground: true,
+                } => {
+                    cli::daemon::serve(
+                        args.registry()?,
+                        &socket,
+                        Duration::from_secs(*idle_timeout),
+                        |select| serde_json::from_value::<SelectArgs>(select)?.selection(),
+                    )
+                    .await?
+                }
+                DaemonAction::Start { .. } => {
+                    match cli::daemon::start(&socket, env::args_os().skip(1)).await? {
+                        true => eprintln!("Started the daemon on {}", socket.display()),
+                        false => eprintln!("The daemon is already running on {}", socket.display()),
+                    }
+                }
+                DaemonAction::Stop => match cli::daemon::stop(&socket).await? {
+                    true => eprintln!("Stopped the daemon on {}", socket.display()),
+                    false => eprintln!("No daemon is running on {}", socket.display()),
+                },
+            }
+        }
+        Some(Command::Stats { image }) if cli::expand::is_pattern(image) => {
+            cli::expand::stats(out, &args.registry()?, image, args.max_matches, args.output).await?
+        }
+        Some(Command::Stats { image }) => {
+            cli::stats::run(out, &args.registry()?, image, args.output).await?
+        }
+        Some(Command::Series {
+            image,
+            level,
+            stable,
+            pattern,
+        }) => {
+            let pattern = pattern.as_deref().map(cli::regex).transpose()?;
+            cli::series::run(
+                out,
+                &args.registry()?,
+                image,
+                *level,
+                *stable,
+                pattern.as_ref(),
+                args.output,
+            )
+            .await?
+        }
+        Some(Command::VerifyOrder {
+            image,
+            limit,
+            pattern,
+        }) => {
+            let pattern = pattern.as_deref().map(cli::regex).transpose()?;
+            cli::verify_order::run(
+                out,
+                &args.registry()?,
+                image,
+                pattern.as_ref(),
+                *limit,
+                args.output,
+            )
+            .await?
+        }
+        Some(Command::Matrix {
+            image,
+            platforms,
+            select,
+        }) => {
+            cli::matrix::run(
+                out,
+                &args.registry()?,
+                image,
+                platforms,
+                &select.selection()?,
+                args.output,
+                args.quiet,
+            )
+            .await?
+        }
+        Some(Command::Published {
+            image,
+            from,
+            to,
+            select,
+        }) => {
+            let window = Window::new(*from, *to, SystemTime::now())?;
+            cli::published::run(
+                out,
+                &args.registry()?,
+                image,
+                window,
+                &select.selection()?,
+                args.output,
+                args.quiet,
+            )
+            .await?
+        }
+        Some(Command::Pin {
+            file,
+            write,
+            check,
+            update,
+            pattern,
+        }) => {
+            let pattern = pattern.as_deref().unwrap_or(cli::pin::DEFAULT_PATTERN);
+            let pattern = cli::regex(pattern)?;
+            let mode = match (write, check) {
+                (true, _) => Mode::Write,
+                (_, true) => Mode::Check,
+                _ => Mode::Print,
+            };
+            let pending = cli::pin::run(
+                out,
+                &args.registry()?,
+                file,
+                &pattern,
+                mode,
+                *update,
+                args.output,
+            )
+            .await?;
+            if pending && *check {
+                return Ok(ExitCode::from(EXIT_CHANGED));
+            }
+        }
+        Some(Command::CheckUpgrades {
+            file,
+            scope,
+            pre_releases,
+            policy_file,
+            pattern,
+            fail_on,
+        }) => {
+            if fail_on.contains(&Condition::Unsigned) {
+                bail!("--fail-on unsigned is only for tag listings with --show-signed");
+            }
+            let pattern = pattern.as_deref().unwrap_or(cli::pin::DEFAULT_PATTERN);
+            let pattern = cli::regex(pattern)?;
+            let policies = policy_file.as_deref().map(PolicyFile::load).transpose()?;
+            let policy = UpgradePolicy {
+                scope: *scope,
+                pre_releases: *pre_releases,
+                ..UpgradePolicy::default()
+            };
+            let (report, triggered) = cli::upgrades::run(
+                &args.registry()?,
+                file,
+                &pattern,
+                policy,
+                policies.as_ref(),
+                fail_on,
+            )
+            .await?;
+            cli::upgrades::print(out, &report, args.output)?;
+            if let Some(code) = fail_on_exit(&triggered) {
+                return Ok(code);
+            }
+            if !report.upgrades.is_empty() {
+                return Ok(ExitCode::from(EXIT_CHANGED));
+            }
+        }
+        Some(Command::Lint {
+            path,
+            disable,
+            enable,
+            mirror,
+            github,
+            pattern,
+        }) => {
+            if *github && args.output != Format::Text {
+                bail!("--github is only for text output");
+            }
+            let pattern = pattern.as_deref().unwrap_or(cli::pin::DEFAULT_PATTERN);
+            let linter = Linter {
+                pattern: cli::regex(pattern)?,
+                rules: cli::lint::rules(enable, disable),
+                options: cli::lint::Options {
+                    mirror: mirror.clone(),
+                },
+            };
+            let errors = cli::lint::run(out, path, &linter, args.output, *github, args.quiet)?;
+            if errors {
+                return Ok(ExitCode::from(EXIT_CHANGED));
+            }
+        }
+        Some(Command::Token { image, show_token }) => {
+            cli::token::run(out, &args.registry()?, image, *show_token, args.output).await?
+        }
+        Some(Command::Intersect {
+            images,
+            union,
+            select,
+        }) => {
+            let common = cli::intersect::run(
+                out,
+                &args.registry()?,
+                images,
+                *union,
+                &select.selection()?,
+                args.output,
+            )
+            .await?;
+            if !common {
+                return Ok(ExitCode::from(EXIT_CHANGED));
+            }
+        }
+        Some(Command::Batch { input, select }) => {
+            let text = cli::batch::read_images(input)?;
+            let (status, _) = cli::batch::run(
+                out,
+                &args.registry()?,
+                &cli::batch::parse_images(&text),
+                &select.selection()?,
+                args.output,
+                args.no_summary,
+                &[],
+            )
+            .await?;
+            return Ok(batch_exit(status));
+        }
+        Some(Command::Sort {
+            input,
+            dedup,
+            select,
+        }) => cli::sort::run(
+            out,
+            input,
+            &select.selection()?,
+            *dedup,
+            args.output,
+            args.quiet,
+        )?,
+        Some(Command::Snapshot {
+            image,
+            digests,
+            dates,
+        }) => {
+            if matches!(args.output, Format::Yaml | Format::Prom) {
+                bail!("Snapshots are JSON documents, --output json is the only other output");
+            }
+            let snapshot = cli::snapshot::take(&args.registry()?, image, *digests, *dates).await?;
+            out.document(Format::Json, &snapshot)?;
+        }
+        Some(Command::DiffSnapshots { old, new }) => {
+            let (old, new) = (Snapshot::load(old)?, Snapshot::load(new)?);
+            if old.image != new.image && !args.quiet {
+                eprintln!(
+                    "Warning: comparing snapshots of {} and {}, which are different images",
+                    old.image, new.image
+                );
+            }
+            let diff = old.diff(&new);
+            match args.output {
+                Format::Text => cli::snapshot::print_diff(out, &diff)?,
+                format => out.document(format, &diff)?,
+            }
+            if !diff.is_empty() {
+                return Ok(ExitCode::from(EXIT_CHANGED));
+            }
+        }
+        Some(Command::History { action }) => {
+            let history = History::open().context("Neither XDG_STATE_HOME nor HOME is set")?;
+            match action {
+                Some(HistoryAction::Clear) => history.clear()?,
+                Some(HistoryAction::List) | None => match args.output {
+                    Format::Text => {
+                        for entry in history.entries()? {
+                            out.line(entry)?;
+                        }
+                    }
+                    format => out.document(
+                        format,
+                        &HistoryReport {
+                            images: history.entries()?,
+                        },
+                    )?,
+                },
+            }
+        }
+        Some(Command::Sets { action }) => {
+            let mut file = args.config_file()?;
+            let count = |n| match n {
+                1 => "1 image".to_string(),
+                n => format!("{n} images"),
+            };
+            match action {
+                SetsAction::List if args.output != Format::Text => {
+                    let report = SetsReport {
+                        sets: file.config.sets.clone(),
+                    };
+                    out.document(args.output, &report)?
+                }
+                SetsAction::List => {
+                    for name in file.config.sets.keys() {
+                        out.line(name)?;
+                    }
+                }
+                SetsAction::Show { name } if args.output != Format::Text => {
+                    let report = SetsReport {
+                        sets: BTreeMap::from([(name.clone(), file.set(name)?.clone())]),
+                    };
+                    out.document(args.output, &report)?
+                }
+                SetsAction::Show { name } => {
+                    for image in &file.set(name)?.images {
+                        out.line(image)?;
+                    }
+                }
+                SetsAction::Add { name, images } => {
+                    let mut set = file.config.sets.get(name).cloned().unwrap_or_default();
+                    let added: Vec<_> = images
+                        .iter()
+                        .filter(|image| !set.images.contains(image))
+                        .cloned()
+                        .collect();
+                    set.images.extend(added.iter().cloned());
+                    file.update(name, Some(set.images))?;
+                    eprintln!(
+                        "Added {} to the set {name} in {}",
+                        count(added.len()),
+                        file.path().display()
+                    );
+                }
+                SetsAction::Remove { name, images } => {
+                    let mut set = file.set(name)?.clone();
+                    if let Some(missing) = images.iter().find(|image| !set.images.contains(image)) {
+                        bail!("{missing} isn't in the set {name}");
+                    }
+                    set.images.retain(|image| !images.contains(image));
+                    match images.is_empty() {
+                        true => {
+                            file.update(name, None)?;
+                            eprintln!("Removed the set {name} from {}", file.path().display());
+                        }
+                        false => {
+                            file.update(name, Some(set.images))?;
+                            eprintln!(
+                                "Removed {} from the set {name} in {}",
+                                count(images.len()),
+                                file.path().display()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Some(Command::Cache {
+            action: CacheAction::Clear,
+        }) => {
+            let cache = NotFoundCache::open(Duration::ZERO)
+                .context("Neither XDG_STATE_HOME nor HOME is set")?;
+            cache.clear()?;
+            #[cfg(feature = "serve")]
+            if let Some(listings) = cli::listing_cache::ListingCache::open() {
+                listings.clear()?
+            }
+        }
+        Some(Command::Version) => match args.output {
+            Format::Text => out.line(docker_tags::build_info())?,
+            format => out.document(format, &docker_tags::build_info())?,
+        },
+        Some(Command::Complete { current }) => {
+            // completion is best-effort, it must not print errors
+            let entries = History::open().and_then(|history| history.complete(current).ok());
+            for entry in entries.unwrap_or_default() {
+                out.line(entry)?;
+            }
+        }
+        None if args.set.is_some() => return print_set(args, out).await,
+        #[cfg(all(feature = "serve", unix))]
+        None if args.via_daemon => return print_via_daemon(args, out).await,
+        None if args.image.as_deref().is_some_and(cli::expand::is_pattern) => {
+            if let Some(flag) = args.single_image_flag() {
+                bail!("An image pattern doesn't go with {flag}");
+            }
+            if args.output == Format::Prom {
+                bail!("--output prom doesn't go with an image pattern");
+            }
+            cli::expand::list(
+                out,
+                &args.registry()?,
+                args.image.as_deref().unwrap_or_default(),
+                args.max_matches,
+                &args.select.selection()?,
+                args.output,
+            )
+            .await?
+        }
+        None => {
+            let registry = args.registry()?;
+            // watching keeps asking anyway
+            let cache = match (&registry.not_found, args.watch) {
+                (Some(cache), None) => registry
+                    .image(args.image.as_deref().unwrap_or_default())
+                    .ok()
+                    .map(|image| (cache, image)),
+                _ => None,
+            };
+            let result = async {
+                if let Some((cache, image)) = &cache {
+                    cache.check(image)?;
+                }
+                print_tags(args, out).await
+            }
+            .await;
+            if let Some((cache, image)) = &cache {
+                // a cache that can't be written only costs requests
+                let _ = cache.update([(image, result.as_ref().err())]);
+            }
+            let code = result?;
+            if args.records_history() {
+                // a history that can't be written isn't worth failing over
+                let _ = History::open()
+                    .map(|history| history.record(args.image.as_deref().unwrap_or_default()));
+            }
+            return Ok(code);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<_> = env::args_os().collect();
+    if args
+        .get(1)
+        .is_some_and(|arg| arg == cli::plugin::METADATA_COMMAND)
+    {
+        println!("{}", cli::plugin::metadata());
+        return ExitCode::SUCCESS;
+    }
+    let invocation = cli::plugin::invocation(args, env::var_os(cli::plugin::PLUGIN_VAR).is_some());
+    if let Some(dir) = invocation.config {
+        // SAFETY: the runtime isn't started, so no other thread reads the
+        // environment
+        unsafe { env::set_var("DOCKER_CONFIG", dir) };
     }
+    let args = Args::parse_from(invocation.args);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed building the Runtime")
+        .block_on(start(args))
+}
 
-    ExitCode::SUCCESS
+async fn start(args: Args) -> ExitCode {
+    let result = async {
+        let mut out = match &args.output_file {
+            Some(path) => Output::to_file(path, args.mkdir, args.force)?,
+            None => Output::new(),
+        };
+        // on failure the output is dropped unwritten
+        let code = run(&args, &mut out).await?;
+        out.finish().map(|()| code)
+    }
+    .await;
+    match result {
+        Ok(code) => code,
+        // the reader went away (e.g. `| head`), which isn't a failure
+        Err(err) if is_broken_pipe(&err) => ExitCode::SUCCESS,
+        // metrics have no room for an error
+        Err(err) if !matches!(args.output, Format::Text | Format::Table | Format::Prom) => {
+            let mut out = Output::new();
+            // nothing more can be reported if even the error can't be written
+            let report = ErrorReport {
+                build: Some(docker_tags::build_info()),
+                ..ErrorReport::from(&err)
+            };
+            let _ = out
+                .document(args.output, &report)
+                .and_then(|()| out.flush());
+            ExitCode::from(1)
+        }
+        Err(err) => {
+            println!("Error: {err}");
+            for (level, cause) in err.chain().skip(1).enumerate() {
+                eprintln!(
+                    "{:indent$}Caused by: {}",
+                    "",
+                    cause,
+                    indent = (level + 1) * 2
+                );
+            }
+            if let Some(hint) = cli::hint(&err) {
+                eprintln!("Hint: {hint}");
+            }
+            ExitCode::from(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        Args::command().debug_assert();
+    }
 }
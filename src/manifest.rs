@@ -0,0 +1,676 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+
+pub(crate) const DOCKER_MANIFEST: &str = "application/vnd.docker.distribution.manifest.v2+json";
+pub(crate) const DOCKER_MANIFEST_LIST: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+pub(crate) const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+pub(crate) const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+/// Legacy Docker image manifests, schema 1, unsigned and signed
+const DOCKER_SCHEMA1: &str = "application/vnd.docker.distribution.manifest.v1+json";
+const DOCKER_SCHEMA1_SIGNED: &str = "application/vnd.docker.distribution.manifest.v1+prettyjws";
+
+/// Artifact types of cosign signatures, SBOMs and attestations
+pub(crate) const COSIGN_SIGNATURE: &str = "application/vnd.dev.cosign.artifact.sig.v1+json";
+pub(crate) const COSIGN_SBOM: &str = "application/vnd.dev.cosign.artifact.sbom.v1+json";
+pub(crate) const DSSE_ENVELOPE: &str = "application/vnd.dsse.envelope.v1+json";
+/// Artifact type of signatures pushed by newer cosign versions in referrers mode
+pub(crate) const SIGSTORE_BUNDLE: &str = "application/vnd.dev.sigstore.bundle.v0.3+json";
+
+/// Media types sent in `Accept` when fetching a manifest
+pub(crate) const MANIFEST_ACCEPT: &[&str] = &[
+    OCI_INDEX,
+    DOCKER_MANIFEST_LIST,
+    OCI_MANIFEST,
+    DOCKER_MANIFEST,
+];
+
+/// Whether `body` is a legacy schema 1 manifest, by its `Content-Type` or,
+/// without a telling one, its `schemaVersion`
+pub(crate) fn is_schema1(content_type: Option<&str>, body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Probe {
+        schema_version: Option<u64>,
+    }
+
+    let media_type = content_type.map(|m| m.split(';').next().unwrap_or_default().trim());
+    match media_type {
+        Some(DOCKER_SCHEMA1 | DOCKER_SCHEMA1_SIGNED) => true,
+        Some(media_type) if MANIFEST_ACCEPT.contains(&media_type) => false,
+        _ => {
+            serde_json::from_slice::<Probe>(body).is_ok_and(|probe| probe.schema_version == Some(1))
+        }
+    }
+}
+
+/// A reference to content by digest
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    #[serde(default)]
+    pub media_type: String,
+    /// Type of an attached artifact, as listed by the referrers API
+    #[serde(default)]
+    pub artifact_type: Option<String>,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// Platform of an index entry
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    /// Whether this is `platform`, given as `os/architecture[/variant]` (e.g.
+    /// `linux/arm64`, `linux/arm/v7`); any variant matches if none is given
+    pub fn matches(&self, platform: &str) -> bool {
+        let mut parts = platform.split('/');
+        let (Some(os), Some(architecture), variant) = (parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+        parts.next().is_none()
+            && self.os == os
+            && self.architecture == architecture
+            && variant.is_none_or(|variant| self.variant.as_deref() == Some(variant))
+    }
+}
+
+/// Single-platform manifest: a config blob and layers
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageManifest {
+    #[serde(default)]
+    pub media_type: String,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// Multi-platform manifest list or OCI index
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestIndex {
+    #[serde(default)]
+    pub media_type: String,
+    pub manifests: Vec<Descriptor>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// A manifest as returned by the registry
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Manifest {
+    /// Docker image manifest v2, schema 2
+    DockerV2(ImageManifest),
+    /// OCI image manifest
+    Oci(ImageManifest),
+    /// OCI image index or Docker manifest list
+    Index(ManifestIndex),
+}
+
+impl Manifest {
+    /// Parse `body` according to `media_type` (the `Content-Type` header),
+    /// falling back to the `mediaType` field and then to the document shape
+    pub fn parse(media_type: Option<&str>, body: &[u8]) -> Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Probe {
+            #[serde(default)]
+            media_type: Option<String>,
+            #[serde(default)]
+            manifests: Option<serde_json::Value>,
+        }
+
+        let probe: Probe = serde_json::from_slice(body).context("Failed to parse manifest")?;
+        let media_type = media_type
+            .map(|m| m.split(';').next().unwrap_or_default().trim().to_string())
+            .filter(|m| MANIFEST_ACCEPT.contains(&m.as_str()))
+            .or(probe.media_type)
+            .unwrap_or_else(|| match probe.manifests {
+                Some(_) => OCI_INDEX.to_string(),
+                None => OCI_MANIFEST.to_string(),
+            });
+
+        let parse_error = || format!("Failed to parse manifest of type {media_type}");
+        Ok(match media_type.as_str() {
+            DOCKER_MANIFEST => {
+                Manifest::DockerV2(serde_json::from_slice(body).with_context(parse_error)?)
+            }
+            OCI_MANIFEST => Manifest::Oci(serde_json::from_slice(body).with_context(parse_error)?),
+            OCI_INDEX | DOCKER_MANIFEST_LIST => {
+                Manifest::Index(serde_json::from_slice(body).with_context(parse_error)?)
+            }
+            media_type => return Err(anyhow!("Unsupported manifest type: {media_type}")),
+        })
+    }
+
+    /// The image manifest, unless this is an index
+    pub fn image(&self) -> Option<&ImageManifest> {
+        match self {
+            Manifest::DockerV2(manifest) | Manifest::Oci(manifest) => Some(manifest),
+            Manifest::Index(_) => None,
+        }
+    }
+}
+
+impl ManifestIndex {
+    /// Entry for `os`/`architecture`, skipping attestations and other artifacts
+    pub fn find(&self, os: &str, architecture: &str) -> Option<&Descriptor> {
+        self.manifests.iter().find(|entry| {
+            entry
+                .platform
+                .as_ref()
+                .is_some_and(|p| p.os == os && p.architecture == architecture)
+        })
+    }
+}
+
+/// Image configuration, the parts of the config blob useful for inspection
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ImageConfig {
+    pub architecture: String,
+    pub os: String,
+    /// CPU variant, e.g. `v7` for `linux/arm/v7`
+    pub variant: Option<String>,
+    /// RFC 3339 timestamp, as written by the builder
+    pub created: Option<String>,
+    pub env: Vec<String>,
+    pub entrypoint: Vec<String>,
+    pub cmd: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl ImageConfig {
+    pub fn parse(body: &[u8]) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Raw {
+            #[serde(default)]
+            architecture: String,
+            #[serde(default)]
+            os: String,
+            #[serde(default)]
+            variant: Option<String>,
+            #[serde(default)]
+            created: Option<String>,
+            #[serde(default)]
+            config: Option<RawConfig>,
+        }
+
+        // builders write `null` for empty values
+        #[derive(Default, Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct RawConfig {
+            env: Option<Vec<String>>,
+            entrypoint: Option<Vec<String>>,
+            cmd: Option<Vec<String>>,
+            labels: Option<BTreeMap<String, String>>,
+        }
+
+        let raw: Raw = serde_json::from_slice(body).context("Failed to parse image config")?;
+        let config = raw.config.unwrap_or_default();
+        Ok(ImageConfig {
+            architecture: raw.architecture,
+            os: raw.os,
+            variant: raw.variant,
+            created: raw.created,
+            env: config.env.unwrap_or_default(),
+            entrypoint: config.entrypoint.unwrap_or_default(),
+            cmd: config.cmd.unwrap_or_default(),
+            labels: config.labels.unwrap_or_default(),
+        })
+    }
+
+    /// [`created`](Self::created) as a point in time, if it's a valid RFC 3339
+    /// timestamp
+    pub fn created_at(&self) -> Option<SystemTime> {
+        self.created.as_deref().and_then(parse_rfc3339)
+    }
+}
+
+/// Keys of the annotations, or labels, telling where an image was built from
+pub(crate) const SOURCE_ANNOTATION: &str = "org.opencontainers.image.source";
+pub(crate) const REVISION_ANNOTATION: &str = "org.opencontainers.image.revision";
+
+/// Repository and commit an image was built from, as the
+/// `org.opencontainers.image.source` and `.revision` annotations tell
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ImageSource {
+    /// E.g. `https://github.com/org/repo`
+    pub repository: Option<String>,
+    pub revision: Option<String>,
+}
+
+impl ImageSource {
+    /// Fill the parts still unknown from `annotations`
+    pub(crate) fn fill(&mut self, annotations: &BTreeMap<String, String>) {
+        let value = |key| {
+            annotations
+                .get(key)
+                .map(|value: &String| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        self.repository = self.repository.take().or_else(|| value(SOURCE_ANNOTATION));
+        self.revision = self.revision.take().or_else(|| value(REVISION_ANNOTATION));
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.repository.is_some() && self.revision.is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.repository.is_none() && self.revision.is_none()
+    }
+}
+
+/// `github.com/org/repo@abcdef1`: the repository without its scheme or
+/// `.git` suffix, and a commit SHA shortened to 7 digits; `-` when neither is
+/// known
+impl fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+        if let Some(repository) = &self.repository {
+            let repository = repository
+                .split_once("://")
+                .map_or(repository.as_str(), |(_, rest)| rest);
+            let repository = repository.strip_prefix("git@").unwrap_or(repository);
+            let repository = repository.trim_end_matches('/');
+            write!(
+                f,
+                "{}",
+                repository.strip_suffix(".git").unwrap_or(repository)
+            )?;
+        }
+        if let Some(revision) = &self.revision {
+            let is_sha = revision.len() >= 7 && revision.bytes().all(|c| c.is_ascii_hexdigit());
+            match is_sha {
+                true => write!(f, "@{}", &revision[..7])?,
+                false => write!(f, "@{revision}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse an RFC 3339 timestamp such as `2025-03-18T14:32:11.239475861Z` or
+/// `2024-10-02T01:00:00+02:00`; times before 1970 are rejected
+pub fn parse_rfc3339(timestamp: &str) -> Option<SystemTime> {
+    fn number(digits: &str, len: usize) -> Option<i64> {
+        match digits.len() == len && digits.bytes().all(|b| b.is_ascii_digit()) {
+            true => digits.parse().ok(),
+            false => None,
+        }
+    }
+
+    let (date, time) = timestamp.split_once(['T', 't', ' '])?;
+    let [year, month, day] = date.splitn(3, '-').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (year, month, day) = (number(year, 4)?, number(month, 2)?, number(day, 2)?);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => return None,
+    };
+    let offset = match offset {
+        "Z" | "z" => 0,
+        offset => {
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let (hours, minutes) = (number(hours, 2)?, number(minutes, 2)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let seconds = hours * 3600 + minutes * 60;
+            if offset.starts_with('-') {
+                -seconds
+            } else {
+                seconds
+            }
+        }
+    };
+    let (time, fraction) = match time.split_once('.') {
+        Some((_, "")) => return None,
+        Some((time, fraction)) => (time, fraction),
+        None => (time, ""),
+    };
+    let [hour, minute, second] = time.splitn(3, ':').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (hour, minute, second) = (number(hour, 2)?, number(minute, 2)?, number(second, 2)?);
+    // 60 for a leap second
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{:0<9}", &fraction[..fraction.len().min(9)])
+        .parse()
+        .ok()?;
+
+    // days since the epoch, from Howard Hinnant's `days_from_civil`
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    Some(UNIX_EPOCH + Duration::new(seconds.try_into().ok()?, nanos))
+}
+
+/// Format `at` as an RFC 3339 timestamp in UTC, such as
+/// `2025-03-18T14:32:11Z`, with as many fractional digits as it takes
+pub fn format_rfc3339(at: SystemTime) -> String {
+    let since = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (days, seconds) = (since.as_secs() / 86_400, since.as_secs() % 86_400);
+
+    // the date of days since the epoch, from Howard Hinnant's
+    // `civil_from_days`
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let fraction = match since.subsec_nanos() {
+        0 => String::new(),
+        nanos => format!(".{nanos:09}").trim_end_matches('0').to_string(),
+    };
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{fraction}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Whether `reference` is a digest (`algorithm:hex`) rather than a tag
+pub(crate) fn is_digest(reference: &str) -> bool {
+    reference.split_once(':').is_some_and(|(algorithm, hex)| {
+        !algorithm.is_empty()
+            && algorithm.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '+' | '.' | '_' | '-')
+            })
+            && !hex.is_empty()
+            && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// Artifact type of a cosign `sha256-<hex>.<kind>` tag
+pub(crate) fn cosign_artifact_type(kind: &str) -> Option<&'static str> {
+    match kind {
+        "sig" => Some(COSIGN_SIGNATURE),
+        "sbom" => Some(COSIGN_SBOM),
+        "att" => Some(DSSE_ENVELOPE),
+        _ => None,
+    }
+}
+
+pub(crate) fn sha256_digest(body: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// Check that `body` hashes to `digest`
+pub(crate) fn verify_digest(digest: &str, body: &[u8]) -> Result<()> {
+    let actual = match digest.split_once(':') {
+        Some(("sha256", _)) => sha256_digest(body),
+        Some(("sha512", _)) => format!("sha512:{:x}", Sha512::digest(body)),
+        _ => return Err(anyhow!("Unsupported digest: {digest}")),
+    };
+    match actual == digest {
+        true => Ok(()),
+        false => Err(anyhow!("Digest mismatch: expected {digest}, got {actual}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_docker_hub_index() {
+        let body = include_bytes!("../tests/fixtures/docker_hub_index.json");
+        let Manifest::Index(index) = Manifest::parse(Some(OCI_INDEX), body).unwrap() else {
+            panic!("expected an index");
+        };
+        assert_eq!(index.manifests.len(), 4);
+        let entry = index.find("linux", "arm64").unwrap();
+        assert_eq!(
+            entry.platform.as_ref().unwrap().variant.as_deref(),
+            Some("v8")
+        );
+        // attestations have an `unknown` platform
+        assert!(index.find("unknown", "unknown").is_some());
+        assert!(index.find("windows", "amd64").is_none());
+    }
+
+    #[test]
+    fn test_parse_ghcr_manifest() {
+        let body = include_bytes!("../tests/fixtures/ghcr_manifest.json");
+        let manifest = Manifest::parse(Some(OCI_MANIFEST), body).unwrap();
+        assert!(matches!(manifest, Manifest::Oci(_)));
+        let image = manifest.image().unwrap();
+        assert_eq!(image.layers.len(), 2);
+        assert_eq!(
+            image.config.media_type,
+            "application/vnd.oci.image.config.v1+json"
+        );
+    }
+
+    #[test]
+    fn test_parse_quay_manifest() {
+        // quay.io sends a charset parameter with the content type
+        let body = include_bytes!("../tests/fixtures/quay_manifest.json");
+        let content_type = format!("{DOCKER_MANIFEST}; charset=utf-8");
+        let manifest = Manifest::parse(Some(&content_type), body).unwrap();
+        assert!(matches!(manifest, Manifest::DockerV2(_)));
+
+        // and the same document without any header
+        let manifest = Manifest::parse(None, body).unwrap();
+        assert!(matches!(manifest, Manifest::DockerV2(_)));
+    }
+
+    #[test]
+    fn test_parse_unsupported() {
+        let body = br#"{"schemaVersion":1,"mediaType":"application/vnd.docker.distribution.manifest.v1+prettyjws"}"#;
+        assert_eq!(
+            Manifest::parse(None, body).unwrap_err().to_string(),
+            "Unsupported manifest type: application/vnd.docker.distribution.manifest.v1+prettyjws"
+        );
+    }
+
+    #[test]
+    fn test_is_schema1() {
+        let body = br#"{"schemaVersion":1,"name":"app","tag":"1.0","fsLayers":[]}"#;
+        assert!(is_schema1(Some(DOCKER_SCHEMA1_SIGNED), body));
+        assert!(is_schema1(Some("application/json"), body));
+        assert!(is_schema1(None, body));
+        // a listed type is taken at its word
+        assert!(!is_schema1(Some(DOCKER_MANIFEST), body));
+        assert!(!is_schema1(None, br#"{"schemaVersion":2,"manifests":[]}"#));
+        assert!(!is_schema1(None, b"not json"));
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let body = include_bytes!("../tests/fixtures/config.json");
+        let config = ImageConfig::parse(body).unwrap();
+        assert_eq!(config.architecture, "amd64");
+        assert_eq!(config.entrypoint, ["/bin/prometheus"]);
+        assert_eq!(
+            config.labels["maintainer"],
+            "The Prometheus Authors <prometheus-developers@googlegroups.com>"
+        );
+        assert_eq!(
+            config.created.as_deref(),
+            Some("2025-03-18T14:32:11.239475861Z")
+        );
+
+        let config = ImageConfig::parse(
+            br#"{"architecture":"arm64","os":"linux","config":{"Env":null,"Labels":null}}"#,
+        )
+        .unwrap();
+        assert!(config.env.is_empty() && config.labels.is_empty());
+    }
+
+    #[test]
+    fn test_platform_matches() {
+        let arm = Platform {
+            architecture: "arm".to_string(),
+            os: "linux".to_string(),
+            variant: Some("v7".to_string()),
+        };
+        assert!(arm.matches("linux/arm"));
+        assert!(arm.matches("linux/arm/v7"));
+        assert!(!arm.matches("linux/arm/v6"));
+        assert!(!arm.matches("linux/arm64"));
+        assert!(!arm.matches("windows/arm"));
+        assert!(!arm.matches("linux"));
+        assert!(!arm.matches("linux/arm/v7/extra"));
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        let at = |seconds, nanos| Some(UNIX_EPOCH + Duration::new(seconds, nanos));
+        let cases = [
+            ("1970-01-01T00:00:00Z", at(0, 0)),
+            (
+                "2025-03-18T14:32:11.239475861Z",
+                at(1_742_308_331, 239_475_861),
+            ),
+            ("2025-03-18T14:32:11.5z", at(1_742_308_331, 500_000_000)),
+            ("2025-03-18T16:32:11+02:00", at(1_742_308_331, 0)),
+            ("2025-03-18T09:02:11-05:30", at(1_742_308_331, 0)),
+            ("2024-02-29T23:00:00Z", at(1_709_247_600, 0)),
+            // Go's RFC3339Nano with more digits than nanoseconds
+            (
+                "2024-02-29T23:00:00.1234567891Z",
+                at(1_709_247_600, 123_456_789),
+            ),
+            ("2024-02-30T00:00:00Z", None),
+            ("2023-02-29T00:00:00Z", None),
+            ("2024-13-01T00:00:00Z", None),
+            ("2024-01-01T24:00:00Z", None),
+            ("2024-01-01T00:00:00", None),
+            ("2024-01-01T00:00:00.Z", None),
+            ("2024-01-01T00:00:00.x1Z", None),
+            ("2024-01-01", None),
+            ("1969-12-31T23:59:59Z", None),
+            ("", None),
+        ];
+        for (timestamp, expected) in cases {
+            assert_eq!(parse_rfc3339(timestamp), expected, "{timestamp:?}");
+        }
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        let at = |seconds, nanos| UNIX_EPOCH + Duration::new(seconds, nanos);
+        assert_eq!(format_rfc3339(at(0, 0)), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_rfc3339(at(1_742_308_331, 239_475_861)),
+            "2025-03-18T14:32:11.239475861Z"
+        );
+        assert_eq!(
+            format_rfc3339(at(1_742_308_331, 500_000_000)),
+            "2025-03-18T14:32:11.5Z"
+        );
+        assert_eq!(format_rfc3339(at(1_709_247_600, 0)), "2024-02-29T23:00:00Z");
+        assert_eq!(format_rfc3339(at(951_868_800, 0)), "2000-03-01T00:00:00Z");
+        for timestamp in [
+            "2024-12-31T23:59:59Z",
+            "2100-03-01T12:00:00.000001Z",
+            "1999-12-31T00:00:00Z",
+        ] {
+            let parsed = parse_rfc3339(timestamp).unwrap();
+            assert_eq!(format_rfc3339(parsed), timestamp);
+        }
+    }
+
+    #[test]
+    fn test_digest() {
+        assert!(is_digest(
+            "sha256:4bcff63911fcb4448bd4fdacec207030997caf25e9bea4045fa6c8c44de311d1"
+        ));
+        assert!(!is_digest("latest"));
+        assert!(!is_digest("sha256:"));
+        assert!(!is_digest("sha256:not-hex"));
+
+        let digest = "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_digest(digest, b"abc").is_ok());
+        assert!(verify_digest(digest, b"abd").is_err());
+        assert!(verify_digest("md5:900150983cd24fb0d6963f7d28e17f72", b"abc").is_err());
+    }
+
+    #[test]
+    fn test_image_source() {
+        let mut source = ImageSource::default();
+        assert_eq!(source.to_string(), "-");
+
+        let annotations = BTreeMap::from([
+            (
+                SOURCE_ANNOTATION.to_string(),
+                "https://github.com/org/repo.git".to_string(),
+            ),
+            (
+                REVISION_ANNOTATION.to_string(),
+                "abcdef1234567890abcdef1234567890abcdef12".to_string(),
+            ),
+        ]);
+        source.fill(&BTreeMap::from([(
+            SOURCE_ANNOTATION.to_string(),
+            "git@gitlab.com:group/app".to_string(),
+        )]));
+        assert!(!source.is_complete());
+        // what's known already stays
+        source.fill(&annotations);
+        assert!(source.is_complete());
+        assert_eq!(source.to_string(), "gitlab.com:group/app@abcdef1");
+
+        let mut source = ImageSource::default();
+        source.fill(&annotations);
+        assert_eq!(source.to_string(), "github.com/org/repo@abcdef1");
+        source.revision = Some("v1.2.3".to_string());
+        assert_eq!(source.to_string(), "github.com/org/repo@v1.2.3");
+        source.repository = None;
+        assert_eq!(source.to_string(), "@v1.2.3");
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::header::HeaderMap;
+
+use crate::manifest::{format_rfc3339, parse_rfc3339};
+
+/// What a registry announced about itself in the headers of its tags
+/// responses, e.g. while it's being migrated or decommissioned
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ServerNotice {
+    /// `Deprecation` (RFC 9745): the API is deprecated, since `since` if the
+    /// registry told when
+    Deprecation { since: Option<SystemTime> },
+    /// `Sunset` (RFC 8594): the API is expected to stop responding at `at`
+    Sunset { at: SystemTime },
+    /// `Warning` (RFC 7234): `code` is e.g. 299 for a persistent warning,
+    /// `agent` the host adding it or `-`
+    Warning {
+        code: u16,
+        agent: String,
+        text: String,
+        date: Option<SystemTime>,
+    },
+}
+
+impl ServerNotice {
+    /// The notices in `headers`; malformed values are left out rather than
+    /// failing the response they came with
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Vec<ServerNotice> {
+        let values = |name| {
+            headers
+                .get_all(name)
+                .into_iter()
+                .filter_map(|value| value.to_str().ok())
+        };
+        let deprecations = values("deprecation").filter_map(parse_deprecation);
+        let sunsets = values("sunset")
+            .filter_map(parse_http_date)
+            .map(|at| ServerNotice::Sunset { at });
+        let warnings = values("warning").flat_map(parse_warnings);
+        deprecations.chain(sunsets).chain(warnings).collect()
+    }
+}
+
+impl fmt::Display for ServerNotice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerNotice::Deprecation { since: None } => write!(f, "the registry is deprecated"),
+            ServerNotice::Deprecation { since: Some(since) } => write!(
+                f,
+                "the registry is deprecated since {}",
+                format_rfc3339(*since)
+            ),
+            ServerNotice::Sunset { at } => {
+                write!(f, "the registry is going away at {}", format_rfc3339(*at))
+            }
+            ServerNotice::Warning {
+                code, agent, text, ..
+            } => match agent.as_str() {
+                "-" => write!(f, "the registry warns ({code}): {text}"),
+                agent => write!(f, "{agent} warns ({code}): {text}"),
+            },
+        }
+    }
+}
+
+/// A `Deprecation` value: a structured field date such as `@1688169599`, or
+/// as earlier drafts had it, an HTTP-date or `true`
+fn parse_deprecation(value: &str) -> Option<ServerNotice> {
+    let value = value.trim();
+    let since = match value.strip_prefix('@') {
+        Some(seconds) if seconds.bytes().all(|b| b.is_ascii_digit()) => {
+            Some(UNIX_EPOCH + Duration::from_secs(seconds.parse().ok()?))
+        }
+        Some(_) => return None,
+        None if value == "true" => None,
+        None => Some(parse_http_date(value)?),
+    };
+    Some(ServerNotice::Deprecation { since })
+}
+
+/// Parse an HTTP-date: `Sun, 06 Nov 1994 08:49:37 GMT`, or one of the
+/// obsolete forms recipients must still accept, `Sunday, 06-Nov-94 08:49:37
+/// GMT` and `Sun Nov  6 08:49:37 1994`
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    fn number(digits: &str, len: std::ops::RangeInclusive<usize>) -> Option<i64> {
+        match len.contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit()) {
+            true => digits.parse().ok(),
+            false => None,
+        }
+    }
+
+    let parts: Vec<_> = value.split_whitespace().collect();
+    let (day, month, year, time) = match parts[..] {
+        [weekday, day, month, year, time, "GMT"] if weekday.ends_with(',') => {
+            (number(day, 1..=2)?, month, number(year, 4..=4)?, time)
+        }
+        [weekday, date, time, "GMT"] if weekday.ends_with(',') => {
+            let [day, month, year] = date.splitn(3, '-').collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            // two-digit years of the 1900s as in RFC 850, or 2000s
+            let year = match number(year, 2..=2)? {
+                year @ 0..70 => 2000 + year,
+                year => 1900 + year,
+            };
+            (number(day, 1..=2)?, month, year, time)
+        }
+        [_, month, day, time, year] => (number(day, 1..=2)?, month, number(year, 4..=4)?, time),
+        _ => return None,
+    };
+    let month = MONTHS.iter().position(|name| *name == month)? + 1;
+    parse_rfc3339(&format!("{year:04}-{month:02}-{day:02}T{time}Z"))
+}
+
+/// The `Warning` values of a header, `299 - "text"` with an optional quoted
+/// date, separated by commas; parsing stops at the first malformed one
+fn parse_warnings(header: &str) -> Vec<ServerNotice> {
+    let mut notices = Vec::new();
+    let mut rest = header;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return notices;
+        }
+        match parse_warning(rest) {
+            Some((notice, tail)) => {
+                notices.push(notice);
+                rest = tail;
+            }
+            None => return notices,
+        }
+    }
+}
+
+/// A `Warning` value at the start of `value` and what follows it, which
+/// must be the end of the header or a comma
+fn parse_warning(value: &str) -> Option<(ServerNotice, &str)> {
+    let (code, rest) = value.split_once(' ')?;
+    if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (agent, rest) = rest.split_once(' ')?;
+    if agent.is_empty() {
+        return None;
+    }
+    let (text, rest) = quoted_string(rest)?;
+    let (date, rest) = match rest.strip_prefix(' ') {
+        Some(tail) if tail.starts_with('"') => {
+            let (date, rest) = quoted_string(tail)?;
+            (Some(parse_http_date(&date)?), rest)
+        }
+        _ => (None, rest),
+    };
+    if !rest.trim_start().is_empty() && !rest.trim_start().starts_with(',') {
+        return None;
+    }
+    let notice = ServerNotice::Warning {
+        code: code.parse().ok()?,
+        agent: agent.to_string(),
+        text,
+        date,
+    };
+    Some((notice, rest))
+}
+
+/// The unescaped content of the quoted string `value` starts with, and what
+/// follows it
+fn quoted_string(value: &str) -> Option<(String, &str)> {
+    let mut chars = value.strip_prefix('"')?.char_indices();
+    let mut text = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((text, &value[i + 2..])),
+            '\\' => text.push(chars.next()?.1),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        for date in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(parse_http_date(date), Some(at(784111777)), "{date}");
+        }
+        assert_eq!(
+            parse_http_date("Wed, 31 Dec 2025 23:59:59 GMT"),
+            parse_rfc3339("2025-12-31T23:59:59Z")
+        );
+        for date in [
+            "",
+            "tomorrow",
+            "2025-12-31T23:59:59Z",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 31 Feb 1994 08:49:37 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun 06 Nov 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(parse_http_date(date), None, "{date}");
+        }
+    }
+
+    #[test]
+    fn test_parse_deprecation() {
+        assert_eq!(
+            parse_deprecation("@1688169599"),
+            Some(ServerNotice::Deprecation {
+                since: Some(at(1688169599))
+            })
+        );
+        assert_eq!(
+            parse_deprecation("true"),
+            Some(ServerNotice::Deprecation { since: None })
+        );
+        assert_eq!(
+            parse_deprecation("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(ServerNotice::Deprecation {
+                since: Some(at(784111777))
+            })
+        );
+        for value in ["", "@", "@-1", "@12.5", "yes", "?1"] {
+            assert_eq!(parse_deprecation(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        assert_eq!(
+            parse_warnings(
+                r#"299 - "Moving to \"harbor.new\"", 199 old.corp:443 "read-only" "Sun, 06 Nov 1994 08:49:37 GMT""#
+            ),
+            [
+                ServerNotice::Warning {
+                    code: 299,
+                    agent: "-".to_string(),
+                    text: r#"Moving to "harbor.new""#.to_string(),
+                    date: None,
+                },
+                ServerNotice::Warning {
+                    code: 199,
+                    agent: "old.corp:443".to_string(),
+                    text: "read-only".to_string(),
+                    date: Some(at(784111777)),
+                },
+            ]
+        );
+        // what precedes a malformed value is kept
+        assert_eq!(
+            parse_warnings(r#"299 - "kept", 99 - "short code", 299 - "dropped""#).len(),
+            1
+        );
+        for header in [
+            "",
+            "299",
+            "299 -",
+            "299 - unquoted",
+            r#"299 - "unterminated"#,
+            r#"2x9 - "text""#,
+            r#"299 - "text" "not a date""#,
+            r#"299 - "text" trailing"#,
+        ] {
+            assert_eq!(parse_warnings(header), [], "{header}");
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Spans for registry requests, named and filled in after OpenTelemetry's
+//! semantic conventions for HTTP clients so that `tracing-opentelemetry`
+//! exports them as such
+
+use reqwest::{Request, Response};
+use tracing::{Span, field::Empty};
+
+use crate::secret::redact_url;
+
+/// Span of `req`, a child of the current one, sent again for the
+/// `resends`-th time after an authentication challenge
+pub(crate) fn request_span(req: &Request, resends: u32) -> Span {
+    let url = req.url();
+    let method = req.method().as_str();
+    let span = tracing::info_span!(
+        "HTTP request",
+        otel.name = method,
+        otel.kind = "client",
+        otel.status_code = Empty,
+        http.request.method = method,
+        http.request.resend_count = Empty,
+        http.response.status_code = Empty,
+        url.full = %redact_url(url),
+        server.address = url.host_str().unwrap_or_default(),
+        server.port = url.port_or_known_default(),
+        error.type = Empty,
+    );
+    if resends > 0 {
+        span.record("http.request.resend_count", resends);
+    }
+    span
+}
+
+/// Record on `span` how its request turned out
+pub(crate) fn record_response(span: &Span, resp: &reqwest::Result<Response>) {
+    // error statuses, 4xx included, fail the span of a client
+    let error = match resp {
+        Ok(resp) => {
+            let status = resp.status();
+            span.record("http.response.status_code", status.as_u16());
+            (status.is_client_error() || status.is_server_error())
+                .then(|| status.as_str().to_string())
+        }
+        Err(err) if err.is_timeout() => Some("timeout".to_string()),
+        Err(err) if err.is_connect() => Some("connect".to_string()),
+        Err(_) => Some("_OTHER".to_string()),
+    };
+    if let Some(error) = error {
+        span.record("error.type", error.as_str());
+        span.record("otel.status_code", "ERROR");
+    }
+}
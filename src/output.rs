@@ -0,0 +1,926 @@
+//! Listings of tags once they're fetched: the [`Pipeline`] choosing and
+//! ordering them, the [`Listing`] of those chosen with what was looked up
+//! about each, and the [`Formatter`]s writing it to any [`Write`]
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::Write as _,
+    io::Write,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+
+use crate::{
+    Aliases, Selection, SeriesLevel, SeriesTag, ServerNotice, Tag, TagFilter, Timings, Warning,
+    latest_per_series, manifest::format_rfc3339,
+};
+
+/// Order of the tags of a [`Pipeline`], before its [`Direction`]
+#[derive(Clone, Debug, Default)]
+pub enum Sort {
+    /// Newest version first, see [`sort_tags_desc`](crate::sort_tags_desc)
+    #[default]
+    Version,
+    /// As the registry listed them
+    Registry,
+    /// The newest stable tag of each version series, newest series first,
+    /// see [`latest_per_series`]
+    LatestPer {
+        level: SeriesLevel,
+        per_variant: bool,
+    },
+    /// Most recently first seen first, going by these times, those seen at
+    /// once or at unknown times in the registry's order, the latter last
+    FirstSeen(HashMap<String, SystemTime>),
+}
+
+/// Whether a [`Pipeline`] keeps its [`Sort`] or turns it around
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reversed,
+}
+
+/// Stages of a listing between fetching the tags and writing them: the
+/// filters, the order, its direction, the limit, and what becomes of alias
+/// tags such as `latest`
+///
+/// Unlike a [`Selection`], which is also used while fetching, it only
+/// arranges tags already fetched.
+#[derive(Clone, Debug, Default)]
+pub struct Pipeline {
+    /// Tags failing any of these are left out
+    pub filters: Vec<TagFilter>,
+    pub sort: Sort,
+    pub direction: Direction,
+    /// At most this many tags are kept, pinned aliases not counted
+    pub limit: Option<usize>,
+    pub aliases: Aliases,
+}
+
+impl Pipeline {
+    /// The stages of `selection`, sorting by `sort` unless it keeps the
+    /// registry's order
+    pub fn new(selection: &Selection, sort: Sort) -> Self {
+        let mut filters = Vec::new();
+        #[cfg(feature = "regex")]
+        filters.extend(selection.pattern.clone().map(TagFilter::pattern));
+        filters.extend(selection.filters.iter().cloned());
+        Pipeline {
+            filters,
+            sort: match selection.keep_order {
+                true => Sort::Registry,
+                false => sort,
+            },
+            direction: match selection.reverse {
+                true => Direction::Reversed,
+                false => Direction::Forward,
+            },
+            limit: selection.limit,
+            aliases: selection.aliases.clone(),
+        }
+    }
+
+    /// The aliases [`Aliases::Pinned`] lists first, then the other tags
+    pub fn apply(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        let (aliases, tags) = self.split(tags);
+        aliases
+            .into_iter()
+            .chain(tags)
+            .map(|entry| Tag::new(entry.name))
+            .collect()
+    }
+
+    /// The aliases [`Aliases::Pinned`] lists first, and the other tags
+    /// filtered, sorted, turned around and limited, as entries for a
+    /// [`Listing`]
+    pub fn split(&self, tags: Vec<Tag>) -> (Vec<TagEntry>, Vec<TagEntry>) {
+        let (aliases, tags) = self.selection().split_aliases(tags);
+        let aliases = aliases.into_iter().map(TagEntry::from).collect();
+        (aliases, self.entries(tags))
+    }
+
+    /// `tags`, from which pinned aliases were taken out, filtered, sorted,
+    /// turned around and limited
+    pub fn entries(&self, tags: Vec<Tag>) -> Vec<TagEntry> {
+        let selection = self.selection();
+        let filtering = Selection {
+            keep_order: !matches!(self.sort, Sort::Version | Sort::LatestPer { .. }),
+            ..selection.filtering()
+        };
+        let tags = filtering.apply(tags);
+        match &self.sort {
+            Sort::Version | Sort::Registry => selection
+                .arrange(tags)
+                .into_iter()
+                .map(TagEntry::from)
+                .collect(),
+            Sort::LatestPer { level, per_variant } => selection
+                .arrange(latest_per_series(&tags, *level, *per_variant))
+                .into_iter()
+                .map(TagEntry::from)
+                .collect(),
+            Sort::FirstSeen(first_seen) => {
+                let mut tags = tags;
+                tags.sort_by_key(|tag| {
+                    let at = first_seen.get(tag.name()).copied();
+                    (at.is_none(), Reverse(at))
+                });
+                selection
+                    .arrange(tags)
+                    .into_iter()
+                    .map(TagEntry::from)
+                    .collect()
+            }
+        }
+    }
+
+    /// The same stages as a [`Selection`], keeping the registry's order
+    /// unless sorting by version
+    pub fn selection(&self) -> Selection {
+        Selection {
+            reverse: self.direction == Direction::Reversed,
+            keep_order: !matches!(self.sort, Sort::Version),
+            // the pattern is one of the filters
+            #[cfg(feature = "regex")]
+            pattern: None,
+            filters: self.filters.clone(),
+            limit: self.limit,
+            aliases: self.aliases.clone(),
+        }
+    }
+}
+
+/// Tags listed for an image (or read from stdin), with what was looked up
+/// about each
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    pub tags: Vec<TagEntry>,
+    pub warnings: Vec<String>,
+    /// What the registry announced about itself, e.g. that it's going away
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notices: Vec<NoticeEntry>,
+    /// False when what was fetched before a failure is listed
+    pub complete: bool,
+    /// Tags fetched, those matching the filter (all of them without one) and
+    /// those listed
+    pub total: usize,
+    pub matched: usize,
+    pub shown: usize,
+    /// Requests sent and time spent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<TimingsReport>,
+    /// Tags the registry has which an ignore list left out
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignored: Vec<String>,
+    /// Alias tags listed apart from the others, see [`Aliases::Pinned`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<TagEntry>,
+}
+
+impl Listing {
+    pub fn new(image: Option<String>, tags: Vec<TagEntry>, warnings: &[Warning]) -> Self {
+        let shown = tags.len();
+        Listing {
+            image,
+            tags,
+            warnings: warnings.iter().map(Warning::to_string).collect(),
+            notices: Vec::new(),
+            complete: true,
+            total: shown,
+            matched: shown,
+            shown,
+            timings: None,
+            ignored: Vec::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    /// `15234 tags total, 412 matched filter, showing 20`, without the
+    /// matches unless `filtered`
+    pub fn summary(&self, filtered: bool) -> String {
+        match filtered {
+            true => format!(
+                "{} tags total, {} matched filter, showing {}",
+                self.total, self.matched, self.shown
+            ),
+            false => format!("{} tags total, showing {}", self.total, self.shown),
+        }
+    }
+
+    /// The aliases, then the other tags
+    fn entries(&self) -> impl Iterator<Item = &TagEntry> {
+        self.aliases.iter().chain(&self.tags)
+    }
+}
+
+/// A tag of a [`Listing`], and what was looked up about it: `None` where
+/// nothing was
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TagEntry {
+    pub name: String,
+    /// Whether a signature is attached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed: Option<bool>,
+    /// Version series the tag is the newest of, see [`Sort::LatestPer`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<String>,
+    /// Digest of the tag's manifest; `null` if it couldn't be resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<Option<String>>,
+    /// Repository and commit the tag was built from, like
+    /// `github.com/org/repo@abcdef1`; `null` if unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Option<String>>,
+    /// Whether the tag can't be overwritten; `null` where the registry
+    /// doesn't tell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub immutable: Option<Option<bool>>,
+}
+
+impl From<Tag> for TagEntry {
+    fn from(tag: Tag) -> Self {
+        TagEntry {
+            name: tag.name().to_string(),
+            signed: None,
+            series: None,
+            digest: None,
+            source: None,
+            immutable: None,
+        }
+    }
+}
+
+impl From<SeriesTag> for TagEntry {
+    fn from(latest: SeriesTag) -> Self {
+        TagEntry {
+            series: Some(latest.series),
+            ..latest.tag.into()
+        }
+    }
+}
+
+/// A [`ServerNotice`], with its time in RFC 3339: when the API was
+/// deprecated, goes away, or the warning was given
+#[derive(Debug, Serialize)]
+pub struct NoticeEntry {
+    /// `deprecation`, `sunset` or `warning`
+    pub kind: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+}
+
+impl From<&ServerNotice> for NoticeEntry {
+    fn from(notice: &ServerNotice) -> Self {
+        let (kind, at, code, agent) = match notice {
+            ServerNotice::Deprecation { since } => ("deprecation", *since, None, None),
+            ServerNotice::Sunset { at } => ("sunset", Some(*at), None, None),
+            ServerNotice::Warning {
+                code, agent, date, ..
+            } => ("warning", *date, Some(*code), Some(agent.clone())),
+        };
+        NoticeEntry {
+            kind,
+            message: notice.to_string(),
+            at: at.map(format_rfc3339),
+            code,
+            agent,
+        }
+    }
+}
+
+/// Requests sent for a listing by what they were for, and the wall time of
+/// each phase in milliseconds
+#[derive(Debug, Serialize)]
+pub struct TimingsReport {
+    pub requests: RequestCounts,
+    pub bytes_received: u64,
+    pub retries: usize,
+    pub auth_ms: f64,
+    pub pagination_ms: f64,
+    pub enrichment_ms: f64,
+    pub sorting_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestCounts {
+    pub token: usize,
+    pub tags: usize,
+    pub manifest: usize,
+    pub blob: usize,
+    pub other: usize,
+}
+
+impl TimingsReport {
+    /// Summarize `timings`, `sorting` being the time spent selecting and
+    /// ordering the tags
+    pub fn new(timings: &Timings, sorting: Duration) -> Self {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        TimingsReport {
+            requests: RequestCounts {
+                token: timings.token_requests,
+                tags: timings.tags_requests,
+                manifest: timings.manifest_requests,
+                blob: timings.blob_requests,
+                other: timings.other_requests,
+            },
+            bytes_received: timings.bytes_received,
+            retries: timings.retries,
+            auth_ms: ms(timings.auth),
+            pagination_ms: ms(timings.pagination),
+            enrichment_ms: ms(timings.enrichment),
+            sorting_ms: ms(sorting),
+        }
+    }
+
+    /// Lines to print after a text listing
+    pub fn lines(&self) -> Vec<String> {
+        let requests = &self.requests;
+        let total =
+            requests.token + requests.tags + requests.manifest + requests.blob + requests.other;
+        vec![
+            format!(
+                "Timings: {total} requests (token {}, tags {}, manifest {}, blob {}, other {}), \
+                 {} bytes received, {} retries",
+                requests.token,
+                requests.tags,
+                requests.manifest,
+                requests.blob,
+                requests.other,
+                self.bytes_received,
+                self.retries
+            ),
+            format!(
+                "Timings: auth {:.1}ms, pagination {:.1}ms, enrichment {:.1}ms, sorting {:.1}ms",
+                self.auth_ms, self.pagination_ms, self.enrichment_ms, self.sorting_ms
+            ),
+        ]
+    }
+}
+
+/// Writes a listing whose tags are final: chosen, ordered, limited and
+/// looked up, so formatting is all that's left
+pub trait Formatter {
+    fn write(&self, w: &mut dyn Write, listing: &Listing) -> Result<()>;
+}
+
+/// One tag per line, the aliases first and apart from the others, followed
+/// by the columns looked up: signed/unsigned (`show_signed`), digest, source
+/// and immutable/mutable
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Plain {
+    pub show_signed: bool,
+}
+
+/// The tags and the columns looked up under a header, aligned with spaces,
+/// as `docker images` prints them
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Table {
+    pub show_signed: bool,
+}
+
+/// A line per tag filled in from a template, see [`Template::parse`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template(Vec<Piece>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+/// What a [`Template`] or a [`Table`] column shows of a tag
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Field {
+    Name,
+    Signed,
+    Series,
+    Digest,
+    Source,
+    Immutable,
+}
+
+/// The whole listing as a JSON document
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+/// The whole listing as a YAML document
+#[cfg(feature = "yaml")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Yaml;
+
+impl Field {
+    const ALL: [Field; 6] = [
+        Field::Name,
+        Field::Signed,
+        Field::Series,
+        Field::Digest,
+        Field::Source,
+        Field::Immutable,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Name => "name",
+            Field::Signed => "signed",
+            Field::Series => "series",
+            Field::Digest => "digest",
+            Field::Source => "source",
+            Field::Immutable => "immutable",
+        }
+    }
+
+    /// The field of `entry`, `-` where nothing was found
+    fn value(self, entry: &TagEntry) -> String {
+        let flag = |value: Option<bool>, yes: &str, no: &str| match value {
+            Some(true) => yes.to_string(),
+            Some(false) => no.to_string(),
+            None => "-".to_string(),
+        };
+        match self {
+            Field::Name => entry.name.clone(),
+            Field::Signed => flag(entry.signed, "signed", "unsigned"),
+            Field::Series => entry.series.clone().unwrap_or_else(|| "-".to_string()),
+            Field::Digest => entry
+                .digest
+                .clone()
+                .flatten()
+                .unwrap_or_else(|| "-".to_string()),
+            Field::Source => entry
+                .source
+                .clone()
+                .flatten()
+                .unwrap_or_else(|| "-".to_string()),
+            Field::Immutable => flag(entry.immutable.flatten(), "immutable", "mutable"),
+        }
+    }
+
+    /// Whether `entry` had the field looked up
+    fn is_looked_up(self, entry: &TagEntry) -> bool {
+        match self {
+            Field::Name => true,
+            Field::Signed => entry.signed.is_some(),
+            Field::Series => entry.series.is_some(),
+            Field::Digest => entry.digest.is_some(),
+            Field::Source => entry.source.is_some(),
+            Field::Immutable => entry.immutable.is_some(),
+        }
+    }
+}
+
+impl Formatter for Plain {
+    fn write(&self, w: &mut dyn Write, listing: &Listing) -> Result<()> {
+        for entry in &listing.aliases {
+            writeln!(w, "{}", self.row(entry))?;
+        }
+        if !listing.aliases.is_empty() && !listing.tags.is_empty() {
+            writeln!(w)?;
+        }
+        for entry in &listing.tags {
+            writeln!(w, "{}", self.row(entry))?;
+        }
+        Ok(())
+    }
+}
+
+impl Plain {
+    fn row(&self, entry: &TagEntry) -> String {
+        let mut row = entry.name.clone();
+        if self.show_signed {
+            row = format!("{row}\t{}", Field::Signed.value(entry));
+        }
+        for field in [Field::Digest, Field::Source, Field::Immutable] {
+            if field.is_looked_up(entry) {
+                row = format!("{row}\t{}", field.value(entry));
+            }
+        }
+        row
+    }
+}
+
+impl Formatter for Table {
+    fn write(&self, w: &mut dyn Write, listing: &Listing) -> Result<()> {
+        // the columns looked up for any tag, which `-` fills in for the others
+        let columns: Vec<_> = Field::ALL
+            .into_iter()
+            .filter(|&field| match field {
+                Field::Name => true,
+                Field::Signed => self.show_signed,
+                field => listing.entries().any(|entry| field.is_looked_up(entry)),
+            })
+            .collect();
+        let header = columns.iter().map(|field| field.name().to_uppercase());
+        let mut rows = vec![header.collect::<Vec<_>>()];
+        rows.extend(
+            listing
+                .entries()
+                .map(|entry| columns.iter().map(|field| field.value(entry)).collect()),
+        );
+        let widths: Vec<_> = (0..columns.len())
+            .map(|i| rows.iter().map(|row| row[i].chars().count()).max())
+            .map(Option::unwrap_or_default)
+            .collect();
+        for row in rows {
+            let mut line = String::new();
+            for (i, cell) in row.iter().enumerate() {
+                match i + 1 == row.len() {
+                    true => line.push_str(cell),
+                    false => write!(line, "{cell:<width$}   ", width = widths[i])?,
+                }
+            }
+            writeln!(w, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Template {
+    /// Read `template`, in which `{name}`, `{signed}`, `{series}`,
+    /// `{digest}`, `{source}` and `{immutable}` stand for what there is of
+    /// each tag, `-` for what wasn't looked up or found; `{{`, `}}`, `\t`
+    /// and `\n` write `{`, `}`, a tab and a newline
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '\\' if chars.as_str().starts_with(['t', 'n']) => {
+                    text.push(match chars.next() {
+                        Some('t') => '\t',
+                        _ => '\n',
+                    });
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        bail!("unclosed `{{` in the template");
+                    };
+                    let Some(field) = Field::ALL.into_iter().find(|field| field.name() == name)
+                    else {
+                        let names: Vec<_> = Field::ALL.iter().map(|field| field.name()).collect();
+                        bail!(
+                            "unknown field {{{name}}} in the template, expected one of {}",
+                            names.join(", ")
+                        );
+                    };
+                    chars = rest.chars();
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => bail!("unmatched `}}` in the template, write `}}}}` for one"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template(pieces))
+    }
+
+    fn render(&self, entry: &TagEntry) -> String {
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(field) => field.value(entry),
+            })
+            .collect()
+    }
+}
+
+impl Formatter for Template {
+    fn write(&self, w: &mut dyn Write, listing: &Listing) -> Result<()> {
+        for entry in listing.entries() {
+            writeln!(w, "{}", self.render(entry))?;
+        }
+        Ok(())
+    }
+}
+
+impl Formatter for Json {
+    fn write(&self, w: &mut dyn Write, listing: &Listing) -> Result<()> {
+        serde_json::to_writer_pretty(&mut *w, listing)?;
+        writeln!(w)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl Formatter for Yaml {
+    fn write(&self, w: &mut dyn Write, listing: &Listing) -> Result<()> {
+        writeln!(w, "{}", serde_yaml_ng::to_string(listing)?.trim_end())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Channels;
+
+    fn tags(names: &[&str]) -> Vec<Tag> {
+        names.iter().map(|name| Tag::new(*name)).collect()
+    }
+
+    fn names(entries: &[TagEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    fn entry(name: &str) -> TagEntry {
+        Tag::new(name).into()
+    }
+
+    fn write(formatter: &dyn Formatter, listing: &Listing) -> String {
+        let mut buf = Vec::new();
+        formatter.write(&mut buf, listing).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    const LISTED: [&str; 7] = [
+        "1.26.0",
+        "latest",
+        "1.27.2",
+        "1.27.0-rc1",
+        "1.26.3",
+        "1.27.1",
+        "stable",
+    ];
+
+    #[test]
+    fn test_pipeline_stages() {
+        let pipeline = Pipeline::default();
+        assert_eq!(
+            pipeline.apply(tags(&LISTED)),
+            tags(&[
+                "1.27.2",
+                "1.27.1",
+                "1.27.0-rc1",
+                "1.26.3",
+                "1.26.0",
+                "latest",
+                "stable"
+            ])
+        );
+
+        let pipeline = Pipeline {
+            filters: vec![TagFilter::stable(), TagFilter::prefix("1.2", false)],
+            direction: Direction::Reversed,
+            limit: Some(3),
+            ..Pipeline::default()
+        };
+        assert_eq!(
+            pipeline.apply(tags(&LISTED)),
+            tags(&["1.26.0", "1.26.3", "1.27.1"])
+        );
+
+        let pipeline = Pipeline {
+            sort: Sort::Registry,
+            limit: Some(2),
+            aliases: Aliases::pinned(),
+            ..Pipeline::default()
+        };
+        let (aliases, entries) = pipeline.split(tags(&LISTED));
+        assert_eq!(names(&aliases), ["latest", "stable"]);
+        assert_eq!(names(&entries), ["1.26.0", "1.27.2"]);
+
+        let pipeline = Pipeline {
+            filters: vec![TagFilter::no_channels(Channels::default())],
+            aliases: Aliases::hidden(),
+            ..Pipeline::default()
+        };
+        assert_eq!(
+            pipeline.apply(tags(&["latest", "1.0", "edge"])),
+            tags(&["1.0"])
+        );
+    }
+
+    #[test]
+    fn test_pipeline_latest_per() {
+        let pipeline = Pipeline {
+            sort: Sort::LatestPer {
+                level: SeriesLevel::Minor,
+                per_variant: false,
+            },
+            direction: Direction::Reversed,
+            ..Pipeline::default()
+        };
+        let entries = pipeline.entries(tags(&LISTED));
+        assert_eq!(names(&entries), ["1.26.3", "1.27.2"]);
+        assert_eq!(entries[1].series.as_deref(), Some("1.27"));
+
+        // a selection keeping the registry's order has the last word
+        let selection = Selection {
+            keep_order: true,
+            ..Selection::default()
+        };
+        let pipeline = Pipeline::new(&selection, Sort::Version);
+        assert!(matches!(pipeline.sort, Sort::Registry));
+    }
+
+    #[test]
+    fn test_pipeline_first_seen() {
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let first_seen = HashMap::from([
+            ("1.0".to_string(), at(100)),
+            ("1.1".to_string(), at(200)),
+            ("1.2".to_string(), at(200)),
+        ]);
+        let pipeline = Pipeline {
+            sort: Sort::FirstSeen(first_seen),
+            ..Pipeline::default()
+        };
+        assert_eq!(
+            pipeline.apply(tags(&["latest", "1.2", "1.0", "1.1", "unknown"])),
+            tags(&["1.2", "1.1", "1.0", "latest", "unknown"])
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut listing = Listing::new(None, vec![entry("1.1.0"), entry("latest")], &[]);
+        (listing.total, listing.matched) = (5, 3);
+        assert_eq!(
+            listing.summary(true),
+            "5 tags total, 3 matched filter, showing 2"
+        );
+        assert_eq!(listing.summary(false), "5 tags total, showing 2");
+    }
+
+    #[test]
+    fn test_timings_lines() {
+        let timings = Timings {
+            token_requests: 1,
+            tags_requests: 4,
+            other_requests: 1,
+            bytes_received: 2154,
+            retries: 1,
+            auth: Duration::from_micros(12_340),
+            pagination: Duration::from_millis(80),
+            ..Timings::default()
+        };
+        assert_eq!(
+            TimingsReport::new(&timings, Duration::from_micros(300)).lines(),
+            [
+                "Timings: 6 requests (token 1, tags 4, manifest 0, blob 0, other 1), \
+                 2154 bytes received, 1 retries",
+                "Timings: auth 12.3ms, pagination 80.0ms, enrichment 0.0ms, sorting 0.3ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain() {
+        let mut listing = Listing::new(None, vec![entry("1.1.0"), entry("1.0.0")], &[]);
+        assert_eq!(write(&Plain::default(), &listing), "1.1.0\n1.0.0\n");
+
+        listing.aliases = vec![entry("latest")];
+        assert_eq!(
+            write(&Plain::default(), &listing),
+            "latest\n\n1.1.0\n1.0.0\n"
+        );
+        listing.tags.clear();
+        assert_eq!(write(&Plain::default(), &listing), "latest\n");
+    }
+
+    fn looked_up() -> Listing {
+        let tags = vec![
+            TagEntry {
+                signed: Some(true),
+                digest: Some(Some("sha256:abc".to_string())),
+                ..entry("1.1.0")
+            },
+            TagEntry {
+                signed: Some(false),
+                digest: Some(None),
+                source: Some(Some("github.com/org/app@abcdef1".to_string())),
+                ..entry("1.0.0")
+            },
+        ];
+        Listing::new(None, tags, &[])
+    }
+
+    #[test]
+    fn test_plain_columns() {
+        let listing = looked_up();
+        assert_eq!(
+            write(&Plain { show_signed: true }, &listing),
+            "1.1.0\tsigned\tsha256:abc\n1.0.0\tunsigned\t-\tgithub.com/org/app@abcdef1\n"
+        );
+        let tags = vec![
+            TagEntry {
+                immutable: Some(Some(true)),
+                ..entry("1.1.0")
+            },
+            TagEntry {
+                immutable: Some(Some(false)),
+                ..entry("1.0.0")
+            },
+            TagEntry {
+                immutable: Some(None),
+                ..entry("0.9.0")
+            },
+        ];
+        assert_eq!(
+            write(&Plain::default(), &Listing::new(None, tags, &[])),
+            "1.1.0\timmutable\n1.0.0\tmutable\n0.9.0\t-\n"
+        );
+        // only signed ones listed, the column left out
+        assert_eq!(
+            write(&Plain::default(), &listing),
+            "1.1.0\tsha256:abc\n1.0.0\t-\tgithub.com/org/app@abcdef1\n"
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let mut listing = looked_up();
+        assert_eq!(
+            write(&Table { show_signed: true }, &listing),
+            "\
+NAME    SIGNED     DIGEST       SOURCE
+1.1.0   signed     sha256:abc   -
+1.0.0   unsigned   -            github.com/org/app@abcdef1
+"
+        );
+        listing.aliases = vec![entry("latest")];
+        assert_eq!(
+            write(&Table::default(), &listing),
+            "\
+NAME     DIGEST       SOURCE
+latest   -            -
+1.1.0    sha256:abc   -
+1.0.0    -            github.com/org/app@abcdef1
+"
+        );
+        assert_eq!(
+            write(&Table::default(), &Listing::new(None, Vec::new(), &[])),
+            "NAME\n"
+        );
+    }
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse(r"{name}\t{digest} {{{signed}}}").unwrap();
+        assert_eq!(
+            write(&template, &looked_up()),
+            "1.1.0\tsha256:abc {signed}\n1.0.0\t- {unsigned}\n"
+        );
+        let mut listing = Listing::new(None, vec![entry("1.0")], &[]);
+        listing.aliases = vec![entry("latest")];
+        let template = Template::parse("{name}: {series}").unwrap();
+        assert_eq!(write(&template, &listing), "latest: -\n1.0: -\n");
+
+        for (template, message) in [
+            (
+                "{tag}",
+                "unknown field {tag} in the template, expected one of name,",
+            ),
+            ("{name", "unclosed `{`"),
+            ("name}", "unmatched `}`"),
+        ] {
+            let err = Template::parse(template).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_documents() {
+        let mut listing = Listing::new(Some("alpine".to_string()), vec![entry("3.21")], &[]);
+        listing.aliases = vec![entry("latest")];
+        let json: serde_json::Value = serde_json::from_str(&write(&Json, &listing)).unwrap();
+        assert_eq!(json["image"], "alpine");
+        assert_eq!(json["tags"], serde_json::json!([{"name": "3.21"}]));
+        assert_eq!(json["aliases"], serde_json::json!([{"name": "latest"}]));
+
+        #[cfg(feature = "yaml")]
+        {
+            let yaml = write(&Yaml, &listing);
+            assert!(
+                yaml.starts_with("image: alpine\ntags:\n- name: '3.21'\n"),
+                "{yaml}"
+            );
+            assert!(yaml.ends_with("- name: latest\n"), "{yaml}");
+        }
+    }
+}
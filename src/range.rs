@@ -0,0 +1,194 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use anyhow::{Result, anyhow};
+
+use crate::{Tag, series::LenientVersion};
+
+/// A range of versions such as `>=1.2 <2`, whose alternatives are separated
+/// by `||`, as Renovate's `allowedVersions` and npm write them
+///
+/// Tags are read leniently as by [`latest_per_series`](crate::latest_per_series),
+/// so `1.2.3-alpine` is in `1.2.x`; tags which aren't versions are in no
+/// range.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionRange {
+    text: String,
+    /// Alternatives, each matching versions all of its comparators match
+    alternatives: Vec<Vec<Comparator>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Comparator {
+    op: Ordering,
+    /// Whether versions equal to `bound` match too
+    or_equal: bool,
+    bound: (u64, u64, u64),
+}
+
+impl VersionRange {
+    /// Parse comparators (`<2`, `<=1.4`, `>1`, `>=1.2.3`, `=1.2`, `1.2`,
+    /// `1.2.x`) separated by spaces or commas, alternatives by `||`
+    pub fn parse(text: &str) -> Result<Self> {
+        let alternatives = text
+            .split("||")
+            .map(|alternative| {
+                alternative
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|part| !part.is_empty())
+                    .map(Comparator::parse)
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if alternatives.iter().any(Vec::is_empty) {
+            return Err(anyhow!("Invalid version range {text:?}"));
+        }
+        Ok(VersionRange {
+            text: text.to_string(),
+            alternatives,
+        })
+    }
+
+    /// Whether `tag` is a version in the range
+    pub fn matches(&self, tag: &Tag) -> bool {
+        let Some(version) = LenientVersion::parse(tag.name()) else {
+            return false;
+        };
+        self.alternatives.iter().any(|comparators| {
+            comparators
+                .iter()
+                .all(|comparator| comparator.matches(&version))
+        })
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        VersionRange::parse(text)
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Comparator {
+    /// `<2`, `<=1.4`, `>1`, `>=1.2.3`, `=1.2` or `1.2`, the latter standing
+    /// for the whole `1.2` series as `1.2.x` and `1.2.*` do
+    fn parse(part: &str) -> Result<Self> {
+        let (op, or_equal, version) = [
+            ("<=", Ordering::Less, true),
+            (">=", Ordering::Greater, true),
+            ("<", Ordering::Less, false),
+            (">", Ordering::Greater, false),
+            ("==", Ordering::Equal, true),
+            ("=", Ordering::Equal, true),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op, or_equal)| {
+            part.strip_prefix(prefix)
+                .map(|version| (op, or_equal, version))
+        })
+        .unwrap_or((Ordering::Equal, true, part));
+
+        let invalid = || anyhow!("Invalid version {version:?} in range");
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let mut numbers = Vec::new();
+        for number in version.split('.') {
+            match number {
+                "x" | "X" | "*" if op == Ordering::Equal => break,
+                number => numbers.push(number.parse::<u64>().map_err(|_| invalid())?),
+            }
+        }
+        if numbers.is_empty() || numbers.len() > 3 {
+            return Err(invalid());
+        }
+        let bound = |i: usize| numbers.get(i).copied();
+        let comparator = match op {
+            // a series, matched by its first and last versions
+            Ordering::Equal if numbers.len() < 3 => Comparator {
+                op: Ordering::Equal,
+                or_equal: true,
+                bound: (numbers[0], bound(1).unwrap_or(u64::MAX), u64::MAX),
+            },
+            _ => Comparator {
+                op,
+                or_equal,
+                bound: (numbers[0], bound(1).unwrap_or(0), bound(2).unwrap_or(0)),
+            },
+        };
+        Ok(comparator)
+    }
+
+    fn matches(&self, version: &LenientVersion) -> bool {
+        let numbers = (
+            version.major,
+            version.minor.unwrap_or(0),
+            version.patch.unwrap_or(0),
+        );
+        if self.op == Ordering::Equal {
+            // `bound` holds the series' last version, u64::MAX standing for
+            // any number
+            let any = |bound: u64, n: u64| bound == u64::MAX || bound == n;
+            return numbers.0 == self.bound.0
+                && any(self.bound.1, numbers.1)
+                && any(self.bound.2, numbers.2);
+        }
+        // pre-releases come before their release
+        let ordering = numbers.cmp(&self.bound).then(if version.pre_release {
+            Ordering::Less
+        } else {
+            Ordering::Equal
+        });
+        ordering == self.op || (self.or_equal && ordering == Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matching(range: &str, names: &[&str]) -> Vec<String> {
+        let range = VersionRange::parse(range).unwrap();
+        names
+            .iter()
+            .map(|name| Tag::new(*name))
+            .filter(|tag| range.matches(tag))
+            .map(|tag| tag.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_version_range() {
+        let names = [
+            "1.1.9",
+            "1.2",
+            "1.2.0",
+            "1.2.3-alpine",
+            "1.3.0",
+            "2.0.0-rc1",
+            "2.0.0",
+            "latest",
+        ];
+        assert_eq!(
+            matching(">=1.2 <2", &names),
+            ["1.2", "1.2.0", "1.2.3-alpine", "1.3.0", "2.0.0-rc1"]
+        );
+        assert_eq!(matching(">=1.2, <2", &names), matching(">=1.2 <2", &names));
+        assert_eq!(matching("<=1.2.0", &names), ["1.1.9", "1.2", "1.2.0"]);
+        assert_eq!(matching("1.2.x", &names), ["1.2", "1.2.0", "1.2.3-alpine"]);
+        assert_eq!(matching("=1.2", &names), matching("1.2", &names));
+        assert_eq!(matching("1.1 || >=2", &names), ["1.1.9", "2.0.0"]);
+        assert_eq!(
+            VersionRange::parse("1.1 || >=2").unwrap().to_string(),
+            "1.1 || >=2"
+        );
+
+        for range in ["", "<", ">=a", "1.2.3.4", "1 ||"] {
+            assert!(VersionRange::parse(range).is_err(), "{range}");
+        }
+    }
+}
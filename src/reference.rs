@@ -0,0 +1,246 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    Image, Tag, client::repository_path, is_valid_tag, manifest::is_digest, registries::DOCKER_HUB,
+};
+
+/// A reference to an image by tag, by digest or both, e.g.
+/// `nginx:1.27@sha256:…`
+///
+/// Displays in the familiar form Docker prints, without `docker.io/` and
+/// `library/` (`nginx:1.27`), or with `{:#}` in the canonical form
+/// (`docker.io/library/nginx:1.27`). Other registries are kept as they are,
+/// ports included.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reference {
+    image: Image,
+    tag: Option<Tag>,
+    digest: Option<String>,
+}
+
+impl Reference {
+    /// `image` without a tag or a digest
+    pub fn new(image: Image) -> Self {
+        Reference {
+            image,
+            tag: None,
+            digest: None,
+        }
+    }
+
+    /// `image` at `reference`, a tag or a digest
+    pub fn of(image: &Image, reference: &str) -> Self {
+        let untagged = Reference::new(image.clone());
+        match is_digest(reference) {
+            true => untagged.with_digest(reference),
+            false => untagged.with_tag(Tag::new(reference)),
+        }
+    }
+
+    pub fn with_tag(mut self, tag: Tag) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    /// Pin the reference to `digest`, replacing any digest it had
+    pub fn with_digest(mut self, digest: impl Into<String>) -> Self {
+        self.digest = Some(digest.into());
+        self
+    }
+
+    pub fn without_digest(mut self) -> Self {
+        self.digest = None;
+        self
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn tag(&self) -> Option<&Tag> {
+        self.tag.as_ref()
+    }
+
+    pub fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
+
+    /// The canonical form, as `{:#}` displays it
+    pub fn canonical(&self) -> String {
+        format!("{self:#}")
+    }
+}
+
+impl From<(&Image, &Tag)> for Reference {
+    fn from((image, tag): (&Image, &Tag)) -> Self {
+        Reference::new(image.clone()).with_tag(tag.clone())
+    }
+}
+
+impl From<Image> for Reference {
+    fn from(image: Image) -> Self {
+        Reference::new(image)
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Image {
+            registry,
+            repository,
+        } = &self.image;
+        match (f.alternate(), registry.as_str()) {
+            (true, _) => write!(f, "{registry}/{}", repository_path(&self.image))?,
+            (false, DOCKER_HUB) => {
+                let short = repository
+                    .strip_prefix("library/")
+                    .filter(|name| !name.contains('/'));
+                write!(f, "{}", short.unwrap_or(repository))?
+            }
+            (false, _) => write!(f, "{registry}/{repository}")?,
+        }
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Reference {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Reference::try_from(value)
+    }
+}
+
+impl TryFrom<&str> for Reference {
+    type Error = &'static str;
+
+    /// Parse `name[:tag][@digest]`, the name as [`Image`] takes it
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (rest, digest) = match value.split_once('@') {
+            Some((rest, digest)) if is_digest(digest) => (rest, Some(digest)),
+            Some(_) => return Err("Invalid digest"),
+            None => (value, None),
+        };
+        // the colon of a port comes before the last `/`
+        let (name, tag) = match rest.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => match is_valid_tag(tag) {
+                true => (name, Some(tag)),
+                false => return Err("Invalid tag"),
+            },
+            _ => (rest, None),
+        };
+        let mut reference = Reference::new(Image::try_from(name)?);
+        if let Some(tag) = tag {
+            reference = reference.with_tag(Tag::new(tag));
+        }
+        if let Some(digest) = digest {
+            reference = reference.with_digest(digest);
+        }
+        Ok(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:1c4eef651f65e2f7daee7ee785882ac164b02b78fb74503052a26dc061c90474";
+
+    /// Familiar and canonical forms of `reference`
+    fn forms(reference: &str) -> (String, String) {
+        let reference = Reference::try_from(reference).unwrap();
+        (reference.to_string(), reference.canonical())
+    }
+
+    #[test]
+    fn test_docker_hub_forms() {
+        for reference in [
+            "nginx:1.27",
+            "docker.io/nginx:1.27",
+            "library/nginx:1.27",
+            "docker.io/library/nginx:1.27",
+        ] {
+            assert_eq!(
+                forms(reference),
+                (
+                    "nginx:1.27".to_string(),
+                    "docker.io/library/nginx:1.27".to_string()
+                ),
+                "{reference}"
+            );
+        }
+        assert_eq!(
+            forms("bitnami/redis:7.4"),
+            (
+                "bitnami/redis:7.4".to_string(),
+                "docker.io/bitnami/redis:7.4".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_other_registries_forms() {
+        for reference in [
+            "ghcr.io/org/app:1.0",
+            "localhost:5000/app:dev",
+            "registry.corp:8443/team/app",
+            "10.0.3.7:5000/app:1",
+            "[::1]:5000/app:1",
+            "quay.io/library/app:1",
+        ] {
+            let (familiar, canonical) = forms(reference);
+            assert_eq!(familiar, reference);
+            assert_eq!(canonical, reference);
+        }
+    }
+
+    #[test]
+    fn test_digests() {
+        assert_eq!(
+            forms(&format!("alpine:3.21@{DIGEST}")),
+            (
+                format!("alpine:3.21@{DIGEST}"),
+                format!("docker.io/library/alpine:3.21@{DIGEST}")
+            )
+        );
+        assert_eq!(
+            forms(&format!("localhost:5000/app@{DIGEST}")).0,
+            format!("localhost:5000/app@{DIGEST}")
+        );
+
+        let image = Image::try_from("ghcr.io/org/app").unwrap();
+        let tagged = Reference::from((&image, &Tag::new("1.0")));
+        let pinned = tagged.clone().with_digest(DIGEST);
+        assert_eq!(pinned.to_string(), format!("ghcr.io/org/app:1.0@{DIGEST}"));
+        assert_eq!(pinned.digest(), Some(DIGEST));
+        assert_eq!(
+            pinned.with_digest("sha256:abc").digest(),
+            Some("sha256:abc")
+        );
+        assert_eq!(tagged.clone().with_digest(DIGEST).without_digest(), tagged);
+
+        assert_eq!(
+            Reference::of(&image, DIGEST).to_string(),
+            format!("ghcr.io/org/app@{DIGEST}")
+        );
+        assert_eq!(Reference::of(&image, "1.0"), tagged);
+        assert_eq!(Reference::new(image).to_string(), "ghcr.io/org/app");
+    }
+
+    #[test]
+    fn test_parse() {
+        let reference = Reference::try_from("localhost:5000/app").unwrap();
+        assert_eq!((reference.tag(), reference.digest()), (None, None));
+        assert_eq!(reference.image().registry(), "localhost:5000");
+
+        for invalid in ["nginx:", "nginx:-1", "nginx@sha256:xyz", "a/b/c/d:1"] {
+            assert!(Reference::try_from(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Well-known registries and the other names they go by
+//!
+//! Docker Hub in particular is written `docker.io` in image references,
+//! served from `registry-1.docker.io` and stored under
+//! `https://index.docker.io/v1/` in Docker's config.
+
+/// Docker Hub, as image references name it
+pub const DOCKER_HUB: &str = "docker.io";
+
+/// Host serving Docker Hub's registry API
+pub const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+
+/// Key of Docker Hub's credentials in Docker's config, also the server its
+/// credential helpers are asked about
+pub const DOCKER_HUB_AUTH_KEY: &str = "https://index.docker.io/v1/";
+
+/// GitHub Container Registry
+pub const GHCR: &str = "ghcr.io";
+
+/// Red Hat Quay
+pub const QUAY: &str = "quay.io";
+
+/// Hosts standing for Docker Hub besides [`DOCKER_HUB`]
+const DOCKER_HUB_ALIASES: &[&str] = &[
+    "index.docker.io",
+    "registry-1.docker.io",
+    "registry.hub.docker.com",
+];
+
+/// Whether `host` is Docker Hub under any of its names, in any case
+pub fn is_docker_hub(host: &str) -> bool {
+    host.eq_ignore_ascii_case(DOCKER_HUB)
+        || DOCKER_HUB_ALIASES
+            .iter()
+            .any(|alias| host.eq_ignore_ascii_case(alias))
+}
+
+/// `host` as image references of this crate name it: lowercase, and
+/// [`DOCKER_HUB`] for any name of Docker Hub
+pub fn canonical_host(host: &str) -> String {
+    match is_docker_hub(host) {
+        true => DOCKER_HUB.to_string(),
+        false => host.to_ascii_lowercase(),
+    }
+}
+
+/// Key of `host`'s credentials in the `auths` of Docker's config, as
+/// `docker login` writes it: [`DOCKER_HUB_AUTH_KEY`] for Docker Hub, the
+/// host itself otherwise
+pub fn auth_config_key(host: &str) -> String {
+    match is_docker_hub(host) {
+        true => DOCKER_HUB_AUTH_KEY.to_string(),
+        false => host.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_docker_hub() {
+        for host in [
+            "docker.io",
+            "Docker.IO",
+            "index.docker.io",
+            "registry-1.docker.io",
+            "registry.hub.docker.com",
+        ] {
+            assert!(is_docker_hub(host), "{host}");
+        }
+        for host in [
+            "hub.docker.com",
+            "docker.io:443",
+            "ghcr.io",
+            "mirror.docker.io.corp",
+            "",
+        ] {
+            assert!(!is_docker_hub(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn test_canonical_host() {
+        for alias in [
+            "docker.io",
+            "index.docker.io",
+            "REGISTRY-1.docker.io",
+            "registry.hub.docker.com",
+        ] {
+            assert_eq!(canonical_host(alias), DOCKER_HUB, "{alias}");
+        }
+        assert_eq!(canonical_host("GHCR.io"), GHCR);
+        assert_eq!(canonical_host("quay.io"), QUAY);
+        assert_eq!(canonical_host("Registry.Corp:5000"), "registry.corp:5000");
+    }
+
+    #[test]
+    fn test_auth_config_key() {
+        for alias in [
+            "docker.io",
+            "index.docker.io",
+            "registry-1.docker.io",
+            "registry.hub.docker.com",
+        ] {
+            assert_eq!(auth_config_key(alias), DOCKER_HUB_AUTH_KEY, "{alias}");
+        }
+        assert_eq!(auth_config_key(GHCR), "ghcr.io");
+        assert_eq!(auth_config_key("localhost:5000"), "localhost:5000");
+    }
+}
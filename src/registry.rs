@@ -0,0 +1,178 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::{
+    Client, Image, Scope, Tag, TagCollector,
+    client::{pull_scope, read_body},
+    ignore::glob_match,
+    is_valid_component, is_valid_host,
+    secret::{Secret, redact_url},
+    timings::RequestKind,
+};
+
+/// Scope needed to list the repositories of a registry
+const CATALOG_SCOPE: &str = "registry:catalog:*";
+
+#[derive(Deserialize)]
+struct CatalogResponse {
+    repositories: Option<Vec<String>>,
+}
+
+/// Many repositories of one registry sharing a connection pool, credentials
+/// looked up once and a token per scope
+///
+/// Cloning is cheap and the clones share their tokens.
+#[derive(Clone, Debug)]
+pub struct Registry {
+    client: Client,
+    host: String,
+    tokens: Arc<Mutex<HashMap<String, Arc<OnceCell<Secret>>>>>,
+}
+
+impl Registry {
+    /// Use `host` (e.g. `quay.io`) with the options of `client`, such as its
+    /// credential provider or the registry's path prefix
+    ///
+    /// Nothing is sent until the first request.
+    pub fn connect(host: impl Into<String>, client: &Client) -> Result<Self> {
+        let host = host.into();
+        if !is_valid_host(&host) {
+            return Err(anyhow!("Invalid registry host: {host:?}"));
+        }
+
+        Ok(Registry {
+            client: client.with_cached_credentials(),
+            host,
+            tokens: Arc::default(),
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Image for repository `name` of this registry, e.g. `prometheus/prometheus`
+    pub fn repository(&self, name: &str) -> Result<Image> {
+        if !name.split('/').all(is_valid_component) {
+            return Err(anyhow!("Invalid repository name: {name:?}"));
+        }
+        Ok(Image::new(&self.host, name))
+    }
+
+    /// Names of all repositories, as listed by the catalog API
+    ///
+    /// Many public registries (Docker Hub, GHCR) don't offer it.
+    pub async fn list_repositories(&self) -> Result<Vec<String>> {
+        let mut token = self.token(CATALOG_SCOPE).await;
+        let mut url = self.client.v2_url(&self.host, ["_catalog"])?;
+        url.query_pairs_mut().append_pair("n", "100");
+
+        let mut repositories = Vec::new();
+        let mut next_url = url.clone();
+        loop {
+            let resp = self
+                .client
+                .send_authorized(&self.host, RequestKind::Other, &next_url, None, &mut token)
+                .await?;
+            if resp.status() == StatusCode::NOT_FOUND {
+                return Err(anyhow!("{} doesn't support the catalog API", self.host));
+            }
+            let body = read_body(resp, &next_url, None).await?;
+            let data: CatalogResponse = serde_json::from_slice(&body)
+                .with_context(|| format!("Failed to parse JSON from {}", redact_url(&next_url)))?;
+
+            let page = data.repositories.unwrap_or_default();
+            let page_len = page.len();
+            let Some(last) = page.last().cloned() else {
+                break;
+            };
+            repositories.extend(page);
+            if page_len < 100 {
+                break;
+            }
+            next_url = url.clone();
+            next_url.query_pairs_mut().append_pair("last", &last);
+        }
+
+        Ok(repositories)
+    }
+
+    /// Names of the repositories matching the glob `pattern`, as listed by
+    /// the catalog API
+    ///
+    /// `*` matches any characters but `/` and `?` one of them, so
+    /// `team-*/app` matches `team-a/app` but neither `team-a/app/cache` nor
+    /// `team-a/sub/app`.
+    pub async fn match_repositories(&self, pattern: &str) -> Result<Vec<String>> {
+        let mut repositories = self.list_repositories().await?;
+        repositories.retain(|name| matches_repository(pattern, name));
+        Ok(repositories)
+    }
+
+    /// Fetch all tags of repository `name`
+    pub async fn fetch_tags(&self, name: &str) -> Result<Vec<Tag>> {
+        let image = self.repository(name)?;
+        let token = self.token(&pull_scope(&image)).await;
+        let mut collector = TagCollector::new();
+        self.client
+            .collect_tags(&image, &mut collector, &mut 0, token)
+            .await?;
+
+        Ok(collector.finish().0)
+    }
+
+    /// Fetch a single token for all of `scopes` (e.g. `repository:org/app:pull`)
+    /// and use it for each of them, instead of a challenge and a token request
+    /// per repository later on, like [`Client::pre_authorize`]
+    ///
+    /// Scopes which already have a token keep it. Nothing is cached if the
+    /// registry doesn't issue a token, so they're then requested one by one.
+    pub async fn pre_authorize(&self, scopes: &[Scope]) {
+        let mut wanted: Vec<String> = Vec::new();
+        for scope in scopes.iter().map(Scope::to_string) {
+            if !wanted.contains(&scope) {
+                wanted.push(scope);
+            }
+        }
+        if wanted.is_empty() {
+            return;
+        }
+        let token = self.client.registry_token(&self.host, &wanted).await;
+        if token.is_empty() {
+            return;
+        }
+        let mut tokens = self.tokens.lock().await;
+        for scope in wanted {
+            // a cell being filled concurrently keeps its own token
+            let _ = tokens.entry(scope).or_default().set(token.clone());
+        }
+    }
+
+    /// Token for `scope`, fetched the first time it's needed
+    async fn token(&self, scope: &str) -> Secret {
+        let cell = self
+            .tokens
+            .lock()
+            .await
+            .entry(scope.to_string())
+            .or_default()
+            .clone();
+        let scopes = [scope.to_string()];
+        cell.get_or_init(|| self.client.registry_token(&self.host, &scopes))
+            .await
+            .clone()
+    }
+}
+
+/// Whether repository `name` matches `pattern` component by component
+fn matches_repository(pattern: &str, name: &str) -> bool {
+    pattern.split('/').count() == name.split('/').count()
+        && pattern
+            .split('/')
+            .zip(name.split('/'))
+            .all(|(pattern, component)| glob_match(pattern, component))
+}
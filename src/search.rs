@@ -0,0 +1,242 @@
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+
+use crate::{
+    Client, Image, TagCollector,
+    manifest::parse_rfc3339,
+    registries::{DOCKER_HUB, QUAY},
+    secret::{redact_str, redact_url},
+    timings::RequestKind,
+};
+
+/// Registry-specific API which filters tag names server-side, used by
+/// [`Client::fetch_tags_matching`](crate::Client::fetch_tags_matching)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SearchApi {
+    /// Docker Hub's `/v2/repositories/<repo>/tags?name=` at this base URL,
+    /// `https://hub.docker.com` for `docker.io`
+    DockerHub(Url),
+    /// Quay's `/api/v1/repository/<repo>/tag/?filter_tag_name=like:` at this
+    /// base URL, `https://quay.io` for `quay.io`
+    Quay(Url),
+}
+
+#[derive(Deserialize)]
+struct HubPage {
+    next: Option<String>,
+    #[serde(default)]
+    results: Vec<NamedTag>,
+}
+
+#[derive(Deserialize)]
+struct NamedTag {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct HubRepository {
+    last_updated: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QuayPage {
+    #[serde(default)]
+    tags: Vec<NamedTag>,
+    #[serde(default)]
+    has_additional: bool,
+}
+
+impl SearchApi {
+    /// Default API for `registry`, if it has one
+    pub(crate) fn for_registry(registry: &str) -> Option<Self> {
+        match registry {
+            DOCKER_HUB => Url::parse("https://hub.docker.com")
+                .ok()
+                .map(SearchApi::DockerHub),
+            QUAY => Url::parse("https://quay.io").ok().map(SearchApi::Quay),
+            _ => None,
+        }
+    }
+
+    /// Feed the names of `image`'s tags containing `needle` into `collector`,
+    /// counting pages; the server may return more, never fewer
+    pub(crate) async fn collect(
+        &self,
+        client: &Client,
+        image: &Image,
+        needle: &str,
+        collector: &mut TagCollector,
+        pages: &mut usize,
+    ) -> Result<()> {
+        let (namespace, name) = split_repository(image)?;
+        match self {
+            SearchApi::DockerHub(base) => {
+                let mut url = api_url(base, &["v2", "repositories", namespace, name, "tags"])?;
+                url.query_pairs_mut()
+                    .append_pair("page_size", "100")
+                    .append_pair("name", needle);
+                loop {
+                    let page: HubPage = get_json(client, &image.registry, &url)
+                        .await
+                        .with_context(|| {
+                            format!("Failed to search tags at {}", redact_url(&url))
+                        })?;
+                    *pages += 1;
+                    collector.push_page(page.results.into_iter().map(|t| t.name));
+                    let Some(next) = page.next else {
+                        break;
+                    };
+                    let next = Url::parse(&next)
+                        .with_context(|| format!("Invalid next page URL: {}", redact_str(&next)))?;
+                    // never follow the pagination to another server
+                    if next.origin() != base.origin() {
+                        return Err(anyhow!(
+                            "Next page URL {} leaves {}",
+                            redact_url(&next),
+                            redact_url(base)
+                        ));
+                    }
+                    url = next;
+                }
+            }
+            SearchApi::Quay(base) => {
+                let url = api_url(
+                    base,
+                    &["api", "v1", "repository", namespace, name, "tag", ""],
+                )?;
+                for page in 1.. {
+                    let mut page_url = url.clone();
+                    page_url
+                        .query_pairs_mut()
+                        .append_pair("limit", "100")
+                        .append_pair("onlyActiveTags", "true")
+                        .append_pair("page", &page.to_string())
+                        .append_pair("filter_tag_name", &format!("like:%{needle}%"));
+                    let data: QuayPage = get_json(client, &image.registry, &page_url)
+                        .await
+                        .with_context(|| {
+                            format!("Failed to search tags at {}", redact_url(&page_url))
+                        })?;
+                    *pages += 1;
+                    collector.push_page(data.tags.into_iter().map(|t| t.name));
+                    if !data.has_additional {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// When `image` was last pushed to, as Docker Hub's repository API tells;
+    /// `None` from Quay's, whose repositories don't say
+    pub(crate) async fn last_pushed(
+        &self,
+        client: &Client,
+        image: &Image,
+    ) -> Result<Option<SystemTime>> {
+        let SearchApi::DockerHub(base) = self else {
+            return Ok(None);
+        };
+        let (namespace, name) = split_repository(image)?;
+        let url = api_url(base, &["v2", "repositories", namespace, name, ""])?;
+        let repository: HubRepository = get_json(client, &image.registry, &url)
+            .await
+            .with_context(|| format!("Failed to read the repository at {}", redact_url(&url)))?;
+        Ok(repository.last_updated.as_deref().and_then(parse_rfc3339))
+    }
+}
+
+/// Namespace and name of `image`, `library` for Docker Hub's official images
+fn split_repository(image: &Image) -> Result<(&str, &str)> {
+    match image.repository.split_once('/') {
+        Some((namespace, name)) => Ok((namespace, name)),
+        None if image.registry == DOCKER_HUB => Ok(("library", image.repository.as_str())),
+        None => Err(anyhow!("No namespace in {image}")),
+    }
+}
+
+/// `base` with `segments` appended, each percent-encoded
+fn api_url(base: &Url, segments: &[&str]) -> Result<Url> {
+    let mut url = base.clone();
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid search API URL: {}", redact_url(base)))?
+        .pop_if_empty()
+        .extend(segments);
+    Ok(url)
+}
+
+async fn get_json<T: for<'de> Deserialize<'de>>(
+    client: &Client,
+    registry: &str,
+    url: &Url,
+) -> Result<T> {
+    let req = client.http_for(registry, url).get(url.clone());
+    let resp = client.send(registry, RequestKind::TagsPage, req).await?;
+    if resp.status() != StatusCode::OK {
+        return Err(anyhow!(resp.status()));
+    }
+    resp.json()
+        .await
+        .map_err(|err| err.without_url())
+        .with_context(|| format!("Failed to parse JSON from {}", redact_url(url)))
+}
+
+/// Substring every match of `pattern` must contain, if `pattern` is a plain
+/// literal with optional `^`/`$` anchors and backslash-escaped punctuation
+pub(crate) fn pushdown_literal(pattern: &str) -> Option<String> {
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    let pattern = match pattern.strip_suffix('$') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => pattern,
+    };
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) if c.is_ascii_punctuation() => literal.push(c),
+                _ => return None, // `\d`, `\b` and friends
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' => {
+                return None;
+            }
+            c => literal.push(c),
+        }
+    }
+    Some(literal).filter(|literal| !literal.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pushdown_literal() {
+        let cases = [
+            ("alpine", Some("alpine")),
+            ("^1\\.27", Some("1.27")),
+            ("-slim$", Some("-slim")),
+            ("^v2\\.0\\.0$", Some("v2.0.0")),
+            ("1.27", None),
+            ("^1\\.2[0-9]", None),
+            ("alpine|slim", None),
+            ("(?i)alpine", None),
+            ("\\d+", None),
+            ("^$", None),
+            ("", None),
+            ("rc\\$", Some("rc$")),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(
+                pushdown_literal(pattern).as_deref(),
+                expected,
+                "{pattern:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,605 @@
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::{
+    Channels, IgnoreList, Image, SortKey, Tag, VersionRange, series::LenientVersion, sort_tags_desc,
+};
+
+/// Sorting, filtering and truncation applied to a tag listing
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// Oldest versions first instead of newest
+    pub reverse: bool,
+    /// Keep the registry's order instead of sorting
+    pub keep_order: bool,
+    /// Keep only tags matching the pattern
+    #[cfg(feature = "regex")]
+    pub pattern: Option<Regex>,
+    /// Keep only tags passing all of these too
+    pub filters: Vec<TagFilter>,
+    /// Keep at most this many tags, after filtering
+    pub limit: Option<usize>,
+    /// What to do with alias tags such as `latest`
+    pub aliases: Aliases,
+}
+
+/// Alias tags [`Aliases::pinned`] lists first
+pub const DEFAULT_ALIASES: [&str; 4] = ["latest", "stable", "edge", "lts"];
+
+/// What a [`Selection`] does with alias tags, such as `latest`, which would
+/// otherwise sort among the names which aren't versions and fall to the limit
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Aliases {
+    /// Sorted and limited like the other tags
+    #[default]
+    Sorted,
+    /// Listed first, in the order named and whatever the order of the other
+    /// tags, without counting towards the limit
+    Pinned(Vec<String>),
+    /// Left out
+    Hidden(Vec<String>),
+}
+
+impl Aliases {
+    /// [`DEFAULT_ALIASES`] listed first
+    pub fn pinned() -> Self {
+        Aliases::Pinned(DEFAULT_ALIASES.map(String::from).to_vec())
+    }
+
+    /// [`DEFAULT_ALIASES`] left out
+    pub fn hidden() -> Self {
+        Aliases::Hidden(DEFAULT_ALIASES.map(String::from).to_vec())
+    }
+}
+
+impl Selection {
+    pub fn apply(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        let (mut aliases, tags) = self.apply_split(tags);
+        aliases.extend(tags);
+        aliases
+    }
+
+    /// The aliases [`Aliases::Pinned`] lists first, then the other tags
+    /// sorted, filtered and limited
+    pub fn apply_split(&self, tags: Vec<Tag>) -> (Vec<Tag>, Vec<Tag>) {
+        let (aliases, mut tags) = self.split_aliases(tags);
+        if !self.keep_order {
+            sort_tags_desc(&mut tags);
+        }
+        tags.retain(|tag| self.matches(tag));
+
+        (aliases, self.arrange(tags))
+    }
+
+    /// Take the aliases [`Aliases::Pinned`] lists first out of `tags`, those
+    /// matching in the order named, leaving the other tags as they are
+    ///
+    /// Strategies ordering the tags their own way take the aliases out
+    /// first, then put them back before what they arranged.
+    pub fn split_aliases(&self, mut tags: Vec<Tag>) -> (Vec<Tag>, Vec<Tag>) {
+        let Aliases::Pinned(names) = &self.aliases else {
+            return (Vec::new(), tags);
+        };
+        let mut aliases = Vec::new();
+        for name in names {
+            if let Some(i) = tags.iter().position(|tag| tag.name() == name) {
+                let alias = tags.remove(i);
+                if self.matches(&alias) {
+                    aliases.push(alias);
+                }
+            }
+        }
+        (aliases, tags)
+    }
+
+    /// Whether `tag` matches the pattern, if there is one, and passes the
+    /// filters
+    pub fn matches(&self, tag: &Tag) -> bool {
+        if let Aliases::Hidden(names) = &self.aliases
+            && names.iter().any(|name| name == tag.name())
+        {
+            return false;
+        }
+        #[cfg(feature = "regex")]
+        if let Some(re) = &self.pattern
+            && !re.is_match(tag.name())
+        {
+            return false;
+        }
+        self.filters.iter().all(|filter| filter.matches(tag))
+    }
+
+    /// Whether there's a pattern or a filter, so not every tag matches
+    pub fn is_filtered(&self) -> bool {
+        #[cfg(feature = "regex")]
+        if self.pattern.is_some() {
+            return true;
+        }
+        !self.filters.is_empty()
+            || matches!(&self.aliases, Aliases::Hidden(names) if !names.is_empty())
+    }
+
+    /// The pattern, the filters and the aliases alone, sorting by version
+    /// without a limit
+    pub fn filtering(&self) -> Selection {
+        Selection {
+            #[cfg(feature = "regex")]
+            pattern: self.pattern.clone(),
+            filters: self.filters.clone(),
+            aliases: self.aliases.clone(),
+            ..Selection::default()
+        }
+    }
+
+    /// Reverse `items`, already in the order a listing strategy gives them
+    /// (versions, dates, the registry's), if asked to, then truncate them
+    ///
+    /// Every strategy goes through this, so `reverse` and `limit` mean the
+    /// same for all of them.
+    pub fn arrange<T>(&self, mut items: Vec<T>) -> Vec<T> {
+        if self.reverse {
+            items.reverse();
+        }
+        if let Some(limit) = self.limit {
+            items.truncate(limit);
+        }
+        items
+    }
+}
+
+/// A test on tag names, combined with the pattern of a [`Selection`]
+#[derive(Clone, Debug)]
+pub struct TagFilter(Filter);
+
+#[derive(Clone, Debug)]
+enum Filter {
+    NoSha,
+    MaxNameLength(usize),
+    VersionLike,
+    Stable,
+    Range(VersionRange),
+    #[cfg(feature = "regex")]
+    Pattern(Regex),
+    #[cfg(feature = "regex")]
+    Excluded(Regex),
+    /// Channel tags only, or none of them when false
+    Channels(Channels, bool),
+    Text {
+        text: String,
+        how: TextMatch,
+        ignore_case: bool,
+    },
+    /// The rules of an ignore list applying to one image
+    NotIgnored(IgnoreList),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TextMatch {
+    Prefix,
+    Contains,
+    Exact,
+}
+
+/// Shortest hex name taken for a commit SHA, as `git log --abbrev` goes
+const MIN_SHA_LEN: usize = 12;
+
+impl TagFilter {
+    /// Drop commit SHAs: names of at least 12 hex digits with a letter among
+    /// them, so dates like `202410021530` stay, and `sha256-<digest>` tags
+    /// such as `sha256-<digest>.sig` signatures
+    pub fn no_sha() -> Self {
+        TagFilter(Filter::NoSha)
+    }
+
+    /// Drop names longer than `max` characters
+    pub fn max_name_length(max: usize) -> Self {
+        TagFilter(Filter::MaxNameLength(max))
+    }
+
+    /// Keep only versions, as read leniently by [`latest_per_series`](crate::latest_per_series)
+    /// (`16`, `3.12-slim`, `17rc1`) or as semantic versions (`1.2.3+build.5`)
+    pub fn version_like() -> Self {
+        TagFilter(Filter::VersionLike)
+    }
+
+    /// Drop pre-releases, such as `2.0.0-rc1` or `17beta2`, keeping releases
+    /// and names which aren't versions
+    pub fn stable() -> Self {
+        TagFilter(Filter::Stable)
+    }
+
+    /// Keep only versions in `range`
+    pub fn version_range(range: VersionRange) -> Self {
+        TagFilter(Filter::Range(range))
+    }
+
+    /// Keep only names matching `re`
+    #[cfg(feature = "regex")]
+    pub fn pattern(re: Regex) -> Self {
+        TagFilter(Filter::Pattern(re))
+    }
+
+    /// Drop names matching `re`
+    #[cfg(feature = "regex")]
+    pub fn excluding(re: Regex) -> Self {
+        TagFilter(Filter::Excluded(re))
+    }
+
+    /// Keep only channel tags such as `stable` or `mainline-alpine`
+    pub fn channels(channels: Channels) -> Self {
+        TagFilter(Filter::Channels(channels, true))
+    }
+
+    /// Drop channel tags, keeping versions and other names
+    pub fn no_channels(channels: Channels) -> Self {
+        TagFilter(Filter::Channels(channels, false))
+    }
+
+    /// Keep only names starting with `prefix`, so `1.27` keeps `1.27.3` but
+    /// not `0.1.27-test`; letters compare regardless of their ASCII case if
+    /// `ignore_case`, as for the other text filters
+    pub fn prefix(prefix: impl Into<String>, ignore_case: bool) -> Self {
+        TagFilter::text(prefix.into(), TextMatch::Prefix, ignore_case)
+    }
+
+    /// Keep only names containing `text`
+    pub fn contains(text: impl Into<String>, ignore_case: bool) -> Self {
+        TagFilter::text(text.into(), TextMatch::Contains, ignore_case)
+    }
+
+    /// Keep only the name `text`
+    pub fn exact(text: impl Into<String>, ignore_case: bool) -> Self {
+        TagFilter::text(text.into(), TextMatch::Exact, ignore_case)
+    }
+
+    /// Drop the tags `ignore` leaves out for `image`
+    pub fn not_ignored(ignore: &IgnoreList, image: &Image) -> Self {
+        TagFilter(Filter::NotIgnored(ignore.for_image(image)))
+    }
+
+    fn text(text: String, how: TextMatch, ignore_case: bool) -> Self {
+        let text = match ignore_case {
+            true => text.to_ascii_lowercase(),
+            false => text,
+        };
+        TagFilter(Filter::Text {
+            text,
+            how,
+            ignore_case,
+        })
+    }
+
+    pub fn matches(&self, tag: &Tag) -> bool {
+        let name = tag.name();
+        match &self.0 {
+            Filter::NoSha => !is_sha(name),
+            Filter::MaxNameLength(max) => name.len() <= *max,
+            Filter::VersionLike => {
+                LenientVersion::parse(name).is_some()
+                    || matches!(tag.sort_key(), SortKey::Semver(_))
+            }
+            // `-alpine` is a variant, not a pre-release as semver has it
+            Filter::Stable => match LenientVersion::parse(name) {
+                Some(version) => !version.pre_release,
+                None => match tag.sort_key() {
+                    SortKey::Semver(version) => version.pre.is_empty(),
+                    _ => true,
+                },
+            },
+            Filter::Range(range) => range.matches(tag),
+            #[cfg(feature = "regex")]
+            Filter::Pattern(re) => re.is_match(name),
+            #[cfg(feature = "regex")]
+            Filter::Excluded(re) => !re.is_match(name),
+            Filter::Channels(channels, keep) => channels.is_channel(tag) == *keep,
+            Filter::Text {
+                text,
+                how,
+                ignore_case,
+            } => {
+                let lowercase;
+                let name = match ignore_case {
+                    true => {
+                        lowercase = name.to_ascii_lowercase();
+                        &lowercase
+                    }
+                    false => name,
+                };
+                match how {
+                    TextMatch::Prefix => name.starts_with(text.as_str()),
+                    TextMatch::Contains => name.contains(text.as_str()),
+                    TextMatch::Exact => name == text,
+                }
+            }
+            Filter::NotIgnored(ignore) => !ignore.ignores(tag),
+        }
+    }
+}
+
+fn is_sha(name: &str) -> bool {
+    let is_hex = |s: &str| s.bytes().all(|c| c.is_ascii_hexdigit());
+    if let Some(rest) = name.strip_prefix("sha256-") {
+        let digest = rest.split_once('.').map_or(rest, |(digest, _)| digest);
+        return digest.len() == 64 && is_hex(digest);
+    }
+    name.len() >= MIN_SHA_LEN && is_hex(name) && name.bytes().any(|c| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_apply() {
+        let tags: Vec<_> = ["latest", "1.0.0", "2.0.0-rc1", "1.1.0", "2.0.0"]
+            .into_iter()
+            .map(Tag::new)
+            .collect();
+        let names = |tags: Vec<Tag>| {
+            tags.iter()
+                .map(|t| t.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(Selection::default().apply(tags.clone())),
+            ["2.0.0", "2.0.0-rc1", "1.1.0", "1.0.0", "latest"]
+        );
+
+        #[cfg(feature = "regex")]
+        {
+            let selection = Selection {
+                reverse: true,
+                pattern: Some(Regex::new(r"^\d").unwrap()),
+                limit: Some(2),
+                ..Selection::default()
+            };
+            assert_eq!(names(selection.apply(tags.clone())), ["1.0.0", "1.1.0"]);
+
+            let selection = Selection {
+                pattern: Some(Regex::new(r"^\d").unwrap()),
+                filters: vec![TagFilter::max_name_length(5)],
+                ..Selection::default()
+            };
+            assert_eq!(
+                names(selection.apply(tags.clone())),
+                ["2.0.0", "1.1.0", "1.0.0"]
+            );
+        }
+
+        let selection = Selection {
+            keep_order: true,
+            limit: Some(3),
+            ..Selection::default()
+        };
+        assert_eq!(
+            names(selection.apply(tags)),
+            ["latest", "1.0.0", "2.0.0-rc1"]
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let tags: Vec<_> = [
+            "1.0.0", "latest", "2.0.0", "edge", "1.1.0", "alpha", "stable",
+        ]
+        .into_iter()
+        .map(Tag::new)
+        .collect();
+        let names = |tags: Vec<Tag>| {
+            tags.iter()
+                .map(|t| t.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        let not_aliases: Vec<_> = tags
+            .iter()
+            .filter(|tag| !DEFAULT_ALIASES.contains(&tag.name()))
+            .cloned()
+            .collect();
+
+        // for every order and limit, pinning puts the aliases before the
+        // tags hiding them leaves
+        for keep_order in [false, true] {
+            for reverse in [false, true] {
+                for limit in [None, Some(0), Some(2), Some(10)] {
+                    let selection = |aliases| Selection {
+                        keep_order,
+                        reverse,
+                        limit,
+                        aliases,
+                        ..Selection::default()
+                    };
+                    let rest = selection(Aliases::Sorted).apply(not_aliases.clone());
+                    let hidden = selection(Aliases::hidden()).apply(tags.clone());
+                    assert_eq!(hidden, rest, "{keep_order} {reverse} {limit:?}");
+                    let (aliases, pinned) = selection(Aliases::pinned()).apply_split(tags.clone());
+                    assert_eq!(names(aliases), ["latest", "stable", "edge"]);
+                    assert_eq!(pinned, rest, "{keep_order} {reverse} {limit:?}");
+                }
+            }
+        }
+
+        let selection = Selection {
+            limit: Some(2),
+            aliases: Aliases::pinned(),
+            ..Selection::default()
+        };
+        assert_eq!(
+            names(selection.apply(tags.clone())),
+            ["latest", "stable", "edge", "2.0.0", "1.1.0"]
+        );
+        let selection = Selection {
+            limit: Some(4),
+            ..Selection::default()
+        };
+        assert_eq!(
+            names(selection.apply(tags.clone())),
+            ["2.0.0", "1.1.0", "1.0.0", "alpha"]
+        );
+
+        // the names given, in their order, and only those passing the filters
+        let selection = Selection {
+            keep_order: true,
+            limit: Some(1),
+            aliases: Aliases::Pinned(vec!["edge".to_string(), "latest".to_string()]),
+            filters: vec![TagFilter::max_name_length(5)],
+            ..Selection::default()
+        };
+        assert_eq!(names(selection.apply(tags.clone())), ["edge", "1.0.0"]);
+        let selection = Selection {
+            aliases: Aliases::pinned(),
+            filters: vec![TagFilter::version_like()],
+            ..Selection::default()
+        };
+        assert_eq!(selection.apply_split(tags.clone()).0, Vec::<Tag>::new());
+
+        let hidden = Selection {
+            aliases: Aliases::hidden(),
+            ..Selection::default()
+        };
+        assert!(hidden.is_filtered());
+        assert!(!hidden.matches(&Tag::new("latest")));
+        assert!(hidden.matches(&Tag::new("nightly")));
+        assert!(!Selection::default().is_filtered());
+    }
+
+    fn kept(filter: &TagFilter, names: &[&str]) -> Vec<String> {
+        names
+            .iter()
+            .map(|&name| Tag::new(name))
+            .filter(|tag| filter.matches(tag))
+            .map(|tag| tag.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_sha() {
+        let digest = "a".repeat(64);
+        let names = [
+            "1.27.2",
+            "latest",
+            "9fceb02d0ae598e95dc970b74767f19372d61af8",
+            "9fceb02d0ae5",
+            "9FCEB02D0AE5",
+            // hex, but too short for a SHA
+            "cafe1234",
+            "9fceb02d0ae",
+            // numeric dates and build numbers
+            "20241002",
+            "202410021530",
+            "deadbeefcafe-alpine",
+            &format!("sha256-{digest}"),
+            &format!("sha256-{digest}.sig"),
+            &format!("sha256-{digest}.att"),
+            "sha256-abc",
+        ];
+        assert_eq!(
+            kept(&TagFilter::no_sha(), &names),
+            [
+                "1.27.2",
+                "latest",
+                "cafe1234",
+                "9fceb02d0ae",
+                "20241002",
+                "202410021530",
+                "deadbeefcafe-alpine",
+                "sha256-abc",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_name_length() {
+        let names = ["1.0", "12345", "123456", ""];
+        assert_eq!(
+            kept(&TagFilter::max_name_length(5), &names),
+            ["1.0", "12345", ""]
+        );
+        assert_eq!(kept(&TagFilter::max_name_length(0), &names), [""]);
+    }
+
+    #[test]
+    fn test_version_like() {
+        let names = [
+            "16",
+            "v1.2.3",
+            "3.12-slim",
+            "17rc1",
+            "1.2.3+build.5",
+            "20241002",
+            "latest",
+            "9fceb02d0ae5",
+            "1.0abc",
+            "sha256-abc",
+        ];
+        assert_eq!(
+            kept(&TagFilter::version_like(), &names),
+            [
+                "16",
+                "v1.2.3",
+                "3.12-slim",
+                "17rc1",
+                "1.2.3+build.5",
+                "20241002"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stable() {
+        let names = [
+            "2.0.0-rc1",
+            "2.0.0",
+            "17beta2",
+            "17",
+            "3.12-slim",
+            "1.27.2-alpine",
+            "v1.0.0-beta.1+build",
+            "latest",
+        ];
+        assert_eq!(
+            kept(&TagFilter::stable(), &names),
+            ["2.0.0", "17", "3.12-slim", "1.27.2-alpine", "latest"]
+        );
+    }
+
+    #[test]
+    fn test_version_range() {
+        let range = VersionRange::parse(">=1.2 <2").unwrap();
+        assert_eq!(
+            kept(
+                &TagFilter::version_range(range),
+                &["1.1", "1.2.0", "1.9-alpine", "2.0", "latest"]
+            ),
+            ["1.2.0", "1.9-alpine"]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_pattern_filter() {
+        let filter = TagFilter::pattern(Regex::new("-alpine$").unwrap());
+        assert_eq!(
+            kept(&filter, &["1.0-alpine", "1.0", "alpine"]),
+            ["1.0-alpine"]
+        );
+        let filter = TagFilter::excluding(Regex::new("-alpine$").unwrap());
+        assert_eq!(
+            kept(&filter, &["1.0-alpine", "1.0", "alpine"]),
+            ["1.0", "alpine"]
+        );
+    }
+
+    #[test]
+    fn test_channel_filters() {
+        let names = ["latest", "1.27.2", "stable-alpine", "mainline", "alpine"];
+        assert_eq!(
+            kept(&TagFilter::channels(Channels::default()), &names),
+            ["latest", "stable-alpine", "mainline"]
+        );
+        assert_eq!(
+            kept(&TagFilter::no_channels(Channels::default()), &names),
+            ["1.27.2", "alpine"]
+        );
+    }
+}
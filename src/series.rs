@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use crate::{MAX_VERSION_LEN, Tag, collate};
+
+/// How coarse the version series passed to [`latest_per_series`] are
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeriesLevel {
+    /// `16.4` and `16.3` are both in series `16`
+    Major,
+    /// `3.12.6` and `3.12.5` are both in series `3.12`
+    Minor,
+}
+
+/// The newest tag of a version series, e.g. `3.12` → `3.12.6`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeriesTag {
+    /// Series label, followed by `-<variant>` when variants are kept apart
+    pub series: String,
+    pub tag: Tag,
+}
+
+/// Reduce `tags` to the newest stable tag of each series, newest series first
+///
+/// Tags are parsed leniently: `v16`, `16.4` and `16.4-alpine` are all
+/// versions, while pre-releases (`17rc1`, `3.13.0rc2`, `2.0.0-beta.1`) and
+/// non-versions (`latest`) are left out. Variant suffixes are ignored unless
+/// `per_variant` is set, in which case each variant gets its own series.
+/// Among equal versions the plain tag wins over a variant and the most
+/// specific one (`17.0` over `17`) over an alias.
+pub fn latest_per_series(tags: &[Tag], level: SeriesLevel, per_variant: bool) -> Vec<SeriesTag> {
+    type Key<'a> = (u64, Option<u64>, Option<&'a str>);
+    let mut newest: HashMap<Key, (LenientVersion, &Tag)> = HashMap::new();
+    for tag in tags {
+        let Some(version) = LenientVersion::parse(tag.name()) else {
+            continue;
+        };
+        if version.pre_release {
+            continue;
+        }
+        let minor = match level {
+            SeriesLevel::Major => None,
+            SeriesLevel::Minor => match version.minor {
+                Some(minor) => Some(minor),
+                None => continue, // `3` doesn't tell which minor it is
+            },
+        };
+        let key = (
+            version.major,
+            minor,
+            version.variant.filter(|_| per_variant),
+        );
+        match newest.get(&key) {
+            Some((best, _)) if !version.is_newer_than(best) => {}
+            _ => {
+                newest.insert(key, (version, tag));
+            }
+        }
+    }
+
+    let mut series: Vec<_> = newest.into_iter().collect();
+    // newest series first, the plain one before its variants
+    series.sort_by(|(a, _), (b, _)| {
+        (b.0, b.1)
+            .cmp(&(a.0, a.1))
+            .then_with(|| a.2.is_some().cmp(&b.2.is_some()))
+            .then_with(|| collate(a.2.unwrap_or_default(), b.2.unwrap_or_default()))
+    });
+    series
+        .into_iter()
+        .map(|((major, minor, variant), (_, tag))| {
+            let mut series = match minor {
+                Some(minor) => format!("{major}.{minor}"),
+                None => major.to_string(),
+            };
+            if let Some(variant) = variant {
+                series = format!("{series}-{variant}");
+            }
+            SeriesTag {
+                series,
+                tag: tag.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Distinct version series of `tags`, newest first, e.g. `17`, `16`, `15`
+///
+/// Tags are parsed leniently as for [`latest_per_series`], and variant
+/// suffixes are ignored. Pre-releases count unless `stable` is set, so a
+/// series with only release candidates so far is listed too.
+pub fn version_series(tags: &[Tag], level: SeriesLevel, stable: bool) -> Vec<String> {
+    let mut series: Vec<(u64, Option<u64>)> = tags
+        .iter()
+        .filter_map(|tag| LenientVersion::parse(tag.name()))
+        .filter(|version| !(stable && version.pre_release))
+        .filter_map(|version| match level {
+            SeriesLevel::Major => Some((version.major, None)),
+            SeriesLevel::Minor => Some((version.major, Some(version.minor?))),
+        })
+        .collect();
+    series.sort_unstable_by(|a, b| b.cmp(a));
+    series.dedup();
+    series
+        .into_iter()
+        .map(|(major, minor)| match minor {
+            Some(minor) => format!("{major}.{minor}"),
+            None => major.to_string(),
+        })
+        .collect()
+}
+
+/// A version read from a tag name without requiring strict semver, as
+/// [`latest_per_series`] and [`version_series`] do
+#[derive(Debug, Eq, PartialEq)]
+pub struct LenientVersion<'a> {
+    pub major: u64,
+    pub minor: Option<u64>,
+    pub patch: Option<u64>,
+    pub pre_release: bool,
+    /// Suffix after the version, e.g. `alpine` or `slim-bookworm`
+    pub variant: Option<&'a str>,
+}
+
+impl<'a> LenientVersion<'a> {
+    /// Read `name` as a version, `None` if it isn't one, has numbers too
+    /// large for `u64` or is longer than 256 characters
+    pub fn parse(name: &'a str) -> Option<Self> {
+        if name.len() > MAX_VERSION_LEN {
+            return None;
+        }
+        let name = name.strip_prefix('v').unwrap_or(name);
+        let end = name
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(name.len());
+        let (numbers, rest) = name.split_at(end);
+        let mut parts = numbers.split('.').map(|part| part.parse::<u64>().ok());
+        let major = parts.next()??;
+        let minor = parts.next().map(|part| part.ok_or(())).transpose().ok()?;
+        let patch = parts.next().map(|part| part.ok_or(())).transpose().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let mut version = LenientVersion {
+            major,
+            minor,
+            patch,
+            pre_release: false,
+            variant: None,
+        };
+        match rest.strip_prefix('-') {
+            _ if rest.is_empty() => {}
+            Some(suffix) if is_pre_release(suffix) => version.pre_release = true,
+            Some("") => return None,
+            Some(suffix) => version.variant = Some(suffix),
+            // `3.13.0rc2`, but not `1.0abc`
+            None if is_pre_release(rest) => version.pre_release = true,
+            None => return None,
+        }
+        Some(version)
+    }
+
+    /// Newer version, or the same one spelled more usefully
+    fn is_newer_than(&self, other: &Self) -> bool {
+        let rank = |v: &Self| (v.major, v.minor, v.patch, v.variant.is_none());
+        rank(self) > rank(other)
+    }
+}
+
+/// `rc1`, `beta.2`, `a1` and the like
+fn is_pre_release(suffix: &str) -> bool {
+    let word_end = suffix
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(suffix.len());
+    let (word, rest) = suffix.split_at(word_end);
+    matches!(
+        word.to_ascii_lowercase().as_str(),
+        "a" | "b" | "alpha" | "beta" | "rc" | "pre" | "preview" | "dev"
+    ) && rest
+        .chars()
+        .next()
+        .is_none_or(|c| c.is_ascii_digit() || matches!(c, '.' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(text: &str) -> Vec<Tag> {
+        text.lines().map(Tag::new).collect()
+    }
+
+    #[test]
+    fn test_lenient_version_parse() {
+        let cases = [
+            ("16", Some((16, None, None, false, None))),
+            ("v1.2.3", Some((1, Some(2), Some(3), false, None))),
+            (
+                "16.4-alpine",
+                Some((16, Some(4), None, false, Some("alpine"))),
+            ),
+            (
+                "3.12.6-slim-bookworm",
+                Some((3, Some(12), Some(6), false, Some("slim-bookworm"))),
+            ),
+            ("3.13.0rc2", Some((3, Some(13), Some(0), true, None))),
+            ("2.0.0-beta.1", Some((2, Some(0), Some(0), true, None))),
+            ("17-rc1", Some((17, None, None, true, None))),
+            ("latest", None),
+            ("1.0abc", None),
+            ("1..2", None),
+            ("1.2.3.4", None),
+            ("1.2-", None),
+        ];
+        for (name, expected) in cases {
+            let version = LenientVersion::parse(name)
+                .map(|v| (v.major, v.minor, v.patch, v.pre_release, v.variant));
+            assert_eq!(version, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_version_series() {
+        let postgres = fixture(include_str!("../tests/fixtures/postgres_tags.txt"));
+        let python = fixture(include_str!("../tests/fixtures/python_tags.txt"));
+        let cases: [(&[Tag], SeriesLevel, bool, &[&str]); 5] = [
+            (
+                &postgres,
+                SeriesLevel::Major,
+                true,
+                &["17", "16", "15", "14", "13"],
+            ),
+            (
+                &postgres,
+                SeriesLevel::Minor,
+                true,
+                &[
+                    "17.0", "16.4", "16.3", "15.8", "15.7", "14.13", "14.12", "13.16", "13.15",
+                ],
+            ),
+            (
+                &python,
+                SeriesLevel::Minor,
+                true,
+                &["3.12", "3.11", "3.10", "2.7"],
+            ),
+            // `3.13.0rc2` and `3.13-rc`
+            (
+                &python,
+                SeriesLevel::Minor,
+                false,
+                &["3.13", "3.12", "3.11", "3.10", "2.7"],
+            ),
+            (&python, SeriesLevel::Major, false, &["3", "2"]),
+        ];
+        for (tags, level, stable, expected) in cases {
+            assert_eq!(
+                version_series(tags, level, stable),
+                expected,
+                "{level:?}, stable: {stable}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_lenient_version_limits() {
+        let digits = "9".repeat(300);
+        let long_variant = format!("1.0-{}", "a".repeat(300));
+        let cases = [
+            ("18446744073709551615", Some((u64::MAX, None))),
+            ("18446744073709551616", None),
+            ("1.18446744073709551616", None),
+            ("1.2.99999999999999999999-alpine", None),
+            ("1.2.3+build.18446744073709551616", None),
+            (digits.as_str(), None),
+            (long_variant.as_str(), None),
+            (&long_variant[..MAX_VERSION_LEN], Some((1, Some(0)))),
+        ];
+        for (name, expected) in cases {
+            let version = LenientVersion::parse(name).map(|v| (v.major, v.minor));
+            assert_eq!(version, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_latest_per_series() {
+        let postgres = fixture(include_str!("../tests/fixtures/postgres_tags.txt"));
+        let python = fixture(include_str!("../tests/fixtures/python_tags.txt"));
+        type Case<'a> = (&'a [Tag], SeriesLevel, bool, &'a [(&'a str, &'a str)]);
+        let cases: [Case; 4] = [
+            (
+                &postgres,
+                SeriesLevel::Major,
+                false,
+                &[
+                    ("17", "17.0"),
+                    ("16", "16.4"),
+                    ("15", "15.8"),
+                    ("14", "14.13"),
+                    ("13", "13.16-alpine"),
+                ],
+            ),
+            (
+                &postgres,
+                SeriesLevel::Major,
+                true,
+                &[
+                    ("17", "17.0"),
+                    ("17-alpine", "17.0-alpine"),
+                    ("17-bookworm", "17.0-bookworm"),
+                    ("16", "16.4"),
+                    ("16-alpine", "16.4-alpine"),
+                    ("16-bullseye", "16.3-bullseye"),
+                    ("15", "15.8"),
+                    ("15-alpine3.20", "15.8-alpine3.20"),
+                    ("14", "14.13"),
+                    ("14-bookworm", "14.13-bookworm"),
+                    ("13", "13.15"),
+                    ("13-alpine", "13.16-alpine"),
+                ],
+            ),
+            (
+                &python,
+                SeriesLevel::Minor,
+                false,
+                &[
+                    ("3.12", "3.12.7-alpine"),
+                    ("3.11", "3.11.10"),
+                    ("3.10", "3.10.15-windowsservercore"),
+                    ("2.7", "2.7.18"),
+                ],
+            ),
+            (
+                &python,
+                SeriesLevel::Major,
+                false,
+                &[("3", "3.12.7-alpine"), ("2", "2.7.18")],
+            ),
+        ];
+        for (tags, level, per_variant, expected) in cases {
+            let series: Vec<_> = latest_per_series(tags, level, per_variant)
+                .into_iter()
+                .map(|s| (s.series, s.tag.name().to_string()))
+                .collect();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|(s, t)| (s.to_string(), t.to_string()))
+                .collect();
+            assert_eq!(series, expected, "{level:?}, per_variant: {per_variant}");
+        }
+    }
+}
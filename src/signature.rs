@@ -0,0 +1,67 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    Client, Image, Secret, Tag,
+    manifest::{COSIGN_SIGNATURE, SIGSTORE_BUNDLE},
+};
+
+/// Detects cosign signatures attached to tags, by a `sha256-<hex>.sig` tag or
+/// a signature referrer
+///
+/// Only the presence of a signature is checked; nothing is verified
+/// cryptographically. Results are cached per digest, so aliases of the same
+/// image are looked up once.
+#[derive(Debug)]
+pub struct SignatureLookup<'a> {
+    client: &'a Client,
+    image: &'a Image,
+    tags: HashSet<&'a str>,
+    signed: HashMap<String, bool>,
+    token: Secret,
+}
+
+impl<'a> SignatureLookup<'a> {
+    /// `tags` is the image's full listing, where signature tags are looked up
+    pub fn new(client: &'a Client, image: &'a Image, tags: &'a [Tag]) -> Self {
+        SignatureLookup {
+            client,
+            image,
+            tags: tags.iter().map(Tag::name).collect(),
+            signed: HashMap::new(),
+            token: Secret::default(),
+        }
+    }
+
+    /// Whether `tag` has a signature attached
+    pub async fn is_signed(&mut self, tag: &Tag) -> Result<bool> {
+        let digest = self
+            .client
+            .resolve_digest(self.image, tag.name(), &mut self.token)
+            .await
+            .with_context(|| format!("Failed to resolve digest of {tag}"))?;
+        if let Some(&signed) = self.signed.get(&digest) {
+            return Ok(signed);
+        }
+
+        let signature_tag = format!("{}.sig", digest.replacen(':', "-", 1));
+        let signed = self.tags.contains(signature_tag.as_str())
+            || self
+                .client
+                .native_referrers(self.image, &digest, &mut self.token)
+                .await
+                .with_context(|| format!("Failed to list referrers of {tag}"))?
+                .unwrap_or_default()
+                .iter()
+                .any(|r| {
+                    matches!(
+                        r.artifact_type.as_deref(),
+                        Some(COSIGN_SIGNATURE | SIGSTORE_BUNDLE)
+                    )
+                });
+        self.signed.insert(digest, signed);
+
+        Ok(signed)
+    }
+}
@@ -0,0 +1,160 @@
+use std::{collections::HashSet, time::SystemTime};
+
+use crate::{Tag, collate, series::LenientVersion};
+
+/// Overview of a repository's tags, see [`TagStats::compute`]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TagStats {
+    pub tags: usize,
+    /// Tags read as versions, pre-releases included
+    pub versions: usize,
+    pub pre_releases: usize,
+    /// Distinct `16` series
+    pub majors: usize,
+    /// Distinct `16.4` series
+    pub minors: usize,
+    /// Newest and oldest stable versions, plain tags winning over variants
+    pub newest: Option<Tag>,
+    pub oldest: Option<Tag>,
+    /// Distinct variant suffixes of stable versions (`alpine`, `slim-bookworm`),
+    /// sorted by [`collate`]
+    pub variants: Vec<String>,
+    /// When the repository was last pushed to, which [`TagStats::compute`]
+    /// leaves to [`Client::fetch_last_pushed`](crate::Client::fetch_last_pushed)
+    pub last_pushed: Option<SystemTime>,
+}
+
+impl TagStats {
+    /// Aggregate `tags`, parsing versions leniently like [`latest_per_series`](crate::latest_per_series)
+    pub fn compute(tags: &[Tag]) -> Self {
+        let mut stats = TagStats {
+            tags: tags.len(),
+            ..TagStats::default()
+        };
+        let mut majors = HashSet::new();
+        let mut minors = HashSet::new();
+        let mut variants = HashSet::new();
+        let mut stable = Vec::new();
+        for tag in tags {
+            let Some(version) = LenientVersion::parse(tag.name()) else {
+                continue;
+            };
+            stats.versions += 1;
+            majors.insert(version.major);
+            if let Some(minor) = version.minor {
+                minors.insert((version.major, minor));
+            }
+            if version.pre_release {
+                stats.pre_releases += 1;
+                continue;
+            }
+            if let Some(variant) = version.variant {
+                variants.insert(variant.to_string());
+            }
+            stable.push((version, tag));
+        }
+
+        let key = |(v, tag): &&(LenientVersion, &Tag)| {
+            (
+                v.major,
+                v.minor,
+                v.patch,
+                v.variant.is_none(),
+                tag.name().to_string(),
+            )
+        };
+        stats.newest = stable.iter().max_by_key(key).map(|(_, tag)| (*tag).clone());
+        let key = |(v, tag): &&(LenientVersion, &Tag)| {
+            (
+                v.major,
+                v.minor,
+                v.patch,
+                v.variant.is_some(),
+                tag.name().to_string(),
+            )
+        };
+        stats.oldest = stable.iter().min_by_key(key).map(|(_, tag)| (*tag).clone());
+        stats.majors = majors.len();
+        stats.minors = minors.len();
+        stats.variants = variants.into_iter().collect();
+        stats.variants.sort_by(|a, b| collate(a, b));
+        stats
+    }
+
+    /// Share of the versions which are pre-releases, 0 without versions
+    pub fn pre_release_ratio(&self) -> f64 {
+        match self.versions {
+            0 => 0.0,
+            versions => self.pre_releases as f64 / versions as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(text: &str) -> Vec<Tag> {
+        text.lines().map(Tag::new).collect()
+    }
+
+    #[test]
+    fn test_compute() {
+        let postgres = TagStats::compute(&fixture(include_str!(
+            "../tests/fixtures/postgres_tags.txt"
+        )));
+        assert_eq!(
+            postgres,
+            TagStats {
+                tags: 26,
+                versions: 23,
+                pre_releases: 3,
+                majors: 5,
+                minors: 9,
+                newest: Some(Tag::new("17.0")),
+                oldest: Some(Tag::new("13.15")),
+                variants: ["alpine", "alpine3.20", "bookworm", "bullseye"]
+                    .map(String::from)
+                    .to_vec(),
+                last_pushed: None,
+            }
+        );
+
+        let python = TagStats::compute(&fixture(include_str!("../tests/fixtures/python_tags.txt")));
+        assert_eq!(
+            (python.tags, python.versions, python.pre_releases),
+            (19, 17, 3)
+        );
+        assert_eq!((python.majors, python.minors), (2, 5));
+        assert_eq!(python.newest, Some(Tag::new("3.12.7-alpine")));
+        assert_eq!(python.oldest, Some(Tag::new("2.7.18")));
+        assert_eq!(
+            python.variants,
+            [
+                "alpine",
+                "buster",
+                "slim",
+                "slim-bookworm",
+                "windowsservercore"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compute_tiny_repositories() {
+        assert_eq!(TagStats::compute(&[]), TagStats::default());
+        assert_eq!(TagStats::compute(&[]).pre_release_ratio(), 0.0);
+
+        let stats = TagStats::compute(&[Tag::new("latest")]);
+        assert_eq!((stats.tags, stats.versions, stats.newest), (1, 0, None));
+
+        let stats = TagStats::compute(&[Tag::new("1.0.0")]);
+        assert_eq!(stats.newest, Some(Tag::new("1.0.0")));
+        assert_eq!(stats.newest, stats.oldest);
+        assert_eq!((stats.majors, stats.minors), (1, 1));
+
+        let stats = TagStats::compute(&[Tag::new("2.0.0-rc1")]);
+        assert_eq!(stats.pre_release_ratio(), 1.0);
+        assert_eq!((stats.versions, stats.newest), (1, None));
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use tokio::{
+    sync::{Mutex, Semaphore, SemaphorePermit},
+    time::{Instant, sleep_until},
+};
+
+/// Limits on the requests sent to one registry, shared by all clones of a
+/// [`Client`](crate::Client)
+///
+/// A permit is only held while a request is sent, never across a token
+/// request or a retry, so nested lookups can't deadlock.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    permits: Option<Semaphore>,
+    interval: Duration,
+    /// Earliest time the next request may start
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(max_concurrent: Option<usize>, interval: Duration) -> Self {
+        Throttle {
+            permits: max_concurrent.map(|n| Semaphore::new(n.max(1))),
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for a free slot and the politeness delay, returning the slot
+    pub(crate) async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = match &self.permits {
+            Some(permits) => permits.acquire().await.ok(),
+            None => None,
+        };
+        if !self.interval.is_zero() {
+            let start = {
+                let mut next = self.next.lock().await;
+                let start = (*next).max(Instant::now());
+                *next = start + self.interval;
+                start
+            };
+            sleep_until(start).await;
+        }
+        permit
+    }
+}
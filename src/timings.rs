@@ -0,0 +1,167 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// What a request sent by a [`Client`](crate::Client) was for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RequestKind {
+    Token,
+    /// The `/v2/` ping asking for a challenge
+    Ping,
+    /// A page of tags, from the registry or its search API
+    TagsPage,
+    /// A manifest, index or list of referrers
+    Manifest,
+    Blob,
+    /// Catalog pages, GitHub API requests
+    Other,
+}
+
+impl RequestKind {
+    const ALL: [RequestKind; 6] = [
+        RequestKind::Token,
+        RequestKind::Ping,
+        RequestKind::TagsPage,
+        RequestKind::Manifest,
+        RequestKind::Blob,
+        RequestKind::Other,
+    ];
+
+    fn phase(self) -> Option<Phase> {
+        match self {
+            RequestKind::Token | RequestKind::Ping => Some(Phase::Auth),
+            RequestKind::TagsPage => Some(Phase::Pagination),
+            RequestKind::Manifest | RequestKind::Blob => Some(Phase::Enrichment),
+            RequestKind::Other => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Auth,
+    Pagination,
+    Enrichment,
+}
+
+/// First request start and last response of a phase, in nanoseconds since
+/// the recorder was created
+#[derive(Debug)]
+struct Span {
+    first: AtomicU64,
+    last: AtomicU64,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span {
+            first: AtomicU64::new(u64::MAX),
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Span {
+    fn wall(&self) -> Duration {
+        let (first, last) = (
+            self.first.load(Ordering::Relaxed),
+            self.last.load(Ordering::Relaxed),
+        );
+        Duration::from_nanos(last.saturating_sub(first))
+    }
+}
+
+/// Counters shared by all clones of a [`Client`](crate::Client)
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    origin: Instant,
+    requests: [AtomicUsize; RequestKind::ALL.len()],
+    spans: [Span; 3],
+    bytes: AtomicU64,
+    retries: AtomicUsize,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Recorder {
+            origin: Instant::now(),
+            requests: Default::default(),
+            spans: Default::default(),
+            bytes: AtomicU64::new(0),
+            retries: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Recorder {
+    /// Count a request of `kind` sent at `started` whose response just came
+    /// in, with a body of `bytes` if known
+    pub(crate) fn request(&self, kind: RequestKind, started: Instant, bytes: Option<u64>) {
+        self.requests[kind as usize].fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes.unwrap_or(0), Ordering::Relaxed);
+        if let Some(phase) = kind.phase() {
+            let since = |at: Instant| at.duration_since(self.origin).as_nanos() as u64;
+            let span = &self.spans[phase as usize];
+            span.first.fetch_min(since(started), Ordering::Relaxed);
+            span.last
+                .fetch_max(since(Instant::now()), Ordering::Relaxed);
+        }
+    }
+
+    /// Count a request sent again, after a challenge or a denial, or a listing
+    /// started over
+    pub(crate) fn retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn timings(&self) -> Timings {
+        let requests = |kind: RequestKind| self.requests[kind as usize].load(Ordering::Relaxed);
+        Timings {
+            token_requests: requests(RequestKind::Token),
+            tags_requests: requests(RequestKind::TagsPage),
+            manifest_requests: requests(RequestKind::Manifest),
+            blob_requests: requests(RequestKind::Blob),
+            other_requests: requests(RequestKind::Ping) + requests(RequestKind::Other),
+            bytes_received: self.bytes.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            auth: self.spans[Phase::Auth as usize].wall(),
+            pagination: self.spans[Phase::Pagination as usize].wall(),
+            enrichment: self.spans[Phase::Enrichment as usize].wall(),
+        }
+    }
+}
+
+/// Requests a [`Client`](crate::Client) and its clones sent so far, see
+/// [`Client::timings`](crate::Client::timings)
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Timings {
+    pub token_requests: usize,
+    /// Pages of tags, from the registry or its search API
+    pub tags_requests: usize,
+    /// Manifests, indexes and lists of referrers
+    pub manifest_requests: usize,
+    pub blob_requests: usize,
+    /// `/v2/` pings, catalog pages, GitHub API requests
+    pub other_requests: usize,
+    /// Bytes of the response bodies whose length the registry gave
+    pub bytes_received: u64,
+    /// Requests sent again after a challenge or a denial, and listings
+    /// started over
+    pub retries: usize,
+    /// Wall time from the first request of each phase to its last response:
+    /// tokens (and pings), pages of tags, then manifests and blobs
+    pub auth: Duration,
+    pub pagination: Duration,
+    pub enrichment: Duration,
+}
+
+impl Timings {
+    pub fn requests(&self) -> usize {
+        self.token_requests
+            + self.tags_requests
+            + self.manifest_requests
+            + self.blob_requests
+            + self.other_requests
+    }
+}
@@ -0,0 +1,97 @@
+use std::{error::Error as _, fmt, io, str::FromStr};
+
+use anyhow::anyhow;
+use rustls::{AlertDescription, PeerIncompatible};
+
+/// Oldest TLS version connections may use, see
+/// [`ClientBuilder::min_tls_version`](crate::ClientBuilder::min_tls_version)
+///
+/// Versions below 1.2 are never negotiated, so they can't be asked for.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum TlsVersion {
+    #[default]
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    pub(crate) fn to_reqwest(self) -> reqwest::tls::Version {
+        match self {
+            TlsVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsVersion::Tls12 => write!(f, "TLS 1.2"),
+            TlsVersion::Tls13 => write!(f, "TLS 1.3"),
+        }
+    }
+}
+
+/// `1.2` or `1.3`, optionally prefixed with `TLS`
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = match s.get(..3) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("tls") => s[3..].trim_start(),
+            _ => s,
+        };
+        match version {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            "1.0" | "1.1" => Err(anyhow!(
+                "TLS {version} can't be allowed, connections use TLS 1.2 or later"
+            )),
+            _ => Err(anyhow!("Invalid TLS version {s:?}, expected 1.2 or 1.3")),
+        }
+    }
+}
+
+/// Whether `err` failed the handshake because the server and the client had
+/// no TLS version in common
+pub(crate) fn is_version_mismatch(err: &reqwest::Error) -> bool {
+    let mut cause = err.source();
+    while let Some(mut err) = cause {
+        // the TLS stream reports its errors as I/O errors holding them,
+        // which may be wrapped in further I/O errors
+        while let Some(inner) = err.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+            err = inner;
+        }
+        if let Some(tls) = err.downcast_ref::<rustls::Error>() {
+            return matches!(
+                tls,
+                rustls::Error::PeerIncompatible(
+                    PeerIncompatible::ServerDoesNotSupportTls12Or13
+                        | PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig
+                        | PeerIncompatible::SupportedVersionsExtensionRequired
+                ) | rustls::Error::AlertReceived(AlertDescription::ProtocolVersion)
+            );
+        }
+        cause = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("1.2".parse::<TlsVersion>().unwrap(), TlsVersion::Tls12);
+        assert_eq!("TLS1.3".parse::<TlsVersion>().unwrap(), TlsVersion::Tls13);
+        assert_eq!("tls 1.3".parse::<TlsVersion>().unwrap(), TlsVersion::Tls13);
+        let err = "1.1".parse::<TlsVersion>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TLS 1.1 can't be allowed, connections use TLS 1.2 or later"
+        );
+        assert!("1.4".parse::<TlsVersion>().is_err());
+        assert!(TlsVersion::Tls12 < TlsVersion::Tls13);
+    }
+}
@@ -0,0 +1,28 @@
+//! Experimental APIs, compiled with the `unstable` feature
+//!
+//! Whatever is here is exempt from semantic versioning while its shape
+//! settles: it may change or go away in any release, minor ones included.
+//! Depend on it knowingly, and pin the crate's version if you do.
+//!
+//! Besides this module, the feature enables [`ClientBuilder::on_page`] and
+//! [`Client::exchange_token`], which take and return types from it.
+//!
+//! [`ClientBuilder::on_page`]: crate::ClientBuilder::on_page
+//! [`Client::exchange_token`]: crate::Client::exchange_token
+
+pub use crate::reference::Reference;
+
+/// Token exchanges and `WWW-Authenticate` challenges
+pub mod auth {
+    pub use crate::auth::*;
+}
+
+/// RFC 3339 timestamps, as image configs and registry APIs write them
+pub mod time {
+    pub use crate::manifest::{format_rfc3339, parse_rfc3339};
+}
+
+/// What [`ClientBuilder`](crate::ClientBuilder) passes to the hooks it takes
+pub mod hooks {
+    pub use crate::client::PageInfo;
+}
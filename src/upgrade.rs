@@ -0,0 +1,340 @@
+use crate::{Tag, collate, series::LenientVersion};
+
+/// How far [`suggest_upgrade`] may move away from the current tag, each
+/// scope allowing more than the one before
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub enum UpgradeScope {
+    /// Same major and minor version, i.e. the same patch series:
+    /// `7.2.4` → `7.2.5`
+    #[default]
+    Patch,
+    /// Same major version, newer minor ones included: `7.2.4` → `7.4.1`
+    Minor,
+    /// At most one major version up: `6.2.14` → `7.4.1`, not `8.0.0`
+    Major,
+    /// Any newer version: `6.2.14` → `8.0.0`
+    Latest,
+}
+
+/// What [`suggest_upgrade`] may suggest
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UpgradePolicy {
+    pub scope: UpgradeScope,
+    /// Whether pre-releases (`7.4.2-rc1`) may be suggested
+    pub pre_releases: bool,
+    /// Whether the suggestion keeps the current variant suffix (`-alpine`),
+    /// or may have any, the plain tag winning among equal versions
+    pub keep_variant: bool,
+}
+
+impl Default for UpgradePolicy {
+    fn default() -> Self {
+        UpgradePolicy {
+            scope: UpgradeScope::default(),
+            pre_releases: false,
+            keep_variant: true,
+        }
+    }
+}
+
+/// The tag to upgrade to and why it was picked
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Suggestion {
+    pub tag: Tag,
+    /// E.g. `newest 7.2 release with variant alpine`
+    pub reason: String,
+}
+
+/// Newest tag among `tags` to upgrade `current` to under `policy`, `None` if
+/// `current` isn't a version or nothing newer qualifies
+///
+/// Tags are parsed leniently as for [`latest_per_series`](crate::latest_per_series).
+/// Suggestions are spelled like `current`: `7.2` only upgrades to other
+/// `x.y` tags and `7.2.4` to `x.y.z` ones. A pre-release current tag
+/// upgrades to its own release.
+pub fn suggest_upgrade(current: &str, tags: &[Tag], policy: UpgradePolicy) -> Option<Suggestion> {
+    let current = LenientVersion::parse(current)?;
+    let rank = |v: &LenientVersion| (v.major, v.minor, v.patch, !v.pre_release);
+    let shape = |v: &LenientVersion| (v.minor.is_some(), v.patch.is_some());
+
+    let mut best: Option<(LenientVersion, &Tag)> = None;
+    for tag in tags {
+        let Some(version) = LenientVersion::parse(tag.name()) else {
+            continue;
+        };
+        let in_scope = match policy.scope {
+            UpgradeScope::Patch => (version.major, version.minor) == (current.major, current.minor),
+            UpgradeScope::Minor => version.major == current.major,
+            UpgradeScope::Major => version.major <= current.major.saturating_add(1),
+            UpgradeScope::Latest => true,
+        };
+        if !in_scope
+            || shape(&version) != shape(&current)
+            || rank(&version) <= rank(&current)
+            || (version.pre_release && !policy.pre_releases)
+            || (policy.keep_variant && version.variant != current.variant)
+        {
+            continue;
+        }
+        let better = match &best {
+            None => true,
+            Some((other, other_tag)) => {
+                let rank = |v: &LenientVersion| (rank(v), v.variant.is_none());
+                rank(&version)
+                    .cmp(&rank(other))
+                    .then_with(|| collate(other_tag.name(), tag.name()))
+                    .is_gt()
+            }
+        };
+        if better {
+            best = Some((version, tag));
+        }
+    }
+
+    let (version, tag) = best?;
+    let mut reason = match policy.scope {
+        UpgradeScope::Patch => match current.minor {
+            Some(minor) => format!("newest {}.{minor} release", current.major),
+            None => format!("newest {} release", current.major),
+        },
+        UpgradeScope::Minor => format!("newest {} release", current.major),
+        UpgradeScope::Major => format!(
+            "newest {} or {} release",
+            current.major,
+            current.major.saturating_add(1)
+        ),
+        UpgradeScope::Latest => "newest release".to_string(),
+    };
+    if let Some(variant) = current.variant.filter(|_| policy.keep_variant) {
+        reason += &format!(" with variant {variant}");
+    }
+    if version.pre_release {
+        reason += ", a pre-release";
+    }
+    Some(Suggestion {
+        tag: tag.clone(),
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_upgrade() {
+        let redis: Vec<_> = include_str!("../tests/fixtures/redis_tags.txt")
+            .lines()
+            .map(Tag::new)
+            .collect();
+        let policy = |scope, pre_releases, keep_variant| UpgradePolicy {
+            scope,
+            pre_releases,
+            keep_variant,
+        };
+        use UpgradeScope::*;
+        let cases = [
+            (
+                "7.2.4",
+                policy(Patch, false, true),
+                Some(("7.2.5", "newest 7.2 release")),
+            ),
+            (
+                "7.2.4-alpine",
+                policy(Patch, false, true),
+                Some(("7.2.5-alpine", "newest 7.2 release with variant alpine")),
+            ),
+            // without the variant kept, any newer one may be suggested
+            (
+                "7.2.4-alpine",
+                policy(Patch, false, false),
+                Some(("7.2.6-bookworm", "newest 7.2 release")),
+            ),
+            (
+                "7.2.4",
+                policy(Patch, false, false),
+                Some(("7.2.6-bookworm", "newest 7.2 release")),
+            ),
+            ("7.2.5", policy(Patch, false, true), None),
+            (
+                "7.2.4",
+                policy(Minor, false, true),
+                Some(("7.4.1", "newest 7 release")),
+            ),
+            (
+                "7.2.4",
+                policy(Minor, true, true),
+                Some(("7.4.2-rc1", "newest 7 release, a pre-release")),
+            ),
+            // the variant after a pre-release isn't read, so `7.4.2-rc1-alpine`
+            // doesn't count as one
+            (
+                "7.2.4-alpine",
+                policy(Minor, true, true),
+                Some(("7.4.1-alpine", "newest 7 release with variant alpine")),
+            ),
+            (
+                "7.2.4",
+                policy(Latest, false, true),
+                Some(("8.0.0", "newest release")),
+            ),
+            (
+                "7.2.4",
+                policy(Latest, true, true),
+                Some(("8.0.1-rc1", "newest release, a pre-release")),
+            ),
+            (
+                "6.2.14-alpine",
+                policy(Latest, false, true),
+                Some(("8.0.0-alpine", "newest release with variant alpine")),
+            ),
+            // tags are suggested in the same spelling
+            (
+                "7.2",
+                policy(Minor, false, true),
+                Some(("7.4", "newest 7 release")),
+            ),
+            ("7.2", policy(Patch, false, true), None),
+            (
+                "7",
+                policy(Latest, false, true),
+                Some(("8", "newest release")),
+            ),
+            ("7-alpine", policy(Latest, false, true), None),
+            (
+                "6.2",
+                policy(Latest, false, true),
+                Some(("8.0", "newest release")),
+            ),
+            // a pre-release upgrades to its release
+            ("7.4.2-rc1", policy(Patch, false, true), None),
+            ("8.0.1-rc1", policy(Latest, true, true), None),
+            (
+                "7.4.0-rc1",
+                policy(Patch, false, true),
+                Some(("7.4.1", "newest 7.4 release")),
+            ),
+            ("latest", policy(Latest, true, false), None),
+            ("9.0.0", policy(Latest, true, false), None),
+        ];
+        for (current, policy, expected) in cases {
+            let suggestion = suggest_upgrade(current, &redis, policy);
+            let suggestion = suggestion
+                .as_ref()
+                .map(|s| (s.tag.name(), s.reason.as_str()));
+            assert_eq!(suggestion, expected, "{current}, {policy:?}");
+        }
+    }
+
+    #[test]
+    fn test_suggest_upgrade_python() {
+        let python: Vec<_> = include_str!("../tests/fixtures/python_tags.txt")
+            .lines()
+            .map(Tag::new)
+            .collect();
+        let upgrade = |current, scope| {
+            let policy = UpgradePolicy {
+                scope,
+                ..UpgradePolicy::default()
+            };
+            suggest_upgrade(current, &python, policy).map(|s| s.tag.name().to_string())
+        };
+        assert_eq!(
+            upgrade("3.12.5", UpgradeScope::Patch).as_deref(),
+            Some("3.12.6")
+        );
+        assert_eq!(
+            upgrade("3.12.5-alpine", UpgradeScope::Patch).as_deref(),
+            Some("3.12.7-alpine")
+        );
+        assert_eq!(upgrade("3.11.9-slim", UpgradeScope::Latest), None);
+        assert_eq!(
+            upgrade("3.10.14", UpgradeScope::Latest).as_deref(),
+            Some("3.12.6")
+        );
+        assert_eq!(upgrade("2.7.18-buster", UpgradeScope::Minor), None);
+    }
+
+    #[test]
+    fn test_suggest_upgrade_scopes() {
+        let fixture = |text: &str| -> Vec<Tag> { text.lines().map(Tag::new).collect() };
+        let redis = fixture(include_str!("../tests/fixtures/redis_tags.txt"));
+        let python = fixture(include_str!("../tests/fixtures/python_tags.txt"));
+        // suggestions within the patch series, the major version, one major
+        // version up and anywhere
+        let cases = [
+            (
+                &redis,
+                "7.2.4",
+                [Some("7.2.5"), Some("7.4.1"), Some("8.0.0"), Some("8.0.0")],
+            ),
+            (
+                &redis,
+                "7.2.4-alpine",
+                [
+                    Some("7.2.5-alpine"),
+                    Some("7.4.1-alpine"),
+                    Some("8.0.0-alpine"),
+                    Some("8.0.0-alpine"),
+                ],
+            ),
+            (&redis, "6.2.14", [None, None, Some("7.4.1"), Some("8.0.0")]),
+            (&redis, "6.2", [None, None, Some("7.4"), Some("8.0")]),
+            (&redis, "7.2", [None, Some("7.4"), Some("8.0"), Some("8.0")]),
+            (&redis, "8.0.0", [None, None, None, None]),
+            (
+                &python,
+                "3.12.5",
+                [
+                    Some("3.12.6"),
+                    Some("3.12.6"),
+                    Some("3.12.6"),
+                    Some("3.12.6"),
+                ],
+            ),
+            (&python, "3.11.9-slim", [None, None, None, None]),
+            (
+                &python,
+                "3.10.14",
+                [None, Some("3.12.6"), Some("3.12.6"), Some("3.12.6")],
+            ),
+            (
+                &python,
+                "2.7.18",
+                [None, None, Some("3.12.6"), Some("3.12.6")],
+            ),
+        ];
+        let scopes = [
+            UpgradeScope::Patch,
+            UpgradeScope::Minor,
+            UpgradeScope::Major,
+            UpgradeScope::Latest,
+        ];
+        for (tags, current, expected) in cases {
+            for (scope, expected) in scopes.into_iter().zip(expected) {
+                let policy = UpgradePolicy {
+                    scope,
+                    ..UpgradePolicy::default()
+                };
+                let suggestion = suggest_upgrade(current, tags, policy);
+                assert_eq!(
+                    suggestion.as_ref().map(|s| s.tag.name()),
+                    expected,
+                    "{current}, {scope:?}"
+                );
+            }
+        }
+        let reason = |current, scope| {
+            let policy = UpgradePolicy {
+                scope,
+                ..UpgradePolicy::default()
+            };
+            suggest_upgrade(current, &redis, policy).unwrap().reason
+        };
+        assert_eq!(
+            reason("6.2.14", UpgradeScope::Major),
+            "newest 6 or 7 release"
+        );
+        assert_eq!(reason("7.2.4", UpgradeScope::Minor), "newest 7 release");
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// A non-fatal issue encountered while listing tags
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Warning {
+    /// Per-tag metadata (e.g. a manifest) couldn't be fetched
+    ManifestFetchFailed { tag: String, source: String },
+    /// The tag's image config has no valid `created` timestamp; it was skipped
+    NoCreatedDate { tag: String },
+    /// The registry returned a name which isn't a valid tag; it was skipped
+    InvalidTagName { raw: String },
+    /// The registry returned the same tag more than once; duplicates were skipped
+    DuplicateTag { name: String },
+    /// A page started at or before the tag the previous one ended with, as
+    /// when tags are deleted while paging; tags may be missing
+    InconsistentPage { last: String, first: String },
+    /// The registry's API telling which tags are immutable couldn't be asked
+    ImmutabilityUnknown { source: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::ManifestFetchFailed { tag, source } => {
+                write!(f, "failed to fetch manifest for {tag}: {source}")
+            }
+            Warning::NoCreatedDate { tag } => write!(f, "skipped {tag} without a created date"),
+            Warning::InvalidTagName { raw } => write!(f, "skipped invalid tag name {raw:?}"),
+            Warning::DuplicateTag { name } => write!(f, "skipped duplicate tag {name}"),
+            Warning::InconsistentPage { last, first } => write!(
+                f,
+                "the page after {last} started at {first}; the tags changed while listing and some may be missing"
+            ),
+            Warning::ImmutabilityUnknown { source } => {
+                write!(f, "failed to look up which tags are immutable: {source}")
+            }
+        }
+    }
+}
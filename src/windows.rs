@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+
+use crate::Tag;
+
+/// Windows releases named in tags, with the OS build each one is
+const RELEASES: &[(&str, u32)] = &[
+    ("1607", 14393),
+    ("ltsc2016", 14393),
+    ("1709", 16299),
+    ("1803", 17134),
+    ("1809", 17763),
+    ("ltsc2019", 17763),
+    ("1903", 18362),
+    ("1909", 18363),
+    ("2004", 19041),
+    ("20H2", 19042),
+    ("ltsc2022", 20348),
+    ("23H2", 25398),
+    ("24H2", 26100),
+    ("ltsc2025", 26100),
+];
+
+/// Windows release a tag names, as those of `mcr.microsoft.com/windows/*`
+/// images: `ltsc2022`, `1809`, `20H2` or a build number like
+/// `10.0.20348.2582`, each optionally followed by a variant (`-amd64`)
+///
+/// Releases order by their OS build, then by the build's revision, a
+/// release's name coming after the revisions of its build since it follows
+/// the newest one. Only the names of known Windows Server releases are taken.
+/// Bare release numbers and builds could be any image's tags, so only
+/// [`WindowsRelease::parse_named`] is taken in any repository.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct WindowsRelease {
+    /// OS build, e.g. 20348 for Windows Server 2022
+    pub build: u32,
+    /// Update revision of the build, `None` for a release's name
+    pub revision: Option<u32>,
+}
+
+impl WindowsRelease {
+    /// The release `name` stands for among a Windows image's tags
+    pub fn parse(name: &str) -> Option<Self> {
+        let release = match name.split_once('-') {
+            Some((release, variant)) if !variant.is_empty() => release,
+            Some(_) => return None,
+            None => name,
+        };
+        if let Some(&(_, build)) = RELEASES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(release))
+        {
+            return Some(WindowsRelease {
+                build,
+                revision: None,
+            });
+        }
+        let number = |part: &str| {
+            (!part.is_empty() && part.len() <= 6 && part.bytes().all(|c| c.is_ascii_digit()))
+                .then(|| part.parse().ok())
+                .flatten()
+        };
+        match release.split('.').collect::<Vec<_>>()[..] {
+            ["10", "0", build, revision] => Some(WindowsRelease {
+                build: number(build)?,
+                revision: Some(number(revision)?),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The release `name` stands for in any image: only the names nothing
+    /// but Windows uses, `ltsc2022` or `20H2`, not `1809` nor builds
+    pub fn parse_named(name: &str) -> Option<Self> {
+        let release = name.split('-').next().unwrap_or(name);
+        WindowsRelease::parse(name).filter(|found| {
+            found.revision.is_none() && !release.bytes().all(|c| c.is_ascii_digit())
+        })
+    }
+}
+
+/// Whether `tags` are a Windows image's, one of them naming a release as
+/// only Windows does (see [`WindowsRelease::parse_named`]), so that their
+/// bare release numbers and builds are releases too
+pub fn is_windows_listing(tags: &[Tag]) -> bool {
+    tags.iter()
+        .any(|tag| WindowsRelease::parse_named(tag.name()).is_some())
+}
+
+impl PartialOrd for WindowsRelease {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WindowsRelease {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.build
+            .cmp(&other.build)
+            .then_with(|| match (self.revision, other.revision) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (a, b) => a.is_none().cmp(&b.is_none()),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let release = |build, revision| Some(WindowsRelease { build, revision });
+        assert_eq!(WindowsRelease::parse("ltsc2022"), release(20348, None));
+        assert_eq!(
+            WindowsRelease::parse("LTSC2019-amd64"),
+            release(17763, None)
+        );
+        assert_eq!(WindowsRelease::parse("20H2"), release(19042, None));
+        assert_eq!(
+            WindowsRelease::parse("1809-KB5041578"),
+            release(17763, None)
+        );
+        assert_eq!(
+            WindowsRelease::parse("10.0.20348.2582-amd64"),
+            release(20348, Some(2582))
+        );
+        for name in [
+            "ltsc2030",
+            "1810",
+            "10.0.20348",
+            "6.3.9600.1",
+            "ltsc2022-",
+            "10.0.a.1",
+        ] {
+            assert_eq!(WindowsRelease::parse(name), None, "{name}");
+        }
+        assert_eq!(
+            WindowsRelease::parse_named("20H2-KB5005565"),
+            release(19042, None)
+        );
+        for name in ["1809", "1809-amd64", "10.0.20348.2582"] {
+            assert_eq!(WindowsRelease::parse_named(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        let parse = |name| WindowsRelease::parse(name).unwrap();
+        assert!(parse("ltsc2019") < parse("20H2"));
+        assert!(parse("10.0.20348.2582") < parse("10.0.20348.2655"));
+        // a release's name stands for its newest revision
+        assert!(parse("10.0.20348.2655") < parse("ltsc2022"));
+        assert!(parse("ltsc2022") < parse("10.0.26100.1"));
+        assert_eq!(parse("1809").cmp(&parse("ltsc2019-amd64")), Ordering::Equal);
+    }
+}
@@ -0,0 +1,69 @@
+//! Deterministic generator of realistic tag lists, shared by tests and benches
+
+const VARIANTS: &[&str] = &[
+    "alpine",
+    "bookworm",
+    "slim",
+    "alpine3.20",
+    "windowsservercore",
+];
+const ALIASES: &[&str] = &["latest", "stable", "edge", "mainline", "lts"];
+
+/// Small linear congruential generator, good enough for fixtures
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Lcg(seed)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// Generate `count` tag names mixing semver, calver, sha-suffixed and
+/// variant-suffixed tags, in no particular order
+pub fn tag_names(count: usize, seed: u64) -> Vec<String> {
+    let mut rng = Lcg::new(seed);
+    let mut names = Vec::with_capacity(count);
+    while names.len() < count {
+        let (major, minor, patch) = (rng.below(30), rng.below(40), rng.below(25));
+        let name = match rng.below(10) {
+            0..=2 => format!("{major}.{minor}.{patch}"),
+            3 => format!("v{major}.{minor}.{patch}"),
+            4 => format!(
+                "{major}.{minor}.{patch}-{}",
+                VARIANTS[rng.below(VARIANTS.len() as u64) as usize]
+            ),
+            5 => format!("{major}.{minor}.{patch}-rc.{}", rng.below(5)),
+            6 => format!(
+                "20{:02}.{:02}.{:02}",
+                15 + rng.below(11),
+                1 + rng.below(12),
+                1 + rng.below(28)
+            ),
+            7 => format!("{major}.{minor}.{patch}-{:07x}", rng.next() & 0xfffffff),
+            8 => format!("{:016x}{:016x}", rng.next(), rng.next()),
+            _ => match rng.below(4) {
+                0 => ALIASES[rng.below(ALIASES.len() as u64) as usize].to_string(),
+                _ => format!("{major}.{minor}"),
+            },
+        };
+        names.push(name);
+    }
+    names
+}
+
+/// Split `names` into registry-style pages of `page_size`
+pub fn pages(names: &[String], page_size: usize) -> Vec<Vec<String>> {
+    names.chunks(page_size).map(<[String]>::to_vec).collect()
+}
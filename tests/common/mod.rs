@@ -0,0 +1,331 @@
+#![allow(dead_code)]
+
+pub mod generator;
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use reqwest::Url;
+use rustls::{
+    ServerConfig, ServerConnection, StreamOwned,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+
+/// CA that signed [`MockRegistry::start_tls`]'s certificate, which is valid for
+/// `localhost` and `127.0.0.1` (both made with `openssl`, expiring in 2126)
+pub const TEST_CA: &[u8] = include_bytes!("../fixtures/tls/ca.pem");
+
+/// A request received by [`MockRegistry`]
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn query(&self, name: &str) -> Option<String> {
+        self.url
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    }
+}
+
+/// A response returned by a [`MockRegistry`] handler
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Response::new(status)
+            .header("Content-Type", "application/json")
+            .body(body.into())
+    }
+
+    /// Close the connection without responding, as if the server died
+    pub fn hang_up() -> Self {
+        Response::new(0)
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// Minimal HTTP/1.1 server standing in for a registry
+pub struct MockRegistry {
+    host: String,
+    scheme: &'static str,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockRegistry {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        Self::listen(handler, None)
+    }
+
+    /// Like [`MockRegistry::start`], but serving HTTPS with a certificate
+    /// signed by [`TEST_CA`]
+    pub fn start_tls(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        Self::start_tls_versions(rustls::DEFAULT_VERSIONS, handler)
+    }
+
+    /// Like [`MockRegistry::start_tls`], but only speaking TLS `versions`
+    pub fn start_tls_versions(
+        versions: &[&'static rustls::SupportedProtocolVersion],
+        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        let certs = CertificateDer::pem_slice_iter(include_bytes!("../fixtures/tls/server.pem"))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let key =
+            PrivateKeyDer::from_pem_slice(include_bytes!("../fixtures/tls/server.key")).unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_protocol_versions(versions)
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+        Self::listen(handler, Some(Arc::new(config)))
+    }
+
+    fn listen(
+        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+        tls: Option<Arc<ServerConfig>>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let (base, log) = (format!("{scheme}://{host}"), requests.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (base, log, handler) = (base.clone(), log.clone(), handler.clone());
+                let tls = tls.clone();
+                thread::spawn(move || match tls {
+                    Some(config) => {
+                        let Ok(conn) = ServerConnection::new(config) else {
+                            return;
+                        };
+                        let mut stream = StreamOwned::new(conn, stream);
+                        serve(&mut stream, &base, &log, handler.as_ref());
+                        stream.conn.send_close_notify();
+                        let _ = stream.flush();
+                    }
+                    None => serve(&mut { stream }, &base, &log, handler.as_ref()),
+                });
+            }
+        });
+
+        MockRegistry {
+            host,
+            scheme,
+            requests,
+        }
+    }
+
+    /// Serve `tags` of `repository` with registry-style pagination, optionally
+    /// behind a bearer token challenge
+    pub fn with_tags(repository: &str, tags: &[&str], auth: bool) -> Self {
+        Self::with_repositories(&[(repository, tags)], auth)
+    }
+
+    /// Like [`MockRegistry::with_tags`] for several repositories at once, also
+    /// listed by the catalog API
+    pub fn with_repositories(repositories: &[(&str, &[&str])], auth: bool) -> Self {
+        let repositories: Vec<(String, Vec<String>)> = repositories
+            .iter()
+            .map(|(repo, tags)| {
+                (
+                    repo.to_string(),
+                    tags.iter().map(|t| t.to_string()).collect(),
+                )
+            })
+            .collect();
+        Self::start_with_base(move |base, req| {
+            let path = req.url.path();
+            if path == "/token" {
+                return Response::json(200, r#"{"token":"mock-token"}"#);
+            }
+            let repository = path
+                .strip_prefix("/v2/")
+                .and_then(|p| p.strip_suffix("/tags/list"));
+            if auth && req.header("Authorization") != Some("Bearer mock-token") {
+                let mut challenge = format!(r#"Bearer realm="{base}/token",service="mock""#);
+                if let Some(repository) = repository {
+                    challenge += &format!(r#",scope="repository:{repository}:pull""#);
+                } else if path == "/v2/_catalog" {
+                    challenge += r#",scope="registry:catalog:*""#;
+                }
+                return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#)
+                    .header("WWW-Authenticate", challenge);
+            }
+            if path == "/v2/_catalog" {
+                let names: Vec<_> = repositories.iter().map(|(repo, _)| repo.clone()).collect();
+                let page = paginate(&names, req);
+                return Response::json(
+                    200,
+                    serde_json::json!({ "repositories": page }).to_string(),
+                );
+            }
+            let Some((repository, tags)) = repositories
+                .iter()
+                .find(|(repo, _)| Some(repo.as_str()) == repository)
+            else {
+                return Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#);
+            };
+            let page = paginate(tags, req);
+            Response::json(
+                200,
+                serde_json::json!({ "name": repository, "tags": page }).to_string(),
+            )
+        })
+    }
+
+    /// Like [`MockRegistry::start`], but the handler also receives the server's base URL
+    pub fn start_with_base(
+        handler: impl Fn(&str, &Request) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        let base = Arc::new(Mutex::new(String::new()));
+        let registry = {
+            let base = base.clone();
+            Self::start(move |req| {
+                let base = base.lock().unwrap().clone();
+                handler(&base, req)
+            })
+        };
+        *base.lock().unwrap() = registry.url();
+        registry
+    }
+
+    /// `host:port` to be used as the registry part of an image reference
+    pub fn host(&self) -> String {
+        self.host.clone()
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}://{}", self.scheme, self.host)
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Requests whose path is `path`
+    pub fn requests_to(&self, path: &str) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.url.path() == path)
+            .collect()
+    }
+}
+
+/// Slice `tags` according to the `n` and `last` query parameters
+pub fn paginate(tags: &[String], req: &Request) -> Vec<String> {
+    let start = match req.query("last") {
+        Some(last) => tags
+            .iter()
+            .position(|t| *t == last)
+            .map_or(tags.len(), |i| i + 1),
+        None => 0,
+    };
+    let n = req
+        .query("n")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(tags.len());
+    tags[start..].iter().take(n).cloned().collect()
+}
+
+fn serve(
+    stream: &mut (impl Read + Write),
+    base: &str,
+    log: &Mutex<Vec<Request>>,
+    handler: &Handler,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            headers.push((k.trim().to_string(), v.trim().to_string()));
+        }
+    }
+    let length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    let _ = reader.read_exact(&mut body);
+
+    let request = Request {
+        method,
+        url: Url::parse(&format!("{base}{target}")).unwrap(),
+        headers,
+        body,
+    };
+    log.lock().unwrap().push(request.clone());
+    let response = handler(&request);
+    if response.status == 0 {
+        return;
+    }
+
+    let mut out = format!("HTTP/1.1 {} Mock\r\n", response.status);
+    for (k, v) in &response.headers {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    let stream = reader.into_inner();
+    let _ = stream.write_all(out.as_bytes());
+    let _ = stream.write_all(&response.body);
+}
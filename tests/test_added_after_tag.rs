@@ -0,0 +1,157 @@
+mod common;
+
+#[cfg(feature = "cli")]
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Response, TEST_CA};
+use docker_tags::{Anonymous, Client, Image, Tag, Warning};
+use sha2::{Digest, Sha256};
+
+/// Tags of a jenkins-like repository and the `created` dates of their configs;
+/// `None` for a config without one
+const TAGS: &[(&str, Option<&str>)] = &[
+    ("lts", Some("2024-09-01T00:00:00Z")),
+    ("lts-jdk17", Some("2024-09-01T00:00:00Z")),
+    ("2.440", Some("2024-05-01T00:00:00Z")),
+    ("2.470", Some("2024-08-01T00:00:00.5Z")),
+    ("2.462.3", Some("2024-10-01T23:30:00Z")),
+    // 2024-10-01T23:00:00Z, older than 2.462.3 despite sorting after it as text
+    ("2.479", Some("2024-10-02T01:00:00+02:00")),
+    ("weekly", Some("2024-10-08T06:00:00.123456789Z")),
+    ("nightly", None),
+    ("gone", Some("2024-12-01T00:00:00Z")),
+];
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn config(created: Option<&str>) -> String {
+    serde_json::json!({ "architecture": "amd64", "os": "linux", "created": created }).to_string()
+}
+
+fn manifest(config: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(config),
+            "size": config.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// Registry serving [`TAGS`] over HTTPS, with the manifest of `gone` missing
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req| {
+        let path = req.url.path();
+        if path == "/v2/jenkins/jenkins/tags/list" {
+            let names: Vec<_> = TAGS.iter().map(|(name, _)| name).collect();
+            return Response::json(200, serde_json::json!({ "tags": names }).to_string());
+        }
+        for &(name, created) in TAGS.iter().filter(|(name, _)| *name != "gone") {
+            let config = config(created);
+            let (content_type, body) = match path.rsplit_once('/') {
+                Some((_, reference)) if path.contains("/manifests/") && reference == name => (
+                    "application/vnd.oci.image.manifest.v1+json",
+                    manifest(&config),
+                ),
+                Some((_, reference))
+                    if path.contains("/blobs/") && reference == digest(&config) =>
+                {
+                    ("application/octet-stream", config)
+                }
+                _ => continue,
+            };
+            return Response::json(200, body.clone())
+                .header("Content-Type", content_type)
+                .header("Docker-Content-Digest", digest(&body));
+        }
+        Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#)
+    })
+}
+
+/// Run the binary against `registry`, trusting its CA and without credentials
+#[cfg(feature = "cli")]
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/jenkins/jenkins", registry.host()))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[cfg(feature = "cli")]
+fn stdout(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_fetch_created() {
+    let registry = registry();
+    let client = Client::builder()
+        .root_certificate(registry.host(), TEST_CA)
+        .unwrap()
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/jenkins/jenkins", registry.host()).as_str()).unwrap();
+
+    let tags = ["2.479", "nightly", "gone", "lts"].map(Tag::new);
+    let (created, warnings) = client.fetch_created(&image, &tags).await;
+    let names: Vec<_> = created.iter().map(|(tag, _)| tag.name()).collect();
+    assert_eq!(names, ["2.479", "lts"]);
+    assert!(created[0].1 > created[1].1);
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        warnings[0],
+        Warning::NoCreatedDate {
+            tag: "nightly".to_string()
+        }
+    );
+    assert!(matches!(&warnings[1], Warning::ManifestFetchFailed { tag, .. } if tag == "gone"));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_added_after_tag() {
+    let registry = registry();
+
+    let output = docker_tags(&registry, &["--added-after-tag", "lts"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(&output), ["weekly", "2.462.3", "2.479"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning: skipped nightly without a created date"));
+    assert!(stderr.contains("Warning: failed to fetch manifest for gone"));
+
+    let output = docker_tags(&registry, &["--added-after-tag", "2.470", "-n", "2", "-q"]);
+    assert_eq!(stdout(&output), ["weekly", "2.462.3"]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    let output = docker_tags(&registry, &["--added-after-tag", "2.440", "-f", "^2\\."]);
+    assert_eq!(stdout(&output), ["2.462.3", "2.479", "2.470"]);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_added_after_missing_tag() {
+    let registry = registry();
+
+    let output = docker_tags(&registry, &["--added-after-tag", "2.999"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stdout);
+    assert!(error.starts_with("Error: No tag 2.999 in "), "{error}");
+    // only the listing was fetched
+    assert_eq!(registry.requests().len(), 1);
+}
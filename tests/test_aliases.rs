@@ -0,0 +1,62 @@
+mod common;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Image, Tag, Warning};
+use sha2::{Digest, Sha256};
+
+fn manifest(version: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": format!("sha256:{:x}", Sha256::digest(version)), "size": 2},
+        "layers": [],
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_find_aliases() {
+    let registry = MockRegistry::start_with_base(move |base, req| {
+        if req.url.path() == "/token" {
+            return Response::json(200, r#"{"token":"mock-token"}"#);
+        }
+        if req.header("Authorization") != Some("Bearer mock-token") {
+            let challenge = format!(r#"Bearer realm="{base}/token",service="mock""#);
+            return Response::new(401).header("WWW-Authenticate", challenge);
+        }
+        let body = match req.url.path().strip_prefix("/v2/nginx/manifests/") {
+            Some("latest" | "1.27.2" | "1.27" | "1" | "mainline") => manifest("1.27.2"),
+            Some("1.26.3" | "stable") => manifest("1.26.3"),
+            _ => return Response::json(500, r#"{"errors":[{"code":"UNKNOWN"}]}"#),
+        };
+        Response::json(200, body)
+            .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+    });
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/nginx", registry.host()).as_str()).unwrap();
+
+    let digest = client.fetch_digest(&image, "latest").await.unwrap();
+    assert_eq!(
+        digest,
+        format!("sha256:{:x}", Sha256::digest(manifest("1.27.2")))
+    );
+
+    let candidates: Vec<_> = [
+        "1.27.2", "1.26.3", "1", "1.27", "broken", "mainline", "stable",
+    ]
+    .into_iter()
+    .map(Tag::new)
+    .collect();
+    let (aliases, warnings) = client.find_aliases(&image, &digest, &candidates).await;
+    let aliases: Vec<_> = aliases.iter().map(Tag::name).collect();
+    assert_eq!(aliases, ["1.27.2", "1", "1.27", "mainline"]);
+    assert!(matches!(
+        warnings.as_slice(),
+        [Warning::ManifestFetchFailed { tag, .. }] if tag == "broken"
+    ));
+    // the token obtained for the first candidate is reused by the others
+    assert_eq!(registry.requests_to("/token").len(), 2);
+}
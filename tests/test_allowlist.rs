@@ -0,0 +1,209 @@
+mod common;
+
+#[cfg(feature = "cli")]
+use std::process::Command;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Error, Image};
+
+fn not_allowed(err: &anyhow::Error) -> Option<String> {
+    err.chain().find_map(|err| match err.downcast_ref() {
+        Some(Error::RegistryNotAllowed { host }) => Some(host.clone()),
+        _ => None,
+    })
+}
+
+/// Service standing in for an internal endpoint no request should reach
+fn internal() -> MockRegistry {
+    MockRegistry::start(|_| Response::json(200, r#"{"token":"internal-token"}"#))
+}
+
+/// Registry challenging with a realm on `internal`, then serving tags to its token
+fn challenging(internal: &MockRegistry, tls: bool) -> MockRegistry {
+    let challenge = format!(
+        r#"Bearer realm="{}/token",service="registry""#,
+        internal.url()
+    );
+    let start = match tls {
+        true => MockRegistry::start_tls,
+        false => MockRegistry::start,
+    };
+    start(
+        move |req: &common::Request| match req.header("Authorization") {
+            Some("Bearer internal-token") => Response::json(200, r#"{"tags":["1.0"]}"#),
+            _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#)
+                .header("WWW-Authenticate", challenge.clone()),
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_disallowed_registry_is_not_contacted() {
+    let registry = MockRegistry::with_tags("app", &["1.0"], false);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .allowed_registries(["docker.io", "ghcr.io"])
+        .build();
+
+    let image = Image::new(registry.host(), "app");
+    let err = client.fetch_tags(&image).await.unwrap_err();
+    assert_eq!(not_allowed(&err), Some(registry.host()), "{err:?}");
+    let err = client.fetch_manifest(&image, "1.0").await.unwrap_err();
+    assert_eq!(not_allowed(&err), Some(registry.host()), "{err:?}");
+    assert!(registry.requests().is_empty());
+
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .allowed_registries([registry.host()])
+        .build();
+    assert_eq!(client.fetch_tags(&image).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_realm_outside_allowlist_is_not_contacted() {
+    let internal = internal();
+    let registry = challenging(&internal, false);
+    let image = Image::new(registry.host(), "app");
+    let client = |auth_hosts: &[String]| {
+        Client::builder()
+            .insecure_registry(registry.host())
+            .credential_provider(Anonymous)
+            .allowed_registries([registry.host()])
+            .allowed_auth_hosts(auth_hosts)
+            .build()
+    };
+
+    // same address, another port
+    let err = client(&[]).fetch_tags(&image).await.unwrap_err();
+    assert_eq!(not_allowed(&err), Some(internal.host()), "{err:?}");
+    assert!(internal.requests().is_empty());
+    assert_eq!(registry.requests().len(), 1);
+
+    let tags = client(&[internal.host()]).fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!(internal.requests_to("/token").len(), 1);
+}
+
+#[tokio::test]
+async fn test_denied_hosts_are_not_contacted() {
+    let internal = internal();
+    let registry = challenging(&internal, false);
+    let image = Image::new(registry.host(), "app");
+    let client = |denied: &[String]| {
+        Client::builder()
+            .insecure_registry(registry.host())
+            .credential_provider(Anonymous)
+            .denied_registries(denied)
+            .build()
+    };
+
+    let err = client(&[registry.host()])
+        .fetch_tags(&image)
+        .await
+        .unwrap_err();
+    assert_eq!(not_allowed(&err), Some(registry.host()), "{err:?}");
+    assert!(registry.requests().is_empty());
+
+    // the realm is denied, though its registry isn't
+    let err = client(&[internal.host()])
+        .fetch_tags(&image)
+        .await
+        .unwrap_err();
+    assert_eq!(not_allowed(&err), Some(internal.host()), "{err:?}");
+    assert!(internal.requests().is_empty());
+    assert_eq!(registry.requests().len(), 1);
+
+    // and refused even when allowed
+    let err = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .allowed_registries([registry.host()])
+        .allowed_auth_hosts([internal.host()])
+        .denied_registries([internal.host()])
+        .build()
+        .fetch_tags(&image)
+        .await
+        .unwrap_err();
+    assert_eq!(not_allowed(&err), Some(internal.host()), "{err:?}");
+    assert!(internal.requests().is_empty());
+
+    assert_eq!(client(&[]).fetch_tags(&image).await.unwrap().len(), 1);
+    assert_eq!(internal.requests_to("/token").len(), 1);
+}
+
+#[tokio::test]
+async fn test_redirect_outside_allowlist_is_not_followed() {
+    let internal = internal();
+    let target = format!("{}/v2/app/tags/list", internal.url());
+    let registry = MockRegistry::start(move |_| Response::new(307).header("Location", &target));
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .allowed_registries([registry.host()])
+        .build();
+
+    let err = client
+        .fetch_tags(&Image::new(registry.host(), "app"))
+        .await
+        .unwrap_err();
+    assert_eq!(not_allowed(&err), Some(internal.host()), "{err:?}");
+    assert!(internal.requests().is_empty());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_allow_and_deny_registry_flags() {
+    let internal = internal();
+    let registry = challenging(&internal, true);
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg(format!("{}/app", registry.host()))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = docker_tags(&["--allow-registry", "docker.io"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("{} is not an allowed host", registry.host())),
+        "{stderr}"
+    );
+    assert!(registry.requests().is_empty());
+
+    let output = docker_tags(&["--allow-registry", &registry.host()]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(internal.requests().is_empty());
+
+    let output = docker_tags(&[
+        "--allow-registry",
+        &registry.host(),
+        "--allow-auth-host",
+        &internal.host(),
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.0\n");
+
+    let served = registry.requests().len();
+    let output = docker_tags(&["--deny-registry", &internal.host()]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("{} is not an allowed host", internal.host())),
+        "{stderr}"
+    );
+    assert_eq!(internal.requests_to("/token").len(), 1);
+
+    let output = docker_tags(&["--deny-registry", &registry.host()]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert_eq!(registry.requests().len(), served + 1);
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+    sync::{Arc, OnceLock},
+};
+
+use common::{MockRegistry, Response};
+
+/// Tags of `org/app`, behind a bearer challenge if `challenge`, with
+/// anonymous tokens
+fn registry(challenge: bool) -> MockRegistry {
+    let base = Arc::new(OnceLock::<String>::new());
+    let registry = MockRegistry::start_tls({
+        let base = base.clone();
+        move |req| match (req.url.path(), req.header("Authorization")) {
+            ("/token", _) => Response::json(200, r#"{"token":"mock-token"}"#),
+            ("/v2/org/app/tags/list", auth) if !challenge || auth == Some("Bearer mock-token") => {
+                Response::json(200, r#"{"tags":["1.0.0","1.1.0"]}"#)
+            }
+            _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(
+                    r#"Bearer realm="{}/token",service="mock",scope="repository:org/app:pull""#,
+                    base.get().unwrap()
+                ),
+            ),
+        }
+    });
+    base.set(registry.url()).unwrap();
+    registry
+}
+
+/// List `org/app` at `registry` with the Docker config in `config`
+fn docker_tags(registry: &MockRegistry, config: &Path, args: &[&str]) -> Output {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config)
+        .env("XDG_STATE_HOME", config)
+        .arg(format!("{}/org/app", registry.host()))
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg("--no-cache")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_config_read_only_when_challenged() {
+    let config = tempfile::tempdir().unwrap();
+    // parsing fails if it's ever read
+    fs::write(config.path().join("config.json"), "{").unwrap();
+
+    let open = registry(false);
+    let output = docker_tags(&open, config.path(), &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.1.0\n1.0.0\n");
+
+    let challenging = registry(true);
+    let output = docker_tags(&challenging, config.path(), &[]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Failed to parse Docker config"),
+        "{output:?}"
+    );
+}
+
+#[test]
+fn test_anonymous() {
+    let config = tempfile::tempdir().unwrap();
+    fs::write(config.path().join("config.json"), "{").unwrap();
+    let registry = registry(true);
+
+    let output = docker_tags(&registry, config.path(), &["--anonymous"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.1.0\n1.0.0\n");
+    assert!(
+        registry
+            .requests_to("/token")
+            .iter()
+            .all(|req| req.header("Authorization").is_none())
+    );
+
+    let output = docker_tags(&registry, config.path(), &["--anonymous", "--require-auth"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{io::Write, process::Command};
+
+use common::{MockRegistry, Request, Response};
+
+/// Registry with tags for `app` and `tools`, denying access to `private`
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(200, r#"{"tags":["1.0.0","1.1.0","latest"]}"#),
+        "/v2/tools/tags/list" => Response::json(200, r#"{"tags":["2.0"]}"#),
+        "/v2/private/tags/list" => Response::json(
+            403,
+            r#"{"errors":[{"code":"DENIED","message":"no access"}]}"#,
+        ),
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+/// Run `batch` over `images` of `registry`, returning the exit code, stdout
+/// and stderr
+fn batch(registry: &MockRegistry, images: &[&str], args: &[&str]) -> (i32, String, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let list = dir.path().join("images.txt");
+    let mut file = std::fs::File::create(&list).unwrap();
+    writeln!(file, "# images to check").unwrap();
+    for image in images {
+        writeln!(file, "{}/{image}", registry.host()).unwrap();
+    }
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .arg("batch")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(&list)
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_batch_lists_every_image() {
+    let registry = registry();
+    let host = registry.host();
+    let (code, stdout, stderr) = batch(&registry, &["app", "tools"], &["-n", "2"]);
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(
+        stdout,
+        format!("{host}/app:1.1.0\n{host}/app:1.0.0\n{host}/tools:2.0\n")
+    );
+    let lines: Vec<_> = stderr
+        .lines()
+        .map(str::split_whitespace)
+        .map(Vec::from_iter)
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            vec!["IMAGE", "STATUS", "TAGS", "ERROR"],
+            vec![&format!("{host}/app"), "ok", "2"],
+            vec![&format!("{host}/tools"), "ok", "1"],
+        ]
+    );
+}
+
+#[test]
+fn test_batch_exit_codes() {
+    let registry = registry();
+    let host = registry.host();
+
+    let (code, stdout, stderr) = batch(&registry, &["app", "missing", "gone"], &[]);
+    assert_eq!(code, 4, "{stderr}");
+    assert_eq!(stdout.lines().count(), 3);
+    assert!(
+        stderr.contains(&format!("{host}/missing")) && stderr.contains("not_found"),
+        "{stderr}"
+    );
+
+    let (code, _, stderr) = batch(&registry, &["private", "app"], &[]);
+    assert_eq!(code, 6, "{stderr}");
+    assert!(stderr.contains("access_denied"), "{stderr}");
+
+    // worst wins: mixed kinds of failure
+    let (code, _, _) = batch(&registry, &["missing", "private", "app"], &[]);
+    assert_eq!(code, 1);
+    let (code, _, stderr) = batch(&registry, &["app", "not a valid image"], &[]);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("Invalid image name"), "{stderr}");
+}
+
+#[test]
+fn test_batch_json_summary() {
+    let registry = registry();
+    let host = registry.host();
+    let (code, stdout, stderr) = batch(&registry, &["app", "missing", "private"], &["-o", "json"]);
+    assert_eq!(code, 1);
+    // the summary is part of the document instead
+    assert_eq!(stderr, "");
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let images = report["images"].as_array().unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0]["image"], format!("{host}/app"));
+    assert_eq!(images[0]["shown"], 3);
+
+    let summary: Vec<_> = report["summary"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|image| {
+            (
+                image["image"].as_str().unwrap().to_string(),
+                image["status"].as_str().unwrap(),
+                image["tags"].as_u64(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (format!("{host}/app"), "ok", Some(3)),
+            (format!("{host}/missing"), "not_found", None),
+            (format!("{host}/private"), "access_denied", None),
+        ]
+    );
+    assert!(
+        report["summary"][2]["error"]
+            .as_str()
+            .unwrap()
+            .contains("no access")
+    );
+}
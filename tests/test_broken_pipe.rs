@@ -0,0 +1,33 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+#[test]
+fn test_closed_pipe_exits_cleanly() {
+    let mut input = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..50_000 {
+        writeln!(input, "1.0.{i}").unwrap();
+    }
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .arg("sort")
+        .arg(input.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // read like `head -1` and hang up
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "1.0.49999\n");
+    drop(stdout);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output.status);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
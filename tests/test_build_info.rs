@@ -0,0 +1,48 @@
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output};
+
+fn docker_tags(args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_version_command() {
+    let output = docker_tags(&["version"]);
+    assert!(output.status.success(), "{output:?}");
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        text.starts_with(concat!("docker-tags ", env!("CARGO_PKG_VERSION"), "\n")),
+        "{text}"
+    );
+    assert!(text.contains("\ntls: rustls\n"), "{text}");
+    assert!(text.contains("features: cli"), "{text}");
+
+    let output = docker_tags(&["version", "-o", "json"]);
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        info,
+        serde_json::to_value(docker_tags::build_info()).unwrap()
+    );
+}
+
+#[test]
+fn test_json_error_carries_build_info() {
+    let output = docker_tags(&["-o", "json", "bad//name"]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        report["error"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid image name")
+    );
+    assert_eq!(report["build"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(report["build"]["tls_backend"], "rustls");
+}
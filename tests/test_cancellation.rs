@@ -0,0 +1,200 @@
+mod common;
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use common::{MockRegistry, Request, Response};
+use docker_tags::{
+    Anonymous, Client, CredentialProvider, Credentials, FetchManyOptions, Image, Tag,
+};
+use sha2::{Digest, Sha256};
+
+const MANIFEST: &str = r#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","config":{"mediaType":"application/vnd.oci.image.config.v1+json","digest":"sha256:0000000000000000000000000000000000000000000000000000000000000000","size":2},"layers":[]}"#;
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn manifest() -> Response {
+    Response::json(200, MANIFEST)
+        .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+        .header("Docker-Content-Digest", digest(MANIFEST))
+}
+
+fn client(registry: &MockRegistry) -> Client {
+    Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build()
+}
+
+/// Stop, then wait long enough for a request started anyway to show up
+async fn settle() {
+    tokio::time::sleep(Duration::from_millis(800)).await;
+}
+
+#[tokio::test]
+async fn test_drop_stops_pagination() {
+    let registry = MockRegistry::start(|req: &Request| {
+        let (tags, next) = match req.query("last").as_deref() {
+            None => ("a", Some("a")),
+            Some("a") => {
+                // still answering when the listing is dropped
+                thread::sleep(Duration::from_millis(400));
+                ("b", Some("b"))
+            }
+            _ => ("c", None),
+        };
+        let mut response = Response::json(200, format!(r#"{{"tags":["{tags}"]}}"#));
+        if let Some(last) = next {
+            response = response.header(
+                "Link",
+                format!(r#"</v2/org/app/tags/list?last={last}>; rel="next""#),
+            );
+        }
+        response
+    });
+    let client = client(&registry);
+    let image = Image::new(registry.host(), "org/app");
+
+    let listing = tokio::time::timeout(Duration::from_millis(150), client.fetch_tags(&image));
+    assert!(
+        listing.await.is_err(),
+        "the listing should still be running"
+    );
+    settle().await;
+    let pages: Vec<_> = registry
+        .requests_to("/v2/org/app/tags/list")
+        .iter()
+        .map(|req| req.query("last"))
+        .collect();
+    // the page after `b` was never asked for
+    assert!(!pages.contains(&Some("b".to_string())), "{pages:?}");
+
+    // the client is still usable
+    let tags = tokio::time::timeout(Duration::from_secs(5), client.fetch_tags(&image))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(tags.len(), 3);
+}
+
+#[tokio::test]
+async fn test_drop_stops_enrichment() {
+    let registry = MockRegistry::start(|req: &Request| match req.url.path() {
+        path if path.starts_with("/v2/org/app/manifests/") => {
+            thread::sleep(Duration::from_millis(300));
+            manifest()
+        }
+        _ => Response::new(404),
+    });
+    let client = client(&registry);
+    let image = Image::new(registry.host(), "org/app");
+    let tags: Vec<_> = (0..40).map(|n| Tag::new(format!("1.0.{n}"))).collect();
+
+    // the first tag alone, then a batch of concurrent lookups in flight
+    let digests = tokio::time::timeout(
+        Duration::from_millis(450),
+        client.fetch_digests(&image, &tags),
+    );
+    assert!(
+        digests.await.is_err(),
+        "the lookups should still be running"
+    );
+    settle().await;
+    let started = registry.requests().len();
+    assert!(
+        started <= 1 + FetchManyOptions::default().concurrency,
+        "{started} lookups started"
+    );
+    settle().await;
+    assert_eq!(registry.requests().len(), started);
+}
+
+/// Credentials for every registry, after panicking the first time asked
+#[derive(Default)]
+struct PanicsOnce {
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl CredentialProvider for PanicsOnce {
+    async fn credentials(&self, _registry: &str) -> anyhow::Result<Option<Credentials>> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("credential store on fire");
+        }
+        Ok(Some(Credentials::basic("user", "password")))
+    }
+}
+
+/// A registry asking for a token before anything else
+fn authenticated() -> MockRegistry {
+    MockRegistry::start_with_base(|base, req| {
+        if req.url.path() == "/token" {
+            return Response::json(200, r#"{"token":"mock-token"}"#);
+        }
+        if req.header("Authorization") != Some("Bearer mock-token") {
+            return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(r#"Bearer realm="{base}/token",service="mock""#),
+            );
+        }
+        match req.url.path() {
+            path if path.ends_with("/tags/list") => Response::json(200, r#"{"tags":["1.0"]}"#),
+            path if path.contains("/manifests/") => manifest(),
+            _ => Response::new(404),
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_panic_fails_one_lookup() {
+    let registry = authenticated();
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(PanicsOnce::default())
+        .build();
+    let image = Image::new(registry.host(), "org/app");
+    let tags: Vec<_> = ["1.0", "1.1", "1.2"].into_iter().map(Tag::new).collect();
+
+    let (digests, warnings) =
+        tokio::time::timeout(Duration::from_secs(5), client.fetch_digests(&image, &tags))
+            .await
+            .expect("a panic mustn't hang the others");
+    assert_eq!(
+        digests,
+        [None, Some(digest(MANIFEST)), Some(digest(MANIFEST))]
+    );
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(
+        warnings[0].to_string(),
+        "failed to fetch manifest for 1.0: Panicked: credential store on fire"
+    );
+}
+
+#[tokio::test]
+async fn test_panic_fails_one_image() {
+    let registry = authenticated();
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(PanicsOnce::default())
+        .build();
+    let images = vec![
+        Image::new(registry.host(), "org/app"),
+        Image::new(registry.host(), "org/tool"),
+    ];
+
+    let results = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.fetch_tags_many(images, FetchManyOptions { concurrency: 1 }),
+    )
+    .await
+    .expect("a panic mustn't hang the others");
+    let err = results[0].1.as_ref().unwrap_err();
+    assert_eq!(err.to_string(), "Panicked: credential store on fire");
+    assert_eq!(results[1].1.as_ref().unwrap(), &[Tag::new("1.0")]);
+}
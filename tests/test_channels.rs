@@ -0,0 +1,160 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Request, Response};
+use sha2::{Digest, Sha256};
+
+const TAGS: &[&str] = &[
+    "1",
+    "1.26",
+    "1.26.3",
+    "1.27",
+    "1.27.2",
+    "latest",
+    "mainline",
+    "nightly",
+    "stable",
+    "stable-alpine",
+];
+
+fn manifest(version: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": format!("sha256:{:x}", Sha256::digest(version)), "size": 2},
+        "layers": [],
+    })
+    .to_string()
+}
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| {
+        let path = req.url.path();
+        if path == "/v2/nginx/tags/list" {
+            return Response::json(200, serde_json::json!({ "tags": TAGS }).to_string());
+        }
+        let body = match path.strip_prefix("/v2/nginx/manifests/") {
+            Some("latest" | "mainline" | "1.27.2" | "1.27" | "1") => manifest("1.27.2"),
+            Some("stable" | "1.26.3" | "1.26") => manifest("1.26.3"),
+            Some("stable-alpine") => manifest("1.26.3-alpine"),
+            _ => return Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+        };
+        Response::json(200, body)
+            .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/nginx", registry.host()))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_channel_filters() {
+    let registry = registry();
+    assert_eq!(
+        stdout(&docker_tags(&registry, &["--channels"])),
+        "latest\nmainline\nnightly\nstable\nstable-alpine\n"
+    );
+    assert_eq!(
+        stdout(&docker_tags(&registry, &["--no-channels"])),
+        "1.27.2\n1.26.3\n1\n1.26\n1.27\n"
+    );
+    assert_eq!(
+        stdout(&docker_tags(
+            &registry,
+            &["--no-channels", "--channel-name", "1"]
+        )),
+        "1.27.2\n1.26.3\n1.26\n1.27\n"
+    );
+}
+
+#[test]
+fn test_channel_resolves_versions() {
+    let registry = registry();
+    assert_eq!(
+        stdout(&docker_tags(&registry, &["--channel", "stable"])),
+        "1.26.3\n1.26\n"
+    );
+    assert_eq!(
+        stdout(&docker_tags(
+            &registry,
+            &["--channel", "mainline", "-n", "1"]
+        )),
+        "1.27.2\n"
+    );
+    // only versions are looked up, not the other channels
+    let looked_up: Vec<_> = registry
+        .requests()
+        .iter()
+        .filter_map(|req| req.url.path().strip_prefix("/v2/nginx/manifests/"))
+        .map(str::to_string)
+        .collect();
+    assert!(
+        !looked_up.iter().any(|tag| tag == "latest"),
+        "{looked_up:?}"
+    );
+
+    let output = docker_tags(&registry, &["--channel", "stable", "-o", "json"]);
+    let listing: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let names: Vec<_> = listing["tags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| tag["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["1.26.3", "1.26"]);
+}
+
+#[test]
+fn test_channel_must_be_known() {
+    let registry = registry();
+    let output = docker_tags(&registry, &["--channel", "1.26"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let message = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        message.contains(r#""1.26" is not a channel tag"#),
+        "{message}"
+    );
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn test_resolve_document() {
+    let registry = registry();
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("resolve")
+        .arg(format!("{}/nginx:stable", registry.host()))
+        .args(["-o", "json", "--ca-cert"])
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["tag"], "stable");
+    assert!(
+        report["digest"].as_str().unwrap().starts_with("sha256:"),
+        "{report}"
+    );
+    assert_eq!(report["aliases"], serde_json::json!(["1.26.3", "1.26"]));
+}
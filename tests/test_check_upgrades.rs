@@ -0,0 +1,199 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+use common::{MockRegistry, Request, Response};
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| {
+        let tags: &[&str] = match req.url.path() {
+            "/v2/library/node/tags/list" => &[
+                "20.10.0-alpine",
+                "20.11.1",
+                "20.11.1-alpine",
+                "22.1.0-alpine",
+            ],
+            "/v2/library/postgres/tags/list" => &["15.4", "15.6", "16.2", "17.0", "17.1"],
+            "/v2/library/redis/tags/list" => &["7.2.1", "7.2.5", "7.4.0", "8.0.0"],
+            "/v2/tools/frozen/tags/list" => &["1.0.0", "2.0.0"],
+            "/v2/library/nginx/tags/list" => &["1.25.3", "1.27.0", "1.27.1-rc1"],
+            _ => return Response::new(404),
+        };
+        Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+    })
+}
+
+/// The fixture's `file` in `dir`, naming the mock registry
+fn fixture(registry: &MockRegistry, dir: &Path, file: &str) -> String {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/renovate");
+    let text = fs::read_to_string(fixture.join(file)).unwrap();
+    let path = dir.join(file);
+    fs::write(&path, text.replace("REGISTRY", &registry.host())).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_policy_file_narrows_suggestions() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let dockerfile = fixture(&registry, dir.path(), "Dockerfile");
+    let policies = fixture(&registry, dir.path(), "renovate.json");
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let check = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .args(["check-upgrades", &dockerfile])
+            .args(args)
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .output()
+            .unwrap()
+    };
+    let host = registry.host();
+
+    let output = check(&[]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "1: {host}/library/node:20.10.0-alpine -> {host}/library/node:22.1.0-alpine (newest release with variant alpine)\n\
+             4: {host}/library/postgres:15.4 -> {host}/library/postgres:17.1 (newest release)\n\
+             5: {host}/library/redis:7.2.1 -> {host}/library/redis:8.0.0 (newest release)\n\
+             6: {host}/tools/frozen:1.0.0 -> {host}/tools/frozen:2.0.0 (newest release)\n\
+             7: {host}/library/nginx:1.25.3 -> {host}/library/nginx:1.27.0 (newest release)\n"
+        )
+    );
+
+    let output = check(&["--policy-file", &policies]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "1: {host}/library/node:20.10.0-alpine -> {host}/library/node:20.11.1-alpine (newest 20 release with variant alpine)\n\
+             4: {host}/library/postgres:15.4 -> {host}/library/postgres:16.2 (newest release)\n\
+             5: {host}/library/redis:7.2.1 -> {host}/library/redis:7.2.5 (newest release)\n\
+             7: {host}/library/nginx:1.25.3 -> {host}/library/nginx:1.27.0 (newest release)\n"
+        )
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Warning: extends: presets aren't read"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Warning: packageRules[2].automerge: unsupported, ignored"),
+        "{stderr}"
+    );
+    // ignored images aren't even listed
+    assert_eq!(registry.requests_to("/v2/tools/frozen/tags/list").len(), 1);
+
+    let output = check(&["--policy-file", &policies, "--scope", "patch", "-q"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+    // `15.4` is already the newest of its own series
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "5: {host}/library/redis:7.2.1 -> {host}/library/redis:7.2.5 (newest 7.2 release)\n"
+        )
+    );
+
+    let output = check(&[
+        "--policy-file",
+        &policies,
+        "--scope",
+        "patch",
+        "-qo",
+        "json",
+    ]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "file": dockerfile,
+            "upgrades": [{
+                "line": 5,
+                "reference": format!("{host}/library/redis:7.2.1"),
+                "upgrade": format!("{host}/library/redis:7.2.5"),
+                "reason": "newest 7.2 release",
+            }],
+        })
+    );
+}
+
+#[test]
+fn test_dependabot_policy_file() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let dockerfile = fixture(&registry, dir.path(), "Dockerfile");
+    let policies = fixture(&registry, dir.path(), "dependabot.yml");
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .args(["check-upgrades", &dockerfile, "--policy-file", &policies])
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap();
+    let host = registry.host();
+
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "1: {host}/library/node:20.10.0-alpine -> {host}/library/node:20.11.1-alpine (newest 20 release with variant alpine)\n\
+             4: {host}/library/postgres:15.4 -> {host}/library/postgres:16.2 (newest release)\n\
+             5: {host}/library/redis:7.2.1 -> {host}/library/redis:7.2.5 (newest 7.2 release)\n\
+             7: {host}/library/nginx:1.25.3 -> {host}/library/nginx:1.27.0 (newest release)\n"
+        )
+    );
+}
+
+#[test]
+fn test_up_to_date_and_invalid_policy_file() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let dockerfile = dir.path().join("Dockerfile");
+    fs::write(
+        &dockerfile,
+        format!("FROM {}/library/postgres:17.1\n", registry.host()),
+    )
+    .unwrap();
+    let policies = dir.path().join("renovate.json");
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let check = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .arg("check-upgrades")
+            .arg(&dockerfile)
+            .args(args)
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .output()
+            .unwrap()
+    };
+
+    let output = check(&[]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    fs::write(
+        &policies,
+        r#"{"packageRules": [{"allowedVersions": ">=x"}]}"#,
+    )
+    .unwrap();
+    let output = check(&["--policy-file", policies.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("packageRules[0].allowedVersions"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::MockRegistry;
+use docker_tags::{Client, CredentialProvider, Credentials, Image};
+#[cfg(feature = "unstable")]
+use reqwest::StatusCode;
+
+struct RecordingProvider(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl CredentialProvider for RecordingProvider {
+    async fn credentials(&self, registry: &str) -> Result<Option<Credentials>> {
+        self.0.lock().unwrap().push(registry.to_string());
+        Ok(Some(Credentials::basic("vault-user", "vault-pass")))
+    }
+}
+
+#[tokio::test]
+async fn test_custom_credential_provider() {
+    let registry = MockRegistry::with_tags("team/app", &["1.0.0", "1.1.0"], true);
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(RecordingProvider(calls.clone()))
+        .build();
+
+    let image = Image::try_from(format!("{}/team/app", registry.host()).as_str()).unwrap();
+    let tags = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(*calls.lock().unwrap(), [registry.host()]);
+
+    let token_requests = registry.requests_to("/token");
+    assert_eq!(token_requests.len(), 1);
+    assert_eq!(
+        token_requests[0].header("Authorization"),
+        Some("Basic dmF1bHQtdXNlcjp2YXVsdC1wYXNz")
+    );
+}
+
+#[cfg(feature = "unstable")]
+#[tokio::test]
+async fn test_on_page_called_per_request() {
+    let names: Vec<_> = (0..150).map(|i| format!("1.0.{i}")).collect();
+    let names: Vec<_> = names.iter().map(String::as_str).collect();
+    let registry = MockRegistry::with_tags("app", &names, true);
+    let pages = Arc::new(Mutex::new(Vec::new()));
+    let client = {
+        let pages = pages.clone();
+        Client::builder()
+            .insecure_registry(registry.host())
+            .on_page(move |page| pages.lock().unwrap().push((page.status, page.tags)))
+            .build()
+    };
+
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let tags = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), 150);
+    assert_eq!(
+        *pages.lock().unwrap(),
+        [
+            (StatusCode::UNAUTHORIZED, 0),
+            (StatusCode::OK, 100),
+            (StatusCode::OK, 50)
+        ]
+    );
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 3);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Anonymous, Client, Error, FetchManyOptions, Image};
+use futures_util::future::join_all;
+
+/// Three pages of tags, the first answered slowly so that callers pile up
+fn registry() -> MockRegistry {
+    MockRegistry::start(|req: &Request| {
+        let path = req.url.path();
+        if path == "/v2/org/missing/tags/list" {
+            thread::sleep(Duration::from_millis(300));
+            return Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#);
+        }
+        if !path.ends_with("/tags/list") {
+            return Response::new(404);
+        }
+        let (tags, next) = match req.query("last").as_deref() {
+            None => {
+                thread::sleep(Duration::from_millis(300));
+                ("a", Some("a"))
+            }
+            Some("a") => ("b", Some("b")),
+            _ => ("c", None),
+        };
+        let mut response = Response::json(200, format!(r#"{{"tags":["{tags}"]}}"#));
+        if let Some(last) = next {
+            response = response.header("Link", format!(r#"<{path}?last={last}>; rel="next""#));
+        }
+        response
+    })
+}
+
+fn client(registry: &MockRegistry) -> Client {
+    Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build()
+}
+
+#[tokio::test]
+async fn test_concurrent_listings_share_requests() {
+    let registry = registry();
+    let client = client(&registry);
+    let image = Image::new(registry.host(), "org/app");
+
+    // clones share the listings in flight too
+    let listings = join_all((0..10).map(|_| {
+        let (client, image) = (client.clone(), image.clone());
+        async move { client.fetch_tags(&image).await }
+    }))
+    .await;
+    for tags in listings {
+        let names: Vec<_> = tags.unwrap().iter().map(|t| t.name().to_string()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+    assert_eq!(registry.requests_to("/v2/org/app/tags/list").len(), 3);
+
+    // partial and complete listings are the same one
+    let (partial, complete) =
+        tokio::join!(client.fetch_tags_partial(&image), client.fetch_tags(&image));
+    let (tags, outcome) = partial.unwrap();
+    assert_eq!(tags, complete.unwrap());
+    assert_eq!((outcome.pages, outcome.listed), (3, 3));
+    // nothing is kept once the listing is done
+    assert_eq!(registry.requests_to("/v2/org/app/tags/list").len(), 6);
+    client.fetch_tags(&image).await.unwrap();
+    assert_eq!(registry.requests_to("/v2/org/app/tags/list").len(), 9);
+}
+
+#[tokio::test]
+async fn test_fetch_many_shares_requests() {
+    let registry = registry();
+    let client = client(&registry);
+    let app = Image::new(registry.host(), "org/app");
+    let tool = Image::new(registry.host(), "org/tool");
+
+    let results = client
+        .fetch_tags_many(
+            vec![app.clone(), tool, app.clone(), app],
+            FetchManyOptions { concurrency: 4 },
+        )
+        .await;
+    for (_, tags) in &results {
+        assert_eq!(tags.as_ref().unwrap().len(), 3);
+    }
+    assert_eq!(registry.requests_to("/v2/org/app/tags/list").len(), 3);
+    assert_eq!(registry.requests_to("/v2/org/tool/tags/list").len(), 3);
+}
+
+#[tokio::test]
+async fn test_errors_reach_every_caller() {
+    let registry = registry();
+    let client = client(&registry);
+    let image = Image::new(registry.host(), "org/missing");
+
+    let listings = join_all((0..10).map(|_| client.fetch_tags(&image))).await;
+    let messages: Vec<_> = listings
+        .iter()
+        .map(|result| format!("{:#}", result.as_ref().unwrap_err()))
+        .collect();
+    assert!(messages.iter().all(|m| m == &messages[0]), "{messages:?}");
+    for result in listings {
+        let err = result.unwrap_err();
+        assert!(
+            matches!(
+                err.chain().find_map(|cause| cause.downcast_ref::<Error>()),
+                Some(Error::NotFound)
+            ),
+            "{err:#}"
+        );
+    }
+    let asked = registry.requests_to("/v2/org/missing/tags/list").len();
+    assert_eq!(asked, 1);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{MockRegistry, Response};
+use docker_tags::{Client, Image, Warning};
+
+fn dataset() -> Vec<String> {
+    (0..250).map(|i| format!("t{i:03}")).collect()
+}
+
+/// Registry whose second page overlaps the first by 5 tags, in every listing
+/// or only in the first one
+fn registry(always: bool) -> MockRegistry {
+    let listings = AtomicUsize::new(0);
+    let tags = dataset();
+    MockRegistry::start(move |req| {
+        let start = match req.query("last") {
+            None => {
+                listings.fetch_add(1, Ordering::SeqCst);
+                0
+            }
+            Some(last) => {
+                let next = tags.iter().position(|t| *t == last).unwrap() + 1;
+                let overlapping = always || listings.load(Ordering::SeqCst) == 1;
+                if next == 100 && overlapping { 95 } else { next }
+            }
+        };
+        let page: Vec<_> = tags.iter().skip(start).take(100).collect();
+        Response::json(200, serde_json::json!({ "tags": page }).to_string())
+    })
+}
+
+fn image(registry: &MockRegistry) -> Image {
+    Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap()
+}
+
+fn listings(registry: &MockRegistry) -> usize {
+    registry
+        .requests()
+        .iter()
+        .filter(|r| r.query("last").is_none())
+        .count()
+}
+
+#[tokio::test]
+async fn test_overlapping_pages_are_deduplicated() {
+    let registry = registry(true);
+    let client = Client::builder().insecure_registry(registry.host()).build();
+
+    for _ in 0..2 {
+        let (tags, outcome) = client.fetch_tags_partial(&image(&registry)).await.unwrap();
+        let names: Vec<_> = tags.iter().map(|t| t.name().to_string()).collect();
+        assert_eq!(names, dataset());
+        assert_eq!(
+            outcome.warnings[0],
+            Warning::InconsistentPage {
+                last: "t099".to_string(),
+                first: "t095".to_string(),
+            }
+        );
+        let duplicates: Vec<_> = outcome.warnings[1..]
+            .iter()
+            .map(|w| match w {
+                Warning::DuplicateTag { name } => name.as_str(),
+                w => panic!("{w:?}"),
+            })
+            .collect();
+        assert_eq!(duplicates, ["t095", "t096", "t097", "t098", "t099"]);
+    }
+}
+
+#[tokio::test]
+async fn test_consistent_listing_starts_over() {
+    let registry = registry(false);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .consistent_listing(true)
+        .build();
+
+    let (tags, outcome) = client.fetch_tags_partial(&image(&registry)).await.unwrap();
+    assert_eq!(tags.len(), 250);
+    assert!(outcome.warnings.is_empty(), "{:?}", outcome.warnings);
+    assert_eq!(listings(&registry), 2);
+}
+
+#[tokio::test]
+async fn test_consistent_listing_retries_once() {
+    let registry = registry(true);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .consistent_listing(true)
+        .build();
+
+    let (tags, outcome) = client.fetch_tags_partial(&image(&registry)).await.unwrap();
+    assert_eq!(tags.len(), 250);
+    assert!(matches!(
+        outcome.warnings[0],
+        Warning::InconsistentPage { .. }
+    ));
+    assert_eq!(listings(&registry), 2);
+}
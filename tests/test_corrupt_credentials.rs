@@ -0,0 +1,103 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    process::Command,
+    sync::{Arc, OnceLock},
+};
+
+use common::{MockRegistry, Response};
+
+/// Tags of `org/app` behind a bearer challenge, for anonymous tokens too
+fn registry() -> MockRegistry {
+    let base = Arc::new(OnceLock::<String>::new());
+    let registry = MockRegistry::start_tls({
+        let base = base.clone();
+        move |req| match (req.url.path(), req.header("Authorization")) {
+            ("/token", _) => Response::json(200, r#"{"token":"mock-token"}"#),
+            ("/v2/org/app/tags/list", Some("Bearer mock-token")) => {
+                Response::json(200, r#"{"tags":["1.0.0"]}"#)
+            }
+            _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(
+                    r#"Bearer realm="{}/token",service="mock",scope="repository:org/app:pull""#,
+                    base.get().unwrap()
+                ),
+            ),
+        }
+    });
+    base.set(registry.url()).unwrap();
+    registry
+}
+
+#[test]
+fn test_corrupt_auth_entry() {
+    let registry = registry();
+    let config = tempfile::tempdir().unwrap();
+    // truncated base64
+    fs::write(
+        config.path().join("config.json"),
+        format!(
+            r#"{{"auths": {{"{}": {{"auth": "dXNlcjpodW50ZXI"}}}}}}"#,
+            registry.host()
+        ),
+    )
+    .unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/org/app", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let entry = format!(r#"auths["{}"] in "#, registry.host());
+
+    let output = docker_tags(&[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1.0.0\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("Warning: corrupt credential entry {entry}")),
+        "{stderr}"
+    );
+    assert!(stderr.contains("config.json: not valid base64"), "{stderr}");
+    // the token was asked for anonymously
+    assert!(
+        registry
+            .requests_to("/token")
+            .iter()
+            .all(|req| req.header("Authorization").is_none())
+    );
+
+    let output = docker_tags(&["-q"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+
+    // rather than "Image not found"
+    let output = docker_tags(&["--require-auth"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with(&format!("Error: Corrupt credential entry {entry}")),
+        "{stdout}"
+    );
+
+    fs::write(config.path().join("config.json"), "{}").unwrap();
+    let output = docker_tags(&["--require-auth"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "Error: No credentials for {} in the Docker config\n",
+            registry.host()
+        )
+    );
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Anonymous, Client, Image};
+
+const TOKEN: &str = "Bearer mock-token";
+
+/// Storage host serving the second page of tags, asking for the registry's
+/// token while `protected`
+fn cdn(protected: Arc<AtomicBool>) -> MockRegistry {
+    MockRegistry::start(move |req: &Request| {
+        if protected.load(Ordering::SeqCst) && req.header("Authorization") != Some(TOKEN) {
+            return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                r#"Bearer realm="http://127.0.0.1:9/token""#,
+            );
+        }
+        Response::json(200, r#"{"tags":["2.0","2.1"]}"#)
+    })
+}
+
+/// Registry behind a token, whose first page links to the next one on `cdn`
+fn registry(cdn: &MockRegistry) -> MockRegistry {
+    let next = format!(r#"<{}/v2/app/tags/list?page=2>; rel="next""#, cdn.url());
+    MockRegistry::start_with_base(move |base, req| match req.url.path() {
+        "/token" => Response::json(200, r#"{"token":"mock-token"}"#),
+        _ if req.header("Authorization") != Some(TOKEN) => {
+            Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(r#"Bearer realm="{base}/token",service="registry""#),
+            )
+        }
+        "/v2/app/tags/list" => {
+            Response::json(200, r#"{"tags":["1.0","1.1"]}"#).header("Link", next.clone())
+        }
+        _ => Response::json(404, "{}"),
+    })
+}
+
+fn client(registry: &MockRegistry, trusted: Option<&MockRegistry>) -> Client {
+    let mut builder = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous);
+    if let Some(cdn) = trusted {
+        builder = builder.trusted_page_host(registry.host(), cdn.host());
+    }
+    builder.build()
+}
+
+fn authorizations(mock: &MockRegistry) -> Vec<Option<String>> {
+    mock.requests()
+        .iter()
+        .map(|req| req.header("Authorization").map(str::to_string))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_token_stays_on_registry() {
+    let cdn = cdn(Arc::default());
+    let registry = registry(&cdn);
+    let image = Image::new(registry.host(), "app");
+
+    let tags = client(&registry, None).fetch_tags(&image).await.unwrap();
+    let names: Vec<_> = tags.iter().map(|tag| tag.name()).collect();
+    assert_eq!(names, ["1.0", "1.1", "2.0", "2.1"]);
+    assert_eq!(authorizations(&cdn), [None]);
+    assert_eq!(cdn.requests()[0].query("page").as_deref(), Some("2"));
+    // challenged, then the first page with the token
+    let pages = registry.requests_to("/v2/app/tags/list");
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[1].header("Authorization"), Some(TOKEN));
+}
+
+#[tokio::test]
+async fn test_untrusted_host_asking_for_token_fails() {
+    let cdn = cdn(Arc::new(AtomicBool::new(true)));
+    let registry = registry(&cdn);
+    let image = Image::new(registry.host(), "app");
+
+    let err = client(&registry, None)
+        .fetch_tags(&image)
+        .await
+        .unwrap_err();
+    let message = format!("{err:#}");
+    assert!(
+        message.contains(&format!(
+            "Got HTTP 401 Unauthorized for tags at {}/v2/app/tags/list?n=100&page=2: the registry's pages lead to {}, which isn't trusted with the token of {}",
+            cdn.url(),
+            cdn.host(),
+            registry.host()
+        )),
+        "{message}"
+    );
+    // neither the token nor the challenge's realm was tried
+    assert_eq!(authorizations(&cdn), [None]);
+    assert_eq!(registry.requests_to("/token").len(), 1);
+}
+
+#[tokio::test]
+async fn test_trusted_host_gets_token() {
+    let cdn = cdn(Arc::new(AtomicBool::new(true)));
+    let registry = registry(&cdn);
+    let image = Image::new(registry.host(), "app");
+
+    let tags = client(&registry, Some(&cdn))
+        .fetch_tags(&image)
+        .await
+        .unwrap();
+    assert_eq!(tags.len(), 4);
+    assert_eq!(authorizations(&cdn), [Some(TOKEN.to_string())]);
+}
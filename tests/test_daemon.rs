@@ -0,0 +1,314 @@
+#![cfg(all(feature = "serve", unix))]
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    process::{Child, Command, Output, Stdio},
+    sync::{Arc, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{MockRegistry, Request, Response, paginate};
+use tempfile::TempDir;
+
+/// Registry serving `app` over HTTPS behind a bearer token challenge
+fn registry() -> MockRegistry {
+    let base = Arc::new(OnceLock::<String>::new());
+    let tags: Vec<_> = ["1.0.0", "1.1.0", "2.0.0", "latest"]
+        .map(String::from)
+        .to_vec();
+    let registry = MockRegistry::start_tls({
+        let base = base.clone();
+        move |req: &Request| {
+            let path = req.url.path();
+            if path == "/token" {
+                return Response::json(200, r#"{"token":"mock-token"}"#);
+            }
+            if req.header("Authorization") != Some("Bearer mock-token") {
+                let challenge = format!(
+                    r#"Bearer realm="{}/token",service="mock",scope="repository:app:pull""#,
+                    base.get().unwrap()
+                );
+                return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#)
+                    .header("WWW-Authenticate", challenge);
+            }
+            match path {
+                "/v2/app/tags/list" => {
+                    let page = paginate(&tags, req);
+                    Response::json(200, serde_json::json!({ "tags": page }).to_string())
+                }
+                _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+            }
+        }
+    });
+    base.set(registry.url()).unwrap();
+    registry
+}
+
+/// The binary run with `args`, its state in `home`, trusting the registry's CA
+fn docker_tags(registry: &MockRegistry, home: &Path, args: &[&str]) -> Command {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_docker-tags"));
+    command
+        .env("DOCKER_CONFIG", home)
+        .env("XDG_STATE_HOME", home)
+        .env_remove("XDG_RUNTIME_DIR")
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()));
+    command
+}
+
+/// `docker-tags daemon start --foreground` on a socket of its own, killed
+/// when dropped
+struct Daemon {
+    child: Child,
+    home: TempDir,
+}
+
+impl Daemon {
+    fn start(registry: &MockRegistry, args: &[&str]) -> Self {
+        let home = tempfile::tempdir().unwrap();
+        let socket = socket(&home, "daemon.sock");
+        let start = [
+            "daemon",
+            "start",
+            "--foreground",
+            "--daemon-socket",
+            &socket,
+        ];
+        let mut child = docker_tags(registry, home.path(), &[&start, args].concat())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stderr.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        assert!(line.starts_with("Listening on "), "{line:?}");
+        Daemon { child, home }
+    }
+
+    fn socket(&self) -> String {
+        socket(&self.home, "daemon.sock")
+    }
+
+    /// Run a client of the daemon, with a state of its own
+    fn list(&self, registry: &MockRegistry, args: &[&str]) -> Output {
+        let home = tempfile::tempdir().unwrap();
+        let socket = self.socket();
+        let via = ["--via-daemon", "--daemon-socket", &socket];
+        docker_tags(registry, home.path(), &[&via, args].concat())
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn socket(home: &TempDir, name: &str) -> String {
+    home.path().join(name).to_str().unwrap().to_string()
+}
+
+/// Send `request` to the daemon on `socket` as one line, returning its reply
+fn exchange(socket: &str, request: &str) -> serde_json::Value {
+    let mut stream = UnixStream::connect(socket).unwrap();
+    stream.write_all(format!("{request}\n").as_bytes()).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    serde_json::from_str(&reply).unwrap()
+}
+
+fn wait_for(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_via_daemon() {
+    let registry = registry();
+    let daemon = Daemon::start(&registry, &[]);
+    let image = format!("{}/app", registry.host());
+
+    let output = daemon.list(&registry, &[&image]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2.0.0\n1.1.0\n1.0.0\nlatest\n"
+    );
+
+    let output = daemon.list(&registry, &[&image, "-r", "-f", r"^1\.", "-o", "json"]);
+    assert!(output.status.success(), "{output:?}");
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(listing["image"], image);
+    assert_eq!(
+        listing["tags"],
+        serde_json::json!([{"name": "1.0.0"}, {"name": "1.1.0"}])
+    );
+    assert_eq!(
+        (listing["total"].as_u64(), listing["matched"].as_u64()),
+        (Some(4), Some(2))
+    );
+
+    let output = daemon.list(&registry, &[&image, "--pin-aliases", "-n", "1"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "latest\n\n2.0.0\n");
+
+    // every run listed anew, all with the token fetched for the first
+    assert_eq!(registry.requests_to("/token").len(), 1);
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 3);
+}
+
+#[test]
+fn test_via_daemon_errors() {
+    let registry = registry();
+    let daemon = Daemon::start(&registry, &[]);
+
+    let output = daemon.list(&registry, &[&format!("{}/missing", registry.host())]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).starts_with("Error: "),
+        "{output:?}"
+    );
+
+    let image = format!("{}/app", registry.host());
+    let output = daemon.list(&registry, &[&image, "-f", "("]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("Invalid regex pattern"),
+        "{output:?}"
+    );
+
+    let output = daemon.list(&registry, &[&image, "--digests"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("--via-daemon doesn't go with --digests"),
+        "{output:?}"
+    );
+
+    // requests of another protocol version are refused, not guessed at
+    let reply = exchange(&daemon.socket(), r#"{"version":99,"command":"ping"}"#);
+    assert_eq!(reply["version"], 1);
+    assert!(
+        reply["error"]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Unsupported protocol version 99"),
+        "{reply}"
+    );
+    let reply = exchange(&daemon.socket(), r#"{"version":1,"command":"ping"}"#);
+    assert_eq!(reply["pid"], daemon.child.id());
+}
+
+#[test]
+fn test_without_daemon() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    let socket = socket(&home, "daemon.sock");
+    let image = format!("{}/app", registry.host());
+    let output = docker_tags(
+        &registry,
+        home.path(),
+        &["--via-daemon", "--daemon-socket", &socket, &image],
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("start one with `docker-tags daemon start`"),
+        "{output:?}"
+    );
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn test_start_and_stop() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    let socket = socket(&home, "run/daemon.sock");
+    let daemon = |action: &str| {
+        docker_tags(
+            &registry,
+            home.path(),
+            &["daemon", action, "--daemon-socket", &socket],
+        )
+        .output()
+        .unwrap()
+    };
+
+    let output = daemon("start");
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Started the daemon"));
+    let mode = |path: &Path| path.metadata().unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(Path::new(&socket)), 0o600);
+    assert_eq!(mode(&home.path().join("run")), 0o700);
+
+    let output = daemon("start");
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("The daemon is already running"));
+
+    let image = format!("{}/app", registry.host());
+    let output = docker_tags(
+        &registry,
+        home.path(),
+        &["--via-daemon", "--daemon-socket", &socket, &image],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let output = daemon("stop");
+    assert!(output.status.success(), "{output:?}");
+    wait_for("the socket to be removed", || !Path::new(&socket).exists());
+    let output = daemon("stop");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("No daemon is running"));
+}
+
+#[test]
+fn test_idle_timeout() {
+    let registry = registry();
+    let mut daemon = Daemon::start(&registry, &["--idle-timeout", "1"]);
+    let started = Instant::now();
+    wait_for("the daemon to exit", || {
+        daemon.child.try_wait().unwrap().is_some()
+    });
+    assert!(started.elapsed() >= Duration::from_millis(900));
+    assert!(daemon.child.wait().unwrap().success());
+    assert!(!Path::new(&daemon.socket()).exists());
+}
+
+#[test]
+fn test_stale_socket() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    let socket = socket(&home, "daemon.sock");
+    // left behind by a daemon which was killed
+    drop(UnixListener::bind(&socket).unwrap());
+
+    for action in ["start", "stop"] {
+        let output = docker_tags(
+            &registry,
+            home.path(),
+            &["daemon", action, "--daemon-socket", &socket],
+        )
+        .output()
+        .unwrap();
+        assert!(output.status.success(), "{output:?}");
+    }
+    assert!(!Path::new(&socket).exists());
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{MockRegistry, Request, Response, paginate};
+use docker_tags::{Anonymous, Client, Image, Tag};
+use sha2::{Digest, Sha256};
+
+/// `1.0.0` to `1.0.199`, listed 50 to a page
+fn tags() -> Vec<String> {
+    (0..200).map(|patch| format!("1.0.{patch}")).collect()
+}
+
+/// Manifest of `tag`, told apart from the others by an annotation
+fn manifest(tag: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": format!("sha256:{:064}", 0),
+            "size": 0,
+        },
+        "layers": [],
+        "annotations": { "tag": tag },
+    })
+    .to_string()
+}
+
+fn digest(tag: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(manifest(tag)))
+}
+
+/// Tags of `app`, each with a manifest of its own but `1.0.7`, which has none
+fn respond(req: &Request) -> Response {
+    let path = req.url.path();
+    if path == "/v2/app/tags/list" {
+        let all = tags();
+        let tags: Vec<_> = paginate(&all, req).into_iter().take(50).collect();
+        let mut resp = Response::json(200, serde_json::json!({ "tags": tags }).to_string());
+        if let Some(last) = tags.last().filter(|last| Some(*last) != all.last()) {
+            resp = resp.header(
+                "Link",
+                format!(r#"</v2/app/tags/list?n=50&last={last}>; rel="next""#),
+            );
+        }
+        return resp;
+    }
+    match path.strip_prefix("/v2/app/manifests/") {
+        Some(tag) if tag != "1.0.7" => Response::json(200, manifest(tag))
+            .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+            .header("Docker-Content-Digest", digest(tag)),
+        _ => Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_digests() {
+    let registry = MockRegistry::start(respond);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::new(registry.host(), "app");
+    let tags: Vec<_> = ["1.0.3", "1.0.7", "1.0.12"].map(Tag::new).into();
+
+    let (digests, warnings) = client.fetch_digests(&image, &tags).await;
+    assert_eq!(
+        digests,
+        [Some(digest("1.0.3")), None, Some(digest("1.0.12"))]
+    );
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].to_string().contains("1.0.7"), "{warnings:?}");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_digests_after_limit() {
+    let registry = MockRegistry::start_tls(respond);
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/app", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg("--digests")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        output
+    };
+    let manifests = || {
+        registry
+            .requests()
+            .into_iter()
+            .filter(|req| req.url.path().starts_with("/v2/app/manifests/"))
+            .count()
+    };
+
+    // sorting needs every tag, but only the three printed are resolved
+    let output = docker_tags(&["-n", "3"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "1.0.199\t{}\n1.0.198\t{}\n1.0.197\t{}\n",
+            digest("1.0.199"),
+            digest("1.0.198"),
+            digest("1.0.197")
+        )
+    );
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 4);
+    assert_eq!(manifests(), 3);
+
+    // filtered and reversed first
+    let output = docker_tags(&["-f", r"^1\.0\.[0-9]$", "-r", "-n", "2"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("1.0.0\t{}\n1.0.1\t{}\n", digest("1.0.0"), digest("1.0.1"))
+    );
+    assert_eq!(manifests(), 5);
+
+    // in registry order, the listing stops early too
+    let listed = registry.requests_to("/v2/app/tags/list").len();
+    let output = docker_tags(&["--no-sort", "-n", "8", "-o", "json"]);
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), listed + 1);
+    assert_eq!(manifests(), 13);
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let tags = listing["tags"].as_array().unwrap();
+    assert_eq!(tags.len(), 8);
+    assert_eq!(tags[0]["digest"], digest("1.0.0"));
+    assert_eq!(tags[7]["digest"], serde_json::Value::Null);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Warning: failed to fetch manifest for 1.0.7"),
+        "{stderr}"
+    );
+
+    // columns under a header, or each tag as a template has it
+    let output = docker_tags(&["-f", r"^1\.0\.[67]$", "-o", "table"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("NAME    DIGEST\n1.0.7   -\n1.0.6   {}\n", digest("1.0.6"))
+    );
+    let output = docker_tags(&["-f", r"^1\.0\.[67]$", "--template", "{{{name}}} {digest}"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{{1.0.7}} -\n{{1.0.6}} {}\n", digest("1.0.6"))
+    );
+}
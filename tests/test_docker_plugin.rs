@@ -0,0 +1,142 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+    sync::{Arc, OnceLock},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use common::{MockRegistry, Response};
+
+/// Tags of `org/private` for the token issued to `ci:hunter2` only
+fn registry() -> MockRegistry {
+    let base = Arc::new(OnceLock::<String>::new());
+    let login = format!("Basic {}", STANDARD.encode("ci:hunter2"));
+    let registry = MockRegistry::start_tls({
+        let base = base.clone();
+        move |req| match (req.url.path(), req.header("Authorization")) {
+            ("/token", Some(auth)) if auth == login => {
+                Response::json(200, r#"{"token":"ci-token"}"#)
+            }
+            ("/token", _) => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#),
+            ("/v2/org/private/tags/list", Some("Bearer ci-token")) => {
+                Response::json(200, r#"{"tags":["1.0.0","latest","1.1.0"]}"#)
+            }
+            _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(
+                    r#"Bearer realm="{}/token",service="mock",scope="repository:org/private:pull""#,
+                    base.get().unwrap()
+                ),
+            ),
+        }
+    });
+    base.set(registry.url()).unwrap();
+    registry
+}
+
+/// Run the binary with `args` in an environment without a Docker config,
+/// as docker runs plugins if `plugin`
+fn docker_tags(home: &Path, plugin: bool, env: &[(&str, &Path)], args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_docker-tags"));
+    command
+        .env_remove("DOCKER_CONFIG")
+        .env("HOME", home)
+        .env("XDG_STATE_HOME", home)
+        .envs(env.iter().copied())
+        .args(args);
+    if plugin {
+        command.env("DOCKER_CLI_PLUGIN_ORIGINAL_CLI_COMMAND", "/usr/bin/docker");
+    }
+    command.output().unwrap()
+}
+
+#[test]
+fn test_plugin_metadata() {
+    let home = tempfile::tempdir().unwrap();
+
+    let output = docker_tags(home.path(), false, &[], &["docker-cli-plugin-metadata"]);
+    assert!(output.status.success(), "{output:?}");
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        metadata,
+        serde_json::json!({
+            "SchemaVersion": "0.1.0",
+            "Vendor": "toogle",
+            "Version": env!("CARGO_PKG_VERSION"),
+            "ShortDescription": "List tags for Docker images with sensible sorting",
+            "URL": "https://github.com/toogle/docker-tags",
+        })
+    );
+}
+
+#[test]
+fn test_plugin_invocation() {
+    let registry = registry();
+    let host = registry.host();
+    let home = tempfile::tempdir().unwrap();
+    let config = tempfile::tempdir().unwrap();
+    fs::write(
+        config.path().join("config.json"),
+        format!(
+            r#"{{"auths": {{"{host}": {{"auth": "{}"}}}}}}"#,
+            STANDARD.encode("ci:hunter2")
+        ),
+    )
+    .unwrap();
+    let image = format!("{host}/org/private");
+    let ca = format!(
+        "--ca-cert={host}={}/tests/fixtures/tls/ca.pem",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let docker_config = [("DOCKER_CONFIG", config.path())];
+
+    let standalone = docker_tags(
+        home.path(),
+        false,
+        &docker_config,
+        &[&image, &ca, "--no-cache"],
+    );
+    assert!(standalone.status.success(), "{standalone:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&standalone.stdout),
+        "1.1.0\n1.0.0\nlatest\n"
+    );
+
+    // `docker tags …` with the config of the environment
+    let plugin = docker_tags(
+        home.path(),
+        true,
+        &docker_config,
+        &["tags", &image, &ca, "--no-cache"],
+    );
+    assert!(plugin.status.success(), "{plugin:?}");
+    assert_eq!(plugin.stdout, standalone.stdout);
+
+    // `docker --config DIR --context ci tags …`
+    let config_dir = config.path().to_str().unwrap();
+    let plugin = docker_tags(
+        home.path(),
+        true,
+        &[],
+        &[
+            "--config",
+            config_dir,
+            "--context=ci",
+            "tags",
+            &image,
+            &ca,
+            "--no-cache",
+        ],
+    );
+    assert!(plugin.status.success(), "{plugin:?}");
+    assert_eq!(plugin.stdout, standalone.stdout);
+
+    // without the config, the registry turns the listing down
+    let plugin = docker_tags(home.path(), true, &[], &["tags", &image, &ca, "--no-cache"]);
+    assert!(!plugin.status.success(), "{plugin:?}");
+}
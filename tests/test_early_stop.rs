@@ -0,0 +1,61 @@
+mod common;
+
+use common::MockRegistry;
+use docker_tags::{Anonymous, Client, Image, Tag};
+
+fn setup() -> (MockRegistry, Client, Image) {
+    // 1.26.x on the first two pages, 1.27.x on the last three
+    let names: Vec<_> = (0..200)
+        .map(|i| format!("1.26.{i}"))
+        .chain((0..300).map(|i| format!("1.27.{i}")))
+        .collect();
+    let names: Vec<_> = names.iter().map(String::as_str).collect();
+    let registry = MockRegistry::with_tags("app", &names, false);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    (registry, client, image)
+}
+
+fn is_1_27(tag: &Tag) -> bool {
+    tag.name().starts_with("1.27.")
+}
+
+#[tokio::test]
+async fn test_filtered_stops_after_limit() {
+    let (registry, client, image) = setup();
+
+    let (tags, outcome) = client
+        .fetch_tags_filtered(&image, is_1_27, Some(3))
+        .await
+        .unwrap();
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    assert_eq!(names, ["1.27.0", "1.27.1", "1.27.2"]);
+    assert!(outcome.is_complete());
+    // the third page has the first matches, the remaining two are never fetched
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 3);
+}
+
+#[tokio::test]
+async fn test_filtered_without_limit_fetches_everything() {
+    let (registry, client, image) = setup();
+
+    let (tags, _) = client
+        .fetch_tags_filtered(&image, is_1_27, None)
+        .await
+        .unwrap();
+    assert_eq!(tags.len(), 300);
+    // five full pages and the empty one confirming the end
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 6);
+}
+
+#[tokio::test]
+async fn test_sorted_listing_fetches_everything() {
+    let (registry, client, image) = setup();
+
+    let tags = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), 500);
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 6);
+}
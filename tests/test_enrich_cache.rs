@@ -0,0 +1,169 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    process::{Command, Output},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use common::{MockRegistry, Request, Response};
+use sha2::{Digest, Sha256};
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn config(created: &str) -> String {
+    serde_json::json!({ "architecture": "amd64", "os": "linux", "created": created }).to_string()
+}
+
+fn manifest(config: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(config),
+            "size": config.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// Registry serving the `created` dates in `tags`, answering the manifests
+/// of 1.2 and 1.4 with 502 while `flaky`
+fn registry(tags: Arc<Mutex<BTreeMap<String, String>>>, flaky: Arc<AtomicBool>) -> MockRegistry {
+    MockRegistry::start_tls(move |req: &Request| {
+        let tags = tags.lock().unwrap();
+        let path = req.url.path();
+        if path == "/v2/app/tags/list" {
+            let names: Vec<_> = tags.keys().collect();
+            return Response::json(200, serde_json::json!({ "tags": names }).to_string());
+        }
+        let reference = path.rsplit('/').next().unwrap_or_default();
+        if path.contains("/manifests/")
+            && flaky.load(Ordering::SeqCst)
+            && ["1.2", "1.4"].contains(&reference)
+        {
+            return Response::json(502, "{}");
+        }
+        for created in tags.values() {
+            let config = config(created);
+            let (content_type, body) = match path {
+                _ if path.contains("/blobs/") && reference == digest(&config) => {
+                    ("application/octet-stream", config)
+                }
+                _ if path.contains("/manifests/") && tags.get(reference) == Some(created) => (
+                    "application/vnd.oci.image.manifest.v1+json",
+                    manifest(&config),
+                ),
+                _ => continue,
+            };
+            return Response::json(200, body.clone())
+                .header("Content-Type", content_type)
+                .header("Docker-Content-Digest", digest(&body));
+        }
+        Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#)
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, state: &Path, args: &[&str]) -> Output {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", state)
+        .env("XDG_STATE_HOME", state)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/app", registry.host()))
+        .args(["--added-after-tag", "base", "--enrich-cache"])
+        .arg(state.join("cache.json"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    output
+}
+
+/// Tags whose manifests or configs were requested since `from`
+fn looked_up(registry: &MockRegistry, from: usize, kind: &str) -> Vec<String> {
+    let mut tags: Vec<_> = registry.requests()[from..]
+        .iter()
+        .filter(|req| req.url.path().contains(kind))
+        .map(|req| req.url.path().rsplit('/').next().unwrap().to_string())
+        .collect();
+    tags.sort();
+    tags
+}
+
+#[test]
+fn test_second_run_refetches_only_failures() {
+    let tags: BTreeMap<_, _> = [
+        ("base", "2024-01-01T00:00:00Z"),
+        ("1.1", "2024-02-01T00:00:00Z"),
+        ("1.2", "2024-03-01T00:00:00Z"),
+        ("1.3", "2024-04-01T00:00:00Z"),
+        ("1.4", "2024-05-01T00:00:00Z"),
+    ]
+    .into_iter()
+    .map(|(name, created)| (name.to_string(), created.to_string()))
+    .collect();
+    let tags = Arc::new(Mutex::new(tags));
+    let flaky = Arc::new(AtomicBool::new(true));
+    let registry = registry(tags.clone(), flaky.clone());
+    let state = tempfile::tempdir().unwrap();
+    let stdout = |output: &Output| String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = |output: &Output| String::from_utf8_lossy(&output.stderr).into_owned();
+
+    let output = docker_tags(&registry, state.path(), &["--retry-failed"]);
+    assert_eq!(stdout(&output), "1.3\n1.1\n");
+    assert!(
+        stderr(&output).contains("Warning: failed to fetch manifest for 1.2"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stderr(&output).contains("Refetched 4 tags: 1.1, 1.2, 1.3, 1.4\n"));
+    let cache: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(state.path().join("cache.json")).unwrap()).unwrap();
+    assert_eq!(cache["version"], 1);
+    let cached: Vec<_> = cache["entries"].as_object().unwrap().keys().collect();
+    assert_eq!(cached, ["1.1", "1.3"]);
+
+    flaky.store(false, Ordering::SeqCst);
+    let from = registry.requests().len();
+    let output = docker_tags(&registry, state.path(), &["--retry-failed"]);
+    assert_eq!(stdout(&output), "1.4\n1.3\n1.2\n1.1\n");
+    assert!(
+        stderr(&output)
+            .contains("Filled 2 tags from the cache: 1.1, 1.3\nRefetched 2 tags: 1.2, 1.4\n")
+    );
+    // besides those of the reference tag
+    assert_eq!(
+        looked_up(&registry, from, "/manifests/"),
+        ["1.2", "1.4", "base"]
+    );
+    assert_eq!(looked_up(&registry, from, "/blobs/").len(), 3);
+
+    // a tag moving along with a new one changes the listing, so every tag is
+    // checked again, but only configs of new digests are fetched
+    {
+        let mut tags = tags.lock().unwrap();
+        tags.insert("1.1".to_string(), "2024-06-01T00:00:00Z".to_string());
+        tags.insert("1.5".to_string(), "2024-07-01T00:00:00Z".to_string());
+    }
+    let from = registry.requests().len();
+    let output = docker_tags(&registry, state.path(), &[]);
+    assert_eq!(stdout(&output), "1.5\n1.1\n1.4\n1.3\n1.2\n");
+    assert_eq!(stderr(&output), "");
+    assert_eq!(
+        looked_up(&registry, from, "/manifests/"),
+        ["1.1", "1.2", "1.3", "1.4", "1.5", "base"]
+    );
+    assert_eq!(looked_up(&registry, from, "/blobs/").len(), 3);
+}
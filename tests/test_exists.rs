@@ -0,0 +1,159 @@
+mod common;
+
+#[cfg(feature = "cli")]
+use std::process::Command;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Anonymous, Client, CredentialProvider, Credentials, ExistsOutcome, Image};
+
+struct StoredLogin;
+
+#[async_trait]
+impl CredentialProvider for StoredLogin {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic("user", "secret")))
+    }
+}
+
+/// Tags of `org/public` for anyone, of `org/private` for the token issued to
+/// stored credentials only, behind a bearer challenge like Harbor's
+fn repositories(base: &str, req: &Request) -> Response {
+    let path = req.url.path();
+    if path == "/token" {
+        return match req.header("Authorization") {
+            Some(_) => Response::json(200, r#"{"token":"user-token"}"#),
+            None => Response::json(200, r#"{"token":"anonymous-token"}"#),
+        };
+    }
+    let Some(token) = req.header("Authorization") else {
+        let repository = path
+            .strip_prefix("/v2/")
+            .and_then(|p| p.strip_suffix("/tags/list"))
+            .unwrap_or_default();
+        return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+            "WWW-Authenticate",
+            format!(
+                r#"Bearer realm="{base}/token",service="mock",scope="repository:{repository}:pull""#
+            ),
+        );
+    };
+    match path {
+        "/v2/org/public/tags/list" => Response::json(200, r#"{"tags":["1.0.0"]}"#),
+        "/v2/org/private/tags/list" if token == "Bearer user-token" => {
+            Response::json(200, r#"{"tags":["1.0.0"]}"#)
+        }
+        "/v2/org/private/tags/list" => {
+            Response::json(403, r#"{"errors":[{"code":"DENIED","message":"denied"}]}"#)
+        }
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    }
+}
+
+async fn exists(registry: &MockRegistry, client: &Client, repository: &str) -> ExistsOutcome {
+    let image = Image::new(registry.host(), repository);
+    client.repository_exists(&image).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_repository_exists() {
+    let registry = MockRegistry::start_with_base(repositories);
+    let anonymous = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let logged_in = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(StoredLogin)
+        .build();
+
+    for client in [&anonymous, &logged_in] {
+        assert_eq!(
+            exists(&registry, client, "org/public").await,
+            ExistsOutcome::Exists
+        );
+        assert_eq!(
+            exists(&registry, client, "org/missing").await,
+            ExistsOutcome::NotFound
+        );
+    }
+    assert_eq!(
+        exists(&registry, &logged_in, "org/private").await,
+        ExistsOutcome::Exists
+    );
+    assert_eq!(
+        exists(&registry, &anonymous, "org/private").await,
+        ExistsOutcome::AccessDenied
+    );
+
+    // a single tag is asked for, whatever the repository holds
+    let listings: Vec<_> = registry
+        .requests()
+        .into_iter()
+        .filter(|r| r.url.path().ends_with("/tags/list"))
+        .collect();
+    assert!(
+        listings
+            .iter()
+            .all(|r| r.query("n").as_deref() == Some("1"))
+    );
+}
+
+#[tokio::test]
+async fn test_unreachable_registry_is_an_error() {
+    let registry = MockRegistry::start(|_| Response::json(500, "{}"));
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::new(registry.host(), "org/public");
+    assert!(client.repository_exists(&image).await.is_err());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_exists_command() {
+    let registry = MockRegistry::start_tls(|req| match req.url.path() {
+        "/v2/org/public/tags/list" => Response::json(200, r#"{"tags":["1.0.0"]}"#),
+        "/v2/org/private/tags/list" => {
+            Response::json(403, r#"{"errors":[{"code":"DENIED","message":"denied"}]}"#)
+        }
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    });
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+
+    for (repository, code, outcome) in [
+        ("org/public", 0, "exists"),
+        ("org/missing", 4, "not found"),
+        ("org/private", 6, "access denied"),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .arg("exists")
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg(format!("{}/{repository}", registry.host()))
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(code), "{output:?}");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{}/{repository}: {outcome}\n", registry.host())
+        );
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .args(["exists", "-o", "json", "--ca-cert"])
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/org/private", registry.host()))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(6), "{output:?}");
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap(),
+        serde_json::json!({
+            "image": format!("{}/org/private", registry.host()),
+            "outcome": "access denied",
+        })
+    );
+}
@@ -0,0 +1,291 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    process::{Command, Output},
+};
+
+use common::{MockRegistry, Response, paginate};
+use sha2::{Digest, Sha256};
+
+fn manifest(version: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": digest(version), "size": 2},
+        "layers": [],
+    })
+    .to_string()
+}
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// `org/app` up to 1.27.1 and a release candidate of 1.28.0, all of them
+/// signed but 1.27.1
+fn registry() -> MockRegistry {
+    let mut tags: Vec<_> = ["1.25.3", "1.26.0", "1.27.1", "1.28.0-rc.1", "latest"]
+        .map(String::from)
+        .to_vec();
+    for signed in ["1.25.3", "1.26.0", "1.28.0-rc.1"] {
+        tags.push(format!(
+            "{}.sig",
+            digest(&manifest(signed)).replace(':', "-")
+        ));
+    }
+    tags.sort();
+    MockRegistry::start_tls(move |req| {
+        let path = req.url.path();
+        if path == "/v2/org/app/tags/list" {
+            let page = paginate(&tags, req);
+            return Response::json(200, serde_json::json!({ "tags": page }).to_string());
+        }
+        match path.strip_prefix("/v2/org/app/manifests/") {
+            Some(version) => {
+                let body = manifest(version);
+                Response::json(200, body.clone())
+                    .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+                    .header("Docker-Content-Digest", digest(&body))
+            }
+            None => Response::new(404),
+        }
+    })
+}
+
+/// Run the binary against `registry`, trusting its CA, `{image}` in `args`
+/// standing for `org/app` there
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let image = format!("{}/org/app", registry.host());
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .args(args.iter().map(|arg| arg.replace("{image}", &image)))
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_fail_on_outdated() {
+    let registry = registry();
+
+    // one minor version behind is fine, two aren't
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--fail-on",
+            "outdated:minor>=2",
+            "--current",
+            "1.26.0",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--fail-on",
+            "outdated:minor>=2",
+            "--current",
+            "1.25.3",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{output:?}");
+    assert_eq!(
+        stderr(&output),
+        "Failed --fail-on outdated:minor>=2: 1.27.1 is 2 minor versions ahead of 1.25.3\n"
+    );
+    // the listing is printed all the same
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("1.28.0-rc.1\n1.27.1\n"));
+
+    // the pre-release isn't stable
+    let output = docker_tags(
+        &registry,
+        &["{image}", "--fail-on", "outdated", "--current", "1.27.1"],
+    );
+    assert!(output.status.success(), "{output:?}");
+
+    // the filters narrow the candidates
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--filter",
+            r"^1\.2[56]",
+            "--fail-on",
+            "outdated",
+            "--current",
+            "1.25.3",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{output:?}");
+    assert!(stderr(&output).contains("1.26.0 is newer than 1.25.3"));
+
+    let output = docker_tags(&registry, &["{image}", "--fail-on", "outdated"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("--fail-on outdated needs --current"),
+        "{output:?}"
+    );
+}
+
+#[test]
+fn test_fail_on_no_match() {
+    let registry = registry();
+
+    let output = docker_tags(
+        &registry,
+        &["{image}", "--filter", "^2", "--fail-on", "no-match"],
+    );
+    assert_eq!(output.status.code(), Some(8), "{output:?}");
+    assert_eq!(
+        stderr(&output),
+        "Failed --fail-on no-match: no tag matched\n"
+    );
+
+    let output = docker_tags(
+        &registry,
+        &["{image}", "--filter", "^1", "--fail-on", "no-match"],
+    );
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn test_fail_on_unsigned() {
+    let registry = registry();
+
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--filter",
+            "^1",
+            "--show-signed",
+            "--fail-on",
+            "unsigned",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(9), "{output:?}");
+    assert_eq!(
+        stderr(&output),
+        "Failed --fail-on unsigned: 1.27.1 isn't signed\n"
+    );
+
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--filter",
+            r"^1\.2[56]",
+            "--show-signed",
+            "--fail-on",
+            "unsigned",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+
+    let output = docker_tags(&registry, &["{image}", "--fail-on", "unsigned"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+}
+
+#[test]
+fn test_fail_on_combination() {
+    let registry = registry();
+
+    // any condition fails, the first one met picking the exit code
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--filter",
+            "^1",
+            "--show-signed",
+            "--fail-on",
+            "no-match,unsigned,outdated:major",
+            "--current",
+            "1.25.3",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(9), "{output:?}");
+    let output = docker_tags(
+        &registry,
+        &[
+            "{image}",
+            "--filter",
+            "^1",
+            "--show-signed",
+            "--fail-on",
+            "outdated,unsigned",
+            "--current",
+            "1.25.3",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{output:?}");
+    assert_eq!(
+        stderr(&output),
+        "Failed --fail-on outdated: 1.27.1 is newer than 1.25.3\n\
+         Failed --fail-on unsigned: 1.27.1 isn't signed\n"
+    );
+
+    let output = docker_tags(&registry, &["{image}", "--fail-on", "stale"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
+
+#[test]
+fn test_check_upgrades_fail_on() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let dockerfile = dir.path().join("Dockerfile");
+    fs::write(
+        &dockerfile,
+        format!(
+            "FROM {host}/org/app:1.26.0 AS build\nFROM {host}/org/app:1.25.3\n",
+            host = registry.host()
+        ),
+    )
+    .unwrap();
+    let dockerfile = dockerfile.to_str().unwrap();
+
+    let output = docker_tags(
+        &registry,
+        &[
+            "check-upgrades",
+            dockerfile,
+            "--fail-on",
+            "outdated:minor>=2",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{output:?}");
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "Failed --fail-on outdated:minor>=2: 2: {}/org/app: \
+             1.27.1 is 2 minor versions ahead of 1.25.3\n",
+            registry.host()
+        )
+    );
+
+    // without conditions met, newer tags still exit with 3
+    let output = docker_tags(
+        &registry,
+        &["check-upgrades", dockerfile, "--fail-on", "outdated:major"],
+    );
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    let output = docker_tags(
+        &registry,
+        &["check-upgrades", dockerfile, "--fail-on", "unsigned"],
+    );
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Error, FetchManyOptions, Image};
+
+fn image(registry: &MockRegistry, repository: &str) -> Image {
+    Image::try_from(format!("{}/{repository}", registry.host()).as_str()).unwrap()
+}
+
+#[tokio::test]
+async fn test_fetch_many_shares_tokens() {
+    let first = MockRegistry::with_repositories(
+        &[("team/app", &["1.0.0", "1.1.0"]), ("team/lib", &["0.1.0"])],
+        true,
+    );
+    let second = MockRegistry::with_repositories(
+        &[("tools/cli", &["2.0.0"]), ("other/app", &["3.0.0"])],
+        true,
+    );
+    let client = Client::builder()
+        .insecure_registry(first.host())
+        .insecure_registry(second.host())
+        .credential_provider(Anonymous)
+        .build();
+
+    let images = vec![
+        image(&first, "team/app"),
+        image(&second, "tools/cli"),
+        image(&first, "team/missing"),
+        image(&second, "other/app"),
+        image(&first, "team/lib"),
+    ];
+    let results = client
+        .fetch_tags_many(images, FetchManyOptions { concurrency: 3 })
+        .await;
+
+    let summary: Vec<_> = results
+        .iter()
+        .map(|(image, result)| {
+            let tags = result.as_ref().map(|tags| tags.len()).ok();
+            (image.to_string(), tags)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (format!("{}/team/app", first.host()), Some(2)),
+            (format!("{}/tools/cli", second.host()), Some(1)),
+            (format!("{}/team/missing", first.host()), None),
+            (format!("{}/other/app", second.host()), Some(1)),
+            (format!("{}/team/lib", first.host()), Some(1)),
+        ]
+    );
+    let err = results[2].1.as_ref().unwrap_err();
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::NotFound));
+
+    let first_tokens = first.requests_to("/token");
+    let second_tokens = second.requests_to("/token");
+    assert_eq!(first_tokens.len() + second_tokens.len(), 2);
+    assert_eq!(first_tokens.len(), 1);
+    let scopes: Vec<_> = first_tokens[0]
+        .url
+        .query_pairs()
+        .filter(|(k, _)| k == "scope")
+        .map(|(_, v)| v.into_owned())
+        .collect();
+    assert_eq!(
+        scopes,
+        [
+            "repository:team/app:pull",
+            "repository:team/missing:pull",
+            "repository:team/lib:pull"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_fetch_many_cancel() {
+    let registry = MockRegistry::start(|_| {
+        thread::sleep(Duration::from_secs(30));
+        Response::new(500)
+    });
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let images = vec![image(&registry, "app"), image(&registry, "lib")];
+
+    let fetch = client.fetch_tags_many(images, FetchManyOptions::default());
+    let result = tokio::time::timeout(Duration::from_millis(200), fetch).await;
+    assert!(result.is_err());
+}
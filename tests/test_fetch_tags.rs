@@ -1,7 +1,17 @@
+//! Tests against live registries, which only run with `DOCKER_TAGS_LIVE_TESTS`
+//! set, so that CI and offline builds don't depend on them
+
 use docker_tags::Image;
 
+fn live() -> bool {
+    std::env::var_os("DOCKER_TAGS_LIVE_TESTS").is_some()
+}
+
 #[tokio::test]
 async fn test_docker_hub_existing_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("nginx").unwrap();
     let tags = image.fetch_tags().await.unwrap();
     assert!(tags.len() > 1000);
@@ -13,6 +23,9 @@ async fn test_docker_hub_existing_image() {
 
 #[tokio::test]
 async fn test_docker_hub_existing_image_with_namespace() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("prom/prometheus").unwrap();
     let tags = image.fetch_tags().await.unwrap();
     assert!(tags.len() > 300);
@@ -24,6 +37,9 @@ async fn test_docker_hub_existing_image_with_namespace() {
 
 #[tokio::test]
 async fn test_docker_hub_nonexisting_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("nonexistingimage").unwrap();
     let err = image.fetch_tags().await.unwrap_err();
     assert_eq!(err.to_string(), "Image not found");
@@ -35,6 +51,9 @@ async fn test_docker_hub_nonexisting_image() {
 
 #[tokio::test]
 async fn test_docker_hub_nonexisting_image_with_namespace() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("prom/nonexistingimage").unwrap();
     let err = image.fetch_tags().await.unwrap_err();
     assert_eq!(err.to_string(), "Image not found");
@@ -46,6 +65,9 @@ async fn test_docker_hub_nonexisting_image_with_namespace() {
 
 #[tokio::test]
 async fn test_ghcr_existing_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("ghcr.io/xtls/xray-core").unwrap();
     let tags = image.fetch_tags().await.unwrap();
     assert!(tags.len() > 1000);
@@ -53,6 +75,9 @@ async fn test_ghcr_existing_image() {
 
 #[tokio::test]
 async fn test_ghcr_nonexisting_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("ghcr.io/xtls/nonexistingimage").unwrap();
     let err = image.fetch_tags().await.unwrap_err();
     assert_eq!(err.to_string(), "Image not found");
@@ -60,6 +85,9 @@ async fn test_ghcr_nonexisting_image() {
 
 #[tokio::test]
 async fn test_quay_existing_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("quay.io/prometheus/prometheus").unwrap();
     let tags = image.fetch_tags().await.unwrap();
     assert!(tags.len() > 300);
@@ -67,6 +95,9 @@ async fn test_quay_existing_image() {
 
 #[tokio::test]
 async fn test_quay_nonexisting_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("quay.io/prometheus/nonexistingimage").unwrap();
     let err = image.fetch_tags().await.unwrap_err();
     assert_eq!(err.to_string(), "Image not found");
@@ -74,6 +105,9 @@ async fn test_quay_nonexisting_image() {
 
 #[tokio::test]
 async fn test_angie_existing_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("docker.angie.software/angie").unwrap();
     let tags = image.fetch_tags().await.unwrap();
     assert!(tags.len() > 200);
@@ -81,7 +115,35 @@ async fn test_angie_existing_image() {
 
 #[tokio::test]
 async fn test_angie_nonexisting_image() {
+    if !live() {
+        return;
+    }
     let image = Image::try_from("docker.angie.software/nonexistingimage").unwrap();
     let err = image.fetch_tags().await.unwrap_err();
     assert_eq!(err.to_string(), "Image not found");
 }
+
+#[tokio::test]
+async fn test_mcr_existing_image() {
+    if !live() {
+        return;
+    }
+    let image = Image::try_from("mcr.microsoft.com/windows/servercore").unwrap();
+    let mut tags = image.fetch_tags().await.unwrap();
+    assert!(tags.len() > 500);
+    docker_tags::sort_tags_desc(&mut tags);
+    assert!(matches!(
+        tags[0].sort_key(),
+        docker_tags::SortKey::Windows(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_mcr_nonexisting_image() {
+    if !live() {
+        return;
+    }
+    let image = Image::try_from("mcr.microsoft.com/windows/nonexistingimage").unwrap();
+    let err = image.fetch_tags().await.unwrap_err();
+    assert_eq!(err.to_string(), "Image not found");
+}
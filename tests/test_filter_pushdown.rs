@@ -0,0 +1,180 @@
+#![cfg(feature = "regex")]
+
+mod common;
+
+use common::{MockRegistry, Response, paginate};
+use docker_tags::{Anonymous, Client, Image, SearchApi, unstable::time::format_rfc3339};
+use regex::Regex;
+use reqwest::Url;
+
+fn dataset() -> Vec<String> {
+    let mut tags = vec!["latest".to_string(), "edge-alpine".to_string()];
+    for minor in 0..120 {
+        tags.push(format!("1.{minor}.0"));
+        tags.push(format!("1.{minor}.0-alpine"));
+    }
+    tags
+}
+
+/// Serves the tags list API as well as Docker Hub's and Quay's search APIs
+fn registry() -> MockRegistry {
+    let tags = dataset();
+    MockRegistry::start(move |req| {
+        let path = req.url.path();
+        let search = |needle: &str| {
+            tags.iter()
+                .filter(|t| t.contains(needle))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        if path == "/v2/repositories/team/app/" {
+            return Response::json(
+                200,
+                r#"{"name":"app","last_updated":"2026-10-01T08:30:00.123456Z"}"#,
+            );
+        }
+        if path == "/v2/repositories/team/app/tags" {
+            let needle = req.query("name").unwrap();
+            let found = search(&needle);
+            let page: usize = req.query("page").map_or(1, |p| p.parse().unwrap());
+            let chunk: Vec<_> = found.iter().skip((page - 1) * 100).take(100).collect();
+            let next = (page * 100 < found.len()).then(|| {
+                let mut next = req.url.clone();
+                next.query_pairs_mut()
+                    .clear()
+                    .append_pair("name", &needle)
+                    .append_pair("page", &(page + 1).to_string());
+                next.to_string()
+            });
+            let results: Vec<_> = chunk
+                .iter()
+                .map(|name| serde_json::json!({ "name": name }))
+                .collect();
+            return Response::json(
+                200,
+                serde_json::json!({ "next": next, "results": results }).to_string(),
+            );
+        }
+        if path == "/api/v1/repository/team/app/tag/" {
+            let filter = req.query("filter_tag_name").unwrap();
+            let needle = filter
+                .strip_prefix("like:%")
+                .unwrap()
+                .strip_suffix('%')
+                .unwrap();
+            let found = search(needle);
+            let page: usize = req.query("page").unwrap().parse().unwrap();
+            let chunk: Vec<_> = found.iter().skip((page - 1) * 100).take(100).collect();
+            let tags: Vec<_> = chunk
+                .iter()
+                .map(|name| serde_json::json!({ "name": name }))
+                .collect();
+            return Response::json(
+                200,
+                serde_json::json!({ "tags": tags, "page": page, "has_additional": page * 100 < found.len() })
+                    .to_string(),
+            );
+        }
+        if path == "/v2/team/app/tags/list" {
+            let page = paginate(&tags, req);
+            return Response::json(200, serde_json::json!({ "tags": page }).to_string());
+        }
+        Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#)
+    })
+}
+
+fn client(registry: &MockRegistry, api: SearchApi, pushdown: bool) -> Client {
+    Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .search_api(registry.host(), api)
+        .filter_pushdown(pushdown)
+        .build()
+}
+
+async fn names(client: &Client, image: &Image, pattern: &str) -> Vec<String> {
+    let (tags, outcome) = client
+        .fetch_tags_matching(image, &Regex::new(pattern).unwrap())
+        .await
+        .unwrap();
+    assert!(outcome.is_complete());
+    let mut names: Vec<_> = tags.iter().map(|t| t.name().to_string()).collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_pushdown_gives_identical_results() {
+    for hub in [true, false] {
+        let registry = registry();
+        let base = Url::parse(&registry.url()).unwrap();
+        let api = match hub {
+            true => SearchApi::DockerHub(base),
+            false => SearchApi::Quay(base),
+        };
+        let image = Image::try_from(format!("{}/team/app", registry.host()).as_str()).unwrap();
+
+        // the server matches substrings, the anchors are only checked locally
+        for pattern in ["alpine", "^1\\.11", "-alpine$", "^edge"] {
+            let pushed = names(&client(&registry, api.clone(), true), &image, pattern).await;
+            let listed = names(&client(&registry, api.clone(), false), &image, pattern).await;
+            assert!(!pushed.is_empty(), "{pattern}");
+            assert_eq!(pushed, listed, "{pattern}");
+        }
+        assert!(!registry.requests_to("/v2/team/app/tags/list").is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_pushdown_skips_tags_list() {
+    let registry = registry();
+    let base = Url::parse(&registry.url()).unwrap();
+    let image = Image::try_from(format!("{}/team/app", registry.host()).as_str()).unwrap();
+
+    let client = client(&registry, SearchApi::DockerHub(base), true);
+    names(&client, &image, "alpine").await;
+    assert!(registry.requests_to("/v2/team/app/tags/list").is_empty());
+    // 121 matches in pages of 100
+    assert_eq!(
+        registry.requests_to("/v2/repositories/team/app/tags").len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_complex_regex_skips_pushdown() {
+    let registry = registry();
+    let base = Url::parse(&registry.url()).unwrap();
+    let image = Image::try_from(format!("{}/team/app", registry.host()).as_str()).unwrap();
+
+    let client = client(&registry, SearchApi::DockerHub(base), true);
+    for pattern in ["alpine|latest", "^1\\.1[0-9]\\.0$", "1.11"] {
+        assert!(!names(&client, &image, pattern).await.is_empty());
+    }
+    assert!(
+        registry
+            .requests_to("/v2/repositories/team/app/tags")
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_last_pushed() {
+    let registry = registry();
+    let base = Url::parse(&registry.url()).unwrap();
+    let image = Image::try_from(format!("{}/team/app", registry.host()).as_str()).unwrap();
+
+    let hub = client(&registry, SearchApi::DockerHub(base.clone()), true);
+    let pushed = hub.fetch_last_pushed(&image).await.unwrap().unwrap();
+    assert_eq!(format_rfc3339(pushed), "2026-10-01T08:30:00.123456Z");
+
+    // neither Quay nor the registry API tell
+    let quay = client(&registry, SearchApi::Quay(base), true);
+    assert_eq!(quay.fetch_last_pushed(&image).await.unwrap(), None);
+    let plain = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    assert_eq!(plain.fetch_last_pushed(&image).await.unwrap(), None);
+    assert_eq!(registry.requests_to("/v2/repositories/team/app/").len(), 1);
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    path::Path,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use common::{MockRegistry, Request, Response};
+
+fn docker_tags(registry: &MockRegistry, state: &Path, args: &[&str]) -> String {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", state)
+        .env("XDG_STATE_HOME", state)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/app", registry.host()))
+        .arg("--state")
+        .arg(state.join("state.json"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_sort_first_seen_over_three_runs() {
+    let tags = Arc::new(Mutex::new(vec!["1.0", "1.1", "latest"]));
+    let listed = tags.clone();
+    let registry = MockRegistry::start_tls(move |req: &Request| match req.url.path() {
+        "/v2/app/tags/list" => {
+            let mut tags = listed.lock().unwrap().clone();
+            // registries list tags in lexical order
+            tags.sort();
+            Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+        }
+        _ => Response::json(404, "{}"),
+    });
+    let state = tempfile::tempdir().unwrap();
+    let first_seen = |args: &[&str]| {
+        docker_tags(
+            &registry,
+            state.path(),
+            &[&["--sort", "first-seen"][..], args].concat(),
+        )
+    };
+
+    // nothing was seen before, so the registry's order stays
+    assert_eq!(first_seen(&[]), "1.0\n1.1\nlatest\n");
+
+    tags.lock().unwrap().extend(["2.0", "1.2"]);
+    assert_eq!(first_seen(&[]), "1.2\n2.0\n1.0\n1.1\nlatest\n");
+
+    tags.lock().unwrap().push("3.0");
+    assert_eq!(first_seen(&[]), "3.0\n1.2\n2.0\n1.0\n1.1\nlatest\n");
+    assert_eq!(first_seen(&["-n", "2"]), "3.0\n1.2\n");
+    assert_eq!(first_seen(&["-f", r"^1\."]), "1.2\n1.0\n1.1\n");
+    assert_eq!(first_seen(&["-r", "-n", "2"]), "latest\n1.1\n");
+
+    // listing by version keeps recording
+    tags.lock().unwrap().push("0.9");
+    assert_eq!(docker_tags(&registry, state.path(), &[]).lines().count(), 7);
+    assert_eq!(first_seen(&["-n", "2"]), "0.9\n3.0\n");
+}
+
+#[test]
+fn test_sort_first_seen_requires_state() {
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .args(["nginx", "--sort", "first-seen"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("required arguments"), "{stderr}");
+}
@@ -0,0 +1,181 @@
+#![cfg(feature = "github")]
+
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Image, Tag};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+const CONFIG: &str = r#"{"architecture":"amd64","os":"linux","created":"2020-01-01T00:00:00Z"}"#;
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// A registry for `owner/app` alongside a GitHub API listing its versions in
+/// two pages under the user `owner`, if `visible`
+fn registry(visible: bool) -> MockRegistry {
+    MockRegistry::start_with_base(move |base, req| {
+        let path = req.url.path();
+        if path == "/users/owner/packages/container/app/versions" && visible {
+            assert_eq!(req.header("Authorization"), Some("Bearer gh-token"));
+            assert!(
+                req.header("User-Agent")
+                    .unwrap()
+                    .starts_with("docker-tags/")
+            );
+            let page = req.query("page").unwrap_or_default();
+            let (versions, link) = match page.as_str() {
+                "" => (
+                    serde_json::json!([
+                        { "created_at": "2024-10-01T12:00:00Z",
+                          "metadata": { "container": { "tags": ["1.2.0", "latest"] } } },
+                    ]),
+                    Some(format!(
+                        r#"<{base}/users/owner/packages/container/app/versions?per_page=100&page=2>; rel="next""#
+                    )),
+                ),
+                _ => (
+                    serde_json::json!([
+                        { "created_at": "2024-09-01T08:30:00Z",
+                          "metadata": { "container": { "tags": ["1.1.0"] } } },
+                    ]),
+                    None,
+                ),
+            };
+            let mut resp = Response::json(200, versions.to_string());
+            if let Some(link) = link {
+                resp = resp.header("Link", link);
+            }
+            return resp;
+        }
+        if path.starts_with("/v2/owner/app/manifests/") {
+            let manifest = serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": {
+                    "mediaType": "application/vnd.oci.image.config.v1+json",
+                    "digest": digest(CONFIG),
+                    "size": CONFIG.len(),
+                },
+                "layers": [],
+            })
+            .to_string();
+            return Response::json(200, manifest.clone())
+                .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+                .header("Docker-Content-Digest", digest(&manifest));
+        }
+        if path == format!("/v2/owner/app/blobs/{}", digest(CONFIG)) {
+            return Response::json(200, CONFIG);
+        }
+        // organizations, and packages the token can't see
+        Response::json(404, r#"{"message":"Not Found"}"#)
+    })
+}
+
+fn client(registry: &MockRegistry, token: bool) -> Client {
+    let mut builder = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .github_api(registry.host(), Url::parse(&registry.url()).unwrap());
+    if token {
+        builder = builder.github_token("gh-token");
+    }
+    builder.build()
+}
+
+fn image(registry: &MockRegistry) -> Image {
+    Image::try_from(format!("{}/owner/app", registry.host()).as_str()).unwrap()
+}
+
+fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[tokio::test]
+async fn test_created_from_packages_api() {
+    let registry = registry(true);
+    let tags = ["1.1.0", "latest", "1.2.0"].map(Tag::new);
+    let (created, warnings) = client(&registry, true)
+        .fetch_created(&image(&registry), &tags)
+        .await;
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert_eq!(
+        created,
+        [
+            (Tag::new("1.1.0"), at(1725179400)),
+            (Tag::new("latest"), at(1727784000)),
+            (Tag::new("1.2.0"), at(1727784000)),
+        ]
+    );
+    // an organization first, then the user's two pages, and no manifests
+    assert_eq!(registry.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_unknown_tags_use_configs() {
+    let registry = registry(true);
+    let tags = ["1.2.0", "nightly"].map(Tag::new);
+    let (created, _) = client(&registry, true)
+        .fetch_created(&image(&registry), &tags)
+        .await;
+    // 2020-01-01T00:00:00Z from the image config
+    assert_eq!(
+        created,
+        [
+            (Tag::new("1.2.0"), at(1727784000)),
+            (Tag::new("nightly"), at(1577836800)),
+        ]
+    );
+    assert_eq!(
+        registry
+            .requests_to("/v2/owner/app/manifests/nightly")
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_falls_back_to_configs() {
+    // the token can't see the package
+    let hidden = registry(false);
+    let tags = ["1.2.0", "1.1.0"].map(Tag::new);
+    let (created, warnings) = client(&hidden, true)
+        .fetch_created(&image(&hidden), &tags)
+        .await;
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert!(
+        created
+            .iter()
+            .all(|(_, created)| *created == at(1577836800))
+    );
+    assert_eq!(created.len(), 2);
+
+    // no token, no API
+    let visible = registry(true);
+    let (created, _) = client(&visible, false)
+        .fetch_created(&image(&visible), &tags)
+        .await;
+    assert_eq!(created.len(), 2);
+    assert!(
+        visible
+            .requests()
+            .iter()
+            .all(|r| r.url.path().starts_with("/v2/"))
+    );
+}
+
+#[tokio::test]
+async fn test_ghcr_packages_live() {
+    // needs a token allowed to read packages
+    let Ok(token) = std::env::var("GITHUB_TOKEN") else {
+        return;
+    };
+    let client = Client::builder().github_token(token).build();
+    let image = Image::try_from("ghcr.io/xtls/xray-core").unwrap();
+    let (created, _) = client.fetch_created(&image, &[Tag::new("latest")]).await;
+    assert_eq!(created.len(), 1);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Client, CredentialProvider, Credentials, Image};
+
+/// Project-level robot account, with the characters Harbor puts in them and
+/// a secret which must not be trimmed or encoded
+const USERNAME: &str = "robot$team+ci";
+const PASSWORD: &str = " s3cr3t+/=:% ";
+
+struct Robot;
+
+#[async_trait]
+impl CredentialProvider for Robot {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic(USERNAME, PASSWORD)))
+    }
+}
+
+/// Harbor challenging with a service name containing a space and a scope
+/// containing a comma, and issuing a token to the robot account only
+fn harbor() -> MockRegistry {
+    MockRegistry::start_with_base(|base, req: &Request| match req.url.path() {
+        "/service/token" => {
+            let expected = format!(
+                "Basic {}",
+                STANDARD.encode(format!("{USERNAME}:{PASSWORD}"))
+            );
+            match req.header("Authorization") == Some(expected.as_str()) {
+                true => Response::json(200, r#"{"token":"robot-token"}"#),
+                false => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#),
+            }
+        }
+        _ if req.header("Authorization") == Some("Bearer robot-token") => {
+            Response::json(200, r#"{"tags":["1.0.0"]}"#)
+        }
+        _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+            "WWW-Authenticate",
+            include_str!("fixtures/harbor_challenge.txt")
+                .trim_end()
+                .replace("https://harbor.corp", base),
+        ),
+    })
+}
+
+#[tokio::test]
+async fn test_robot_account_gets_token() {
+    let harbor = harbor();
+    let client = Client::builder()
+        .insecure_registry(harbor.host())
+        .credential_provider(Robot)
+        .build();
+
+    let tags = client
+        .fetch_tags(&Image::new(harbor.host(), "team/app"))
+        .await
+        .unwrap();
+    assert_eq!(tags.len(), 1);
+
+    let token = &harbor.requests_to("/service/token")[0];
+    assert_eq!(token.query("service").as_deref(), Some("Harbor Registry"));
+    assert_eq!(
+        token.query("scope").as_deref(),
+        Some("repository:team one/app:pull,push")
+    );
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{path::Path, process::Command};
+
+use common::{MockRegistry, Response};
+
+fn docker_tags(state: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", state)
+        .env("XDG_STATE_HOME", state)
+        .env_remove("DOCKER_TAGS_NO_HISTORY")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{args:?}: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_history_of_listed_images() {
+    let registry = MockRegistry::start_tls(|req| match req.url.path() {
+        "/v2/team/app/tags/list" | "/v2/team/api/tags/list" => {
+            Response::json(200, r#"{"tags":["1.0.0"]}"#)
+        }
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    });
+    let state = tempfile::tempdir().unwrap();
+    let ca = format!(
+        "{}={}",
+        registry.host(),
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem")
+    );
+    let app = format!("{}/team/app", registry.host());
+    let api = format!("{}/team/api", registry.host());
+    let list = |image: &str, extra: &[&str]| {
+        docker_tags(
+            state.path(),
+            &[&[image, "--ca-cert", &ca][..], extra].concat(),
+        )
+    };
+
+    list(&app, &[]);
+    list(&api, &[]);
+    list(&app, &[]);
+    assert_eq!(
+        docker_tags(state.path(), &["history"]),
+        format!("{app}\n{api}\n")
+    );
+    let path = state.path().join("docker-tags").join("history");
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        format!("{app}\n{api}\n")
+    );
+    assert_eq!(
+        docker_tags(state.path(), &["history", "-o", "yaml"]),
+        format!("images:\n- {app}\n- {api}\n")
+    );
+
+    // failed listings aren't recorded, and neither are opted-out ones
+    let missing = format!("{}/team/missing", registry.host());
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", state.path())
+        .env("XDG_STATE_HOME", state.path())
+        .args([&missing, "--ca-cert", &ca])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    list(&api, &["--no-history"]);
+    assert_eq!(
+        docker_tags(state.path(), &["history", "list"]),
+        format!("{app}\n{api}\n")
+    );
+
+    assert_eq!(
+        docker_tags(
+            state.path(),
+            &["__complete", &format!("{}/team/ap", registry.host())]
+        ),
+        format!("{app}\n{api}\n")
+    );
+    assert_eq!(
+        docker_tags(
+            state.path(),
+            &["__complete", &format!("{}/team/api", registry.host())]
+        ),
+        format!("{api}\n")
+    );
+    assert_eq!(docker_tags(state.path(), &["__complete", "nginx"]), "");
+
+    docker_tags(state.path(), &["history", "clear"]);
+    assert!(!path.exists());
+    assert_eq!(docker_tags(state.path(), &["history"]), "");
+}
+
+#[test]
+fn test_history_disabled_by_env() {
+    let registry = MockRegistry::start_tls(|_| Response::json(200, r#"{"tags":["1.0.0"]}"#));
+    let state = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", state.path())
+        .env("XDG_STATE_HOME", state.path())
+        .env("DOCKER_TAGS_NO_HISTORY", "1")
+        .arg(format!("{}/app", registry.host()))
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(!state.path().join("docker-tags").exists());
+}
+
+#[test]
+fn test_history_disabled_by_config() {
+    let registry = MockRegistry::start_tls(|_| Response::json(200, r#"{"tags":["1.0.0"]}"#));
+    let state = tempfile::tempdir().unwrap();
+    let config = state.path().join("config.toml");
+    let ca = format!(
+        "{}={}",
+        registry.host(),
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem")
+    );
+    let image = format!("{}/app", registry.host());
+    let args = [
+        &image,
+        "--ca-cert",
+        &ca,
+        "--config",
+        config.to_str().unwrap(),
+    ];
+
+    std::fs::write(&config, "history = false\n").unwrap();
+    docker_tags(state.path(), &args);
+    assert!(!state.path().join("docker-tags").exists());
+
+    std::fs::write(&config, "history = true\n").unwrap();
+    docker_tags(state.path(), &args);
+    assert_eq!(
+        docker_tags(state.path(), &["history"]),
+        format!("{image}\n")
+    );
+}
@@ -0,0 +1,186 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{MockRegistry, Request, Response};
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| {
+        let tags: &[&str] = match req.url.path() {
+            "/v2/org/app/tags/list" => {
+                &["1.25.1", "1.25.3", "1.27.0", "1.27.1", "1.27.2", "latest"]
+            }
+            "/v2/org/tool/tags/list" => &["1.25.1", "1.27.1", "2.0.0"],
+            _ => return Response::new(404),
+        };
+        Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+    })
+}
+
+#[test]
+fn test_ignore_file() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let host = registry.host();
+    let ignore = dir.path().join("tags.ignore");
+    fs::write(
+        &ignore,
+        format!(
+            "# re-pushed as 1.27.2\n\
+             1.27.1\n\
+             {host}/org/app: 1.25.*\n\
+             {host}/org/app: !1.25.3\n"
+        ),
+    )
+    .unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .args(args)
+            .arg("--ignore-file")
+            .arg(&ignore)
+            .arg("--ca-cert")
+            .arg(format!("{host}={ca}"))
+            .output()
+            .unwrap()
+    };
+    let listed = |args: &[&str]| {
+        let output = docker_tags(args);
+        assert!(output.status.success(), "{output:?}");
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let app = format!("{host}/org/app");
+    let tool = format!("{host}/org/tool");
+
+    let (stdout, stderr) = listed(&[&app]);
+    assert_eq!(stdout, "1.27.2\n1.27.0\n1.25.3\nlatest\n");
+    assert!(stderr.is_empty(), "{stderr}");
+    // rules scoped to another image don't apply
+    assert_eq!(listed(&[&tool]).0, "2.0.0\n1.25.1\n");
+    assert_eq!(
+        listed(&[&app, "--no-sort", "-n", "2"]).0,
+        "1.25.3\n1.27.0\n"
+    );
+    assert_eq!(
+        listed(&[&app, "--latest-per", "minor"]).0,
+        "1.27.2\n1.25.3\n"
+    );
+    assert_eq!(listed(&[&tool, "--latest"]).0, "2.0.0\n");
+
+    let (stdout, stderr) = listed(&[&app, "--show-ignored"]);
+    assert_eq!(stdout, "1.27.2\n1.27.0\n1.25.3\nlatest\n");
+    assert_eq!(
+        stderr,
+        "Warning: left out 1.27.1, ignored by the ignore file\n\
+         Warning: left out 1.25.1, ignored by the ignore file\n"
+    );
+    // only tags the filter keeps are reported
+    let (_, stderr) = listed(&[&app, "--show-ignored", "-f", "^1.25"]);
+    assert_eq!(
+        stderr,
+        "Warning: left out 1.25.1, ignored by the ignore file\n"
+    );
+    let listing: serde_json::Value =
+        serde_json::from_str(&listed(&[&app, "--show-ignored", "-o", "json"]).0).unwrap();
+    assert_eq!(listing["ignored"], serde_json::json!(["1.27.1", "1.25.1"]));
+    assert_eq!(listing["tags"].as_array().unwrap().len(), 4);
+    let listing: serde_json::Value =
+        serde_json::from_str(&listed(&[&app, "-o", "json"]).0).unwrap();
+    assert!(listing.get("ignored").is_none(), "{listing}");
+
+    // ignored tags are never suggested
+    let dockerfile = dir.path().join("Dockerfile");
+    fs::write(
+        &dockerfile,
+        format!("FROM {app}:1.25.1\nFROM {tool}:1.25.1\n"),
+    )
+    .unwrap();
+    let output = docker_tags(&[
+        "check-upgrades",
+        dockerfile.to_str().unwrap(),
+        "--scope",
+        "minor",
+    ]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("1: {app}:1.25.1 -> {app}:1.27.2 (newest 1 release)\n")
+    );
+}
+
+#[test]
+fn test_config_ignore_file() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let host = registry.host();
+    fs::create_dir(dir.path().join("config")).unwrap();
+    let config = dir.path().join("config/config.toml");
+    fs::write(dir.path().join("tags.ignore"), "1.27.2\n").unwrap();
+    fs::write(dir.path().join("other.ignore"), "1.27.*\n").unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .current_dir(dir.path())
+            .arg(format!("{host}/org/app"))
+            .arg("--config")
+            .arg(&config)
+            .arg("--ca-cert")
+            .arg(format!("{host}={ca}"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let listed = |args: &[&str]| {
+        let output = docker_tags(args);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let output = docker_tags(&["--show-ignored"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("--show-ignored needs --ignore-file"),
+        "{stdout}"
+    );
+    assert_eq!(listed(&["--latest"]), "1.27.2\n");
+
+    // relative to the config file, and replaced by the flag
+    fs::write(&config, "ignore_file = \"../tags.ignore\"\n").unwrap();
+    assert_eq!(listed(&["--latest"]), "1.27.1\n");
+    assert_eq!(
+        listed(&["--latest", "--ignore-file", "other.ignore"]),
+        "1.25.3\n"
+    );
+}
+
+#[test]
+fn test_invalid_ignore_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let ignore = dir.path().join("tags.ignore");
+    fs::write(&ignore, "1.27.1\nnginx: 1.27/1\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .args(["nginx", "--ignore-file"])
+        .arg(&ignore)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Invalid ignore file"), "{stdout}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(r#"line 2: not a tag name or a glob: "1.27/1""#),
+        "{stderr}"
+    );
+}
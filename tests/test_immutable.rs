@@ -0,0 +1,268 @@
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{MockRegistry, Request, Response};
+use docker_tags::{
+    Anonymous, AwsCredentials, Client, CredentialProvider, Credentials, Image, ImmutabilityLookup,
+    Tag, TagImmutability,
+};
+
+struct StoredLogin;
+
+#[async_trait]
+impl CredentialProvider for StoredLogin {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic("user", "secret")))
+    }
+}
+
+const ARTIFACTS: &str = "/api/v2.0/projects/team/repositories/sub%252Fapp/artifacts";
+
+/// Harbor serving `team/sub/app` and `team/app` alike, whose artifacts come
+/// two to a page, to stored credentials only
+fn harbor(req: &Request) -> Response {
+    let path = req.url.path();
+    let artifacts = path.starts_with("/api/v2.0/projects/team/") && path.ends_with("/artifacts");
+    match path {
+        "/v2/team/app/tags/list" => {
+            Response::json(200, r#"{"tags":["1.0.0","1.1.0","1.2.0","latest"]}"#)
+        }
+        _ if artifacts && req.header("Authorization").is_none() => Response::json(
+            401,
+            r#"{"errors":[{"code":"UNAUTHORIZED","message":"unauthorized"}]}"#,
+        ),
+        _ if artifacts => match req.query("page").as_deref() {
+            None | Some("1") => Response::json(
+                200,
+                r#"[
+                    {"digest":"sha256:12","tags":[{"name":"1.2.0","immutable":true},{"name":"latest","immutable":false}]},
+                    {"digest":"sha256:00","tags":null}
+                ]"#,
+            )
+            .header(
+                "Link",
+                format!(r#"<{path}?page=2&page_size=2&with_tag=true>; rel="next""#),
+            ),
+            _ => Response::json(
+                200,
+                r#"[{"digest":"sha256:11","tags":[{"name":"1.1.0","immutable":false}]}]"#,
+            ),
+        },
+        _ => Response::json(404, r#"{"errors":[{"code":"NOT_FOUND"}]}"#),
+    }
+}
+
+fn client(registry: &MockRegistry, credentials: impl CredentialProvider + 'static) -> Client {
+    Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(credentials)
+        .build()
+}
+
+fn tags() -> Vec<Tag> {
+    ["1.2.0", "1.1.0", "1.0.0", "latest"].map(Tag::new).into()
+}
+
+/// Whether each tag looked up is immutable, in order
+fn immutable(tags: &[TagImmutability]) -> Vec<Option<bool>> {
+    tags.iter().map(|tag| tag.immutable).collect()
+}
+
+#[tokio::test]
+async fn test_harbor() {
+    let registry = MockRegistry::start(harbor);
+    let client = client(&registry, StoredLogin);
+    // Harbor wants the slash of the name encoded twice
+    let image = Image::new(registry.host(), "team/sub/app");
+
+    let (looked_up, warnings) = client.fetch_immutable(&image, &tags()).await;
+    assert_eq!(
+        immutable(&looked_up),
+        [Some(true), Some(false), None, Some(false)]
+    );
+    assert_eq!(looked_up[0].tag, Tag::new("1.2.0"));
+    assert!(warnings.is_empty(), "{warnings:?}");
+    let requests = registry.requests_to(ARTIFACTS);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0].query("with_immutable_status").as_deref(),
+        Some("true")
+    );
+    assert_eq!(
+        requests[0].header("Authorization"),
+        Some("Basic dXNlcjpzZWNyZXQ=")
+    );
+}
+
+#[tokio::test]
+async fn test_harbor_denied() {
+    let registry = MockRegistry::start(harbor);
+    let client = client(&registry, Anonymous);
+    let image = Image::new(registry.host(), "team/sub/app");
+
+    let (looked_up, warnings) = client.fetch_immutable(&image, &tags()).await;
+    assert_eq!(immutable(&looked_up), [None; 4]);
+    assert_eq!(warnings.len(), 1);
+    assert!(
+        warnings[0]
+            .to_string()
+            .starts_with("failed to look up which tags are immutable: Got HTTP 401 Unauthorized"),
+        "{warnings:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_unknown() {
+    // a registry without Harbor's API
+    let registry = MockRegistry::with_tags("team/app", &["1.0.0"], false);
+    let client = client(&registry, Anonymous);
+    let image = Image::new(registry.host(), "team/app");
+    let (looked_up, warnings) = client.fetch_immutable(&image, &[Tag::new("1.0.0")]).await;
+    assert_eq!(immutable(&looked_up), [None]);
+    assert!(warnings.is_empty(), "{warnings:?}");
+
+    // nor one to ask, as Harbor's repositories are in a project
+    let image = Image::new(registry.host(), "app");
+    let before = registry.requests().len();
+    let (looked_up, _) = client.fetch_immutable(&image, &[Tag::new("1.0.0")]).await;
+    assert_eq!(immutable(&looked_up), [None]);
+    assert_eq!(registry.requests().len(), before);
+}
+
+const ECR: &str = "123456789012.dkr.ecr.us-east-1.amazonaws.com";
+
+/// ECR's API describing `team/app`, immutable but for `latest` and `dev-*`,
+/// to signed requests only
+fn ecr_api(req: &Request) -> Response {
+    let target = req.header("X-Amz-Target").unwrap_or_default();
+    let signed = req
+        .header("Authorization")
+        .is_some_and(|auth| auth.starts_with("AWS4-HMAC-SHA256 Credential=AKID/"));
+    let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap_or_default();
+    match body["repositoryNames"][0].as_str() {
+        _ if !target.ends_with(".DescribeRepositories") || !signed => Response::json(
+            403,
+            r#"{"__type":"AccessDeniedException","message":"not signed"}"#,
+        ),
+        Some("team/app") => Response::json(
+            200,
+            include_str!("fixtures/immutable/ecr_repositories.json"),
+        ),
+        _ => Response::json(
+            400,
+            r#"{"__type":"RepositoryNotFoundException","message":"The repository with name 'team/other' does not exist in the registry with id '123456789012'"}"#,
+        ),
+    }
+}
+
+#[tokio::test]
+async fn test_ecr() {
+    let api = MockRegistry::start(ecr_api);
+    let client = Client::builder()
+        .credential_provider(Anonymous)
+        .ecr_api(ECR, format!("http://{}/", api.host()).parse().unwrap())
+        .aws_credentials(AwsCredentials::new("AKID", "secret"))
+        .build();
+    let tags: Vec<_> = ["1.0.0", "latest", "dev-abc"].map(Tag::new).into();
+
+    let (looked_up, warnings) = client
+        .fetch_immutable(&Image::new(ECR, "team/app"), &tags)
+        .await;
+    assert_eq!(
+        immutable(&looked_up),
+        [Some(true), Some(false), Some(false)]
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+    let requests = api.requests();
+    assert_eq!(requests.len(), 1);
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"registryId": "123456789012", "repositoryNames": ["team/app"]})
+    );
+    let authorization = requests[0].header("Authorization").unwrap();
+    assert!(
+        authorization.contains("/us-east-1/ecr/aws4_request, "),
+        "{authorization}"
+    );
+
+    let (looked_up, warnings) = client
+        .fetch_immutable(&Image::new(ECR, "team/other"), &tags)
+        .await;
+    assert_eq!(immutable(&looked_up), [None; 3]);
+    assert_eq!(warnings.len(), 1);
+    assert!(
+        warnings[0]
+            .to_string()
+            .contains(": The repository with name 'team/other' does not exist"),
+        "{warnings:?}"
+    );
+}
+
+/// Tags made immutable by a release process, whatever the registry
+struct Releases;
+
+#[async_trait]
+impl ImmutabilityLookup for Releases {
+    async fn immutable(
+        &self,
+        _client: &Client,
+        _image: &Image,
+        tags: &[Tag],
+    ) -> Result<Vec<Option<bool>>> {
+        Ok(tags
+            .iter()
+            .map(|tag| Some(tag.name() != "latest"))
+            .collect())
+    }
+}
+
+#[tokio::test]
+async fn test_immutability_lookup() {
+    let registry = MockRegistry::start(harbor);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(StoredLogin)
+        .immutability_lookup(registry.host(), Releases)
+        .build();
+    let image = Image::new(registry.host(), "team/app");
+    let (looked_up, warnings) = client.fetch_immutable(&image, &tags()).await;
+    assert_eq!(
+        immutable(&looked_up),
+        [Some(true), Some(true), Some(true), Some(false)]
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+    // instead of Harbor's API
+    assert!(registry.requests().is_empty());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_show_immutable_flag() {
+    let registry = MockRegistry::start_tls(harbor);
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    std::fs::write(
+        config.path().join("config.json"),
+        format!(
+            r#"{{"auths":{{"{}":{{"auth":"dXNlcjpzZWNyZXQ="}}}}}}"#,
+            registry.host()
+        ),
+    )
+    .unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg(format!("{}/team/app", registry.host()))
+        .args(["--show-immutable", "-n", "3"])
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1.2.0\timmutable\n1.1.0\tmutable\n1.0.0\t-\n"
+    );
+}
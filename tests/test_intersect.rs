@@ -0,0 +1,130 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Request, Response};
+
+/// `corp/app-api`, `corp/app-worker` and `corp/app-ui`, released together
+/// but not always all of them, and `corp/other` sharing none of their tags
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| {
+        let tags: &[&str] = match req.url.path() {
+            "/v2/corp/app-api/tags/list" => &["1.0.0", "1.1.0", "1.2.0", "1.3.0-rc1", "latest"],
+            "/v2/corp/app-worker/tags/list" => &["1.1.0", "1.2.0", "1.3.0-rc1", "latest"],
+            "/v2/corp/app-ui/tags/list" => &["0.9.0", "1.1.0", "1.2.0", "latest"],
+            "/v2/corp/other/tags/list" => &["3.0.0"],
+            _ => return Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+        };
+        Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, images: &[&str], args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("intersect")
+        .args(
+            images
+                .iter()
+                .map(|image| format!("{}/corp/{image}", registry.host())),
+        )
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const APP: &[&str] = &["app-api", "app-worker", "app-ui"];
+
+#[test]
+fn test_intersect() {
+    let registry = registry();
+
+    let output = docker_tags(&registry, APP, &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(output), "1.2.0\n1.1.0\nlatest\n");
+    // every image is listed once
+    for image in APP {
+        let path = format!("/v2/corp/{image}/tags/list");
+        assert_eq!(registry.requests_to(&path).len(), 1, "{image}");
+    }
+
+    // filters apply to the common tags
+    let output = docker_tags(&registry, APP, &["--version-like", "-r", "-n", "1"]);
+    assert_eq!(stdout(output), "1.1.0\n");
+    let output = docker_tags(&registry, &APP[..2], &[]);
+    assert_eq!(stdout(output), "1.3.0-rc1\n1.2.0\n1.1.0\nlatest\n");
+
+    let output = docker_tags(&registry, APP, &["-f", "^1", "-o", "json"]);
+    let listing: serde_json::Value = serde_json::from_str(&stdout(output)).unwrap();
+    assert_eq!(
+        listing["tags"],
+        serde_json::json!([{"name": "1.2.0"}, {"name": "1.1.0"}])
+    );
+    assert_eq!(
+        (listing["total"].as_u64(), listing["matched"].as_u64()),
+        (Some(3), Some(2))
+    );
+}
+
+#[test]
+fn test_empty_intersection() {
+    let registry = registry();
+    let output = docker_tags(&registry, &["app-api", "other"], &[]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    // common tags which the filter drops still count
+    let output = docker_tags(&registry, APP, &["-f", "^2"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = docker_tags(&registry, &["app-api", "missing"], &[]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let message = String::from_utf8_lossy(&output.stdout);
+    assert!(message.contains("corp/missing"), "{message}");
+}
+
+#[test]
+fn test_union() {
+    let registry = registry();
+    let host = registry.host();
+
+    let output = docker_tags(&registry, APP, &["--union", "--version-like"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(output),
+        format!(
+            "TAG\t{host}/corp/app-api\t{host}/corp/app-worker\t{host}/corp/app-ui\n\
+             1.3.0-rc1\t✓\t✓\t✗\n\
+             1.2.0\t✓\t✓\t✓\n\
+             1.1.0\t✓\t✓\t✓\n\
+             1.0.0\t✓\t✗\t✗\n\
+             0.9.0\t✗\t✗\t✓\n"
+        )
+    );
+
+    let output = docker_tags(&registry, &["app-api", "other"], &["--union", "-o", "json"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    let rows: serde_json::Value = serde_json::from_str(&stdout(output)).unwrap();
+    assert_eq!(rows.as_array().unwrap().len(), 6);
+    assert_eq!(
+        rows[0],
+        serde_json::json!({
+            "tag": "3.0.0",
+            "images": {
+                format!("{host}/corp/app-api"): false,
+                format!("{host}/corp/other"): true,
+            }
+        })
+    );
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Anonymous, Client, CredentialProvider, Credentials, Error, Image};
+use reqwest::Url;
+
+struct Login(&'static str);
+
+#[async_trait]
+impl CredentialProvider for Login {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic(self.0, "secret")))
+    }
+}
+
+/// Docker Hub with only `toogle/myapp`
+fn hub() -> MockRegistry {
+    MockRegistry::start(|req: &Request| match req.url.path() {
+        "/v2/toogle/myapp/tags/list" => Response::json(200, r#"{"tags":["1.0"]}"#),
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+fn client(hub: &MockRegistry, credentials: impl CredentialProvider + 'static) -> Client {
+    Client::builder()
+        .docker_hub_endpoint(Url::parse(&hub.url()).unwrap())
+        .credential_provider(credentials)
+        .build()
+}
+
+fn lookups(hub: &MockRegistry) -> Vec<String> {
+    hub.requests()
+        .iter()
+        .map(|req| req.url.path().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_not_found_suggests_own_namespace() {
+    let hub = hub();
+    let err = client(&hub, Login("toogle"))
+        .fetch_tags(&Image::new("docker.io", "myapp"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Image not found as library/myapp; did you mean toogle/myapp?"
+    );
+    assert!(
+        err.chain()
+            .any(|cause| cause.downcast_ref() == Some(&Error::NotFound))
+    );
+    assert_eq!(
+        lookups(&hub),
+        ["/v2/library/myapp/tags/list", "/v2/toogle/myapp/tags/list"]
+    );
+    assert_eq!(hub.requests()[1].query("n").as_deref(), Some("1"));
+}
+
+#[tokio::test]
+async fn test_not_found_without_own_repository() {
+    let hub = hub();
+    let err = client(&hub, Login("someone"))
+        .fetch_tags(&Image::new("docker.io", "myapp"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Image not found");
+    assert_eq!(
+        lookups(&hub),
+        ["/v2/library/myapp/tags/list", "/v2/someone/myapp/tags/list"]
+    );
+
+    // nothing to suggest without credentials or with a namespace
+    let hub = self::hub();
+    let err = client(&hub, Anonymous)
+        .fetch_tags(&Image::new("docker.io", "myapp"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Image not found");
+    let err = client(&hub, Login("toogle"))
+        .fetch_tags(&Image::new("docker.io", "org/myapp"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Image not found");
+    assert_eq!(
+        lookups(&hub),
+        ["/v2/library/myapp/tags/list", "/v2/org/myapp/tags/list"]
+    );
+}
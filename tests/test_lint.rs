@@ -0,0 +1,128 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+fn docker_tags(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .current_dir(dir)
+        .env("DOCKER_CONFIG", dir)
+        .env("XDG_STATE_HOME", dir)
+        .arg("lint")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// A Dockerfile, a compose file and a manifest to lint, and a file which
+/// isn't one of them
+fn project(dir: &Path) {
+    fs::create_dir_all(dir.join("deploy")).unwrap();
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(
+        dir.join("Dockerfile"),
+        "FROM golang:1.23.4 AS build\nFROM build\nFROM alpine:latest\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("compose.yaml"),
+        "services:\n  db:\n    image: postgres\n  cache:\n    image: redis:7.4.1\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("deploy/app.yaml"),
+        "spec:\n  containers:\n    - image: k8s.gcr.io/pause:3.9\n",
+    )
+    .unwrap();
+    fs::write(dir.join("notes.txt"), "FROM nginx\n").unwrap();
+    fs::write(dir.join(".git/compose.yaml"), "image: nginx\n").unwrap();
+}
+
+#[test]
+fn test_lint_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    project(dir.path());
+
+    let output = docker_tags(dir.path(), &["."]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "{stdout}");
+    assert!(lines[0].starts_with("LOCATION"), "{stdout}");
+    let columns = |line: &str| -> Vec<String> {
+        line.split_whitespace()
+            .take(4)
+            .map(str::to_string)
+            .collect()
+    };
+    assert_eq!(
+        columns(lines[1]),
+        ["./Dockerfile:3:6", "warning", "latest", "alpine:latest"]
+    );
+    assert_eq!(
+        columns(lines[2]),
+        ["./compose.yaml:3:12", "error", "missing-tag", "postgres"]
+    );
+    assert!(lines[3].contains("deprecated-registry"), "{stdout}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "2 errors, 1 warnings\n");
+
+    // warnings alone pass
+    let output = docker_tags(
+        dir.path(),
+        &[".", "--disable", "missing-tag,deprecated-registry"],
+    );
+    assert!(output.status.success(), "{output:?}");
+
+    let output = docker_tags(
+        dir.path(),
+        &["Dockerfile", "--enable", "unpinned", "--disable", "latest"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("unpinned").count(), 2, "{stdout}");
+
+    let output = docker_tags(dir.path(), &[".", "--disable", "latests"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
+
+#[test]
+fn test_lint_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    project(dir.path());
+
+    let output = docker_tags(dir.path(), &["compose.yaml", "--mirror", "mirror.corp"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("docker-hub").count(), 2, "{stdout}");
+
+    let output = docker_tags(dir.path(), &["Dockerfile", "--github"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "::warning file=Dockerfile,line=3,col=6,title=latest::alpine:latest: `latest` moves with every push; name a version\n"
+    );
+
+    let output = docker_tags(dir.path(), &["deploy", "-o", "json"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["errors"], 1);
+    assert_eq!(report["warnings"], 0);
+    assert_eq!(
+        report["findings"][0],
+        serde_json::json!({
+            "file": "deploy/app.yaml",
+            "line": 3,
+            "column": 14,
+            "reference": "k8s.gcr.io/pause:3.9",
+            "rule": "deprecated-registry",
+            "severity": "error",
+            "message": "k8s.gcr.io is frozen, pull from registry.k8s.io",
+        })
+    );
+
+    let output = docker_tags(dir.path(), &["Dockerfile", "--github", "-o", "json"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+}
@@ -0,0 +1,271 @@
+mod common;
+
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Anonymous, Client, Image, Manifest};
+use sha2::{Digest, Sha256};
+
+const INDEX: &str = include_str!("fixtures/docker_hub_index.json");
+const CONFIG: &str = include_str!("fixtures/config.json");
+
+const AMD64: &str = "sha256:1c4eef651f65e2f7daee7ee785882ac164b02b78fb74503052a26dc061c90474";
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// OCI manifest for the amd64 entry of the index, pointing to [`CONFIG`]
+fn amd64_manifest() -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(CONFIG),
+            "size": CONFIG.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// The index fixture with its amd64 entry pointing to [`amd64_manifest`]
+fn index() -> String {
+    INDEX.replace(AMD64, &digest(&amd64_manifest()))
+}
+
+/// Registry serving the fixtures behind a token challenge
+fn registry(tamper: bool) -> MockRegistry {
+    MockRegistry::start_with_base(move |base, req| {
+        if req.url.path() == "/token" {
+            return Response::json(200, r#"{"token":"mock-token"}"#);
+        }
+        if req.header("Authorization") != Some("Bearer mock-token") {
+            return Response::new(401).header(
+                "WWW-Authenticate",
+                format!(r#"Bearer realm="{base}/token",service="mock""#),
+            );
+        }
+        let (content_type, body) = match req.url.path() {
+            "/v2/library/alpine/manifests/3.21" => {
+                ("application/vnd.oci.image.index.v1+json", index())
+            }
+            path if path
+                == format!("/v2/library/alpine/manifests/{}", digest(&amd64_manifest())) =>
+            {
+                (
+                    "application/vnd.oci.image.manifest.v1+json",
+                    amd64_manifest(),
+                )
+            }
+            path if path == format!("/v2/library/alpine/blobs/{}", digest(CONFIG)) => {
+                match tamper {
+                    true => ("application/octet-stream", CONFIG.replace("amd64", "arm64")),
+                    false => ("application/octet-stream", CONFIG.to_string()),
+                }
+            }
+            _ => {
+                return Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#);
+            }
+        };
+        Response::json(200, body.clone())
+            .header("Content-Type", content_type)
+            .header("Docker-Content-Digest", digest(&body))
+    })
+}
+
+fn client(registry: &MockRegistry) -> (Client, Image) {
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/library/alpine", registry.host()).as_str()).unwrap();
+    (client, image)
+}
+
+#[tokio::test]
+async fn test_fetch_manifest() {
+    let registry = registry(false);
+    let (client, image) = client(&registry);
+
+    let manifest = client.fetch_manifest(&image, "3.21").await.unwrap();
+    let Manifest::Index(index) = manifest else {
+        panic!("expected an index: {manifest:?}");
+    };
+    let entry = index.find("linux", "amd64").unwrap();
+    assert_eq!(entry.digest, digest(&amd64_manifest()));
+
+    let requests = registry.requests_to("/v2/library/alpine/manifests/3.21");
+    let accept = requests.last().unwrap().header("Accept").unwrap();
+    assert!(accept.contains("application/vnd.oci.image.index.v1+json"));
+    assert!(accept.contains("application/vnd.docker.distribution.manifest.v2+json"));
+}
+
+#[tokio::test]
+async fn test_fetch_config() {
+    let registry = registry(false);
+    let (client, image) = client(&registry);
+
+    let config = client.fetch_config(&image, "3.21").await.unwrap();
+    assert_eq!(config.os, "linux");
+    assert_eq!(config.entrypoint, ["/bin/prometheus"]);
+    // one token for the index, the manifest and the blob
+    assert_eq!(registry.requests_to("/token").len(), 1);
+}
+
+#[tokio::test]
+async fn test_fetch_config_digest_mismatch() {
+    let registry = registry(true);
+    let (client, image) = client(&registry);
+
+    let err = client.fetch_config(&image, "3.21").await.unwrap_err();
+    assert!(format!("{err:#}").contains("Digest mismatch"), "{err:#}");
+}
+
+#[tokio::test]
+async fn test_fetch_manifest_errors() {
+    let registry = registry(false);
+    let (client, image) = client(&registry);
+
+    let err = client.fetch_manifest(&image, "missing").await.unwrap_err();
+    assert_eq!(err.to_string(), "Registry error MANIFEST_UNKNOWN");
+
+    let err = client.fetch_manifest(&image, "../blobs").await.unwrap_err();
+    assert_eq!(err.to_string(), r#"Invalid reference: "../blobs""#);
+}
+
+const SCHEMA1: &str = include_str!("fixtures/schema1_manifest.json");
+const DOCKER_MANIFEST: &str = "application/vnd.docker.distribution.manifest.v2+json";
+
+/// Docker schema 2 manifest of `1.0`, pointing to [`CONFIG`]
+fn schema2_manifest() -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": DOCKER_MANIFEST,
+        "config": {
+            "mediaType": "application/vnd.docker.container.image.v1+json",
+            "digest": digest(CONFIG),
+            "size": CONFIG.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// Registry behaving like older Artifactory versions, answering a manifest
+/// request which accepts a list type with a signed schema1 manifest, and
+/// one which accepts only the Docker schema 2 manifest with that
+fn legacy_registry(start: fn(fn(&Request) -> Response) -> MockRegistry) -> MockRegistry {
+    start(|req| {
+        match req.url.path() {
+            "/v2/library/alpine/tags/list" => {
+                return Response::json(200, r#"{"tags":["1.0"]}"#);
+            }
+            "/v2/library/alpine/manifests/1.0" => {}
+            _ => return Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+        }
+        let accept = req.header("Accept").unwrap_or_default();
+        if accept.contains("list") || accept.contains("index") {
+            // the digest of the payload, which leaves out the signatures
+            let payload = &SCHEMA1[..SCHEMA1
+                .find(
+                    r#",
+   "signatures""#,
+                )
+                .unwrap()];
+            return Response::json(200, SCHEMA1)
+                .header(
+                    "Content-Type",
+                    "application/vnd.docker.distribution.manifest.v1+prettyjws",
+                )
+                .header("Docker-Content-Digest", digest(payload));
+        }
+        let body = schema2_manifest();
+        Response::json(200, body.clone())
+            .header("Content-Type", DOCKER_MANIFEST)
+            .header("Docker-Content-Digest", digest(&body))
+    })
+}
+
+#[tokio::test]
+async fn test_schema1_manifest() {
+    let registry = legacy_registry(MockRegistry::start);
+    let (client, image) = client(&registry);
+
+    let err = client.fetch_manifest(&image, "1.0").await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Legacy schema1 manifest not supported: {}/library/alpine:1.0",
+            registry.host()
+        )
+    );
+    // rather than failing to verify its digest
+    let err = client.fetch_digest(&image, "1.0").await.unwrap_err();
+    assert!(
+        err.to_string().starts_with("Legacy schema1 manifest"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn test_narrowed_accept() {
+    let registry = legacy_registry(MockRegistry::start);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .manifest_accept(registry.host(), [DOCKER_MANIFEST])
+        .unwrap()
+        .build();
+    let image = Image::try_from(format!("{}/library/alpine", registry.host()).as_str()).unwrap();
+
+    let manifest = client.fetch_manifest(&image, "1.0").await.unwrap();
+    assert!(matches!(manifest, Manifest::DockerV2(_)), "{manifest:?}");
+    let requests = registry.requests_to("/v2/library/alpine/manifests/1.0");
+    assert_eq!(requests[0].header("Accept"), Some(DOCKER_MANIFEST));
+
+    for types in [
+        &["application/vnd.docker.distribution.manifest.v1+prettyjws"][..],
+        &[],
+    ] {
+        let Err(err) = Client::builder().manifest_accept(registry.host(), types) else {
+            panic!("{types:?} accepted");
+        };
+        assert!(
+            err.to_string().contains("manifest type"),
+            "{types:?}: {err:#}"
+        );
+    }
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_accept_flag() {
+    let registry = legacy_registry(MockRegistry::start_tls);
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/library/alpine", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg("--digests")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = docker_tags(&[]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Legacy schema1 manifest not supported"),
+        "{output:?}"
+    );
+
+    let output = docker_tags(&["--accept", DOCKER_MANIFEST]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("1.0\t{}\n", digest(&schema2_manifest()))
+    );
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use docker_tags::{Tag, TagFilter};
+
+const FIXTURE: &str = include_str!("fixtures/match_tags.txt");
+
+fn kept(filter: &TagFilter) -> Vec<&'static str> {
+    FIXTURE
+        .lines()
+        .filter(|name| filter.matches(&Tag::new(*name)))
+        .collect()
+}
+
+#[test]
+fn test_text_filters() {
+    assert_eq!(
+        kept(&TagFilter::prefix("1.27", false)),
+        ["1.27", "1.27.3", "1.27.3-alpine"]
+    );
+    assert_eq!(
+        kept(&TagFilter::contains("1.27", false)),
+        [
+            "1.27",
+            "1.27.3",
+            "1.27.3-alpine",
+            "0.1.27-test",
+            "v1.27.0",
+            "Alpine-1.27"
+        ]
+    );
+    assert_eq!(kept(&TagFilter::exact("1.27", false)), ["1.27"]);
+
+    assert_eq!(kept(&TagFilter::prefix("alpine", false)), [] as [&str; 0]);
+    assert_eq!(kept(&TagFilter::prefix("alpine", true)), ["Alpine-1.27"]);
+    assert_eq!(
+        kept(&TagFilter::contains("ALPINE", true)),
+        ["1.27.3-alpine", "Alpine-1.27"]
+    );
+    assert_eq!(kept(&TagFilter::exact("LATEST", true)), ["latest"]);
+    assert_eq!(kept(&TagFilter::exact("LATEST", false)), [] as [&str; 0]);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_match_flag() {
+    use common::{MockRegistry, Response};
+
+    let tags: Vec<_> = FIXTURE.lines().collect();
+    let body = serde_json::json!({ "tags": tags }).to_string();
+    let registry = MockRegistry::start_tls(move |_| Response::json(200, body.clone()));
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/app", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg("--no-sort")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let listed = |args: &[&str]| {
+        let output = docker_tags(args);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // `.` matches any character and the regex isn't anchored
+    let regex = "1.27\n1.27.3\n1.27.3-alpine\n0.1.27-test\n1x27\nv1.27.0\nAlpine-1.27\n";
+    assert_eq!(listed(&["-f", "1.27"]), regex);
+    assert_eq!(listed(&["-f", "1.27", "--match", "regex"]), regex);
+    assert_eq!(
+        listed(&["-f", "1.27", "--match", "prefix"]),
+        "1.27\n1.27.3\n1.27.3-alpine\n"
+    );
+    assert_eq!(
+        listed(&["-f", "1.27", "--match", "contains"]),
+        "1.27\n1.27.3\n1.27.3-alpine\n0.1.27-test\nv1.27.0\nAlpine-1.27\n"
+    );
+    assert_eq!(listed(&["-f", "1.27", "--match", "exact"]), "1.27\n");
+
+    assert_eq!(listed(&["-f", "^alpine"]), "");
+    assert_eq!(listed(&["-f", "^alpine", "--ignore-case"]), "Alpine-1.27\n");
+    assert_eq!(
+        listed(&["-f", "alpine", "--match", "prefix", "--ignore-case"]),
+        "Alpine-1.27\n"
+    );
+    assert_eq!(
+        listed(&["-f", "LATEST", "--match", "exact", "--ignore-case"]),
+        "latest\n"
+    );
+
+    // the regex crate's message points at what's wrong
+    let output = docker_tags(&["-f", "1.27("]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(r#"Invalid regex pattern: "1.27(""#),
+        "{stdout}"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unclosed group"), "{stderr}");
+    assert!(stderr.contains("    ^"), "{stderr}");
+    // text modes take it as it is
+    assert_eq!(listed(&["-f", "1.27(", "--match", "contains"]), "");
+}
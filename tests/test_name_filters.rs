@@ -0,0 +1,67 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::Command;
+
+use common::{MockRegistry, Response};
+
+/// Repository of a CI pipeline pushing versions, commit SHAs and signatures
+fn ci_registry() -> MockRegistry {
+    let signature = format!("sha256-{}.sig", "b".repeat(64));
+    let tags = serde_json::json!({
+        "tags": [
+            "1.2.0",
+            "1.3.0",
+            "9fceb02d0ae598e95dc970b74767f19372d61af8",
+            "3e1a7c2",
+            "20241002",
+            "main",
+            "feature-very-long-branch-name",
+            signature,
+        ]
+    })
+    .to_string();
+    MockRegistry::start_tls(move |_| Response::json(200, tags.clone()))
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> String {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/app", registry.host()))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_name_filters() {
+    let registry = ci_registry();
+    assert_eq!(
+        docker_tags(&registry, &["--no-sha"]),
+        "1.3.0\n1.2.0\n20241002\n3e1a7c2\nfeature-very-long-branch-name\nmain\n"
+    );
+    assert_eq!(
+        docker_tags(&registry, &["--no-sha", "--max-name-length", "10"]),
+        "1.3.0\n1.2.0\n20241002\n3e1a7c2\nmain\n"
+    );
+    assert_eq!(
+        docker_tags(&registry, &["--numeric-only"]),
+        "1.3.0\n1.2.0\n20241002\n"
+    );
+    // along with the pattern, in the registry's order too
+    assert_eq!(
+        docker_tags(
+            &registry,
+            &["--version-like", "-f", "^1", "--no-sort", "-n", "1"]
+        ),
+        "1.2.0\n"
+    );
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Output},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use common::{MockRegistry, Request, Response};
+
+/// Registry with tags for `app`, and for `dead` once `revived`
+fn registry(revived: Arc<AtomicBool>) -> MockRegistry {
+    MockRegistry::start_tls(move |req: &Request| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(200, r#"{"tags":["1.0.0"]}"#),
+        "/v2/dead/tags/list" if revived.load(Ordering::SeqCst) => {
+            Response::json(200, r#"{"tags":["2.0.0"]}"#)
+        }
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, state: &Path, args: &[&str]) -> Output {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", state)
+        .env("XDG_STATE_HOME", state)
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+fn dead_requests(registry: &MockRegistry) -> usize {
+    registry.requests_to("/v2/dead/tags/list").len()
+}
+
+#[test]
+fn test_batch_skips_cached_dead_repository() {
+    let revived = Arc::new(AtomicBool::new(false));
+    let registry = registry(revived.clone());
+    let state = tempfile::tempdir().unwrap();
+    let list = state.path().join("images.txt");
+    let mut file = std::fs::File::create(&list).unwrap();
+    writeln!(file, "{0}/app\n{0}/dead", registry.host()).unwrap();
+    let batch = |args: &[&str]| {
+        let list = list.to_str().unwrap();
+        docker_tags(
+            &registry,
+            state.path(),
+            &[&["batch", list][..], args].concat(),
+        )
+    };
+
+    let output = batch(&[]);
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+    assert_eq!(dead_requests(&registry), 1);
+
+    let output = batch(&[]);
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not found (cached)"), "{stderr}");
+    assert_eq!(dead_requests(&registry), 1);
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 2);
+
+    // asking again, which finds it and forgets it was missing
+    revived.store(true, Ordering::SeqCst);
+    assert_eq!(batch(&["--no-cache"]).status.code(), Some(0));
+    assert_eq!(dead_requests(&registry), 2);
+    assert_eq!(batch(&[]).status.code(), Some(0));
+    assert_eq!(dead_requests(&registry), 3);
+}
+
+#[test]
+fn test_listing_skips_cached_dead_repository() {
+    let registry = registry(Arc::default());
+    let state = tempfile::tempdir().unwrap();
+    let dead = format!("{}/dead", registry.host());
+    let list =
+        |args: &[&str]| docker_tags(&registry, state.path(), &[&[&dead[..]][..], args].concat());
+
+    assert_eq!(list(&[]).status.code(), Some(1));
+    let output = list(&[]);
+    assert_eq!(output.status.code(), Some(1));
+    let message = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        message.contains(&format!("{dead}: not found (cached)")),
+        "{message}"
+    );
+    assert_eq!(dead_requests(&registry), 1);
+
+    assert_eq!(list(&["--not-found-ttl", "0"]).status.code(), Some(1));
+    assert_eq!(dead_requests(&registry), 2);
+
+    let output = docker_tags(&registry, state.path(), &["cache", "clear"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!state.path().join("docker-tags/not-found.json").exists());
+    list(&[]);
+    assert_eq!(dead_requests(&registry), 3);
+}
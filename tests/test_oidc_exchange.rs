@@ -0,0 +1,238 @@
+mod common;
+
+use std::{
+    fs,
+    sync::{Arc, OnceLock},
+};
+
+use common::{MockRegistry, Request, Response, TEST_CA};
+use docker_tags::{Anonymous, Client, Error, Image, OidcTokens, Tag, TokenSource};
+use reqwest::Url;
+
+/// Fields of the form `req` posted
+fn form(req: &Request) -> Vec<(String, String)> {
+    let body = String::from_utf8_lossy(&req.body);
+    Url::parse(&format!("http://form/?{body}"))
+        .unwrap()
+        .query_pairs()
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect()
+}
+
+/// Tags of `org/app` for the token issued in exchange for the OIDC token
+/// `ci-id-token`; a token service without `exchange` only knows Basic
+/// authentication
+fn registry(exchange: bool) -> MockRegistry {
+    let base = Arc::new(OnceLock::<String>::new());
+    let registry = MockRegistry::start_tls({
+        let base = base.clone();
+        move |req| {
+            let path = req.url.path();
+            if path == "/token" && req.method == "POST" {
+                let form = form(req);
+                let field = |name: &str| {
+                    form.iter()
+                        .find(|(field, _)| field == name)
+                        .map(|(_, value)| value.as_str())
+                };
+                if !exchange
+                    || field("grant_type")
+                        != Some("urn:ietf:params:oauth:grant-type:token-exchange")
+                {
+                    return Response::json(400, r#"{"error":"unsupported_grant_type"}"#);
+                }
+                return match field("subject_token") {
+                    Some("ci-id-token") => {
+                        Response::json(200, r#"{"access_token":"registry-token"}"#)
+                    }
+                    _ => Response::json(
+                        400,
+                        r#"{"error":"invalid_grant","error_description":"subject token expired"}"#,
+                    ),
+                };
+            }
+            match (path, req.header("Authorization")) {
+                ("/token", _) => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#),
+                ("/v2/org/app/tags/list", Some("Bearer registry-token")) => {
+                    Response::json(200, r#"{"tags":["1.0.0","1.1.0"]}"#)
+                }
+                _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                    "WWW-Authenticate",
+                    format!(
+                        r#"Bearer realm="{}/token",service="mock",scope="repository:org/app:pull""#,
+                        base.get().unwrap()
+                    ),
+                ),
+            }
+        }
+    });
+    base.set(registry.url()).unwrap();
+    registry
+}
+
+/// Client exchanging the OIDC token `token` at `registry`
+fn client(registry: &MockRegistry, token: &str) -> (Client, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("id-token");
+    fs::write(&path, format!("{token}\n")).unwrap();
+    let tokens =
+        OidcTokens::new(Anonymous).add(registry.host(), "docker-tags", TokenSource::File(path));
+    let client = Client::builder()
+        .root_certificate(registry.host(), TEST_CA)
+        .unwrap()
+        .credential_provider(tokens)
+        .build();
+    (client, dir)
+}
+
+fn image(registry: &MockRegistry) -> Image {
+    Image::try_from(format!("{}/org/app", registry.host()).as_str()).unwrap()
+}
+
+#[tokio::test]
+async fn test_exchange() {
+    let registry = registry(true);
+    let (client, _dir) = client(&registry, "ci-id-token");
+
+    let tags = client.fetch_tags(&image(&registry)).await.unwrap();
+    assert_eq!(tags, [Tag::new("1.0.0"), Tag::new("1.1.0")]);
+
+    let exchanges = registry.requests_to("/token");
+    assert_eq!(exchanges.len(), 1);
+    let exchange = &exchanges[0];
+    assert_eq!(exchange.method, "POST");
+    assert_eq!(exchange.url.query(), None);
+    assert_eq!(
+        exchange.header("Content-Type"),
+        Some("application/x-www-form-urlencoded")
+    );
+    let expected = [
+        (
+            "grant_type",
+            "urn:ietf:params:oauth:grant-type:token-exchange",
+        ),
+        ("subject_token", "ci-id-token"),
+        (
+            "subject_token_type",
+            "urn:ietf:params:oauth:token-type:id_token",
+        ),
+        ("client_id", "docker-tags"),
+        ("service", "mock"),
+        ("scope", "repository:org/app:pull"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    assert_eq!(form(exchange), expected);
+}
+
+#[tokio::test]
+async fn test_expired_subject_token() {
+    let registry = registry(true);
+    let (client, _dir) = client(&registry, "expired-id-token");
+
+    let err = client.fetch_tags(&image(&registry)).await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::TokenExchange {
+            realm: format!("{}/token", registry.url()),
+            status: 400,
+            message: "invalid_grant: subject token expired".to_string(),
+        })
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Token exchange at {}/token failed with HTTP 400: invalid_grant: subject token expired",
+            registry.url()
+        )
+    );
+    // no listing without a token
+    assert_eq!(registry.requests_to("/v2/org/app/tags/list").len(), 1);
+}
+
+#[tokio::test]
+async fn test_unsupported_grant() {
+    let registry = registry(false);
+    let (client, _dir) = client(&registry, "ci-id-token");
+
+    let err = client.fetch_tags(&image(&registry)).await.unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::TokenExchange { status: 400, message, .. }) if message == "unsupported_grant_type"
+        ),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_unreadable_subject_token() {
+    let registry = registry(true);
+    let tokens = OidcTokens::new(Anonymous).add(
+        registry.host(),
+        "docker-tags",
+        TokenSource::Env("DOCKER_TAGS_TEST_UNSET_ID_TOKEN".to_string()),
+    );
+    let client = Client::builder()
+        .root_certificate(registry.host(), TEST_CA)
+        .unwrap()
+        .credential_provider(tokens)
+        .build();
+
+    let err = client.fetch_tags(&image(&registry)).await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::OidcToken {
+            registry: registry.host(),
+            message: "$DOCKER_TAGS_TEST_UNSET_ID_TOKEN is not set".to_string(),
+        })
+    );
+    assert!(registry.requests_to("/token").is_empty());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_oidc_flags() {
+    use std::process::Command;
+
+    let registry = registry(true);
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .env("CI_ID_TOKEN", "ci-id-token")
+            .arg(format!("{host}/org/app"))
+            .arg("--ca-cert")
+            .arg(format!("{host}={ca}"))
+            .arg("--no-cache")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let client_id = format!("--oidc-client-id={host}=docker-tags");
+
+    let output = docker_tags(&[&client_id, &format!("--oidc-token-env={host}=CI_ID_TOKEN")]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.1.0\n1.0.0\n");
+
+    let path = dir.path().join("id-token");
+    fs::write(&path, "expired-id-token\n").unwrap();
+    let token_file = format!("--oidc-token-file={host}={}", path.display());
+    let output = docker_tags(&[&client_id, &token_file]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let error = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        error.contains("failed with HTTP 400: invalid_grant: subject token expired"),
+        "{error}"
+    );
+
+    let output = docker_tags(&[&token_file]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let error = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        error.contains(&format!("needs --oidc-client-id {host}=ID")),
+        "{error}"
+    );
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::Command;
+
+use common::{MockRegistry, Response};
+use sha2::{Digest, Sha256};
+
+/// Tags in registry order, with the `created` dates of their configs
+const TAGS: &[(&str, &str)] = &[
+    ("latest", "2024-10-05T00:00:00Z"),
+    ("1.0.0", "2024-01-01T00:00:00Z"),
+    ("2.0.0", "2024-06-01T00:00:00Z"),
+    ("1.1.0", "2024-03-01T00:00:00Z"),
+    ("2.1.0-rc1", "2024-09-01T00:00:00Z"),
+    ("1.10.0", "2024-05-01T00:00:00Z"),
+];
+
+/// Arguments and the exact tags printed, `None` for a usage error
+const CASES: &[(&[&str], Option<&[&str]>)] = &[
+    // versions, newest first
+    (
+        &[],
+        Some(&["2.1.0-rc1", "2.0.0", "1.10.0", "1.1.0", "1.0.0", "latest"]),
+    ),
+    (
+        &["-r"],
+        Some(&["latest", "1.0.0", "1.1.0", "1.10.0", "2.0.0", "2.1.0-rc1"]),
+    ),
+    (&["-n", "2"], Some(&["2.1.0-rc1", "2.0.0"])),
+    (&["-r", "-n", "2"], Some(&["latest", "1.0.0"])),
+    // the registry's order
+    (
+        &["--no-sort"],
+        Some(&["latest", "1.0.0", "2.0.0", "1.1.0", "2.1.0-rc1", "1.10.0"]),
+    ),
+    (
+        &["--no-sort", "-r"],
+        Some(&["1.10.0", "2.1.0-rc1", "1.1.0", "2.0.0", "1.0.0", "latest"]),
+    ),
+    (&["--no-sort", "-n", "2"], Some(&["latest", "1.0.0"])),
+    (
+        &["--no-sort", "-r", "-n", "2"],
+        Some(&["1.10.0", "2.1.0-rc1"]),
+    ),
+    // created dates, newest first
+    (
+        &["--added-after-tag", "1.0.0"],
+        Some(&["latest", "2.1.0-rc1", "2.0.0", "1.10.0", "1.1.0"]),
+    ),
+    (
+        &["--added-after-tag", "1.0.0", "-r"],
+        Some(&["1.1.0", "1.10.0", "2.0.0", "2.1.0-rc1", "latest"]),
+    ),
+    (
+        &["--added-after-tag", "1.0.0", "-n", "2"],
+        Some(&["latest", "2.1.0-rc1"]),
+    ),
+    (
+        &["--added-after-tag", "1.0.0", "-r", "-n", "2"],
+        Some(&["1.1.0", "1.10.0"]),
+    ),
+    // newest stable version of each series
+    (&["--latest-per", "major"], Some(&["2.0.0", "1.10.0"])),
+    (&["--latest-per", "major", "-n", "1"], Some(&["2.0.0"])),
+    (&["--latest-per", "major", "-r"], None),
+    (&["--latest-per", "minor", "-r", "-n", "1"], None),
+];
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req| {
+        let path = req.url.path();
+        if path == "/v2/app/tags/list" {
+            let names: Vec<_> = TAGS.iter().map(|(name, _)| name).collect();
+            return Response::json(200, serde_json::json!({ "tags": names }).to_string());
+        }
+        for (name, created) in TAGS {
+            let config = serde_json::json!({ "os": "linux", "created": created }).to_string();
+            let body = if path == format!("/v2/app/manifests/{name}") {
+                serde_json::json!({
+                    "schemaVersion": 2,
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "config": {
+                        "mediaType": "application/vnd.oci.image.config.v1+json",
+                        "digest": digest(&config),
+                        "size": config.len(),
+                    },
+                    "layers": [],
+                })
+                .to_string()
+            } else if path == format!("/v2/app/blobs/{}", digest(&config)) {
+                config
+            } else {
+                continue;
+            };
+            return Response::json(200, body.clone())
+                .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+                .header("Docker-Content-Digest", digest(&body));
+        }
+        Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#)
+    })
+}
+
+#[test]
+fn test_order_flag_combinations() {
+    let registry = registry();
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+
+    for (args, expected) in CASES {
+        let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg(format!("{}/app", registry.host()))
+            .args(*args)
+            .output()
+            .unwrap();
+        match expected {
+            Some(expected) => {
+                assert!(output.status.success(), "{args:?}: {output:?}");
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                assert_eq!(stdout.lines().collect::<Vec<_>>(), *expected, "{args:?}");
+            }
+            None => {
+                assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                assert!(stderr.contains("cannot be used with"), "{args:?}: {stderr}");
+            }
+        }
+    }
+}
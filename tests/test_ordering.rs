@@ -0,0 +1,222 @@
+use std::cmp::Ordering;
+
+#[cfg(feature = "unstable")]
+use docker_tags::unstable::auth::{parse_challenge, parse_www_authenticate};
+use docker_tags::{Image, SeriesLevel, SortKey, Tag, TagStats, latest_per_series, sort_tags_desc};
+use proptest::prelude::*;
+
+/// Tag names mixing versions, near-versions and arbitrary Unicode
+fn tag_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "v?[0-9]{1,3}\\.[0-9]{1,3}\\.[0-9]{1,3}(-[a-z0-9.]{1,6})?(\\+[a-z0-9.]{1,6})?",
+        "[0-9]{1,25}\\.[0-9]{1,25}\\.[0-9]{1,25}",
+        "v?[0-9]{1,3}(\\.[0-9]{1,3})?(-[a-z]{1,6})?",
+        "[a-zA-Z0-9._-]{0,12}",
+        "\\PC{0,16}",
+        "[0-9]{1,3}".prop_map(|s| format!("{}.0.0", s.repeat(3000))),
+        Just("1.2.3+build.18446744073709551616".to_string()),
+        "v?[0-9]{15,25}(\\.[0-9]{15,25}){0,2}(-[a-z0-9]{1,6})?",
+        "[0-9]{250,300}",
+        "[0-9]{1,3}\\.[0-9]{1,3}-[a-z]{250,260}",
+    ]
+}
+
+/// Tags seen on registries which broke version parsing elsewhere
+const PATHOLOGICAL: &[&str] = &[
+    "1.2.3+build.18446744073709551616",
+    "18446744073709551616",
+    "1.18446744073709551616.0",
+    "0.0.1-18446744073709551616",
+    "2024.06.18446744073709551616",
+    "1.0.0+99999999999999999999999999999999999999",
+];
+
+proptest! {
+    #[test]
+    fn test_cmp_is_antisymmetric(a in tag_name(), b in tag_name()) {
+        let (a, b) = (Tag::new(a), Tag::new(b));
+        prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+        prop_assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+    }
+
+    #[test]
+    fn test_cmp_is_transitive(mut names in prop::collection::vec(tag_name(), 3)) {
+        let mut tags: Vec<_> = names.drain(..).map(Tag::new).collect();
+        tags.sort();
+        let (a, b, c) = (&tags[0], &tags[1], &tags[2]);
+        prop_assert!(a <= b && b <= c && a <= c);
+    }
+
+    #[test]
+    fn test_sort_is_deterministic(
+        names in prop::collection::vec(tag_name(), 0..50),
+        seed in any::<u64>(),
+    ) {
+        let mut expected: Vec<_> = names.iter().cloned().map(Tag::new).collect();
+        expected.sort();
+
+        // Deterministic shuffle driven by `seed`
+        let mut shuffled: Vec<_> = names.into_iter().map(Tag::new).collect();
+        let mut state = seed;
+        for i in (1..shuffled.len()).rev() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            shuffled.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        shuffled.sort();
+
+        prop_assert_eq!(shuffled, expected);
+    }
+
+    #[test]
+    fn test_version_parsing_never_panics(names in prop::collection::vec(tag_name(), 0..20)) {
+        let mut tags: Vec<_> = names.into_iter().map(Tag::new).collect();
+        sort_tags_desc(&mut tags);
+        let _ = TagStats::compute(&tags);
+        for level in [SeriesLevel::Major, SeriesLevel::Minor] {
+            let _ = latest_per_series(&tags, level, true);
+        }
+    }
+
+    #[test]
+    fn test_image_try_from_never_panics(reference in "\\PC{0,64}") {
+        let _ = Image::try_from(reference.as_str());
+    }
+
+    #[cfg(feature = "unstable")]
+    #[test]
+    fn test_parse_challenge_never_panics(hdr in "\\PC{0,64}") {
+        let _ = parse_challenge(&hdr);
+        if let Ok(challenges) = parse_www_authenticate(&hdr) {
+            assert!(!challenges.is_empty());
+        }
+    }
+}
+
+#[test]
+fn test_prefixed_versions_are_distinct() {
+    let mut tags = vec![Tag::new("v1.0.0"), Tag::new("1.0.0")];
+    tags.sort();
+    assert_eq!(tags, [Tag::new("1.0.0"), Tag::new("v1.0.0")]);
+    assert_ne!(Tag::new("1.0.0").cmp(&Tag::new("v1.0.0")), Ordering::Equal);
+}
+
+#[test]
+fn test_max_is_newest_version() {
+    let tags = ["latest", "1.10.0", "v2.0.0-rc1", "1.9.3", "edge"].map(Tag::new);
+    assert_eq!(tags.iter().max(), Some(&Tag::new("v2.0.0-rc1")));
+    assert_eq!(tags.iter().min(), Some(&Tag::new("edge")));
+
+    let set: std::collections::BTreeSet<_> = tags.iter().cloned().collect();
+    let names: Vec<_> = set.iter().map(Tag::name).collect();
+    assert_eq!(names, ["edge", "latest", "1.9.3", "1.10.0", "v2.0.0-rc1"]);
+}
+
+#[test]
+fn test_sort_tags_desc() {
+    let mut tags = ["latest", "1.10.0", "v2.0.0-rc1", "1.9.3", "edge", "2.0.0"].map(Tag::new);
+    sort_tags_desc(&mut tags);
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    assert_eq!(
+        names,
+        ["2.0.0", "v2.0.0-rc1", "1.10.0", "1.9.3", "edge", "latest"]
+    );
+}
+
+#[test]
+fn test_sort_key() {
+    let key = Tag::new("v1.27.2-alpine").sort_key();
+    assert_eq!(key, SortKey::Semver("1.27.2-alpine".parse().unwrap()));
+    assert_eq!(Tag::new("1.27").sort_key(), SortKey::Opaque);
+    assert!(SortKey::Opaque < key);
+    assert_eq!(
+        Tag::new("1.27").opaque_reason().as_deref(),
+        Some("unexpected end of input while parsing minor version number")
+    );
+    assert_eq!(Tag::new("1.27.2").opaque_reason(), None);
+    assert_eq!(
+        Tag::new("1".repeat(300)).opaque_reason().as_deref(),
+        Some("longer than 256 characters")
+    );
+}
+
+#[test]
+fn test_pathological_tags() {
+    let digits = "1".repeat(300);
+    let mut tags: Vec<_> = PATHOLOGICAL
+        .iter()
+        .copied()
+        .chain([digits.as_str(), "1.2.3", "latest"])
+        .map(Tag::new)
+        .collect();
+    sort_tags_desc(&mut tags);
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    // build metadata orders the same version, too large numbers or too long
+    // names make tags which aren't versions
+    assert_eq!(
+        names,
+        [
+            "1.2.3+build.18446744073709551616",
+            "1.2.3",
+            "1.0.0+99999999999999999999999999999999999999",
+            "0.0.1-18446744073709551616",
+            "1.18446744073709551616.0",
+            digits.as_str(),
+            "18446744073709551616",
+            "2024.06.18446744073709551616",
+            "latest",
+        ]
+    );
+
+    let stats = TagStats::compute(&tags);
+    assert_eq!(stats.newest, Some(Tag::new("1.2.3")));
+    let series: Vec<_> = latest_per_series(&tags, SeriesLevel::Minor, false)
+        .into_iter()
+        .map(|s| s.series)
+        .collect();
+    // the lenient parser reads `-18446744073709551616` as a variant suffix
+    assert_eq!(series, ["1.2", "0.0"]);
+}
+
+#[test]
+fn test_mixed_case_order() {
+    let mut tags: Vec<_> = [
+        "rc1",
+        "latest",
+        "RC1",
+        "Alpha",
+        "1.0-bookworm",
+        "alpha",
+        "1.0-alpine",
+        "1.0-Alpine",
+        "2.0.0",
+    ]
+    .map(Tag::new)
+    .into();
+    sort_tags_desc(&mut tags);
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    // letters regardless of case, then uppercase first; byte order would
+    // put `Alpha` and `RC1` before every lowercase name
+    assert_eq!(
+        names,
+        [
+            "2.0.0",
+            "1.0-Alpine",
+            "1.0-alpine",
+            "1.0-bookworm",
+            "Alpha",
+            "alpha",
+            "latest",
+            "RC1",
+            "rc1",
+        ]
+    );
+    assert_eq!(
+        TagStats::compute(&tags).variants,
+        ["Alpine", "alpine", "bookworm"]
+    );
+    let series: Vec<_> = latest_per_series(&tags, SeriesLevel::Minor, true)
+        .into_iter()
+        .map(|s| s.series)
+        .collect();
+    assert_eq!(series, ["2.0", "1.0-Alpine", "1.0-alpine", "1.0-bookworm"]);
+}
@@ -0,0 +1,194 @@
+#![cfg(feature = "otel")]
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use common::MockRegistry;
+use docker_tags::{Anonymous, Client, Image};
+use reqwest::header::HeaderValue;
+use tracing::{
+    Event, Instrument, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+
+/// A span as [`Spans`] saw it
+#[derive(Clone, Debug, Default)]
+struct SpanData {
+    parent: Option<u64>,
+    fields: HashMap<String, String>,
+}
+
+impl SpanData {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+impl Visit for SpanData {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields
+            .insert(field.name().to_string(), value.to_string());
+    }
+}
+
+/// Subscriber keeping the fields of every span, by id in order of creation
+#[derive(Clone, Default)]
+struct Spans {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+    entered: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Spans {
+    fn get(&self) -> Vec<SpanData> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl Subscriber for Spans {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.into_u64()),
+            None if attrs.is_contextual() => self.entered.lock().unwrap().last().copied(),
+            None => None,
+        };
+        let mut span = SpanData {
+            parent,
+            ..Default::default()
+        };
+        span.fields
+            .insert("name".to_string(), attrs.metadata().name().to_string());
+        attrs.record(&mut span);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut spans[span.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        self.entered.lock().unwrap().push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+}
+
+/// A W3C `traceparent` for `span`, with its root standing for the trace
+fn traceparent(spans: &Spans, span: &tracing::Span) -> HeaderValue {
+    let id = span.id().unwrap().into_u64();
+    let all = spans.get();
+    let mut root = id;
+    while let Some(parent) = all[root as usize - 1].parent {
+        root = parent;
+    }
+    format!("00-{root:032x}-{id:016x}-01").parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_request_spans() {
+    let spans = Spans::default();
+    let _guard = tracing::subscriber::set_default(spans.clone());
+    let mock = MockRegistry::with_tags("org/app", &["1.0.0", "1.1.0"], true);
+    let client = Client::builder()
+        .credential_provider(Anonymous)
+        .insecure_registry(mock.host())
+        .inject_context({
+            let spans = spans.clone();
+            move |span, headers| {
+                headers.insert("traceparent", traceparent(&spans, span));
+            }
+        })
+        .build();
+    let image = Image::try_from(format!("{}/org/app", mock.host()).as_str()).unwrap();
+
+    let listing = tracing::info_span!("listing");
+    let tags = client.fetch_tags(&image).instrument(listing).await.unwrap();
+    assert_eq!(tags.len(), 2);
+
+    // the challenge, the token and the listing again
+    let all = spans.get();
+    let requests: Vec<_> = all.iter().skip(1).collect();
+    assert_eq!(requests.len(), 3, "{all:#?}");
+    let tags_url = format!("{}/v2/org/app/tags/list?n=100", mock.url());
+    let port = mock.host().rsplit_once(':').unwrap().1.to_string();
+    for (span, url, status, resends) in [
+        (requests[0], tags_url.as_str(), "401", None),
+        (requests[2], tags_url.as_str(), "200", Some("1")),
+    ] {
+        assert_eq!(span.parent, Some(1), "{span:?}");
+        assert_eq!(span.field("otel.name"), Some("GET"));
+        assert_eq!(span.field("otel.kind"), Some("client"));
+        assert_eq!(span.field("http.request.method"), Some("GET"));
+        assert_eq!(span.field("url.full"), Some(url));
+        assert_eq!(span.field("server.address"), Some("127.0.0.1"));
+        assert_eq!(span.field("server.port"), Some(port.as_str()));
+        assert_eq!(span.field("http.response.status_code"), Some(status));
+        assert_eq!(span.field("http.request.resend_count"), resends);
+    }
+    assert_eq!(requests[0].field("error.type"), Some("401"));
+    assert_eq!(requests[0].field("otel.status_code"), Some("ERROR"));
+    assert_eq!(requests[2].field("error.type"), None);
+    assert!(
+        requests[1]
+            .field("url.full")
+            .unwrap()
+            .starts_with(&format!("{}/token?", mock.url()))
+    );
+
+    // each request carries the context of its own span
+    let received: Vec<_> = mock
+        .requests()
+        .iter()
+        .map(|req| req.header("traceparent").unwrap().to_string())
+        .collect();
+    assert_eq!(
+        received,
+        [2, 3, 4].map(|id| format!("00-{:032x}-{id:016x}-01", 1))
+    );
+}
+
+#[tokio::test]
+async fn test_spans_without_hook() {
+    let spans = Spans::default();
+    let _guard = tracing::subscriber::set_default(spans.clone());
+    let mock = MockRegistry::with_tags("org/app", &["1.0.0"], false);
+    let client = Client::builder()
+        .credential_provider(Anonymous)
+        .insecure_registry(mock.host())
+        .build();
+    let image = Image::try_from(format!("{}/org/app", mock.host()).as_str()).unwrap();
+
+    client.fetch_tags(&image).await.unwrap();
+    let all = spans.get();
+    assert_eq!(all.len(), 1, "{all:#?}");
+    assert_eq!(all[0].parent, None);
+    assert_eq!(all[0].field("http.response.status_code"), Some("200"));
+    assert!(
+        mock.requests()
+            .iter()
+            .all(|req| req.header("traceparent").is_none())
+    );
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Image, PageCursor};
+
+const TAGS: &[&str] = &["1.0", "1.1", "1.2", "2.0", "2.1", "2.2", "3.0"];
+
+fn client(registry: &MockRegistry) -> Client {
+    Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build()
+}
+
+/// Walk all pages of `app`, keeping only the serialized cursor between pages
+/// and using a new client for each, as a web UI handling separate requests would
+async fn walk(registry: &MockRegistry, page_size: usize) -> (Vec<Vec<String>>, Vec<String>) {
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let (mut pages, mut cursors) = (Vec::new(), Vec::new());
+    let mut held: Option<String> = None;
+    loop {
+        let cursor: Option<PageCursor> = held.as_deref().map(|c| serde_json::from_str(c).unwrap());
+        let (tags, next) = client(registry)
+            .fetch_tags_page(&image, cursor, page_size)
+            .await
+            .unwrap();
+        pages.push(tags.iter().map(|t| t.name().to_string()).collect());
+        let Some(next) = next else {
+            break;
+        };
+        let json = serde_json::to_string(&next).unwrap();
+        cursors.push(json.clone());
+        held = Some(json);
+    }
+    (pages, cursors)
+}
+
+#[tokio::test]
+async fn test_walk_pages_with_last() {
+    let registry = MockRegistry::with_tags("app", TAGS, true);
+    let (pages, cursors) = walk(&registry, 3).await;
+    assert_eq!(
+        pages,
+        [
+            vec!["1.0", "1.1", "1.2"],
+            vec!["2.0", "2.1", "2.2"],
+            vec!["3.0"]
+        ]
+    );
+    assert_eq!(cursors.len(), 2);
+    // the token the registry handed out isn't part of the cursor
+    assert!(
+        cursors.iter().all(|c| !c.contains("mock-token")),
+        "{cursors:?}"
+    );
+    let pages = registry.requests_to("/v2/app/tags/list");
+    assert!(
+        pages
+            .iter()
+            .any(|r| r.query("last").as_deref() == Some("2.2"))
+    );
+
+    // walking everything at once gives the same tags
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let tags = client(&registry).fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), TAGS.len());
+}
+
+#[tokio::test]
+async fn test_walk_pages_with_link() {
+    // a registry paging through an opaque `page` parameter announced by `Link`
+    let registry = MockRegistry::start(|req| {
+        if req.url.path() != "/v2/app/tags/list" {
+            return Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#);
+        }
+        let n: usize = req.query("n").unwrap().parse().unwrap();
+        let start: usize = req.query("page").map_or(0, |p| p.parse().unwrap());
+        let page: Vec<_> = TAGS.iter().skip(start).take(n).collect();
+        let mut resp = Response::json(200, serde_json::json!({ "tags": page }).to_string());
+        if start + n < TAGS.len() {
+            let next = format!(
+                r#"</v2/app/tags/list?n={n}&page={}>; rel="next""#,
+                start + n
+            );
+            resp = resp.header("Link", next);
+        }
+        resp
+    });
+    let (pages, cursors) = walk(&registry, 3).await;
+    assert_eq!(pages.concat(), TAGS);
+    assert_eq!(pages.len(), 3);
+    assert_eq!(cursors[1], r#"{"next":"/v2/app/tags/list?n=3&page=6"}"#);
+
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let tags = client(&registry).fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), TAGS.len());
+}
+
+#[tokio::test]
+async fn test_cursor_off_registry() {
+    let registry = MockRegistry::with_tags("app", TAGS, false);
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let cursor: PageCursor = serde_json::from_str(r#"{"next":"//example.com/steal"}"#).unwrap();
+    let err = client(&registry)
+        .fetch_tags_page(&image, Some(cursor), 3)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid page cursor"), "{err:#}");
+    assert!(registry.requests().is_empty());
+}
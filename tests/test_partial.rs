@@ -0,0 +1,59 @@
+mod common;
+
+use common::{MockRegistry, Response, paginate};
+use docker_tags::{Client, Image};
+
+/// Registry serving 250 tags which dies when asked for the third page
+fn flaky_registry() -> MockRegistry {
+    let tags: Vec<_> = (0..250).map(|i| format!("1.0.{i}")).collect();
+    MockRegistry::start(move |req| match req.query("last").as_deref() {
+        Some("1.0.199") => Response::hang_up(),
+        _ => Response::json(
+            200,
+            serde_json::json!({ "tags": paginate(&tags, req) }).to_string(),
+        ),
+    })
+}
+
+#[tokio::test]
+async fn test_partial_fetch_keeps_collected_pages() {
+    let registry = flaky_registry();
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    let (tags, outcome) = client.fetch_tags_partial(&image).await.unwrap();
+    assert_eq!(tags.len(), 200);
+    assert_eq!(outcome.pages, 2);
+    assert!(!outcome.is_complete());
+    assert!(outcome.error.is_some());
+}
+
+#[tokio::test]
+async fn test_strict_fetch_fails_on_interrupted_pagination() {
+    let registry = flaky_registry();
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    assert!(client.fetch_tags(&image).await.is_err());
+}
+
+#[tokio::test]
+async fn test_partial_fetch_fails_without_any_page() {
+    let registry = MockRegistry::start(|_| Response::hang_up());
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    assert!(client.fetch_tags_partial(&image).await.is_err());
+}
+
+#[tokio::test]
+async fn test_partial_fetch_complete_listing() {
+    let registry = MockRegistry::with_tags("app", &["1.0.0", "1.0.1"], false);
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    let (tags, outcome) = client.fetch_tags_partial(&image).await.unwrap();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(outcome.pages, 1);
+    assert!(outcome.is_complete());
+}
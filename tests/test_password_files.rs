@@ -0,0 +1,201 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    process::{Command, Output},
+    sync::{Arc, OnceLock},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use common::{MockRegistry, Response};
+
+/// Tags of `org/private` for the token issued to `ci:hunter2` only, or a
+/// token minted elsewhere
+fn registry() -> MockRegistry {
+    let base = Arc::new(OnceLock::<String>::new());
+    let login = format!("Basic {}", STANDARD.encode("ci:hunter2"));
+    let registry = MockRegistry::start_tls({
+        let base = base.clone();
+        move |req| match (req.url.path(), req.header("Authorization")) {
+            ("/token", Some(auth)) if auth == login => {
+                Response::json(200, r#"{"token":"ci-token"}"#)
+            }
+            ("/token", _) => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#),
+            ("/v2/org/private/tags/list", Some("Bearer ci-token" | "Bearer minted")) => {
+                Response::json(200, r#"{"tags":["1.0.0"]}"#)
+            }
+            _ => Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(
+                    r#"Bearer realm="{}/token",service="mock",scope="repository:org/private:pull""#,
+                    base.get().unwrap()
+                ),
+            ),
+        }
+    });
+    base.set(registry.url()).unwrap();
+    registry
+}
+
+#[test]
+fn test_password_file() {
+    let registry = registry();
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    // Docker's config has other credentials for the registry
+    fs::write(
+        dir.path().join("config.json"),
+        format!(
+            r#"{{"auths": {{"{host}": {{"auth": "{}"}}}}}}"#,
+            STANDARD.encode("stored:wrong")
+        ),
+    )
+    .unwrap();
+    let password = dir.path().join("password");
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .arg(format!("{host}/org/private"))
+            .arg("--ca-cert")
+            .arg(format!("{host}={ca}"))
+            // the first run finds it missing without the right credentials
+            .arg("--no-cache")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let username = format!("--username={host}=ci");
+    let password_file = format!("--password-file={host}={}", password.display());
+
+    // the stored credentials don't do
+    let output = docker_tags(&[]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+
+    // the file's trailing newline isn't part of the password, and the file
+    // takes precedence over Docker's config
+    fs::write(&password, "hunter2\n").unwrap();
+    let output = docker_tags(&[&username, &password_file]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1.0.0\n");
+
+    // an empty file is an error of its own, naming the file only
+    fs::write(&password, "\n").unwrap();
+    let output = docker_tags(&[&username, &password_file]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "Error: Failed to read the password for {host}: The secret file {} is empty\n",
+            password.display()
+        )
+    );
+
+    for (arg, message) in [
+        (&password_file, format!("needs --username {host}=USER")),
+        (&username, format!("needs --password-file {host}=FILE")),
+    ] {
+        let output = docker_tags(&[arg]);
+        assert_eq!(output.status.code(), Some(1), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(&message), "{stdout}");
+    }
+}
+
+#[test]
+fn test_profile_and_token_files() {
+    let registry = registry();
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let config = dir.path().join("config.toml");
+    fs::write(dir.path().join("password"), "hunter2\n").unwrap();
+    fs::write(dir.path().join("wrong"), "wrong\n").unwrap();
+    fs::write(dir.path().join("token"), "minted\n").unwrap();
+    let docker_tags = |profile: &str, args: &[&str]| {
+        fs::write(&config, format!("[registries.\"{host}\"]\n{profile}")).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", dir.path())
+            .env("XDG_STATE_HOME", dir.path())
+            .current_dir(dir.path())
+            .arg(format!("{host}/org/private"))
+            .arg(format!("--ca-cert={host}={ca}"))
+            .arg("--config")
+            .arg(&config)
+            .arg("--no-cache")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let listed = |output: Output| {
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "1.0.0\n");
+    };
+    let username = format!("--username={host}=ci");
+    let password_file = format!("--password-file={host}=password");
+    let token_file = format!("--token-file={host}=token");
+
+    // relative to the config file
+    listed(docker_tags(
+        "username = \"ci\"\npassword_file = \"password\"\n",
+        &[],
+    ));
+    listed(docker_tags("token_file = \"token\"\n", &[]));
+
+    // the command line's replace the profile's
+    let wrong = "username = \"ci\"\npassword_file = \"wrong\"\n";
+    assert_eq!(docker_tags(wrong, &[]).status.code(), Some(1));
+    listed(docker_tags(wrong, &[&username, &password_file]));
+    listed(docker_tags(wrong, &[&token_file]));
+    let output = docker_tags("token_file = \"wrong\"\n", &[&username, &password_file]);
+    listed(output);
+
+    // the token is read when needed, naming the file only
+    fs::write(dir.path().join("token"), "").unwrap();
+    let output = docker_tags("", &[&token_file]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!(
+            "Failed to read the token for {host}: The secret file"
+        )),
+        "{stdout}"
+    );
+
+    for (profile, args, message) in [
+        (
+            "password_file = \"password\"\n",
+            &[][..],
+            "needs username with password_file, or token_file alone",
+        ),
+        (
+            "",
+            &[username.as_str(), &password_file, &token_file][..],
+            "Only one of --password-file and --token-file",
+        ),
+    ] {
+        let output = docker_tags(profile, args);
+        assert_eq!(output.status.code(), Some(1), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(message), "{stdout}");
+    }
+}
+
+#[cfg(feature = "github")]
+#[test]
+fn test_github_token_file_conflicts_with_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", dir.path())
+        .args(["ghcr.io/org/app", "--github-token", "inline"])
+        .arg("--github-token-file")
+        .arg(dir.path().join("token"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{MockRegistry, Response, paginate};
+use docker_tags::{Anonymous, Client, Image};
+
+const PREFIX: &str = "/artifactory/api/docker/team-docker";
+
+/// Artifactory without subdomain routing: the API, including the token
+/// endpoint, lives under the repository's path
+fn artifactory() -> MockRegistry {
+    let tags: Vec<String> = (0..120).map(|i| format!("1.0.{i}")).collect();
+    MockRegistry::start_with_base(move |base, req| {
+        let Some(path) = req.url.path().strip_prefix(PREFIX) else {
+            return Response::new(404);
+        };
+        if path == "/v2/token" {
+            return Response::json(200, r#"{"token":"mock-token"}"#);
+        }
+        if req.header("Authorization") != Some("Bearer mock-token") {
+            let challenge = format!(
+                r#"Bearer realm="{base}{PREFIX}/v2/token",service="artifactory.corp",scope="repository:myimage:pull""#
+            );
+            return Response::new(401).header("WWW-Authenticate", challenge);
+        }
+        match path {
+            "/v2/myimage/tags/list" => {
+                let page = paginate(&tags, req);
+                Response::json(200, serde_json::json!({ "tags": page }).to_string())
+            }
+            _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_path_prefix() {
+    let registry = artifactory();
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .path_prefix(registry.host(), format!("{PREFIX}/"))
+        .credential_provider(Anonymous)
+        .build();
+
+    let reference = format!("{}{PREFIX}/myimage", registry.host());
+    let image = Image::parse_with_prefix(&reference, PREFIX).unwrap();
+    assert_eq!(image.repository(), "myimage");
+
+    let tags = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), 120);
+
+    let paths: Vec<_> = registry
+        .requests()
+        .iter()
+        .map(|r| r.url.path().to_string())
+        .collect();
+    assert!(paths.iter().all(|p| p.starts_with(PREFIX)), "{paths:?}");
+    assert_eq!(registry.requests_to(&format!("{PREFIX}/v2/token")).len(), 1);
+    // both pages carry the prefix too
+    assert_eq!(
+        registry
+            .requests_to(&format!("{PREFIX}/v2/myimage/tags/list"))
+            .len(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_missing_path_prefix() {
+    let registry = artifactory();
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/myimage", registry.host()).as_str()).unwrap();
+
+    let err = client.fetch_tags(&image).await.unwrap_err();
+    assert_eq!(err.to_string(), "Image not found");
+}
+
+#[tokio::test]
+async fn test_invalid_path_prefix() {
+    let client = Client::builder()
+        .path_prefix("artifactory.corp", "/artifactory/../admin")
+        .build();
+    let image = Image::new("artifactory.corp", "myimage");
+
+    let err = client.fetch_tags(&image).await.unwrap_err();
+    assert!(err.to_string().starts_with("Invalid path prefix"), "{err}");
+}
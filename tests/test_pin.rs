@@ -0,0 +1,165 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+    sync::{Arc, Mutex},
+};
+
+use common::{MockRegistry, Response};
+use sha2::{Digest, Sha256};
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn manifest(revision: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(revision),
+            "size": revision.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// Registry serving `team/app:1.0`, pointing to whatever `current` holds, and
+/// `team/app:2.0`
+fn registry(current: Arc<Mutex<String>>) -> MockRegistry {
+    MockRegistry::start_tls(move |req| {
+        let body = match req.url.path() {
+            "/v2/team/app/manifests/1.0" => current.lock().unwrap().clone(),
+            "/v2/team/app/manifests/2.0" => manifest("2.0"),
+            _ => return Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+        };
+        Response::json(200, body.clone())
+            .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+            .header("Docker-Content-Digest", digest(&body))
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, file: &Path, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .arg("pin")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .args(args)
+        .arg(file)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_pin_write_check_update() {
+    let current = Arc::new(Mutex::new(manifest("1.0")));
+    let registry = registry(current.clone());
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("Dockerfile");
+    let original = format!(
+        "FROM {host}/team/app:1.0 AS build\r\nRUN make  \r\n\nFROM {host}/team/app:2.0\nCOPY --from=build /out /"
+    );
+    fs::write(&file, &original).unwrap();
+
+    // nothing is pinned yet
+    let output = docker_tags(&registry, &file, &["--check"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        format!("1: {host}/team/app:1.0 is not pinned\n4: {host}/team/app:2.0 is not pinned\n")
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), original);
+    let output = docker_tags(&registry, &file, &["--check", "-o", "json"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["written"], false);
+    assert_eq!(report["references"][1]["line"], 4);
+    assert_eq!(report["references"][1]["state"], "missing");
+    assert_eq!(report["references"][1]["pinned"], serde_json::Value::Null);
+    assert_eq!(report["references"][1]["rewritten"], false);
+
+    // printing leaves the file alone
+    let (v1, v2) = (digest(&manifest("1.0")), digest(&manifest("2.0")));
+    let pinned = original
+        .replace("app:1.0", &format!("app:1.0@{v1}"))
+        .replace("app:2.0", &format!("app:2.0@{v2}"));
+    let output = docker_tags(&registry, &file, &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(&output), pinned);
+    assert_eq!(fs::read_to_string(&file).unwrap(), original);
+
+    let output = docker_tags(&registry, &file, &["--write"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(fs::read_to_string(&file).unwrap(), pinned);
+    assert_eq!(stdout(&output).lines().count(), 2);
+
+    // idempotent
+    let output = docker_tags(&registry, &file, &["--write"]);
+    assert_eq!(stdout(&output), "");
+    assert_eq!(fs::read_to_string(&file).unwrap(), pinned);
+    assert!(docker_tags(&registry, &file, &["--check"]).status.success());
+
+    // 1.0 moves to another image
+    *current.lock().unwrap() = manifest("1.0.1");
+    let v1_new = digest(&manifest("1.0.1"));
+    let output = docker_tags(&registry, &file, &["--check"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        stdout(&output),
+        format!("1: {host}/team/app:1.0 is pinned to {v1}, the tag now points to {v1_new}\n")
+    );
+
+    let output = docker_tags(&registry, &file, &["--write"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass --update"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), pinned);
+
+    let output = docker_tags(&registry, &file, &["--write", "--update", "-o", "yaml"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "file: {}\nreferences:\n\
+             - line: 1\n  reference: {host}/team/app:1.0\n  digest: {v1_new}\n  pinned: {v1}\n  state: outdated\n  rewritten: true\n\
+             - line: 4\n  reference: {host}/team/app:2.0\n  digest: {v2}\n  pinned: {v2}\n  state: current\n  rewritten: false\n\
+             written: true\n",
+            file.display()
+        )
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        pinned.replace(&v1, &v1_new)
+    );
+}
+
+#[test]
+fn test_pin_unknown_tag_fails_without_writing() {
+    let registry = registry(Arc::new(Mutex::new(manifest("1.0"))));
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("compose.yaml");
+    let original = format!(
+        "services:\n  a:\n    image: {host}/team/app:1.0\n  b:\n    image: {host}/team/app:9.9\n"
+    );
+    fs::write(&file, &original).unwrap();
+
+    let output = docker_tags(&registry, &file, &["--write"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains(&format!("Failed to resolve {host}/team/app:9.9")));
+    assert_eq!(fs::read_to_string(&file).unwrap(), original);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Request, Response};
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| match req.url.path() {
+        "/v2/org/app/tags/list" => Response::json(
+            200,
+            r#"{"tags":["1.0.0","latest","2.0.0","alpha","1.1.0","stable","beta"]}"#,
+        ),
+        _ => Response::new(404),
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .arg(format!("{}/org/app", registry.host()))
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+fn listed(registry: &MockRegistry, args: &[&str]) -> String {
+    let output = docker_tags(registry, args);
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_pin_aliases() {
+    let registry = registry();
+
+    // piped output sorts them like any other tag
+    assert_eq!(
+        listed(&registry, &["-n", "4"]),
+        "2.0.0\n1.1.0\n1.0.0\nalpha\n"
+    );
+    assert_eq!(
+        listed(&registry, &["-n", "4", "--pin-aliases=false"]),
+        "2.0.0\n1.1.0\n1.0.0\nalpha\n"
+    );
+
+    // first, apart from the others and not counted by --limit
+    assert_eq!(
+        listed(&registry, &["-n", "2", "--pin-aliases"]),
+        "latest\nstable\n\n2.0.0\n1.1.0\n"
+    );
+    assert_eq!(
+        listed(&registry, &["-n", "2", "--pin-aliases", "-r"]),
+        "latest\nstable\n\nbeta\nalpha\n"
+    );
+    assert_eq!(
+        listed(&registry, &["-n", "2", "--pin-aliases", "--no-sort"]),
+        "latest\nstable\n\n1.0.0\n2.0.0\n"
+    );
+    assert_eq!(
+        listed(
+            &registry,
+            &["--pin-aliases", "--aliases", "beta,latest", "-n", "1"]
+        ),
+        "beta\nlatest\n\n2.0.0\n"
+    );
+    // the filter still applies to them
+    assert_eq!(
+        listed(&registry, &["--pin-aliases", "-f", "^[0-9]", "-n", "1"]),
+        "2.0.0\n"
+    );
+
+    assert_eq!(
+        listed(&registry, &["--no-aliases"]),
+        "2.0.0\n1.1.0\n1.0.0\nalpha\nbeta\n"
+    );
+    assert_eq!(
+        listed(&registry, &["--no-aliases", "--no-sort", "-n", "3"]),
+        "1.0.0\n2.0.0\nalpha\n"
+    );
+
+    let listing: serde_json::Value = serde_json::from_str(&listed(
+        &registry,
+        &["--pin-aliases", "-n", "1", "-o", "json"],
+    ))
+    .unwrap();
+    assert_eq!(
+        listing["aliases"],
+        serde_json::json!([{"name": "latest"}, {"name": "stable"}])
+    );
+    assert_eq!(listing["tags"], serde_json::json!([{"name": "2.0.0"}]));
+    let listing: serde_json::Value =
+        serde_json::from_str(&listed(&registry, &["-o", "json"])).unwrap();
+    assert!(listing.get("aliases").is_none(), "{listing}");
+}
+
+#[test]
+fn test_pin_aliases_conflicts() {
+    let registry = registry();
+    for args in [
+        &["--pin-aliases", "--no-aliases"][..],
+        &["--pin-aliases", "--top", "2"],
+        &["--pin-aliases", "--latest-per", "major"],
+    ] {
+        let output = docker_tags(&registry, args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+    }
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Image, Tag};
+use sha2::{Digest, Sha256};
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+const CONFIG: &str = r#"{"architecture":"arm","os":"linux","variant":"v7"}"#;
+
+/// Single-platform manifest pointing to [`CONFIG`]
+fn arm_manifest() -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(CONFIG),
+            "size": CONFIG.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// Index for linux/amd64 and linux/arm64, plus an attestation
+fn index() -> String {
+    let entry = |os: &str, architecture: &str, digest: &str| {
+        serde_json::json!({
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "digest": digest,
+            "size": 100,
+            "platform": { "os": os, "architecture": architecture },
+        })
+    };
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.index.v1+json",
+        "manifests": [
+            entry("linux", "amd64", &format!("sha256:{:064x}", 1)),
+            entry("linux", "arm64", &format!("sha256:{:064x}", 2)),
+            entry("unknown", "unknown", &format!("sha256:{:064x}", 3)),
+        ],
+    })
+    .to_string()
+}
+
+/// `1.27.2`, `1.27` and `mainline` point to the index, `1.26` and
+/// `1.26-arm` to a single-platform image; `broken` has no manifest
+fn registry() -> MockRegistry {
+    MockRegistry::start(|req| {
+        let (content_type, body) = match req.url.path() {
+            "/v2/library/nginx/manifests/1.27.2"
+            | "/v2/library/nginx/manifests/1.27"
+            | "/v2/library/nginx/manifests/mainline" => {
+                ("application/vnd.oci.image.index.v1+json", index())
+            }
+            "/v2/library/nginx/manifests/1.26" | "/v2/library/nginx/manifests/1.26-arm" => {
+                ("application/vnd.oci.image.manifest.v1+json", arm_manifest())
+            }
+            path if path == format!("/v2/library/nginx/blobs/{}", digest(CONFIG)) => {
+                ("application/octet-stream", CONFIG.to_string())
+            }
+            _ => return Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+        };
+        Response::json(200, body.clone())
+            .header("Content-Type", content_type)
+            .header("Docker-Content-Digest", digest(&body))
+    })
+}
+
+#[tokio::test]
+async fn test_fetch_platforms() {
+    let registry = registry();
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/library/nginx", registry.host()).as_str()).unwrap();
+    let tags = ["1.27.2", "1.27", "mainline", "1.26", "1.26-arm", "broken"].map(Tag::new);
+
+    let (platforms, warnings) = client.fetch_platforms(&image, &tags).await;
+    let names: Vec<_> = platforms
+        .iter()
+        .map(|found| {
+            found.as_ref().map(|found| {
+                found
+                    .iter()
+                    .filter(|p| p.os != "unknown")
+                    .map(|p| format!("{}/{}", p.os, p.architecture))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let index = Some(vec!["linux/amd64".to_string(), "linux/arm64".to_string()]);
+    let arm = Some(vec!["linux/arm".to_string()]);
+    assert_eq!(
+        names,
+        [index.clone(), index.clone(), index, arm.clone(), arm, None]
+    );
+    assert!(platforms[3].as_ref().unwrap()[0].matches("linux/arm/v7"));
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].to_string().contains("broken"));
+
+    // one request per tag, and the config shared by two tags once
+    let requests = registry.requests();
+    assert_eq!(requests.len(), tags.len() + 1);
+    assert_eq!(
+        registry
+            .requests_to(&format!("/v2/library/nginx/blobs/{}", digest(CONFIG)))
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,291 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    process::{Command, Output},
+};
+
+use common::{MockRegistry, Request, Response};
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| match req.url.path() {
+        "/v2/org/app/tags/list" => Response::json(
+            200,
+            r#"{"tags":["1.25.3","1.26.2","1.27.0","1.28.0-rc1","latest"]}"#,
+        ),
+        "/v2/org/tool/tags/list" => Response::json(200, r#"{"tags":["2.0.0","edge"]}"#),
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .args(args)
+        .args(["--output", "prom", "--ca-cert"])
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+type Sample = (String, BTreeMap<String, String>, f64);
+
+/// Parse `text` as the Prometheus text exposition format, failing on
+/// anything a scraper would reject: a sample without its `# HELP` and
+/// `# TYPE` lines before it, a family split in two, a malformed name, label
+/// or value, or a series given twice
+fn parse_exposition(text: &str) -> Vec<Sample> {
+    let is_name = |name: &str| {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    };
+    let (mut samples, mut families, mut series) = (Vec::new(), Vec::new(), HashSet::new());
+    let mut current: Option<(String, bool, bool)> = None;
+    assert!(text.ends_with('\n'), "no final line feed");
+    for line in text.lines() {
+        if let Some(comment) = line.strip_prefix("# ") {
+            let (kind, rest) = comment.split_once(' ').unwrap();
+            let (name, rest) = rest.split_once(' ').unwrap();
+            assert!(is_name(name), "{line}");
+            if current.as_ref().is_none_or(|(family, ..)| family != name) {
+                assert!(
+                    !families.contains(&name.to_string()),
+                    "{name} split: {line}"
+                );
+                families.push(name.to_string());
+                current = Some((name.to_string(), false, false));
+            }
+            let (_, help, typed) = current.as_mut().unwrap();
+            match kind {
+                "HELP" => {
+                    assert!(!*help && !rest.contains('\n'), "{line}");
+                    *help = true;
+                }
+                "TYPE" => {
+                    assert!(!*typed, "{line}");
+                    assert!(
+                        ["counter", "gauge", "histogram", "summary", "untyped"].contains(&rest),
+                        "{line}"
+                    );
+                    *typed = true;
+                }
+                _ => panic!("unknown comment: {line}"),
+            }
+            continue;
+        }
+        let (name, rest) = line.split_once('{').unwrap();
+        let (family, help, typed) = current.as_ref().expect("a sample before its family");
+        assert_eq!(name, family, "{line}");
+        assert!(*help && *typed, "{line}");
+
+        let mut labels = BTreeMap::new();
+        let mut chars = rest.chars().peekable();
+        loop {
+            let label: String = chars.by_ref().take_while(|&c| c != '=').collect();
+            assert!(is_name(&label), "{line}");
+            assert_eq!(chars.next(), Some('"'), "{line}");
+            let mut value = String::new();
+            loop {
+                match chars.next().expect("unterminated label value") {
+                    '"' => break,
+                    '\\' => value.push(match chars.next() {
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        Some('n') => '\n',
+                        other => panic!("bad escape {other:?}: {line}"),
+                    }),
+                    '\n' => panic!("line feed in a label value: {line}"),
+                    c => value.push(c),
+                }
+            }
+            assert!(labels.insert(label, value).is_none(), "{line}");
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                other => panic!("unexpected {other:?}: {line}"),
+            }
+        }
+        let value: String = chars.collect();
+        let value = value.strip_prefix(' ').expect("no space before the value");
+        let value: f64 = value
+            .parse()
+            .unwrap_or_else(|_| panic!("bad value: {line}"));
+        assert!(
+            series.insert((name.to_string(), labels.clone())),
+            "twice: {line}"
+        );
+        samples.push((name.to_string(), labels, value));
+    }
+    samples
+}
+
+fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_parse_exposition() {
+    let samples =
+        parse_exposition("# HELP m A help\n# TYPE m gauge\nm{a=\"x\\\"y\\\\z\\n\",b=\"\"} 1\n");
+    assert_eq!(
+        samples,
+        [(
+            "m".to_string(),
+            labels(&[("a", "x\"y\\z\n"), ("b", "")]),
+            1.0
+        )]
+    );
+    for invalid in [
+        "m{a=\"x\"} 1\n",
+        "# HELP m A\n# TYPE m gauge\nm{a=\"x\"y\"} 1\n",
+        "# HELP m A\n# TYPE m gauge\nm{a=\"x\"} 1\nm{a=\"x\"} 2\n",
+        "# HELP m A\n# TYPE m gauge\nm{a=\"x\\q\"} 1\n",
+    ] {
+        let parsed = std::panic::catch_unwind(|| parse_exposition(invalid));
+        assert!(parsed.is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_batch_metrics() {
+    let registry = registry();
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("images.txt");
+    fs::write(
+        &input,
+        format!(
+            "{host}/org/app:1.25.3\n{host}/org/tool:2.0.0\n{host}/org/missing:1.0\n{host}/org/app:1.25.3\n"
+        ),
+    )
+    .unwrap();
+    let output = docker_tags(&registry, &["batch", input.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+    let samples = parse_exposition(&String::from_utf8(output.stdout).unwrap());
+
+    let (app, tool, missing) = (
+        format!("{host}/org/app"),
+        format!("{host}/org/tool"),
+        format!("{host}/org/missing"),
+    );
+    let expected = [
+        ("docker_tags_total", labels(&[("image", &app)]), 5.0),
+        ("docker_tags_total", labels(&[("image", &tool)]), 2.0),
+        (
+            "docker_tags_latest_semver_info",
+            labels(&[("image", &app), ("tag", "1.27.0")]),
+            1.0,
+        ),
+        (
+            "docker_tags_latest_semver_info",
+            labels(&[("image", &tool), ("tag", "2.0.0")]),
+            1.0,
+        ),
+        (
+            "docker_tags_newer_available",
+            labels(&[("image", &app), ("current_tag", "1.25.3")]),
+            1.0,
+        ),
+        (
+            "docker_tags_newer_available",
+            labels(&[("image", &tool), ("current_tag", "2.0.0")]),
+            0.0,
+        ),
+        ("docker_tags_scrape_error", labels(&[("image", &app)]), 0.0),
+        ("docker_tags_scrape_error", labels(&[("image", &tool)]), 0.0),
+        (
+            "docker_tags_scrape_error",
+            labels(&[("image", &missing)]),
+            1.0,
+        ),
+    ]
+    .map(|(name, labels, value)| (name.to_string(), labels, value));
+    assert_eq!(samples, expected);
+
+    // the tags a filter keeps decide the newest version
+    let output = docker_tags(
+        &registry,
+        &["batch", input.to_str().unwrap(), "-f", "^1.26"],
+    );
+    let samples = parse_exposition(&String::from_utf8(output.stdout).unwrap());
+    assert!(samples.contains(&(
+        "docker_tags_latest_semver_info".to_string(),
+        labels(&[("image", &app), ("tag", "1.26.2")]),
+        1.0
+    )));
+}
+
+#[test]
+fn test_listing_metrics() {
+    let registry = registry();
+    let host = registry.host();
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("docker_tags.prom");
+
+    let output = docker_tags(
+        &registry,
+        &[
+            &format!("{host}/org/app"),
+            "--output-file",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let samples = parse_exposition(&(fs::read_to_string(&file).unwrap()));
+    let names: Vec<_> = samples.iter().map(|(name, ..)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "docker_tags_total",
+            "docker_tags_latest_semver_info",
+            "docker_tags_scrape_error"
+        ]
+    );
+    assert_eq!(samples[2].2, 0.0);
+
+    // a failure still leaves metrics for the collector, telling about it
+    let output = docker_tags(
+        &registry,
+        &[
+            &format!("{host}/org/missing"),
+            "--output-file",
+            file.to_str().unwrap(),
+            "--force",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let samples = parse_exposition(&(fs::read_to_string(&file).unwrap()));
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].0, "docker_tags_scrape_error");
+    assert_eq!(samples[0].2, 1.0);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Error: "), "{stderr}");
+
+    let output = docker_tags(&registry, &[&format!("{host}/org/app"), "--digests"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("--output prom doesn't go with --digests")
+    );
+    // commands without images to report on
+    let output = docker_tags(&registry, &["version"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("--output prom is only for tag listings and batch")
+    );
+}
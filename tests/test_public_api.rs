@@ -0,0 +1,326 @@
+//! The crate's public items, the methods of public types and the items of
+//! public modules included, checked against `tests/fixtures/public_api.txt`
+//! so that exposing something new, experimental APIs outside `unstable`
+//! especially, is a decision rather than an accident. Run with
+//! `UPDATE_API_SNAPSHOT=1` to accept a change.
+
+use std::{env, fs, path::Path};
+
+/// `#[cfg(feature = "…")]` on the line, if any
+fn feature(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#[cfg(feature = \"")?
+        .strip_suffix("\")]")
+}
+
+/// The paths `pub use crate::{…}` brings in, nested braces flattened
+fn used_paths(tree: &str) -> Vec<String> {
+    let tree = tree.trim();
+    let Some((base, rest)) = tree.split_once('{') else {
+        return vec![tree.to_string()];
+    };
+    let inner = rest.strip_suffix('}').unwrap();
+    let (mut parts, mut depth, mut start) = (Vec::new(), 0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&inner[start..]);
+    parts
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .flat_map(used_paths)
+        .map(|path| format!("{base}{path}"))
+        .collect()
+}
+
+/// Name of the item a `pub …` line declares
+fn item_name(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("pub ")?;
+    let rest = rest.strip_prefix("async ").unwrap_or(rest);
+    let rest = rest
+        .strip_prefix("const ")
+        .filter(|rest| rest.starts_with("fn "))
+        .unwrap_or(rest);
+    let (kind, rest) = rest.split_once(' ')?;
+    if ![
+        "struct", "enum", "fn", "trait", "const", "static", "type", "mod",
+    ]
+    .contains(&kind)
+    {
+        return None;
+    }
+    rest.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// A public item: where it's declared in the crate, e.g. `client::Client`,
+/// the path it's public under, e.g. `Client`, and the features it takes
+#[derive(Clone)]
+struct Item {
+    source: String,
+    path: String,
+    gates: Vec<String>,
+}
+
+impl Item {
+    fn line(&self) -> String {
+        let mut gates = Vec::new();
+        for gate in &self.gates {
+            if !gates.contains(gate) {
+                gates.push(gate.clone());
+            }
+        }
+        match gates.is_empty() {
+            true => self.path.clone(),
+            false => format!("{} [{}]", self.path, gates.join(", ")),
+        }
+    }
+}
+
+/// Public items of `source`, the file of module `prefix`, each with the
+/// features it takes, and the public modules kept in their own files
+fn public_names(source: &str, prefix: &str, features: &[String]) -> (Vec<Item>, Vec<Item>) {
+    let (mut names, mut files) = (Vec::new(), Vec::new());
+    let mut lines = source.lines();
+    let mut pending = Vec::new();
+    // inline modules being read, and their indentation
+    let mut modules: Vec<(String, usize, Vec<String>)> = Vec::new();
+    while let Some(line) = lines.next() {
+        let indent = line.len() - line.trim_start().len();
+        let depth = modules.len();
+        if line.trim() == "}"
+            && let Some((_, open, _)) = modules.last()
+            && *open == indent
+        {
+            modules.pop();
+            continue;
+        }
+        if indent != 4 * depth {
+            continue;
+        }
+        let line = line.trim();
+        if let Some(feature) = feature(line) {
+            pending.push(feature.to_string());
+            continue;
+        }
+        if line.starts_with("///") || line.starts_with("#[") {
+            continue;
+        }
+        let own = std::mem::take(&mut pending);
+        let gates: Vec<String> = features
+            .iter()
+            .cloned()
+            .chain(modules.iter().flat_map(|(_, _, f)| f.clone()))
+            .chain(own.clone())
+            .collect();
+        let path: String = modules
+            .iter()
+            .map(|(name, _, _)| format!("{name}::"))
+            .collect();
+        let mut found = Vec::new();
+        if let Some(tree) = line.strip_prefix("pub use crate::") {
+            let mut tree = tree.to_string();
+            while !tree.ends_with(';') {
+                tree.push_str(lines.next().unwrap().trim());
+            }
+            for used in used_paths(tree.trim_end_matches(';')) {
+                let name = used.rsplit("::").next().unwrap().to_string();
+                found.push((used, name));
+            }
+        } else if let Some(name) = item_name(line) {
+            found.push((format!("{prefix}{path}{name}"), name.to_string()));
+            if line.starts_with("pub mod ") && line.ends_with('{') {
+                modules.push((name.to_string(), indent, own.clone()));
+            } else if line.starts_with("pub mod ") && line.ends_with(';') {
+                files.push(Item {
+                    source: format!("{prefix}{path}{name}"),
+                    path: format!("{prefix}{path}{name}::"),
+                    gates: gates.clone(),
+                });
+            }
+        }
+        for (source, name) in found {
+            names.push(Item {
+                source,
+                path: format!("{prefix}{path}{name}"),
+                gates: gates.clone(),
+            });
+        }
+    }
+    (names, files)
+}
+
+/// The type an `impl …` line is for, unless it implements a trait
+fn receiver(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("impl")?;
+    let rest = match rest.strip_prefix('<') {
+        Some(generics) => generics.split_once('>')?.1,
+        None => rest,
+    };
+    if rest.contains(" for ") {
+        return None;
+    }
+    rest.trim_start()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// Public methods and constants of the `impl` blocks of `source`, the file
+/// of `module`, on the types of `public`, e.g. `ClientBuilder::on_page [unstable]`
+fn public_methods(source: &str, module: &str, public: &[Item]) -> Vec<Item> {
+    let lookup = |name: &str| {
+        let source = format!("{module}::{name}");
+        let source = source.trim_start_matches("::");
+        let exact: Vec<Item> = public
+            .iter()
+            .filter(|item| item.source == source)
+            .cloned()
+            .collect();
+        if !exact.is_empty() {
+            return exact;
+        }
+        // `pub use crate::module::*`
+        let glob = format!("{module}::*");
+        public
+            .iter()
+            .filter(|item| item.source == glob)
+            .map(|item| Item {
+                path: format!("{}{name}", item.path.trim_end_matches('*')),
+                ..item.clone()
+            })
+            .collect()
+    };
+    let mut methods = Vec::new();
+    let mut receivers = Vec::new();
+    let (mut outer, mut pending) = (Vec::new(), Vec::new());
+    for line in source.lines().filter(|line| !line.is_empty()) {
+        if !line.starts_with(' ') {
+            if let Some(feature) = feature(line) {
+                outer.push(feature.to_string());
+            } else if let Some(name) = receiver(line) {
+                receivers = lookup(name);
+                for item in &mut receivers {
+                    item.gates.append(&mut outer.clone());
+                }
+            } else if !line.starts_with("///") && !line.starts_with("#[") {
+                receivers.clear();
+            }
+            if !line.starts_with("#[") && !line.starts_with("///") {
+                outer.clear();
+            }
+            continue;
+        }
+        if line.starts_with("     ") {
+            continue;
+        }
+        let line = line.trim();
+        if let Some(feature) = feature(line) {
+            pending.push(feature.to_string());
+        } else if let Some(name) = item_name(line) {
+            for item in &receivers {
+                methods.push(Item {
+                    source: format!("{}::{name}", item.source),
+                    path: format!("{}::{name}", item.path),
+                    gates: item.gates.iter().chain(&pending).cloned().collect(),
+                });
+            }
+            pending.clear();
+        } else if !line.starts_with("///") && !line.starts_with("#[") {
+            pending.clear();
+        }
+    }
+    methods
+}
+
+/// The public API of the sources `read` returns by file name in `src`
+fn public_api(read: impl Fn(&str) -> String) -> String {
+    let (mut public, mut files) = public_names(&read("lib.rs"), "", &[]);
+    while let Some(file) = files.pop() {
+        let source = read(&format!("{}.rs", file.source.replace("::", "/")));
+        let (names, nested) = public_names(&source, &file.path, &file.gates);
+        public.extend(names);
+        files.extend(nested);
+    }
+
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut methods = Vec::new();
+    for entry in fs::read_dir(src).unwrap() {
+        let file = entry.unwrap().file_name().into_string().unwrap();
+        let Some(module) = file.strip_suffix(".rs").filter(|m| *m != "main") else {
+            continue;
+        };
+        let module = if module == "lib" { "" } else { module };
+        methods.extend(public_methods(&read(&file), module, &public));
+    }
+
+    let mut lines: Vec<String> = public.iter().chain(&methods).map(Item::line).collect();
+    lines.sort();
+    lines.dedup();
+    lines.join("\n") + "\n"
+}
+
+fn read(file: &str) -> String {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    fs::read_to_string(src.join(file)).unwrap()
+}
+
+#[test]
+fn test_public_api_snapshot() {
+    let snapshot = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/public_api.txt");
+    let api = public_api(read);
+    if env::var_os("UPDATE_API_SNAPSHOT").is_some() {
+        fs::write(&snapshot, &api).unwrap();
+    }
+    assert_eq!(
+        api,
+        fs::read_to_string(&snapshot).unwrap(),
+        "the public API changed, run with UPDATE_API_SNAPSHOT=1 to accept it"
+    );
+}
+
+#[test]
+fn test_experimental_apis_are_gated() {
+    let api = public_api(read);
+    for name in ["Reference", "PageInfo", "auth", "on_page", "exchange_token"] {
+        let lines: Vec<_> = api
+            .lines()
+            .filter(|line| {
+                let path = line.split(' ').next().unwrap();
+                path.rsplit("::").next() == Some(name)
+            })
+            .collect();
+        assert!(!lines.is_empty(), "no {name}");
+        for line in lines {
+            assert!(line.contains("unstable"), "{line} outside `unstable`");
+        }
+    }
+}
+
+#[test]
+fn test_new_methods_change_the_api() {
+    let api = public_api(|file| match file {
+        "client.rs" => read(file) + "\nimpl Client {\n    pub fn brand_new(&self) {}\n}\n",
+        _ => read(file),
+    });
+    assert_ne!(api, public_api(read));
+    assert!(api.lines().any(|line| line == "Client::brand_new"), "{api}");
+}
+
+#[test]
+fn test_used_paths() {
+    assert_eq!(
+        used_paths("{a::{B, c::{D, E}}, F, g::H,}"),
+        ["a::B", "a::c::D", "a::c::E", "F", "g::H"]
+    );
+    assert_eq!(used_paths("search::SearchApi"), ["search::SearchApi"]);
+}
@@ -0,0 +1,231 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Response};
+use sha2::{Digest, Sha256};
+
+/// Tags and the `created` dates of their configs, around a window from
+/// 2026-03-03 to 2026-03-05 inclusive; `None` for a config without one
+const TAGS: &[(&str, Option<&str>)] = &[
+    ("1.0.0", Some("2026-03-02T23:59:59.999Z")),
+    // 2026-03-03T00:00:00Z, the first instant of the window
+    ("1.1.0", Some("2026-03-03T01:00:00+01:00")),
+    ("1.1.1", Some("2026-03-04T12:30:15.25Z")),
+    ("latest", Some("2026-03-04T12:30:15.25Z")),
+    ("1.2.0-rc.1", Some("2026-03-05T23:59:59Z")),
+    ("1.2.0", Some("2026-03-06T00:00:00Z")),
+    ("nightly", None),
+];
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn config(created: Option<&str>) -> String {
+    serde_json::json!({ "architecture": "amd64", "os": "linux", "created": created }).to_string()
+}
+
+fn manifest(config: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(config),
+            "size": config.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+/// Registry serving [`TAGS`] over HTTPS
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req| {
+        let path = req.url.path();
+        if path == "/v2/org/app/tags/list" {
+            let names: Vec<_> = TAGS.iter().map(|(name, _)| name).collect();
+            return Response::json(200, serde_json::json!({ "tags": names }).to_string());
+        }
+        for &(name, created) in TAGS {
+            let config = config(created);
+            let (content_type, body) = match path.rsplit_once('/') {
+                Some((_, reference)) if path.contains("/manifests/") && reference == name => (
+                    "application/vnd.oci.image.manifest.v1+json",
+                    manifest(&config),
+                ),
+                Some((_, reference))
+                    if path.contains("/blobs/") && reference == digest(&config) =>
+                {
+                    ("application/octet-stream", config)
+                }
+                _ => continue,
+            };
+            return Response::json(200, body.clone())
+                .header("Content-Type", content_type)
+                .header("Docker-Content-Digest", digest(&body));
+        }
+        Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#)
+    })
+}
+
+/// Run `published` against `registry`, trusting its CA and without
+/// credentials
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("published")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/org/app", registry.host()))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_published_between_days() {
+    let registry = registry();
+
+    let output = docker_tags(&registry, &["--from", "2026-03-03", "--to", "2026-03-05"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        [
+            "2026-03-03T00:00:00Z\t1.1.0",
+            "2026-03-04T12:30:15.25Z\t1.1.1",
+            "2026-03-04T12:30:15.25Z\tlatest",
+            "2026-03-05T23:59:59Z\t1.2.0-rc.1",
+            "unknown date\tnightly",
+        ]
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Warning: skipped nightly without a created date"),
+        "{stderr}"
+    );
+
+    // an exact --to is left out, an exact --from kept
+    let output = docker_tags(
+        &registry,
+        &[
+            "--from",
+            "2026-03-04T12:30:15.25Z",
+            "--to",
+            "2026-03-06T00:00:00Z",
+            "-q",
+        ],
+    );
+    assert_eq!(
+        stdout(&output),
+        [
+            "2026-03-04T12:30:15.25Z\t1.1.1",
+            "2026-03-04T12:30:15.25Z\tlatest",
+            "2026-03-05T23:59:59Z\t1.2.0-rc.1",
+            "unknown date\tnightly",
+        ]
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn test_published_filters() {
+    let registry = registry();
+
+    let output = docker_tags(
+        &registry,
+        &[
+            "--from",
+            "2026-03-01",
+            "--version-like",
+            "-r",
+            "-n",
+            "2",
+            "-q",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        [
+            "2026-03-06T00:00:00Z\t1.2.0",
+            "2026-03-05T23:59:59Z\t1.2.0-rc.1"
+        ]
+    );
+
+    let output = docker_tags(&registry, &["--from", "2026-03-05", "-f", "^1\\.2", "-q"]);
+    assert_eq!(
+        stdout(&output),
+        [
+            "2026-03-05T23:59:59Z\t1.2.0-rc.1",
+            "2026-03-06T00:00:00Z\t1.2.0"
+        ]
+    );
+
+    // relative to now, long after the fixtures
+    let output = docker_tags(&registry, &["--from", "-48h", "-q"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(&output), ["unknown date\tnightly"]);
+}
+
+#[test]
+fn test_published_json() {
+    let registry = registry();
+
+    let output = docker_tags(
+        &registry,
+        &[
+            "--from",
+            "2026-03-03T00:00:00+01:00",
+            "--to",
+            "2026-03-03",
+            "-o",
+            "json",
+            "-q",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "from": "2026-03-02T23:00:00Z",
+            "to": "2026-03-04T00:00:00Z",
+            "tags": [
+                { "name": "1.0.0", "published": "2026-03-02T23:59:59.999Z" },
+                { "name": "1.1.0", "published": "2026-03-03T00:00:00Z" },
+            ],
+            "unknown": ["nightly"],
+        })
+    );
+}
+
+#[test]
+fn test_published_invalid_window() {
+    let registry = registry();
+
+    let output = docker_tags(&registry, &["--from", "2026-03-05", "--to", "2026-03-01"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let error = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        error.contains("--from 2026-03-05T00:00:00Z isn't before --to 2026-03-02T00:00:00Z"),
+        "{error}"
+    );
+
+    let output = docker_tags(&registry, &["--from", "yesterday"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    assert!(registry.requests().is_empty());
+}
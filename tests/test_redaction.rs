@@ -0,0 +1,63 @@
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{MockRegistry, Response};
+use docker_tags::{Client, CredentialProvider, Credentials, Image};
+
+const PASSWORD: &str = "vault-secret-pass";
+const REALM_SECRET: &str = "realm-secret-123";
+
+struct StaticProvider;
+
+#[async_trait]
+impl CredentialProvider for StaticProvider {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic("ci", PASSWORD)))
+    }
+}
+
+/// Registry whose token endpoint fails with `token_response`
+fn registry(token_response: Response) -> MockRegistry {
+    MockRegistry::start_with_base(move |base, req| match req.url.path() {
+        "/token" => token_response.clone(),
+        _ => Response::new(401).header(
+            "WWW-Authenticate",
+            format!(r#"Bearer realm="{base}/token?token={REALM_SECRET}",service="mock""#),
+        ),
+    })
+}
+
+async fn formatted_error(registry: &MockRegistry) -> String {
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(StaticProvider)
+        .build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let err = client.fetch_tags(&image).await.unwrap_err();
+    format!("{err} {err:#} {err:?}")
+}
+
+#[tokio::test]
+async fn test_auth_failure_errors_are_redacted() {
+    for response in [
+        Response::new(500),
+        Response::hang_up(),
+        Response::json(200, "not json"),
+    ] {
+        let registry = registry(response);
+        let message = formatted_error(&registry).await;
+        assert!(!message.contains(REALM_SECRET), "{message}");
+        assert!(!message.contains(PASSWORD), "{message}");
+
+        // The secret was still sent, only hidden from messages
+        let request = &registry.requests_to("/token")[0];
+        assert_eq!(request.query("token").as_deref(), Some(REALM_SECRET));
+    }
+}
+
+#[test]
+fn test_credentials_debug_is_redacted() {
+    let credentials = Credentials::basic("ci", PASSWORD);
+    assert!(!format!("{credentials:?}").contains(PASSWORD));
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::{MockRegistry, Response, paginate};
+use docker_tags::{Anonymous, Client, Image};
+use sha2::{Digest, Sha256};
+
+const MANIFEST: &str = include_str!("fixtures/ghcr_manifest.json");
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn client(registry: &MockRegistry) -> (Client, Image) {
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/org/app", registry.host()).as_str()).unwrap();
+    (client, image)
+}
+
+/// Small manifest standing in for a signature or attestation
+fn artifact(kind: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": digest(kind), "size": kind.len()},
+        "layers": [],
+    })
+    .to_string()
+}
+
+fn manifest_response(body: &str) -> Response {
+    Response::json(200, body)
+        .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+        .header("Docker-Content-Digest", digest(body))
+}
+
+#[tokio::test]
+async fn test_list_referrers_native() {
+    let registry = MockRegistry::start(|req| {
+        let referrers = format!("/v2/org/app/referrers/{}", digest(MANIFEST));
+        match req.url.path() {
+            "/v2/org/app/manifests/1.0" => manifest_response(MANIFEST),
+            path if path == referrers => Response::json(
+                200,
+                serde_json::json!({
+                    "schemaVersion": 2,
+                    "mediaType": "application/vnd.oci.image.index.v1+json",
+                    "manifests": [
+                        {
+                            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                            "artifactType": "application/vnd.dev.sigstore.bundle.v0.3+json",
+                            "digest": digest("bundle"),
+                            "size": 714,
+                            "annotations": {"dev.sigstore.bundle.predicateType": "https://sigstore.dev/cosign/sign/v1"}
+                        },
+                        {
+                            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                            "artifactType": "application/spdx+json",
+                            "digest": digest("sbom"),
+                            "size": 512
+                        }
+                    ]
+                })
+                .to_string(),
+            ),
+            _ => Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+        }
+    });
+    let (client, image) = client(&registry);
+
+    let digest = client.fetch_digest(&image, "1.0").await.unwrap();
+    let referrers = client.list_referrers(&image, &digest).await.unwrap();
+    let types: Vec<_> = referrers
+        .iter()
+        .map(|r| r.artifact_type.as_deref().unwrap())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "application/vnd.dev.sigstore.bundle.v0.3+json",
+            "application/spdx+json"
+        ]
+    );
+    assert_eq!(referrers[1].size, 512);
+    assert_eq!(
+        referrers[0].annotations["dev.sigstore.bundle.predicateType"],
+        "https://sigstore.dev/cosign/sign/v1"
+    );
+
+    let accept = registry.requests_to(&format!("/v2/org/app/referrers/{digest}"))[0]
+        .header("Accept")
+        .map(str::to_string);
+    assert_eq!(
+        accept.as_deref(),
+        Some("application/vnd.oci.image.index.v1+json")
+    );
+}
+
+#[tokio::test]
+async fn test_list_referrers_tag_fallback() {
+    let prefix = digest(MANIFEST).replace(':', "-");
+    let tags: Vec<String> = vec![
+        "1.0".to_string(),
+        format!("{prefix}.att"),
+        format!("{prefix}.sig"),
+        // attached to another image
+        format!("sha256-{:x}.sig", Sha256::digest("other")),
+    ];
+    let registry = {
+        let prefix = prefix.clone();
+        MockRegistry::start(move |req| {
+            let path = req.url.path();
+            if path == "/v2/org/app/tags/list" {
+                let page = paginate(&tags, req);
+                return Response::json(200, serde_json::json!({ "tags": page }).to_string());
+            }
+            match path.strip_prefix("/v2/org/app/manifests/") {
+                Some("1.0") => manifest_response(MANIFEST),
+                Some(tag) if tag == format!("{prefix}.sig") => manifest_response(&artifact("sig")),
+                Some(tag) if tag == format!("{prefix}.att") => manifest_response(&artifact("att")),
+                // the registry predates the referrers API
+                _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+            }
+        })
+    };
+    let (client, image) = client(&registry);
+
+    let digest = client.fetch_digest(&image, "1.0").await.unwrap();
+    let referrers = client.list_referrers(&image, &digest).await.unwrap();
+    let found: Vec<_> = referrers
+        .iter()
+        .map(|r| (r.artifact_type.as_deref().unwrap(), r.digest.clone()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (
+                "application/vnd.dsse.envelope.v1+json",
+                format!("sha256:{:x}", Sha256::digest(artifact("att")))
+            ),
+            (
+                "application/vnd.dev.cosign.artifact.sig.v1+json",
+                format!("sha256:{:x}", Sha256::digest(artifact("sig")))
+            ),
+        ]
+    );
+    assert_eq!(
+        registry
+            .requests_to(&format!("/v2/org/app/referrers/{digest}"))
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_list_referrers_invalid_digest() {
+    let registry = MockRegistry::start(|_| Response::new(500));
+    let (client, image) = client(&registry);
+
+    let err = client.list_referrers(&image, "latest").await.unwrap_err();
+    assert_eq!(err.to_string(), r#"Invalid digest: "latest""#);
+    assert!(registry.requests().is_empty());
+}
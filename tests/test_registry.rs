@@ -0,0 +1,189 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::MockRegistry;
+use docker_tags::{Client, CredentialProvider, Credentials, Image, Registry, Scope};
+
+/// Stands in for `~/.docker/config.json`, counting how often it's read
+struct CountingProvider(Arc<AtomicUsize>);
+
+#[async_trait]
+impl CredentialProvider for CountingProvider {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(Credentials::basic("user", "password")))
+    }
+}
+
+#[tokio::test]
+async fn test_registry_reuses_credentials_and_tokens() {
+    let mock = MockRegistry::with_repositories(
+        &[
+            ("team/app", &["1.0.0", "1.1.0"]),
+            ("team/lib", &["0.1.0"]),
+            ("tools/cli", &["2.0.0"]),
+        ],
+        true,
+    );
+    let reads = Arc::new(AtomicUsize::new(0));
+    let client = Client::builder()
+        .insecure_registry(mock.host())
+        .credential_provider(CountingProvider(reads.clone()))
+        .build();
+    let registry = Registry::connect(mock.host(), &client).unwrap();
+
+    let repositories = registry.list_repositories().await.unwrap();
+    assert_eq!(repositories, ["team/app", "team/lib", "tools/cli"]);
+    for name in &repositories {
+        assert!(!registry.fetch_tags(name).await.unwrap().is_empty());
+    }
+    let tags = registry.fetch_tags("team/app").await.unwrap();
+    assert_eq!(tags.len(), 2);
+
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+    let mut scopes: Vec<_> = mock
+        .requests_to("/token")
+        .iter()
+        .filter_map(|req| req.query("scope"))
+        .collect();
+    scopes.sort();
+    assert_eq!(
+        scopes,
+        [
+            "registry:catalog:*",
+            "repository:team/app:pull",
+            "repository:team/lib:pull",
+            "repository:tools/cli:pull",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_pre_authorize_mints_one_token() {
+    let repositories: &[(&str, &[&str])] = &[
+        ("team/app", &["1.0.0"]),
+        ("team/lib", &["0.1.0"]),
+        ("tools/cli", &["2.0.0"]),
+    ];
+    let scopes = [
+        Scope::pull("team/app"),
+        Scope::pull("team/lib"),
+        Scope::pull("tools/cli"),
+    ];
+    let mut round_trips = Vec::new();
+    for pre_authorize in [false, true] {
+        let mock = MockRegistry::with_repositories(repositories, true);
+        let client = Client::builder().insecure_registry(mock.host()).build();
+        let registry = Registry::connect(mock.host(), &client).unwrap();
+        if pre_authorize {
+            registry.pre_authorize(&scopes).await;
+        }
+        for (name, tags) in repositories {
+            assert_eq!(registry.fetch_tags(name).await.unwrap().len(), tags.len());
+        }
+
+        let tokens = mock.requests_to("/token");
+        if pre_authorize {
+            assert_eq!(tokens.len(), 1);
+            let requested: Vec<_> = tokens[0]
+                .url
+                .query_pairs()
+                .filter(|(key, _)| key == "scope")
+                .map(|(_, scope)| scope.into_owned())
+                .collect();
+            assert_eq!(
+                requested,
+                [
+                    "repository:team/app:pull",
+                    "repository:team/lib:pull",
+                    "repository:tools/cli:pull",
+                ]
+            );
+        }
+        round_trips.push(mock.requests().len());
+    }
+    // a challenge and a token per repository, against one of each up front
+    assert_eq!(round_trips, [9, 5]);
+}
+
+#[tokio::test]
+async fn test_client_pre_authorize() {
+    let repositories: &[(&str, &[&str])] = &[
+        ("team/app", &["1.0.0"]),
+        ("team/lib", &["0.1.0"]),
+        ("tools/cli", &["2.0.0"]),
+    ];
+    let mut round_trips = Vec::new();
+    for pre_authorize in [false, true] {
+        let mock = MockRegistry::with_repositories(repositories, true);
+        let client = Client::builder().insecure_registry(mock.host()).build();
+        if pre_authorize {
+            let scopes: Vec<_> = repositories
+                .iter()
+                .map(|(name, _)| Scope::pull(*name))
+                .collect();
+            assert!(client.pre_authorize(&mock.host(), &scopes).await);
+        }
+        for (name, tags) in repositories {
+            let image: Image = format!("{}/{name}", mock.host()).parse().unwrap();
+            assert_eq!(client.fetch_tags(&image).await.unwrap().len(), tags.len());
+        }
+        if pre_authorize {
+            assert_eq!(mock.requests_to("/token").len(), 1);
+        }
+        round_trips.push(mock.requests().len());
+    }
+    assert_eq!(round_trips, [9, 5]);
+}
+
+#[test]
+fn test_registry_rejects_invalid_names() {
+    let client = Client::new();
+    assert!(Registry::connect("evil.com:80@internal", &client).is_err());
+
+    let registry = Registry::connect("quay.io", &client).unwrap();
+    assert!(registry.repository("../other").is_err());
+    assert_eq!(
+        registry
+            .repository("prometheus/prometheus")
+            .unwrap()
+            .to_string(),
+        "quay.io/prometheus/prometheus"
+    );
+}
+
+#[tokio::test]
+async fn test_match_repositories() {
+    let mock = MockRegistry::with_repositories(
+        &[
+            ("team-a/app", &["1.0.0"]),
+            ("team-a/app/cache", &["1.0.0"]),
+            ("team-a/lib", &["0.1.0"]),
+            ("team-b/app", &["2.0.0"]),
+            ("team-b/sub/app", &["2.0.0"]),
+            ("tools/app", &["3.0.0"]),
+        ],
+        false,
+    );
+    let client = Client::builder().insecure_registry(mock.host()).build();
+    let registry = Registry::connect(mock.host(), &client).unwrap();
+
+    let cases: &[(&str, &[&str])] = &[
+        ("team-*/app", &["team-a/app", "team-b/app"]),
+        ("team-?/*", &["team-a/app", "team-a/lib", "team-b/app"]),
+        ("*/app", &["team-a/app", "team-b/app", "tools/app"]),
+        ("team-a/app/*", &["team-a/app/cache"]),
+        ("tools/app", &["tools/app"]),
+        ("team-c/*", &[]),
+    ];
+    for (pattern, expected) in cases {
+        let matched = registry.match_repositories(pattern).await.unwrap();
+        assert_eq!(matched, *expected, "{pattern}");
+    }
+}
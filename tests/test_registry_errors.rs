@@ -0,0 +1,129 @@
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, CredentialProvider, Credentials, Error, Image};
+
+async fn fetch_error(response: Response) -> anyhow::Error {
+    let registry = MockRegistry::start(move |_| response.clone());
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/group/app", registry.host()).as_str()).unwrap();
+    client.fetch_tags(&image).await.unwrap_err()
+}
+
+#[tokio::test]
+async fn test_ok_with_error_body() {
+    let err = fetch_error(Response::json(
+        200,
+        r#"{"errors":[{"code":"NAME_UNKNOWN","message":"repository name not known to registry"}]}"#,
+    ))
+    .await;
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::NotFound));
+    assert_eq!(err.to_string(), "Image not found");
+}
+
+#[tokio::test]
+async fn test_client_error_with_error_body() {
+    let err = fetch_error(Response::json(
+        403,
+        r#"{"errors":[{"code":"DENIED","message":"access forbidden"}]}"#,
+    ))
+    .await;
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::AccessDenied { repository, message, .. })
+            if repository == "group/app" && message == "access forbidden"
+    ));
+
+    let err = fetch_error(Response::json(
+        429,
+        r#"{"errors":[{"code":"TOOMANYREQUESTS","message":"slow down"}]}"#,
+    ))
+    .await;
+    assert_eq!(err.to_string(), "Registry error TOOMANYREQUESTS: slow down");
+}
+
+#[tokio::test]
+async fn test_errors_without_body() {
+    let err = fetch_error(Response::new(404)).await;
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::NotFound));
+
+    let err = fetch_error(Response::new(502)).await;
+    assert_eq!(err.to_string(), "502 Bad Gateway");
+}
+
+struct StoredLogin;
+
+#[async_trait]
+impl CredentialProvider for StoredLogin {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic("user", "ghp_token")))
+    }
+}
+
+/// Denies anonymous requests with 403 and, unless `deny_all`, accepts the
+/// token issued for stored credentials
+fn forbidding_registry(deny_all: bool) -> MockRegistry {
+    MockRegistry::start_with_base(move |base, req| match req.url.path() {
+        "/token" if req.header("Authorization").is_some() => {
+            Response::json(200, r#"{"token":"user-token"}"#)
+        }
+        "/token" => Response::json(200, r#"{"token":"anonymous-token"}"#),
+        "/v2/" => Response::json(401, "{}").header(
+            "WWW-Authenticate",
+            format!(r#"Bearer realm="{base}/token",service="mock""#),
+        ),
+        _ if !deny_all && req.header("Authorization") == Some("Bearer user-token") => {
+            Response::json(200, r#"{"name":"org/private","tags":["1.0.0"]}"#)
+        }
+        _ => Response::json(403, r#"{"errors":[{"code":"DENIED","message":"denied"}]}"#),
+    })
+}
+
+#[tokio::test]
+async fn test_forbidden_retries_with_stored_credentials() {
+    let registry = forbidding_registry(false);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(StoredLogin)
+        .build();
+    let image = Image::try_from(format!("{}/org/private", registry.host()).as_str()).unwrap();
+
+    let tags = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(tags.len(), 1);
+    let token = &registry.requests_to("/token")[0];
+    assert_eq!(token.query("scope").unwrap(), "repository:org/private:pull");
+}
+
+#[tokio::test]
+async fn test_forbidden_is_access_denied() {
+    let registry = forbidding_registry(true);
+    let image = Image::try_from(format!("{}/org/private", registry.host()).as_str()).unwrap();
+    for anonymous in [true, false] {
+        let builder = Client::builder().insecure_registry(registry.host());
+        let client = match anonymous {
+            true => builder.credential_provider(Anonymous).build(),
+            false => builder.credential_provider(StoredLogin).build(),
+        };
+
+        let err = client.fetch_tags(&image).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::AccessDenied {
+                registry: registry.host(),
+                repository: "org/private".to_string(),
+                message: "denied".to_string(),
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            format!("Access denied to {}/org/private: denied", registry.host())
+        );
+    }
+    // one re-authentication with the stored credentials, none without them
+    assert_eq!(registry.requests_to("/token").len(), 1);
+}
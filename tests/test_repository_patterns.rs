@@ -0,0 +1,202 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Response, paginate};
+
+/// Repositories of the catalog with their tags, besides 150 `bulk/app-N`
+/// ones spanning two pages
+const REPOSITORIES: &[(&str, &[&str])] = &[
+    ("team-a/app", &["1.0.0", "1.1.0", "latest"]),
+    ("team-a/app/cache", &["0.1.0"]),
+    ("team-a/lib", &["0.1.0"]),
+    ("team-b/app", &["2.0.0", "2.1.0-rc.1"]),
+    ("team-b/sub/app", &["3.0.0"]),
+    ("tools/cli", &["1.0.0"]),
+];
+
+/// Registry serving [`REPOSITORIES`] over HTTPS, with the catalog API unless
+/// `catalog` is false
+fn registry(catalog: bool) -> MockRegistry {
+    MockRegistry::start_tls(move |req| {
+        let path = req.url.path();
+        if path == "/v2/_catalog" {
+            if !catalog {
+                return Response::json(404, "404 page not found");
+            }
+            let mut names: Vec<_> = REPOSITORIES
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect();
+            names.extend((0..150).map(|i| format!("bulk/app-{i:03}")));
+            names.sort();
+            let page = paginate(&names, req);
+            return Response::json(200, serde_json::json!({ "repositories": page }).to_string());
+        }
+        let repository = path
+            .strip_prefix("/v2/")
+            .and_then(|path| path.strip_suffix("/tags/list"));
+        match REPOSITORIES
+            .iter()
+            .find(|(name, _)| Some(*name) == repository)
+        {
+            Some((_, tags)) => Response::json(200, serde_json::json!({ "tags": tags }).to_string()),
+            None => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+        }
+    })
+}
+
+/// Run the binary against `registry`, trusting its CA and without
+/// credentials, `{host}` in `args` standing for its host
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .args(
+            args.iter()
+                .map(|arg| arg.replace("{host}", &registry.host())),
+        )
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_pattern_listing() {
+    let registry = registry(true);
+    let host = registry.host();
+
+    let output = docker_tags(&registry, &["{host}/team-*/app"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{host}/team-a/app:\n1.1.0\n1.0.0\nlatest\n\n\
+             {host}/team-b/app:\n2.1.0-rc.1\n2.0.0\n"
+        )
+    );
+
+    // the latest version of each
+    let output = docker_tags(
+        &registry,
+        &["{host}/team-?/app", "--version-like", "-n", "1"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        format!("{host}/team-a/app:\n1.1.0\n\n{host}/team-b/app:\n2.1.0-rc.1\n")
+    );
+
+    let output = docker_tags(&registry, &["{host}/team-a/*", "-o", "json"]);
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["pattern"], format!("{host}/team-a/*"));
+    let images: Vec<_> = report["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|listing| (listing["image"].clone(), listing["shown"].clone()))
+        .collect();
+    assert_eq!(
+        images,
+        [
+            (format!("{host}/team-a/app").into(), 3.into()),
+            (format!("{host}/team-a/lib").into(), 1.into()),
+        ]
+    );
+    // each expansion reads the two pages of the catalog once
+    assert_eq!(registry.requests_to("/v2/_catalog").len(), 6);
+}
+
+#[test]
+fn test_pattern_stats() {
+    let registry = registry(true);
+    let host = registry.host();
+
+    let output = docker_tags(&registry, &["stats", "{host}/team-*/app"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = stdout(&output);
+    let groups: Vec<_> = stdout.split("\n\n").collect();
+    assert_eq!(groups.len(), 2, "{stdout}");
+    assert!(
+        groups[0].starts_with(&format!("{host}/team-a/app:\ntags: 3\n")),
+        "{stdout}"
+    );
+    assert!(
+        groups[1].starts_with(&format!("{host}/team-b/app:\ntags: 2\n")),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_pattern_limits() {
+    let registry = registry(true);
+    let host = registry.host();
+
+    let output = docker_tags(&registry, &["{host}/bulk/*", "--max-matches", "100"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        stdout(&output).contains(&format!(
+            "{host}/bulk/* matches 150 repositories, more than --max-matches 100"
+        )),
+        "{output:?}"
+    );
+    // nothing is listed past the catalog
+    assert!(
+        registry
+            .requests()
+            .iter()
+            .all(|req| !req.url.path().ends_with("/tags/list"))
+    );
+
+    let output = docker_tags(&registry, &["{host}/team-c/*"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        stdout(&output).contains(&format!("No repository of {host} matches team-c/*")),
+        "{output:?}"
+    );
+
+    let output = docker_tags(&registry, &["{host}/team-*/app", "--watch", "60"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        stdout(&output).contains("An image pattern doesn't go with --watch"),
+        "{output:?}"
+    );
+}
+
+#[test]
+fn test_pattern_without_catalog() {
+    let registry = registry(false);
+    let host = registry.host();
+
+    let output = docker_tags(&registry, &["{host}/team-*/app"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let error = stdout(&output);
+    assert!(
+        error.contains(&format!(
+            "Failed to expand {host}/team-*/app from the catalog"
+        )),
+        "{error}"
+    );
+    let causes = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        causes.contains(&format!("{host} doesn't support the catalog API")),
+        "{causes}"
+    );
+
+    let output = docker_tags(&registry, &["team-*/app"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        stdout(&output).contains("Docker Hub doesn't offer the catalog API"),
+        "{output:?}"
+    );
+}
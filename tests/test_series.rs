@@ -0,0 +1,64 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Response};
+
+fn postgres() -> MockRegistry {
+    MockRegistry::start_tls(|req| match req.url.path() {
+        "/v2/library/postgres/tags/list" => {
+            let tags: Vec<_> = include_str!("fixtures/postgres_tags.txt").lines().collect();
+            Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+        }
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .arg("series")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/library/postgres", registry.host()))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_series_command() {
+    let registry = postgres();
+    assert_eq!(stdout(&docker_tags(&registry, &[])), "17\n16\n15\n14\n13\n");
+    assert_eq!(
+        stdout(&docker_tags(
+            &registry,
+            &["--level", "minor", "-f", "^1[45]"]
+        )),
+        "15.8\n15.7\n14.13\n14.12\n"
+    );
+    // `13.16-alpine` is the only tag of 13 with a variant, which is ignored
+    assert_eq!(
+        stdout(&docker_tags(&registry, &["-f", "alpine"])),
+        "17\n16\n15\n13\n"
+    );
+    // `17rc1` and `17-rc1` are pre-releases
+    assert_eq!(stdout(&docker_tags(&registry, &["-f", "rc"])), "17\n");
+    assert_eq!(
+        stdout(&docker_tags(&registry, &["-f", "rc", "--stable"])),
+        ""
+    );
+
+    let series: Vec<String> =
+        serde_json::from_str(&stdout(&docker_tags(&registry, &["-o", "json"]))).unwrap();
+    assert_eq!(series, ["17", "16", "15", "14", "13"]);
+}
@@ -0,0 +1,197 @@
+#![cfg(feature = "serve")]
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    net::TcpStream,
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use common::{MockRegistry, Request, Response};
+
+/// `docker-tags serve` on a random port, killed when dropped
+struct Daemon {
+    child: Child,
+    url: String,
+    _state: Option<tempfile::TempDir>,
+}
+
+impl Daemon {
+    fn start(registry: &MockRegistry, args: &[&str]) -> Self {
+        let state = tempfile::tempdir().unwrap();
+        let mut daemon = Daemon::start_in(state.path(), registry, args);
+        daemon._state = Some(state);
+        daemon
+    }
+
+    /// With its state, such as the listings on disk, kept in `state`
+    fn start_in(state: &Path, registry: &MockRegistry, args: &[&str]) -> Self {
+        let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", state)
+            .env("XDG_STATE_HOME", state)
+            .args(["serve", "--listen", "127.0.0.1:0", "--ca-cert"])
+            .arg(format!("{}={ca}", registry.host()))
+            .args(args)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stderr.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let url = line
+            .trim()
+            .strip_prefix("Listening on ")
+            .unwrap_or_else(|| panic!("{line:?}"))
+            .to_string();
+        Daemon {
+            child,
+            url,
+            _state: None,
+        }
+    }
+
+    async fn get(&self, path: &str) -> (u16, serde_json::Value) {
+        let resp = reqwest::get(format!("{}{path}", self.url)).await.unwrap();
+        let status = resp.status().as_u16();
+        (status, resp.json().await.unwrap())
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(200, r#"{"tags":["1.0.0","latest","1.1.0"]}"#),
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+fn names(listing: &serde_json::Value) -> Vec<&str> {
+    listing["tags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| tag["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_serve_tags() {
+    let registry = registry();
+    let daemon = Daemon::start(&registry, &[]);
+    let image = format!("{}/app", registry.host());
+
+    let (status, health) = daemon.get("/healthz").await;
+    assert_eq!((status, health["status"].as_str()), (200, Some("ok")));
+
+    let (status, listing) = daemon.get(&format!("/v1/tags?image={image}")).await;
+    assert_eq!(status, 200, "{listing}");
+    assert_eq!(listing["image"], image);
+    assert_eq!(names(&listing), ["1.1.0", "1.0.0", "latest"]);
+
+    let (_, listing) = daemon
+        .get(&format!(
+            "/v1/tags?image={image}&filter=%5E1%5C.&sort=version&reverse=true"
+        ))
+        .await;
+    assert_eq!(names(&listing), ["1.0.0", "1.1.0"]);
+    assert_eq!(
+        (listing["total"].as_u64(), listing["matched"].as_u64()),
+        (Some(3), Some(2))
+    );
+    let (_, listing) = daemon
+        .get(&format!("/v1/tags?image={image}&sort=registry&limit=2"))
+        .await;
+    assert_eq!(names(&listing), ["1.0.0", "latest"]);
+
+    // the listing was fetched once and reused
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 1);
+}
+
+#[tokio::test]
+async fn test_serve_ttl() {
+    let registry = registry();
+    let daemon = Daemon::start(&registry, &["--ttl", "0"]);
+    let path = format!("/v1/tags?image={}/app", registry.host());
+    daemon.get(&path).await;
+    daemon.get(&path).await;
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 2);
+}
+
+#[tokio::test]
+async fn test_serve_disk_cache() {
+    let registry = registry();
+    let state = tempfile::tempdir().unwrap();
+    let path = format!("/v1/tags?image={}/app", registry.host());
+    let daemon = Daemon::start_in(state.path(), &registry, &[]);
+    let (_, listing) = daemon.get(&path).await;
+    drop(daemon);
+
+    // a restarted daemon reuses the listing within the TTL
+    let daemon = Daemon::start_in(state.path(), &registry, &[]);
+    assert_eq!(daemon.get(&path).await.1, listing);
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 1);
+    drop(daemon);
+
+    let daemon = Daemon::start_in(state.path(), &registry, &["--no-cache"]);
+    assert_eq!(daemon.get(&path).await.1, listing);
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 2);
+    drop(daemon);
+
+    let daemon = Daemon::start_in(state.path(), &registry, &["--ttl", "0"]);
+    daemon.get(&path).await;
+    assert_eq!(registry.requests_to("/v2/app/tags/list").len(), 3);
+}
+
+#[tokio::test]
+async fn test_serve_errors() {
+    let registry = registry();
+    let daemon = Daemon::start(&registry, &["--allow-registry", &registry.host()]);
+
+    let (status, report) = daemon.get("/v1/tags").await;
+    assert_eq!(status, 400);
+    assert_eq!(report["error"], "Missing the image parameter");
+    let path = format!("/v1/tags?image={}/app&sort=date", registry.host());
+    assert_eq!(daemon.get(&path).await.0, 400);
+
+    let path = format!("/v1/tags?image={}/missing", registry.host());
+    assert_eq!(daemon.get(&path).await.0, 404);
+    let (status, report) = daemon.get("/v1/tags?image=quay.io/org/app").await;
+    assert_eq!(status, 403);
+    assert!(
+        report
+            .to_string()
+            .contains("quay.io is not an allowed host"),
+        "{report}"
+    );
+    assert_eq!(daemon.get("/v2/").await.0, 404);
+}
+
+#[test]
+fn test_serve_oversized_head() {
+    let registry = registry();
+    let daemon = Daemon::start(&registry, &[]);
+    let mut stream = TcpStream::connect(daemon.url.trim_start_matches("http://")).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    // a request line that never ends, kept open
+    let line = format!("GET /healthz?{} ", "a".repeat(16 * 1024));
+    let _ = stream.write_all(line.as_bytes());
+
+    let mut reply = Vec::new();
+    match stream.read_to_end(&mut reply) {
+        Ok(_) => assert!(reply.is_empty(), "{}", String::from_utf8_lossy(&reply)),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::ConnectionReset),
+    }
+}
@@ -0,0 +1,209 @@
+mod common;
+
+#[cfg(feature = "cli")]
+use std::process::{Command, Output};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::{MockRegistry, Response, TEST_CA, paginate};
+use docker_tags::{Client, Image, ServerNotice};
+
+/// Registry listing 150 tags of `org/app` over HTTPS in two pages, each
+/// sent with `headers`
+fn registry(headers: &'static [(&'static str, &'static str)]) -> MockRegistry {
+    let tags: Vec<_> = (0..150).map(|i| format!("1.0.{i:03}")).collect();
+    MockRegistry::start_tls(move |req| {
+        if req.url.path() != "/v2/org/app/tags/list" {
+            return Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#);
+        }
+        let page = paginate(&tags, req);
+        let mut resp = Response::json(200, serde_json::json!({ "tags": page }).to_string());
+        for (name, value) in headers {
+            resp = resp.header(name, *value);
+        }
+        resp
+    })
+}
+
+const HEADERS: &[(&str, &str)] = &[
+    ("Deprecation", "@1767225600"),
+    ("Sunset", "Wed, 30 Sep 2026 00:00:00 GMT"),
+    (
+        "Warning",
+        r#"299 - "Moving to harbor.new", 199 old.corp "Read-only" "Thu, 01 Jan 2026 00:00:00 GMT""#,
+    ),
+];
+
+fn at(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+async fn notices(registry: &MockRegistry) -> Vec<ServerNotice> {
+    let client = Client::builder()
+        .root_certificate(registry.host(), TEST_CA)
+        .unwrap()
+        .build();
+    let image = Image::try_from(format!("{}/org/app", registry.host()).as_str()).unwrap();
+    let (tags, outcome) = client.fetch_tags_partial(&image).await.unwrap();
+    assert_eq!(tags.len(), 150);
+    assert_eq!(outcome.pages, 2);
+    outcome.notices
+}
+
+#[tokio::test]
+async fn test_notices() {
+    let registry = registry(HEADERS);
+    // both pages announce the same, which is told once
+    assert_eq!(
+        notices(&registry).await,
+        [
+            ServerNotice::Deprecation {
+                since: Some(at(1767225600))
+            },
+            ServerNotice::Sunset { at: at(1790726400) },
+            ServerNotice::Warning {
+                code: 299,
+                agent: "-".to_string(),
+                text: "Moving to harbor.new".to_string(),
+                date: None,
+            },
+            ServerNotice::Warning {
+                code: 199,
+                agent: "old.corp".to_string(),
+                text: "Read-only".to_string(),
+                date: Some(at(1767225600)),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_without_notices() {
+    let registry = registry(&[]);
+    assert_eq!(notices(&registry).await, []);
+}
+
+#[tokio::test]
+async fn test_malformed_notices() {
+    // the listing goes on, without what couldn't be parsed
+    let registry = registry(&[
+        ("Deprecation", "soon"),
+        ("Sunset", "2026-09-30"),
+        ("Warning", r#"299 - "Moving to harbor.new", oops"#),
+        ("Warning", "unquoted text"),
+    ]);
+    assert_eq!(
+        notices(&registry).await,
+        [ServerNotice::Warning {
+            code: 299,
+            agent: "-".to_string(),
+            text: "Moving to harbor.new".to_string(),
+            date: None,
+        }]
+    );
+}
+
+#[cfg(feature = "cli")]
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_cli_notices() {
+    let registry = registry(HEADERS);
+    let host = registry.host();
+    let image = format!("{host}/org/app");
+
+    let output = docker_tags(&registry, &[&image, "-n", "1"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.0.149\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "Warning: {host}: the registry is deprecated since 2026-01-01T00:00:00Z\n\
+             Warning: {host}: the registry is going away at 2026-09-30T00:00:00Z\n\
+             Warning: {host}: the registry warns (299): Moving to harbor.new\n\
+             Warning: {host}: old.corp warns (199): Read-only\n"
+        )
+    );
+
+    let output = docker_tags(&registry, &[&image, "-n", "1", "--quiet"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+
+    let output = docker_tags(&registry, &[&image, "-n", "1", "-o", "json"]);
+    assert!(output.status.success(), "{output:?}");
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        listing["notices"],
+        serde_json::json!([
+            {
+                "kind": "deprecation",
+                "message": "the registry is deprecated since 2026-01-01T00:00:00Z",
+                "at": "2026-01-01T00:00:00Z",
+            },
+            {
+                "kind": "sunset",
+                "message": "the registry is going away at 2026-09-30T00:00:00Z",
+                "at": "2026-09-30T00:00:00Z",
+            },
+            {
+                "kind": "warning",
+                "message": "the registry warns (299): Moving to harbor.new",
+                "code": 299,
+                "agent": "-",
+            },
+            {
+                "kind": "warning",
+                "message": "old.corp warns (199): Read-only",
+                "at": "2026-01-01T00:00:00Z",
+                "code": 199,
+                "agent": "old.corp",
+            },
+        ])
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_cli_without_notices() {
+    let registry = registry(&[]);
+    let image = format!("{}/org/app", registry.host());
+
+    let output = docker_tags(&registry, &[&image, "-n", "1", "-o", "json"]);
+    assert!(output.status.success(), "{output:?}");
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(listing.get("notices").is_none(), "{listing}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_batch_notices_once() {
+    let registry = registry(HEADERS);
+    let host = registry.host();
+    let list = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        list.path(),
+        format!("{host}/org/app\n{host}/org/app:1.0.0\n"),
+    )
+    .unwrap();
+
+    let output = docker_tags(&registry, &["batch", list.path().to_str().unwrap()]);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.matches("the registry is going away").count(),
+        1,
+        "{stderr}"
+    );
+}
@@ -0,0 +1,215 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+use common::{MockRegistry, Request, Response};
+
+/// Registry with tags for `app` and `tools`
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(200, r#"{"tags":["1.0.0","1.1.0","2.0.0"]}"#),
+        "/v2/tools/tags/list" => Response::json(200, r#"{"tags":["2.0"]}"#),
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+/// Run the binary with `args` and its config in `home`, returning the exit
+/// code, stdout and stderr
+fn docker_tags(registry: &MockRegistry, home: &Path, args: &[&str]) -> (i32, String, String) {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", home)
+        .env("XDG_STATE_HOME", home)
+        .env("XDG_CONFIG_HOME", home)
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Write the config file of `home` with the set `platform` of `images`
+fn write_config(home: &Path, images: &[String]) {
+    let images: Vec<_> = images.iter().map(|image| format!("{image:?}")).collect();
+    let dir = home.join("docker-tags");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("config.toml"),
+        format!(
+            "# checked every morning\n[sets.platform]\nimages = [{}]\n",
+            images.join(", ")
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_set() {
+    let registry = registry();
+    let host = registry.host();
+    let home = tempfile::tempdir().unwrap();
+    write_config(
+        home.path(),
+        &[format!("{host}/app"), format!("{host}/tools")],
+    );
+
+    let (code, stdout, stderr) = docker_tags(
+        &registry,
+        home.path(),
+        &["--set", "platform", "-n", "2", "--no-summary"],
+    );
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(
+        stdout,
+        format!("{host}/app:2.0.0\n{host}/app:1.1.0\n{host}/tools:2.0\n")
+    );
+
+    let (code, stdout, stderr) = docker_tags(
+        &registry,
+        home.path(),
+        &["--set", "platform", "-f", r"^1\.", "-o", "json"],
+    );
+    assert_eq!(code, 0, "{stderr}");
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["images"][0]["image"], format!("{host}/app"));
+    assert_eq!(
+        report["images"][0]["tags"],
+        serde_json::json!([{"name": "1.1.0"}, {"name": "1.0.0"}])
+    );
+    assert_eq!(report["summary"][1]["tags"], 0);
+
+    let (code, stdout, _) =
+        docker_tags(&registry, home.path(), &["--set", "platform", "--digests"]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "Error: --set doesn't go with --digests\n");
+}
+
+#[test]
+fn test_set_fail_on() {
+    let registry = registry();
+    let host = registry.host();
+    let home = tempfile::tempdir().unwrap();
+    write_config(
+        home.path(),
+        &[format!("{host}/app:1.1.0"), format!("{host}/tools")],
+    );
+
+    let (code, _, stderr) = docker_tags(
+        &registry,
+        home.path(),
+        &["--set", "platform", "--fail-on", "outdated:major", "-q"],
+    );
+    assert_eq!(code, 7, "{stderr}");
+    assert!(
+        stderr.contains(&format!(
+            "Failed --fail-on outdated:major: {host}/app:1.1.0: 2.0.0 is 1 major version ahead of 1.1.0"
+        )),
+        "{stderr}"
+    );
+
+    let (code, _, stderr) = docker_tags(
+        &registry,
+        home.path(),
+        &["--set", "platform", "-f", "^1", "--fail-on", "no-match"],
+    );
+    assert_eq!(code, 8, "{stderr}");
+    assert!(
+        stderr.contains(&format!("{host}/tools: no tag matched")),
+        "{stderr}"
+    );
+
+    // failures to list come first
+    write_config(home.path(), &[format!("{host}/missing")]);
+    let (code, _, stderr) = docker_tags(
+        &registry,
+        home.path(),
+        &["--set", "platform", "--fail-on", "no-match"],
+    );
+    assert_eq!(code, 4, "{stderr}");
+}
+
+#[test]
+fn test_unknown_set() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    write_config(home.path(), &[]);
+
+    let (code, stdout, _) = docker_tags(&registry, home.path(), &["--set", "web"]);
+    assert_eq!(code, 1);
+    let path = home.path().join("docker-tags").join("config.toml");
+    assert_eq!(
+        stdout,
+        format!("Error: No set named \"web\" in {path:?}; there are platform\n")
+    );
+    assert!(registry.requests().is_empty());
+
+    let (code, _, stderr) = docker_tags(&registry, home.path(), &["sets", "show", "web"]);
+    assert_eq!(code, 1, "{stderr}");
+}
+
+#[test]
+fn test_sets_commands() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    write_config(home.path(), &["nginx".to_string()]);
+    let path = home.path().join("docker-tags").join("config.toml");
+    let sets = |args: &[&str]| {
+        let (code, stdout, stderr) =
+            docker_tags(&registry, home.path(), &[&["sets"], args].concat());
+        assert_eq!(code, 0, "{args:?}: {stderr}");
+        (stdout, stderr)
+    };
+
+    let (_, stderr) = sets(&["add", "platform", "redis", "nginx", "ghcr.io/org/app"]);
+    assert_eq!(
+        stderr,
+        format!("Added 2 images to the set platform in {}\n", path.display())
+    );
+    sets(&["add", "db", "postgres:16.4"]);
+    assert_eq!(sets(&["list"]).0, "db\nplatform\n");
+    assert_eq!(
+        sets(&["show", "platform"]).0,
+        "nginx\nredis\nghcr.io/org/app\n"
+    );
+    assert_eq!(
+        sets(&["list", "-o", "yaml"]).0,
+        "sets:\n  db:\n    images:\n    - postgres:16.4\n  platform:\n    images:\n    - nginx\n    - redis\n    - ghcr.io/org/app\n"
+    );
+    let (report, _) = sets(&["show", "db", "-o", "json"]);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&report).unwrap(),
+        serde_json::json!({"sets": {"db": {"images": ["postgres:16.4"]}}})
+    );
+
+    sets(&["remove", "platform", "redis"]);
+    assert_eq!(sets(&["show", "platform"]).0, "nginx\nghcr.io/org/app\n");
+    sets(&["remove", "db"]);
+    assert_eq!(sets(&["list"]).0, "platform\n");
+    // only the set's table was rewritten
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# checked every morning\n[sets.platform]\nimages = [\n    \"nginx\",\n    \"ghcr.io/org/app\",\n]\n"
+    );
+
+    let (code, _, stderr) = docker_tags(
+        &registry,
+        home.path(),
+        &["sets", "remove", "platform", "redis"],
+    );
+    assert_eq!(code, 1, "{stderr}");
+    let (code, _, stderr) = docker_tags(&registry, home.path(), &["sets", "add", "a.b", "x"]);
+    assert_eq!(code, 2, "{stderr}");
+
+    // another file, created with its directory
+    let other = home.path().join("elsewhere").join("sets.toml");
+    let other = other.to_str().unwrap();
+    sets(&["add", "web", "nginx", "--config", other]);
+    assert_eq!(sets(&["list", "--config", other]).0, "web\n");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{MockRegistry, Response, paginate};
+use docker_tags::{Anonymous, Client, Image, SignatureLookup, Tag};
+use sha2::{Digest, Sha256};
+
+fn manifest(version: &str) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": digest(version), "size": 2},
+        "layers": [],
+    })
+    .to_string()
+}
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// `1.0.0` is signed by tag, `2.0.0` by referrer, `3.0.0` (aliased as `stable`) isn't
+fn registry() -> MockRegistry {
+    let signature_tag = format!("{}.sig", digest(&manifest("1.0.0")).replace(':', "-"));
+    let tags = vec![
+        "1.0.0".to_string(),
+        "2.0.0".to_string(),
+        "3.0.0".to_string(),
+        "stable".to_string(),
+        signature_tag,
+    ];
+    MockRegistry::start(move |req| {
+        let path = req.url.path();
+        if path == "/v2/app/tags/list" {
+            let page = paginate(&tags, req);
+            return Response::json(200, serde_json::json!({ "tags": page }).to_string());
+        }
+        if let Some(reference) = path.strip_prefix("/v2/app/manifests/") {
+            let body = match reference {
+                "stable" => manifest("3.0.0"),
+                version => manifest(version),
+            };
+            return Response::json(200, body.clone())
+                .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+                .header("Docker-Content-Digest", digest(&body));
+        }
+        let manifests = match path.strip_prefix("/v2/app/referrers/") {
+            Some(d) if d == digest(&manifest("2.0.0")) => serde_json::json!([{
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "artifactType": "application/vnd.dev.sigstore.bundle.v0.3+json",
+                "digest": digest("bundle"),
+                "size": 700
+            }]),
+            Some(_) => serde_json::json!([{
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "artifactType": "application/spdx+json",
+                "digest": digest("sbom"),
+                "size": 300
+            }]),
+            None => return Response::new(404),
+        };
+        Response::json(
+            200,
+            serde_json::json!({ "schemaVersion": 2, "manifests": manifests }).to_string(),
+        )
+    })
+}
+
+#[tokio::test]
+async fn test_signature_lookup() {
+    let registry = registry();
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+    let tags = client.fetch_tags(&image).await.unwrap();
+    let mut lookup = SignatureLookup::new(&client, &image, &tags);
+
+    let mut signed = Vec::new();
+    for name in ["1.0.0", "2.0.0", "3.0.0", "stable"] {
+        signed.push(lookup.is_signed(&Tag::new(name)).await.unwrap());
+    }
+    assert_eq!(signed, [true, true, false, false]);
+
+    // the signature tag is enough for 1.0.0, and `stable` reuses 3.0.0's result
+    let referrers = |version| {
+        let path = format!("/v2/app/referrers/{}", digest(&manifest(version)));
+        registry.requests_to(&path).len()
+    };
+    assert_eq!(
+        (referrers("1.0.0"), referrers("2.0.0"), referrers("3.0.0")),
+        (0, 1, 1)
+    );
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+use common::{MockRegistry, Request, Response};
+use sha2::{Digest, Sha256};
+
+const OLD: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/snapshots/old.json"
+);
+const NEW: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/snapshots/new.json"
+);
+
+fn digest(tag: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(tag))
+}
+
+/// Tags of `app`, each of whose manifests is only its name
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req: &Request| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(200, r#"{"tags":["1.0.0","latest","1.1.0"]}"#),
+        path => match path.strip_prefix("/v2/app/manifests/") {
+            Some(tag) => Response::json(200, tag)
+                .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+                .header("Docker-Content-Digest", digest(tag)),
+            None => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+        },
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, home: &Path, args: &[&str]) -> std::process::Output {
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", home)
+        .env("XDG_STATE_HOME", home)
+        .args(args)
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    let image = format!("{}/app", registry.host());
+    let path = home.path().join("app.json");
+    let out = path.to_str().unwrap();
+
+    let output = docker_tags(
+        &registry,
+        home.path(),
+        &["snapshot", &image, "--digests", "--out", out],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+    let snapshot: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(snapshot["version"], 1);
+    assert_eq!(snapshot["image"], image);
+    assert!(snapshot["fetched_at"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(
+        snapshot["tags"],
+        serde_json::json!([
+            {"name": "1.1.0", "digest": digest("1.1.0")},
+            {"name": "1.0.0", "digest": digest("1.0.0")},
+            {"name": "latest", "digest": digest("latest")},
+        ])
+    );
+
+    // a snapshot is the same as itself
+    let output = docker_tags(&registry, home.path(), &["diff-snapshots", out, out]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    // without digests, only the names are recorded and nothing is resolved
+    let output = docker_tags(&registry, home.path(), &["snapshot", &image]);
+    assert!(output.status.success(), "{output:?}");
+    let snapshot: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(snapshot["tags"][0], serde_json::json!({"name": "1.1.0"}));
+    assert_eq!(registry.requests_to("/v2/app/manifests/1.1.0").len(), 1);
+}
+
+#[test]
+fn test_diff_snapshots() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+
+    let output = docker_tags(&registry, home.path(), &["diff-snapshots", OLD, NEW]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        [
+            format!("added\t3.22.0\tsha256:22{:062}\n", 0),
+            format!("removed\t3.19.4\tsha256:194{:061}\n", 0),
+            format!("changed\t3.21.0\tsha256:21a{0:061}\tsha256:21b{0:061}\n", 0),
+            format!("changed\tlatest\tsha256:21a{0:061}\tsha256:22{0:062}\n", 0),
+        ]
+        .concat()
+    );
+
+    let output = docker_tags(
+        &registry,
+        home.path(),
+        &["diff-snapshots", NEW, OLD, "-o", "json"],
+    );
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(diff["added"][0]["name"], "3.19.4");
+    assert_eq!(diff["removed"][0]["name"], "3.22.0");
+    assert_eq!(diff["changed"][0]["new"], format!("sha256:21a{:061}", 0));
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn test_newer_snapshot_version() {
+    let registry = registry();
+    let home = tempfile::tempdir().unwrap();
+    let path = home.path().join("future.json");
+    fs::write(
+        &path,
+        fs::read_to_string(NEW)
+            .unwrap()
+            .replace(r#""version": 1"#, r#""version": 2"#),
+    )
+    .unwrap();
+
+    let output = docker_tags(
+        &registry,
+        home.path(),
+        &["diff-snapshots", OLD, path.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let (stdout, stderr) = (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    );
+    assert_eq!(stdout, format!("Error: Invalid snapshot {path:?}\n"));
+    assert!(stderr.contains("version 2 is newer"), "{stderr}");
+}
@@ -0,0 +1,121 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+const TAGS: &str = "\
+1.27.2
+1.27.2-alpine
+1.28.0-rc1
+1.26.3
+
+latest
+1.25.5
+1.27.2
+";
+
+fn sort(args: &[&str]) -> Output {
+    // a broken Docker config shows that it's never read
+    let config = tempfile::tempdir().unwrap();
+    std::fs::write(config.path().join("config.json"), "{").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .args(["sort", "-"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(TAGS.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_sort_filters() {
+    assert_eq!(
+        stdout(&sort(&["--dedup", "-q"])),
+        "1.28.0-rc1\n1.27.2\n1.27.2-alpine\n1.26.3\n1.25.5\nlatest\n"
+    );
+    assert_eq!(
+        stdout(&sort(&[
+            "--dedup",
+            "-q",
+            "--stable",
+            "--exclude",
+            "-alpine$"
+        ])),
+        "1.27.2\n1.26.3\n1.25.5\nlatest\n"
+    );
+    assert_eq!(
+        stdout(&sort(&[
+            "--dedup",
+            "-q",
+            "--constraint",
+            ">=1.26 <1.28",
+            "-n",
+            "2"
+        ])),
+        "1.28.0-rc1\n1.27.2\n"
+    );
+    assert_eq!(
+        stdout(&sort(&[
+            "--dedup",
+            "-q",
+            "--constraint",
+            ">=1.26 <1.28",
+            "--stable",
+            "-n",
+            "2"
+        ])),
+        "1.27.2\n1.27.2-alpine\n"
+    );
+    assert_eq!(
+        stdout(&sort(&[
+            "--exclude",
+            "ALPINE",
+            "--ignore-case",
+            "-f",
+            "^1.27"
+        ])),
+        "1.27.2\n1.27.2\n"
+    );
+}
+
+#[test]
+fn test_sort_document() {
+    let output = sort(&["--dedup", "--stable", "-r", "-o", "yaml"]);
+    assert_eq!(
+        stdout(&output),
+        "\
+tags:
+- name: latest
+- name: 1.25.5
+- name: 1.26.3
+- name: 1.27.2-alpine
+- name: 1.27.2
+warnings:
+- skipped duplicate tag 1.27.2
+complete: true
+total: 6
+matched: 5
+shown: 5
+"
+    );
+
+    let output = sort(&["--constraint", "1.2.3.4"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::{MockRegistry, Request, Response};
+use docker_tags::{Anonymous, Client, Image, ImageSource, Tag};
+use sha2::{Digest, Sha256};
+
+const INDEX: &str = include_str!("fixtures/docker_hub_index.json");
+/// Config labelled with its source but not its revision
+const CONFIG: &str = include_str!("fixtures/config.json");
+const BARE_CONFIG: &str = r#"{"architecture":"amd64","os":"linux","config":{}}"#;
+
+const AMD64: &str = "sha256:1c4eef651f65e2f7daee7ee785882ac164b02b78fb74503052a26dc061c90474";
+const REVISION: &str = "4f2b1c9d8e7a6b5c4d3e2f1a0b9c8d7e6f5a4b3c";
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn manifest(config: &str, annotations: serde_json::Value) -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(config),
+            "size": config.len(),
+        },
+        "layers": [],
+        "annotations": annotations,
+    })
+    .to_string()
+}
+
+/// Manifest of a tag annotated with both its source and revision
+fn annotated() -> String {
+    manifest(
+        BARE_CONFIG,
+        serde_json::json!({
+            "org.opencontainers.image.source": "https://github.com/org/app.git",
+            "org.opencontainers.image.revision": REVISION,
+        }),
+    )
+}
+
+/// Manifest annotated with its revision only, the source being a label of
+/// [`CONFIG`]
+fn labelled() -> String {
+    manifest(
+        CONFIG,
+        serde_json::json!({ "org.opencontainers.image.revision": REVISION }),
+    )
+}
+
+/// The index fixture, annotated by Docker Hub's builders
+fn index() -> String {
+    INDEX.replace(AMD64, &digest(&labelled()))
+}
+
+/// Tags `annotated`, `labelled` and its alias `alias`, `index`, `bare`
+/// without any annotation, and `missing`
+fn respond(req: &Request) -> Response {
+    let path = req.url.path();
+    let serve = |content_type, body: String| {
+        Response::json(200, body.clone())
+            .header("Content-Type", content_type)
+            .header("Docker-Content-Digest", digest(&body))
+    };
+    let oci = "application/vnd.oci.image.manifest.v1+json";
+    let blob = |body: &str| {
+        Response::json(200, body.to_string()).header("Content-Type", "application/octet-stream")
+    };
+    match path.strip_prefix("/v2/org/app/") {
+        Some("tags/list") => Response::json(
+            200,
+            r#"{"tags":["alias","annotated","bare","index","labelled","missing"]}"#,
+        ),
+        Some("manifests/annotated") => serve(oci, annotated()),
+        Some("manifests/bare") => serve(oci, manifest(BARE_CONFIG, serde_json::json!({}))),
+        Some("manifests/labelled" | "manifests/alias") => serve(oci, labelled()),
+        Some("manifests/index") => serve("application/vnd.oci.image.index.v1+json", index()),
+        Some(other) if other == format!("blobs/{}", digest(CONFIG)) => blob(CONFIG),
+        Some(other) if other == format!("blobs/{}", digest(BARE_CONFIG)) => blob(BARE_CONFIG),
+        _ => Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#),
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_sources() {
+    let registry = MockRegistry::start(respond);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::new(registry.host(), "org/app");
+    let tags: Vec<_> = ["annotated", "labelled", "alias", "index", "bare", "missing"]
+        .into_iter()
+        .map(Tag::new)
+        .collect();
+
+    let (sources, warnings) = client.fetch_sources(&image, &tags).await;
+    let shown: Vec<_> = sources
+        .iter()
+        .map(|source| source.as_ref().map(ImageSource::to_string))
+        .collect();
+    assert_eq!(
+        shown,
+        [
+            Some("github.com/org/app@4f2b1c9".to_string()),
+            Some("github.com/prometheus/prometheus@4f2b1c9".to_string()),
+            Some("github.com/prometheus/prometheus@4f2b1c9".to_string()),
+            Some("github.com/alpinelinux/docker-alpine@9b6b5dd".to_string()),
+            Some("-".to_string()),
+            None,
+        ]
+    );
+    assert!(sources[4].as_ref().unwrap().is_empty());
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].to_string().contains("missing"), "{warnings:?}");
+
+    // configs are fetched once, and not at all when annotations tell all:
+    // neither for `annotated` nor for the index's entries
+    let blobs = |config| registry.requests_to(&format!("/v2/org/app/blobs/{}", digest(config)));
+    assert_eq!(blobs(CONFIG).len(), 1);
+    assert_eq!(blobs(BARE_CONFIG).len(), 1);
+    assert!(
+        registry
+            .requests_to(&format!("/v2/org/app/manifests/{}", digest(&labelled())))
+            .is_empty()
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_show_source_flag() {
+    let registry = MockRegistry::start_tls(respond);
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/org/app", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .args(["--show-source", "-f", "^(annotated|bare|labelled)$"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        docker_tags(&[]),
+        "annotated\tgithub.com/org/app@4f2b1c9\n\
+         bare\t-\n\
+         labelled\tgithub.com/prometheus/prometheus@4f2b1c9\n"
+    );
+
+    let listing: serde_json::Value = serde_json::from_str(&docker_tags(&["-o", "json"])).unwrap();
+    let tags = listing["tags"].as_array().unwrap();
+    assert_eq!(tags[0]["source"], "github.com/org/app@4f2b1c9");
+    assert_eq!(tags[1]["source"], serde_json::Value::Null);
+    assert_eq!(tags[2]["name"], "labelled");
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Response};
+
+fn registry() -> MockRegistry {
+    MockRegistry::start_tls(|req| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(
+            200,
+            r#"{"tags":["1.0.0","1.1.0","2.0.0","2.0.0-alpine","latest"]}"#,
+        ),
+        _ => Response::json(404, "{}"),
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/app", registry.host()))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    output
+}
+
+#[test]
+fn test_summary_counts() {
+    let registry = registry();
+    for (args, counts) in [
+        (&[][..], (5, 5, 5)),
+        (&["-f", r"^\d+\.\d+\.\d+$"][..], (5, 3, 3)),
+        (&["-f", r"^\d", "-n", "2"][..], (5, 4, 2)),
+        (&["--latest-per", "major"][..], (5, 5, 2)),
+        // the early stop still lists the whole first page
+        (&["--no-sort", "-n", "1"][..], (5, 1, 1)),
+    ] {
+        let listing: serde_json::Value = serde_json::from_slice(
+            &docker_tags(&registry, &[args, &["-o", "json"]].concat()).stdout,
+        )
+        .unwrap();
+        assert_eq!(
+            (&listing["total"], &listing["matched"], &listing["shown"]),
+            (&counts.0.into(), &counts.1.into(), &counts.2.into()),
+            "{args:?}"
+        );
+    }
+
+    let yaml = docker_tags(&registry, &["-f", r"^2", "-o", "yaml"]).stdout;
+    let yaml = String::from_utf8_lossy(&yaml);
+    assert!(yaml.contains("\ntotal: 5\nmatched: 2\nshown: 2"), "{yaml}");
+}
+
+#[test]
+fn test_no_summary_in_pipes() {
+    let registry = registry();
+    let output = docker_tags(&registry, &["-n", "2"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2.0.0\n2.0.0-alpine\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, ClientBuilder, Image, Tag};
+use sha2::{Digest, Sha256};
+
+const CONFIG: &str = r#"{"architecture":"amd64","os":"linux","created":"2024-10-01T00:00:00Z"}"#;
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// When requests arrived and the most handled at once
+#[derive(Default)]
+struct Log {
+    started: Mutex<Vec<Instant>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+/// Registry behind a token challenge serving a manifest for any tag, each
+/// response taking 20ms
+fn registry(log: Arc<Log>) -> MockRegistry {
+    MockRegistry::start_with_base(move |base, req| {
+        log.started.lock().unwrap().push(Instant::now());
+        let in_flight = log.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        log.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        log.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let path = req.url.path();
+        if path == "/token" {
+            return Response::json(200, r#"{"token":"mock-token"}"#);
+        }
+        if req.header("Authorization") != Some("Bearer mock-token") {
+            let challenge = format!(
+                r#"Bearer realm="{base}/token",service="mock",scope="repository:app:pull""#
+            );
+            return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#)
+                .header("WWW-Authenticate", challenge);
+        }
+        if path.starts_with("/v2/app/manifests/") {
+            let manifest = serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": {
+                    "mediaType": "application/vnd.oci.image.config.v1+json",
+                    "digest": digest(CONFIG),
+                    "size": CONFIG.len(),
+                },
+                "layers": [],
+            })
+            .to_string();
+            return Response::json(200, manifest.clone())
+                .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+                .header("Docker-Content-Digest", digest(&manifest));
+        }
+        if path == format!("/v2/app/blobs/{}", digest(CONFIG)) {
+            return Response::json(200, CONFIG);
+        }
+        Response::json(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#)
+    })
+}
+
+/// Fetch the created dates of 12 tags, which fetches their manifests and
+/// configs concurrently
+async fn enrich(configure: impl FnOnce(ClientBuilder, &str) -> ClientBuilder) -> Arc<Log> {
+    let log = Arc::new(Log::default());
+    let registry = registry(log.clone());
+    let builder = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous);
+    let client = configure(builder, &registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    let tags: Vec<_> = (0..12).map(|i| Tag::new(format!("1.{i}"))).collect();
+    let fetch = client.fetch_created(&image, &tags);
+    let (created, warnings) = tokio::time::timeout(Duration::from_secs(30), fetch)
+        .await
+        .expect("throttled requests never finished");
+    assert_eq!((created.len(), warnings.len()), (12, 0));
+    log
+}
+
+#[tokio::test]
+async fn test_max_concurrent_requests() {
+    let log = enrich(|builder, _| builder).await;
+    assert!(log.max_in_flight.load(Ordering::SeqCst) > 1);
+
+    // the first request is challenged and retried with a token under the same
+    // limit without deadlocking
+    let log = enrich(|builder, host| builder.max_concurrent_requests(host, 1)).await;
+    assert_eq!(log.max_in_flight.load(Ordering::SeqCst), 1);
+
+    let log = enrich(|builder, host| builder.max_concurrent_requests(host, 3)).await;
+    assert!(log.max_in_flight.load(Ordering::SeqCst) <= 3);
+}
+
+#[tokio::test]
+async fn test_min_request_interval() {
+    let interval = Duration::from_millis(50);
+    let log = enrich(|builder, host| builder.min_request_interval(host, interval)).await;
+
+    let mut started = log.started.lock().unwrap().clone();
+    started.sort();
+    // the challenge, the token, the retry and 12 manifests and configs; the
+    // server sees them a little earlier or later than they were sent
+    assert!(started.len() >= 15, "{}", started.len());
+    for pair in started.windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(gap >= interval - Duration::from_millis(15), "{gap:?}");
+    }
+    // requests to other registries aren't delayed
+    let log =
+        enrich(|builder, _| builder.min_request_interval("registry.example.com", interval)).await;
+    assert!(log.max_in_flight.load(Ordering::SeqCst) > 1);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_registry_profile_throttles() {
+    let log = Arc::new(Log::default());
+    let handler_log = log.clone();
+    let registry = MockRegistry::start_tls(move |req| {
+        handler_log.started.lock().unwrap().push(Instant::now());
+        let in_flight = handler_log.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        handler_log
+            .max_in_flight
+            .fetch_max(in_flight, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(10));
+        handler_log.in_flight.fetch_sub(1, Ordering::SeqCst);
+        match req.url.path() {
+            "/v2/app/tags/list" => Response::json(200, r#"{"tags":["1.0","1.1","1.2","1.3"]}"#),
+            _ => Response::json(200, "{}").header("Docker-Content-Digest", digest(CONFIG)),
+        }
+    });
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[registries.\"{}\"]\nmax_concurrent_requests = 1\nmin_request_interval = 50\n",
+            registry.host()
+        ),
+    )
+    .unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .arg(format!("{}/app", registry.host()))
+        .arg("--digests")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg("--config")
+        .arg(&config)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    // the listing, then the digests of its 4 tags, one at a time
+    let started = log.started.lock().unwrap();
+    assert_eq!(started.len(), 5);
+    assert_eq!(log.max_in_flight.load(Ordering::SeqCst), 1);
+    for pair in started.windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(gap >= Duration::from_millis(35), "{gap:?}");
+    }
+}
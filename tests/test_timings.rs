@@ -0,0 +1,132 @@
+mod common;
+
+#[cfg(feature = "cli")]
+use std::process::Command;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, Image};
+use sha2::{Digest, Sha256};
+
+const CONFIG: &str = r#"{"architecture":"amd64","os":"linux","created":"2024-01-01T00:00:00Z"}"#;
+
+#[cfg(feature = "cli")]
+const TAGS: &str = r#"{"tags":["1.0.0","2.0.0"]}"#;
+
+fn digest(body: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn manifest() -> String {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(CONFIG),
+            "size": CONFIG.len(),
+        },
+        "layers": [],
+    })
+    .to_string()
+}
+
+fn client(registry: &MockRegistry) -> Client {
+    Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build()
+}
+
+#[tokio::test]
+async fn test_timings_count_served_requests() {
+    let tags: Vec<_> = (0..250).map(|i| format!("1.0.{i}")).collect();
+    let tags: Vec<_> = tags.iter().map(String::as_str).collect();
+    let registry = MockRegistry::with_tags("app", &tags, true);
+    let client = client(&registry);
+
+    let image = Image::new(registry.host(), "app");
+    assert_eq!(client.fetch_tags(&image).await.unwrap().len(), 250);
+    let timings = client.timings();
+    // challenged once, then three pages with the token
+    assert_eq!(
+        timings.tags_requests,
+        registry.requests_to("/v2/app/tags/list").len()
+    );
+    assert_eq!(timings.tags_requests, 4);
+    assert_eq!(timings.token_requests, registry.requests_to("/token").len());
+    assert_eq!(timings.token_requests, 1);
+    assert_eq!(timings.retries, 1);
+    assert_eq!(timings.requests(), registry.requests().len());
+    assert!(timings.bytes_received > 0);
+    assert!(!timings.pagination.is_zero());
+}
+
+#[tokio::test]
+async fn test_timings_of_enrichment() {
+    let registry = MockRegistry::start(|req| match req.url.path() {
+        "/v2/app/manifests/1.0" => Response::json(200, manifest())
+            .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+            .header("Docker-Content-Digest", digest(&manifest())),
+        path if path == format!("/v2/app/blobs/{}", digest(CONFIG)) => Response::json(200, CONFIG),
+        _ => Response::json(404, "{}"),
+    });
+    let client = client(&registry);
+    let image = Image::new(registry.host(), "app");
+
+    client.fetch_config(&image, "1.0").await.unwrap();
+    // clones share the counters
+    client.clone().fetch_manifest(&image, "1.0").await.unwrap();
+    let timings = client.timings();
+    assert_eq!(timings.manifest_requests, 2);
+    assert_eq!(timings.blob_requests, 1);
+    assert_eq!(timings.requests(), registry.requests().len());
+    assert_eq!(
+        timings.bytes_received,
+        (2 * manifest().len() + CONFIG.len()) as u64
+    );
+    assert_eq!(timings.retries, 0);
+    assert!(timings.auth.is_zero());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_timings_flag() {
+    let registry = MockRegistry::start_tls(|req| match req.url.path() {
+        "/v2/app/tags/list" => Response::json(200, TAGS),
+        _ => Response::json(404, "{}"),
+    });
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .arg(format!("{}/app", registry.host()))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        output
+    };
+
+    let output = docker_tags(&["--timings"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2.0.0\n1.0.0\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Timings: 1 requests (token 0, tags 1, manifest 0, blob 0, other 0)"),
+        "{stderr}"
+    );
+
+    let listing: serde_json::Value =
+        serde_json::from_slice(&docker_tags(&["--timings", "-o", "json"]).stdout).unwrap();
+    let timings = &listing["timings"];
+    assert_eq!(timings["requests"]["tags"], 1);
+    assert_eq!(timings["bytes_received"], TAGS.len());
+    assert!(timings["sorting_ms"].as_f64().unwrap() >= 0.0);
+
+    let listing: serde_json::Value =
+        serde_json::from_slice(&docker_tags(&["-o", "json"]).stdout).unwrap();
+    assert_eq!(listing.get("timings"), None);
+}
@@ -0,0 +1,399 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use common::{MockRegistry, Response, TEST_CA};
+use docker_tags::{Anonymous, Client, ClientBuilder, Error, Image, TlsVersion};
+
+/// Token service on a port of its own, as with `auth.corp.example.com`
+fn auth_service() -> MockRegistry {
+    MockRegistry::start_tls(|req| match req.url.path() {
+        "/token" => Response::json(200, r#"{"token":"mock-token"}"#),
+        _ => Response::new(404),
+    })
+}
+
+/// Registry sending clients to `auth` for a token
+fn registry(auth: &MockRegistry, tls: bool) -> MockRegistry {
+    let realm = format!("{}/token", auth.url());
+    let handler = move |req: &common::Request| {
+        if req.header("Authorization") != Some("Bearer mock-token") {
+            return Response::json(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#).header(
+                "WWW-Authenticate",
+                format!(
+                    r#"Bearer realm="{realm}",service="mock",scope="repository:team/app:pull""#
+                ),
+            );
+        }
+        Response::json(200, r#"{"name":"team/app","tags":["1.0.0","1.1.0"]}"#)
+    };
+    match tls {
+        true => MockRegistry::start_tls(handler),
+        false => MockRegistry::start(handler),
+    }
+}
+
+async fn fetch(builder: ClientBuilder, registry: &MockRegistry) -> anyhow::Result<Vec<String>> {
+    let image = Image::try_from(format!("{}/team/app", registry.host()).as_str()).unwrap();
+    let tags = builder
+        .credential_provider(Anonymous)
+        .build()
+        .fetch_tags(&image)
+        .await?;
+    Ok(tags.iter().map(|t| t.name().to_string()).collect())
+}
+
+#[tokio::test]
+async fn test_token_host_with_its_own_ca() {
+    let auth = auth_service();
+    let registry = registry(&auth, false);
+    let builder = || Client::builder().insecure_registry(registry.host());
+
+    assert!(fetch(builder(), &registry).await.is_err());
+    assert!(auth.requests_to("/token").is_empty());
+
+    let builder = builder().root_certificate(auth.host(), TEST_CA).unwrap();
+    assert_eq!(fetch(builder, &registry).await.unwrap(), ["1.0.0", "1.1.0"]);
+    assert_eq!(auth.requests_to("/token").len(), 1);
+}
+
+#[tokio::test]
+async fn test_token_host_inherits_registry_ca() {
+    let auth = auth_service();
+    let registry = registry(&auth, true);
+
+    assert!(fetch(Client::builder(), &registry).await.is_err());
+    assert!(registry.requests().is_empty());
+
+    let builder = Client::builder()
+        .root_certificate(registry.host(), TEST_CA)
+        .unwrap();
+    assert_eq!(fetch(builder, &registry).await.unwrap(), ["1.0.0", "1.1.0"]);
+    assert_eq!(registry.requests_to("/v2/team/app/tags/list").len(), 2);
+    assert_eq!(auth.requests_to("/token").len(), 1);
+}
+
+#[test]
+fn test_root_certificate_rejects_garbage() {
+    assert!(
+        Client::builder()
+            .root_certificate("registry.corp", b"not a certificate")
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_tls_server_name_for_ip_registry() {
+    // the certificate is valid for `localhost` and 127.0.0.1, the registry's address
+    let auth = auth_service();
+    let registry = registry(&auth, true);
+    let builder = |server_name| {
+        Client::builder()
+            .root_certificate(registry.host(), TEST_CA)
+            .unwrap()
+            .root_certificate(auth.host(), TEST_CA)
+            .unwrap()
+            .tls_server_name(registry.host(), server_name)
+            .unwrap()
+    };
+
+    assert_eq!(
+        fetch(builder("localhost"), &registry).await.unwrap(),
+        ["1.0.0", "1.1.0"]
+    );
+    let port = registry.host().rsplit_once(':').unwrap().1.to_string();
+    assert!(
+        registry
+            .requests()
+            .iter()
+            .all(|r| r.header("Host") == Some(format!("localhost:{port}").as_str()))
+    );
+
+    let served = registry.requests().len();
+    // connected to the registry's address, which isn't `registry.corp`
+    let err = fetch(builder("registry.corp"), &registry)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:?}").contains("not valid for name \"registry.corp\""),
+        "{err:?}"
+    );
+    assert_eq!(registry.requests().len(), served);
+}
+
+#[test]
+fn test_tls_server_name_needs_ip_registry() {
+    assert!(
+        Client::builder()
+            .tls_server_name("registry.corp:5000", "registry.corp")
+            .is_err()
+    );
+    for server_name in ["", "registry.corp:5000", "10.0.3.8", "bad/name"] {
+        assert!(
+            Client::builder()
+                .tls_server_name("10.0.3.7:5000", server_name)
+                .is_err(),
+            "{server_name}"
+        );
+    }
+    assert!(
+        Client::builder()
+            .tls_server_name("[2001:db8::1]:5000", "registry.corp")
+            .is_ok()
+    );
+}
+
+/// Server answering every `ClientHello` with a `ServerHello` for TLS 1.1,
+/// as one limited to TLS 1.0 and 1.1 does, returning its address
+fn tls11_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut header = [0; 5];
+            if stream.read_exact(&mut header).is_err() {
+                continue;
+            }
+            let mut hello = vec![0; u16::from_be_bytes([header[3], header[4]]).into()];
+            let _ = stream.read_exact(&mut hello);
+            // version, random, no session id, TLS_RSA_WITH_AES_128_CBC_SHA, no compression
+            let mut body = vec![0x03, 0x02];
+            body.extend([7; 32]);
+            body.extend([0, 0x00, 0x2f, 0]);
+            let mut record = vec![0x16, 0x03, 0x02, 0, body.len() as u8 + 4, 0x02, 0, 0];
+            record.push(body.len() as u8);
+            record.extend(body);
+            let _ = stream.write_all(&record);
+            let _ = stream.read(&mut [0; 64]);
+        }
+    });
+    host
+}
+
+fn tls_error(err: &anyhow::Error) -> Option<&Error> {
+    err.downcast_ref::<Error>()
+        .or_else(|| err.chain().find_map(|cause| cause.downcast_ref()))
+}
+
+#[tokio::test]
+async fn test_refuses_tls_below_1_2() {
+    let host = tls11_server();
+    let image = Image::try_from(format!("{host}/team/app").as_str()).unwrap();
+    let client = Client::builder().credential_provider(Anonymous).build();
+    let err = client.fetch_tags(&image).await.unwrap_err();
+    assert_eq!(
+        tls_error(&err),
+        Some(&Error::TlsVersion {
+            host: host.clone(),
+            min: TlsVersion::Tls12
+        }),
+        "{err:?}"
+    );
+    assert!(
+        format!("{err:#}").contains(&format!(
+            "TLS handshake with {host} failed: it doesn't offer TLS 1.2 or later, the minimum allowed"
+        )),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn test_min_tls_version_for_registry() {
+    let registry = MockRegistry::start_tls_versions(&[&rustls::version::TLS12], |_| {
+        Response::json(200, r#"{"name":"team/app","tags":["1.0.0"]}"#)
+    });
+    let builder = || {
+        Client::builder()
+            .root_certificate(registry.host(), TEST_CA)
+            .unwrap()
+    };
+    assert_eq!(fetch(builder(), &registry).await.unwrap(), ["1.0.0"]);
+
+    let strict = builder()
+        .min_tls_version_for(registry.host(), TlsVersion::Tls13)
+        .unwrap();
+    let err = fetch(strict, &registry).await.unwrap_err();
+    assert_eq!(
+        tls_error(&err),
+        Some(&Error::TlsVersion {
+            host: registry.host(),
+            min: TlsVersion::Tls13
+        }),
+        "{err:?}"
+    );
+    assert_eq!(registry.requests().len(), 1);
+
+    // set after the certificates, it still applies to their client
+    let strict = builder().min_tls_version(TlsVersion::Tls13).unwrap();
+    assert!(fetch(strict, &registry).await.is_err());
+    let other = builder()
+        .min_tls_version_for("registry.corp", TlsVersion::Tls13)
+        .unwrap();
+    assert_eq!(fetch(other, &registry).await.unwrap(), ["1.0.0"]);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_tls_min_flag() {
+    let registry = MockRegistry::start_tls_versions(&[&rustls::version::TLS12], |_| {
+        Response::json(200, r#"{"name":"team/app","tags":["1.0.0"]}"#)
+    });
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |tls_min: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/team/app", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .args(["--tls-min", tls_min])
+            .output()
+            .unwrap()
+    };
+
+    let output = docker_tags("1.2");
+    assert!(output.status.success(), "{output:?}");
+    let output = docker_tags(&format!("{}=1.3", registry.host()));
+    assert_eq!(output.status.code(), Some(1));
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        message.contains("doesn't offer TLS 1.3 or later, the minimum allowed"),
+        "{message}"
+    );
+
+    assert_eq!(docker_tags("1.1").status.code(), Some(2));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_registry_profile_ca_certs() {
+    let auth = auth_service();
+    let registry = registry(&auth, true);
+    let home = tempfile::tempdir().unwrap();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    std::fs::copy(format!("{fixtures}/ca.pem"), home.path().join("ca.pem")).unwrap();
+    let docker_tags = |auth_ca: Option<&str>| {
+        // relative to the config file
+        let mut config = format!(
+            "[registries.\"{}\"]\nca_cert = \"ca.pem\"\n",
+            registry.host()
+        );
+        if let Some(path) = auth_ca {
+            config.push_str(&format!(
+                "auth_hosts = {{ \"{}\" = \"{path}\" }}\n",
+                auth.host()
+            ));
+        }
+        std::fs::write(home.path().join("config.toml"), config).unwrap();
+        std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", home.path())
+            .env("XDG_STATE_HOME", home.path())
+            .arg(format!("{}/team/app", registry.host()))
+            .arg("--config")
+            .arg(home.path().join("config.toml"))
+            .output()
+            .unwrap()
+    };
+
+    // the token service inherits the registry's CA
+    let output = docker_tags(None);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.1.0\n1.0.0\n");
+    assert_eq!(auth.requests_to("/token").len(), 1);
+
+    // unless it has certificates of its own, here not those of its CA
+    let output = docker_tags(Some(&format!("{fixtures}/server.pem")));
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(message.contains("certificate"), "{message}");
+    assert_eq!(auth.requests_to("/token").len(), 1);
+
+    let output = docker_tags(Some("ca.pem"));
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(auth.requests_to("/token").len(), 2);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_registry_profile_tls_server_name() {
+    let auth = auth_service();
+    let registry = registry(&auth, true);
+    let home = tempfile::tempdir().unwrap();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let docker_tags = |server_name: &str, args: &[String]| {
+        let config = format!(
+            "[registries.\"{}\"]\nca_cert = \"{fixtures}/ca.pem\"\ntls_server_name = \"{server_name}\"\n",
+            registry.host()
+        );
+        std::fs::write(home.path().join("config.toml"), config).unwrap();
+        std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", home.path())
+            .env("XDG_STATE_HOME", home.path())
+            .arg(format!("{}/team/app", registry.host()))
+            .arg("--config")
+            .arg(home.path().join("config.toml"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = docker_tags("localhost", &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.1.0\n1.0.0\n");
+
+    let output = docker_tags("registry.corp", &[]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(message.contains("registry.corp"), "{message}");
+
+    // the command line's name comes first
+    let flag = format!("{}=localhost", registry.host());
+    let output = docker_tags("registry.corp", &["--tls-server-name".into(), flag]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_registry_profile_tls_min() {
+    let registry = MockRegistry::start_tls_versions(&[&rustls::version::TLS12], |_| {
+        Response::json(200, r#"{"name":"team/app","tags":["1.0.0"]}"#)
+    });
+    let home = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |tls_min: &str, args: &[String]| {
+        let config = format!(
+            "[registries.\"{}\"]\nca_cert = \"{ca}\"\ntls_min = \"{tls_min}\"\n",
+            registry.host()
+        );
+        std::fs::write(home.path().join("config.toml"), config).unwrap();
+        std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", home.path())
+            .env("XDG_STATE_HOME", home.path())
+            .arg(format!("{}/team/app", registry.host()))
+            .arg("--config")
+            .arg(home.path().join("config.toml"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = docker_tags("1.2", &[]);
+    assert!(output.status.success(), "{output:?}");
+    let output = docker_tags("1.3", &[]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        message.contains("doesn't offer TLS 1.3 or later, the minimum allowed"),
+        "{message}"
+    );
+
+    // the command line's minimum for the registry comes first
+    let flag = format!("{}=1.2", registry.host());
+    let output = docker_tags("1.3", &["--tls-min".into(), flag]);
+    assert!(output.status.success(), "{output:?}");
+}
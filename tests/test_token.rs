@@ -0,0 +1,180 @@
+#![cfg(feature = "unstable")]
+
+mod common;
+
+#[cfg(feature = "cli")]
+use std::process::Command;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use common::{MockRegistry, Response};
+use docker_tags::{Anonymous, Client, CredentialProvider, Credentials, Image};
+
+struct StoredLogin;
+
+#[async_trait]
+impl CredentialProvider for StoredLogin {
+    async fn credentials(&self, _registry: &str) -> Result<Option<Credentials>> {
+        Ok(Some(Credentials::basic("user", "secret")))
+    }
+}
+
+/// A JWT like Docker Hub's, with a made-up signature
+fn jwt() -> String {
+    let claims = serde_json::json!({
+        "access": [{ "type": "repository", "name": "org/app", "actions": ["pull"] }],
+        "exp": 1760400300,
+    });
+    format!(
+        "{}.{}.c2lnbmF0dXJl",
+        URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    )
+}
+
+/// Token service issuing [`jwt`]
+fn token_service() -> MockRegistry {
+    MockRegistry::start(|req| match req.url.path() {
+        "/token" => Response::json(200, serde_json::json!({ "token": jwt() }).to_string()),
+        _ => Response::json(404, "{}"),
+    })
+}
+
+/// Registry sending bearer challenges for `realm`, with rate limits on the
+/// manifests of `org/app`
+fn registry(realm: String, tls: bool) -> MockRegistry {
+    let handler = move |req: &common::Request| {
+        if req.header("Authorization") != Some(format!("Bearer {}", jwt()).as_str()) {
+            return Response::new(401).header(
+                "WWW-Authenticate",
+                format!(r#"Bearer realm="{realm}",service="mock",scope="repository:org/app:pull""#),
+            );
+        }
+        match (req.method.as_str(), req.url.path()) {
+            ("HEAD", "/v2/org/app/manifests/latest") => Response::new(200)
+                .header("ratelimit-limit", "100;w=21600")
+                .header("ratelimit-remaining", "76;w=21600")
+                .header("docker-ratelimit-source", "203.0.113.7"),
+            _ => Response::new(404),
+        }
+    };
+    match tls {
+        true => MockRegistry::start_tls(handler),
+        false => MockRegistry::start(handler),
+    }
+}
+
+#[tokio::test]
+async fn test_exchange_token() {
+    let tokens = token_service();
+    let mock = registry(format!("{}/token", tokens.url()), false);
+    let image = Image::try_from(format!("{}/org/app", mock.host()).as_str()).unwrap();
+
+    for authenticated in [false, true] {
+        let builder = Client::builder().insecure_registry(mock.host());
+        let client = match authenticated {
+            true => builder.credential_provider(StoredLogin).build(),
+            false => builder.credential_provider(Anonymous).build(),
+        };
+        let exchange = client.exchange_token(&image).await.unwrap();
+        let grant = exchange.grant.unwrap();
+        assert_eq!(grant.scheme, "Bearer");
+        assert_eq!(grant.realm, format!("{}/token", tokens.url()));
+        assert_eq!(grant.service.as_deref(), Some("mock"));
+        assert_eq!(grant.scopes, ["repository:org/app:pull"]);
+        assert_eq!(grant.authenticated, authenticated);
+        assert_eq!(grant.token.expose(), jwt());
+        assert_eq!(exchange.status, 200);
+        assert_eq!(
+            exchange.rate_limits,
+            [
+                ("ratelimit-limit".to_string(), "100;w=21600".to_string()),
+                ("ratelimit-remaining".to_string(), "76;w=21600".to_string()),
+                (
+                    "docker-ratelimit-source".to_string(),
+                    "203.0.113.7".to_string()
+                ),
+            ]
+        );
+    }
+    // the scope of the challenge is asked for once
+    let token = &tokens.requests_to("/token")[0];
+    let mut query: Vec<_> = token.url.query_pairs().collect();
+    query.sort();
+    assert_eq!(
+        query,
+        [
+            ("scope".into(), "repository:org/app:pull".into()),
+            ("service".into(), "mock".into())
+        ]
+    );
+    assert!(mock.requests().iter().all(|r| r.method == "HEAD"));
+}
+
+#[tokio::test]
+async fn test_exchange_without_challenge() {
+    let mock = MockRegistry::start(|_| Response::new(200));
+    let client = Client::builder().insecure_registry(mock.host()).build();
+    let image = Image::try_from(format!("{}/org/app", mock.host()).as_str()).unwrap();
+
+    let exchange = client.exchange_token(&image).await.unwrap();
+    assert!(exchange.grant.is_none());
+    assert!(exchange.rate_limits.is_empty());
+}
+
+#[cfg(feature = "cli")]
+fn docker_tags(mock: &MockRegistry, args: &[&str]) -> String {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let output = Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .arg("token")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", mock.host()))
+        .arg(format!("{}/org/app", mock.host()))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_token_command() {
+    let tokens = token_service();
+    let mock = registry(format!("{}/token", tokens.url()), true);
+
+    let text = docker_tags(&mock, &[]);
+    assert_eq!(
+        text,
+        format!(
+            "realm: {}/token\n\
+             service: mock\n\
+             scope: repository:org/app:pull\n\
+             credentials: anonymous\n\
+             status: 200\n\
+             claims:\n  \
+             access: [{{\"actions\":[\"pull\"],\"name\":\"org/app\",\"type\":\"repository\"}}]\n  \
+             exp: 1760400300\n\
+             docker-ratelimit-source: 203.0.113.7\n\
+             ratelimit-limit: 100;w=21600\n\
+             ratelimit-remaining: 76;w=21600\n",
+            tokens.url()
+        )
+    );
+    // neither the token nor its signature
+    assert!(!text.contains("c2lnbmF0dXJl"));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&docker_tags(&mock, &["-o", "json"])).unwrap();
+    assert_eq!(report["scopes"][0], "repository:org/app:pull");
+    assert_eq!(report["claims"]["exp"], 1760400300);
+    assert_eq!(report["rate_limits"]["ratelimit-remaining"], "76;w=21600");
+    assert_eq!(report.get("token"), None);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&docker_tags(&mock, &["-o", "json", "--show-token"])).unwrap();
+    assert_eq!(report["token"], jwt());
+}
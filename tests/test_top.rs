@@ -0,0 +1,125 @@
+mod common;
+
+use common::MockRegistry;
+use docker_tags::{Anonymous, Client, Image, Tag, sort_tags_desc};
+
+/// 50k names in lexical order, as registries list them: versions with
+/// pre-releases and variants, Windows builds, commit SHAs and others
+fn names() -> Vec<String> {
+    let mut state = 0x2545_f491_u64;
+    let mut next = move |n: u64| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (state >> 33) % n
+    };
+    let mut names: Vec<_> = (0..50_000)
+        .map(|i| match next(7) {
+            0 | 1 => format!("{}.{}.{i}", next(20), next(40)),
+            2 => format!("{}.{}.{i}-rc.{}", next(20), next(40), next(5)),
+            3 => format!("{}.{}.{i}-alpine", next(20), next(40)),
+            4 => format!("10.0.{}.{i}", [17763, 20348, 26100][next(3) as usize]),
+            5 => format!("{:08x}{:08x}", next(1 << 32), i),
+            _ => format!("build-{i}"),
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn names_of(tags: &[Tag]) -> Vec<&str> {
+    tags.iter().map(Tag::name).collect()
+}
+
+#[tokio::test]
+async fn test_top_matches_full_sort() {
+    let names = names();
+    let refs: Vec<_> = names.iter().map(String::as_str).collect();
+    let registry = MockRegistry::with_tags("ci/app", &refs, false);
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::new(registry.host(), "ci/app");
+
+    let mut sorted = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(sorted.len(), names.len());
+    sort_tags_desc(&mut sorted);
+
+    for n in [1, 1000] {
+        let (top, outcome) = client
+            .fetch_tags_top(&image, |_| true, n, false)
+            .await
+            .unwrap();
+        assert_eq!(names_of(&top), names_of(&sorted[..n]), "top {n}");
+        assert_eq!(
+            (outcome.listed, outcome.matched),
+            (names.len(), names.len())
+        );
+
+        let (bottom, _) = client
+            .fetch_tags_top(&image, |_| true, n, true)
+            .await
+            .unwrap();
+        let mut expected = names_of(&sorted[sorted.len() - n..]);
+        expected.reverse();
+        assert_eq!(names_of(&bottom), expected, "bottom {n}");
+    }
+
+    let alpine = |tag: &Tag| tag.name().ends_with("-alpine");
+    let (top, outcome) = client
+        .fetch_tags_top(&image, alpine, 50, false)
+        .await
+        .unwrap();
+    let expected: Vec<_> = sorted.iter().filter(|tag| alpine(tag)).collect();
+    assert_eq!(outcome.matched, expected.len());
+    assert_eq!(
+        names_of(&top),
+        expected[..50]
+            .iter()
+            .map(|tag| tag.name())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_top_flag() {
+    let names = names()[..5000].to_vec();
+    let registry = MockRegistry::start_tls(move |req| match req.url.path() {
+        "/v2/ci/app/tags/list" => common::Response::json(
+            200,
+            serde_json::json!({ "tags": common::paginate(&names, req) }).to_string(),
+        ),
+        _ => common::Response::new(404),
+    });
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    let docker_tags = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+            .env("DOCKER_CONFIG", config.path())
+            .env("XDG_STATE_HOME", config.path())
+            .arg(format!("{}/ci/app", registry.host()))
+            .arg("--ca-cert")
+            .arg(format!("{}={ca}", registry.host()))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    for reverse in [&[][..], &["-r"]] {
+        let top = docker_tags(&[reverse, &["--no-sha", "--top", "20"]].concat());
+        let limited = docker_tags(&[reverse, &["--no-sha", "-n", "20"]].concat());
+        assert_eq!(top.lines().count(), 20);
+        assert_eq!(top, limited);
+    }
+
+    let listing: serde_json::Value =
+        serde_json::from_str(&docker_tags(&["--no-sha", "--top", "3", "-o", "json"])).unwrap();
+    let full: serde_json::Value =
+        serde_json::from_str(&docker_tags(&["--no-sha", "-o", "json"])).unwrap();
+    assert_eq!(listing["tags"].as_array().unwrap().len(), 3);
+    assert_eq!(listing["total"], 5000);
+    assert_eq!(listing["matched"], full["matched"]);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Client, Image};
+
+fn registry() -> MockRegistry {
+    MockRegistry::start(|_| Response::json(200, r#"{"tags":["1.0.0"]}"#))
+}
+
+#[tokio::test]
+async fn test_repository_components_are_encoded() {
+    let registry = registry();
+    let client = Client::builder().insecure_registry(registry.host()).build();
+
+    for (repository, path) in [
+        (
+            "team/..%2F..%2Fother",
+            "/v2/team/..%252F..%252Fother/tags/list",
+        ),
+        ("team/app?n=1#x", "/v2/team/app%3Fn=1%23x/tags/list"),
+        ("team/a\\b", "/v2/team/a%5Cb/tags/list"),
+    ] {
+        let image = Image::new(registry.host(), repository);
+        client.fetch_tags(&image).await.unwrap();
+        let request = registry.requests().pop().unwrap();
+        assert_eq!(request.url.path(), path);
+        assert_eq!(request.query("n").as_deref(), Some("100"));
+    }
+}
+
+#[tokio::test]
+async fn test_dot_segments_are_rejected() {
+    let registry = registry();
+    let client = Client::builder().insecure_registry(registry.host()).build();
+
+    for repository in ["team/../other", "./app", "team//app", ""] {
+        let image = Image::new(registry.host(), repository);
+        let err = client.fetch_tags(&image).await.unwrap_err();
+        assert!(err.to_string().starts_with("Invalid repository name"));
+    }
+    assert!(registry.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_invalid_hosts_are_rejected() {
+    let registry = registry();
+    let client = Client::builder().build();
+
+    for host in [
+        format!("user:password@{}", registry.host()),
+        format!("{}/path", registry.host()),
+        format!("http://{}", registry.host()),
+        format!("{}?x=1", registry.host()),
+    ] {
+        let image = Image::new(host, "app");
+        let err = client.fetch_tags(&image).await.unwrap_err();
+        assert!(err.to_string().starts_with("Invalid registry host"));
+    }
+    assert!(registry.requests().is_empty());
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{MockRegistry, Response};
+
+fn postgres() -> MockRegistry {
+    MockRegistry::start_tls(|req| match req.url.path() {
+        "/v2/library/postgres/tags/list" => {
+            let tags: Vec<_> = include_str!("fixtures/postgres_tags.txt").lines().collect();
+            Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+        }
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+fn docker_tags(registry: &MockRegistry, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+    Command::new(env!("CARGO_BIN_EXE_docker-tags"))
+        .env("DOCKER_CONFIG", config.path())
+        .env("XDG_STATE_HOME", config.path())
+        .arg("verify-order")
+        .arg("--ca-cert")
+        .arg(format!("{}={ca}", registry.host()))
+        .arg(format!("{}/library/postgres", registry.host()))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_verify_order_snapshot() {
+    let registry = postgres();
+    let output = docker_tags(&registry, &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        include_str!("fixtures/verify_order/postgres.txt")
+    );
+}
+
+#[test]
+fn test_verify_order_limit_and_filter() {
+    let registry = postgres();
+    let output = docker_tags(&registry, &["-f", "^16", "-n", "2", "-o", "json"]);
+    assert!(output.status.success(), "{output:?}");
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let tags: Vec<_> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["tag"].as_str().unwrap(),
+                entry["bucket"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(tags.len(), 2);
+    assert!(
+        tags.iter().all(|(tag, _)| tag.starts_with("16")),
+        "{tags:?}"
+    );
+}
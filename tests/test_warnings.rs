@@ -0,0 +1,65 @@
+mod common;
+
+use common::{MockRegistry, Response};
+use docker_tags::{Client, Image, Warning};
+
+#[tokio::test]
+async fn test_invalid_and_duplicate_tags() {
+    let registry = MockRegistry::start(|_| {
+        Response::json(
+            200,
+            r#"{"tags":["1.0.0","-bad","1.0.0","../etc","latest",".hidden"]}"#,
+        )
+    });
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    let (tags, outcome) = client.fetch_tags_partial(&image).await.unwrap();
+    let names: Vec<_> = tags.iter().map(|t| t.name()).collect();
+    assert_eq!(names, ["1.0.0", "latest"]);
+    assert_eq!(
+        outcome.warnings,
+        [
+            Warning::InvalidTagName {
+                raw: "-bad".to_string()
+            },
+            Warning::DuplicateTag {
+                name: "1.0.0".to_string()
+            },
+            Warning::InvalidTagName {
+                raw: "../etc".to_string()
+            },
+            Warning::InvalidTagName {
+                raw: ".hidden".to_string()
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_duplicates_across_pages() {
+    let registry = MockRegistry::start(|req| match req.query("last") {
+        None => {
+            let tags: Vec<_> = (0..100).map(|i| format!("1.0.{i}")).collect();
+            Response::json(200, serde_json::json!({ "tags": tags }).to_string())
+        }
+        Some(_) => Response::json(200, r#"{"tags":["1.0.99","1.1.0"]}"#),
+    });
+    let client = Client::builder().insecure_registry(registry.host()).build();
+    let image = Image::try_from(format!("{}/app", registry.host()).as_str()).unwrap();
+
+    let (tags, outcome) = client.fetch_tags_partial(&image).await.unwrap();
+    assert_eq!(tags.len(), 101);
+    assert_eq!(
+        outcome.warnings,
+        [
+            Warning::InconsistentPage {
+                last: "1.0.99".to_string(),
+                first: "1.0.99".to_string()
+            },
+            Warning::DuplicateTag {
+                name: "1.0.99".to_string()
+            }
+        ]
+    );
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{MockRegistry, Request, Response};
+use docker_tags::{
+    Anonymous, Client, Image, SortKey, Tag, WindowsRelease, is_windows_listing, sort_tags_desc,
+};
+
+fn servercore() -> Vec<Tag> {
+    include_str!("fixtures/servercore_tags.txt")
+        .lines()
+        .map(Tag::new)
+        .collect()
+}
+
+#[test]
+fn test_servercore_order() {
+    let mut tags = servercore();
+    tags.reverse();
+    sort_tags_desc(&mut tags);
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    assert_eq!(
+        names,
+        [
+            // Windows Server 2025, its name standing for its newest revision
+            "ltsc2025",
+            "ltsc2025-amd64",
+            "10.0.26100.1742",
+            "10.0.26100.1742-amd64",
+            "23H2",
+            "10.0.25398.1085",
+            "ltsc2022",
+            "ltsc2022-amd64",
+            "ltsc2022-KB5041160",
+            "10.0.20348.2655",
+            "10.0.20348.2655-amd64",
+            "10.0.20348.2582",
+            "10.0.20348.2582-amd64",
+            "20H2",
+            "20H2-KB5005565",
+            "10.0.19042.1889",
+            "2004",
+            "10.0.19041.1415",
+            "1909",
+            "10.0.18363.1556",
+            "1903",
+            "10.0.18362.1256",
+            "1809",
+            "1809-amd64",
+            "1809-KB5041578",
+            "ltsc2019",
+            "ltsc2019-amd64",
+            "ltsc2019-KB5041578",
+            "10.0.17763.6189",
+            "10.0.17763.6189-amd64",
+            "10.0.17763.6054",
+            "10.0.17763.6054-amd64",
+            "1803",
+            "1709",
+            "ltsc2016",
+            "ltsc2016-amd64",
+            "10.0.14393.7259",
+            "10.0.14393.7259-amd64",
+            "insider",
+        ]
+    );
+}
+
+#[test]
+fn test_windows_sort_key() {
+    let key = Tag::new("ltsc2022-amd64").sort_key();
+    assert_eq!(
+        key,
+        SortKey::Windows(WindowsRelease {
+            build: 20348,
+            revision: None
+        })
+    );
+    assert_eq!(Tag::new("ltsc2022").opaque_reason(), None);
+    assert!(SortKey::Opaque < key);
+    assert!(key < Tag::new("1.0.0").sort_key());
+}
+
+#[test]
+fn test_bare_numbers_outside_windows() {
+    // `1809` is a Windows release only among a Windows image's tags
+    let mut tags: Vec<_> = ["1810", "latest", "1809", "1.0.0"].map(Tag::new).into();
+    sort_tags_desc(&mut tags);
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    assert_eq!(names, ["1.0.0", "1809", "1810", "latest"]);
+    for name in ["1809", "1810", "10.0.20348.2582"] {
+        assert_eq!(Tag::new(name).sort_key(), SortKey::Opaque, "{name}");
+        assert!(Tag::new(name).opaque_reason().is_some(), "{name}");
+    }
+    assert!(!is_windows_listing(&tags));
+
+    tags.push(Tag::new("ltsc2019"));
+    assert!(is_windows_listing(&tags));
+    sort_tags_desc(&mut tags);
+    let names: Vec<_> = tags.iter().map(Tag::name).collect();
+    assert_eq!(names, ["1.0.0", "1809", "ltsc2019", "1810", "latest"]);
+    assert!(matches!(
+        Tag::new("1809").sort_key_in(true),
+        SortKey::Windows(_)
+    ));
+}
+
+/// Registry answering like `mcr.microsoft.com`: anonymously, and with every
+/// tag in one page whatever the `n` asked for
+fn mcr(tags: Vec<String>) -> MockRegistry {
+    MockRegistry::start(move |req: &Request| match req.url.path() {
+        "/v2/windows/servercore/tags/list" => Response::json(
+            200,
+            serde_json::json!({ "name": "windows/servercore", "tags": tags }).to_string(),
+        ),
+        _ => Response::json(404, r#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#),
+    })
+}
+
+#[tokio::test]
+async fn test_single_large_page() {
+    let tags: Vec<_> = (0..5000)
+        .map(|i| format!("10.0.20348.{}", 10_000 + i))
+        .chain(servercore().into_iter().map(|tag| tag.name().to_string()))
+        .collect();
+    let registry = mcr(tags.clone());
+    let client = Client::builder()
+        .insecure_registry(registry.host())
+        .credential_provider(Anonymous)
+        .build();
+    let image = Image::new(registry.host(), "windows/servercore");
+
+    let fetched = client.fetch_tags(&image).await.unwrap();
+    assert_eq!(fetched.len(), tags.len());
+    let pages = registry.requests_to("/v2/windows/servercore/tags/list");
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].query("n").as_deref(), Some("100"));
+    assert!(pages[0].header("Authorization").is_none());
+}